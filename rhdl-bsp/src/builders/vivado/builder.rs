@@ -1,11 +1,18 @@
 use rhdl::{
     core::RHDLError,
-    prelude::{Circuit, Fixture},
+    prelude::{BoardTop, Circuit, ExportError, Fixture, Module},
 };
 
 use super::tcl::{self, AddFiles, FileType};
 use std::io::Write;
 
+fn write(path: &camino::Utf8Path, contents: &str) -> Result<(), ExportError> {
+    std::fs::write(path, contents).map_err(|source| ExportError::WriteFailed {
+        path: path.to_string(),
+        source,
+    })
+}
+
 pub struct Builder {
     project_name: String,
     part_name: String,
@@ -16,7 +23,10 @@ pub struct Builder {
 impl Builder {
     pub fn new(path: &str, project_name: &str, part_name: &str) -> Result<Self, RHDLError> {
         let mut script = tcl::Script::default();
-        std::fs::create_dir_all(path)?;
+        std::fs::create_dir_all(path).map_err(|source| ExportError::WriteFailed {
+            path: path.into(),
+            source,
+        })?;
         script.add(tcl::CreateProject {
             path: path.into(),
             part: part_name.into(),
@@ -43,12 +53,17 @@ impl Builder {
         Ok(())
     }
     pub fn add_fixture<T: Circuit>(self, fixture: Fixture<T>) -> Result<Self, RHDLError> {
-        let fixture_v_path = self.root_path.join(format!("{}.v", fixture.name()));
         let module = fixture.module()?;
-        std::fs::write(&fixture_v_path, module.to_string())?;
-        let xdc_path = self.root_path.join(format!("{}.xdc", fixture.name()));
-        let constraints = fixture.constraints();
-        std::fs::write(&xdc_path, &constraints)?;
+        self.add_module(fixture.name(), &module, &fixture.constraints())
+    }
+    pub fn add_top(self, top: &BoardTop) -> Result<Self, RHDLError> {
+        self.add_module(top.name(), top.module(), top.constraints())
+    }
+    fn add_module(self, name: &str, module: &Module, constraints: &str) -> Result<Self, RHDLError> {
+        let fixture_v_path = self.root_path.join(format!("{name}.v"));
+        write(&fixture_v_path, &module.to_string())?;
+        let xdc_path = self.root_path.join(format!("{name}.xdc"));
+        write(&xdc_path, constraints)?;
         Ok(self
             .step(AddFiles {
                 kind: FileType::Source,
@@ -89,4 +104,17 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn test_write_failure_is_an_export_error() {
+        // A directory cannot be created inside a file
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml/project");
+        let Err(err) = Builder::new(path, "demo", "xc7a50tfgg484-1") else {
+            panic!("Expected the builder to fail");
+        };
+        assert!(matches!(
+            err.export_error(),
+            Some(ExportError::WriteFailed { path: failed, .. }) if failed == path
+        ));
+    }
 }
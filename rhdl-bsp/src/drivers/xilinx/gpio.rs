@@ -0,0 +1,74 @@
+use rhdl::prelude::*;
+use serde::Serialize;
+
+use crate::constraints::{IOStandard, Location};
+use crate::utils::tt_render;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Options {
+    pub io_standard: IOStandard,
+    pub pins: Vec<Location>,
+}
+
+#[derive(Serialize)]
+struct Context {
+    name: String,
+    options: Options,
+}
+
+static XDC: &str = r#"
+{{ for pin in options.pins -}}
+set_property IOSTANDARD {options.io_standard} [get_ports {name}[{@index}]]
+set_property PACKAGE_PIN {pin} [get_ports {name}[{@index}]]
+{{ endfor}}
+"#;
+
+/// Create a general purpose input resource for use with a [FixtureBuilder].
+/// The input is asynchronous, so it will be synchronized to the clock
+/// of the core it drives.
+pub fn input(name: &str, options: &Options) -> Result<Resource, RHDLError> {
+    let context = Context {
+        name: name.into(),
+        options: options.clone(),
+    };
+    Ok(
+        Resource::input(name, options.pins.len(), ResourceKind::Asynchronous)
+            .with_constraints(&tt_render(XDC, &context)?),
+    )
+}
+
+/// Create a general purpose output resource for use with a [FixtureBuilder]
+pub fn output(name: &str, options: &Options) -> Result<Resource, RHDLError> {
+    let context = Context {
+        name: name.into(),
+        options: options.clone(),
+    };
+    Ok(Resource::output(name, options.pins.len()).with_constraints(&tt_render(XDC, &context)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use crate::bga_pin;
+
+    use super::*;
+
+    #[test]
+    fn test_gpio_input() {
+        let options = Options {
+            io_standard: IOStandard::LowVoltageCMOS_3v3,
+            pins: vec![bga_pin!(A, 1)],
+        };
+        let resource = input("rx", &options).unwrap();
+        assert_eq!(resource.kind, ResourceKind::Asynchronous);
+        assert_eq!(resource.width, 1);
+        let expect = expect![[r#"
+
+            set_property IOSTANDARD LVCMOS33 [get_ports rx[0]]
+            set_property PACKAGE_PIN A1 [get_ports rx[0]]
+
+        "#]];
+        expect.assert_eq(&resource.constraints);
+    }
+}
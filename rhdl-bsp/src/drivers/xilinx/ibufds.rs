@@ -4,6 +4,7 @@ use rhdl::prelude::*;
 use serde::Serialize;
 
 use crate::constraints::{IOStandard, Location};
+use crate::utils::tt_render;
 
 #[derive(Clone, Debug)]
 pub struct UpcaseBool(bool);
@@ -82,11 +83,39 @@ pub fn build<T: CircuitIO>(
     Ok(driver)
 }
 
+#[derive(Serialize)]
+struct ResourceContext {
+    name: String,
+    options: Options,
+    output: String,
+}
+
+/// Create a differential clock resource for use with a [FixtureBuilder]
+pub fn resource(name: &str, options: &Options) -> Result<Resource, RHDLError> {
+    let context = ResourceContext {
+        name: name.into(),
+        options: options.clone(),
+        output: Resource::net_name(name),
+    };
+    Ok(Resource {
+        name: name.into(),
+        direction: Direction::Input,
+        width: 1,
+        kind: ResourceKind::Clock,
+        ports: vec![
+            DriverPort::input(&format!("{name}_p"), 1),
+            DriverPort::input(&format!("{name}_n"), 1),
+        ],
+        hdl: tt_render(HDL, &context)?,
+        constraints: tt_render(XDC, &context)?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use expect_test::expect;
 
-    use crate::{bga_pin, utils::tt_render};
+    use crate::bga_pin;
 
     use super::*;
 
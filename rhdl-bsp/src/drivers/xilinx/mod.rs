@@ -1,2 +1,3 @@
+pub mod gpio;
 pub mod ibufds;
 pub mod open_collector;
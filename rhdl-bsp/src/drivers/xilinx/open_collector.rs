@@ -2,6 +2,7 @@ use rhdl::prelude::*;
 use serde::Serialize;
 
 use crate::constraints::{IOStandard, Location};
+use crate::utils::tt_render;
 
 #[derive(Clone, Debug, Serialize)]
 pub struct Options {
@@ -52,6 +53,34 @@ pub fn build<T: CircuitIO>(
     Ok(driver)
 }
 
+#[derive(Serialize)]
+struct ResourceContext {
+    name: String,
+    pins_msb: usize,
+    options: Options,
+    output: String,
+}
+
+/// Create an open collector output resource for use with a [FixtureBuilder]
+pub fn resource(name: &str, options: &Options) -> Result<Resource, RHDLError> {
+    let width = options.pins.len();
+    let context = ResourceContext {
+        name: name.into(),
+        pins_msb: width - 1,
+        options: options.clone(),
+        output: Resource::net_name(name),
+    };
+    Ok(Resource {
+        name: name.into(),
+        direction: Direction::Output,
+        width,
+        kind: ResourceKind::Synchronous,
+        ports: vec![DriverPort::output(name, width)],
+        hdl: tt_render(HDL, &context)?,
+        constraints: tt_render(XDC, &context)?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::bga_pin;
@@ -1,3 +1,4 @@
 pub mod host;
 pub mod leds;
+pub mod resources;
 pub mod sys_clock;
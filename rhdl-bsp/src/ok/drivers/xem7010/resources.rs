@@ -0,0 +1,45 @@
+use crate::bga_pin;
+use crate::constraints::IOStandard;
+use crate::drivers::xilinx::{ibufds, open_collector};
+use rhdl::prelude::*;
+
+/// The sys clock (200 MHz) as a resource for a [FixtureBuilder]
+pub fn sys_clock() -> Result<Resource, RHDLError> {
+    let mut resource = ibufds::resource(
+        "sysclk",
+        &ibufds::Options {
+            diff_term: false.into(),
+            ibuf_low_pwr: true.into(),
+            io_standard: Some(IOStandard::LowVoltageDifferentialSignal_2v5),
+            pos_pin: bga_pin!(K, 4),
+            neg_pin: bga_pin!(J, 4),
+        },
+    )?;
+    resource.constraints += "create_clock -period 5 [get_ports sysclk_p]\n";
+    Ok(resource)
+}
+
+/// The 8 LEDs as a resource for a [FixtureBuilder]
+pub fn leds() -> Result<Resource, RHDLError> {
+    open_collector::resource(
+        "led",
+        &open_collector::Options {
+            io_standard: IOStandard::LowVoltageCMOS_3v3,
+            pins: vec![
+                bga_pin!(N, 13),
+                bga_pin!(N, 14),
+                bga_pin!(P, 15),
+                bga_pin!(P, 16),
+                bga_pin!(N, 17),
+                bga_pin!(P, 17),
+                bga_pin!(R, 16),
+                bga_pin!(R, 17),
+            ],
+        },
+    )
+}
+
+/// All of the resources on the board
+pub fn resources() -> Result<Vec<Resource>, RHDLError> {
+    Ok(vec![sys_clock()?, leds()?])
+}
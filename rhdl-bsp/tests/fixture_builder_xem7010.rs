@@ -0,0 +1,238 @@
+// Assemble a blinker and a serial echo onto an XEM7010, using
+// the fixture builder to connect them to the board resources.
+
+use rhdl::prelude::*;
+use rhdl_bsp::bga_pin;
+use rhdl_bsp::constraints::{IOStandard, Location};
+use rhdl_bsp::drivers::xilinx::gpio;
+use rhdl_bsp::ok::drivers::xem7010::resources;
+
+mod blinker {
+    use super::*;
+
+    #[derive(Clone, Synchronous, SynchronousDQ, Default)]
+    pub struct U {
+        counter: rhdl_fpga::core::counter::Counter<U8>,
+    }
+
+    impl SynchronousIO for U {
+        type I = ();
+        type O = b8;
+        type Kernel = blinker;
+    }
+
+    #[kernel]
+    pub fn blinker(_cr: ClockReset, _i: (), q: Q) -> (b8, D) {
+        let mut d = D::dont_care();
+        d.counter = true;
+        let output_bit = (q.counter >> 4) & 1 != 0;
+        let o = if output_bit { bits(0xaa) } else { bits(0x55) };
+        (o, d)
+    }
+}
+
+mod echo {
+    use super::*;
+
+    // Echo the serial line back, one clock later
+    #[derive(Clone, Synchronous, SynchronousDQ)]
+    pub struct U {
+        line: rhdl_fpga::core::dff::DFF<bool>,
+    }
+
+    impl Default for U {
+        fn default() -> Self {
+            Self {
+                line: rhdl_fpga::core::dff::DFF::new(true),
+            }
+        }
+    }
+
+    impl SynchronousIO for U {
+        type I = bool;
+        type O = bool;
+        type Kernel = echo;
+    }
+
+    #[kernel]
+    pub fn echo(_cr: ClockReset, i: bool, q: Q) -> (bool, D) {
+        (q.line, D { line: i })
+    }
+}
+
+fn lvcmos(pin: Location) -> gpio::Options {
+    gpio::Options {
+        io_standard: IOStandard::LowVoltageCMOS_3v3,
+        pins: vec![pin],
+    }
+}
+
+fn board() -> Result<FixtureBuilder, RHDLError> {
+    let mut builder = FixtureBuilder::new("top");
+    builder.add_resources(resources::resources()?)?;
+    builder.add_resource(gpio::input("uart_rx", &lvcmos(bga_pin!(U, 20)))?)?;
+    builder.add_resource(gpio::output("uart_tx", &lvcmos(bga_pin!(V, 20)))?)?;
+    Ok(builder)
+}
+
+fn blinky_echo_top() -> Result<BoardTop, RHDLError> {
+    let mut builder = board()?;
+    let blinker: Adapter<blinker::U, Red> = Adapter::new(blinker::U::default());
+    let echo: Adapter<echo::U, Red> = Adapter::new(echo::U::default());
+    builder.add_instance("blinker", &blinker)?;
+    builder.add_instance("echo", &echo)?;
+    for name in ["blinker", "echo"] {
+        builder.drive("sysclk", name, &path!(.clock_reset.val().clock))?;
+        builder.constant(reset(false), name, &path!(.clock_reset.val().reset))?;
+    }
+    builder.sink("blinker", &path!(.val()), "led")?;
+    builder.drive("uart_rx", "echo", &path!(.input.val()))?;
+    builder.sink("echo", &path!(.val()), "uart_tx")?;
+    builder.build()
+}
+
+static IBUFDS_MODEL: &str = r#"
+module IBUFDS #(
+    parameter DIFF_TERM = "FALSE",
+    parameter IBUF_LOW_PWR = "TRUE",
+    parameter IOSTANDARD = "DEFAULT"
+) (output O, input I, input IB);
+    assign O = I;
+endmodule
+"#;
+
+static TESTBENCH: &str = r#"
+module testbench;
+    reg clk = 0;
+    reg rx = 1;
+    wire tx;
+    wire [7:0] led;
+    integer i;
+    top dut(.sysclk_p(clk), .sysclk_n(~clk), .led(led), .uart_rx(rx), .uart_tx(tx));
+    always #5 clk = ~clk;
+    initial begin
+        #100;
+        for (i = 0; i < 32; i = i + 1) begin
+            rx = i[0] ^ i[2];
+            // Two synchronizer stages, and the echo register
+            #40;
+            if (tx !== rx) begin
+                $display("FAILED: tx is %b, expected %b", tx, rx);
+                $finish;
+            end
+            if (led !== 8'b0z0z0z0z && led !== 8'bz0z0z0z0) begin
+                $display("FAILED: led is %b", led);
+                $finish;
+            end
+        end
+        $display("TESTBENCH OK");
+        $finish;
+    end
+endmodule
+"#;
+
+#[test]
+fn test_blinky_echo_top() -> Result<(), RHDLError> {
+    let top = blinky_echo_top()?;
+    let verilog = top.module().to_string();
+    assert!(verilog.contains("blinker blinker_inst"));
+    assert!(verilog.contains("echo echo_inst"));
+    // The serial input is synchronized, but the clock is not
+    assert!(verilog.contains("sync0_meta <= res_uart_rx;"));
+    assert!(!verilog.contains("sync1_meta"));
+    assert!(verilog.contains("assign blinker_input[0] = res_sysclk;"));
+    let constraints = top.constraints();
+    for pin in ["K4", "J4", "N13", "R17", "U20", "V20"] {
+        assert!(constraints.contains(&format!("PACKAGE_PIN {pin} ")));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_blinky_echo_top_simulates() -> Result<(), RHDLError> {
+    let top = blinky_echo_top()?;
+    let root = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("blinky_echo");
+    std::fs::create_dir_all(&root)?;
+    let source = root.join("testbench.v");
+    std::fs::write(
+        &source,
+        format!("{IBUFDS_MODEL}\n{TESTBENCH}\n{}", top.module()),
+    )?;
+    let status = std::process::Command::new("iverilog")
+        .arg("-o")
+        .arg(root.join("testbench"))
+        .arg(&source)
+        .status()
+        .expect("Icarus Verilog should be installed and in your PATH.");
+    assert!(status.success());
+    let output = std::process::Command::new("vvp")
+        .arg(root.join("testbench"))
+        .output()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.contains("FAILED"), "{output}");
+    assert!(output.contains("TESTBENCH OK"), "{output}");
+    Ok(())
+}
+
+#[test]
+fn test_unsynchronized_input() -> Result<(), RHDLError> {
+    let mut builder = board()?;
+    let echo: Adapter<echo::U, Red> = Adapter::new(echo::U::default());
+    builder.add_instance("echo", &echo)?;
+    builder.drive("sysclk", "echo", &path!(.clock_reset.val().clock))?;
+    builder.constant(reset(false), "echo", &path!(.clock_reset.val().reset))?;
+    builder.drive_unsynchronized("uart_rx", "echo", &path!(.input.val()))?;
+    builder.sink("echo", &path!(.val()), "uart_tx")?;
+    // The LEDs are not used, so the fixture is incomplete
+    let err = builder.build().unwrap_err();
+    assert!(matches!(
+        err.export_error(),
+        Some(ExportError::ResourceNotDriven(resource)) if resource == "led"
+    ));
+    let blinker: Adapter<blinker::U, Red> = Adapter::new(blinker::U::default());
+    builder.add_instance("blinker", &blinker)?;
+    builder.drive("sysclk", "blinker", &path!(.clock_reset.val().clock))?;
+    builder.constant(reset(false), "blinker", &path!(.clock_reset.val().reset))?;
+    builder.sink("blinker", &path!(.val()), "led")?;
+    let verilog = builder.build()?.module().to_string();
+    assert!(!verilog.contains("sync0"));
+    assert!(verilog.contains("assign echo_input[2] = res_uart_rx;"));
+    Ok(())
+}
+
+#[test]
+fn test_mismatched_width_is_an_error() -> Result<(), RHDLError> {
+    let mut builder = board()?;
+    let echo: Adapter<echo::U, Red> = Adapter::new(echo::U::default());
+    builder.add_instance("echo", &echo)?;
+    // The echo output is a single bit, but there are 8 LEDs
+    let err = builder.sink("echo", &path!(.val()), "led").unwrap_err();
+    assert!(matches!(
+        err.export_error(),
+        Some(ExportError::ConnectionWidthMismatch {
+            from_width: 1,
+            to,
+            to_width: 8,
+            ..
+        }) if to == "led"
+    ));
+    // Outputs cannot drive inputs, and clocks only drive clocks
+    let err = builder
+        .drive("led", "echo", &path!(.input.val()))
+        .unwrap_err();
+    assert!(matches!(
+        err.export_error(),
+        Some(ExportError::ResourceDirection {
+            resource,
+            expected: Direction::Input,
+        }) if resource == "led"
+    ));
+    let err = builder
+        .drive("sysclk", "echo", &path!(.input.val()))
+        .unwrap_err();
+    assert!(matches!(
+        err.export_error(),
+        Some(ExportError::ClockMismatch { from, to }) if from == "sysclk" && to == "echo.input@"
+    ));
+    Ok(())
+}
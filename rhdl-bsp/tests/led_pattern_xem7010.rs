@@ -0,0 +1,160 @@
+// Run an LED pattern on an XEM7010, using the fixture builder to
+// connect the pattern generator to the board LEDs.
+
+use expect_test::expect_file;
+use rhdl::prelude::*;
+use rhdl_bsp::ok::drivers::xem7010::resources;
+use rhdl_fpga::micro::program::Program;
+
+mod lights {
+    use super::*;
+    use rhdl_fpga::led::pattern::LedPattern;
+    use rhdl_fpga::micro::sequencer;
+
+    // Light all of the LEDs with the output of the pattern generator
+    #[derive(Clone, Synchronous, SynchronousDQ)]
+    pub struct U {
+        pattern: LedPattern<32>,
+    }
+
+    impl U {
+        pub fn new(program: &Program) -> Self {
+            Self {
+                pattern: LedPattern::new(program.assemble().expect("Pattern does not fit the ROM")),
+            }
+        }
+    }
+
+    impl SynchronousIO for U {
+        type I = ();
+        type O = b8;
+        type Kernel = lights;
+    }
+
+    #[kernel]
+    pub fn lights(_cr: ClockReset, _i: (), q: Q) -> (b8, D) {
+        let d = D {
+            pattern: sequencer::In {
+                conditions: [false; 8],
+            },
+        };
+        let o = if q.pattern.led { bits(0xff) } else { bits(0) };
+        (o, d)
+    }
+}
+
+// The usual breathing pattern, with each level held for
+// 200 x 65536 clocks (about 65ms with the 200MHz system clock)
+fn breathe_slowly() -> Program {
+    let mut program = Program::new();
+    program.label("top");
+    for (n, level) in [0, 32, 128, 255, 128, 32].into_iter().enumerate() {
+        let hold = format!("hold{n}");
+        program
+            .set(0x00FF, level)
+            .load(0, 200)
+            .label(&hold)
+            .wait(0xFFFF)
+            .loop_to(0, &hold);
+    }
+    program.jump("top");
+    program
+}
+
+fn led_pattern_top(program: &Program) -> Result<BoardTop, RHDLError> {
+    let mut builder = FixtureBuilder::new("top");
+    builder.add_resources(resources::resources()?)?;
+    let lights: Adapter<lights::U, Red> = Adapter::new(lights::U::new(program));
+    builder.add_instance("lights", &lights)?;
+    builder.drive("sysclk", "lights", &path!(.clock_reset.val().clock))?;
+    builder.constant(reset(false), "lights", &path!(.clock_reset.val().reset))?;
+    builder.sink("lights", &path!(.val()), "led")?;
+    builder.build()
+}
+
+static IBUFDS_MODEL: &str = r#"
+module IBUFDS #(
+    parameter DIFF_TERM = "FALSE",
+    parameter IBUF_LOW_PWR = "TRUE",
+    parameter IOSTANDARD = "DEFAULT"
+) (output O, input I, input IB);
+    assign O = I;
+endmodule
+"#;
+
+// The LEDs are open collector, so a lit LED is driven low, and a dark
+// one is left floating.  With a brightness of 64, the LEDs are lit for
+// 64 clocks out of every 256.
+static TESTBENCH: &str = r#"
+module testbench;
+    reg clk = 0;
+    wire [7:0] led;
+    integer i;
+    integer lit = 0;
+    top dut(.sysclk_p(clk), .sysclk_n(~clk), .led(led));
+    always #5 clk = ~clk;
+    initial begin
+        #2;
+        for (i = 0; i < 2048; i = i + 1) begin
+            #10;
+            if (led === 8'b0000_0000) begin
+                lit = lit + 1;
+            end else if (led !== 8'bzzzz_zzzz) begin
+                $display("FAILED: led is %b", led);
+                $finish;
+            end
+        end
+        if (lit < 7 * 64 || lit > 8 * 64) begin
+            $display("FAILED: lit for %d clocks", lit);
+            $finish;
+        end
+        $display("TESTBENCH OK");
+        $finish;
+    end
+endmodule
+"#;
+
+#[test]
+fn test_led_pattern_top() -> Result<(), RHDLError> {
+    let top = led_pattern_top(&breathe_slowly())?;
+    let verilog = top.module().to_string();
+    assert!(verilog.contains("lights lights_inst"));
+    assert!(verilog.contains("assign lights_input[0] = res_sysclk;"));
+    let constraints = top.constraints();
+    for pin in [
+        "K4", "J4", "N13", "N14", "P15", "P16", "N17", "P17", "R16", "R17",
+    ] {
+        assert!(constraints.contains(&format!("PACKAGE_PIN {pin} ")));
+    }
+    expect_file!["led_pattern_xem7010.v.expect"].assert_eq(&verilog);
+    Ok(())
+}
+
+#[test]
+fn test_led_pattern_top_simulates() -> Result<(), RHDLError> {
+    // A pattern that is quick enough to simulate
+    let mut program = Program::new();
+    program.set(0x00FF, 64).halt();
+    let top = led_pattern_top(&program)?;
+    let root = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("led_pattern");
+    std::fs::create_dir_all(&root)?;
+    let source = root.join("testbench.v");
+    std::fs::write(
+        &source,
+        format!("{IBUFDS_MODEL}\n{TESTBENCH}\n{}", top.module()),
+    )?;
+    let status = std::process::Command::new("iverilog")
+        .arg("-o")
+        .arg(root.join("testbench"))
+        .arg(&source)
+        .status()
+        .expect("Icarus Verilog should be installed and in your PATH.");
+    assert!(status.success());
+    let output = std::process::Command::new("vvp")
+        .arg(root.join("testbench"))
+        .output()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.contains("FAILED"), "{output}");
+    assert!(output.contains("TESTBENCH OK"), "{output}");
+    Ok(())
+}
@@ -0,0 +1,198 @@
+// Run a serial echo on an XEM7010, using the fixture builder to
+// connect the echo to a pair of GPIO pins, and its error flags to
+// the board LEDs.
+
+use expect_test::expect_file;
+use rhdl::prelude::*;
+use rhdl_bsp::bga_pin;
+use rhdl_bsp::constraints::{IOStandard, Location};
+use rhdl_bsp::drivers::xilinx::gpio;
+use rhdl_bsp::ok::drivers::xem7010::resources;
+
+mod serial {
+    use super::*;
+    use rhdl_fpga::core::dff;
+    use rhdl_fpga::uart::echo::UartEcho;
+
+    #[derive(PartialEq, Debug, Digital)]
+    pub struct Out {
+        pub tx: bool,
+        pub led: b8,
+    }
+
+    // Echo the serial line, and light an LED for each kind of
+    // error seen (overflow on the first, framing on the second)
+    #[derive(Clone, Synchronous, SynchronousDQ)]
+    pub struct U {
+        echo: UartEcho<U4>,
+        errors: dff::DFF<b8>,
+    }
+
+    impl U {
+        pub fn new(clocks_per_bit: u16) -> Self {
+            Self {
+                echo: UartEcho::new(clocks_per_bit),
+                errors: dff::DFF::default(),
+            }
+        }
+    }
+
+    impl SynchronousIO for U {
+        type I = bool;
+        type O = Out;
+        type Kernel = serial;
+    }
+
+    #[kernel]
+    pub fn serial(_cr: ClockReset, i: bool, q: Q) -> (Out, D) {
+        let overflow: b8 = if q.echo.overflow { bits(1) } else { bits(0) };
+        let frame_error: b8 = if q.echo.frame_error { bits(2) } else { bits(0) };
+        let d = D {
+            echo: i,
+            errors: q.errors | overflow | frame_error,
+        };
+        let o = Out {
+            tx: q.echo.tx,
+            led: q.errors,
+        };
+        (o, d)
+    }
+}
+
+fn lvcmos(pin: Location) -> gpio::Options {
+    gpio::Options {
+        io_standard: IOStandard::LowVoltageCMOS_3v3,
+        pins: vec![pin],
+    }
+}
+
+fn uart_echo_top(clocks_per_bit: u16) -> Result<BoardTop, RHDLError> {
+    let mut builder = FixtureBuilder::new("top");
+    builder.add_resources(resources::resources()?)?;
+    builder.add_resource(gpio::input("uart_rx", &lvcmos(bga_pin!(U, 20)))?)?;
+    builder.add_resource(gpio::output("uart_tx", &lvcmos(bga_pin!(V, 20)))?)?;
+    let serial: Adapter<serial::U, Red> = Adapter::new(serial::U::new(clocks_per_bit));
+    builder.add_instance("serial", &serial)?;
+    builder.drive("sysclk", "serial", &path!(.clock_reset.val().clock))?;
+    builder.constant(reset(false), "serial", &path!(.clock_reset.val().reset))?;
+    builder.drive("uart_rx", "serial", &path!(.input.val()))?;
+    builder.sink("serial", &path!(.val().tx), "uart_tx")?;
+    builder.sink("serial", &path!(.val().led), "led")?;
+    builder.build()
+}
+
+static IBUFDS_MODEL: &str = r#"
+module IBUFDS #(
+    parameter DIFF_TERM = "FALSE",
+    parameter IBUF_LOW_PWR = "TRUE",
+    parameter IOSTANDARD = "DEFAULT"
+) (output O, input I, input IB);
+    assign O = I;
+endmodule
+"#;
+
+// Each bit lasts 8 clocks (80ns).  Each byte is sent on the rx line,
+// and the echo on the tx line is sampled in the middle of each bit.
+static TESTBENCH: &str = r#"
+module testbench;
+    reg clk = 0;
+    reg rx = 1;
+    wire tx;
+    wire [7:0] led;
+    reg [7:0] echo;
+    integer i;
+    integer k;
+    top dut(.sysclk_p(clk), .sysclk_n(~clk), .led(led), .uart_rx(rx), .uart_tx(tx));
+    always #5 clk = ~clk;
+    task send(input [7:0] value);
+        begin
+            rx = 0;
+            #80;
+            for (k = 0; k < 8; k = k + 1) begin
+                rx = value[k];
+                #80;
+            end
+            rx = 1;
+            #80;
+        end
+    endtask
+    task receive;
+        integer j;
+        begin
+            @(negedge tx);
+            #40;
+            for (j = 0; j < 8; j = j + 1) begin
+                #80;
+                echo[j] = tx;
+            end
+            #80;
+            if (tx !== 1'b1) begin
+                $display("FAILED: missing stop bit");
+                $finish;
+            end
+        end
+    endtask
+    initial begin
+        #202;
+        for (i = 0; i < 4; i = i + 1) begin
+            fork
+                send(8'h5A + i * 8'h33);
+                receive;
+            join
+            if (echo !== 8'h5A + i * 8'h33) begin
+                $display("FAILED: echo is %h", echo);
+                $finish;
+            end
+            #160;
+        end
+        if (led !== 8'bzzzz_zzzz) begin
+            $display("FAILED: led is %b", led);
+            $finish;
+        end
+        $display("TESTBENCH OK");
+        $finish;
+    end
+endmodule
+"#;
+
+#[test]
+fn test_uart_echo_top() -> Result<(), RHDLError> {
+    // 115200 baud with the 200MHz system clock
+    let top = uart_echo_top(1736)?;
+    let verilog = top.module().to_string();
+    assert!(verilog.contains("serial serial_inst"));
+    assert!(verilog.contains("sync0_meta <= res_uart_rx;"));
+    assert!(verilog.contains("assign serial_input[0] = res_sysclk;"));
+    let constraints = top.constraints();
+    for pin in ["K4", "J4", "N13", "R17", "U20", "V20"] {
+        assert!(constraints.contains(&format!("PACKAGE_PIN {pin} ")));
+    }
+    expect_file!["uart_echo_xem7010.v.expect"].assert_eq(&verilog);
+    Ok(())
+}
+
+#[test]
+fn test_uart_echo_top_simulates() -> Result<(), RHDLError> {
+    let top = uart_echo_top(8)?;
+    let root = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("uart_echo");
+    std::fs::create_dir_all(&root)?;
+    let source = root.join("testbench.v");
+    std::fs::write(
+        &source,
+        format!("{IBUFDS_MODEL}\n{TESTBENCH}\n{}", top.module()),
+    )?;
+    let status = std::process::Command::new("iverilog")
+        .arg("-o")
+        .arg(root.join("testbench"))
+        .arg(&source)
+        .status()
+        .expect("Icarus Verilog should be installed and in your PATH.");
+    assert!(status.success());
+    let output = std::process::Command::new("vvp")
+        .arg(root.join("testbench"))
+        .output()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.contains("FAILED"), "{output}");
+    assert!(output.contains("TESTBENCH OK"), "{output}");
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use rhdl::prelude::*;
+use rhdl_fpga::doc::write_svg_as_markdown;
+use rhdl_fpga::led::pattern::{breathe, LedPattern};
+use rhdl_fpga::micro::sequencer::In;
+
+fn main() -> Result<(), RHDLError> {
+    // A short pattern, so that the steps are visible in the trace.
+    // On a board, the dwell would be millions of clocks.
+    let program = breathe(&[16, 128, 240, 128], 511);
+    let uut = LedPattern::<9>::new(program.assemble().expect("Pattern does not fit the ROM"));
+    let input = std::iter::repeat(In {
+        conditions: [false; 8],
+    })
+    .with_reset(1)
+    .clock_pos_edge(100);
+    let vcd = uut
+        .run(input)?
+        .take_while(|t| t.time < 250_000)
+        .collect::<Vcd>();
+    write_svg_as_markdown(vcd, "led_pattern.md", SvgOptions::default())?;
+    Ok(())
+}
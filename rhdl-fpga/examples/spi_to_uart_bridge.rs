@@ -0,0 +1,27 @@
+use rhdl::prelude::*;
+use rhdl_fpga::doc::write_svg_as_markdown;
+use rhdl_fpga::spi::uart_bridge::{In, SpiToUartBridge};
+
+fn main() -> Result<(), RHDLError> {
+    // Two bytes from the host, with 4 clocks per bit, and an SPI
+    // clock of 1/4 the system clock.  The device is not connected,
+    // so it replies with all ones.
+    let frame = |byte: u8| {
+        std::iter::once(false)
+            .chain((0..8).map(move |k| byte & (1 << k) != 0))
+            .chain(std::iter::once(true))
+    };
+    let line = std::iter::repeat_n(true, 4)
+        .chain(frame(0x5A))
+        .chain(frame(0xC3))
+        .chain(std::iter::repeat_n(true, 20))
+        .flat_map(|b| std::iter::repeat_n(b, 4));
+    let uut = SpiToUartBridge::<U2>::new(4, 2);
+    let input = line
+        .map(|rx| In { rx, miso: true })
+        .with_reset(1)
+        .clock_pos_edge(100);
+    let vcd = uut.run(input)?.collect::<Vcd>();
+    write_svg_as_markdown(vcd, "spi_to_uart_bridge.md", SvgOptions::default())?;
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use rhdl::prelude::*;
+use rhdl_fpga::{
+    core::{shift_out::ShiftOut, strobe_gen::StrobeGen},
+    doc::write_svg_as_markdown,
+};
+
+#[derive(Clone, Synchronous, SynchronousDQ)]
+/// A [ShiftOut] that sends a bit on each strobe of a [StrobeGen],
+/// so each bit is held on the line for a whole period of the strobe.
+struct Transmitter {
+    strobe: StrobeGen<U4>,
+    shift: ShiftOut<U8>,
+}
+
+impl SynchronousIO for Transmitter {
+    type I = Option<b8>;
+    type O = bool;
+    type Kernel = kernel;
+}
+
+#[kernel]
+pub fn kernel(_cr: ClockReset, i: Option<b8>, q: Q) -> (bool, D) {
+    let (load, data) = match i {
+        Some(data) => (true, data),
+        None => (false, b8(0)),
+    };
+    let d = D {
+        strobe: (false, b4(0)),
+        shift: (q.strobe, load, data),
+    };
+    (q.shift, d)
+}
+
+fn main() -> Result<(), RHDLError> {
+    let uut = Transmitter {
+        strobe: StrobeGen::new(b4(4)),
+        shift: ShiftOut::new(true),
+    };
+    let input = (0..48)
+        .map(|n| (n == 2).then_some(b8(0b1011_0010)))
+        .with_reset(1)
+        .clock_pos_edge(100);
+    let vcd = uut.run(input)?.collect::<Vcd>();
+    write_svg_as_markdown(vcd, "strobe_gen.md", SvgOptions::default())?;
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use rhdl::prelude::*;
+use rhdl_fpga::doc::write_svg_as_markdown;
+use rhdl_fpga::uart::echo::UartEcho;
+
+fn main() -> Result<(), RHDLError> {
+    // Two bytes sent back to back, with 4 clocks per bit, so that
+    // the bits are visible in the trace.
+    let frame = |byte: u8| {
+        std::iter::once(false)
+            .chain((0..8).map(move |k| byte & (1 << k) != 0))
+            .chain(std::iter::once(true))
+    };
+    let line = std::iter::repeat_n(true, 4)
+        .chain(frame(0x5A))
+        .chain(frame(0xC3))
+        .chain(std::iter::repeat_n(true, 16))
+        .flat_map(|b| std::iter::repeat_n(b, 4));
+    let uut = UartEcho::<U2>::new(4);
+    let input = line.with_reset(1).clock_pos_edge(100);
+    let vcd = uut.run(input)?.collect::<Vcd>();
+    write_svg_as_markdown(vcd, "uart_echo.md", SvgOptions::default())?;
+    Ok(())
+}
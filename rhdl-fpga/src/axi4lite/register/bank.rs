@@ -49,7 +49,10 @@
 ")]
 
 use badascii_doc::{badascii, badascii_formal};
-use rhdl::prelude::*;
+use rhdl::{
+    core::report::{Access, CsrMap, CsrRegister, Description},
+    prelude::*,
+};
 
 use crate::{
     axi4lite::{
@@ -93,6 +96,30 @@ impl<const N: usize> AxiRegBank<N> {
     }
 }
 
+impl<const N: usize> Describe for AxiRegBank<N> {
+    fn describe(&self) -> Description {
+        // Each register takes 4 bytes, starting at the base address
+        let registers = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(ndx, reg)| CsrRegister {
+                name: format!("reg{ndx}"),
+                offset: ndx as u64 * 4,
+                width: 32,
+                access: Access::ReadWrite,
+                reset: reg.reset_value().raw() as u64,
+            })
+            .collect();
+        Description::new("AxiRegBank")
+            .param("registers", N)
+            .csr(CsrMap {
+                base: self.address_low.value().raw() as u64,
+                registers,
+            })
+    }
+}
+
 #[derive(PartialEq, Debug, Digital)]
 /// Input for the [AxiRegBank]
 pub struct In {
@@ -0,0 +1,285 @@
+//! Rotating Find First Set
+//!
+//! Schedulers (such as round robin arbiters) need to find the next set
+//! bit in a request bitmap at or after some starting position, wrapping
+//! around to the start of the bitmap if there is none.  A plain priority
+//! encoder always starts from bit 0.
+//!
+//! The [rotating_ffs] kernel uses the double bitmap trick.  The requests
+//! at or above the starting position are searched first, and if there are
+//! none, all of the requests are searched.  Each search isolates the
+//! lowest set bit with `x & (!x + 1)`, and the index of that bit is
+//! encoded by masking it with a constant for each bit of the index.  None
+//! of the steps depend on the width of the bitmap, so the logic depth
+//! grows only with the log of the width (in the adder and reductions).
+//!
+//! The [RotatingFfs] core wraps the kernel, and registers the result.
+//! The bitmap can be up to 128 bits wide.  A starting position at or
+//! beyond the width of the bitmap searches from bit 0.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+RotatingFfs+--------+
+ bN   |                      | b8
++---->| req            index +---->
+ b8   |                      | bool
++---->| start          found +---->
+      +----------------------+
+")]
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::bitops::ffs::rotating_ffs;
+//!
+//! // Bits 1 and 5 are set.  Starting at 2 finds 5, and starting at 6 wraps to 1.
+//! assert_eq!(rotating_ffs::<U8>(b8(0b0010_0010), b8(2)).index, b8(5));
+//! assert_eq!(rotating_ffs::<U8>(b8(0b0010_0010), b8(6)).index, b8(1));
+//!```
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The result of a search
+pub struct Ffs {
+    /// The index of the first set bit (in rotated order)
+    pub index: b8,
+    /// Set if any bit is set.  If not, the index is 0.
+    pub found: bool,
+}
+
+#[kernel]
+#[allow(clippy::needless_range_loop)]
+/// Find the first set bit of `req` at or after `start`, wrapping
+/// around to bit 0 if there is none.
+pub fn rotating_ffs<N: BitWidth>(req: Bits<N>, start: b8) -> Ffs {
+    // The requests at or above the starting position
+    let ones: Bits<N> = !bits(0);
+    let above = req & (ones << start);
+    let search = if above.any() { above } else { req };
+    // Isolate the lowest set bit
+    let lowest = search & (!search + 1);
+    // Bit k of the index is set if the lowest set bit sits at
+    // a position with bit k set
+    // (Underscores are not allowed in kernel literals)
+    let masks = [
+        b128(0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA),
+        b128(0xCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC),
+        b128(0xF0F0F0F0F0F0F0F0F0F0F0F0F0F0F0F0),
+        b128(0xFF00FF00FF00FF00FF00FF00FF00FF00),
+        b128(0xFFFF0000FFFF0000FFFF0000FFFF0000),
+        b128(0xFFFFFFFF00000000FFFFFFFF00000000),
+        b128(0xFFFFFFFFFFFFFFFF0000000000000000),
+    ];
+    let mut index = b8(0);
+    for k in 0..7 {
+        let mask: Bits<N> = masks[k].resize();
+        if (lowest & mask).any() {
+            index |= 1 << k;
+        }
+    }
+    Ffs {
+        index,
+        found: req.any(),
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [RotatingFfs] core
+pub struct In<N: BitWidth> {
+    /// The request bitmap
+    pub req: Bits<N>,
+    /// The position to start the search from
+    pub start: b8,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The rotating find first set core.  The result of the
+/// search appears one clock after the inputs.
+pub struct RotatingFfs<N: BitWidth> {
+    result: dff::DFF<Ffs>,
+    _n: std::marker::PhantomData<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for RotatingFfs<N> {
+    fn default() -> Self {
+        assert!(N::BITS <= 128, "The bitmap can be at most 128 bits wide");
+        Self {
+            result: dff::DFF::new(Ffs::default()),
+            _n: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for RotatingFfs<N> {
+    type I = In<N>;
+    type O = Ffs;
+    type Kernel = rotating_ffs_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn rotating_ffs_kernel<N: BitWidth>(_cr: ClockReset, i: In<N>, q: Q<N>) -> (Ffs, D<N>) {
+    let d = D::<N> {
+        result: rotating_ffs::<N>(i.req, i.start),
+        _n: (),
+    };
+    (q.result, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{Rng, SeedableRng};
+    use rhdl::core::ntl::{spec::OpCode, visit::visit_wires};
+
+    use super::*;
+
+    fn reference(req: u128, start: u32, width: u32) -> Ffs {
+        (0..width)
+            .map(|n| (start + n) % width)
+            .find(|n| req & (1 << n) != 0)
+            .map(|n| Ffs {
+                index: bits(n as u128),
+                found: true,
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_exhaustive_8_bit() {
+        for req in 0..256 {
+            for start in 0..8 {
+                assert_eq!(
+                    rotating_ffs::<U8>(bits(req), bits(start)),
+                    reference(req, start as u32, 8),
+                    "{req:#010b} from {start}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_start_past_the_end_wraps() {
+        assert_eq!(rotating_ffs::<U8>(b8(0b1000_0100), b8(8)).index, b8(2));
+        assert_eq!(rotating_ffs::<U8>(b8(0b1000_0100), b8(200)).index, b8(2));
+    }
+
+    #[test]
+    fn test_random_64_bit() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5EED);
+        for _ in 0..2000 {
+            // Sparse maps exercise the wrap more often
+            let req = rng.random::<u64>() & rng.random::<u64>() & rng.random::<u64>();
+            let start = rng.random_range(0..64);
+            assert_eq!(
+                rotating_ffs::<U64>(bits(req as u128), bits(start)),
+                reference(req as u128, start as u32, 64),
+                "{req:#x} from {start}"
+            );
+        }
+        assert_eq!(rotating_ffs::<U64>(bits(0), b8(12)), Ffs::default());
+        assert_eq!(rotating_ffs::<U64>(bits(1 << 63), b8(63)).index, b8(63));
+    }
+
+    #[test]
+    fn test_core_registers_result() -> miette::Result<()> {
+        let uut = RotatingFfs::<U8>::default();
+        let inputs = (0..256)
+            .flat_map(|req| (0..8).map(move |start| (req, start)))
+            .collect::<Vec<_>>();
+        let outputs = uut
+            .run(
+                inputs
+                    .iter()
+                    .map(|(req, start)| In {
+                        req: bits(*req),
+                        start: bits(*start),
+                    })
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        for ((req, start), o) in inputs.iter().zip(outputs) {
+            assert_eq!(o, reference(*req, *start as u32, 8));
+        }
+        Ok(())
+    }
+
+    // The longest chain of netlist operations from an input to
+    // a register in the core
+    fn logic_depth<N: BitWidth>() -> miette::Result<usize> {
+        let uut = RotatingFfs::<N>::default();
+        let descriptor = uut.descriptor("top")?;
+        let ntl = &descriptor.ntl;
+        let mut depth = HashMap::new();
+        let mut deepest = 0;
+        // The ops are not necessarily in order, so iterate to a fixed point
+        loop {
+            let mut changed = false;
+            for lop in &ntl.ops {
+                if matches!(lop.op, OpCode::BlackBox(_)) {
+                    continue;
+                }
+                let mut level = 0;
+                visit_wires(&lop.op, |sense, wire| {
+                    if let Some(reg) = wire.reg() {
+                        if sense.is_read() {
+                            level = level.max(depth.get(&reg).copied().unwrap_or(0));
+                        }
+                    }
+                });
+                visit_wires(&lop.op, |sense, wire| {
+                    if let Some(reg) = wire.reg() {
+                        if sense.is_write() && depth.get(&reg) != Some(&(level + 1)) {
+                            depth.insert(reg, level + 1);
+                            deepest = deepest.max(level + 1);
+                            changed = true;
+                        }
+                    }
+                });
+            }
+            if !changed {
+                return Ok(deepest);
+            }
+        }
+    }
+
+    #[test]
+    fn test_logic_depth_is_logarithmic() -> miette::Result<()> {
+        let narrow = logic_depth::<U8>()?;
+        let wide = logic_depth::<U64>()?;
+        let widest = logic_depth::<U128>()?;
+        assert!(narrow > 0);
+        // The depth does not depend on the width of the bitmap
+        // (beyond the log depth reductions inside each operation)
+        assert_eq!(narrow, wide, "8 bits: {narrow}, 64 bits: {wide}");
+        assert_eq!(wide, widest, "64 bits: {wide}, 128 bits: {widest}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotating_ffs_hdl() -> miette::Result<()> {
+        let uut = RotatingFfs::<U8>::default();
+        let input = [(0b1010_0000, 0), (0b1010_0000, 6), (0b0000_0001, 3), (0, 4)]
+            .into_iter()
+            .map(|(req, start)| In {
+                req: b8(req),
+                start: b8(start),
+            });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
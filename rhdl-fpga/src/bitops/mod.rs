@@ -0,0 +1,7 @@
+//! Bit manipulation cores
+//!
+//! Combinational building blocks that operate on bitmaps, such
+//! as finding the next set bit in a request vector.
+pub mod ffs;
+
+pub use ffs::RotatingFfs;
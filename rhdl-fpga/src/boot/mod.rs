@@ -0,0 +1,3 @@
+//! Cores for bringing up a board in an orderly fashion
+pub mod sequencer;
+pub mod uart_loader;
@@ -0,0 +1,348 @@
+//! Boot Sequencer
+//!
+//! Real boards need an orderly bring-up.  For example, you may
+//! need to wait for a PLL to lock, release resets in a specific
+//! order, wait between enabling power rails, and then finally
+//! enable the logic.  The [Sequencer] walks through an ordered
+//! list of [Step]s.  Each step has:
+//!
+//! - a wait condition, which is the corresponding bit in the
+//!   `ready` input (e.g., `pll_locked`),
+//! - a minimum dwell time, which is the minimum number of clock
+//!   cycles spent in the step before moving on,
+//! - an optional timeout, which is the maximum number of clock
+//!   cycles to wait for the condition to become true.
+//!
+//! When step `n` completes, the `enable[n]` output is asserted
+//! and remains asserted.  If the wait condition for a step is not
+//! met within the timeout, the sequencer stops, and reports the
+//! index of the failing step on the `error` output.  Asserting
+//! the `retry` input restarts the failing step (with its timers
+//! cleared).  Steps that have already completed are not repeated.
+//!
+//! The current step and the number of cycles spent in it are
+//! available on the output for debug.
+//!
+//! Note that the wait condition for a step is only checked while
+//! that step is active.  If a condition deasserts after its step
+//! completes, the sequencer does not react.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+Sequencer+-------+
+[bool;S]|                   | [bool;S]
+ +----->| ready      enable +------->
+  bool  |                   | bool
+ +----->| retry        done +------->
+        |                   | ?b8
+        |             error +------->
+        |                   | b8
+        |              step +------->
+        |                   | b32
+        |           elapsed +------->
+        +-------------------+
+")]
+//!
+//!# Example
+//!
+//! A typical bring-up, where we wait for the PLL to lock
+//! (with a timeout), then release a reset after a delay, and
+//! then enable the logic.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::boot::sequencer::{Sequencer, Step};
+//!
+//! let uut = Sequencer::new([
+//!     Step { dwell: bits(0), timeout: Some(bits(1000)) },
+//!     Step { dwell: bits(100), timeout: None },
+//!     Step { dwell: bits(10), timeout: None },
+//! ]);
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// A single step in the boot sequence
+pub struct Step {
+    /// The minimum number of clock cycles spent in this
+    /// step before it completes.
+    pub dwell: b32,
+    /// The maximum number of clock cycles to wait for the
+    /// condition to become true, or `None` to wait forever.
+    pub timeout: Option<b32>,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum State {
+    #[default]
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The boot sequencer core.  Here `STEPS` is the number of
+/// steps in the sequence, which must be between 1 and 256.
+pub struct Sequencer<const STEPS: usize> {
+    steps: constant::Constant<[Step; STEPS]>,
+    last: constant::Constant<b8>,
+    state: dff::DFF<State>,
+    step: dff::DFF<b8>,
+    elapsed: dff::DFF<b32>,
+    enable: dff::DFF<[bool; STEPS]>,
+}
+
+impl<const STEPS: usize> Sequencer<STEPS> {
+    /// Create a new [Sequencer] for the given list of steps,
+    /// which are executed in order.
+    pub fn new(steps: [Step; STEPS]) -> Self {
+        assert!(
+            (1..=256).contains(&STEPS),
+            "The boot sequencer needs between 1 and 256 steps"
+        );
+        Self {
+            steps: constant::Constant::new(steps),
+            last: constant::Constant::new(bits(STEPS as u128 - 1)),
+            state: dff::DFF::default(),
+            step: dff::DFF::default(),
+            elapsed: dff::DFF::default(),
+            enable: dff::DFF::new([false; STEPS]),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [Sequencer]
+pub struct In<const STEPS: usize> {
+    /// The wait condition for each step
+    pub ready: [bool; STEPS],
+    /// Retry the failing step
+    pub retry: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [Sequencer]
+pub struct Out<const STEPS: usize> {
+    /// The enable bit for each completed step
+    pub enable: [bool; STEPS],
+    /// Set once all of the steps have completed
+    pub done: bool,
+    /// The index of the step that timed out, if any
+    pub error: Option<b8>,
+    /// The index of the current step
+    pub step: b8,
+    /// The number of clock cycles spent in the current step
+    pub elapsed: b32,
+}
+
+impl<const STEPS: usize> SynchronousIO for Sequencer<STEPS> {
+    type I = In<STEPS>;
+    type O = Out<STEPS>;
+    type Kernel = sequencer_kernel<STEPS>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn sequencer_kernel<const STEPS: usize>(
+    cr: ClockReset,
+    i: In<STEPS>,
+    q: Q<STEPS>,
+) -> (Out<STEPS>, D<STEPS>) {
+    let mut d = D::<STEPS> {
+        steps: (),
+        last: (),
+        state: q.state,
+        step: q.step,
+        elapsed: q.elapsed,
+        enable: q.enable,
+    };
+    let mut o = Out::<STEPS> {
+        enable: q.enable,
+        done: false,
+        error: None,
+        step: q.step,
+        elapsed: q.elapsed,
+    };
+    // Count the time spent in the step, saturating at the maximum
+    let elapsed = if q.elapsed.all() {
+        q.elapsed
+    } else {
+        q.elapsed + 1
+    };
+    match q.state {
+        State::Running => {
+            let step = q.steps[q.step];
+            let ready = i.ready[q.step];
+            let timed_out = match step.timeout {
+                Some(timeout) => !ready && q.elapsed >= timeout,
+                None => false,
+            };
+            if ready && q.elapsed >= step.dwell {
+                d.enable[q.step] = true;
+                d.elapsed = bits(0);
+                if q.step == q.last {
+                    d.state = State::Done;
+                } else {
+                    d.step = q.step + 1;
+                }
+            } else if timed_out {
+                d.state = State::Failed;
+            } else {
+                d.elapsed = elapsed;
+            }
+        }
+        State::Done => {
+            o.done = true;
+        }
+        State::Failed => {
+            o.error = Some(q.step);
+            if i.retry {
+                d.state = State::Running;
+                d.elapsed = bits(0);
+            }
+        }
+    }
+    if cr.reset.any() {
+        d.state = State::Running;
+        d.step = bits(0);
+        d.elapsed = bits(0);
+        d.enable = [false; STEPS];
+        o.enable = [false; STEPS];
+        o.done = false;
+        o.error = None;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_steps() -> [Step; 3] {
+        [
+            // Wait for the PLL to lock
+            Step {
+                dwell: bits(0),
+                timeout: Some(bits(20)),
+            },
+            // Give the power rail some time to settle
+            Step {
+                dwell: bits(10),
+                timeout: None,
+            },
+            // Release the reset for the logic
+            Step {
+                dwell: bits(5),
+                timeout: Some(bits(20)),
+            },
+        ]
+    }
+
+    fn drive(
+        inputs: impl Fn(usize) -> In<3> + Clone,
+        len: usize,
+    ) -> impl Iterator<Item = TimedSample<(ClockReset, In<3>)>> + Clone {
+        (0..len).map(inputs).with_reset(1).clock_pos_edge(100)
+    }
+
+    fn run(
+        inputs: impl Fn(usize) -> In<3> + Clone,
+        len: usize,
+    ) -> miette::Result<Vec<Out<3>>> {
+        let uut = Sequencer::new(test_steps());
+        Ok(uut
+            .run(drive(inputs, len))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // The PLL locks at cycle 5, and the other conditions are
+    // always true.
+    fn normal(n: usize) -> In<3> {
+        In {
+            ready: [n >= 5, true, true],
+            retry: false,
+        }
+    }
+
+    #[test]
+    fn test_normal_bringup() -> miette::Result<()> {
+        let output = run(normal, 50)?;
+        // The enables must come on in order, and stay on
+        let first_on = |n: usize| output.iter().position(|o| o.enable[n]).unwrap();
+        assert!(first_on(0) < first_on(1));
+        assert!(first_on(1) < first_on(2));
+        for n in 0..3 {
+            assert!(output[first_on(n)..].iter().all(|o| o.enable[n]));
+        }
+        // The dwell times must be respected
+        assert!(first_on(1) - first_on(0) > 10);
+        assert!(first_on(2) - first_on(1) > 5);
+        // The sequence should complete with no errors
+        assert!(output.last().unwrap().done);
+        assert!(output.iter().all(|o| o.error.is_none()));
+        // The debug outputs track the sequence
+        assert_eq!(output[first_on(0)].step, b8(1));
+        assert_eq!(output[first_on(0)].elapsed, b32(0));
+        assert_eq!(output[first_on(0) + 4].elapsed, b32(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_times_out() -> miette::Result<()> {
+        // The last step never becomes ready
+        let output = run(
+            |n| In {
+                ready: [n >= 5, true, false],
+                retry: false,
+            },
+            100,
+        )?;
+        let last = output.last().unwrap();
+        assert_eq!(last.error, Some(b8(2)));
+        assert_eq!(last.enable, [true, true, false]);
+        assert!(!last.done);
+        // The failure is reported once the timeout expires
+        let failed = output.iter().position(|o| o.error.is_some()).unwrap();
+        let entered = output.iter().position(|o| o.step == b8(2)).unwrap();
+        assert_eq!(failed - entered, 21);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_after_condition_asserts() -> miette::Result<()> {
+        // The PLL takes too long to lock, so the first step fails.  It
+        // eventually locks, and a retry lets the sequence complete.
+        let output = run(
+            |n| In {
+                ready: [n >= 40, true, true],
+                retry: n == 50,
+            },
+            100,
+        )?;
+        assert!(output[30..45].iter().all(|o| o.error == Some(b8(0))));
+        assert!(output[30..45].iter().all(|o| o.enable == [false; 3]));
+        let last = output.last().unwrap();
+        assert!(last.done);
+        assert!(last.error.is_none());
+        assert_eq!(last.enable, [true; 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequencer_hdl() -> miette::Result<()> {
+        let uut = Sequencer::new(test_steps());
+        let tb = uut
+            .run(drive(normal, 50))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
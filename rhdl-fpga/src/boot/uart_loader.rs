@@ -0,0 +1,505 @@
+//! Serial Boot Loader
+//!
+//! During bring-up, it is often handy to load a RAM image over the
+//! serial port before releasing a soft system from reset.  The
+//! [UartLoader] takes the bytes received by a UART (or any other byte
+//! source), parses a small framed protocol, and writes the payload into
+//! a RAM through a Wishbone master port (for example, to a
+//! [RamSlave](crate::wishbone::RamSlave)).
+//!
+//! A frame consists of (with all multi-byte fields little endian):
+//!
+//! - the 4 byte magic number [MAGIC] (the ASCII string `RHDL`),
+//! - the length of the payload in 32 bit words (2 bytes),
+//! - the word offset in the RAM at which to write the payload (2 bytes),
+//! - the payload,
+//! - the CRC-32 (see [crc32](crate::crc::crc32)) of the length, offset
+//!   and payload (4 bytes).
+//!
+//! Bytes received outside of a frame are ignored, so the loader will
+//! synchronize to the next magic number.  Once a frame is complete
+//! (and all of the words have been written), the loader checks the
+//! CRC, and asserts `load_ok` (or reports a [LoadError] on
+//! `load_error`).  The result is held until the start of the next
+//! frame, so it can be used as the `ready` condition of a step in
+//! the [Sequencer](super::sequencer::Sequencer).  A retry simply
+//! sends the frame again.
+//!
+//! Each word is written with a classic Wishbone cycle.  The bytes
+//! arriving from a UART cannot be stalled, so the write of each word
+//! must complete before the next word is received.  If it does not,
+//! the frame fails with [LoadError::Overrun].  Note that the RAM is
+//! written as the payload arrives, and so the RAM contents cannot be
+//! trusted after a failed load.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+UartLoader+-------+
+  ?b8   |                    | ToSlave
+ +----->| data        bus    +------->
+FromSlv |                    | bool
+ +----->| bus        load_ok +------->
+        |                    | ?LoadError
+        |         load_error +------->
+        |                    | bool
+        |               busy +------->
+        +--------------------+
+")]
+//!
+//!# Example
+//!
+//! A loader for a RAM of 1024 words.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::boot::uart_loader::UartLoader;
+//!
+//! let uut = UartLoader::<U10>::default();
+//!```
+use rhdl::{
+    core::report::{Description, ParamValue},
+    prelude::*,
+};
+
+use crate::{
+    core::{constant, dff, slice::lsbs},
+    crc::crc32::{crc32_update, CRC32_INIT},
+    wishbone::types::{burst_types, cycle_types, FromSlave, ToSlave},
+};
+
+/// The magic number that starts a frame (`RHDL` in ASCII,
+/// sent as the bytes `R`, `H`, `D`, `L`)
+pub const MAGIC: b32 = bits(0x4C44_4852);
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The reason a load failed
+pub enum LoadError {
+    /// The CRC of the frame did not match
+    #[default]
+    Crc,
+    /// The payload does not fit in the RAM
+    Range,
+    /// A word arrived before the previous one was written
+    Overrun,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum State {
+    #[default]
+    Hunt,
+    Header,
+    Payload,
+    Crc,
+    Check,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum Status {
+    #[default]
+    Idle,
+    Busy,
+    Ok,
+    Failed(LoadError),
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The serial boot loader
+///
+///   `A` is the number of bits in the (word) address of the RAM
+pub struct UartLoader<A: BitWidth> {
+    size: constant::Constant<b32>,
+    state: dff::DFF<State>,
+    status: dff::DFF<Status>,
+    shift: dff::DFF<b32>,
+    count: dff::DFF<b2>,
+    len: dff::DFF<b16>,
+    adr: dff::DFF<Bits<A>>,
+    crc: dff::DFF<b32>,
+    write: dff::DFF<Option<(Bits<A>, b32)>>,
+}
+
+impl<A: BitWidth> Default for UartLoader<A> {
+    fn default() -> Self {
+        Self {
+            size: constant::Constant::new(bits(1 << A::BITS)),
+            state: dff::DFF::default(),
+            status: dff::DFF::default(),
+            shift: dff::DFF::default(),
+            count: dff::DFF::default(),
+            len: dff::DFF::default(),
+            adr: dff::DFF::default(),
+            crc: dff::DFF::new(CRC32_INIT),
+            write: dff::DFF::new(None),
+        }
+    }
+}
+
+impl<A: BitWidth> Describe for UartLoader<A> {
+    fn describe(&self) -> Description {
+        Description::new("UartLoader")
+            .param("address_bits", A::BITS)
+            .param("magic", ParamValue::Hex(MAGIC.raw() as u64))
+            .param("crc", "CRC-32")
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [UartLoader]
+pub struct In {
+    /// The byte received in this cycle (if any)
+    pub data: Option<b8>,
+    /// The bus signals from the RAM
+    pub bus: FromSlave,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [UartLoader]
+pub struct Out<A: BitWidth> {
+    /// The bus signals to the RAM
+    pub bus: ToSlave<A>,
+    /// The last frame was loaded successfully
+    pub load_ok: bool,
+    /// The last frame failed to load
+    pub load_error: Option<LoadError>,
+    /// A frame is being loaded
+    pub busy: bool,
+}
+
+impl<A: BitWidth> SynchronousIO for UartLoader<A> {
+    type I = In;
+    type O = Out<A>;
+    type Kernel = uart_loader_kernel<A>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn uart_loader_kernel<A: BitWidth>(cr: ClockReset, i: In, q: Q<A>) -> (Out<A>, D<A>) {
+    let mut d = D::<A>::dont_care();
+    d.state = q.state;
+    d.status = q.status;
+    d.shift = q.shift;
+    d.count = q.count;
+    d.len = q.len;
+    d.adr = q.adr;
+    d.crc = q.crc;
+    d.write = q.write;
+    // Each word is written with a classic cycle
+    let mut bus = ToSlave::<A> {
+        cyc: false,
+        stb: false,
+        we: true,
+        adr: bits(0),
+        dat: bits(0),
+        sel: bits(0xF),
+        cti: cycle_types::CLASSIC,
+        bte: burst_types::LINEAR,
+    };
+    let mut pending = false;
+    if let Some((adr, dat)) = q.write {
+        pending = true;
+        bus.cyc = true;
+        bus.stb = true;
+        bus.adr = adr;
+        bus.dat = dat;
+        if i.bus.ack {
+            d.write = None;
+        }
+    }
+    // Once the last word is written, check the CRC
+    if q.state == State::Check && !pending {
+        d.status = if q.shift == !q.crc {
+            Status::Ok
+        } else {
+            Status::Failed(LoadError::Crc)
+        };
+        d.state = State::Hunt;
+    }
+    if let Some(byte) = i.data {
+        // The bytes arrive LSB first, so shift them in at the top
+        let top: b32 = byte.resize();
+        let shift = (q.shift >> 8) | (top << 24);
+        let last = q.count == 3;
+        d.shift = shift;
+        d.count = q.count + 1;
+        d.crc = crc32_update(q.crc, byte);
+        match q.state {
+            State::Header => {
+                if last {
+                    let len = lsbs::<U16, U32>(shift);
+                    let offset = lsbs::<U16, U32>(shift >> 16);
+                    let end: b32 = offset.resize::<U32>() + len.resize::<U32>();
+                    d.len = len;
+                    d.adr = offset.resize();
+                    if end > q.size {
+                        d.status = Status::Failed(LoadError::Range);
+                        d.state = State::Hunt;
+                    } else if len == 0 {
+                        d.state = State::Crc;
+                    } else {
+                        d.state = State::Payload;
+                    }
+                }
+            }
+            State::Payload => {
+                if last {
+                    if pending && !i.bus.ack {
+                        d.status = Status::Failed(LoadError::Overrun);
+                        d.state = State::Hunt;
+                    } else {
+                        d.write = Some((q.adr, shift));
+                        d.adr = q.adr + 1;
+                        d.len = q.len - 1;
+                        if q.len == 1 {
+                            d.state = State::Crc;
+                        }
+                    }
+                }
+            }
+            State::Crc => {
+                // The received CRC is left in the shift register
+                d.crc = q.crc;
+                if last {
+                    d.state = State::Check;
+                }
+            }
+            _ => {
+                // Hunt for the magic number
+                if shift == MAGIC {
+                    d.state = State::Header;
+                    d.status = Status::Busy;
+                    d.count = bits(0);
+                    d.crc = CRC32_INIT;
+                }
+            }
+        }
+    }
+    let load_error = match q.status {
+        Status::Failed(e) => Some(e),
+        _ => None,
+    };
+    let o = Out::<A> {
+        bus,
+        load_ok: q.status == Status::Ok,
+        load_error,
+        busy: q.status == Status::Busy,
+    };
+    if cr.reset.any() {
+        d.state = State::Hunt;
+        d.status = Status::Idle;
+        d.write = None;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crc::crc32::crc32, wishbone::RamSlave};
+
+    #[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+    struct Fixture {
+        loader: UartLoader<U8>,
+        ram: RamSlave<U8>,
+    }
+
+    #[derive(PartialEq, Debug, Digital)]
+    struct FixtureIn {
+        data: Option<b8>,
+        peek: b8,
+    }
+
+    #[derive(PartialEq, Debug, Digital)]
+    struct FixtureOut {
+        load_ok: bool,
+        load_error: Option<LoadError>,
+        busy: bool,
+        ack: bool,
+        dat: b32,
+    }
+
+    impl SynchronousIO for Fixture {
+        type I = FixtureIn;
+        type O = FixtureOut;
+        type Kernel = fixture_kernel;
+    }
+
+    // The RAM is read back at the `peek` address whenever the loader
+    // is not using the bus
+    #[kernel]
+    fn fixture_kernel(_cr: ClockReset, i: FixtureIn, q: Q) -> (FixtureOut, D) {
+        let mut d = D::dont_care();
+        d.loader.data = i.data;
+        d.loader.bus = q.ram.bus;
+        d.ram = q.loader.bus;
+        if !q.loader.bus.cyc {
+            d.ram.cyc = true;
+            d.ram.stb = true;
+            d.ram.we = false;
+            d.ram.adr = i.peek;
+        }
+        let o = FixtureOut {
+            load_ok: q.loader.load_ok,
+            load_error: q.loader.load_error,
+            busy: q.loader.busy,
+            ack: q.ram.bus.ack,
+            dat: q.ram.bus.dat,
+        };
+        (o, d)
+    }
+
+    fn frame(offset: u16, words: &[u32]) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend((words.len() as u16).to_le_bytes());
+        body.extend(offset.to_le_bytes());
+        for w in words {
+            body.extend(w.to_le_bytes());
+        }
+        let crc = crc32(&body);
+        let mut frame = b"RHDL".to_vec();
+        frame.extend(body);
+        frame.extend(crc.to_le_bytes());
+        frame
+    }
+
+    // Send the bytes with `gap` idle clocks after each one (as a UART
+    // would), then read back the first 256 words of the RAM.
+    fn run(bytes: &[u8], gap: usize) -> miette::Result<(Vec<FixtureOut>, Vec<u32>)> {
+        let uut = Fixture::default();
+        let mut input = vec![];
+        for &b in bytes {
+            input.push(FixtureIn {
+                data: Some(b8(b as u128)),
+                peek: b8(0),
+            });
+            input.extend((0..gap).map(|_| FixtureIn {
+                data: None,
+                peek: b8(0),
+            }));
+        }
+        let load_len = input.len() + 10;
+        input.extend((0..10).map(|_| FixtureIn {
+            data: None,
+            peek: b8(0),
+        }));
+        // Hold each address long enough for a read to complete
+        input.extend((0..256 * 4).map(|n| FixtureIn {
+            data: None,
+            peek: b8((n / 4) as u128),
+        }));
+        let samples = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| (t.value.1, t.value.2))
+            .collect::<Vec<_>>();
+        let mut ram = vec![0; 256];
+        for (i, o) in &samples[load_len..] {
+            if o.ack {
+                ram[i.peek.raw() as usize] = o.dat.raw() as u32;
+            }
+        }
+        let output = samples.into_iter().take(load_len).map(|(_, o)| o).collect();
+        Ok((output, ram))
+    }
+
+    fn image() -> Vec<u32> {
+        (0..40)
+            .map(|n| 0x1234_5678_u32.wrapping_mul(n + 1))
+            .collect()
+    }
+
+    #[test]
+    fn test_load_image() -> miette::Result<()> {
+        let image = image();
+        // Some noise before the frame is ignored
+        let mut bytes = vec![0x00, b'R', b'H', 0x55];
+        bytes.extend(frame(100, &image));
+        let (output, ram) = run(&bytes, 3)?;
+        let last = output.last().unwrap();
+        assert!(last.load_ok);
+        assert_eq!(last.load_error, None);
+        assert!(output.iter().any(|o| o.busy));
+        assert!(output.iter().all(|o| !(o.busy && o.load_ok)));
+        assert_eq!(&ram[100..140], &image[..]);
+        assert!(ram[..100].iter().all(|&w| w == 0));
+        assert!(ram[140..].iter().all(|&w| w == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc_error_then_retry() -> miette::Result<()> {
+        let image = image();
+        let good = frame(8, &image);
+        let mut bad = good.clone();
+        bad[20] ^= 0x10;
+        let (output, _) = run(&bad, 3)?;
+        let last = output.last().unwrap();
+        assert!(!last.load_ok);
+        assert_eq!(last.load_error, Some(LoadError::Crc));
+        // Send the corrupted frame, and then retry
+        let mut bytes = bad;
+        bytes.extend(good);
+        let (output, ram) = run(&bytes, 3)?;
+        assert!(output.iter().any(|o| o.load_error == Some(LoadError::Crc)));
+        let last = output.last().unwrap();
+        assert!(last.load_ok);
+        assert_eq!(last.load_error, None);
+        assert_eq!(&ram[8..48], &image[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_error() -> miette::Result<()> {
+        let (output, ram) = run(&frame(250, &image()), 3)?;
+        assert_eq!(output.last().unwrap().load_error, Some(LoadError::Range));
+        assert!(ram.iter().all(|&w| w == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_overrun() -> miette::Result<()> {
+        // Back to back bytes are fine, since a write takes 2 clocks
+        let (output, _) = run(&frame(0, &image()), 0)?;
+        assert!(output.last().unwrap().load_ok);
+        // But a slow RAM cannot keep up
+        let uut = UartLoader::<U8>::default();
+        let input = frame(0, &image())
+            .into_iter()
+            .map(|b| In {
+                data: Some(b8(b as u128)),
+                bus: FromSlave {
+                    ack: false,
+                    dat: b32(0),
+                },
+            })
+            .collect::<Vec<_>>();
+        let last = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .last()
+            .unwrap();
+        assert_eq!(last.value.2.load_error, Some(LoadError::Overrun));
+        Ok(())
+    }
+
+    #[test]
+    fn test_uart_loader_hdl() -> miette::Result<()> {
+        let uut = Fixture::default();
+        let mut bytes = frame(0, &image()[..4]);
+        bytes[12] ^= 1;
+        bytes.extend(frame(2, &image()[..4]));
+        let input = bytes
+            .into_iter()
+            .flat_map(|b| [Some(b8(b as u128)), None].map(|data| FixtureIn { data, peek: b8(1) }));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+//! Metastability fault injection
+//!
+//!# Purpose
+//!
+//! As explained in the [synchronizer](super::synchronizer) docs, `rhdl`
+//! does not simulate metastability.  Every [Sync1Bit] captures its
+//! input on exactly the clock edge you would expect, which means that
+//! a CDC structure that depends on all of its bits arriving together
+//! will pass its tests in simulation, and then fail in hardware.
+//!
+//! Fault injection makes the simulation less forgiving.  When it is
+//! enabled, each time the input to a [Sync1Bit] changes, the first
+//! flop may (with the configured probability) keep its old value for
+//! one more destination clock.  This is the "resolves false" case in
+//! the synchronizer docs: the new value still arrives, but one clock
+//! late, and independently of every other synchronizer.  The choices
+//! are made by a seeded generator, so a failing run can be repeated.
+//!
+//! A correct CDC structure (like the [CrossCounter](super::cross_counter::CrossCounter),
+//! the [AsyncFIFO](crate::fifo::asynchronous::AsyncFIFO) or the
+//! [gals](crate::gals) handshakes) must work for any such delay.  A
+//! broken one (like a binary counter sent through a [Sync1Bit] per
+//! bit) will show torn values.
+//!
+//! Fault injection only changes the simulation.  The generated HDL is
+//! the same, so do not compare a test bench recorded with injection
+//! enabled against the HDL.
+//!
+//!# Enabling
+//!
+//! Injection can be enabled for a single synchronizer with
+//! [Sync1Bit::with_metastability], or for every synchronizer in a
+//! simulation with [inject].  The global setting is scoped to the
+//! closure (and the current thread), so tests that use it can still
+//! run in parallel.  Each synchronizer gets its own stream of random
+//! choices, derived from the seed and the order in which the
+//! synchronizers are initialized.  An instance setting takes
+//! precedence over the global one.
+//!
+//! Note that the state of the circuit is initialized when the
+//! simulation starts, so the whole simulation must be run inside
+//! the closure passed to [inject].
+//!
+//!# Example
+//!
+//! Run a [CrossCounter](super::cross_counter::CrossCounter) with
+//! fault injection on every bit.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::cdc::cross_counter::{CrossCounter, In};
+//! use rhdl_fpga::cdc::metastability::{inject, Metastability};
+//!
+//! let uut = CrossCounter::<Red, Blue, 8>::default();
+//! let red = std::iter::repeat_n(true, 50).with_reset(1).clock_pos_edge(100);
+//! let blue = std::iter::repeat(()).with_reset(1).clock_pos_edge(79);
+//! let input = merge(red, blue, |r: (ClockReset, bool), b: (ClockReset, ())| In {
+//!     incr: signal(r.1),
+//!     incr_cr: signal(r.0),
+//!     cr: signal(b.0),
+//! });
+//! let counts = inject(Metastability::new(42), || {
+//!     uut.run(input)
+//!         .unwrap()
+//!         .sample_at_pos_edge(|t| t.value.0.cr.val().clock)
+//!         .map(|t| t.value.1.count.val())
+//!         .collect::<Vec<_>>()
+//! });
+//! assert!(counts.windows(2).all(|w| w[0] <= w[1]));
+//!```
+use std::cell::Cell;
+
+use rhdl::prelude::*;
+
+#[cfg(doc)]
+use super::synchronizer::Sync1Bit;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The configuration of metastability fault injection
+pub struct Metastability {
+    /// The seed for the random choices
+    pub seed: u64,
+    /// The probability that a change on the input is captured
+    /// one destination clock late
+    pub probability: f64,
+}
+
+impl Metastability {
+    /// Inject faults with the given seed, and a probability of `0.25`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            probability: 0.25,
+        }
+    }
+    /// Set the probability that a change is captured late
+    pub fn probability(self, probability: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "Probability must be between 0 and 1"
+        );
+        Self {
+            probability,
+            ..self
+        }
+    }
+}
+
+thread_local! {
+    static GLOBAL: Cell<Option<Metastability>> = const { Cell::new(None) };
+    static INSTANCE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Enable fault injection for every [Sync1Bit] initialized
+/// while `f` runs (on this thread).  The previous setting is
+/// restored afterwards.
+pub fn inject<T>(config: Metastability, f: impl FnOnce() -> T) -> T {
+    let previous = GLOBAL.replace(Some(config));
+    let instance = INSTANCE.replace(0);
+    let result = f();
+    GLOBAL.set(previous);
+    INSTANCE.set(instance);
+    result
+}
+
+// Mix the seed, so that nearby seeds (and instances) give
+// unrelated streams.  This is the splitmix64 finalizer.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// The `(rng, threshold)` state for a synchronizer with the given
+/// instance setting.  A threshold of zero disables injection.
+pub(crate) fn seed(instance: Option<Metastability>) -> (b64, b64) {
+    let config = match instance {
+        Some(config) => Some((config, 0)),
+        None => GLOBAL.get().map(|config| {
+            let index = INSTANCE.get();
+            INSTANCE.set(index + 1);
+            (config, index)
+        }),
+    };
+    let Some((config, index)) = config else {
+        return (b64(0), b64(0));
+    };
+    let rng = mix(config.seed ^ mix(index)) | 1;
+    let threshold = (config.probability * u64::MAX as f64) as u64;
+    (b64(rng as u128), b64(threshold as u128))
+}
+
+/// Advance the generator, and return `true` if the capture
+/// should be delayed
+pub(crate) fn delay(state: &mut b64, threshold: b64) -> bool {
+    if threshold == 0 {
+        return false;
+    }
+    // xorshift64
+    let mut rng = state.raw() as u64;
+    rng ^= rng << 13;
+    rng ^= rng >> 7;
+    rng ^= rng << 17;
+    *state = b64(rng as u128);
+    *state < threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cdc::{cross_counter::CrossCounter, synchronizer::Sync1Bit},
+        core::dff,
+    };
+
+    // A deliberately broken CDC.  The binary count is sent across
+    // with one synchronizer per bit, so that a change of several bits
+    // can be seen half done in the read domain.
+    #[derive(Clone, Circuit, CircuitDQ)]
+    struct BinaryCrosser<W: Domain, R: Domain> {
+        counter: Adapter<dff::DFF<b8>, W>,
+        syncs: [Sync1Bit<W, R>; 8],
+    }
+
+    impl<W: Domain, R: Domain> Default for BinaryCrosser<W, R> {
+        fn default() -> Self {
+            Self {
+                counter: Adapter::new(dff::DFF::default()),
+                syncs: array_init::array_init(|_| Sync1Bit::default()),
+            }
+        }
+    }
+
+    #[derive(PartialEq, Debug, Digital, Timed)]
+    struct In<W: Domain, R: Domain> {
+        incr: Signal<bool, W>,
+        incr_cr: Signal<ClockReset, W>,
+        cr: Signal<ClockReset, R>,
+    }
+
+    impl<W: Domain, R: Domain> CircuitIO for BinaryCrosser<W, R> {
+        type I = In<W, R>;
+        type O = Signal<b8, R>;
+        type Kernel = binary_crosser_kernel<W, R>;
+    }
+
+    #[kernel]
+    fn binary_crosser_kernel<W: Domain, R: Domain>(
+        input: In<W, R>,
+        q: Q<W, R>,
+    ) -> (Signal<b8, R>, D<W, R>) {
+        let mut d = D::<W, R>::dont_care();
+        d.counter.clock_reset = input.incr_cr;
+        d.counter.input = signal(q.counter.val() + if input.incr.val() { 1 } else { 0 });
+        let count = q.counter.val();
+        for i in 0..8 {
+            d.syncs[i].data = signal((count & (1 << i)) != 0);
+            d.syncs[i].cr = input.cr;
+        }
+        let mut o = bits(0);
+        for i in 0..8 {
+            if q.syncs[i].val() {
+                o |= bits(1 << i);
+            }
+        }
+        (signal(o), d)
+    }
+
+    fn stream() -> impl Iterator<Item = TimedSample<In<Red, Blue>>> {
+        let red = std::iter::repeat_n(true, 200)
+            .with_reset(1)
+            .clock_pos_edge(100);
+        let blue = std::iter::repeat(()).with_reset(1).clock_pos_edge(79);
+        merge(red, blue, |r: (ClockReset, bool), b: (ClockReset, ())| In {
+            incr: signal(r.1),
+            incr_cr: signal(r.0),
+            cr: signal(b.0),
+        })
+    }
+
+    fn is_monotonic(counts: &[u128]) -> bool {
+        counts.windows(2).all(|w| w[0] <= w[1])
+    }
+
+    fn binary_counts() -> Vec<u128> {
+        BinaryCrosser::<Red, Blue>::default()
+            .run(stream())
+            .unwrap()
+            .sample_at_pos_edge(|t| t.value.0.cr.val().clock)
+            .map(|t| t.value.1.val().raw())
+            .collect()
+    }
+
+    fn gray_counts(uut: CrossCounter<Red, Blue, 8>) -> Vec<u128> {
+        let input = stream().map(|t| {
+            t.map(|x| crate::cdc::cross_counter::In {
+                incr: x.incr,
+                incr_cr: x.incr_cr,
+                cr: x.cr,
+            })
+        });
+        uut.run(input)
+            .unwrap()
+            .sample_at_pos_edge(|t| t.value.0.cr.val().clock)
+            .map(|t| t.value.1.count.val().raw())
+            .collect()
+    }
+
+    #[test]
+    fn test_binary_crossing_passes_without_injection() {
+        let counts = binary_counts();
+        assert!(is_monotonic(&counts));
+        assert!(*counts.last().unwrap() > 150);
+    }
+
+    #[test]
+    fn test_binary_crossing_fails_with_injection() {
+        for seed in 0..4 {
+            let counts = inject(Metastability::new(seed), binary_counts);
+            assert!(!is_monotonic(&counts), "seed {seed} did not tear the count");
+        }
+    }
+
+    #[test]
+    fn test_gray_crossing_passes_with_injection() {
+        for seed in 0..4 {
+            let counts = inject(Metastability::new(seed).probability(0.5), || {
+                gray_counts(CrossCounter::default())
+            });
+            assert!(is_monotonic(&counts));
+            assert!(*counts.last().unwrap() > 150);
+        }
+    }
+
+    #[test]
+    fn test_injection_is_repeatable() {
+        let a = inject(Metastability::new(7), binary_counts);
+        let b = inject(Metastability::new(7), binary_counts);
+        let c = inject(Metastability::new(8), binary_counts);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_injection_delays_by_one_clock() {
+        // With every change delayed, a single synchronizer takes
+        // one more destination clock to follow its input.
+        let plain = Sync1Bit::<Red, Blue>::default();
+        let late = Sync1Bit::<Red, Blue>::default()
+            .with_metastability(Metastability::new(1).probability(1.0));
+        let input = || {
+            let red = [false, true, true, true, true, true]
+                .into_iter()
+                .with_reset(1)
+                .clock_pos_edge(100);
+            let blue = std::iter::repeat(()).with_reset(1).clock_pos_edge(100);
+            merge(red, blue, |r: (ClockReset, bool), b: (ClockReset, ())| {
+                crate::cdc::synchronizer::In {
+                    data: signal(r.1),
+                    cr: signal(b.0),
+                }
+            })
+        };
+        let first_high = |uut: Sync1Bit<Red, Blue>| {
+            uut.run(input())
+                .unwrap()
+                .sample_at_pos_edge(|t| t.value.0.cr.val().clock)
+                .position(|t| t.value.1.val())
+                .unwrap()
+        };
+        assert_eq!(first_high(late), first_high(plain) + 1);
+    }
+}
@@ -2,5 +2,7 @@
 #![warn(missing_docs)]
 /// A Clock domain crossing binary counter
 pub mod cross_counter;
+/// Metastability fault injection for simulation
+pub mod metastability;
 /// A one-bit synchronizer
 pub mod synchronizer;
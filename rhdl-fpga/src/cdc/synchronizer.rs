@@ -122,7 +122,9 @@ clk   +----+    +----+    +----+
 //! Finally:  Note that `rhdl` does not simulate metastability.  Essentially
 //! for DFFs in `rhdl`, the value of δt is `0`.  So be extra cautious when
 //! assuming that your designs are safe if you are using synchronizers as
-//! raw components.
+//! raw components.  You can, however, ask the simulation to randomly
+//! delay the capture of a change by one clock, which will shake out
+//! many of these bugs.  See [metastability](super::metastability).
 //!
 //!# Connections
 //!
@@ -170,12 +172,26 @@ use rhdl::{
     prelude::*,
 };
 
+use super::metastability::{self, Metastability};
+
 /// A simple two-register synchronizer for crossing
 /// a single bit from the W domain to the R domain
 #[derive(PartialEq, Debug, Clone, Default)]
 pub struct Sync1Bit<W: Domain, R: Domain> {
     _w: std::marker::PhantomData<W>,
     _r: std::marker::PhantomData<R>,
+    metastability: Option<Metastability>,
+}
+
+impl<W: Domain, R: Domain> Sync1Bit<W, R> {
+    /// Enable metastability fault injection for this synchronizer
+    /// in simulation.  See [metastability](super::metastability).
+    pub fn with_metastability(self, config: Metastability) -> Self {
+        Self {
+            metastability: Some(config),
+            ..self
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Digital, Timed)]
@@ -206,18 +222,25 @@ pub struct S {
     reg1_current: bool,
     reg2_next: bool,
     reg2_current: bool,
+    rng: b64,
+    threshold: b64,
+    delayed: bool,
 }
 
 impl<W: Domain, R: Domain> Circuit for Sync1Bit<W, R> {
     type S = S;
 
     fn init(&self) -> Self::S {
+        let (rng, threshold) = metastability::seed(self.metastability);
         S {
             clock: Clock::dont_care(),
             reg1_next: false,
             reg1_current: false,
             reg2_next: false,
             reg2_current: false,
+            rng,
+            threshold,
+            delayed: false,
         }
     }
 
@@ -236,7 +259,16 @@ impl<W: Domain, R: Domain> Circuit for Sync1Bit<W, R> {
             state.reg2_next = state.reg1_current;
         }
         if clock.raw() && !state.clock.raw() {
-            state.reg1_current = state.reg1_next;
+            // With fault injection, a change may resolve to the old value,
+            // and so be captured one clock later (but no later than that)
+            let changed = state.reg1_next != state.reg1_current;
+            state.delayed = changed
+                && !state.delayed
+                && !reset.raw()
+                && metastability::delay(&mut state.rng, state.threshold);
+            if !state.delayed {
+                state.reg1_current = state.reg1_next;
+            }
             state.reg2_current = state.reg2_next;
         }
         if reset.raw() {
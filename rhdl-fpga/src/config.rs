@@ -0,0 +1,64 @@
+//! Validated core configurations
+//!
+//! Many cores take construction parameters that only make sense
+//! within some limits (a FIFO threshold beyond the depth of the FIFO,
+//! a PWM dead time longer than half the period, or two address decoder
+//! regions that overlap).  A mistake in these
+//! parameters does not fail to build.  Instead, it shows up as confusing
+//! behavior in simulation, or worse, on the board.
+//!
+//! Cores with such parameters follow a common pattern.  The parameters
+//! are collected in a `Config` struct in the module of the core, which
+//! implements [Validate].  Calling [Validate::validate] checks the
+//! constraints, and returns either a [Validated] configuration or the
+//! `ConfigError` of the module, which names the offending field and the
+//! constraint it broke (and renders as a [miette] diagnostic).  The
+//! constructor of the core only accepts the [Validated] form, so a core
+//! can not be built from an unchecked configuration.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::config::Validate;
+//! use rhdl_fpga::fifo::synchronous::{Config, ConfigError, SyncFIFO};
+//!
+//! // A FIFO of 15 entries that is almost full at 12
+//! let config = Config::<U4>::default().with_almost_full(12).validate().unwrap();
+//! let uut = SyncFIFO::<b8, U4>::new(config);
+//! // A threshold beyond the depth is rejected
+//! let err = Config::<U4>::default().with_almost_full(16).validate().unwrap_err();
+//! assert!(matches!(err, ConfigError::AlmostFullTooDeep { .. }));
+//!```
+
+/// A configuration with constraints that can be checked
+pub trait Validate: Sized {
+    /// The error describing which constraint was broken
+    type Error: miette::Diagnostic;
+    /// Check the constraints on the configuration
+    fn check(&self) -> Result<(), Self::Error>;
+    /// Check the constraints, and wrap the configuration as [Validated]
+    fn validate(self) -> Result<Validated<Self>, Self::Error> {
+        self.check()?;
+        Ok(Validated(self))
+    }
+}
+
+/// A configuration that has passed its checks.  This can only
+/// be built through [Validate::validate].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Validated<C>(C);
+
+impl<C> Validated<C> {
+    /// The checked configuration
+    pub fn into_inner(self) -> C {
+        self.0
+    }
+}
+
+impl<C> std::ops::Deref for Validated<C> {
+    type Target = C;
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
@@ -0,0 +1,175 @@
+//! Protocol conformance reports
+//!
+//! Functional tests show that a core moves the right bits.  A conformance
+//! report shows that it does so with the right timing.  The report is built
+//! from a simulation run, by measuring the waveforms on the pins of the core
+//! (with the [WaveStats](wave::WaveStats) analyzer), and comparing each
+//! measurement against the [Target] for the configuration of the core.
+//!
+//! Each [Metric] records the target, the range of values measured, the worst
+//! case margin against the target, and whether it passed.  The whole
+//! [ConformanceReport] can be checked in a test, or rendered as a markdown
+//! table to be kept with the design.
+//!
+//! All times are in clocks of the core.
+//!
+//! The protocols covered are
+//!
+//! - SPI style clock and data links ([spi]).
+use std::fmt::Write;
+
+pub mod spi;
+pub mod wave;
+
+/// The requirement on a measured value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// Every measurement must equal the value
+    Exact(u64),
+    /// Every measurement must be at least the value
+    AtLeast(u64),
+    /// Every measurement must be at most the value
+    AtMost(u64),
+    /// Every measurement must lie in the (inclusive) range
+    Range(u64, u64),
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Exact(x) => write!(f, "= {x}"),
+            Target::AtLeast(x) => write!(f, ">= {x}"),
+            Target::AtMost(x) => write!(f, "<= {x}"),
+            Target::Range(lo, hi) => write!(f, "{lo}..={hi}"),
+        }
+    }
+}
+
+impl Target {
+    // The worst case margin of the measurements in `min..=max`.
+    // Negative margins are violations.
+    fn margin(&self, min: u64, max: u64) -> i64 {
+        let (min, max) = (min as i64, max as i64);
+        match *self {
+            Target::Exact(x) => -(min - x as i64).abs().max((max - x as i64).abs()),
+            Target::AtLeast(x) => min - x as i64,
+            Target::AtMost(x) => x as i64 - max,
+            Target::Range(lo, hi) => (min - lo as i64).min(hi as i64 - max),
+        }
+    }
+}
+
+/// A single measurement in a [ConformanceReport]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    /// The name of the measurement
+    pub name: String,
+    /// The requirement on the measurement
+    pub target: Target,
+    /// The number of times the quantity was measured
+    pub count: usize,
+    /// The smallest value measured
+    pub min: u64,
+    /// The largest value measured
+    pub max: u64,
+}
+
+impl Metric {
+    /// Check a set of measurements against the target
+    pub fn new(name: &str, target: Target, values: impl IntoIterator<Item = u64>) -> Self {
+        let summary = wave::Summary::of(values);
+        Self {
+            name: name.into(),
+            target,
+            count: summary.map(|s| s.count).unwrap_or_default(),
+            min: summary.map(|s| s.min).unwrap_or_default(),
+            max: summary.map(|s| s.max).unwrap_or_default(),
+        }
+    }
+    /// The worst case margin against the target (negative for
+    /// a violation), or `None` if nothing was measured
+    pub fn margin(&self) -> Option<i64> {
+        (self.count > 0).then(|| self.target.margin(self.min, self.max))
+    }
+    /// The metric passes if it was measured, and met its target
+    pub fn passed(&self) -> bool {
+        self.margin().is_some_and(|m| m >= 0)
+    }
+}
+
+/// The result of checking a simulation run against a protocol
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConformanceReport {
+    /// The protocol checked
+    pub protocol: String,
+    /// The measurements
+    pub metrics: Vec<Metric>,
+}
+
+impl ConformanceReport {
+    /// True if every metric passed
+    pub fn passed(&self) -> bool {
+        self.metrics.iter().all(Metric::passed)
+    }
+    /// The metrics that failed
+    pub fn failures(&self) -> impl Iterator<Item = &Metric> {
+        self.metrics.iter().filter(|m| !m.passed())
+    }
+    /// Look up a metric by name
+    pub fn get(&self, name: &str) -> Option<&Metric> {
+        self.metrics.iter().find(|m| m.name == name)
+    }
+    /// The report as a markdown table
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "## {} conformance\n", self.protocol);
+        let _ = writeln!(
+            md,
+            "| Metric | Target | Measured | Count | Margin | Result |"
+        );
+        let _ = writeln!(
+            md,
+            "|--------|--------|----------|-------|--------|--------|"
+        );
+        for m in &self.metrics {
+            let (measured, margin) = match m.margin() {
+                Some(margin) if m.min == m.max => (m.min.to_string(), margin.to_string()),
+                Some(margin) => (format!("{}..={}", m.min, m.max), margin.to_string()),
+                None => ("-".into(), "-".into()),
+            };
+            let result = if m.passed() { "pass" } else { "FAIL" };
+            let _ = writeln!(
+                md,
+                "| {} | {} | {} | {} | {} | {} |",
+                m.name, m.target, measured, m.count, margin, result
+            );
+        }
+        md
+    }
+}
+
+impl std::fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_markdown())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_margins() {
+        let m = |target, values: &[u64]| Metric::new("x", target, values.iter().copied());
+        assert_eq!(m(Target::Exact(4), &[4, 4]).margin(), Some(0));
+        assert_eq!(m(Target::Exact(4), &[3, 6]).margin(), Some(-2));
+        assert_eq!(m(Target::AtLeast(2), &[5, 3]).margin(), Some(1));
+        assert_eq!(m(Target::AtMost(2), &[1, 3]).margin(), Some(-1));
+        assert_eq!(m(Target::Range(2, 8), &[3, 6]).margin(), Some(1));
+        assert_eq!(m(Target::Range(2, 8), &[3, 9]).margin(), Some(-1));
+        // A metric that was never measured does not pass
+        let missing = m(Target::AtLeast(0), &[]);
+        assert_eq!(missing.margin(), None);
+        assert!(!missing.passed());
+    }
+}
@@ -0,0 +1,292 @@
+//! SPI Conformance
+//!
+//! Checks the timing of an SPI style link, with a clock (`sclk`), a data
+//! line driven by the core (`mosi`), and an optional active low chip select
+//! (`cs_n`).  The data is assumed to be sampled on the rising edge of the
+//! clock (SPI mode 0), which is also the case for the
+//! [Apa102Driver](crate::led::apa102::Apa102Driver).
+//!
+//! A low phase of the clock at least [SpiTargets::idle] clocks long
+//! separates two frames.  The measurements are
+//!
+//! - `sclk high` and `sclk low`: the widths of the clock pulses within a frame,
+//! - `sclk period`: the time between rising edges within a frame,
+//! - `mosi setup`: the time the data is stable before each rising edge,
+//! - `mosi hold`: the time the data is stable after each rising edge,
+//! - `frame gap`: the idle time between frames,
+//! - `cs lead` and `cs lag` (if there is a chip select): the time from
+//!   the assertion of `cs_n` to the first rising edge, and from the last
+//!   edge of the clock to the release of `cs_n`.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl_fpga::conformance::{spi::{SpiPins, SpiTargets}, Target};
+//!
+//! // Two bits, with a half period of 2 clocks
+//! let pins = [(0, 1), (0, 1), (1, 1), (1, 1), (0, 0), (0, 0), (1, 0), (1, 0), (0, 0)]
+//!     .map(|(sclk, mosi)| SpiPins { sclk: sclk != 0, mosi: mosi != 0, cs_n: None });
+//! let report = SpiTargets::new(2, 8).check(&pins);
+//! assert_eq!(report.get("sclk period").unwrap().min, 4);
+//! assert_eq!(report.get("mosi setup").unwrap().target, Target::AtLeast(1));
+//!```
+use super::{wave::WaveStats, ConformanceReport, Metric, Target};
+
+/// The pins of an SPI link, sampled once per clock
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpiPins {
+    /// The serial clock
+    pub sclk: bool,
+    /// The data driven by the core
+    pub mosi: bool,
+    /// The (active low) chip select, if the link has one
+    pub cs_n: Option<bool>,
+}
+
+/// The timing targets for an SPI link
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpiTargets {
+    /// The width of each high pulse of the clock
+    pub sclk_high: Target,
+    /// The width of each low pulse of the clock, within a frame
+    pub sclk_low: Target,
+    /// The time between rising edges of the clock, within a frame
+    pub period: Target,
+    /// The time the data is stable before a rising edge
+    pub setup: Target,
+    /// The time the data is stable after a rising edge
+    pub hold: Target,
+    /// The idle time between frames
+    pub frame_gap: Target,
+    /// The time from selecting the device to the first rising edge
+    pub cs_lead: Target,
+    /// The time from the last clock edge to deselecting the device
+    pub cs_lag: Target,
+    /// A low phase of the clock at least this long ends a frame
+    pub idle: usize,
+}
+
+impl SpiTargets {
+    /// The targets for a clock with each half lasting exactly
+    /// `half_period` clocks, and frames separated by at least
+    /// `idle` clocks.  The data and chip select only need one
+    /// clock of setup and hold.
+    pub fn new(half_period: u64, idle: usize) -> Self {
+        Self {
+            sclk_high: Target::Exact(half_period),
+            sclk_low: Target::Exact(half_period),
+            period: Target::Exact(2 * half_period),
+            setup: Target::AtLeast(1),
+            hold: Target::AtLeast(1),
+            frame_gap: Target::AtLeast(idle as u64),
+            cs_lead: Target::AtLeast(1),
+            cs_lag: Target::AtLeast(1),
+            idle,
+        }
+    }
+    /// Measure the link, and compare the measurements to the targets
+    pub fn check(&self, pins: &[SpiPins]) -> ConformanceReport {
+        let sclk = pins.iter().map(|p| p.sclk).collect::<Vec<_>>();
+        let mosi = pins.iter().map(|p| p.mosi).collect::<Vec<_>>();
+        let stats = WaveStats::new(&sclk);
+        let mut low = vec![];
+        let mut gaps = vec![];
+        let mut periods = vec![];
+        let mut last_rise = None;
+        for run in stats.complete_runs() {
+            if run.level {
+                if let Some(rise) = last_rise {
+                    periods.push((run.start - rise) as u64);
+                }
+                last_rise = Some(run.start);
+            } else if run.len >= self.idle {
+                gaps.push(run.len as u64);
+                last_rise = None;
+            } else {
+                low.push(run.len as u64);
+            }
+        }
+        // The indices at which the data changes
+        let changes = (1..mosi.len())
+            .filter(|&n| mosi[n] != mosi[n - 1])
+            .collect::<Vec<_>>();
+        let rising = stats.rising_edges();
+        let setup = rising.iter().map(|&edge| {
+            let since = changes.partition_point(|&c| c <= edge);
+            let last = since.checked_sub(1).map(|ndx| changes[ndx]).unwrap_or(0);
+            (edge - last) as u64
+        });
+        let hold = rising.iter().filter_map(|&edge| {
+            let next = changes.partition_point(|&c| c <= edge);
+            changes.get(next).map(|&c| (c - edge) as u64)
+        });
+        let mut metrics = vec![
+            Metric::new("sclk high", self.sclk_high, stats.high_widths()),
+            Metric::new("sclk low", self.sclk_low, low),
+            Metric::new("sclk period", self.period, periods),
+            Metric::new("mosi setup", self.setup, setup),
+            Metric::new("mosi hold", self.hold, hold),
+            Metric::new("frame gap", self.frame_gap, gaps),
+        ];
+        if let Some(cs_n) = pins.iter().map(|p| p.cs_n).collect::<Option<Vec<_>>>() {
+            let cs = WaveStats::new(&cs_n);
+            let clock_edges = stats
+                .runs()
+                .iter()
+                .skip(1)
+                .map(|r| r.start)
+                .collect::<Vec<_>>();
+            let lead = cs.falling_edges().into_iter().filter_map(|select| {
+                let first = rising.partition_point(|&r| r < select);
+                rising.get(first).map(|&r| (r - select) as u64)
+            });
+            let lag = cs.rising_edges().into_iter().filter_map(|release| {
+                let last = clock_edges.partition_point(|&e| e < release);
+                last.checked_sub(1)
+                    .map(|ndx| (release - clock_edges[ndx]) as u64)
+            });
+            metrics.push(Metric::new("cs lead", self.cs_lead, lead));
+            metrics.push(Metric::new("cs lag", self.cs_lag, lag));
+        }
+        ConformanceReport {
+            protocol: "SPI".into(),
+            metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use rhdl::{core::sim::ResetOrData, prelude::*};
+
+    use super::*;
+    use crate::led::apa102::{Apa102Driver, Pixel};
+
+    const IDLE: usize = 16;
+
+    // Send two updates of a strip of 3 LEDs, with a pause
+    // between them, and collect the pins of the link
+    fn run(uut: &Apa102Driver<U4>) -> miette::Result<Vec<SpiPins>> {
+        let pixel = Pixel {
+            brightness: b5(17),
+            red: b8(0xA5),
+            green: b8(0x3C),
+            blue: b8(0x0F),
+        };
+        let mut sent = 0;
+        let mut clocks = 0;
+        let mut pause = 0;
+        Ok(uut
+            .run_fn(
+                |out| {
+                    clocks += 1;
+                    if clocks == 1 {
+                        return Some(ResetOrData::Reset);
+                    }
+                    if clocks > 2500 {
+                        return None;
+                    }
+                    if pause > 0 {
+                        pause -= 1;
+                        return Some(ResetOrData::Data(None));
+                    }
+                    let offer = (sent < 6).then_some(pixel);
+                    if out.ready.raw && offer.is_some() {
+                        sent += 1;
+                        if sent == 3 {
+                            pause = 500;
+                        }
+                    }
+                    Some(ResetOrData::Data(offer))
+                },
+                100,
+            )
+            .synchronous_sample()
+            .map(|t| SpiPins {
+                sclk: t.value.2.sclk,
+                mosi: t.value.2.sdo,
+                cs_n: None,
+            })
+            .collect())
+    }
+
+    // The driver loads the next word while the clock is low,
+    // which stretches the low phase between words by a clock
+    fn apa102_targets(half_period: u64) -> SpiTargets {
+        SpiTargets {
+            sclk_low: Target::Range(half_period, half_period + 1),
+            period: Target::Range(2 * half_period, 2 * half_period + 1),
+            ..SpiTargets::new(half_period, IDLE)
+        }
+    }
+
+    #[test]
+    fn test_apa102_conforms() -> miette::Result<()> {
+        let report = apa102_targets(2).check(&run(&Apa102Driver::new(3, 2))?);
+        assert!(report.passed(), "{report}");
+        // There is one pause between the two updates
+        assert_eq!(report.get("frame gap").unwrap().count, 1);
+        let expect = expect![[r#"
+            ## SPI conformance
+
+            | Metric | Target | Measured | Count | Margin | Result |
+            |--------|--------|----------|-------|--------|--------|
+            | sclk high | = 2 | 2 | 320 | 0 | pass |
+            | sclk low | 2..=3 | 2..=3 | 318 | 0 | pass |
+            | sclk period | 4..=5 | 4..=5 | 318 | 0 | pass |
+            | mosi setup | >= 1 | 2..=499 | 320 | 1 | pass |
+            | mosi hold | >= 1 | 2..=500 | 288 | 1 | pass |
+            | frame gap | >= 16 | 247 | 1 | 231 | pass |
+        "#]];
+        expect.assert_eq(&report.to_markdown());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mis_divided_clock_fails_period() -> miette::Result<()> {
+        // The core is built with a half period of 3 clocks, but
+        // checked against a target of 2
+        let report = apa102_targets(2).check(&run(&Apa102Driver::new(3, 3))?);
+        assert!(!report.passed());
+        let period = report.get("sclk period").unwrap();
+        assert!(!period.passed());
+        assert_eq!((period.min, period.max), (6, 7));
+        assert_eq!(period.margin(), Some(-2));
+        assert!(report
+            .to_markdown()
+            .contains("| sclk period | 4..=5 | 6..=7 |"));
+        // The data timing is still fine
+        assert!(report.get("mosi setup").unwrap().passed());
+        assert!(report.get("mosi hold").unwrap().passed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chip_select_framing() {
+        // Select, two clocks with a half period of 1, and release
+        let wave = [
+            (1, 0, 1),
+            (0, 0, 1),
+            (0, 0, 1),
+            (0, 1, 0),
+            (0, 0, 0),
+            (0, 1, 1),
+            (0, 0, 1),
+            (1, 0, 1),
+        ];
+        let pins = wave.map(|(cs_n, sclk, mosi)| SpiPins {
+            sclk: sclk != 0,
+            mosi: mosi != 0,
+            cs_n: Some(cs_n != 0),
+        });
+        let report = SpiTargets::new(1, 4).check(&pins);
+        let lead = report.get("cs lead").unwrap();
+        assert_eq!((lead.count, lead.min), (1, 2));
+        let lag = report.get("cs lag").unwrap();
+        assert_eq!((lag.count, lag.min), (1, 1));
+        // Without a chip select, there are no framing metrics
+        let pins = pins.map(|p| SpiPins { cs_n: None, ..p });
+        assert!(SpiTargets::new(1, 4).check(&pins).get("cs lead").is_none());
+    }
+}
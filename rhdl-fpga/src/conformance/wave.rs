@@ -0,0 +1,161 @@
+//! Waveform Statistics
+//!
+//! The [WaveStats] analyzer takes a single bit signal, sampled once per
+//! clock (as from a `synchronous_sample` of a run), and splits it into
+//! runs of constant level.  The widths of the high and low pulses, and
+//! the periods between rising edges, are then available as lists, or
+//! summarized as a [Summary].
+//!
+//! The first and last runs are cut off by the ends of the trace, and
+//! so their widths are not known.  They are not included in the pulse
+//! widths (but their edges are included in the edge lists).
+//!
+//!# Example
+//!
+//!```
+//! use rhdl_fpga::conformance::wave::WaveStats;
+//!
+//! let clk = [0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0].map(|x| x != 0);
+//! let stats = WaveStats::new(&clk);
+//! assert_eq!(stats.high_widths(), vec![3, 3]);
+//! assert_eq!(stats.low_widths(), vec![2]);
+//! assert_eq!(stats.periods(), vec![5]);
+//!```
+
+/// A run of samples with the same level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Run {
+    /// The level of the signal
+    pub level: bool,
+    /// The index of the first sample in the run
+    pub start: usize,
+    /// The number of samples in the run
+    pub len: usize,
+}
+
+/// The minimum, maximum and mean of a set of measurements
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    /// The number of measurements
+    pub count: usize,
+    /// The smallest measurement
+    pub min: u64,
+    /// The largest measurement
+    pub max: u64,
+    /// The mean of the measurements
+    pub mean: f64,
+}
+
+impl Summary {
+    /// Summarize a set of measurements, or `None` if there are none
+    pub fn of(values: impl IntoIterator<Item = u64>) -> Option<Self> {
+        let values = values.into_iter().collect::<Vec<_>>();
+        let count = values.len();
+        Some(Self {
+            count,
+            min: *values.iter().min()?,
+            max: *values.iter().max()?,
+            mean: values.iter().sum::<u64>() as f64 / count as f64,
+        })
+    }
+}
+
+/// Pulse and edge statistics for a single bit signal
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveStats {
+    runs: Vec<Run>,
+}
+
+impl WaveStats {
+    /// Analyze a signal with one sample per clock
+    pub fn new(samples: &[bool]) -> Self {
+        let mut runs: Vec<Run> = vec![];
+        for (ndx, &level) in samples.iter().enumerate() {
+            match runs.last_mut() {
+                Some(run) if run.level == level => run.len += 1,
+                _ => runs.push(Run {
+                    level,
+                    start: ndx,
+                    len: 1,
+                }),
+            }
+        }
+        Self { runs }
+    }
+    /// All of the runs, including the partial ones at the ends
+    pub fn runs(&self) -> &[Run] {
+        &self.runs
+    }
+    /// The runs that are bounded by an edge on both sides
+    pub fn complete_runs(&self) -> impl Iterator<Item = &Run> {
+        let n = self.runs.len();
+        self.runs.iter().take(n.saturating_sub(1)).skip(1)
+    }
+    /// The widths of the complete high pulses
+    pub fn high_widths(&self) -> Vec<u64> {
+        self.widths(true)
+    }
+    /// The widths of the complete low pulses
+    pub fn low_widths(&self) -> Vec<u64> {
+        self.widths(false)
+    }
+    fn widths(&self, level: bool) -> Vec<u64> {
+        self.complete_runs()
+            .filter(|r| r.level == level)
+            .map(|r| r.len as u64)
+            .collect()
+    }
+    /// The sample indices of the rising edges (the first high sample)
+    pub fn rising_edges(&self) -> Vec<usize> {
+        self.edges(true)
+    }
+    /// The sample indices of the falling edges (the first low sample)
+    pub fn falling_edges(&self) -> Vec<usize> {
+        self.edges(false)
+    }
+    fn edges(&self, level: bool) -> Vec<usize> {
+        self.runs
+            .iter()
+            .skip(1)
+            .filter(|r| r.level == level)
+            .map(|r| r.start)
+            .collect()
+    }
+    /// The number of samples between successive rising edges
+    pub fn periods(&self) -> Vec<u64> {
+        self.rising_edges()
+            .windows(2)
+            .map(|w| (w[1] - w[0]) as u64)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_runs_are_excluded() {
+        let wave = [1, 1, 0, 0, 0, 1, 0, 1, 1, 1].map(|x| x != 0);
+        let stats = WaveStats::new(&wave);
+        assert_eq!(stats.runs().len(), 5);
+        assert_eq!(stats.high_widths(), vec![1]);
+        assert_eq!(stats.low_widths(), vec![3, 1]);
+        assert_eq!(stats.rising_edges(), vec![5, 7]);
+        assert_eq!(stats.falling_edges(), vec![2, 6]);
+        assert_eq!(stats.periods(), vec![2]);
+        let summary = Summary::of(stats.low_widths()).unwrap();
+        assert_eq!((summary.count, summary.min, summary.max), (2, 1, 3));
+        assert_eq!(summary.mean, 2.0);
+        assert!(Summary::of(stats.periods().into_iter().skip(1)).is_none());
+    }
+
+    #[test]
+    fn test_constant_signal() {
+        let stats = WaveStats::new(&[false; 10]);
+        assert!(stats.high_widths().is_empty());
+        assert!(stats.low_widths().is_empty());
+        assert!(stats.rising_edges().is_empty());
+        assert!(WaveStats::new(&[]).runs().is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+//! Binary to BCD Converter
+//!
+//! Displaying a binary value (for example on a seven segment
+//! display) requires converting it into binary coded decimal
+//! (BCD), in which each decimal digit is held in 4 bits.  The
+//! [BinToBcd] core uses the double dabble algorithm.  The bits of
+//! the value are shifted into the BCD digits one at a time, MSB
+//! first.  Before each shift, any digit of 5 or more has 3 added
+//! to it, so that it carries into the next digit once doubled.
+//!
+//! The core can be constructed in one of two [Mode]s:
+//!
+//! - [Mode::Iterative] performs one shift per clock, so a conversion
+//!   takes `N` clocks.  A new value is accepted when the core is not
+//!   `busy`, and values presented while it is `busy` are ignored.
+//! - [Mode::Combinational] performs all of the shifts in a single
+//!   clock, and registers the result.  A new value can be presented
+//!   on every clock, and the result appears one clock later.  This
+//!   uses far more logic for wide values.
+//!
+//! In either case, the result is presented for a single clock.  The
+//! digits are ordered from least to most significant, so that
+//! `digits[0]` holds the units.  If the value does not fit in `DIGITS`
+//! decimal digits, the `overflow` flag is set, and the digits
+//! hold the value modulo `10^DIGITS`.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+BinToBcd+-----------+
+ ?bN    |                      | bool
+ +----->| start           busy +----->
+        |                      | ?Bcd
+        |               result +----->
+        +----------------------+
+")]
+//!
+//!# Example
+//!
+//! Converting a 16 bit value into 5 digits.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::convert::bcd::{BinToBcd, Mode};
+//!
+//! let uut = BinToBcd::<U16, 5>::new(Mode::Combinational);
+//! let input = [Some(b16(1234)), None];
+//! let result = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .find_map(|t| t.value.2.result)
+//!     .unwrap();
+//! assert_eq!(result.digits, [b4(4), b4(3), b4(2), b4(1), b4(0)]);
+//! assert!(!result.overflow);
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// How the conversion is carried out
+pub enum Mode {
+    #[default]
+    /// One bit per clock, taking `N` clocks
+    Iterative,
+    /// All bits in a single clock
+    Combinational,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The result of a conversion
+pub struct Bcd<const DIGITS: usize> {
+    /// The decimal digits, least significant first
+    pub digits: [b4; DIGITS],
+    /// Set if the value does not fit in the digits
+    pub overflow: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [BinToBcd] core
+pub struct Out<const DIGITS: usize> {
+    /// Set while an iterative conversion is in progress
+    pub busy: bool,
+    /// The result of the conversion, valid for one clock
+    pub result: Option<Bcd<DIGITS>>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The binary to BCD converter.  Here `N` is the width
+/// of the binary value (at most 128 bits), and `DIGITS`
+/// is the number of decimal digits in the result.
+pub struct BinToBcd<N: BitWidth, const DIGITS: usize> {
+    mode: constant::Constant<Mode>,
+    steps: constant::Constant<b8>,
+    value: dff::DFF<Bits<N>>,
+    digits: dff::DFF<[b4; DIGITS]>,
+    overflow: dff::DFF<bool>,
+    remaining: dff::DFF<b8>,
+    result: dff::DFF<Option<Bcd<DIGITS>>>,
+}
+
+impl<N: BitWidth, const DIGITS: usize> BinToBcd<N, DIGITS> {
+    /// Create a converter that operates in the given [Mode]
+    pub fn new(mode: Mode) -> Self {
+        assert!(DIGITS > 0, "The converter needs at least one digit");
+        Self {
+            mode: constant::Constant::new(mode),
+            steps: constant::Constant::new(bits(N::BITS as u128)),
+            value: dff::DFF::default(),
+            digits: dff::DFF::new([b4(0); DIGITS]),
+            overflow: dff::DFF::default(),
+            remaining: dff::DFF::default(),
+            result: dff::DFF::new(None),
+        }
+    }
+}
+
+impl<N: BitWidth, const DIGITS: usize> SynchronousIO for BinToBcd<N, DIGITS> {
+    type I = Option<Bits<N>>;
+    type O = Out<DIGITS>;
+    type Kernel = bin_to_bcd_kernel<N, DIGITS>;
+}
+
+#[kernel]
+#[allow(clippy::needless_range_loop)]
+/// A single step of the double dabble algorithm.  Shifts `bit`
+/// into the LSB of `digits`, and returns the new digits and the
+/// bit shifted out of the most significant digit.
+pub fn dabble<const DIGITS: usize>(digits: [b4; DIGITS], bit: bool) -> ([b4; DIGITS], bool) {
+    let mut next = digits;
+    let mut carry = bit;
+    for k in 0..DIGITS {
+        let mut digit = digits[k];
+        if digit >= 5 {
+            digit += 3;
+        }
+        let lsb: b4 = if carry { bits(1) } else { bits(0) };
+        next[k] = (digit << 1) | lsb;
+        carry = digit & 8 != 0;
+    }
+    (next, carry)
+}
+
+#[kernel]
+/// Convert a binary value into `DIGITS` BCD digits in a
+/// single (combinatorial) step.
+pub fn bin_to_bcd<N: BitWidth, const DIGITS: usize>(value: Bits<N>) -> Bcd<DIGITS> {
+    let mut digits = [b4(0); DIGITS];
+    let mut overflow = false;
+    for i in 0..N::BITS {
+        let bit = value & (1 << (N::BITS - 1 - i)) != 0;
+        let (next, carry) = dabble::<DIGITS>(digits, bit);
+        digits = next;
+        overflow |= carry;
+    }
+    Bcd::<DIGITS> { digits, overflow }
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn bin_to_bcd_kernel<N: BitWidth, const DIGITS: usize>(
+    cr: ClockReset,
+    i: Option<Bits<N>>,
+    q: Q<N, DIGITS>,
+) -> (Out<DIGITS>, D<N, DIGITS>) {
+    let mut d = D::<N, DIGITS>::dont_care();
+    d.value = q.value;
+    d.digits = q.digits;
+    d.overflow = q.overflow;
+    d.remaining = q.remaining;
+    d.result = None;
+    match q.mode {
+        Mode::Iterative => {
+            if q.remaining != 0 {
+                let bit = q.value & (1 << (N::BITS - 1)) != 0;
+                let (digits, carry) = dabble::<DIGITS>(q.digits, bit);
+                d.value = q.value << 1;
+                d.digits = digits;
+                d.overflow = q.overflow || carry;
+                d.remaining = q.remaining - 1;
+                if q.remaining == 1 {
+                    d.result = Some(Bcd::<DIGITS> {
+                        digits,
+                        overflow: d.overflow,
+                    });
+                }
+            } else if let Some(value) = i {
+                d.value = value;
+                d.digits = [b4(0); DIGITS];
+                d.overflow = false;
+                d.remaining = q.steps;
+            }
+        }
+        Mode::Combinational => {
+            if let Some(value) = i {
+                d.result = Some(bin_to_bcd::<N, DIGITS>(value));
+            }
+        }
+    }
+    let o = Out::<DIGITS> {
+        busy: q.remaining != 0,
+        result: q.result,
+    };
+    if cr.reset.any() {
+        d.remaining = bits(0);
+        d.result = None;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Present each value, and wait for the result
+    fn convert<N: BitWidth, const DIGITS: usize>(
+        mode: Mode,
+        values: &[u128],
+    ) -> miette::Result<Vec<Bcd<DIGITS>>> {
+        let uut = BinToBcd::<N, DIGITS>::new(mode);
+        let gap = match mode {
+            Mode::Iterative => N::BITS + 1,
+            Mode::Combinational => 0,
+        };
+        let input = values
+            .iter()
+            .flat_map(|v| std::iter::once(Some(bits(*v))).chain(std::iter::repeat_n(None, gap)))
+            .chain(std::iter::repeat_n(None, N::BITS + 2));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .filter_map(|t| t.value.2.result)
+            .collect())
+    }
+
+    fn expected<const DIGITS: usize>(value: u128) -> Bcd<DIGITS> {
+        let text = format!("{value:0DIGITS$}");
+        let mut digits = [b4(0); DIGITS];
+        for (digit, c) in digits.iter_mut().zip(text.chars().rev()) {
+            *digit = bits(c.to_digit(10).unwrap() as u128);
+        }
+        Bcd {
+            digits,
+            overflow: text.len() > DIGITS,
+        }
+    }
+
+    fn check<N: BitWidth, const DIGITS: usize>(values: &[u128]) -> miette::Result<()> {
+        for mode in [Mode::Iterative, Mode::Combinational] {
+            let results = convert::<N, DIGITS>(mode, values)?;
+            assert_eq!(results.len(), values.len());
+            for (value, result) in values.iter().zip(results) {
+                assert_eq!(result, expected::<DIGITS>(*value), "{value} in {mode:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn boundaries(max: u128) -> Vec<u128> {
+        let mut values = vec![0, 1, 5, 9, max];
+        let mut power = 10;
+        while power <= max {
+            values.extend([power - 1, power, power + 1]);
+            power *= 10;
+        }
+        values
+    }
+
+    #[test]
+    fn test_16_bit_values() -> miette::Result<()> {
+        let mut values = boundaries(0xFFFF);
+        values.extend([1234, 4999, 5000, 31415, 42000]);
+        check::<U16, 5>(&values)
+    }
+
+    #[test]
+    fn test_overflow() -> miette::Result<()> {
+        // Values of 10000 and above do not fit in 4 digits
+        let values = boundaries(0xFFFF);
+        check::<U16, 4>(&values)?;
+        check::<U8, 2>(&boundaries(0xFF))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_wide_values() -> miette::Result<()> {
+        let max = (1 << 40) - 1;
+        let mut values = boundaries(max);
+        values.push(123_456_789_012);
+        check::<U40, 13>(&values)
+    }
+
+    #[test]
+    fn test_iterative_ignores_start_while_busy() -> miette::Result<()> {
+        let uut = BinToBcd::<U8, 3>::new(Mode::Iterative);
+        let mut input = vec![None; 30];
+        input[0] = Some(b8(42));
+        input[3] = Some(b8(99));
+        input[12] = Some(b8(255));
+        let output = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let results = output.iter().filter_map(|o| o.result).collect::<Vec<_>>();
+        assert_eq!(results, [expected::<3>(42), expected::<3>(255)]);
+        // The reset is the first sample, and the core is busy for 8 clocks
+        assert_eq!(output.iter().filter(|o| o.busy).count(), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bin_to_bcd_hdl() -> miette::Result<()> {
+        for mode in [Mode::Iterative, Mode::Combinational] {
+            let uut = BinToBcd::<U8, 2>::new(mode);
+            let input = [0, 9, 10, 99, 100, 255, 37]
+                .into_iter()
+                .flat_map(|v| std::iter::once(Some(b8(v))).chain(std::iter::repeat_n(None, 9)));
+            let tb = uut
+                .run(input.with_reset(1).clock_pos_edge(100))?
+                .collect::<SynchronousTestBench<_, _>>();
+            let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+            let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+        }
+        Ok(())
+    }
+}
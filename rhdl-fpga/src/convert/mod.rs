@@ -0,0 +1,2 @@
+//! Conversion cores
+pub mod bcd;
@@ -0,0 +1,155 @@
+//! BCD Counter
+//!
+//! A decimal counter of `DIGITS` digits, made of a chain of
+//! [BcdDigit] cores.  The whole count advances by one on each clock
+//! when `enable` is high, and holds when it is low.  Each digit is
+//! enabled when `enable` is high, and all of the digits below it are at
+//! `9`, so that the digits roll over together, as they do on an odometer.
+//! `carry` is high on the clock on which the count rolls over from all
+//! nines to zero (so counters can be cascaded further).  On reset, all
+//! of the digits are cleared.
+//!
+//! The output holds the digits, least significant first (i.e., `digits[0]`
+//! is the units).
+//!
+//!# Example
+//!
+//! Counting to 12 with 2 digits.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::bcd::counter::BcdCounter;
+//!
+//! let uut = BcdCounter::<2>::default();
+//! let digits = uut
+//!     .run(std::iter::repeat_n(true, 13).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2
+//!     .digits;
+//! assert_eq!(digits, [b4(2), b4(1)]);
+//!```
+use rhdl::prelude::*;
+
+use super::digit::BcdDigit;
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [BcdCounter]
+pub struct Out<const DIGITS: usize> {
+    /// The digits of the count, least significant first
+    pub digits: [b4; DIGITS],
+    /// High on the clock the count rolls over from all nines to zero
+    pub carry: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The BCD counter core
+///   `DIGITS` is the number of decimal digits
+pub struct BcdCounter<const DIGITS: usize> {
+    digits: [BcdDigit; DIGITS],
+}
+
+impl<const DIGITS: usize> Default for BcdCounter<DIGITS> {
+    fn default() -> Self {
+        Self {
+            digits: core::array::from_fn(|_| BcdDigit::default()),
+        }
+    }
+}
+
+impl<const DIGITS: usize> SynchronousIO for BcdCounter<DIGITS> {
+    type I = bool;
+    type O = Out<DIGITS>;
+    type Kernel = bcd_counter_kernel<DIGITS>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn bcd_counter_kernel<const DIGITS: usize>(
+    _cr: ClockReset,
+    enable: bool,
+    q: Q<DIGITS>,
+) -> (Out<DIGITS>, D<DIGITS>) {
+    let mut d = D::<DIGITS>::dont_care();
+    let mut o = Out::<DIGITS>::dont_care();
+    // Each digit is enabled when all of the digits below it are at 9.
+    // This is the same as chaining the carries, but does not ripple
+    // through the digit cores.
+    let mut carry = enable;
+    for i in 0..DIGITS {
+        d.digits[i] = carry;
+        o.digits[i] = q.digits[i].digit;
+        carry = carry && q.digits[i].digit == 9;
+    }
+    o.carry = carry;
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(digits: &[b4]) -> u128 {
+        digits.iter().rev().fold(0, |acc, d| acc * 10 + d.raw())
+    }
+
+    #[test]
+    fn test_count_past_999() -> miette::Result<()> {
+        let uut = BcdCounter::<3>::default();
+        let input = (0..1200).map(|n| n % 11 != 5).collect::<Vec<_>>();
+        let output = uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        for (enable, o) in input.iter().zip(&output) {
+            assert!(o.digits.iter().all(|d| d.raw() < 10));
+            assert_eq!(decimal(&o.digits), count);
+            assert_eq!(o.carry, *enable && count == 999);
+            if *enable {
+                count = (count + 1) % 1000;
+            }
+        }
+        // The count went past 999 exactly once
+        assert_eq!(output.iter().filter(|o| o.carry).count(), 1);
+        let wrap = output.iter().position(|o| o.carry).unwrap();
+        assert_eq!(output[wrap].digits, [b4(9); 3]);
+        assert_eq!(output[wrap + 1].digits, [b4(0); 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_clears_digits() -> miette::Result<()> {
+        let uut = BcdCounter::<2>::default();
+        let input = std::iter::repeat_n(true, 15)
+            .with_reset(1)
+            .chain(std::iter::repeat_n(true, 2).with_reset(1))
+            .clock_pos_edge(100);
+        let counts = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| decimal(&t.value.2.digits))
+            .collect::<Vec<_>>();
+        assert_eq!(counts[15..], [14, 15, 0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bcd_counter_hdl() -> miette::Result<()> {
+        let uut = BcdCounter::<3>::default();
+        let input = (0..150).map(|n| n % 7 != 3);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+//! BCD Digit
+//!
+//! A decade counter, that counts from `0` to `9` in a [b4], and then
+//! wraps back to `0`.  The digit advances on each clock when `enable` is
+//! high, and holds when it is low.  `carry` is high on the clock on
+//! which the digit rolls over from `9` to `0`.  The carry is qualified
+//! by `enable`, so it can be used directly as the `enable` of the next
+//! digit up, without the next digit counting while this one sits at `9`.
+//! On reset, the digit is cleared.
+//!
+//!# Example
+//!
+//! Counting through a roll over.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::bcd::digit::BcdDigit;
+//!
+//! let uut = BcdDigit::default();
+//! let output = uut
+//!     .run(std::iter::repeat_n(true, 12).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| (t.value.2.digit.raw(), t.value.2.carry))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output[9..], [(9, true), (0, false), (1, false)]);
+//!```
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [BcdDigit]
+pub struct Out {
+    /// The current digit (`0` to `9`)
+    pub digit: b4,
+    /// High on the clock the digit rolls over from `9` to `0`
+    pub carry: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The BCD digit core
+pub struct BcdDigit {
+    digit: dff::DFF<b4>,
+}
+
+impl Default for BcdDigit {
+    fn default() -> Self {
+        Self {
+            digit: dff::DFF::new(b4(0)),
+        }
+    }
+}
+
+impl SynchronousIO for BcdDigit {
+    type I = bool;
+    type O = Out;
+    type Kernel = bcd_digit_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn bcd_digit_kernel(cr: ClockReset, enable: bool, q: Q) -> (Out, D) {
+    let carry = enable && q.digit >= 9;
+    let mut d = D { digit: q.digit };
+    if carry {
+        d.digit = bits(0);
+    } else if enable {
+        d.digit = q.digit + 1;
+    }
+    if cr.reset.any() {
+        d.digit = bits(0);
+    }
+    let o = Out {
+        digit: q.digit,
+        carry,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_decades() -> miette::Result<()> {
+        let input = (0..50).map(|n| n % 4 != 1).collect::<Vec<_>>();
+        let output = BcdDigit::default()
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let mut digit = 0;
+        for (enable, o) in input.iter().zip(&output) {
+            assert_eq!(o.digit.raw(), digit);
+            assert_eq!(o.carry, *enable && digit == 9);
+            if *enable {
+                digit = (digit + 1) % 10;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bcd_digit_hdl() -> miette::Result<()> {
+        let uut = BcdDigit::default();
+        let input = (0..60).map(|n| n % 7 != 3);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+//! BCD (Binary Coded Decimal) counters
+//!
+//! Counters that count in decimal, with each digit held in 4 bits
+//! (e.g., to drive multi-digit seven segment displays).  The
+//! [digit::BcdDigit] core is a single decade counter, with a carry out
+//! for cascading, and the [counter::BcdCounter] core chains several of
+//! them into a multi-digit counter.
+pub mod counter;
+pub mod digit;
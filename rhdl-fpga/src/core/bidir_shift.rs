@@ -0,0 +1,238 @@
+//! Bidirectional Shift Register (serial in, parallel out)
+//!
+//! A shift register that can shift either way, under control of the
+//! `direction` input.  When `direction` is low, the register shifts
+//! left, with the serial bit entering at the LSB, like the
+//! [ShiftRegister](super::shift_reg::ShiftRegister).  When `direction`
+//! is high, the register shifts right, with the serial bit entering at
+//! the MSB, like the [ShiftRegisterMsbIn](super::shift_reg_msb_in::ShiftRegisterMsbIn).
+//! The direction can change on any clock, and the bits already in the
+//! register simply move the other way.  When `enable` is low, the
+//! register holds its value.  On reset, the register is cleared.
+//!
+//! The input is a tuple of `(enable, direction, serial_in)`, and the
+//! output is the contents of the register.
+//!
+//!# Example
+//!
+//! Shifting in a nibble to the left, and then moving it back to the
+//! right.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::bidir_shift::BidirShift;
+//!
+//! let uut = BidirShift::<U8>::default();
+//! let input = [1, 0, 1, 1]
+//!     .into_iter()
+//!     .map(|bit| (true, false, bit == 1))
+//!     .chain(std::iter::repeat_n((true, true, false), 2))
+//!     .chain(std::iter::once((false, false, false)));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2;
+//! assert_eq!(output, b8(0b0000_0010));
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[doc_symbol(BidirShift<U8>, table)]
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The bidirectional shift register core
+///   `N` is the number of bits in the register
+///
+/// Here is the schematic symbol (for `N = 8`)
+#[doc = badascii_doc::badascii_formal!("
+      +-+BidirShift+---+
+ bool |                | b8
++---->| i.0     output +---->
+ bool |                |
++---->| i.1            |
+ bool |                |
++---->| i.2            |
+      +----------------+
+")]
+///
+/// | Port | Direction | Type | Bits |
+/// |------|-----------|------|------|
+/// | `i.0` | input | `bool` | 1 |
+/// | `i.1` | input | `bool` | 1 |
+/// | `i.2` | input | `bool` | 1 |
+/// | `output` | output | `b8` | 8 |
+///
+/// Here `i.0` is the enable, `i.1` is the direction (low to shift
+/// left, high to shift right), and `i.2` is the serial input.
+pub struct BidirShift<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for BidirShift<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for BidirShift<N> {
+    type I = (bool, bool, bool);
+    type O = Bits<N>;
+    type Kernel = bidir_shift_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn bidir_shift_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, bool),
+    q: Q<N>,
+) -> (Bits<N>, D<N>) {
+    let (enable, direction, serial_in) = i;
+    let mut d = D::<N> { reg: q.reg };
+    if enable {
+        if direction {
+            d.reg = q.reg >> 1;
+            if serial_in {
+                d.reg |= 1 << (N::BITS - 1);
+            }
+        } else {
+            d.reg = q.reg << 1;
+            if serial_in {
+                d.reg |= 1;
+            }
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+    }
+    (q.reg, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run the inputs, and return the contents after each clock
+    fn run<N: BitWidth>(input: &[(bool, bool, bool)]) -> miette::Result<Vec<u128>> {
+        let uut = BidirShift::<N>::default();
+        let input = input
+            .iter()
+            .copied()
+            .chain(std::iter::once((false, false, true)));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2.raw())
+            .collect())
+    }
+
+    fn bits_in(direction: bool, input: &[u8]) -> Vec<(bool, bool, bool)> {
+        input
+            .iter()
+            .map(|&bit| (true, direction, bit == 1))
+            .collect()
+    }
+
+    #[test]
+    fn test_both_directions() -> miette::Result<()> {
+        let left = run::<U8>(&bits_in(false, &[1, 1, 0, 1, 0, 0, 0, 1]))?;
+        assert_eq!(left.last(), Some(&0b1101_0001));
+        let right = run::<U8>(&bits_in(true, &[1, 1, 0, 1, 0, 0, 0, 1]))?;
+        assert_eq!(right.last(), Some(&0b1000_1011));
+        // The same bits, in the two directions, are bit reversed
+        assert_eq!(
+            *left.last().unwrap() as u8,
+            (*right.last().unwrap() as u8).reverse_bits()
+        );
+        // Other widths place the bits the same way
+        assert_eq!(
+            run::<U4>(&bits_in(false, &[1, 0, 0, 0]))?.last(),
+            Some(&0b1000)
+        );
+        assert_eq!(
+            run::<U4>(&bits_in(true, &[1, 0, 0, 0]))?.last(),
+            Some(&0b0001)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch_direction_mid_stream() -> miette::Result<()> {
+        // Shift 1011 in to the left, then two zeros in to the right,
+        // and then a one to the left again
+        let input = [
+            bits_in(false, &[1, 0, 1, 1]),
+            bits_in(true, &[0, 0]),
+            bits_in(false, &[1]),
+        ]
+        .concat();
+        let output = run::<U8>(&input)?;
+        assert_eq!(
+            output,
+            [
+                0b0000_0001,
+                0b0000_0010,
+                0b0000_0101,
+                0b0000_1011,
+                0b0000_0101,
+                0b0000_0010,
+                0b0000_0101
+            ]
+        );
+        // Going right and then back left by the same number of bits
+        // restores the contents, apart from the bits shifted out
+        let input = [
+            bits_in(false, &[1, 1, 1, 0, 0, 1, 1, 1]),
+            bits_in(true, &[0, 0, 0]),
+            bits_in(false, &[1, 1, 1]),
+        ]
+        .concat();
+        assert_eq!(run::<U8>(&input)?.last(), Some(&0b1110_0111));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_and_reset() -> miette::Result<()> {
+        let uut = BidirShift::<U4>::default();
+        let input = [
+            (true, false, true),
+            (false, true, true),
+            (false, false, false),
+            (true, true, true),
+        ]
+        .into_iter()
+        .with_reset(1)
+        .chain(std::iter::repeat_n((true, false, true), 2).with_reset(1))
+        .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2.raw())
+            .collect::<Vec<_>>();
+        // The register holds while the enable is low, and is
+        // cleared on the clock after the reset, before shifting again
+        assert_eq!(output, [0, 0, 0b0001, 0b0001, 0b0001, 0b1000, 0, 0b0001]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bidir_shift_hdl() -> miette::Result<()> {
+        let uut = BidirShift::<U4>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 7 < 3, n % 5 < 2));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -16,6 +16,10 @@
 //! core simply provides the constant value all the
 //! time.
 //!
+//! Constants that only hold the configuration of a core (such
+//! as a threshold chosen at construction) can be built with
+//! [Constant::untraced], which leaves them out of the trace.
+//!
 //!# Example
 //!
 //! Here is an example of the constant being
@@ -43,12 +47,27 @@ use rhdl::{
 /// The core to include for the constant driver
 pub struct Constant<T: Digital> {
     value: T,
+    traced: bool,
 }
 
 impl<T: Digital> Constant<T> {
     ///. Create a new constant driver with the provided value
     pub fn new(value: T) -> Self {
-        Self { value }
+        Self {
+            value,
+            traced: true,
+        }
+    }
+    /// Create a new constant driver that does not appear in the trace
+    pub fn untraced(value: T) -> Self {
+        Self {
+            value,
+            traced: false,
+        }
+    }
+    /// The value driven by the constant
+    pub fn value(&self) -> T {
+        self.value
     }
 }
 
@@ -69,9 +88,11 @@ impl<T: Digital> Synchronous for Constant<T> {
     fn init(&self) -> Self::S {}
 
     fn sim(&self, _clock_reset: ClockReset, _input: Self::I, _state: &mut Self::S) -> Self::O {
-        trace_push_path("constant");
-        trace("value", &self.value);
-        trace_pop_path();
+        if self.traced {
+            trace_push_path("constant");
+            trace("value", &self.value);
+            trace_pop_path();
+        }
         self.value
     }
 
@@ -2,7 +2,10 @@
 //!
 //! A simple counter that counts the number of boolean true
 //! values it has seen.  It is parameterized by the number of
-//! bits in the counter.
+//! bits in the counter.  To know when the count wraps, use the
+//! [RolloverCounter](super::rollover_counter::RolloverCounter), and
+//! to preset the count (or count down), use the
+//! [LoadableCounter](super::loadable_counter::LoadableCounter).
 //!
 //! Here is the schematic symbol
 #![doc = badascii_doc::badascii_formal!("
@@ -0,0 +1,238 @@
+//! Debouncer
+//!
+//! Filters the bounces out of a mechanical input (e.g., a push button
+//! or a switch).  The output only changes once the input has differed
+//! from it for `hold` consecutive clocks.  A counter counts the clocks
+//! the input has been at the new level, and is cleared whenever the
+//! input returns to the level of the output, so chattering around a
+//! transition never gets through.  A clean step on the input appears on
+//! the output exactly `hold` clocks later.  A `hold` of `0` is treated
+//! as `1` (i.e., the output is just the input delayed by one clock).
+//!
+//! Along with the debounced level, the `pressed` and `released` outputs
+//! are strobed for one clock when the level goes high and low
+//! respectively, so a separate [EdgeDetect](super::edge_detect::EdgeDetect)
+//! is not needed.  The outputs are registered.  The input is taken to be
+//! active high (so invert an active low button first), and the level
+//! resets to low, so a button held through reset is reported as pressed
+//! `hold` clocks after reset.
+//!
+//! The `hold` is given at construction, and the default is the longest
+//! the `N` bit counter can count (i.e., `2^N - 1` clocks), so the width
+//! of the counter sets the time.  For example, a 20 bit counter on a
+//! 100 MHz clock gives a hold of about 10 ms.  The input is assumed to
+//! be synchronous to the clock (for example, using a
+//! [Sync1Bit](crate::cdc::synchronizer::Sync1Bit) core).
+//!
+//!# Example
+//!
+//! A press that bounces for a few clocks, with a hold of 4.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::debounce::Debounce;
+//!
+//! let uut = Debounce::<U4>::new(b4(4));
+//! let input = [0, 1, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1];
+//! let output = uut
+//!     .run(input.map(|x| x == 1).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! let level = output.iter().map(|o| o.level as u8).collect::<Vec<_>>();
+//! assert_eq!(level, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
+//! assert_eq!(output.iter().filter(|o| o.pressed).count(), 1);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [Debounce]
+pub struct Out {
+    /// The debounced level of the input
+    pub level: bool,
+    /// High for one clock when the level goes high
+    pub pressed: bool,
+    /// High for one clock when the level goes low
+    pub released: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The debouncer core
+///   `N` is the number of bits in the counter
+pub struct Debounce<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+    out: dff::DFF<Out>,
+    hold: constant::Constant<Bits<N>>,
+}
+
+impl<N: BitWidth> Debounce<N> {
+    /// Create a debouncer that changes its output once the input
+    /// has been stable for `hold` clocks
+    pub fn new(hold: Bits<N>) -> Self {
+        Self {
+            count: dff::DFF::default(),
+            out: dff::DFF::default(),
+            hold: constant::Constant::new(hold),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for Debounce<N> {
+    fn default() -> Self {
+        Self::new(Bits::<N>::MAX)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for Debounce<N> {
+    type I = bool;
+    type O = Out;
+    type Kernel = debounce_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn debounce_kernel<N: BitWidth>(_cr: ClockReset, i: bool, q: Q<N>) -> (Out, D<N>) {
+    let level = q.out.level;
+    let mut d = D::<N> {
+        count: bits(0),
+        out: Out {
+            level,
+            pressed: false,
+            released: false,
+        },
+        hold: (),
+    };
+    // Count the clocks the input has been away from the level,
+    // and change the level once it has been away for long enough
+    if i != level {
+        if q.hold == 0 || q.count >= q.hold - 1 {
+            d.out = Out {
+                level: i,
+                pressed: i,
+                released: !i,
+            };
+        } else {
+            d.count = q.count + 1;
+        }
+    }
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOLD: usize = 10;
+
+    fn run(input: &[bool]) -> miette::Result<Vec<Out>> {
+        let uut = Debounce::<U4>::new(bits(HOLD as u128));
+        Ok(uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // A transition to `level` that bounces a few times on the way,
+    // followed by the level held for `stable` clocks
+    fn bouncy(level: bool, stable: usize) -> Vec<bool> {
+        let mut wave = vec![];
+        for (on, off) in [(1, 2), (3, 1), (2, 4), (HOLD - 1, 1), (1, 1)] {
+            wave.extend(std::iter::repeat_n(level, on));
+            wave.extend(std::iter::repeat_n(!level, off));
+        }
+        wave.extend(std::iter::repeat_n(level, stable));
+        wave
+    }
+
+    #[test]
+    fn test_step_delay() -> miette::Result<()> {
+        let mut input = vec![false; 5];
+        input.extend(vec![true; 30]);
+        input.extend(vec![false; 30]);
+        let output = run(&input)?;
+        // The output follows each clean step exactly HOLD clocks later
+        for (n, o) in output.iter().enumerate() {
+            assert_eq!(o.level, (5 + HOLD..35 + HOLD).contains(&n));
+            assert_eq!(o.pressed, n == 5 + HOLD);
+            assert_eq!(o.released, n == 35 + HOLD);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bouncy_presses() -> miette::Result<()> {
+        let mut input = vec![false; 5];
+        for _ in 0..4 {
+            input.extend(bouncy(true, 3 * HOLD));
+            input.extend(bouncy(false, 3 * HOLD));
+        }
+        let output = run(&input)?;
+        // Exactly one clean transition each way per press
+        assert_eq!(output.iter().filter(|o| o.pressed).count(), 4);
+        assert_eq!(output.iter().filter(|o| o.released).count(), 4);
+        let changes = output
+            .windows(2)
+            .filter(|w| w[0].level != w[1].level)
+            .count();
+        assert_eq!(changes, 8);
+        // The strobes line up with the changes in level
+        for w in output.windows(2) {
+            assert_eq!(w[1].pressed, w[1].level && !w[0].level);
+            assert_eq!(w[1].released, !w[1].level && w[0].level);
+        }
+        // And the level changes HOLD clocks after the bouncing stops
+        let settle = bouncy(true, 0).len();
+        let first = output.iter().position(|o| o.pressed).unwrap();
+        assert_eq!(first, 5 + settle + HOLD);
+        Ok(())
+    }
+
+    #[test]
+    fn test_glitches_are_ignored() -> miette::Result<()> {
+        // Pulses of up to HOLD - 1 clocks never get through
+        let mut input = vec![];
+        for width in 1..HOLD {
+            input.extend(vec![true; width]);
+            input.extend(vec![false; 3]);
+        }
+        let output = run(&input)?;
+        assert!(output.iter().all(|o| *o == Out::default()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_hold() -> miette::Result<()> {
+        let uut = Debounce::<U4>::new(b4(0));
+        let input = [false, true, true, false, true];
+        let level = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.level)
+            .collect::<Vec<_>>();
+        assert_eq!(level, [false, false, true, true, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_debounce_hdl() -> miette::Result<()> {
+        let uut = Debounce::<U4>::new(bits(HOLD as u128));
+        let mut input = vec![false; 5];
+        input.extend(bouncy(true, 2 * HOLD));
+        input.extend(bouncy(false, 2 * HOLD));
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
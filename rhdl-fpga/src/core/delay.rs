@@ -140,6 +140,80 @@ mod tests {
         Ok(())
     }
 
+    #[derive(PartialEq, Debug, Digital, Default)]
+    struct Payload {
+        tag: b4,
+        value: s8,
+    }
+
+    fn payload(n: usize) -> Payload {
+        Payload {
+            tag: bits((n % 16) as u128),
+            value: signed((n as i128 * 37) % 128 - 64),
+        }
+    }
+
+    // Each value comes out exactly `N` clocks after it goes in, and
+    // the stages start out (after reset) holding the default value
+    fn check_latency<const N: usize>() -> miette::Result<()> {
+        let uut = Delay::<Payload, N>::default();
+        let input = (1..=20).map(payload).collect::<Vec<_>>();
+        let output = uut
+            .run(input.clone().into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert!(output[..N].iter().all(|o| *o == Payload::default()));
+        assert_eq!(output[N..], input[..input.len() - N]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delay_latency_1() -> miette::Result<()> {
+        check_latency::<1>()
+    }
+
+    #[test]
+    fn test_delay_latency_3() -> miette::Result<()> {
+        check_latency::<3>()
+    }
+
+    #[test]
+    fn test_delay_latency_8() -> miette::Result<()> {
+        check_latency::<8>()
+    }
+
+    #[test]
+    fn test_delay_stages_staggered() -> miette::Result<()> {
+        let uut = Delay::<Payload, 3>::default();
+        let mut session = SimSession::new(&uut).recorded();
+        session.reset();
+        for n in 1..=6 {
+            session.step(payload(n));
+            // After the clock edge, stage k holds the value that
+            // went in k clocks before this one
+            for k in 0..3 {
+                let stage = session
+                    .peek(&format!("top.dffs.array.[{k}].dff.output"))
+                    .unwrap();
+                let expected = if n > k {
+                    payload(n - k)
+                } else {
+                    Payload::default()
+                };
+                assert_eq!(stage, expected.typed_bits());
+            }
+        }
+        let mut vcd = vec![];
+        session.dump_vcd(&mut vcd).unwrap();
+        let vcd = String::from_utf8(vcd).unwrap();
+        assert!(["[0]", "[1]", "[2]"]
+            .iter()
+            .all(|stage| vcd.contains(stage)));
+        Ok(())
+    }
+
     #[test]
     fn test_delay_hdl_works() -> miette::Result<()> {
         let uut = Delay::<Option<Bits<U8>>, 4>::default();
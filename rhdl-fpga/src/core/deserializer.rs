@@ -0,0 +1,297 @@
+//! Deserializer (serial in, framed words out)
+//!
+//! Frames a serial stream into words, aligned to a sync pattern.  Bits
+//! arrive MSB first, one per enabled clock.  Until the core is locked,
+//! the last `N` bits received are compared against the sync pattern
+//! given at construction.  When they match, the core locks, and the
+//! bits that follow are collected into `N` bit words.  Each time a word
+//! is complete, `valid` is high for one clock, with the word on `data`
+//! (the first bit received is the MSB).  At other times, `data` holds
+//! the word being collected, and should be ignored.
+//!
+//! Once locked, the stream is not searched for the sync pattern, so
+//! payload words that happen to look like the sync pattern are passed
+//! through as data, and do not realign the framing.  To search for the
+//! sync pattern again (e.g., when the framing is known to be lost), set
+//! `resync`.  The bit received on the same clock (if any) is the first
+//! bit of the new search.  Only bits received after reset (or after a
+//! resync) can make up the sync pattern, so a pattern with leading
+//! zeros cannot be matched against a partially filled window.
+//!
+//! Both the search window and the word being collected are held in a
+//! [ShiftRegister](super::shift_reg::ShiftRegister).  On reset, the core
+//! is unlocked, and starts to search for the sync pattern.
+//!
+//!# Example
+//!
+//! Garbage, the sync pattern `0b1110_0100` and two payload bytes.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::deserializer::{Deserializer, In};
+//!
+//! let uut = Deserializer::<U8>::new(b8(0b1110_0100));
+//! let bits = [0b1011_0111_u8, 0b1110_0100, 0b0001_0010, 0b1110_0100]
+//!     .into_iter()
+//!     .flat_map(|byte| (0..8).rev().map(move |n| byte & (1 << n) != 0));
+//! let input = bits
+//!     .map(|serial| In {
+//!         enable: true,
+//!         serial,
+//!         resync: false,
+//!     })
+//!     .chain(std::iter::once(In::default()));
+//! let words = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .filter(|t| t.value.2.valid)
+//!     .map(|t| t.value.2.data)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(words, [b8(0b0001_0010), b8(0b1110_0100)]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff, shift_reg::ShiftRegister};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [Deserializer]
+pub struct In {
+    /// Receive the serial bit on this clock
+    pub enable: bool,
+    /// The serial bit
+    pub serial: bool,
+    /// Drop the lock, and search for the sync pattern again
+    pub resync: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [Deserializer]
+pub struct Out<N: BitWidth> {
+    /// The word received (when `valid` is high)
+    pub data: Bits<N>,
+    /// High for one clock when `data` holds a complete word
+    pub valid: bool,
+    /// High when the sync pattern has been found
+    pub locked: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The deserializer core
+///   `N` is the number of bits in a word (and in the sync pattern)
+pub struct Deserializer<N: BitWidth> {
+    window: ShiftRegister<N>,
+    word: ShiftRegister<N>,
+    count: dff::DFF<b8>,
+    locked: dff::DFF<bool>,
+    valid: dff::DFF<bool>,
+    sync: constant::Constant<Bits<N>>,
+    last: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> Deserializer<N> {
+    /// Create a deserializer that locks to the given sync pattern
+    pub fn new(sync: Bits<N>) -> Self {
+        Self {
+            window: ShiftRegister::default(),
+            word: ShiftRegister::default(),
+            count: dff::DFF::new(b8(0)),
+            locked: dff::DFF::new(false),
+            valid: dff::DFF::new(false),
+            sync: constant::Constant::new(sync),
+            last: constant::Constant::new(bits(N::BITS as u128 - 1)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for Deserializer<N> {
+    type I = In;
+    type O = Out<N>;
+    type Kernel = deserializer_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn deserializer_kernel<N: BitWidth>(cr: ClockReset, i: In, q: Q<N>) -> (Out<N>, D<N>) {
+    let locked = q.locked && !i.resync;
+    let count = if i.resync { b8(0) } else { q.count };
+    let mut d = D::<N> {
+        window: (i.enable, false, i.serial, bits(0)),
+        word: (i.enable && locked, false, i.serial, bits(0)),
+        count,
+        locked,
+        valid: false,
+        sync: (),
+        last: (),
+    };
+    if i.enable {
+        if locked {
+            // Collecting a word
+            if count == q.last {
+                d.count = b8(0);
+                d.valid = true;
+            } else {
+                d.count = count + 1;
+            }
+        } else {
+            // Searching, with the count saturating once the window is full
+            let mut window = q.window << 1;
+            if i.serial {
+                window |= 1;
+            }
+            if count == q.last && window == q.sync {
+                d.locked = true;
+                d.count = b8(0);
+            } else if count != q.last {
+                d.count = count + 1;
+            }
+        }
+    }
+    if cr.reset.any() {
+        d.count = b8(0);
+        d.locked = false;
+        d.valid = false;
+    }
+    let o = Out::<N> {
+        data: q.word,
+        valid: q.valid,
+        locked: q.locked,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYNC: u8 = 0b1110_0100;
+
+    fn stream(bytes: &[u8]) -> Vec<In> {
+        bytes
+            .iter()
+            .flat_map(|byte| {
+                (0..8).rev().map(move |n| In {
+                    enable: true,
+                    serial: byte & (1 << n) != 0,
+                    resync: false,
+                })
+            })
+            .collect()
+    }
+
+    fn run(input: Vec<In>) -> miette::Result<Vec<Out<U8>>> {
+        let uut = Deserializer::<U8>::new(b8(SYNC as u128));
+        let input = input.into_iter().chain(std::iter::once(In::default()));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn words(output: &[Out<U8>]) -> Vec<u128> {
+        output
+            .iter()
+            .filter(|o| o.valid)
+            .map(|o| o.data.raw())
+            .collect()
+    }
+
+    #[test]
+    fn test_garbage_sync_and_payload() -> miette::Result<()> {
+        // The garbage is not byte aligned, and contains a near miss
+        let mut input = stream(&[0b0110_0101, 0b1110_0101])[3..].to_vec();
+        input.extend(stream(&[SYNC, 0x12, 0x34, 0xAB, 0xCD]));
+        let output = run(input)?;
+        assert_eq!(words(&output), [0x12, 0x34, 0xAB, 0xCD]);
+        // Locked on the clock after the last bit of the sync pattern
+        let locked = output.iter().position(|o| o.locked);
+        assert_eq!(locked, Some(21));
+        assert!(output[21..].iter().all(|o| o.locked));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_in_payload_does_not_realign() -> miette::Result<()> {
+        // The sync pattern as a payload word, and straddling two
+        // payload words, is passed through as data
+        let straddle = [(SYNC << 4) | 0x3, (SYNC >> 4) | 0x50];
+        let mut input = stream(&[SYNC, 0x01, SYNC]);
+        input.extend(stream(&straddle));
+        input.extend(stream(&[0x02]));
+        let output = run(input)?;
+        assert_eq!(
+            words(&output),
+            [
+                0x01,
+                SYNC as u128,
+                straddle[0] as u128,
+                straddle[1] as u128,
+                0x02
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_gaps_in_enable() -> miette::Result<()> {
+        let input = stream(&[SYNC, 0x5A, 0xC3])
+            .into_iter()
+            .flat_map(|i| [i, In::default()])
+            .collect();
+        let output = run(input)?;
+        assert_eq!(words(&output), [0x5A, 0xC3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resync() -> miette::Result<()> {
+        // Lock, receive a word, then resync in the middle of the next
+        // one.  The bits up to the next sync pattern are dropped.
+        let mut input = stream(&[SYNC, 0x11, 0x22]);
+        input[20].resync = true;
+        input.extend(stream(&[0xFF, SYNC, 0x33]));
+        let output = run(input)?;
+        assert_eq!(words(&output), [0x11, 0x33]);
+        assert!(!output[21].locked);
+        assert_eq!(output.iter().filter(|o| o.locked).count(), 13 + 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_window_does_not_match() -> miette::Result<()> {
+        // With zeros left in the window by reset, the first three bits
+        // would complete a sync pattern with leading zeros
+        let uut = Deserializer::<U8>::new(b8(0b0000_0111));
+        let input = stream(&[0b1111_1111, 0b0000_0111, 0x42]);
+        let input = input[5..]
+            .iter()
+            .copied()
+            .chain(std::iter::once(In::default()));
+        let words = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .filter(|t| t.value.2.valid)
+            .map(|t| t.value.2.data)
+            .collect::<Vec<_>>();
+        assert_eq!(words, [b8(0x42)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserializer_hdl() -> miette::Result<()> {
+        let uut = Deserializer::<U8>::new(b8(SYNC as u128));
+        let mut input = stream(&[0x3C, SYNC, 0x12, SYNC, 0x34]);
+        input[8].enable = false;
+        input[30].resync = true;
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
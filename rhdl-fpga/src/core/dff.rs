@@ -87,6 +87,10 @@ impl<T: Digital> DFF<T> {
     pub fn new(reset: T) -> Self {
         Self { reset }
     }
+    /// The value the [DFF] holds after reset
+    pub fn reset_value(&self) -> T {
+        self.reset
+    }
 }
 
 impl<T: Digital + Default> Default for DFF<T> {
@@ -0,0 +1,162 @@
+//! Edge Detector
+//!
+//! Detects the rising and falling edges of a single bit input (e.g.,
+//! a push button after a [Debounce](super::debounce::Debounce), or a
+//! strobe from another clock domain after a
+//! [Sync1Bit](crate::cdc::synchronizer::Sync1Bit)).  The previous value
+//! of the input is held in a history register, and the outputs are
+//! high for the one clock on which the input differs from it.  The
+//! outputs are combinational, and are held low during reset.
+//!
+//! The history register resets to empty, and is filled by the first
+//! clock after reset.  So there are no edges on the first clock after
+//! reset, whatever the level of the input, and an input that is high
+//! coming out of reset does not produce a spurious rising edge.
+//!
+//! For per-bit edges on a whole word, use the
+//! [EdgeDetectBits](super::edge_detect_bits::EdgeDetectBits).
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::edge_detect::EdgeDetect;
+//!
+//! let uut = EdgeDetect::default();
+//! let input = [true, true, false, false, true, false];
+//! let output = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! let rising = output.iter().map(|o| o.rising as u8).collect::<Vec<_>>();
+//! let falling = output.iter().map(|o| o.falling as u8).collect::<Vec<_>>();
+//! assert_eq!(rising, [0, 0, 0, 0, 1, 0]);
+//! assert_eq!(falling, [0, 0, 1, 0, 0, 1]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [EdgeDetect]
+pub struct Out {
+    /// High on the clock the input goes from low to high
+    pub rising: bool,
+    /// High on the clock the input goes from high to low
+    pub falling: bool,
+    /// High on either edge
+    pub any: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The edge detector core
+pub struct EdgeDetect {
+    prev: dff::DFF<Option<bool>>,
+}
+
+impl Default for EdgeDetect {
+    fn default() -> Self {
+        Self {
+            prev: dff::DFF::new(None),
+        }
+    }
+}
+
+impl SynchronousIO for EdgeDetect {
+    type I = bool;
+    type O = Out;
+    type Kernel = edge_detect_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn edge_detect_kernel(cr: ClockReset, i: bool, q: Q) -> (Out, D) {
+    let d = D { prev: Some(i) };
+    let mut o = Out::default();
+    if let Some(prev) = q.prev {
+        o.rising = i && !prev;
+        o.falling = !i && prev;
+        o.any = i != prev;
+    }
+    if cr.reset.any() {
+        o = Out::default();
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(
+        input: impl Iterator<Item = TimedSample<(ClockReset, bool)>>,
+    ) -> miette::Result<Vec<Out>> {
+        let uut = EdgeDetect::default();
+        Ok(uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_edges() -> miette::Result<()> {
+        let input = (0..100)
+            .map(|n| (n / 3) % 2 == 1 || n % 17 == 0)
+            .collect::<Vec<_>>();
+        let output = run(input.iter().copied().with_reset(1).clock_pos_edge(100))?;
+        // Skip the reset, and the first clock after it
+        for (n, o) in output.iter().enumerate().skip(2) {
+            let (prev, now) = (input[n - 2], input[n - 1]);
+            assert_eq!(o.rising, now && !prev);
+            assert_eq!(o.falling, !now && prev);
+            assert_eq!(o.any, now != prev);
+        }
+        assert_eq!(output.iter().filter(|o| o.rising).count(), 18);
+        assert_eq!(output.iter().filter(|o| o.falling).count(), 18);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_edge_after_reset() -> miette::Result<()> {
+        // The history register resets to empty, rather than to a level
+        // the input might not have, so the first clock after reset has
+        // no edge whether the input is high or low
+        for level in [false, true] {
+            let input = [level, level, !level];
+            let output = run(input.with_reset(1).clock_pos_edge(100))?;
+            assert_eq!(output[1], Out::default());
+            assert_eq!(output[2], Out::default());
+            assert!(output[3].any);
+            assert_eq!(output[3].rising, !level);
+        }
+        // Including a reset in the middle of a run, with the input high
+        let input = [false, true, true]
+            .with_reset(1)
+            .chain([true, false].with_reset(1))
+            .clock_pos_edge(100);
+        let output = run(input)?;
+        let rising = output.iter().map(|o| o.rising).collect::<Vec<_>>();
+        let falling = output.iter().map(|o| o.falling).collect::<Vec<_>>();
+        assert_eq!(rising, [false, false, true, false, false, false, false]);
+        assert_eq!(falling, [false, false, false, false, false, false, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_edge_detect_hdl() -> miette::Result<()> {
+        let uut = EdgeDetect::default();
+        let input = (0..100).map(|n| (n / 4) % 3 == 0);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
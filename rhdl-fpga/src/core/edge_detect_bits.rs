@@ -0,0 +1,146 @@
+//! Edge Detector for Words
+//!
+//! Like the [EdgeDetect](super::edge_detect::EdgeDetect), but for each
+//! bit of an `N` bit input (e.g., a bank of buttons, or a set of status
+//! flags).  The outputs are masks, with a bit set for each input bit
+//! that has the corresponding edge on this clock.  The outputs are
+//! combinational, and are held low during reset.
+//!
+//! As with the [EdgeDetect](super::edge_detect::EdgeDetect), the history
+//! register resets to empty, so there are no edges on the first clock
+//! after reset, whatever the value of the input.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::edge_detect_bits::EdgeDetectBits;
+//!
+//! let uut = EdgeDetectBits::<U4>::default();
+//! let input = [b4(0b1100), b4(0b1010), b4(0b1010)];
+//! let output = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output[1].rising, b4(0b0010));
+//! assert_eq!(output[1].falling, b4(0b0100));
+//! assert_eq!(output[1].any, b4(0b0110));
+//! assert_eq!(output[2].any, b4(0));
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [EdgeDetectBits]
+pub struct Out<N: BitWidth> {
+    /// The bits that went from low to high
+    pub rising: Bits<N>,
+    /// The bits that went from high to low
+    pub falling: Bits<N>,
+    /// The bits that changed
+    pub any: Bits<N>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The word edge detector core
+///   `N` is the number of bits in the input
+pub struct EdgeDetectBits<N: BitWidth> {
+    prev: dff::DFF<Option<Bits<N>>>,
+}
+
+impl<N: BitWidth> Default for EdgeDetectBits<N> {
+    fn default() -> Self {
+        Self {
+            prev: dff::DFF::new(None),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for EdgeDetectBits<N> {
+    type I = Bits<N>;
+    type O = Out<N>;
+    type Kernel = edge_detect_bits_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn edge_detect_bits_kernel<N: BitWidth>(cr: ClockReset, i: Bits<N>, q: Q<N>) -> (Out<N>, D<N>) {
+    let d = D::<N> { prev: Some(i) };
+    let mut o = Out::<N>::default();
+    if let Some(prev) = q.prev {
+        o.rising = i & !prev;
+        o.falling = !i & prev;
+        o.any = i ^ prev;
+    }
+    if cr.reset.any() {
+        o = Out::<N>::default();
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_bit_edges() -> miette::Result<()> {
+        let uut = EdgeDetectBits::<U8>::default();
+        let input = (0..200)
+            .map(|_| b8(rand::random::<u8>() as u128))
+            .collect::<Vec<_>>();
+        let output = uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output[0], Out::default());
+        for (n, o) in output.iter().enumerate().skip(1) {
+            let (prev, now) = (input[n - 1].raw(), input[n].raw());
+            for bit in 0..8 {
+                let (was, is) = (prev & (1 << bit) != 0, now & (1 << bit) != 0);
+                assert_eq!(o.rising.raw() & (1 << bit) != 0, is && !was);
+                assert_eq!(o.falling.raw() & (1 << bit) != 0, !is && was);
+                assert_eq!(o.any.raw() & (1 << bit) != 0, is != was);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_edge_after_reset() -> miette::Result<()> {
+        let uut = EdgeDetectBits::<U8>::default();
+        let input = [b8(0xA5), b8(0xA5), b8(0x5A)]
+            .with_reset(1)
+            .chain([b8(0xFF), b8(0x0F)].with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let rising = output.iter().map(|o| o.rising.raw()).collect::<Vec<_>>();
+        let falling = output.iter().map(|o| o.falling.raw()).collect::<Vec<_>>();
+        assert_eq!(rising, [0, 0, 0, 0x5A, 0, 0, 0]);
+        assert_eq!(falling, [0, 0, 0, 0xA5, 0, 0, 0xF0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_edge_detect_bits_hdl() -> miette::Result<()> {
+        let uut = EdgeDetectBits::<U8>::default();
+        let input = (0..100).map(|n| b8((n * 37) % 256));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+//! Gearbox (width converter)
+//!
+//! Converts a stream of `IN` bit words into a stream of `OUT` bit
+//! words, where the widths need not divide one another (e.g., 8 bit
+//! bytes into the 10 bit symbols of an 8b/10b coder, and back).  The
+//! stream is treated as a sequence of bits, with the MSB of each word
+//! first, so output words can straddle input words, and vice versa.
+//!
+//! Input words arrive on `data` as an `Option`.  They are appended to a
+//! buffer of `IN + OUT` bits (made of a [DFF](super::dff::DFF)), and a
+//! count of the bits held in the buffer is kept.  Whenever the buffer
+//! holds at least `OUT` bits, the oldest `OUT` of them are presented on
+//! the output (as `Some`), and removed on that clock.  The count is the
+//! phase of the gearbox: it returns to zero after every `lcm(IN, OUT)`
+//! bits.
+//!
+//! When up-converting (`IN < OUT`), an input word can be accepted on
+//! every clock.  When down-converting (`IN > OUT`), output words are
+//! produced faster than input words are consumed, so the input must be
+//! throttled.  The `ready` output is high when the buffer has room for
+//! another input word, and a word presented when `ready` is low is
+//! dropped.  `ready` does not depend on the input, so a producer can
+//! check it before presenting a word.  For example, going from 10 bits
+//! to 8 bits, 4 input words are accepted in every 5 clocks.
+//!
+//! On reset, the buffer is emptied.
+//!
+//!# Example
+//!
+//! Four bytes into three 10 bit words (with two bits left over).
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::gearbox::Gearbox;
+//!
+//! let uut = Gearbox::<U8, U10>::default();
+//! let input = [0b1100_0011, 0b0000_1111, 0b1010_0101, 0b1111_1111]
+//!     .map(|x| Some(b8(x)))
+//!     .into_iter()
+//!     .chain(std::iter::repeat_n(None, 2));
+//! let words = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .filter_map(|t| t.value.2.data)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(
+//!     words,
+//!     [b10(0b11_0000_1100), b10(0b00_1111_1010), b10(0b01_0111_1111)]
+//! );
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [Gearbox]
+pub struct Out<OUT: BitWidth> {
+    /// The next output word (if the buffer holds one)
+    pub data: Option<Bits<OUT>>,
+    /// High when an input word can be accepted
+    pub ready: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The gearbox core
+///   `IN` is the number of bits in an input word
+///   `OUT` is the number of bits in an output word
+pub struct Gearbox<IN: BitWidth, OUT: BitWidth>
+where
+    IN: std::ops::Add<OUT>,
+    Sum<IN, OUT>: BitWidth,
+{
+    buffer: dff::DFF<Bits<Sum<IN, OUT>>>,
+    fill: dff::DFF<b8>,
+    in_width: constant::Constant<b8>,
+    out_width: constant::Constant<b8>,
+}
+
+impl<IN: BitWidth, OUT: BitWidth> Default for Gearbox<IN, OUT>
+where
+    IN: std::ops::Add<OUT>,
+    Sum<IN, OUT>: BitWidth,
+{
+    fn default() -> Self {
+        Self {
+            buffer: dff::DFF::new(Bits::default()),
+            fill: dff::DFF::new(b8(0)),
+            in_width: constant::Constant::new(bits(IN::BITS as u128)),
+            out_width: constant::Constant::new(bits(OUT::BITS as u128)),
+        }
+    }
+}
+
+impl<IN: BitWidth, OUT: BitWidth> SynchronousIO for Gearbox<IN, OUT>
+where
+    IN: std::ops::Add<OUT>,
+    Sum<IN, OUT>: BitWidth,
+{
+    type I = Option<Bits<IN>>;
+    type O = Out<OUT>;
+    type Kernel = gearbox_kernel<IN, OUT>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn gearbox_kernel<IN, OUT>(
+    cr: ClockReset,
+    i: Option<Bits<IN>>,
+    q: Q<IN, OUT>,
+) -> (Out<OUT>, D<IN, OUT>)
+where
+    IN: BitWidth + std::ops::Add<OUT>,
+    OUT: BitWidth,
+    Sum<IN, OUT>: BitWidth,
+{
+    let mut d = D::<IN, OUT> {
+        buffer: q.buffer,
+        fill: q.fill,
+        in_width: (),
+        out_width: (),
+    };
+    // The oldest OUT bits of the buffer
+    let emit = q.fill >= q.out_width;
+    let word = (q.buffer >> (q.fill - q.out_width)).resize::<OUT>();
+    let fill = if emit { q.fill - q.out_width } else { q.fill };
+    // There is room for IN more bits
+    let ready = fill <= q.out_width;
+    d.fill = fill;
+    if let Some(data) = i {
+        if ready {
+            d.buffer = (q.buffer << (IN::BITS as u128)) | data.resize::<Sum<IN, OUT>>();
+            d.fill = fill + q.in_width;
+        }
+    }
+    if cr.reset.any() {
+        d.fill = b8(0);
+    }
+    let o = Out::<OUT> {
+        data: if emit { Some(word) } else { None },
+        ready,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    // The reference model: a queue of bits
+    fn reference(input: &[u128], in_bits: usize, out_bits: usize) -> Vec<u128> {
+        let mut queue = input
+            .iter()
+            .flat_map(|x| (0..in_bits).rev().map(move |n| x & (1 << n) != 0))
+            .collect::<VecDeque<_>>();
+        let mut output = vec![];
+        while queue.len() >= out_bits {
+            let word = queue
+                .drain(..out_bits)
+                .fold(0, |acc, bit| (acc << 1) | bit as u128);
+            output.push(word);
+        }
+        output
+    }
+
+    // Feed the words through the gearbox, holding each one until it is
+    // accepted, and collect the output words
+    fn stream<IN, OUT>(input: &[u128]) -> (Vec<u128>, u64)
+    where
+        IN: BitWidth + std::ops::Add<OUT>,
+        OUT: BitWidth,
+        Sum<IN, OUT>: BitWidth,
+    {
+        let uut = Gearbox::<IN, OUT>::default();
+        let mut session = SimSession::new(&uut);
+        session.reset();
+        let mut output = vec![];
+        let mut pending = input.iter().copied().peekable();
+        let mut clocks = 0;
+        while let Some(&word) = pending.peek() {
+            let o = session.step(Some(bits(word)));
+            if o.ready {
+                pending.next();
+            }
+            output.extend(o.data.map(|x| x.raw()));
+            clocks += 1;
+        }
+        for _ in 0..OUT::BITS {
+            let o = session.step(None);
+            output.extend(o.data.map(|x| x.raw()));
+        }
+        (output, clocks)
+    }
+
+    fn words(count: usize, width: usize) -> Vec<u128> {
+        (0..count as u128)
+            .map(|n| (n * 0x9E37 + (n << 5) + 3) & ((1 << width) - 1))
+            .collect()
+    }
+
+    #[test]
+    fn test_8_to_10() {
+        // 5 input words to a phase wrap, so 3 wraps and a bit
+        let input = words(17, 8);
+        let (output, clocks) = stream::<U8, U10>(&input);
+        assert_eq!(output, reference(&input, 8, 10));
+        assert_eq!(output.len(), 13);
+        // Up-conversion never throttles the input
+        assert_eq!(clocks, 17);
+    }
+
+    #[test]
+    fn test_10_to_8() {
+        // 4 input words to a phase wrap, so 3 wraps and a bit
+        let input = words(14, 10);
+        let (output, clocks) = stream::<U10, U8>(&input);
+        assert_eq!(output, reference(&input, 10, 8));
+        assert_eq!(output.len(), 17);
+        // 4 words accepted in every 5 clocks
+        assert_eq!(clocks, 17);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = words(40, 8);
+        let (tens, _) = stream::<U8, U10>(&input);
+        let (bytes, _) = stream::<U10, U8>(&tens);
+        assert_eq!(bytes, input);
+    }
+
+    #[test]
+    fn test_phase_wraps() -> miette::Result<()> {
+        // The pattern of output words repeats every 5 input words,
+        // when the buffer empties at the end of each phase wrap
+        let uut = Gearbox::<U8, U10>::default();
+        let input = words(15, 8).into_iter().map(|x| Some(b8(x)));
+        let valid = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.data.is_some())
+            .collect::<Vec<_>>();
+        let emitted = valid
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| **e)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(emitted, [2, 3, 4, 5, 7, 8, 9, 10, 12, 13, 14]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dropped_when_not_ready() {
+        // Words presented on every clock, regardless of ready,
+        // are dropped when the buffer is full
+        let uut = Gearbox::<U10, U8>::default();
+        let mut session = SimSession::new(&uut);
+        session.reset();
+        let input = words(10, 10);
+        let mut accepted = vec![];
+        let mut output = vec![];
+        for &word in &input {
+            let o = session.step(Some(b10(word)));
+            if o.ready {
+                accepted.push(word);
+            }
+            output.extend(o.data.map(|x| x.raw()));
+        }
+        for _ in 0..4 {
+            output.extend(session.step(None).data.map(|x| x.raw()));
+        }
+        assert_eq!(accepted.len(), 9);
+        assert_eq!(output, reference(&accepted, 10, 8));
+    }
+
+    #[test]
+    fn test_gearbox_hdl() -> miette::Result<()> {
+        let uut = Gearbox::<U10, U8>::default();
+        let input = words(40, 10)
+            .into_iter()
+            .enumerate()
+            .map(|(n, x)| (n % 7 != 3).then(|| b10(x)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Gray Code Counter
+//!
+//! A counter whose output is Gray coded, so that only one bit of the
+//! output changes on each increment (e.g., for a FIFO pointer that is
+//! passed through bit synchronizers to another clock domain).  The count
+//! is kept in binary internally, and advances by one on each clock when
+//! `enable` is high.  The output is the [Gray] code of the current count
+//! (i.e., `bin ^ (bin >> 1)`), and is decoded back to binary with
+//! [gray_decode](crate::gray::decode::gray_decode).  The count wraps
+//! from all ones to zero, which is also a single bit change.  On reset,
+//! the count is cleared.
+//!
+//!# Example
+//!
+//! A 3 bit counter.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::gray_counter::GrayCounter;
+//!
+//! let uut = GrayCounter::<U3>::default();
+//! let output = uut
+//!     .run(std::iter::repeat_n(true, 9).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2 .0.raw())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(
+//!     output,
+//!     [0b000, 0b001, 0b011, 0b010, 0b110, 0b111, 0b101, 0b100, 0b000]
+//! );
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+use crate::gray::{encode::gray_code, Gray};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The Gray code counter core
+///   `N` is the bitwidth of the counter
+pub struct GrayCounter<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for GrayCounter<N> {
+    fn default() -> Self {
+        Self {
+            count: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for GrayCounter<N> {
+    type I = bool;
+    type O = Gray<N>;
+    type Kernel = gray_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn gray_counter_kernel<N: BitWidth>(cr: ClockReset, enable: bool, q: Q<N>) -> (Gray<N>, D<N>) {
+    let mut d = D::<N> { count: q.count };
+    if enable {
+        d.count = q.count + 1;
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+    }
+    (gray_code::<N>(q.count), d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gray::decode::gray_decode;
+
+    fn run(input: Vec<bool>) -> miette::Result<Vec<Gray<U5>>> {
+        let uut = GrayCounter::<U5>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_one_bit_changes_over_a_wrap() -> miette::Result<()> {
+        let output = run(vec![true; 70])?;
+        assert!(output.windows(2).all(|x| {
+            let changed = x[0].0 ^ x[1].0;
+            changed.to_bools().into_iter().filter(|b| *b).count() == 1
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decodes_to_plain_count() -> miette::Result<()> {
+        let input = (0..100).map(|n| n % 5 != 2).collect::<Vec<_>>();
+        let mut count = 0;
+        let expected = input
+            .iter()
+            .map(|&enable| {
+                let now = count;
+                if enable {
+                    count = (count + 1) % 32;
+                }
+                now
+            })
+            .collect::<Vec<_>>();
+        let decoded = run(input)?
+            .into_iter()
+            .map(|g| gray_decode::<U5>(g).raw())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gray_counter_hdl() -> miette::Result<()> {
+        let uut = GrayCounter::<U5>::default();
+        let input = (0..80).map(|n| n % 7 != 3);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+//! Johnson (Twisted Ring) Counter
+//!
+//! A shift register that feeds the inverted MSB back into the LSB, so
+//! that `N` flops step through `2N` states, one bit changing at a
+//! time.  From reset (all zeros), the register fills with ones from
+//! the LSB up, and then empties again:
+//!
+//!```text
+//! 000 -> 001 -> 011 -> 111 -> 110 -> 100 -> 000 -> ...
+//!```
+//!
+//! Each state can be decoded without glitches by looking at two
+//! adjacent bits, which makes the counter handy for generating clock
+//! phases (e.g., quadrature phases with `N = 2`).  The register moves
+//! on each enabled clock, and holds when `enable` is low.  Alongside
+//! the register, the counter keeps the index of the current state (the
+//! phase), which runs from `0` to `2N - 1`.  On reset, both are cleared.
+//!
+//! The input is the enable, and the output is the register and the
+//! phase.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::johnson_counter::JohnsonCounter;
+//!
+//! let uut = JohnsonCounter::<U3>::default();
+//! let output = uut
+//!     .run(std::iter::repeat_n(true, 7).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.state)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [0b000, 0b001, 0b011, 0b111, 0b110, 0b100, 0b000].map(b3));
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [JohnsonCounter]
+pub struct Out<N: BitWidth> {
+    /// The contents of the register
+    pub state: Bits<N>,
+    /// The index of the current state, from `0` to `2N - 1`
+    pub phase: b8,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The Johnson counter core
+///   `N` is the number of bits in the register (at most 128)
+pub struct JohnsonCounter<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    phase: dff::DFF<b8>,
+    last: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> Default for JohnsonCounter<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+            phase: dff::DFF::new(b8(0)),
+            last: constant::Constant::new(bits(2 * N::BITS as u128 - 1)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for JohnsonCounter<N> {
+    type I = bool;
+    type O = Out<N>;
+    type Kernel = johnson_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn johnson_counter_kernel<N: BitWidth>(
+    cr: ClockReset,
+    enable: bool,
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let mut d = D::<N> {
+        reg: q.reg,
+        phase: q.phase,
+        last: (),
+    };
+    if enable {
+        d.reg = q.reg << 1;
+        if q.reg & (1 << (N::BITS - 1)) == 0 {
+            d.reg |= 1;
+        }
+        d.phase = if q.phase == q.last {
+            bits(0)
+        } else {
+            q.phase + 1
+        };
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+        d.phase = bits(0);
+    }
+    let o = Out::<N> {
+        state: q.reg,
+        phase: q.phase,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run<N: BitWidth>(input: Vec<bool>) -> miette::Result<Vec<Out<N>>> {
+        let uut = JohnsonCounter::<N>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // Walk through two full cycles, and check the states against
+    // the expected sequence of `2N` states
+    fn check_sequence<N: BitWidth>(expected: &[u128]) -> miette::Result<()> {
+        assert_eq!(expected.len(), 2 * N::BITS);
+        let output = run::<N>(vec![true; 4 * N::BITS + 1])?;
+        for (n, o) in output.iter().enumerate() {
+            let phase = n % (2 * N::BITS);
+            assert_eq!(o.state, bits(expected[phase]));
+            assert_eq!(o.phase, b8(phase as u128));
+        }
+        // Consecutive states differ in a single bit
+        for pair in output.windows(2) {
+            assert_eq!((pair[0].state ^ pair[1].state).raw().count_ones(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequence_3() -> miette::Result<()> {
+        check_sequence::<U3>(&[0b000, 0b001, 0b011, 0b111, 0b110, 0b100])
+    }
+
+    #[test]
+    fn test_sequence_4() -> miette::Result<()> {
+        check_sequence::<U4>(&[
+            0b0000, 0b0001, 0b0011, 0b0111, 0b1111, 0b1110, 0b1100, 0b1000,
+        ])
+    }
+
+    #[test]
+    fn test_hold_when_disabled() -> miette::Result<()> {
+        let output = run::<U3>(vec![true, false, true, false, false, true])?;
+        let states = output.iter().map(|o| o.state.raw()).collect::<Vec<_>>();
+        assert_eq!(states, [0b000, 0b001, 0b001, 0b011, 0b011, 0b011]);
+        let phases = output.iter().map(|o| o.phase.raw()).collect::<Vec<_>>();
+        assert_eq!(phases, [0, 1, 1, 2, 2, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_johnson_counter_hdl() -> miette::Result<()> {
+        let uut = JohnsonCounter::<U4>::default();
+        let input = (0..40).map(|n| n % 3 != 0);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
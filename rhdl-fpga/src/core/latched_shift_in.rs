@@ -0,0 +1,227 @@
+//! Shift In Register with a latched output (serial in, parallel out)
+//!
+//! A [ShiftRegister](super::shift_reg::ShiftRegister) whose parallel
+//! output only changes when a whole word has arrived.  Bits are shifted
+//! in, MSB first, one per enabled clock, and an internal counter counts
+//! them.  When the `N`th bit of a word is shifted in, the completed word
+//! is captured in a second register, which drives the `data` output, and
+//! `updated` pulses for one clock.  Both appear on the clock after the
+//! last bit of the word is shifted in.  Between updates, `data` holds the
+//! last complete word, rather than the partial word being shifted in
+//! (compare the [ShiftRegisterWord](super::shift_reg_word::ShiftRegisterWord),
+//! whose output changes on every shift).
+//!
+//! The shift register keeps running while the word is captured, so the
+//! first bit of the next word can be shifted in on the clock after the
+//! last bit of the previous one.  On reset, the partial word being
+//! shifted in is discarded, the latched word is cleared, and the next
+//! bit shifted in is the first bit of a word.
+//!
+//! The input is a tuple of `(enable, serial_in)`.
+//!
+//!# Example
+//!
+//! Receiving the byte `0x5A`.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::latched_shift_in::LatchedShiftIn;
+//!
+//! let uut = LatchedShiftIn::<U8>::default();
+//! let input = (0..8)
+//!     .rev()
+//!     .map(|n| (true, 0x5A & (1 << n) != 0))
+//!     .chain(std::iter::repeat_n((false, false), 2));
+//! let data = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| (t.value.2.data.raw(), t.value.2.updated))
+//!     .collect::<Vec<_>>();
+//! assert!(data[..8].iter().all(|&d| d == (0, false)));
+//! assert_eq!(data[8..], [(0x5A, true), (0x5A, false)]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff, shift_reg::ShiftRegister};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [LatchedShiftIn]
+pub struct Out<N: BitWidth> {
+    /// The last complete word received
+    pub data: Bits<N>,
+    /// High for one clock when `data` has been updated
+    pub updated: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The shift in register with a latched output
+///   `N` is the number of bits in a word
+pub struct LatchedShiftIn<N: BitWidth> {
+    reg: ShiftRegister<N>,
+    count: dff::DFF<b8>,
+    latch: dff::DFF<Bits<N>>,
+    updated: dff::DFF<bool>,
+    last: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> Default for LatchedShiftIn<N> {
+    fn default() -> Self {
+        Self {
+            reg: ShiftRegister::default(),
+            count: dff::DFF::new(b8(0)),
+            latch: dff::DFF::new(Bits::<N>::default()),
+            updated: dff::DFF::new(false),
+            last: constant::Constant::new(bits(N::BITS as u128 - 1)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for LatchedShiftIn<N> {
+    type I = (bool, bool);
+    type O = Out<N>;
+    type Kernel = latched_shift_in_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn latched_shift_in_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (enable, serial_in) = i;
+    let mut d = D::<N> {
+        reg: (enable, false, serial_in, bits(0)),
+        count: q.count,
+        latch: q.latch,
+        updated: false,
+        last: (),
+    };
+    if enable {
+        if q.count == q.last {
+            // Capture the word as it will be after this shift
+            let mut word = q.reg << 1;
+            if serial_in {
+                word |= 1;
+            }
+            d.latch = word;
+            d.updated = true;
+            d.count = bits(0);
+        } else {
+            d.count = q.count + 1;
+        }
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+        d.updated = false;
+    }
+    let o = Out::<N> {
+        data: q.latch,
+        updated: q.updated,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bits of the bytes, MSB first
+    fn serialize(bytes: &[u8]) -> Vec<(bool, bool)> {
+        bytes
+            .iter()
+            .flat_map(|&byte| (0..8).rev().map(move |n| (true, byte & (1 << n) != 0)))
+            .collect()
+    }
+
+    fn run(input: Vec<(bool, bool)>) -> miette::Result<Vec<Out<U8>>> {
+        Ok(LatchedShiftIn::<U8>::default()
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_output_stable_between_words() -> miette::Result<()> {
+        let bytes = [0xA5, 0x3C, 0xFF, 0x00, 0x81];
+        let mut input = serialize(&bytes);
+        input.push((false, false));
+        let output = run(input)?;
+        // Each word is held for 8 clocks (the words are back to back)
+        for (ndx, byte) in bytes.iter().enumerate() {
+            let start = 8 * (ndx + 1);
+            let held = &output[start..(start + 8).min(output.len())];
+            assert!(held.iter().all(|o| o.data.raw() == *byte as u128));
+            assert!(held[0].updated);
+            assert!(held[1..].iter().all(|o| !o.updated));
+        }
+        assert!(output[..8].iter().all(|o| o.data == b8(0) && !o.updated));
+        Ok(())
+    }
+
+    #[test]
+    fn test_enable_gaps() -> miette::Result<()> {
+        let input = serialize(&[0x69, 0x96])
+            .into_iter()
+            .flat_map(|i| [i, (false, true)])
+            .collect::<Vec<_>>();
+        let output = run(input)?;
+        let updates = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.updated)
+            .map(|(ndx, o)| (ndx, o.data.raw()))
+            .collect::<Vec<_>>();
+        assert_eq!(updates, [(15, 0x69), (31, 0x96)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_discards_partial_word() -> miette::Result<()> {
+        let uut = LatchedShiftIn::<U8>::default();
+        // A full word, then half of one, then a reset.  The next word
+        // is received whole, without the stale bits.
+        let mut first = serialize(&[0xC3]);
+        first.extend(serialize(&[0xFF])[..4].iter().copied());
+        let mut second = serialize(&[0x24]);
+        second.push((false, false));
+        let input = first
+            .into_iter()
+            .with_reset(1)
+            .chain(second.into_iter().with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let updates = output
+            .iter()
+            .filter(|o| o.updated)
+            .map(|o| o.data.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(updates, [0xC3, 0x24]);
+        // The latched word is cleared by the reset
+        assert_eq!(output[13].data, b8(0xC3));
+        assert!(output[14..22].iter().all(|o| o.data == b8(0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_latched_shift_in_hdl() -> miette::Result<()> {
+        let uut = LatchedShiftIn::<U8>::default();
+        let input = (0..60).map(|n| (n % 6 != 5, (n * 5) % 7 < 3));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
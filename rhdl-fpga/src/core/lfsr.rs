@@ -0,0 +1,279 @@
+//! Linear Feedback Shift Register
+//!
+//! A pseudo-random bit generator (for scramblers, test patterns and
+//! the like), in either of the two classic forms.  The polynomial is
+//! given as a mask of taps, in which bit `k` stands for the term
+//! `x^(k+1)` (the constant term is implied).  So the maximal length
+//! polynomial `x^8 + x^6 + x^5 + x^4 + 1` is given by the taps
+//! `0b1011_1000`.  The top bit of the taps (the `x^N` term) must be
+//! set, and the seed must not be zero.
+//!
+//! - In the [Feedback::Fibonacci] form (the default), the register
+//!   shifts left on each enabled clock, and the parity of the tapped
+//!   bits enters at the LSB.  The serial output is the MSB.
+//! - In the [Feedback::Galois] form, the register shifts right on each
+//!   enabled clock, and when the bit leaving at the LSB is a one, the
+//!   taps are XORed into the register.  The serial output is the LSB.
+//!
+//! For the polynomial above, starting from a seed of `1`, the states
+//! after each clock are:
+//!
+//!```text
+//! Fibonacci: 0x02 0x04 0x08 0x11 0x23 0x47 0x8e 0x1c ...
+//! Galois:    0xb8 0x5c 0x2e 0x17 0xb3 0xe1 0xc8 0x64 ...
+//!```
+//!
+//! Both forms step through all `2^N - 1` non-zero states when the
+//! polynomial is primitive.  With the top tap set, neither form can
+//! reach the all zero state from any other state.  As a safeguard, the
+//! register reloads the seed if it is ever found to be zero (e.g., if
+//! it was never reset).  On reset, the register returns to the seed.
+//!
+//! The input is the enable, and the output is the serial bit and the
+//! state of the register.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::lfsr::Lfsr;
+//!
+//! let uut = Lfsr::<U8>::new(b8(0b1011_1000), b8(1));
+//! let output = uut
+//!     .run(std::iter::repeat_n(true, 4).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.state)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [b8(0x01), b8(0x02), b8(0x04), b8(0x08)]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The structure of the feedback of the [Lfsr]
+pub enum Feedback {
+    /// Shift left, with the parity of the taps entering at the LSB
+    #[default]
+    Fibonacci,
+    /// Shift right, with the taps XORed in when a one leaves the LSB
+    Galois,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [Lfsr]
+pub struct Out<N: BitWidth> {
+    /// The serial output (the bit that leaves the register next)
+    pub serial: bool,
+    /// The state of the register
+    pub state: Bits<N>,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+#[doc(hidden)]
+pub struct Config<N: BitWidth> {
+    feedback: Feedback,
+    taps: Bits<N>,
+    seed: Bits<N>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The LFSR core
+///   `N` is the number of bits in the register
+pub struct Lfsr<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    config: constant::Constant<Config<N>>,
+}
+
+impl<N: BitWidth> Lfsr<N> {
+    /// Create a Fibonacci LFSR with the given taps, that starts
+    /// from (and resets to) the given seed
+    pub fn new(taps: Bits<N>, seed: Bits<N>) -> Self {
+        assert!(
+            taps.raw() & (1 << (N::BITS - 1)) != 0,
+            "The top bit of the taps must be set"
+        );
+        assert!(seed.raw() != 0, "The seed must not be zero");
+        Self {
+            reg: dff::DFF::new(seed),
+            config: constant::Constant::new(Config {
+                feedback: Feedback::Fibonacci,
+                taps,
+                seed,
+            }),
+        }
+    }
+    /// Select the structure of the feedback
+    pub fn with_feedback(self, feedback: Feedback) -> Self {
+        let config = self.config.value();
+        Self {
+            config: constant::Constant::new(Config { feedback, ..config }),
+            ..self
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for Lfsr<N> {
+    type I = bool;
+    type O = Out<N>;
+    type Kernel = lfsr_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn lfsr_kernel<N: BitWidth>(cr: ClockReset, enable: bool, q: Q<N>) -> (Out<N>, D<N>) {
+    let mut d = D::<N> {
+        reg: q.reg,
+        config: (),
+    };
+    let msb = q.reg & (1 << (N::BITS - 1)) != 0;
+    let lsb = q.reg & 1 != 0;
+    let serial = match q.config.feedback {
+        Feedback::Fibonacci => msb,
+        Feedback::Galois => lsb,
+    };
+    if q.reg == 0 {
+        d.reg = q.config.seed;
+    } else if enable {
+        match q.config.feedback {
+            Feedback::Fibonacci => {
+                d.reg = q.reg << 1;
+                if (q.reg & q.config.taps).xor() {
+                    d.reg |= 1;
+                }
+            }
+            Feedback::Galois => {
+                d.reg = q.reg >> 1;
+                if lsb {
+                    d.reg ^= q.config.taps;
+                }
+            }
+        }
+    }
+    if cr.reset.any() {
+        d.reg = q.config.seed;
+    }
+    let o = Out::<N> {
+        serial,
+        state: q.reg,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAPS: b8 = b8(0b1011_1000);
+
+    fn states(uut: &Lfsr<U8>, clocks: usize) -> miette::Result<Vec<Out<U8>>> {
+        Ok(uut
+            .run(
+                std::iter::repeat_n(true, clocks)
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // Every non-zero state once, and then back to the seed
+    fn check_maximal_length(uut: &Lfsr<U8>, seed: u128) -> miette::Result<()> {
+        let output = states(uut, 256)?;
+        let mut seen = output[..255]
+            .iter()
+            .map(|o| o.state.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(seen[0], seed);
+        assert_eq!(output[255].state.raw(), seed);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen, (1..=255).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fibonacci_maximal_length() -> miette::Result<()> {
+        check_maximal_length(&Lfsr::new(TAPS, b8(0x5A)), 0x5A)
+    }
+
+    #[test]
+    fn test_galois_maximal_length() -> miette::Result<()> {
+        check_maximal_length(
+            &Lfsr::new(TAPS, b8(0x5A)).with_feedback(Feedback::Galois),
+            0x5A,
+        )
+    }
+
+    #[test]
+    fn test_documented_sequences() -> miette::Result<()> {
+        let fibonacci = states(&Lfsr::new(TAPS, b8(1)), 9)?;
+        let galois = states(&Lfsr::new(TAPS, b8(1)).with_feedback(Feedback::Galois), 9)?;
+        let raw = |output: &[Out<U8>]| output.iter().map(|o| o.state.raw()).collect::<Vec<_>>();
+        assert_eq!(
+            raw(&fibonacci),
+            [0x01, 0x02, 0x04, 0x08, 0x11, 0x23, 0x47, 0x8e, 0x1c]
+        );
+        assert_eq!(
+            raw(&galois),
+            [0x01, 0xb8, 0x5c, 0x2e, 0x17, 0xb3, 0xe1, 0xc8, 0x64]
+        );
+        // The serial output is the bit that leaves the register
+        assert!(fibonacci
+            .iter()
+            .all(|o| o.serial == (o.state.raw() & 0x80 != 0)));
+        assert!(galois.iter().all(|o| o.serial == (o.state.raw() & 1 != 0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_reset_and_recovery() -> miette::Result<()> {
+        let uut = Lfsr::new(TAPS, b8(0x81)).with_feedback(Feedback::Galois);
+        // Without a reset the register starts out empty, and reloads
+        // the seed.  A later reset also returns it to the seed.
+        let input = [true, false, true, false, true]
+            .into_iter()
+            .without_reset()
+            .chain([true, true].into_iter().with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2.state.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(output, [0x00, 0x81, 0x81, 0xf8, 0xf8, 0x7c, 0x81, 0xf8]);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_seed() {
+        let _ = Lfsr::new(TAPS, b8(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_missing_top_tap() {
+        let _ = Lfsr::new(b8(0b0011_1000), b8(1));
+    }
+
+    #[test]
+    fn test_lfsr_hdl() -> miette::Result<()> {
+        for feedback in [Feedback::Fibonacci, Feedback::Galois] {
+            let uut = Lfsr::new(TAPS, b8(0x33)).with_feedback(feedback);
+            let input = (0..40).map(|n| n % 3 != 0);
+            let tb = uut
+                .run(input.with_reset(1).clock_pos_edge(100))?
+                .collect::<SynchronousTestBench<_, _>>();
+            let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+            let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+        }
+        Ok(())
+    }
+}
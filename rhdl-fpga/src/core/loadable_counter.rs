@@ -0,0 +1,197 @@
+//! Loadable Counter
+//!
+//! A counter that can be preset to any value, for building down
+//! counters (load a length, and count down to zero) and address
+//! generators (load a base address, and count up from it).  On clocks
+//! when `load` is high, the count is set to `value`.  Otherwise, on
+//! clocks when `enable` is high, it counts up by one, or down by one if
+//! `down` is high.  So `load` takes priority over `enable`, as it does
+//! for the [ShiftOut](super::shift_out::ShiftOut).  The count wraps in
+//! both directions, from all ones to zero counting up, and from zero to
+//! all ones counting down.  On reset, the count is cleared.
+//!
+//! The `zero` output is high whenever the count is zero, so with a down
+//! count it marks the end of a run (without waiting for the wrap).
+//!
+//! The input is a tuple of `(enable, load, value, down)`, and the
+//! outputs are the current count and the `zero` flag (so a load or a
+//! step shows up on the next clock).
+//!
+//!# Example
+//!
+//! Load a count of 3, and count down to zero.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::loadable_counter::LoadableCounter;
+//!
+//! let uut = LoadableCounter::<U4>::default();
+//! let input = std::iter::once((false, true, b4(3), false))
+//!     .chain(std::iter::repeat_n((true, false, b4(0), true), 3));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| (t.value.2.count.raw(), t.value.2.zero))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [(3, false), (2, false), (1, false)]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [LoadableCounter]
+pub struct Out<N: BitWidth> {
+    /// The current count
+    pub count: Bits<N>,
+    /// High when the count is zero
+    pub zero: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The loadable counter core
+///   `N` is the bitwidth of the counter
+pub struct LoadableCounter<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for LoadableCounter<N> {
+    fn default() -> Self {
+        Self {
+            count: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for LoadableCounter<N> {
+    type I = (bool, bool, Bits<N>, bool);
+    type O = Out<N>;
+    type Kernel = loadable_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn loadable_counter_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, Bits<N>, bool),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (enable, load, value, down) = i;
+    let mut d = D::<N> { count: q.count };
+    if load {
+        d.count = value;
+    } else if enable {
+        if down {
+            d.count = q.count - 1;
+        } else {
+            d.count = q.count + 1;
+        }
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+    }
+    let o = Out::<N> {
+        count: q.count,
+        zero: q.count == 0,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type In = (bool, bool, Bits<U4>, bool);
+
+    fn run(input: &[In]) -> miette::Result<Vec<Out<U4>>> {
+        let uut = LoadableCounter::<U4>::default();
+        Ok(uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn counts(output: &[Out<U4>]) -> Vec<u128> {
+        output.iter().map(|o| o.count.raw()).collect()
+    }
+
+    const UP: In = (true, false, b4(0), false);
+    const DOWN: In = (true, false, b4(0), true);
+    const HOLD: In = (false, false, b4(0), false);
+
+    fn load(value: u128) -> In {
+        (false, true, bits(value), false)
+    }
+
+    #[test]
+    fn test_load_over_count() -> miette::Result<()> {
+        // Loading while enabled, in either direction, takes the value
+        let input = [
+            UP,
+            UP,
+            (true, true, b4(9), false),
+            UP,
+            (true, true, b4(4), true),
+            DOWN,
+            HOLD,
+        ];
+        let output = run(&input)?;
+        assert_eq!(counts(&output), [0, 1, 2, 9, 10, 4, 3]);
+        // And a load holds no matter the enable
+        let input = [load(6), HOLD, load(6), HOLD];
+        let output = run(&input)?;
+        assert_eq!(counts(&output), [0, 6, 6, 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_down_to_zero() -> miette::Result<()> {
+        let mut input = vec![load(5)];
+        input.extend([DOWN; 5]);
+        input.extend([HOLD; 3]);
+        let output = run(&input)?;
+        assert_eq!(counts(&output), [0, 5, 4, 3, 2, 1, 0, 0, 0]);
+        let zero = output.iter().map(|o| o.zero).collect::<Vec<_>>();
+        assert_eq!(
+            zero,
+            [true, false, false, false, false, false, true, true, true]
+        );
+        // The enable gates the count, and the flag follows it
+        let input = [load(2), DOWN, HOLD, HOLD, DOWN, HOLD];
+        let output = run(&input)?;
+        assert_eq!(counts(&output), [0, 2, 1, 1, 1, 0]);
+        assert!(output.iter().all(|o| o.zero == (o.count == 0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_both_ways() -> miette::Result<()> {
+        let mut input = vec![load(14)];
+        input.extend([UP; 4]);
+        input.extend([DOWN; 4]);
+        let output = run(&input)?;
+        assert_eq!(counts(&output), [0, 14, 15, 0, 1, 2, 1, 0, 15]);
+        // Counting down from zero after reset
+        let output = run(&[DOWN, DOWN, UP, UP])?;
+        assert_eq!(counts(&output), [0, 15, 14, 15]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_loadable_counter_hdl() -> miette::Result<()> {
+        let uut = LoadableCounter::<U4>::default();
+        let input = (0..80).map(|n| (n % 4 != 3, n % 17 == 0, bits(n % 16), n > 40));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Manchester Encoder
+//!
+//! Encodes words onto a line with Manchester (Biphase-L) coding, in
+//! which each bit is sent as two half-bit symbols, with a transition in
+//! the middle of every bit.  The first half of each bit is the bit
+//! itself, and the second half is its complement, so that a one is sent
+//! as high then low, and a zero as low then high.  (IEEE 802.3 uses the
+//! opposite convention, which is had by inverting the line.)
+//!
+//! The core is clocked at twice the bit rate, so each clock is one half
+//! of a bit.  A half-bit phase flop tracks which half is being sent, and
+//! the bits of the word come from a [ShiftOut](super::shift_out::ShiftOut),
+//! MSB first, which shifts once the second half of a bit has been sent.
+//! Words are presented on the input as an `Option`, so that single bits
+//! can be sent with `N = 1` (i.e., as a bit and valid pair).
+//!
+//! A word presented when `ready` is high is taken on that clock.  The
+//! core is ready when it is idle, and on the clock that sends the last
+//! half of the last bit of a word, so that words presented as soon as
+//! the core is ready are sent back to back, without a gap.  A word
+//! presented when the core is not ready is ignored.  When no word is
+//! being sent, the line is held at the idle level given at construction
+//! (low for the [Default]).  On reset, the word being sent is abandoned,
+//! and the line goes idle.
+//!
+//!# Example
+//!
+//! Sending the nibble `0b1001`, with the line idling low.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::manchester::ManchesterEncode;
+//!
+//! let uut = ManchesterEncode::<U4>::default();
+//! let input = std::iter::once(Some(b4(0b1001))).chain(std::iter::repeat_n(None, 9));
+//! let line = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.line as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(line, [0, 1, 0, 0, 1, 0, 1, 1, 0, 0]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff, shift_out::ShiftOut};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ManchesterEncode]
+pub struct Out {
+    /// The line level
+    pub line: bool,
+    /// High while a word is being sent
+    pub busy: bool,
+    /// High when a word presented on the input will be taken
+    pub ready: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The Manchester encoder core
+///   `N` is the number of bits in a word
+pub struct ManchesterEncode<N: BitWidth> {
+    shift: ShiftOut<N>,
+    count: dff::DFF<b8>,
+    phase: dff::DFF<bool>,
+    width: constant::Constant<b8>,
+    idle: constant::Constant<bool>,
+}
+
+impl<N: BitWidth> ManchesterEncode<N> {
+    /// Create an encoder whose line idles at the given level
+    pub fn new(idle: bool) -> Self {
+        Self {
+            shift: ShiftOut::default(),
+            count: dff::DFF::new(b8(0)),
+            phase: dff::DFF::new(false),
+            width: constant::Constant::new(bits(N::BITS as u128)),
+            idle: constant::Constant::new(idle),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for ManchesterEncode<N> {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ManchesterEncode<N> {
+    type I = Option<Bits<N>>;
+    type O = Out;
+    type Kernel = manchester_encode_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn manchester_encode_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: Option<Bits<N>>,
+    q: Q<N>,
+) -> (Out, D<N>) {
+    let busy = q.count != 0;
+    // The second half of a bit is being sent, so shift on to the next
+    let shift = busy && q.phase;
+    let ready = !busy || (shift && q.count == 1);
+    let mut d = D::<N> {
+        shift: (shift, false, bits(0)),
+        count: q.count,
+        phase: busy && !q.phase,
+        width: (),
+        idle: (),
+    };
+    if shift {
+        d.count = q.count - 1;
+    }
+    if let Some(word) = i {
+        if ready {
+            d.shift = (shift, true, word);
+            d.count = q.width;
+            d.phase = false;
+        }
+    }
+    if cr.reset.any() {
+        d.count = b8(0);
+        d.phase = false;
+    }
+    let o = Out {
+        line: if busy { q.shift ^ q.phase } else { q.idle },
+        busy,
+        ready,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(uut: &ManchesterEncode<U8>, input: Vec<Option<b8>>) -> miette::Result<Vec<u8>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.line as u8)
+            .collect())
+    }
+
+    // The two half-bit symbols of each bit of a byte
+    fn symbols(data: u8) -> Vec<u8> {
+        (0..8)
+            .rev()
+            .flat_map(|n| {
+                let bit = (data >> n) & 1;
+                [bit, 1 - bit]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_known_byte() -> miette::Result<()> {
+        let mut input = vec![None, None, Some(b8(0b1100_1010))];
+        input.extend(std::iter::repeat_n(None, 19));
+        let output = line(&ManchesterEncode::new(true), input)?;
+        // Idle high, then the byte (taken on the third clock), then idle
+        #[rustfmt::skip]
+        let expected = [
+            1, 1, 1,
+            1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1,
+            1, 1, 1,
+        ];
+        assert_eq!(output, expected);
+        assert_eq!(output[3..19], symbols(0b1100_1010));
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_transitions() -> miette::Result<()> {
+        // Idling low, a byte starting with a zero starts with no edge
+        // from idle, and one ending in a one ends with no edge to idle.
+        let mut input = vec![None, Some(b8(0b0000_0001))];
+        input.extend(std::iter::repeat_n(None, 18));
+        let output = line(&ManchesterEncode::default(), input)?;
+        let mut expected = vec![0, 0];
+        expected.extend(symbols(0b0000_0001));
+        expected.extend([0, 0]);
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_back_to_back() -> miette::Result<()> {
+        // A word presented on every clock is taken when the core is
+        // ready, so the bytes go out without a gap
+        let uut = ManchesterEncode::<U8>::default();
+        let words = [0xA5_u8, 0x0F, 0x80];
+        let mut input = vec![];
+        for word in words {
+            input.extend(std::iter::repeat_n(Some(b8(word as u128)), 16));
+        }
+        input.extend(std::iter::repeat_n(None, 4));
+        let output = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let line = output.iter().map(|o| o.line as u8).collect::<Vec<_>>();
+        let mut expected = vec![0];
+        for word in words {
+            expected.extend(symbols(word));
+        }
+        expected.extend([0, 0, 0]);
+        assert_eq!(line, expected);
+        let ready = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.ready)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(ready, [0, 16, 32, 48, 49, 50, 51]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_bits() -> miette::Result<()> {
+        let uut = ManchesterEncode::<U1>::new(true);
+        let input = [
+            Some(b1(0)),
+            None,
+            Some(b1(1)),
+            None,
+            None,
+            Some(b1(0)),
+            None,
+            None,
+        ];
+        let line = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.line as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(line, [1, 0, 1, 1, 0, 1, 0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_manchester_hdl() -> miette::Result<()> {
+        let uut = ManchesterEncode::<U8>::new(true);
+        let input = (0..80).map(|n| (n % 11 < 3).then(|| b8(n * 3)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -1,9 +1,81 @@
 #![warn(missing_docs)]
 //! Core components (RAMs, DFF, constants, etc)
+//!
+//! The shift registers come in several variants.  Those that shift
+//! in serially, with a parallel output, are
+//!
+//! - [ShiftRegister](shift_reg::ShiftRegister), for streams that arrive MSB first,
+//! - [ShiftRegisterMsbIn](shift_reg_msb_in::ShiftRegisterMsbIn), for streams that
+//!   arrive LSB first,
+//! - [ShiftRegisterCe](shift_reg_ce::ShiftRegisterCe), with a clock enable strobe,
+//! - [ShiftRegisterTap](shift_reg_tap::ShiftRegisterTap), which brings out a single
+//!   bit of the chain,
+//! - [ShiftRegisterChained](shift_reg_chained::ShiftRegisterChained), which brings
+//!   out the bit that falls off the end, for daisy chains,
+//! - [ShiftRegisterWord](shift_reg_word::ShiftRegisterWord), which strobes when each
+//!   word is complete,
+//! - [LatchedShiftIn](latched_shift_in::LatchedShiftIn), whose output only changes
+//!   once a word is complete,
+//! - [VarShiftIn](var_shift_in::VarShiftIn), with a word length chosen at run time,
+//! - [ShiftInWide](shift_in_wide::ShiftInWide), which takes `K` bits per clock.
+//!
+//! Those that are loaded in parallel, and shift out serially, are
+//!
+//! - [ShiftOut](shift_out::ShiftOut), MSB or LSB first,
+//! - [ShiftOutCe](shift_out_ce::ShiftOutCe), with a clock enable strobe,
+//! - [ShiftOutCounted](shift_out_counted::ShiftOutCounted), which counts the bits
+//!   still to be sent,
+//! - [ShiftOutBuffered](shift_out_buffered::ShiftOutBuffered), which holds a
+//!   pending word,
+//! - [ShiftOutRotate](shift_out_rotate::ShiftOutRotate), which recirculates the word,
+//! - [ShiftOutWide](shift_out_wide::ShiftOutWide), which sends `K` bits per clock.
+pub mod bcd;
+pub mod bidir_shift;
 pub mod constant;
 pub mod counter;
+pub mod debounce;
 pub mod delay;
+pub mod deserializer;
 pub mod dff;
+pub mod edge_detect;
+pub mod edge_detect_bits;
+pub mod gearbox;
+pub mod gray_counter;
+pub mod johnson_counter;
+pub mod latched_shift_in;
+pub mod lfsr;
+pub mod loadable_counter;
+pub mod manchester;
+pub mod modulo_counter;
+pub mod nrzi;
+pub mod one_shot;
 pub mod option;
+pub mod pwm;
+pub mod pwm_center;
 pub mod ram;
+pub mod ring_counter;
+pub mod rollover_counter;
+pub mod sat_counter;
+pub mod scan;
+pub mod serial_crc;
+pub mod serializer;
+pub mod shift_in_wide;
+pub mod shift_out;
+pub mod shift_out_buffered;
+pub mod shift_out_ce;
+pub mod shift_out_counted;
+pub mod shift_out_rotate;
+pub mod shift_out_wide;
+pub mod shift_reg;
+pub mod shift_reg_ce;
+pub mod shift_reg_chained;
+pub mod shift_reg_msb_in;
+pub mod shift_reg_tap;
+pub mod shift_reg_word;
+pub mod signed_shift;
 pub mod slice;
+pub mod strobe_div;
+pub mod strobe_gen;
+pub mod universal_shift;
+pub mod up_down_counter;
+pub mod var_shift_in;
@@ -0,0 +1,209 @@
+//! Modulo-N Counter
+//!
+//! A counter that counts from `0` to `modulus - 1`, and then wraps back
+//! to `0` (e.g., for clock dividers and schedulers whose period is not
+//! a power of two).  The count advances on each clock when `enable` is
+//! high, and holds when it is low.  `terminal` is high while the count
+//! is on its last value (`modulus - 1`), so it can be combined with
+//! `enable` to cascade counters.  On reset, the count is cleared.
+//!
+//! The modulus is an input, so it can be changed at run time.  A
+//! modulus of `0` or `1` holds the count at `0`, with `terminal` always
+//! high.  If the modulus is reduced below the count, the count is
+//! treated as being on its last value, so that it wraps to `0` on the
+//! next enabled clock, rather than running up through the full range of
+//! the counter.
+//!
+//!# Example
+//!
+//! Counting modulo 3.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::modulo_counter::{In, ModuloCounter};
+//!
+//! let uut = ModuloCounter::<U4>::default();
+//! let input = std::iter::repeat_n(
+//!     In {
+//!         enable: true,
+//!         modulus: b4(3),
+//!     },
+//!     7,
+//! );
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| (t.value.2.count.raw(), t.value.2.terminal))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(
+//!     output,
+//!     [
+//!         (0, false),
+//!         (1, false),
+//!         (2, true),
+//!         (0, false),
+//!         (1, false),
+//!         (2, true),
+//!         (0, false)
+//!     ]
+//! );
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [ModuloCounter]
+pub struct In<N: BitWidth> {
+    /// Advance the count on this clock
+    pub enable: bool,
+    /// The number of values the counter steps through
+    pub modulus: Bits<N>,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ModuloCounter]
+pub struct Out<N: BitWidth> {
+    /// The current count
+    pub count: Bits<N>,
+    /// High while the count is on its last value
+    pub terminal: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The modulo counter core
+///   `N` is the bitwidth of the counter (and the modulus)
+pub struct ModuloCounter<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for ModuloCounter<N> {
+    fn default() -> Self {
+        Self {
+            count: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ModuloCounter<N> {
+    type I = In<N>;
+    type O = Out<N>;
+    type Kernel = modulo_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn modulo_counter_kernel<N: BitWidth>(cr: ClockReset, i: In<N>, q: Q<N>) -> (Out<N>, D<N>) {
+    let terminal = i.modulus == 0 || q.count >= i.modulus - 1;
+    let mut d = D::<N> { count: q.count };
+    if i.enable {
+        if terminal {
+            d.count = bits(0);
+        } else {
+            d.count = q.count + 1;
+        }
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+    }
+    let o = Out::<N> {
+        count: q.count,
+        terminal,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: Vec<(bool, u128)>) -> miette::Result<Vec<(u128, bool)>> {
+        let uut = ModuloCounter::<U4>::default();
+        let input = input.into_iter().map(|(enable, modulus)| In {
+            enable,
+            modulus: b4(modulus),
+        });
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| (t.value.2.count.raw(), t.value.2.terminal))
+            .collect())
+    }
+
+    #[test]
+    fn test_modulus_one_is_always_terminal() -> miette::Result<()> {
+        for modulus in [0, 1] {
+            let output = run(vec![(true, modulus); 10])?;
+            assert!(output.iter().all(|&o| o == (0, true)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_modulus_of_max() -> miette::Result<()> {
+        let output = run(vec![(true, 15); 40])?;
+        let counts = output.iter().map(|o| o.0).collect::<Vec<_>>();
+        let expected = (0..40).map(|n| n % 15).collect::<Vec<_>>();
+        assert_eq!(counts, expected);
+        let terminals = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.1)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(terminals, [14, 29]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_modulus_change_mid_run() -> miette::Result<()> {
+        // Count up to 9 modulo 12, then drop the modulus to 4
+        let mut input = vec![(true, 12); 9];
+        input.extend([(true, 4); 6]);
+        let output = run(input)?;
+        let counts = output.iter().map(|o| o.0).collect::<Vec<_>>();
+        assert_eq!(counts, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 0]);
+        // The count is above the new modulus, so it is terminal,
+        // and wraps on the next clock
+        assert_eq!(output[9], (9, true));
+        assert_eq!(output[13], (3, true));
+        assert_eq!(output.iter().filter(|o| o.1).count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_when_disabled() -> miette::Result<()> {
+        let input = vec![
+            (true, 5),
+            (true, 5),
+            (false, 5),
+            (false, 5),
+            (true, 5),
+            (true, 5),
+        ];
+        let output = run(input)?;
+        let counts = output.iter().map(|o| o.0).collect::<Vec<_>>();
+        assert_eq!(counts, [0, 1, 2, 2, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_modulo_counter_hdl() -> miette::Result<()> {
+        let uut = ModuloCounter::<U4>::default();
+        let input = (0..80).map(|n| In {
+            enable: n % 7 != 3,
+            modulus: b4(if n < 40 { 10 } else { 3 }),
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+//! NRZI Decoder
+//!
+//! Recovers bits from samples of an [NRZI](super) line.  On each
+//! enabled clock, the line sample is compared against the previous
+//! sample: a change decodes as the toggling symbol of the [Polarity],
+//! and no change as the other symbol.  The decoded bit is output on the
+//! same clock as the sample (as `Some`), and the sample is kept for the
+//! comparison with the next one.  When `enable` is low, the output is
+//! `None`, and the previous sample is kept.  On reset, the previous
+//! sample is taken to be low (the level of the
+//! [NrziEncode](super::encode::NrziEncode) after reset).
+//!
+//!# Example
+//!
+//! Decoding the line from the [NrziEncode](super::encode::NrziEncode)
+//! example.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::nrzi::decode::{In, NrziDecode};
+//!
+//! let uut = NrziDecode::default();
+//! let input = [0, 1, 0, 0, 1].map(|l| In {
+//!     enable: true,
+//!     line: l == 1,
+//! });
+//! let bits = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .filter_map(|t| t.value.2)
+//!     .map(|b| b as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(bits, [1, 0, 0, 1, 0]);
+//!```
+use rhdl::prelude::*;
+
+use super::Polarity;
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [NrziDecode]
+pub struct In {
+    /// Decode the line sample on this clock
+    pub enable: bool,
+    /// The line sample
+    pub line: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The NRZI decoder core
+pub struct NrziDecode {
+    previous: dff::DFF<bool>,
+    polarity: constant::Constant<Polarity>,
+}
+
+impl NrziDecode {
+    /// Create a decoder with the given polarity
+    pub fn new(polarity: Polarity) -> Self {
+        Self {
+            previous: dff::DFF::new(false),
+            polarity: constant::Constant::new(polarity),
+        }
+    }
+}
+
+impl Default for NrziDecode {
+    fn default() -> Self {
+        Self::new(Polarity::default())
+    }
+}
+
+impl SynchronousIO for NrziDecode {
+    type I = In;
+    type O = Option<bool>;
+    type Kernel = nrzi_decode_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn nrzi_decode_kernel(cr: ClockReset, i: In, q: Q) -> (Option<bool>, D) {
+    let toggled = i.line != q.previous;
+    let data = match q.polarity {
+        Polarity::ZeroToggles => !toggled,
+        Polarity::OneToggles => toggled,
+    };
+    let mut d = D {
+        previous: q.previous,
+        polarity: (),
+    };
+    let mut o = None;
+    if i.enable {
+        d.previous = i.line;
+        o = Some(data);
+    }
+    if cr.reset.any() {
+        d.previous = false;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::random;
+
+    use super::*;
+    use crate::core::nrzi::encode::{self, NrziEncode};
+
+    // Encode the bits, and decode the line, enabling both
+    // with the same (gappy) pattern
+    fn round_trip(polarity: Polarity, data: &[bool]) -> miette::Result<Vec<bool>> {
+        let mut input = vec![];
+        for (n, &bit) in data.iter().enumerate() {
+            if n % 3 == 1 {
+                input.push(encode::In::default());
+            }
+            input.push(encode::In {
+                enable: true,
+                data: bit,
+            });
+        }
+        input.push(encode::In::default());
+        let line = NrziEncode::new(polarity)
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The line for each bit appears on the clock after it was encoded
+        let samples = input
+            .iter()
+            .zip(line)
+            .map(|(i, line)| In {
+                enable: i.enable,
+                line,
+            })
+            .collect::<Vec<_>>();
+        Ok(NrziDecode::new(polarity)
+            .run(samples.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .filter_map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_round_trip_random_bytes() -> miette::Result<()> {
+        for polarity in [Polarity::ZeroToggles, Polarity::OneToggles] {
+            let bytes = (0..32).map(|_| random::<u8>()).collect::<Vec<_>>();
+            let data = bytes
+                .iter()
+                .flat_map(|b| (0..8).rev().map(move |n| b & (1 << n) != 0))
+                .collect::<Vec<_>>();
+            assert_eq!(round_trip(polarity, &data)?, data);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_static_line_decodes_as_run() -> miette::Result<()> {
+        let input = [true; 10].map(|line| In { enable: true, line });
+        let bits = NrziDecode::new(Polarity::OneToggles)
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .filter_map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The first sample is a change from the (low) reset level
+        assert!(bits[0]);
+        assert!(bits[1..].iter().all(|b| !b));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nrzi_decode_hdl() -> miette::Result<()> {
+        for polarity in [Polarity::ZeroToggles, Polarity::OneToggles] {
+            let uut = NrziDecode::new(polarity);
+            let input = (0..40).map(|n| In {
+                enable: n % 5 != 2,
+                line: (n * 7) % 3 == 0,
+            });
+            let tb = uut
+                .run(input.with_reset(1).clock_pos_edge(100))?
+                .collect::<SynchronousTestBench<_, _>>();
+            let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+            let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+        }
+        Ok(())
+    }
+}
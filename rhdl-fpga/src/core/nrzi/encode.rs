@@ -0,0 +1,163 @@
+//! NRZI Encoder
+//!
+//! Encodes bits as [NRZI](super) line levels.  On each enabled clock,
+//! the line toggles if the bit is the toggling symbol of the [Polarity],
+//! and holds otherwise.  The line level is held in a flop, so the level
+//! for a bit appears on the clock after it is presented.  When `enable`
+//! is low, the line holds its level.  On reset, the line is set low.
+//!
+//!# Example
+//!
+//! Encoding the bits `1, 0, 0, 1, 0` with a zero toggling the line.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::nrzi::encode::{In, NrziEncode};
+//!
+//! let uut = NrziEncode::default();
+//! let input = [1, 0, 0, 1, 0, 0].map(|b| In {
+//!     enable: true,
+//!     data: b == 1,
+//! });
+//! let line = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2 as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(line, [0, 1, 0, 0, 1]);
+//!```
+use rhdl::prelude::*;
+
+use super::Polarity;
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [NrziEncode]
+pub struct In {
+    /// Encode the bit on this clock
+    pub enable: bool,
+    /// The bit to encode
+    pub data: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The NRZI encoder core
+pub struct NrziEncode {
+    line: dff::DFF<bool>,
+    polarity: constant::Constant<Polarity>,
+}
+
+impl NrziEncode {
+    /// Create an encoder with the given polarity
+    pub fn new(polarity: Polarity) -> Self {
+        Self {
+            line: dff::DFF::new(false),
+            polarity: constant::Constant::new(polarity),
+        }
+    }
+}
+
+impl Default for NrziEncode {
+    fn default() -> Self {
+        Self::new(Polarity::default())
+    }
+}
+
+impl SynchronousIO for NrziEncode {
+    type I = In;
+    type O = bool;
+    type Kernel = nrzi_encode_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn nrzi_encode_kernel(cr: ClockReset, i: In, q: Q) -> (bool, D) {
+    let toggle = match q.polarity {
+        Polarity::ZeroToggles => !i.data,
+        Polarity::OneToggles => i.data,
+    };
+    let mut d = D {
+        line: q.line,
+        polarity: (),
+    };
+    if i.enable && toggle {
+        d.line = !q.line;
+    }
+    if cr.reset.any() {
+        d.line = false;
+    }
+    (q.line, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(polarity: Polarity, input: Vec<In>) -> miette::Result<Vec<bool>> {
+        Ok(NrziEncode::new(polarity)
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn bits(data: &[u8]) -> Vec<In> {
+        data.iter()
+            .map(|&b| In {
+                enable: true,
+                data: b == 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_runs_keep_line_static() -> miette::Result<()> {
+        // A long run of the holding symbol leaves the line alone, and a
+        // run of the toggling symbol toggles it on every bit
+        let mut input = bits(&[0]);
+        input.extend(bits(&[1; 20]));
+        input.extend(bits(&[0; 6]));
+        let output = line(Polarity::ZeroToggles, input)?;
+        assert!(!output[0]);
+        assert!(output[1..22].iter().all(|l| *l));
+        assert_eq!(output[22..], [false, true, false, true, false]);
+        let output = line(Polarity::OneToggles, bits(&[0; 20]))?;
+        assert!(output.iter().all(|l| !l));
+        Ok(())
+    }
+
+    #[test]
+    fn test_disabled_holds() -> miette::Result<()> {
+        let input = [true, false, true, false]
+            .map(|enable| In {
+                enable,
+                data: false,
+            })
+            .to_vec();
+        let output = line(Polarity::ZeroToggles, input)?;
+        assert_eq!(output, [false, true, true, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nrzi_encode_hdl() -> miette::Result<()> {
+        for polarity in [Polarity::ZeroToggles, Polarity::OneToggles] {
+            let uut = NrziEncode::new(polarity);
+            let input = (0..40).map(|n| In {
+                enable: n % 5 != 2,
+                data: (n * 7) % 3 == 0,
+            });
+            let tb = uut
+                .run(input.with_reset(1).clock_pos_edge(100))?
+                .collect::<SynchronousTestBench<_, _>>();
+            let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+            let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+//! NRZI (Non Return to Zero, Inverted) line coding
+//!
+//! In NRZI, bits are sent as changes of the line level, rather than as
+//! levels.  One of the symbols toggles the line, and the other holds it,
+//! so a long run of the holding symbol leaves the line static (which is
+//! why links like USB stuff bits into such runs).  The [Polarity] sets
+//! which symbol toggles the line.  The [encode::NrziEncode] core turns
+//! bits into line levels, and the [decode::NrziDecode] core recovers the
+//! bits from samples of the line.
+use rhdl::prelude::*;
+
+pub mod decode;
+pub mod encode;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The symbol that toggles the line
+pub enum Polarity {
+    /// A zero toggles the line, and a one holds it (as for USB)
+    #[default]
+    ZeroToggles,
+    /// A one toggles the line, and a zero holds it
+    OneToggles,
+}
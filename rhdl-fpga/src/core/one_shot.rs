@@ -0,0 +1,216 @@
+//! One Shot
+//!
+//! Turns a trigger into a pulse of a fixed length (e.g., to stretch a
+//! one clock strobe into a pulse long enough to see on an LED, or to
+//! guarantee a minimum pulse width).  When `trigger` is high, the
+//! output goes high on the next clock, and stays high for `width`
+//! clocks, timed by an internal down counter.  A `width` of `0` gives
+//! no pulse at all.
+//!
+//! A trigger that arrives while the output is high is handled according
+//! to the `retrigger` flag given at construction:
+//! - If `retrigger` is false, the trigger is ignored, so the pulse is
+//!   always `width` clocks long.  This includes a trigger on the last
+//!   clock of a pulse.
+//! - If `retrigger` is true, the count restarts, so the output stays
+//!   high until `width` clocks after the last trigger.  A trigger on the
+//!   last clock of a pulse extends it with no gap.
+//!
+//! Reset terminates the pulse immediately (i.e., the output is low
+//! while reset is asserted, and the count is cleared).
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::one_shot::OneShot;
+//!
+//! let uut = OneShot::<U4>::new(b4(3), false);
+//! let input = [1, 0, 1, 0, 0, 0, 0, 0];
+//! let output = uut
+//!     .run(input.map(|x| x == 1).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2 as u8)
+//!     .collect::<Vec<_>>();
+//! // The second trigger is ignored
+//! assert_eq!(output, [0, 1, 1, 1, 0, 0, 0, 0]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The one shot core
+///   `N` is the number of bits in the counter
+pub struct OneShot<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+    width: constant::Constant<Bits<N>>,
+    retrigger: constant::Constant<bool>,
+}
+
+impl<N: BitWidth> OneShot<N> {
+    /// Create a one shot with a pulse of `width` clocks, that
+    /// restarts the pulse on a trigger while it is active if
+    /// `retrigger` is set
+    pub fn new(width: Bits<N>, retrigger: bool) -> Self {
+        Self {
+            count: dff::DFF::default(),
+            width: constant::Constant::new(width),
+            retrigger: constant::Constant::new(retrigger),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for OneShot<N> {
+    fn default() -> Self {
+        Self::new(Bits::<N>::MAX, false)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for OneShot<N> {
+    type I = bool;
+    type O = bool;
+    type Kernel = one_shot_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn one_shot_kernel<N: BitWidth>(cr: ClockReset, trigger: bool, q: Q<N>) -> (bool, D<N>) {
+    let active = q.count != 0;
+    let mut d = D::<N> {
+        count: if active { q.count - 1 } else { bits(0) },
+        width: (),
+        retrigger: (),
+    };
+    if trigger && (!active || q.retrigger) {
+        d.count = q.width;
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+    }
+    (active && !cr.reset.any(), d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 5;
+
+    fn run(retrigger: bool, triggers: &[usize], len: usize) -> miette::Result<Vec<bool>> {
+        let uut = OneShot::<U4>::new(bits(WIDTH as u128), retrigger);
+        let input = (0..len).map(|n| triggers.contains(&n));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // The clocks on which the output is high
+    fn high(output: &[bool]) -> Vec<usize> {
+        output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| **o)
+            .map(|(ndx, _)| ndx)
+            .collect()
+    }
+
+    #[test]
+    fn test_single_pulse() -> miette::Result<()> {
+        for retrigger in [false, true] {
+            let output = run(retrigger, &[3], 20)?;
+            assert_eq!(high(&output), (4..4 + WIDTH).collect::<Vec<_>>());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_trigger_mid_pulse() -> miette::Result<()> {
+        // Ignored without retriggering, so the pulse keeps its width,
+        // and the next trigger after it ends starts a new one
+        let output = run(false, &[2, 4, 5, 10], 20)?;
+        assert_eq!(high(&output), [3, 4, 5, 6, 7, 11, 12, 13, 14, 15]);
+        // With retriggering, the pulse lasts until WIDTH clocks
+        // after the last trigger
+        let output = run(true, &[2, 4, 5, 10], 20)?;
+        assert_eq!(high(&output), (3..16).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trigger_on_last_cycle() -> miette::Result<()> {
+        // The pulse from a trigger on clock 2 is high on clocks 3 to 7
+        let output = run(false, &[2, 7], 20)?;
+        assert_eq!(high(&output), [3, 4, 5, 6, 7]);
+        let output = run(true, &[2, 7], 20)?;
+        assert_eq!(high(&output), (3..13).collect::<Vec<_>>());
+        // Just after the pulse ends, both modes start a new pulse
+        for retrigger in [false, true] {
+            let output = run(retrigger, &[2, 8], 20)?;
+            assert_eq!(high(&output), [3, 4, 5, 6, 7, 9, 10, 11, 12, 13]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_continuous_trigger() -> miette::Result<()> {
+        // Without retriggering, a held trigger gives a train of pulses
+        let output = run(false, &(0..20).collect::<Vec<_>>(), 20)?;
+        let expected = (0..20).map(|n| n > 0 && (n - 1) % (WIDTH + 1) < WIDTH);
+        assert_eq!(output, expected.collect::<Vec<_>>());
+        // With retriggering, the output just stays high
+        let output = run(true, &(0..20).collect::<Vec<_>>(), 20)?;
+        assert!(output[1..].iter().all(|o| *o));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_terminates_pulse() -> miette::Result<()> {
+        let uut = OneShot::<U4>::new(b4(10), false);
+        let input = [true, false, false]
+            .with_reset(1)
+            .chain([false, false].with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output, [false, false, true, true, false, false, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_width() -> miette::Result<()> {
+        let uut = OneShot::<U4>::new(b4(0), true);
+        let input = (0..10).map(|n| n % 3 == 0);
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert!(output.iter().all(|o| !*o));
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_shot_hdl() -> miette::Result<()> {
+        for retrigger in [false, true] {
+            let uut = OneShot::<U4>::new(b4(6), retrigger);
+            let input = (0..100).map(|n| n % 13 == 0 || n % 17 == 0);
+            let tb = uut
+                .run(input.with_reset(1).clock_pos_edge(100))?
+                .collect::<SynchronousTestBench<_, _>>();
+            let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+            let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! PWM Generator
+//!
+//! A pulse width modulator (e.g., for dimming an LED, or driving a
+//! servo).  A free running counter counts from `0` to `2^N - 2`, so that
+//! each period is `2^N - 1` clocks long, and the output is high while the
+//! count is less than the duty.  The output is thus high for `duty`
+//! clocks out of each period, so a duty of `0` gives a constant low,
+//! and a duty of all ones gives a constant high, with no glitch as the
+//! counter wraps.
+//!
+//! The `duty` input is sampled on the last clock of each period, and
+//! held in a register for the whole of the next period.  So a change of
+//! duty takes effect at the start of the next period, and never causes a
+//! runt pulse.  On reset, the counter starts on the last clock of a
+//! period, so the duty is sampled on the first clock after reset.
+//!
+//! For a complementary pair of outputs with dead time, use the
+//! [DeadtimePwm](crate::motion::deadtime_pwm::DeadtimePwm).
+//!
+//!# Example
+//!
+//! A duty of 5 out of 15.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::pwm::Pwm;
+//!
+//! let uut = Pwm::<U4>::default();
+//! let output = uut
+//!     .run(std::iter::repeat_n(b4(5), 32).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2 as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output[..15], [1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+//! assert_eq!(output[..15], output[15..30]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The PWM core
+///   `N` is the number of bits in the counter (and the duty)
+pub struct Pwm<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+    duty: dff::DFF<Bits<N>>,
+    last: constant::Constant<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for Pwm<N> {
+    fn default() -> Self {
+        let last = Bits::<N>::MAX - 1;
+        Self {
+            // Start on the last count of a period, so that the
+            // duty is loaded on the first clock
+            count: dff::DFF::new(last),
+            duty: dff::DFF::default(),
+            last: constant::Constant::new(last),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for Pwm<N> {
+    type I = Bits<N>;
+    type O = bool;
+    type Kernel = pwm_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn pwm_kernel<N: BitWidth>(_cr: ClockReset, duty: Bits<N>, q: Q<N>) -> (bool, D<N>) {
+    let mut d = D::<N> {
+        count: q.count + 1,
+        duty: q.duty,
+        last: (),
+    };
+    // At the end of the period, restart the count, and
+    // take the duty for the next period
+    if q.count == q.last {
+        d.count = bits(0);
+        d.duty = duty;
+    }
+    (q.count < q.duty, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: usize = 15;
+
+    fn run(input: Vec<b4>) -> miette::Result<Vec<bool>> {
+        let uut = Pwm::<U4>::default();
+        // Skip the reset, and the clock that loads the first duty
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn high_times(output: &[bool]) -> Vec<usize> {
+        output
+            .chunks_exact(PERIOD)
+            .map(|period| period.iter().filter(|o| **o).count())
+            .collect()
+    }
+
+    #[test]
+    fn test_high_time() -> miette::Result<()> {
+        for duty in [0, 1, 7, 14, 15] {
+            let output = run(vec![b4(duty); 4 * PERIOD + 1])?;
+            assert_eq!(high_times(&output), [duty as usize; 4]);
+            // Each period is a single pulse, starting at the period boundary
+            for period in output.chunks_exact(PERIOD) {
+                assert!(period.iter().take(duty as usize).all(|o| *o));
+                assert!(period.iter().skip(duty as usize).all(|o| !*o));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_extremes_are_constant() -> miette::Result<()> {
+        assert!(run(vec![b4(0); 100])?[..99].iter().all(|o| !*o));
+        assert!(run(vec![b4(15); 100])?[..99].iter().all(|o| *o));
+        Ok(())
+    }
+
+    #[test]
+    fn test_duty_changes_at_period_boundary() -> miette::Result<()> {
+        // The duty changes part way through the second period (while
+        // the output is still high), and again after the pulse ends
+        let mut input = vec![b4(6); PERIOD + 3];
+        input.extend(vec![b4(2); 6]);
+        input.extend(vec![b4(10); 3 * PERIOD]);
+        let output = run(input)?;
+        assert_eq!(high_times(&output), [6, 6, 10, 10]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pwm_hdl() -> miette::Result<()> {
+        let uut = Pwm::<U4>::default();
+        let input = (0..100).map(|n| b4((n / 20) * 3));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
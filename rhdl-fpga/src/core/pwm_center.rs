@@ -0,0 +1,308 @@
+//! Center Aligned PWM
+//!
+//! A pulse width modulator with a triangle carrier, and complementary
+//! outputs with dead time (e.g., for the two switches of a half bridge
+//! in a motor drive).  The carrier counts up from `0` to `2^N - 2` (the
+//! peak), and back down again, so each period is `2 * (2^N - 2)` clocks
+//! long, and the edges of the PWM signal are placed symmetrically about
+//! the peak (and the valley) of the carrier.
+//!
+//! The nominal PWM signal is high while the carrier is less than
+//! `compare`, so it is a pulse centered on the valley of the carrier.
+//! A `compare` of `0` gives a constant low, and a `compare` of all ones
+//! gives a constant high.  The `out_a` output follows the nominal
+//! signal, and the `out_b` output follows its complement, except that
+//! each output only asserts after the nominal signal has been stable for
+//! `deadtime` clocks (counted by a small counter).  So after one output
+//! deasserts, the other asserts exactly `deadtime` clocks later, and the
+//! two are never high together.  A `deadtime` of `0` makes the outputs
+//! exact complements, and a pulse shorter than the dead time is dropped
+//! entirely.
+//!
+//! The `compare` and `deadtime` inputs are sampled at the peak of the
+//! carrier, so both halves of each pulse use the same settings.  The
+//! `peak` output marks the peak (e.g., to trigger a current measurement
+//! when neither switch is changing).  The outputs are registered, and
+//! cleared on reset.  The counter must have at least 2 bits.
+//!
+//! For an edge aligned PWM with dead time, use the
+//! [DeadtimePwm](crate::motion::deadtime_pwm::DeadtimePwm).
+//!
+//!# Example
+//!
+//! A 3 bit carrier, with a compare of 2 and no dead time.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::pwm_center::{In, PwmCenter};
+//!
+//! let uut = PwmCenter::<U3>::default();
+//! let input = std::iter::repeat_n(
+//!     In {
+//!         compare: b3(2),
+//!         deadtime: b3(0),
+//!     },
+//!     30,
+//! );
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! // The carrier runs 6, 5, 4, 3, 2, 1, 0, 1, 2, 3, 4, 5, ...
+//! let a = output.iter().take(12).map(|o| o.out_a as u8).collect::<Vec<_>>();
+//! assert_eq!(a, [0, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 0]);
+//! assert!(output.iter().all(|o| o.out_a != o.out_b));
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [PwmCenter]
+pub struct In<N: BitWidth> {
+    /// The nominal signal is high while the carrier is below this
+    pub compare: Bits<N>,
+    /// The number of clocks both outputs are held low around each edge
+    pub deadtime: Bits<N>,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [PwmCenter]
+pub struct Out {
+    /// Follows the nominal signal (after the dead time)
+    pub out_a: bool,
+    /// Follows the complement of the nominal signal (after the dead time)
+    pub out_b: bool,
+    /// High on the clock the carrier is at its peak
+    pub peak: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The center aligned PWM core
+///   `N` is the number of bits in the carrier
+pub struct PwmCenter<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+    down: dff::DFF<bool>,
+    compare: dff::DFF<Bits<N>>,
+    deadtime: dff::DFF<Bits<N>>,
+    nominal: dff::DFF<bool>,
+    since: dff::DFF<Bits<N>>,
+    out: dff::DFF<Out>,
+    top: constant::Constant<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for PwmCenter<N> {
+    fn default() -> Self {
+        assert!(N::BITS >= 2, "The carrier must have at least 2 bits");
+        let top = Bits::<N>::MAX - 1;
+        Self {
+            // Start at the peak, so that the settings are
+            // loaded on the first clock
+            count: dff::DFF::new(top),
+            down: dff::DFF::default(),
+            compare: dff::DFF::default(),
+            deadtime: dff::DFF::default(),
+            nominal: dff::DFF::default(),
+            since: dff::DFF::default(),
+            out: dff::DFF::default(),
+            top: constant::Constant::new(top),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for PwmCenter<N> {
+    type I = In<N>;
+    type O = Out;
+    type Kernel = pwm_center_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn pwm_center_kernel<N: BitWidth>(_cr: ClockReset, i: In<N>, q: Q<N>) -> (Out, D<N>) {
+    let peak = q.count == q.top;
+    let down = if peak {
+        true
+    } else if q.count == 0 {
+        false
+    } else {
+        q.down
+    };
+    let mut d = D::<N> {
+        count: if down { q.count - 1 } else { q.count + 1 },
+        down,
+        compare: q.compare,
+        deadtime: q.deadtime,
+        nominal: q.nominal,
+        since: q.since,
+        out: q.out,
+        top: (),
+    };
+    // The settings take effect at the peak
+    if peak {
+        d.compare = i.compare;
+        d.deadtime = i.deadtime;
+    }
+    let nominal = q.count < q.compare;
+    d.nominal = nominal;
+    // The number of clocks the nominal signal has been stable,
+    // saturating at the maximum
+    let since = if nominal != q.nominal {
+        bits(0)
+    } else if q.since.all() {
+        q.since
+    } else {
+        q.since + 1
+    };
+    d.since = since;
+    let settled = since >= q.deadtime;
+    d.out = Out {
+        out_a: nominal && settled,
+        out_b: !nominal && settled,
+        peak,
+    };
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The carrier peaks at 30, so the period is 60 clocks
+    const PERIOD: usize = 60;
+
+    fn run(input: Vec<In<U5>>) -> miette::Result<Vec<Out>> {
+        let uut = PwmCenter::<U5>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn setting(compare: u128, deadtime: u128) -> In<U5> {
+        In {
+            compare: bits(compare),
+            deadtime: bits(deadtime),
+        }
+    }
+
+    fn peaks(output: &[Out]) -> Vec<usize> {
+        output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.peak)
+            .map(|(ndx, _)| ndx)
+            .collect()
+    }
+
+    #[test]
+    fn test_symmetric_about_peak() -> miette::Result<()> {
+        for compare in [1, 2, 10, 29, 30] {
+            let output = run(vec![setting(compare, 0); PERIOD * 4])?;
+            let peaks = peaks(&output);
+            assert_eq!(peaks.len(), 4);
+            assert!(peaks.windows(2).all(|p| p[1] - p[0] == PERIOD));
+            // Skip the first period, which starts with the reset
+            for &p in &peaks[1..3] {
+                for k in 1..PERIOD / 2 {
+                    assert_eq!(output[p - k], output[p + k]);
+                }
+            }
+            let period = &output[peaks[1]..peaks[2]];
+            let high = period.iter().filter(|o| o.out_a).count();
+            assert_eq!(high, 2 * compare as usize - 1);
+            assert!(period.iter().all(|o| o.out_a != o.out_b));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_extremes_are_constant() -> miette::Result<()> {
+        let output = run(vec![setting(0, 0); PERIOD * 3])?;
+        assert!(output[2..].iter().all(|o| !o.out_a && o.out_b));
+        let output = run(vec![setting(31, 0); PERIOD * 3])?;
+        assert!(output[3..].iter().all(|o| o.out_a && !o.out_b));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deadtime_never_overlaps() -> miette::Result<()> {
+        for deadtime in [1, 3, 7] {
+            let input = (0..32)
+                .flat_map(|compare| std::iter::repeat_n(setting(compare, deadtime), PERIOD * 2))
+                .collect::<Vec<_>>();
+            let output = run(input)?;
+            assert!(output.iter().all(|o| !(o.out_a && o.out_b)));
+            // Each output asserts `deadtime` clocks after the other deasserts
+            // (dropped pulses aside)
+            let mut gaps = vec![];
+            let mut last_off = None;
+            for (n, w) in output.windows(2).enumerate() {
+                let edges = [
+                    (w[0].out_a, w[1].out_a, true),
+                    (w[0].out_b, w[1].out_b, false),
+                ];
+                for (was, is, a) in edges {
+                    if was && !is {
+                        last_off = Some((n, a));
+                    }
+                }
+                for (was, is, a) in edges {
+                    if !was && is {
+                        if let Some((off, other)) = last_off {
+                            if other != a {
+                                gaps.push(n - off);
+                            }
+                        }
+                    }
+                }
+            }
+            assert!(gaps.len() > 50);
+            assert!(gaps.iter().all(|g| *g == deadtime as usize), "{gaps:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_deadtime_longer_than_pulse() -> miette::Result<()> {
+        // The nominal pulse is 3 clocks long, so it is dropped
+        let output = run(vec![setting(2, 5); PERIOD * 4])?;
+        assert!(output.iter().all(|o| !o.out_a));
+        assert!(output.iter().any(|o| o.out_b));
+        Ok(())
+    }
+
+    #[test]
+    fn test_settings_load_at_peak() -> miette::Result<()> {
+        // Change the compare just after the valley of the second period
+        let mut input = vec![setting(8, 0); PERIOD + 32];
+        input.extend(vec![setting(20, 0); PERIOD * 2]);
+        let output = run(input)?;
+        let peaks = peaks(&output);
+        let widths = peaks
+            .windows(2)
+            .map(|p| output[p[0]..p[1]].iter().filter(|o| o.out_a).count())
+            .collect::<Vec<_>>();
+        assert_eq!(widths, [15, 15, 39]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pwm_center_hdl() -> miette::Result<()> {
+        let uut = PwmCenter::<U5>::default();
+        let mut input = vec![setting(10, 2); PERIOD * 2];
+        input.extend(vec![setting(25, 4); PERIOD * 2]);
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -4,4 +4,5 @@ pub mod asynchronous;
 pub mod option_async;
 pub mod option_sync;
 pub mod pipe_sync;
+pub mod rmw;
 pub mod synchronous;
@@ -0,0 +1,272 @@
+//! A Read-Modify-Write port for a block ram
+//!
+//! Many cores (histograms, counters held in RAM, cache tags)
+//! need to read a location in a RAM, modify the value, and
+//! write it back.  Because the block ram has a single cycle of
+//! read latency, a naive implementation will return stale data
+//! when two consecutive operations target the same address, since
+//! the second read is issued before the first write lands.  The
+//! [RmwPort] handles this by forwarding the most recently written
+//! value when the addresses match, so that every operation sees the
+//! result of all of the operations that preceded it.  It sustains
+//! one operation per clock cycle.
+//!
+//! The modification is performed by a user supplied (synthesizable)
+//! function with the signature `fn(ClockReset, (T, T)) -> T`, that
+//! receives the current contents of the cell and the operand provided
+//! with the request, and returns the new contents of the cell.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +--+RmwPort+------+
+ ?(A,T)|                | ?(A,T)
++----->| op      result +------>
+       |                |
+       +----------------+
+")]
+//!
+//!# Internals
+//!
+//! Internally, the request is used to address the RAM, and is held
+//! in a stage register until the read data is available.  The value
+//! written in the previous cycle is also kept, and replaces the RAM
+//! data if it was written to the same address.
+#![doc = badascii_doc::badascii!("
+          +-+RAM+---+                 +-+Func+-+
+  addr    |         |    +-----+      |        | new
+ +------->|rd    out+--->|0    |  old |        +---+--->
+ |        |         |    | mux +----->|        |   |
+ |        |       wr|<---+1    |  arg |        |   |
+ |  +-+Stg+-+       |    +--+--+  +-->|        |   |
+ |  |       | addr  |       ^     |   +--------+   |
+ +->|d     q+-------+  match|     |                |
+op  |       +---------------+-----+  +-+Last+-+    |
+    +-------+               +--------+q      d|<---+
+                                     +--------+
+")]
+//!
+//! The result of each operation (the address and the new value of the
+//! cell) is provided on the output one clock cycle after the request is
+//! presented.  Reads can be performed with a modify function that returns
+//! the old value when the operand signals a read.
+//!
+//! Note that the contents of the RAM are not affected by reset.
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+use super::synchronous::{SyncBRAM, Write};
+
+#[derive(Clone, Synchronous, SynchronousDQ)]
+/// The Read-Modify-Write core
+///
+/// Here `T` is the type of the data held in the RAM, and
+/// `A` is the number of address bits.
+pub struct RmwPort<T: Digital, A: BitWidth> {
+    ram: SyncBRAM<T, A>,
+    stage: dff::DFF<Option<(Bits<A>, T)>>,
+    last: dff::DFF<Option<(Bits<A>, T)>>,
+    modify: Func<(T, T), T>,
+}
+
+impl<T: Digital, A: BitWidth> RmwPort<T, A> {
+    /// Construct a [RmwPort] with the given modify kernel.
+    ///
+    /// The kernel must have the signature `fn(ClockReset, (T, T)) -> T`,
+    /// where the first element of the tuple is the current contents of
+    /// the cell, and the second is the operand from the request.
+    pub fn try_new<K>() -> Result<Self, RHDLError>
+    where
+        K: DigitalFn,
+        K: DigitalFn2<A0 = ClockReset, A1 = (T, T), O = T>,
+    {
+        Self::try_new_with_init::<K>(std::iter::empty())
+    }
+
+    /// Construct a [RmwPort] with the given modify kernel
+    /// and initial contents for the RAM.
+    pub fn try_new_with_init<K>(
+        initial: impl IntoIterator<Item = (Bits<A>, T)>,
+    ) -> Result<Self, RHDLError>
+    where
+        K: DigitalFn,
+        K: DigitalFn2<A0 = ClockReset, A1 = (T, T), O = T>,
+    {
+        Ok(Self {
+            ram: SyncBRAM::new(initial),
+            stage: dff::DFF::new(None),
+            last: dff::DFF::new(None),
+            modify: Func::try_new::<K>()?,
+        })
+    }
+}
+
+/// The input to the [RmwPort] is an optional request,
+/// containing the address and the operand.
+pub type In<T, A> = Option<(Bits<A>, T)>;
+
+/// The output of the [RmwPort] is an optional result,
+/// containing the address and the value written back.
+pub type Out<T, A> = Option<(Bits<A>, T)>;
+
+impl<T: Digital, A: BitWidth> SynchronousIO for RmwPort<T, A> {
+    type I = In<T, A>;
+    type O = Out<T, A>;
+    type Kernel = rmw_kernel<T, A>;
+}
+
+#[kernel(allow_weak_partial)]
+#[doc(hidden)]
+pub fn rmw_kernel<T: Digital, A: BitWidth>(
+    cr: ClockReset,
+    i: In<T, A>,
+    q: Q<T, A>,
+) -> (Out<T, A>, D<T, A>) {
+    let mut d = D::<T, A>::dont_care();
+    // Issue the read for the incoming request
+    d.ram.read_addr = bits(0);
+    if let Some((addr, _arg)) = i {
+        d.ram.read_addr = addr;
+    }
+    d.stage = i;
+    // By default, nothing is written
+    d.ram.write = Write::<T, A> {
+        addr: bits(0),
+        value: T::dont_care(),
+        enable: false,
+    };
+    d.last = None;
+    d.modify = (T::dont_care(), T::dont_care());
+    let mut o = None;
+    if let Some((addr, arg)) = q.stage {
+        // The read for this request was issued in the same cycle
+        // as the previous write, so it does not reflect that write.
+        // Forward the last written value if it targets this address.
+        let mut old = q.ram;
+        if let Some((last_addr, last_value)) = q.last {
+            if last_addr == addr {
+                old = last_value;
+            }
+        }
+        d.modify = (old, arg);
+        let new = q.modify;
+        d.ram.write = Write::<T, A> {
+            addr,
+            value: new,
+            enable: true,
+        };
+        d.last = Some((addr, new));
+        o = Some((addr, new));
+    }
+    if cr.reset.any() {
+        d.stage = None;
+        d.last = None;
+        d.ram.write.enable = false;
+        o = None;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    #[kernel]
+    fn accumulate(_cr: ClockReset, i: (b8, b8)) -> b8 {
+        let (old, arg) = i;
+        old + arg
+    }
+
+    fn make_uut() -> Result<RmwPort<b8, U2>, RHDLError> {
+        RmwPort::try_new_with_init::<accumulate>((0..4).map(|a| (bits(a), bits(0))))
+    }
+
+    // Generate bursts of operations that target the same address,
+    // separated by occasional idle cycles.
+    fn bursts(seed: u64, count: usize) -> Vec<In<b8, U2>> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut ops = vec![];
+        while ops.len() < count {
+            let addr = bits(rng.random_range(0..4));
+            let len = rng.random_range(1..6);
+            for _ in 0..len {
+                ops.push(Some((addr, bits(rng.random_range(0..256)))));
+            }
+            if rng.random_bool(0.3) {
+                ops.push(None);
+            }
+        }
+        ops
+    }
+
+    fn model(ops: &[In<b8, U2>]) -> Vec<(b2, b8)> {
+        let mut mem = BTreeMap::new();
+        ops.iter()
+            .flatten()
+            .map(|(addr, arg)| {
+                let cell = mem.entry(*addr).or_insert(b8(0));
+                *cell += *arg;
+                (*addr, *cell)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_back_to_back_same_address() -> miette::Result<()> {
+        let uut = make_uut()?;
+        let ops = vec![
+            Some((b2(1), b8(1))),
+            Some((b2(1), b8(2))),
+            Some((b2(1), b8(4))),
+            None,
+            Some((b2(1), b8(8))),
+            Some((b2(2), b8(1))),
+            Some((b2(1), b8(16))),
+            None,
+            None,
+        ];
+        let input = ops.clone().into_iter().with_reset(1).clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .filter_map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output, model(&ops));
+        assert_eq!(output[2], (b2(1), b8(7)));
+        assert_eq!(output[5], (b2(1), b8(31)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_bursts_match_model() -> miette::Result<()> {
+        let uut = make_uut()?;
+        for seed in 0..10 {
+            let mut ops = bursts(seed, 2000);
+            ops.push(None);
+            let input = ops.clone().into_iter().with_reset(1).clock_pos_edge(100);
+            let output = uut
+                .run(input)?
+                .synchronous_sample()
+                .filter_map(|t| t.value.2)
+                .collect::<Vec<_>>();
+            assert_eq!(output, model(&ops), "Mismatch with seed {seed}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rmw_hdl() -> miette::Result<()> {
+        let uut = make_uut()?;
+        let input = bursts(42, 100).into_iter().with_reset(1).clock_pos_edge(100);
+        let tb = uut.run(input)?.collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
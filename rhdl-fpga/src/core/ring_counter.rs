@@ -0,0 +1,137 @@
+//! Ring Counter
+//!
+//! A one-hot counter, for sequencing multi-phase logic without
+//! decoding a binary count.  The register holds a single hot bit,
+//! which resets to bit 0, and moves one place to the left on each
+//! enabled clock, wrapping around from the MSB back to the LSB.  When
+//! `enable` is low, the register holds its value.
+//!
+//! A register of all zeros would circulate forever, so the counter
+//! reloads the seed (`0b...0001`) on the next clock whenever it finds
+//! the register empty (e.g., after a glitch, or if it was never
+//! reset), whether or not it is enabled.
+//!
+//! The input is the enable, and the output is the one-hot register.
+//!
+//!# Example
+//!
+//! Sequencing four phases.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::ring_counter::RingCounter;
+//!
+//! let uut = RingCounter::<U4>::default();
+//! let output = uut
+//!     .run(std::iter::repeat_n(true, 5).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [b4(0b0001), b4(0b0010), b4(0b0100), b4(0b1000), b4(0b0001)]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The ring counter core
+///   `N` is the number of bits in the register (and of phases)
+pub struct RingCounter<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for RingCounter<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(bits(1)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for RingCounter<N> {
+    type I = bool;
+    type O = Bits<N>;
+    type Kernel = ring_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn ring_counter_kernel<N: BitWidth>(cr: ClockReset, enable: bool, q: Q<N>) -> (Bits<N>, D<N>) {
+    let mut d = D::<N> { reg: q.reg };
+    if q.reg == 0 {
+        d.reg = bits(1);
+    } else if enable {
+        d.reg = q.reg << 1;
+        if q.reg & (1 << (N::BITS - 1)) != 0 {
+            d.reg |= 1;
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(1);
+    }
+    (q.reg, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: impl Iterator<Item = bool>) -> miette::Result<Vec<b4>> {
+        let uut = RingCounter::<U4>::default();
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_full_rotation() -> miette::Result<()> {
+        let output = run(std::iter::repeat_n(true, 9))?;
+        let expected = [1, 2, 4, 8, 1, 2, 4, 8, 1].map(b4);
+        assert_eq!(output, expected);
+        // Exactly one bit is hot at all times
+        assert!(output.iter().all(|o| o.raw().count_ones() == 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_when_disabled() -> miette::Result<()> {
+        let input = [true, false, false, true, false, true, true];
+        let output = run(input.into_iter())?;
+        assert_eq!(output, [1, 2, 2, 2, 4, 4, 8].map(b4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovers_from_all_zeros() -> miette::Result<()> {
+        // Without a reset, the register starts out empty, and
+        // the counter reloads the seed even while disabled
+        let uut = RingCounter::<U4>::default();
+        let input = [false, false, true, true].into_iter().without_reset();
+        let output = uut
+            .run(input.clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output, [0, 1, 1, 2].map(b4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ring_counter_hdl() -> miette::Result<()> {
+        let uut = RingCounter::<U4>::default();
+        let input = (0..40).map(|n| n % 3 != 0);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! Counter with a rollover flag
+//!
+//! An up counter that counts the enabled clocks, like the
+//! [Counter](super::counter::Counter), and also flags when it wraps.
+//! The count advances by one on each clock when `enable` is high, and
+//! holds when it is low.  `rollover` is high on the (enabled) clock on
+//! which the count wraps from all ones to zero, so with `enable` held
+//! high, it is a pulse one clock long every `2^N` clocks.  It can be
+//! used as the `enable` of a second counter, to cascade them.  On reset,
+//! the count is cleared.
+//!
+//! The input is the `enable`, and the output is the current count and
+//! the rollover flag.
+//!
+//!# Example
+//!
+//! A 2 bit counter, with a gap in the enable.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::rollover_counter::RolloverCounter;
+//!
+//! let uut = RolloverCounter::<U2>::default();
+//! let input = [true, true, false, true, true, true];
+//! let output = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| (t.value.2.count.raw(), t.value.2.rollover))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(
+//!     output,
+//!     [(0, false), (1, false), (2, false), (2, false), (3, true), (0, false)]
+//! );
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [RolloverCounter]
+pub struct Out<N: BitWidth> {
+    /// The current count
+    pub count: Bits<N>,
+    /// High on the clock the count wraps to zero
+    pub rollover: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The counter core with a rollover flag
+///   `N` is the bitwidth of the counter
+pub struct RolloverCounter<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+    max: constant::Constant<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for RolloverCounter<N> {
+    fn default() -> Self {
+        Self {
+            count: dff::DFF::new(Bits::<N>::default()),
+            max: constant::Constant::new(Bits::<N>::MAX),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for RolloverCounter<N> {
+    type I = bool;
+    type O = Out<N>;
+    type Kernel = rollover_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn rollover_counter_kernel<N: BitWidth>(
+    cr: ClockReset,
+    enable: bool,
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let mut d = D::<N> {
+        count: q.count,
+        max: (),
+    };
+    if enable {
+        d.count = q.count + 1;
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+    }
+    let o = Out::<N> {
+        count: q.count,
+        rollover: enable && q.count == q.max,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: Vec<bool>) -> miette::Result<Vec<Out<U4>>> {
+        let uut = RolloverCounter::<U4>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_two_full_wraps() -> miette::Result<()> {
+        let output = run(vec![true; 40])?;
+        let counts = output.iter().map(|o| o.count.raw()).collect::<Vec<_>>();
+        let expected = (0..40).map(|n| n % 16).collect::<Vec<_>>();
+        assert_eq!(counts, expected);
+        // The rollover is one clock long, on the clock the count is 15
+        let rollovers = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.rollover)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(rollovers, [15, 31]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_when_disabled() -> miette::Result<()> {
+        let mut input = vec![true; 15];
+        // Stop at the all ones count
+        input.extend([false; 5]);
+        input.extend([true; 2]);
+        let output = run(input)?;
+        assert!(output[15..20]
+            .iter()
+            .all(|o| o.count.raw() == 15 && !o.rollover));
+        assert!(output[20].rollover);
+        assert_eq!(output[21].count.raw(), 0);
+        assert_eq!(output.iter().filter(|o| o.rollover).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_clears_count() -> miette::Result<()> {
+        let uut = RolloverCounter::<U4>::default();
+        let input = std::iter::repeat_n(true, 6)
+            .with_reset(1)
+            .chain(std::iter::repeat_n(true, 3).with_reset(1))
+            .clock_pos_edge(100);
+        let counts = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2.count.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [0, 0, 1, 2, 3, 4, 5, 6, 0, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollover_counter_hdl() -> miette::Result<()> {
+        let uut = RolloverCounter::<U4>::default();
+        let input = (0..60).map(|n| n % 7 != 3);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
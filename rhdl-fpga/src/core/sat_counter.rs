@@ -0,0 +1,219 @@
+//! Saturating Counter
+//!
+//! A counter that clamps at the ends of its range instead of wrapping.
+//! It counts up on clocks when `inc` is high, and down on clocks when
+//! `dec` is high.  Counting up at all ones, or down at zero, holds the
+//! count, as does raising both `inc` and `dec` together.  The `at_max`
+//! and `at_min` outputs flag when the count is at either end of the
+//! range.
+//!
+//! The classic use is the 2 bit counter of a branch predictor, which is
+//! incremented when a branch is taken and decremented when it is not,
+//! with the top bit as the prediction.  The count starts from the
+//! `initial` value given at construction (and returns to it on reset),
+//! so a predictor can start out weakly taken.  For a counter that also
+//! reports the steps it refused, or that can wrap instead, use the
+//! [UpDownCounter](super::up_down_counter::UpDownCounter).
+//!
+//! The input is a tuple of `(inc, dec)`, and the outputs are the
+//! current count and its flags (so a step shows up on the next clock).
+//!
+//!# Example
+//!
+//! A 2 bit predictor, that starts weakly taken.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::sat_counter::SatCounter;
+//!
+//! let uut = SatCounter::<U2>::new(b2(2));
+//! let taken = [true, true, true, false, true, false, false, false, false];
+//! let input = taken.into_iter().map(|t| (t, !t));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.count.raw())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [2, 3, 3, 3, 2, 3, 2, 1, 0]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [SatCounter]
+pub struct Out<N: BitWidth> {
+    /// The current count
+    pub count: Bits<N>,
+    /// High when the count is all ones
+    pub at_max: bool,
+    /// High when the count is zero
+    pub at_min: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The saturating counter core
+///   `N` is the bitwidth of the counter
+pub struct SatCounter<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> SatCounter<N> {
+    /// Create a counter that starts from `initial`
+    pub fn new(initial: Bits<N>) -> Self {
+        Self {
+            count: dff::DFF::new(initial),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for SatCounter<N> {
+    fn default() -> Self {
+        Self::new(Bits::<N>::default())
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for SatCounter<N> {
+    type I = (bool, bool);
+    type O = Out<N>;
+    type Kernel = sat_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn sat_counter_kernel<N: BitWidth>(
+    _cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (inc, dec) = i;
+    let at_max = q.count.all();
+    let at_min = q.count == 0;
+    let mut d = D::<N> { count: q.count };
+    if inc && !dec && !at_max {
+        d.count = q.count + 1;
+    }
+    if dec && !inc && !at_min {
+        d.count = q.count - 1;
+    }
+    let o = Out::<N> {
+        count: q.count,
+        at_max,
+        at_min,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::random;
+
+    use super::*;
+
+    fn run(uut: &SatCounter<U3>, input: &[(bool, bool)]) -> miette::Result<Vec<Out<U3>>> {
+        Ok(uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn counts(output: &[Out<U3>]) -> Vec<u128> {
+        output.iter().map(|o| o.count.raw()).collect()
+    }
+
+    const INC: (bool, bool) = (true, false);
+    const DEC: (bool, bool) = (false, true);
+
+    #[test]
+    fn test_hold_at_max() -> miette::Result<()> {
+        let mut input = vec![INC; 50];
+        input.push(DEC);
+        input.push(INC);
+        let output = run(&SatCounter::default(), &input)?;
+        let counts = counts(&output);
+        assert_eq!(counts[..8], [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(counts[7..51].iter().all(|c| *c == 7));
+        assert_eq!(counts[51..], [6]);
+        assert!(output.iter().all(|o| o.at_max == (o.count == 7)));
+        assert!(output[7..51].iter().all(|o| o.at_max && !o.at_min));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_at_min() -> miette::Result<()> {
+        let mut input = vec![DEC; 50];
+        input.push(INC);
+        input.push(DEC);
+        let uut = SatCounter::<U3>::new(b3(5));
+        let output = run(&uut, &input)?;
+        let counts = counts(&output);
+        assert_eq!(counts[..6], [5, 4, 3, 2, 1, 0]);
+        assert!(counts[5..51].iter().all(|c| *c == 0));
+        assert_eq!(counts[51..], [1]);
+        assert!(output.iter().all(|o| o.at_min == (o.count == 0)));
+        assert!(output[5..51].iter().all(|o| o.at_min && !o.at_max));
+        Ok(())
+    }
+
+    #[test]
+    fn test_inc_and_dec_holds() -> miette::Result<()> {
+        let input = [INC, INC, (true, true), (true, true), DEC, (false, false)];
+        let output = run(&SatCounter::default(), &input)?;
+        assert_eq!(counts(&output), [0, 1, 2, 2, 2, 1]);
+        // Including at either end
+        for initial in [0, 7] {
+            let uut = SatCounter::<U3>::new(bits(initial));
+            let output = run(&uut, &[(true, true); 8])?;
+            assert!(counts(&output).iter().all(|c| *c == initial));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_walk() -> miette::Result<()> {
+        for initial in [0, 3, 7] {
+            let input = (0..500)
+                .map(|_| (random::<bool>(), random::<bool>()))
+                .collect::<Vec<_>>();
+            let uut = SatCounter::<U3>::new(bits(initial));
+            let output = run(&uut, &input)?
+                .into_iter()
+                .map(|o| (o.count.raw(), o.at_max, o.at_min))
+                .collect::<Vec<_>>();
+            // The clamped software model (with the count before the step)
+            let mut count = initial;
+            let expected = input
+                .iter()
+                .map(|&(inc, dec)| {
+                    let now = count;
+                    count = match (inc, dec) {
+                        (true, false) => (count + 1).min(7),
+                        (false, true) => count.max(1) - 1,
+                        _ => count,
+                    };
+                    (now, now == 7, now == 0)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(output, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sat_counter_hdl() -> miette::Result<()> {
+        let uut = SatCounter::<U3>::new(b3(2));
+        let input = (0..80).map(|n| (n % 3 != 2 && n < 40, n % 5 == 1 || n > 50));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Scan Chain Wrapper
+//!
+//! For debugging a design after synthesis, it is handy to be able to
+//! stop it, read out the contents of its flops, and (perhaps) load them
+//! with new values before letting it run again.  The [ScanWrapper] does
+//! this for a core, by holding the state of the core in a register that
+//! can be reconfigured into a scan chain:
+//!
+//! - When `scan_enable` is low, the wrapper behaves identically to the
+//!   wrapped core.  The state is updated by the kernel of the core, and
+//!   the input and output are passed through.
+//! - When `scan_enable` is high, the core is frozen, and its state is
+//!   shifted left by one bit on each clock, with `scan_in` entering at
+//!   the LSB, and the MSB presented on `scan_out` (before shifting).
+//!   After `W` clocks (the number of bits in the state), the old state
+//!   has been shifted out, and replaced by the bits shifted in.  The
+//!   output of the core is still presented (computed from the state
+//!   being shifted), but should be ignored.
+//!
+//! The wrapper cannot reach into an arbitrary core, so the core must
+//! describe its state with the [Scannable] trait.  This first version
+//! is limited to cores whose state is a single register of `W` bits.
+//! Such a core provides a kernel that computes its output and next state
+//! from its input and current state (usually by calling the kernel of
+//! the core itself), and the value its state takes on reset.  On reset,
+//! the wrapper returns the state to that value, as the core would.  The
+//! wrapper is built from the core with [ScanWrapper::try_new], which
+//! compiles the kernel, and takes the input and output types (and the
+//! width of the state) from the core.
+//!
+//!# Example
+//!
+//! Loading a [ShiftRegister](super::shift_reg::ShiftRegister) through
+//! the scan chain, and reading it back out again.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::scan::{In, ScanWrapper};
+//! use rhdl_fpga::core::shift_reg::ShiftRegister;
+//!
+//! let uut = ScanWrapper::try_new(ShiftRegister::<U8>::default()).unwrap();
+//! let scan = |bit: bool| In {
+//!     scan_enable: true,
+//!     scan_in: bit,
+//!     data: (false, false, false, b8(0)),
+//! };
+//! let input = [1, 0, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+//!     .map(|b| scan(b == 1));
+//! let scan_out = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(9)
+//!     .map(|t| t.value.2.scan_out as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(scan_out, [1, 0, 1, 1, 0, 0, 1, 0]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+/// A core whose state can be scanned by the [ScanWrapper]
+pub trait Scannable: SynchronousIO {
+    /// The number of bits in the state of the core
+    type W: BitWidth;
+    /// A kernel that computes the output and next state of the core, with
+    /// the signature `fn(ClockReset, (I, Bits<W>)) -> (O, Bits<W>)`
+    type Next: DigitalFn
+        + DigitalFn2<A0 = ClockReset, A1 = (Self::I, Bits<Self::W>), O = (Self::O, Bits<Self::W>)>;
+    /// The state of the core after reset
+    fn reset_state(&self) -> Bits<Self::W>;
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The inputs of the [ScanWrapper]
+pub struct In<I: Digital> {
+    /// Shift the state along the scan chain (and freeze the core)
+    pub scan_enable: bool,
+    /// The bit shifted into the LSB of the state
+    pub scan_in: bool,
+    /// The input of the core
+    pub data: I,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ScanWrapper]
+pub struct Out<O: Digital> {
+    /// The MSB of the state
+    pub scan_out: bool,
+    /// The output of the core
+    pub data: O,
+}
+
+#[derive(Clone, Synchronous, SynchronousDQ)]
+/// The scan chain wrapper
+///   `I` and `O` are the input and output of the wrapped core
+///   `W` is the number of bits in the state of the wrapped core
+pub struct ScanWrapper<I: Digital, O: Digital, W: BitWidth> {
+    state: dff::DFF<Bits<W>>,
+    next: Func<(I, Bits<W>), (O, Bits<W>)>,
+}
+
+impl<I: Digital, O: Digital, W: BitWidth> ScanWrapper<I, O, W> {
+    /// Wrap the given core in a scan chain
+    pub fn try_new<C>(core: C) -> Result<Self, RHDLError>
+    where
+        C: Scannable<I = I, O = O, W = W>,
+    {
+        Ok(Self {
+            state: dff::DFF::new(core.reset_state()),
+            next: Func::try_new::<C::Next>()?,
+        })
+    }
+}
+
+impl<I: Digital, O: Digital, W: BitWidth> SynchronousIO for ScanWrapper<I, O, W> {
+    type I = In<I>;
+    type O = Out<O>;
+    type Kernel = scan_wrapper_kernel<I, O, W>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn scan_wrapper_kernel<I: Digital, O: Digital, W: BitWidth>(
+    _cr: ClockReset,
+    i: In<I>,
+    q: Q<I, O, W>,
+) -> (Out<O>, D<I, O, W>) {
+    let (data, next) = q.next;
+    let mut d = D::<I, O, W> {
+        state: next,
+        next: (i.data, q.state),
+    };
+    if i.scan_enable {
+        d.state = q.state << 1;
+        if i.scan_in {
+            d.state |= 1;
+        }
+    }
+    let o = Out::<O> {
+        scan_out: q.state & (1 << (W::BITS - 1)) != 0,
+        data,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::shift_reg::ShiftRegister;
+
+    type ShiftIn = (bool, bool, bool, b8);
+
+    const HOLD: ShiftIn = (false, false, false, b8(0));
+
+    fn scan(bit: bool) -> In<ShiftIn> {
+        In {
+            scan_enable: true,
+            scan_in: bit,
+            data: HOLD,
+        }
+    }
+
+    fn functional(data: ShiftIn) -> In<ShiftIn> {
+        In {
+            scan_enable: false,
+            scan_in: false,
+            data,
+        }
+    }
+
+    fn scan_bits(data: u8) -> Vec<In<ShiftIn>> {
+        (0..8).rev().map(|n| scan(data & (1 << n) != 0)).collect()
+    }
+
+    #[test]
+    fn test_scan_in_run_scan_out() -> miette::Result<()> {
+        let uut = ScanWrapper::try_new(ShiftRegister::<U8>::default())?;
+        // Scan in a pattern, shift a one into the register with a
+        // functional clock, and scan the new state out
+        let mut input = scan_bits(0b1100_1010);
+        input.push(functional((true, false, true, b8(0))));
+        input.extend(scan_bits(0));
+        let output = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The core sees the scanned in state on the functional clock
+        assert_eq!(output[8].data, b8(0b1100_1010));
+        let scan_out = output[9..17]
+            .iter()
+            .fold(0, |acc, o| (acc << 1) | o.scan_out as u8);
+        assert_eq!(scan_out, 0b1001_0101);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transparent_without_scan() -> miette::Result<()> {
+        let core = ShiftRegister::<U8>::default();
+        let uut = ScanWrapper::try_new(core.clone())?;
+        let stimulus = (0..50_u32)
+            .map(|n| (n % 4 != 1, n % 17 == 5, (n * 7) % 3 == 0, b8(0xA5)))
+            .collect::<Vec<_>>();
+        let expected = core
+            .run(stimulus.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let output = uut
+            .run(
+                stimulus
+                    .iter()
+                    .copied()
+                    .map(functional)
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .map(|t| t.value.2.data)
+            .collect::<Vec<_>>();
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_clears_scanned_state() -> miette::Result<()> {
+        let uut = ScanWrapper::try_new(ShiftRegister::<U8>::default())?;
+        let input = scan_bits(0xFF)
+            .into_iter()
+            .with_reset(1)
+            .chain(std::iter::repeat_n(functional(HOLD), 2).with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2.data)
+            .collect::<Vec<_>>();
+        assert_eq!(output[9], b8(0xFF));
+        assert_eq!(output[10..], [b8(0), b8(0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_wrapper_hdl() -> miette::Result<()> {
+        let uut = ScanWrapper::try_new(ShiftRegister::<U8>::default())?;
+        let mut input = scan_bits(0b0110_1001);
+        input.extend((0..12).map(|n| functional((n % 3 != 0, n == 7, n % 2 == 0, b8(0x3C)))));
+        input.extend(scan_bits(0));
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+//! Serial CRC Generator
+//!
+//! Computes a CRC one bit at a time, as the bits of a message are
+//! shifted in (typically alongside a
+//! [ShiftRegisterWord](super::shift_reg_word::ShiftRegisterWord) or
+//! [VarShiftIn](super::var_shift_in::VarShiftIn) that deserializes the
+//! same bits).  The CRC register is an LFSR with the data as an input:
+//! on each enabled clock, the serial bit is XORed with the MSB of the
+//! register, the register shifts left, and if the result was a one, the
+//! polynomial is XORed into the register.
+//!
+//! The polynomial is given in the usual (normal) form, without the
+//! `x^N` term, so CRC-8 (`x^8 + x^2 + x + 1`) is `0x07` and CRC-16/CCITT
+//! (`x^16 + x^12 + x^5 + 1`) is `0x1021`.  Bits are expected MSB first,
+//! and the CRC is neither reflected nor inverted on output, which
+//! covers CRCs like CRC-8/ATM, CRC-16/CCITT-FALSE and CRC-16/XMODEM.
+//! The initial value of the register defaults to zero, and can be set
+//! with [SerialCrc::with_init].
+//!
+//! The `clear` input starts a new message by loading the initial value.
+//! If the first bit of the new message arrives on the same clock
+//! (i.e., `enable` is also high), it is folded into the initial value,
+//! so that the message can be streamed without a gap.  The output is
+//! the CRC of the bits shifted in so far, so after the last bit of the
+//! message (or of the message and its checksum), it can be compared
+//! against the received checksum (or zero).  On reset, the register
+//! is loaded with the initial value.
+//!
+//!# Example
+//!
+//! The CRC-8/ATM of the byte `0x31` (`'1'`) is `0x97`.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::serial_crc::{In, SerialCrc};
+//!
+//! let uut = SerialCrc::<U8>::new(b8(0x07));
+//! let input = (0..8).rev().map(|n| In {
+//!     clear: n == 7,
+//!     enable: true,
+//!     serial: 0x31 & (1 << n) != 0,
+//! });
+//! // One more clock, so that the last bit shows up on the output
+//! let input = input.chain(std::iter::once(In::default()));
+//! let crc = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2;
+//! assert_eq!(crc, b8(0x97));
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [SerialCrc]
+pub struct In {
+    /// Load the initial value (start a new message)
+    pub clear: bool,
+    /// Fold the serial bit into the CRC on this clock
+    pub enable: bool,
+    /// The serial bit
+    pub serial: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+#[doc(hidden)]
+pub struct Config<N: BitWidth> {
+    poly: Bits<N>,
+    init: Bits<N>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The serial CRC core
+///   `N` is the width of the CRC
+pub struct SerialCrc<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    config: constant::Constant<Config<N>>,
+}
+
+impl<N: BitWidth> SerialCrc<N> {
+    /// Create a CRC generator for the given polynomial (in normal form,
+    /// without the `x^N` term), with an initial value of zero
+    pub fn new(poly: Bits<N>) -> Self {
+        Self {
+            reg: dff::DFF::new(bits(0)),
+            config: constant::Constant::new(Config {
+                poly,
+                init: bits(0),
+            }),
+        }
+    }
+    /// Set the initial value of the CRC register
+    pub fn with_init(self, init: Bits<N>) -> Self {
+        let poly = self.config.value().poly;
+        Self {
+            reg: dff::DFF::new(init),
+            config: constant::Constant::new(Config { poly, init }),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for SerialCrc<N> {
+    type I = In;
+    type O = Bits<N>;
+    type Kernel = serial_crc_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn serial_crc_kernel<N: BitWidth>(cr: ClockReset, i: In, q: Q<N>) -> (Bits<N>, D<N>) {
+    let mut crc = q.reg;
+    if i.clear {
+        crc = q.config.init;
+    }
+    if i.enable {
+        let feedback = (crc & (1 << (N::BITS - 1)) != 0) ^ i.serial;
+        crc <<= 1;
+        if feedback {
+            crc ^= q.config.poly;
+        }
+    }
+    let mut d = D::<N> {
+        reg: crc,
+        config: (),
+    };
+    if cr.reset.any() {
+        d.reg = q.config.init;
+    }
+    (q.reg, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bits of a message, MSB first, with `clear` on the first bit
+    fn message(bytes: &[u8]) -> impl Iterator<Item = In> + Clone + '_ {
+        bytes.iter().enumerate().flat_map(|(ndx, byte)| {
+            (0..8).rev().map(move |n| In {
+                clear: ndx == 0 && n == 7,
+                enable: true,
+                serial: byte & (1 << n) != 0,
+            })
+        })
+    }
+
+    fn final_crc<N: BitWidth>(
+        uut: &SerialCrc<N>,
+        input: impl Iterator<Item = In>,
+    ) -> miette::Result<Bits<N>> {
+        // One more clock, so that the last bit shows up on the output
+        let input = input.chain(std::iter::once(In::default()));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .last()
+            .unwrap()
+            .value
+            .2)
+    }
+
+    #[test]
+    fn test_crc8_atm_check() -> miette::Result<()> {
+        let uut = SerialCrc::<U8>::new(b8(0x07));
+        assert_eq!(final_crc(&uut, message(b"123456789"))?, b8(0xF4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc16_ccitt_check() -> miette::Result<()> {
+        let uut = SerialCrc::<U16>::new(b16(0x1021)).with_init(b16(0xFFFF));
+        assert_eq!(final_crc(&uut, message(b"123456789"))?, b16(0x29B1));
+        let uut = SerialCrc::<U16>::new(b16(0x1021));
+        assert_eq!(final_crc(&uut, message(b"123456789"))?, b16(0x31C3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum_appended_gives_zero() -> miette::Result<()> {
+        let uut = SerialCrc::<U8>::new(b8(0x07));
+        assert_eq!(final_crc(&uut, message(b"123456789\xF4"))?, b8(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_clear_with_first_bit() -> miette::Result<()> {
+        // Two messages back to back, with the second one cleared on the
+        // same clock as its first bit.  Idle clocks in between (and
+        // garbage before the first) do not matter.
+        let uut = SerialCrc::<U16>::new(b16(0x1021)).with_init(b16(0xFFFF));
+        let garbage = message(b"junk").map(|i| In { clear: false, ..i });
+        let idle = std::iter::repeat_n(
+            In {
+                serial: true,
+                ..Default::default()
+            },
+            3,
+        );
+        let input = garbage
+            .chain(message(b"123456789"))
+            .chain(idle)
+            .chain(message(b"123456789"));
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The CRC of the first message, after its last bit and the idle
+        // clocks, then the second message finishing on the last clock
+        let first = 1 + 32 + 72;
+        assert!(output[first..first + 4]
+            .iter()
+            .all(|crc| *crc == b16(0x29B1)));
+        assert_eq!(
+            final_crc(&uut, message(b"junk").chain(message(b"123456789")))?,
+            b16(0x29B1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_loads_init() -> miette::Result<()> {
+        let uut = SerialCrc::<U16>::new(b16(0x1021)).with_init(b16(0xFFFF));
+        let output = uut
+            .run(
+                std::iter::repeat_n(In::default(), 3)
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output, [b16(0xFFFF); 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_serial_crc_hdl() -> miette::Result<()> {
+        let uut = SerialCrc::<U16>::new(b16(0x1021)).with_init(b16(0xFFFF));
+        let input = message(b"1234").chain(message(b"56"));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
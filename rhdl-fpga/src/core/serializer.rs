@@ -0,0 +1,315 @@
+//! Serializer (words in, gapless serial out)
+//!
+//! A [ShiftOut](super::shift_out::ShiftOut) that manages its own
+//! load timing.  Words are presented on the `data` input, and shifted
+//! out serially, MSB first, one bit per enabled clock.  A bit counter
+//! keeps track of the bits of the current word still to be sent, so
+//! that:
+//!
+//! - When the core is idle, a word presented on `data` is taken
+//!   on that clock, and its first bit is output on the next one.
+//! - When the core is busy, a word presented on `data` is taken on the
+//!   (enabled) clock that sends the last bit of the current word, so
+//!   that the first bit of the new word follows it without a gap.  At
+//!   any other time, a word presented while busy is ignored.
+//! - `next_word` pulses on the enabled clock that sends the next to
+//!   last bit of a word, so that a producer can register it, and present
+//!   the next word in time for a gapless transmission.
+//! - `taken` is high on the clock that a word is taken, so a producer
+//!   that presents a word early holds it until `taken` is seen.
+//!
+//! When no word is being sent, the serial output is driven to the idle
+//! level given at construction (low for the [Default]).  On reset, the
+//! word being sent is abandoned, and the core goes idle.
+//!
+//! The width of the word `N` must be at least 2 bits, and less than 256.
+//!
+//!# Example
+//!
+//! Two words back to back, with the line idling high before and after.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::serializer::{In, Serializer};
+//!
+//! let uut = Serializer::<U4>::new(true);
+//! let word = |data: Option<u128>| In {
+//!     enable: true,
+//!     data: data.map(b4),
+//! };
+//! let input = [None, Some(0b0001), None, None, None, Some(0b0110), None, None, None, None, None]
+//!     .map(word);
+//! let serial = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.serial as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(serial, [1, 1, 0, 0, 0, 1, 0, 1, 1, 0, 1]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff, shift_out::ShiftOut};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [Serializer]
+pub struct In<N: BitWidth> {
+    /// Send a bit on this clock
+    pub enable: bool,
+    /// The next word to send (if any)
+    pub data: Option<Bits<N>>,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [Serializer]
+pub struct Out {
+    /// The serial output (the idle level when no word is being sent)
+    pub serial: bool,
+    /// High while bits of a word remain to be sent
+    pub busy: bool,
+    /// High on the clock the next to last bit of a word is sent
+    pub next_word: bool,
+    /// High on the clock the word on `data` is taken
+    pub taken: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The serializer core
+///   `N` is the number of bits in a word
+pub struct Serializer<N: BitWidth> {
+    shift: ShiftOut<N>,
+    count: dff::DFF<b8>,
+    width: constant::Constant<b8>,
+    idle: constant::Constant<bool>,
+}
+
+impl<N: BitWidth> Serializer<N> {
+    /// Create a serializer whose serial output idles at the given level
+    pub fn new(idle: bool) -> Self {
+        assert!(
+            (2..256).contains(&N::BITS),
+            "The word must have between 2 and 255 bits"
+        );
+        Self {
+            shift: ShiftOut::default(),
+            count: dff::DFF::new(b8(0)),
+            width: constant::Constant::new(bits(N::BITS as u128)),
+            idle: constant::Constant::new(idle),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for Serializer<N> {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for Serializer<N> {
+    type I = In<N>;
+    type O = Out;
+    type Kernel = serializer_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn serializer_kernel<N: BitWidth>(cr: ClockReset, i: In<N>, q: Q<N>) -> (Out, D<N>) {
+    let busy = q.count != 0;
+    let shift = i.enable && busy;
+    let last = shift && q.count == 1;
+    let (load, word) = match i.data {
+        Some(word) => (!busy || last, word),
+        None => (false, bits(0)),
+    };
+    let mut d = D::<N> {
+        shift: (shift, load, word),
+        count: q.count,
+        width: (),
+        idle: (),
+    };
+    if load {
+        d.count = q.width;
+    } else if shift {
+        d.count = q.count - 1;
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+    }
+    let o = Out {
+        serial: if busy { q.shift } else { q.idle },
+        busy,
+        next_word: shift && q.count == 2,
+        taken: load,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(uut: &Serializer<U8>, input: Vec<In<U8>>) -> miette::Result<Vec<Out>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn word(data: u8) -> In<U8> {
+        In {
+            enable: true,
+            data: Some(b8(data as u128)),
+        }
+    }
+
+    const SHIFT: In<U8> = In {
+        enable: true,
+        data: None,
+    };
+
+    fn bits_of(data: u8) -> Vec<bool> {
+        (0..8).rev().map(|n| data & (1 << n) != 0).collect()
+    }
+
+    #[test]
+    fn test_gapless_back_to_back() -> miette::Result<()> {
+        // A producer that presents each word on the clock after the
+        // next_word strobe.  The first word is taken when idle.
+        let words = [0b1100_1010, 0b0000_0001, 0b1111_1110, 0b0101_0011];
+        let mut input = vec![word(words[0])];
+        for &w in &words[1..] {
+            input.extend(std::iter::repeat_n(SHIFT, 7));
+            input.push(word(w));
+        }
+        input.extend(std::iter::repeat_n(SHIFT, 8));
+        let output = run(&Serializer::default(), input)?;
+        let serial = output[1..33].iter().map(|o| o.serial).collect::<Vec<_>>();
+        let expected = words.iter().flat_map(|&w| bits_of(w)).collect::<Vec<_>>();
+        assert_eq!(serial, expected);
+        // Busy throughout, with the strobe before each last bit
+        assert!(output[1..33].iter().all(|o| o.busy));
+        let strobes = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.next_word)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(strobes, [7, 15, 23, 31]);
+        assert_eq!(output.iter().filter(|o| o.taken).count(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gap_idles() -> miette::Result<()> {
+        // No word is ready after the first, so the line idles
+        // (high) until the second one is presented
+        let mut input = vec![word(0b0000_0000)];
+        input.extend(std::iter::repeat_n(SHIFT, 11));
+        input.push(word(0b1010_1010));
+        input.extend(std::iter::repeat_n(SHIFT, 9));
+        let output = run(&Serializer::new(true), input)?;
+        let serial = output.iter().map(|o| o.serial).collect::<Vec<_>>();
+        let mut expected = vec![true];
+        expected.extend(bits_of(0b0000_0000));
+        expected.extend([true; 4]);
+        expected.extend(bits_of(0b1010_1010));
+        expected.push(true);
+        assert_eq!(serial, expected);
+        assert_eq!(output.iter().filter(|o| o.busy).count(), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_enable_gaps_and_early_words() -> miette::Result<()> {
+        // With gaps in the enable, bits are held, and a word presented
+        // early is ignored until the last bit is sent
+        let mut input = vec![word(0b1000_0001)];
+        for _ in 0..8 {
+            input.push(In {
+                enable: false,
+                data: Some(b8(0b1111_1111)),
+            });
+            input.push(word(0b1111_1111));
+        }
+        input.extend(std::iter::repeat_n(SHIFT, 9));
+        let output = run(&Serializer::default(), input)?;
+        let taken = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.taken)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(taken, [0, 16]);
+        let serial = output
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .take(8)
+            .map(|o| o.serial)
+            .collect::<Vec<_>>();
+        assert_eq!(serial, bits_of(0b1000_0001));
+        assert!(output[17..25].iter().all(|o| o.serial));
+        assert!(!output[25].busy);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_mid_word() -> miette::Result<()> {
+        let uut = Serializer::<U8>::new(true);
+        let input = [word(0b0000_0000), SHIFT, SHIFT, SHIFT]
+            .into_iter()
+            .with_reset(1)
+            .chain([SHIFT, SHIFT].into_iter().with_reset(2))
+            .chain([word(0b0111_1111), SHIFT].into_iter().without_reset())
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| (t.value.2.serial, t.value.2.busy))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output,
+            [
+                (true, false),
+                (true, false),
+                (false, true),
+                (false, true),
+                (false, true),
+                // Reset abandons the word (on the next clock)
+                (false, true),
+                (true, false),
+                (true, false),
+                (true, false),
+                (true, false),
+                (false, true),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_single_bit_word() {
+        let _ = Serializer::<U1>::default();
+    }
+
+    #[test]
+    fn test_serializer_hdl() -> miette::Result<()> {
+        let uut = Serializer::<U8>::new(true);
+        let input = (0..60).map(|n| In {
+            enable: n % 7 != 3,
+            data: (n % 5 == 0).then(|| b8(n * 3)),
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! Wide Shift Register (`K` bits in per clock, parallel out)
+//!
+//! A [ShiftRegister](super::shift_reg::ShiftRegister) that shifts in
+//! `K` bits per enabled clock, for interfaces that deliver more than
+//! one bit per clock (e.g., a DDR input, which delivers two).  On each
+//! enabled clock, the register shifts left by `K`, and the new bits
+//! fill the low `K` positions, so that the MSB of the new bits is the
+//! earlier one in the serial stream.  The result is the same as
+//! shifting the `K` bits into a [ShiftRegister](super::shift_reg::ShiftRegister)
+//! one at a time, MSB first.  When `enable` is low, the register holds
+//! its value.  On reset, the register is cleared.
+//!
+//! `K` must be no larger than `N`.  If `N` is not a multiple of `K`,
+//! the register still shifts by `K` on each clock, so that a word of
+//! `N` bits does not line up with the clocks: after `ceil(N / K)`
+//! clocks, the oldest `K - N % K` bits have been shifted out of the
+//! top of the register.
+//!
+//! The input is a tuple of `(enable, data)`, and the output is the
+//! contents of the register.
+//!
+//!# Example
+//!
+//! Shifting in a byte, two bits at a time.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_in_wide::ShiftInWide;
+//!
+//! let uut = ShiftInWide::<U8, U2>::default();
+//! let input = [0b10, 0b11, 0b00, 0b10]
+//!     .into_iter()
+//!     .map(|pair| (true, b2(pair)))
+//!     .chain(std::iter::once((false, b2(0))));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2;
+//! assert_eq!(output, b8(0b1011_0010));
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The wide shift register core
+///   `N` is the number of bits in the register
+///   `K` is the number of bits shifted in per clock
+pub struct ShiftInWide<N: BitWidth, K: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    width: std::marker::PhantomData<Bits<K>>,
+}
+
+impl<N: BitWidth, K: BitWidth> Default for ShiftInWide<N, K> {
+    fn default() -> Self {
+        assert!(
+            K::BITS <= N::BITS,
+            "Can not shift more bits per clock than the register holds"
+        );
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+            width: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<N: BitWidth, K: BitWidth> SynchronousIO for ShiftInWide<N, K> {
+    type I = (bool, Bits<K>);
+    type O = Bits<N>;
+    type Kernel = shift_in_wide_kernel<N, K>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_in_wide_kernel<N: BitWidth, K: BitWidth>(
+    cr: ClockReset,
+    i: (bool, Bits<K>),
+    q: Q<N, K>,
+) -> (Bits<N>, D<N, K>) {
+    let (enable, data) = i;
+    let mut d = D::<N, K> {
+        reg: q.reg,
+        width: (),
+    };
+    if enable {
+        d.reg = (q.reg << (K::BITS as u128)) | data.resize::<N>();
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+    }
+    (q.reg, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::shift_reg::ShiftRegister;
+
+    // A serial stream of bits
+    fn stream() -> Vec<bool> {
+        (0..96_u32).map(|n| (n * 7 + n / 3) % 5 < 2).collect()
+    }
+
+    // The single bit core, one bit per clock
+    fn serial_words(bits: &[bool]) -> miette::Result<Vec<b8>> {
+        let uut = ShiftRegister::<U8>::default();
+        Ok(uut
+            .run(
+                bits.iter()
+                    .map(|&bit| (true, false, bit, b8(0)))
+                    .chain(std::iter::once((false, false, false, b8(0))))
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // Check the wide core against the single bit one.  The bits are
+    // grouped into `K` bit chunks (with an idle clock between some
+    // of them), and the register must match the single bit core after
+    // the same number of bits have gone in.
+    fn check_equivalence<K: BitWidth>() -> miette::Result<()> {
+        let serial_bits = stream();
+        let chunks = serial_bits
+            .chunks_exact(K::BITS)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0_u128, |acc, &bit| (acc << 1) | bit as u128)
+            })
+            .collect::<Vec<_>>();
+        let mut input = vec![];
+        for (n, &chunk) in chunks.iter().enumerate() {
+            input.push((true, Bits::<K>::from(chunk)));
+            if n % 3 == 2 {
+                input.push((false, Bits::<K>::from(0)));
+            }
+        }
+        input.push((false, Bits::<K>::from(0)));
+        let uut = ShiftInWide::<U8, K>::default();
+        let wide = uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let serial = serial_words(&serial_bits)?;
+        // The register after each enabled clock (which is the
+        // output on the clock after it)
+        let mut count = 0;
+        for (n, (enable, _)) in input.iter().enumerate() {
+            if *enable {
+                count += K::BITS;
+                assert_eq!(wide[n + 1], serial[count]);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_bits_per_clock() -> miette::Result<()> {
+        check_equivalence::<U2>()
+    }
+
+    #[test]
+    fn test_four_bits_per_clock() -> miette::Result<()> {
+        check_equivalence::<U4>()
+    }
+
+    #[test]
+    fn test_uneven_width() -> miette::Result<()> {
+        // With 3 bits per clock, three clocks shift in 9 bits,
+        // and the first of them falls off the top of the register
+        let uut = ShiftInWide::<U8, U3>::default();
+        let input = [(true, b3(0b110)), (true, b3(0b101)), (true, b3(0b011))]
+            .into_iter()
+            .chain(std::iter::once((false, b3(0))));
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .last()
+            .unwrap()
+            .value
+            .2;
+        assert_eq!(output, b8(0b1010_1011));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_wide() {
+        let _ = ShiftInWide::<U4, U8>::default();
+    }
+
+    #[test]
+    fn test_shift_in_wide_hdl() -> miette::Result<()> {
+        let uut = ShiftInWide::<U8, U2>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, b2(n % 4)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+//! Shift Out Register (parallel in, serial out)
+//!
+//! A shift register that is loaded with a word in parallel,
+//! and then shifts it out serially, MSB first, one bit per
+//! enabled clock.  The serial output is the MSB of the register
+//! (before shifting), and zeros are shifted in at the LSB.  If
+//! `load` and `enable` are both asserted, the load takes priority.
+//! On reset, the register is cleared.
+//!
+//! The bit order is set by the second type parameter.  The default
+//! is [MsbFirst].  With [LsbFirst] (e.g., for SPI peripherals in LSB
+//! first mode), the serial output is bit 0 of the register, and the
+//! register shifts right, with zeros shifted in at the MSB.
+//!
+//! An internal counter keeps track of the bits of the word still to be
+//! sent.  It is loaded with `N` on `load`, and counts down on each
+//! enabled shift.  When no word is being sent (before the first load,
+//! and after the last bit of a word has been shifted out), the serial
+//! output is driven to the idle level given at construction (low for
+//! the [Default]).  For lines that idle high, like a UART or SPI data
+//! line, use `ShiftOut::new(true)`.  Taking `enable` low in the middle
+//! of a word holds the current bit on the output, and does not idle the
+//! line.  The width of the word `N` must be less than 256.
+//!
+//! The input is a tuple of `(enable, load, data)`, and the
+//! output is the serial bit.  To know when the whole word has been
+//! sent, use the [ShiftOutCounted](super::shift_out_counted::ShiftOutCounted),
+//! which counts the bits as they are shifted out.  To play a word
+//! back continuously, use the [ShiftOutRotate](super::shift_out_rotate::ShiftOutRotate).
+//! To send a stream of words without a gap between them, use the
+//! [ShiftOutBuffered](super::shift_out_buffered::ShiftOutBuffered).
+//! When the bit clock is a clock enable strobe, use the
+//! [ShiftOutCe](super::shift_out_ce::ShiftOutCe).
+//!
+//!# Example
+//!
+//! Shifting out a byte.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out::ShiftOut;
+//!
+//! let uut = ShiftOut::<U8>::default();
+//! let input = std::iter::once((false, true, b8(0b1011_0010)))
+//!     .chain(std::iter::repeat_n((true, false, b8(0)), 8));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [true, false, true, true, false, false, true, false]);
+//!```
+//!
+//! The same byte, LSB first.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out::{LsbFirst, ShiftOut};
+//!
+//! let uut = ShiftOut::<U8, LsbFirst>::default();
+//! let input = std::iter::once((false, true, b8(0b1011_0010)))
+//!     .chain(std::iter::repeat_n((true, false, b8(0)), 8));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [false, true, false, false, true, true, false, true]);
+//!```
+//!
+//! A nibble on a line that idles high.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out::ShiftOut;
+//!
+//! let uut = ShiftOut::<U4>::new(true);
+//! let input = [(false, false, b4(0)), (false, true, b4(0b0100))]
+//!     .into_iter()
+//!     .chain(std::iter::repeat_n((true, false, b4(0)), 6));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2 as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [1, 1, 0, 1, 0, 0, 1, 1]);
+//!```
+use std::marker::PhantomData;
+
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+/// The order in which a [ShiftOut] sends the bits of a word
+pub trait BitOrder: Digital + Default {
+    /// True if bit 0 is sent first
+    const LSB_FIRST: bool;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+/// Send the most significant bit first (shifting left)
+pub struct MsbFirst;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+/// Send the least significant bit first (shifting right)
+pub struct LsbFirst;
+
+// The markers carry no data, so they are empty in hardware (the
+// derive macro does not accept unit structs)
+macro_rules! impl_bit_order {
+    ($marker: ident, $lsb_first: expr) => {
+        impl BitOrder for $marker {
+            const LSB_FIRST: bool = $lsb_first;
+        }
+
+        impl Digital for $marker {
+            const BITS: usize = 0;
+            fn static_kind() -> Kind {
+                Kind::Empty
+            }
+            fn static_trace_type() -> rtt::TraceType {
+                rtt::TraceType::Empty
+            }
+            fn bin(self) -> Vec<BitX> {
+                vec![]
+            }
+            fn dont_care() -> Self {
+                Self
+            }
+        }
+    };
+}
+
+impl_bit_order!(MsbFirst, false);
+impl_bit_order!(LsbFirst, true);
+
+#[doc_symbol(ShiftOut<U8>)]
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The shift out core
+///   `N` is the number of bits in the register
+///   `O` is the [BitOrder] ([MsbFirst] by default)
+///
+/// Here is the schematic symbol (for `N = 8`)
+#[doc = badascii_doc::badascii_formal!("
+      +-+ShiftOut+----+
+ bool |               | bool
++---->| i.0    output +---->
+ bool |               |
++---->| i.1           |
+ b8   |               |
++---->| i.2           |
+      +---------------+
+")]
+///
+/// Here `i.0` is the enable, `i.1` is the load, and `i.2` is the data to load.
+pub struct ShiftOut<N: BitWidth, O: BitOrder = MsbFirst> {
+    reg: dff::DFF<Bits<N>>,
+    count: dff::DFF<b8>,
+    width: constant::Constant<b8>,
+    idle: constant::Constant<bool>,
+    lsb_first: constant::Constant<bool>,
+    order: PhantomData<O>,
+}
+
+impl<N: BitWidth, O: BitOrder> ShiftOut<N, O> {
+    /// Create a shift out register whose serial output idles at the given level
+    pub fn new(idle: bool) -> Self {
+        assert!(N::BITS < 256, "The word must have fewer than 256 bits");
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+            count: dff::DFF::new(b8(0)),
+            width: constant::Constant::new(bits(N::BITS as u128)),
+            idle: constant::Constant::new(idle),
+            lsb_first: constant::Constant::new(O::LSB_FIRST),
+            order: PhantomData,
+        }
+    }
+}
+
+impl<N: BitWidth, O: BitOrder> Default for ShiftOut<N, O> {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl<N: BitWidth, O: BitOrder> SynchronousIO for ShiftOut<N, O> {
+    type I = (bool, bool, Bits<N>);
+    type O = bool;
+    type Kernel = shift_out_kernel<N, O>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_out_kernel<N: BitWidth, O: BitOrder>(
+    cr: ClockReset,
+    i: (bool, bool, Bits<N>),
+    q: Q<N, O>,
+) -> (bool, D<N, O>) {
+    let (enable, load, data) = i;
+    let mut d = D::<N, O> {
+        reg: q.reg,
+        count: q.count,
+        width: (),
+        idle: (),
+        lsb_first: (),
+        order: (),
+    };
+    let busy = q.count != 0;
+    if load {
+        d.reg = data;
+        d.count = q.width;
+    } else if enable {
+        if q.lsb_first {
+            d.reg = q.reg >> 1;
+        } else {
+            d.reg = q.reg << 1;
+        }
+        if busy {
+            d.count = q.count - 1;
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+        d.count = bits(0);
+    }
+    let bit = if q.lsb_first {
+        q.reg & 1 != 0
+    } else {
+        q.reg & (1 << (N::BITS - 1)) != 0
+    };
+    let serial = if busy { bit } else { q.idle };
+    (serial, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use rhdl::core::circuit::doc_symbol::schematic_symbol;
+
+    use super::*;
+
+    fn serial(uut: &ShiftOut<U8>, input: Vec<(bool, bool, b8)>) -> miette::Result<Vec<bool>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_shift_out_byte() -> miette::Result<()> {
+        let uut = ShiftOut::<U8>::default();
+        let mut input = vec![(false, true, b8(0b1100_1010))];
+        input.extend(std::iter::repeat_n((true, false, b8(0)), 9));
+        let output = serial(&uut, input)?;
+        assert_eq!(
+            output[1..],
+            [true, true, false, false, true, false, true, false, false]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_priority() -> miette::Result<()> {
+        let uut = ShiftOut::<U8>::default();
+        let input = vec![
+            (false, true, b8(0b1000_0000)),
+            // Load and enable together: the load wins
+            (true, true, b8(0b0100_0000)),
+            (false, false, b8(0)),
+            // Shift the 1 into the MSB
+            (true, false, b8(0)),
+            (false, false, b8(0)),
+        ];
+        let output = serial(&uut, input)?;
+        assert_eq!(output, [false, true, false, false, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_lsb_first_reverses() -> miette::Result<()> {
+        let mut input = vec![(false, true, b8(0b1100_1010))];
+        input.extend(std::iter::repeat_n((true, false, b8(0)), 8));
+        let sample = |output: Vec<TimedSample<(ClockReset, _, bool)>>| {
+            output
+                .into_iter()
+                .synchronous_sample()
+                .skip(2)
+                .map(|t| t.value.2)
+                .collect::<Vec<_>>()
+        };
+        let msb = sample(
+            ShiftOut::<U8, MsbFirst>::default()
+                .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+                .collect(),
+        );
+        let mut lsb = sample(
+            ShiftOut::<U8, LsbFirst>::default()
+                .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+                .collect(),
+        );
+        assert_eq!(lsb, [false, true, false, true, false, false, true, true]);
+        lsb.reverse();
+        assert_eq!(msb, lsb);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_idle_high() -> miette::Result<()> {
+        let uut = ShiftOut::<U8>::new(true);
+        let hold = (false, false, b8(0));
+        let shift = (true, false, b8(0));
+        let mut input = vec![hold, shift, (false, true, b8(0b0100_0110))];
+        input.extend([shift, shift, hold, hold]);
+        input.extend(std::iter::repeat_n(shift, 6));
+        input.extend([hold, shift]);
+        let output = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2 as u8)
+            .collect::<Vec<_>>();
+        // Idle high before the load (even when enabled)
+        assert_eq!(output[..3], [1, 1, 1]);
+        // The third bit (a zero) is held while enable is low
+        assert_eq!(output[3..7], [0, 1, 0, 0]);
+        assert_eq!(output[7..13], [0, 0, 0, 1, 1, 0]);
+        // Idle high after the last bit
+        assert_eq!(output[13..], [1, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_idle_high_hdl() -> miette::Result<()> {
+        let uut = ShiftOut::<U8>::new(true);
+        let input = (0..40).map(|n| (n % 3 != 0, n % 17 == 2, b8((n * 7 + 3) % 256)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_lsb_first_hdl() -> miette::Result<()> {
+        let uut = ShiftOut::<U8, LsbFirst>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 11 == 0, b8((n * 7 + 3) % 256)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_session() -> miette::Result<()> {
+        let uut = ShiftOut::<U8>::default();
+        let mut input = vec![(false, true, b8(0b1011_0010))];
+        input.extend(std::iter::repeat_n((true, false, b8(0)), 8));
+        let mut session = SimSession::new(&uut).recorded();
+        session.reset();
+        let mut output = vec![];
+        for (n, &i) in input.iter().enumerate() {
+            output.push(session.step(i));
+            // After the load, each edge shifts the register left by one
+            let reg = session.peek("top.reg.dff.output").unwrap().as_i64()?;
+            assert_eq!(reg, (0b1011_0010 << n) & 0xFF);
+        }
+        assert_eq!(
+            output[1..],
+            [true, false, true, true, false, false, true, false]
+        );
+        // The register is empty, so the output never rises again
+        session.poke_input((true, false, b8(0)));
+        let err = session.run_until(|&o| o, 4).unwrap_err();
+        assert!(err.session_error().is_some());
+        assert_eq!(session.cycle(), 14);
+        let mut vcd = vec![];
+        session.dump_vcd(&mut vcd).unwrap();
+        assert!(String::from_utf8(vcd).unwrap().contains("$enddefinitions"));
+        // The recorded trace matches a batch run of the same inputs
+        input.extend(std::iter::repeat_n((true, false, b8(0)), 4));
+        let batch = uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .collect::<Vec<_>>();
+        assert_eq!(session.samples(), batch);
+        let batch = batch
+            .into_iter()
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output, batch[..output.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_hdl() -> miette::Result<()> {
+        let uut = ShiftOut::<U8>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 11 == 0, b8((n * 7 + 3) % 256)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_symbol() {
+        let expect = expect![[r#"
+                  +-+ShiftOut+----+
+             bool |               | bool
+            +---->| i.0    output +---->
+             bool |               |
+            +---->| i.1           |
+             b8   |               |
+            +---->| i.2           |
+                  +---------------+"#]];
+        expect.assert_eq(&schematic_symbol::<ShiftOut<U8>>("ShiftOut"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Double Buffered Shift Out Register (parallel in, serial out)
+//!
+//! A [ShiftOut](super::shift_out::ShiftOut) with a second register
+//! that holds a pending word, so that a stream of words can be sent
+//! without gaps.  With a plain [ShiftOut](super::shift_out::ShiftOut),
+//! the next word can only be loaded once the last bit of the current
+//! one has been sent, which costs a clock between words.  Here, a word
+//! loaded while the core is busy is stored in the pending register, and
+//! transferred to the shift register on the (enabled) clock that sends
+//! the last bit of the current word, so that its first bit follows
+//! without a gap.
+//!
+//! Words are shifted out MSB first, one bit per enabled clock, and a bit
+//! counter keeps track of the bits still to be sent from the shift
+//! register.  A word loaded while the core is idle goes straight to the
+//! shift register.  The `ready_for_data` output is high when the
+//! pending register is free.  A load while it is not is dropped (the
+//! words already held are kept), and flagged by `dropped` on the same
+//! clock.  A load on the clock that the pending word is transferred is
+//! accepted, since that frees the pending register.  On reset, both
+//! registers are emptied.
+//!
+//! The input is a tuple of `(enable, load, data)`, as for the
+//! [ShiftOut](super::shift_out::ShiftOut).
+//!
+//!# Example
+//!
+//! Two nibbles, loaded back to back, and sent without a gap.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out_buffered::ShiftOutBuffered;
+//!
+//! let uut = ShiftOutBuffered::<U4>::default();
+//! let input = [(false, true, b4(0b1001)), (true, true, b4(0b0110))]
+//!     .into_iter()
+//!     .chain(std::iter::repeat_n((true, false, b4(0)), 8));
+//! let serial = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2.serial as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(serial, [1, 0, 0, 1, 0, 1, 1, 0, 0]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff, shift_out::ShiftOut};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ShiftOutBuffered]
+pub struct Out {
+    /// The serial output (the MSB of the shift register)
+    pub serial: bool,
+    /// High while bits of a word remain to be sent
+    pub busy: bool,
+    /// High when the pending register is free for a load
+    pub ready_for_data: bool,
+    /// High on the clock that a load is dropped
+    pub dropped: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The double buffered shift out core
+///   `N` is the number of bits in a word
+pub struct ShiftOutBuffered<N: BitWidth> {
+    shift: ShiftOut<N>,
+    count: dff::DFF<b8>,
+    pending: dff::DFF<Bits<N>>,
+    full: dff::DFF<bool>,
+    width: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> Default for ShiftOutBuffered<N> {
+    fn default() -> Self {
+        Self {
+            shift: ShiftOut::default(),
+            count: dff::DFF::new(b8(0)),
+            pending: dff::DFF::new(Bits::<N>::default()),
+            full: dff::DFF::new(false),
+            width: constant::Constant::new(bits(N::BITS as u128)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftOutBuffered<N> {
+    type I = (bool, bool, Bits<N>);
+    type O = Out;
+    type Kernel = shift_out_buffered_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_out_buffered_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, Bits<N>),
+    q: Q<N>,
+) -> (Out, D<N>) {
+    let (enable, load, data) = i;
+    let busy = q.count != 0;
+    let shift = enable && busy;
+    // The shift register is free after this clock
+    let empty = !busy || (shift && q.count == 1);
+    let mut d = D::<N> {
+        shift: (shift, false, q.pending),
+        count: q.count,
+        pending: q.pending,
+        full: q.full,
+        width: (),
+    };
+    let mut dropped = false;
+    if shift {
+        d.count = q.count - 1;
+    }
+    if empty && q.full {
+        // Transfer the pending word (and refill the pending register)
+        d.shift = (shift, true, q.pending);
+        d.count = q.width;
+        d.full = load;
+        d.pending = data;
+    } else if empty && load {
+        d.shift = (shift, true, data);
+        d.count = q.width;
+    } else if load {
+        if q.full {
+            dropped = true;
+        } else {
+            d.pending = data;
+            d.full = true;
+        }
+    }
+    if cr.reset.any() {
+        d.count = b8(0);
+        d.full = false;
+    }
+    let o = Out {
+        serial: q.shift,
+        busy,
+        ready_for_data: !q.full,
+        dropped,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: Vec<(bool, bool, b8)>) -> miette::Result<Vec<Out>> {
+        let uut = ShiftOutBuffered::<U8>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn load(enable: bool, data: u8) -> (bool, bool, b8) {
+        (enable, true, b8(data as u128))
+    }
+
+    const SHIFT: (bool, bool, b8) = (true, false, b8(0));
+    const IDLE: (bool, bool, b8) = (false, false, b8(0));
+
+    fn bits_of(data: u8) -> Vec<bool> {
+        (0..8).rev().map(|n| data & (1 << n) != 0).collect()
+    }
+
+    #[test]
+    fn test_gapless_two_words() -> miette::Result<()> {
+        let mut input = vec![load(false, 0b1100_1010), load(true, 0b0011_0101)];
+        input.extend(std::iter::repeat_n(SHIFT, 17));
+        let output = run(input)?;
+        let serial = output[1..17].iter().map(|o| o.serial).collect::<Vec<_>>();
+        let mut expected = bits_of(0b1100_1010);
+        expected.extend(bits_of(0b0011_0101));
+        assert_eq!(serial, expected);
+        assert!(output[1..17].iter().all(|o| o.busy));
+        assert!(!output[17].busy);
+        // The pending register is full until the transfer
+        let ready = output.iter().map(|o| o.ready_for_data).collect::<Vec<_>>();
+        assert!(ready[0] && ready[1]);
+        assert!(ready[2..9].iter().all(|r| !r));
+        assert!(ready[9..].iter().all(|r| *r));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_when_full_is_dropped() -> miette::Result<()> {
+        let mut input = vec![
+            load(false, 0b1111_0000),
+            load(true, 0b1010_1010),
+            load(true, 0b1111_1111),
+        ];
+        input.extend(std::iter::repeat_n(SHIFT, 5));
+        // A load on the clock of the transfer is accepted
+        input.push(load(true, 0b0000_0001));
+        input.extend(std::iter::repeat_n(SHIFT, 24));
+        let output = run(input)?;
+        let dropped = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.dropped)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(dropped, [2]);
+        let serial = output[1..25].iter().map(|o| o.serial).collect::<Vec<_>>();
+        let expected = [0b1111_0000, 0b1010_1010, 0b0000_0001]
+            .into_iter()
+            .flat_map(bits_of)
+            .collect::<Vec<_>>();
+        assert_eq!(serial, expected);
+        assert!(!output[25].busy);
+        Ok(())
+    }
+
+    #[test]
+    fn test_enable_gaps() -> miette::Result<()> {
+        // The transfer waits for the enabled clock of the last bit
+        let mut input = vec![load(false, 0b1000_0001), load(false, 0b0111_1110)];
+        for _ in 0..16 {
+            input.push(SHIFT);
+            input.push(IDLE);
+        }
+        let output = run(input)?;
+        let serial = output
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .take(16)
+            .map(|o| o.serial)
+            .collect::<Vec<_>>();
+        let mut expected = bits_of(0b1000_0001);
+        expected.extend(bits_of(0b0111_1110));
+        assert_eq!(serial, expected);
+        assert_eq!(output.iter().filter(|o| o.busy).count(), 32);
+        assert!(output.iter().all(|o| !o.dropped));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_empties_both() -> miette::Result<()> {
+        let uut = ShiftOutBuffered::<U8>::default();
+        let input = [load(false, 0xFF), load(true, 0xFF), SHIFT]
+            .into_iter()
+            .with_reset(1)
+            .chain([SHIFT; 12].into_iter().with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .skip(5)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert!(output
+            .iter()
+            .all(|o| !o.serial && !o.busy && o.ready_for_data));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_buffered_hdl() -> miette::Result<()> {
+        let uut = ShiftOutBuffered::<U8>::default();
+        let input = (0..80).map(|n| (n % 9 != 4, n % 6 == 0, b8((n * 7) % 256)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
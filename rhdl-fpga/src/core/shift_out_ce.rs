@@ -0,0 +1,187 @@
+//! Shift Out Register with a clock enable (parallel in, serial out)
+//!
+//! A [ShiftOut](super::shift_out::ShiftOut) for serial interfaces whose
+//! bit clock is a clock enable strobe (from a
+//! [StrobeDiv](super::strobe_div::StrobeDiv), for example), rather than a
+//! clock of its own.  The strobe is a dedicated input, `ce`, so it does
+//! not have to be combined with `enable` at each use.  The register only
+//! shifts on clocks when both `ce` and `enable` are high, so each bit is
+//! held on the serial output for a whole period of the strobe.  The
+//! `load` is sampled on every system clock, whether or not the strobe is
+//! high, so a word can be loaded between strobes, and its first bit is
+//! presented until the next strobe shifts it out.  As for the
+//! [ShiftOut](super::shift_out::ShiftOut), a load takes priority over a
+//! shift, and the register is cleared on reset.
+//!
+//!# Example
+//!
+//! Sending a nibble, with a strobe every other clock.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out_ce::{In, ShiftOutCe};
+//!
+//! let uut = ShiftOutCe::<U4>::default();
+//! let input = (0..9).map(|n| In {
+//!     ce: n % 2 == 0,
+//!     enable: true,
+//!     load: n == 0,
+//!     data: b4(0b1011),
+//! });
+//! let serial = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2 as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(serial, [1, 1, 0, 0, 1, 1, 1, 1]);
+//!```
+use rhdl::prelude::*;
+
+use super::shift_out::ShiftOut;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [ShiftOutCe]
+pub struct In<N: BitWidth> {
+    /// The clock enable strobe
+    pub ce: bool,
+    /// Shift on this clock (if the strobe is high)
+    pub enable: bool,
+    /// Load the data on this clock (regardless of the strobe)
+    pub load: bool,
+    /// The data to load
+    pub data: Bits<N>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+/// The shift out core with a clock enable
+///   `N` is the number of bits in the register
+pub struct ShiftOutCe<N: BitWidth> {
+    reg: ShiftOut<N>,
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftOutCe<N> {
+    type I = In<N>;
+    type O = bool;
+    type Kernel = shift_out_ce_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_out_ce_kernel<N: BitWidth>(_cr: ClockReset, i: In<N>, q: Q<N>) -> (bool, D<N>) {
+    let d = D::<N> {
+        reg: (i.ce && i.enable, i.load, i.data),
+    };
+    (q.reg, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::strobe_div::StrobeDiv;
+
+    // A transmitter with the bit clock divided by 4
+    #[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+    struct Transmitter {
+        strobe: StrobeDiv<U4>,
+        reg: ShiftOutCe<U8>,
+    }
+
+    impl SynchronousIO for Transmitter {
+        type I = Option<b8>;
+        type O = (bool, bool);
+        type Kernel = transmitter_kernel;
+    }
+
+    #[kernel]
+    fn transmitter_kernel(_cr: ClockReset, i: Option<b8>, q: Q) -> ((bool, bool), D) {
+        let (load, data) = match i {
+            Some(data) => (true, data),
+            None => (false, b8(0)),
+        };
+        let d = D {
+            strobe: b4(4),
+            reg: In::<U8> {
+                ce: q.strobe,
+                enable: true,
+                load,
+                data,
+            },
+        };
+        ((q.reg, q.strobe), d)
+    }
+
+    #[test]
+    fn test_byte_at_divide_by_4() -> miette::Result<()> {
+        // The load comes between strobes (which are on clocks 3, 7, ...)
+        let mut input = vec![None; 5];
+        input.push(Some(b8(0b1011_0010)));
+        input.extend(std::iter::repeat_n(None, 34));
+        let output = Transmitter::default()
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let strobes = output
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, s))| *s)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(strobes[..3], [3, 7, 11]);
+        // The first bit is presented from the clock after the load,
+        // until the next strobe, and each bit after that for 4 clocks
+        let serial = output.iter().map(|(s, _)| *s as u8).collect::<Vec<_>>();
+        assert_eq!(serial[..6], [0; 6]);
+        assert_eq!(serial[6..8], [1, 1]);
+        let bits = (0..8)
+            .map(|n| serial[8 + 4 * n..12 + 4 * n].to_vec())
+            .collect::<Vec<_>>();
+        for (bit, expected) in bits.iter().zip([0, 1, 1, 0, 0, 1, 0, 0]) {
+            assert_eq!(bit, &[expected; 4]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_needs_strobe_and_enable() -> miette::Result<()> {
+        let uut = ShiftOutCe::<U8>::default();
+        let input = [
+            (false, false, true),
+            (true, false, false),
+            (false, true, false),
+            (true, true, false),
+            (false, false, false),
+        ]
+        .map(|(ce, enable, load)| In {
+            ce,
+            enable,
+            load,
+            data: b8(0b1000_0000),
+        });
+        let serial = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(serial, [false, true, true, true, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transmitter_hdl() -> miette::Result<()> {
+        let uut = Transmitter::default();
+        let input = (0..60).map(|n| (n % 23 == 2).then(|| b8(n * 3)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
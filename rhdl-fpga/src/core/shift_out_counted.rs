@@ -0,0 +1,237 @@
+//! Shift Out Register with a bit counter (parallel in, serial out)
+//!
+//! A [ShiftOut](super::shift_out::ShiftOut) that keeps track of the
+//! bits still to be sent.  A word is loaded in parallel, and shifted
+//! out serially, MSB first, one bit per enabled clock.  An internal
+//! counter is loaded with `N` on `load`, and counts down on each enabled
+//! shift, so that:
+//!
+//! - `busy` is high from the clock after the load, until the last bit
+//!   has been shifted out.
+//! - `done` pulses on the clock on which the last bit is presented on
+//!   the serial output, and shifted out (i.e., when `enable` is high).
+//!
+//! As for the [ShiftOut](super::shift_out::ShiftOut), if `load` and
+//! `enable` are both asserted, the load takes priority.  A load while
+//! the core is busy abandons the word being sent, and restarts the count
+//! with the new one.  Enabled shifts when the core is not busy shift
+//! zeros out, and leave it idle.  On reset, the register and the counter
+//! are cleared.
+//!
+//! The input is a tuple of `(enable, load, data)`.
+//!
+//!# Example
+//!
+//! Sending a byte to a serial DAC.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out_counted::ShiftOutCounted;
+//!
+//! let uut = ShiftOutCounted::<U8>::default();
+//! let input = std::iter::once((false, true, b8(0b1011_0010)))
+//!     .chain(std::iter::repeat_n((true, false, b8(0)), 10));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! let serial = output.iter().take(8).map(|o| o.serial).collect::<Vec<_>>();
+//! assert_eq!(serial, [true, false, true, true, false, false, true, false]);
+//! assert!(output[7].done);
+//! assert!(!output[8].busy);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ShiftOutCounted]
+pub struct Out {
+    /// The serial output (the MSB of the register)
+    pub serial: bool,
+    /// High while bits of the word remain to be sent
+    pub busy: bool,
+    /// High on the clock the last bit of the word is sent
+    pub done: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The shift out core with a bit counter
+///   `N` is the number of bits in the register
+pub struct ShiftOutCounted<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    count: dff::DFF<b8>,
+    width: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> Default for ShiftOutCounted<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+            count: dff::DFF::new(b8(0)),
+            width: constant::Constant::new(bits(N::BITS as u128)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftOutCounted<N> {
+    type I = (bool, bool, Bits<N>);
+    type O = Out;
+    type Kernel = shift_out_counted_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_out_counted_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, Bits<N>),
+    q: Q<N>,
+) -> (Out, D<N>) {
+    let (enable, load, data) = i;
+    let mut d = D::<N> {
+        reg: q.reg,
+        count: q.count,
+        width: (),
+    };
+    let busy = q.count != 0;
+    if load {
+        d.reg = data;
+        d.count = q.width;
+    } else if enable {
+        d.reg = q.reg << 1;
+        if busy {
+            d.count = q.count - 1;
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+        d.count = bits(0);
+    }
+    let o = Out {
+        serial: q.reg & (1 << (N::BITS - 1)) != 0,
+        busy,
+        done: enable && !load && q.count == 1,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: Vec<(bool, bool, b8)>) -> miette::Result<Vec<Out>> {
+        let uut = ShiftOutCounted::<U8>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn load(data: u8) -> (bool, bool, b8) {
+        (false, true, b8(data as u128))
+    }
+
+    const SHIFT: (bool, bool, b8) = (true, false, b8(0));
+
+    #[test]
+    fn test_full_transmission() -> miette::Result<()> {
+        let mut input = vec![load(0b1100_1010)];
+        input.extend(std::iter::repeat_n(SHIFT, 8));
+        let output = run(input)?;
+        let serial = output[1..9].iter().map(|o| o.serial).collect::<Vec<_>>();
+        assert_eq!(serial, [true, true, false, false, true, false, true, false]);
+        // Busy for the 8 bits, and done with the last one
+        let busy = output.iter().map(|o| o.busy).collect::<Vec<_>>();
+        assert_eq!(
+            busy,
+            [false, true, true, true, true, true, true, true, true]
+        );
+        let done = output.iter().position(|o| o.done);
+        assert_eq!(done, Some(8));
+        assert_eq!(output.iter().filter(|o| o.done).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_done_waits_for_enable() -> miette::Result<()> {
+        // Gaps in the enable stretch the transmission, and the
+        // done pulse comes with the shift of the last bit
+        let mut input = vec![load(0xFF)];
+        for _ in 0..8 {
+            input.push(SHIFT);
+            input.push((false, false, b8(0)));
+        }
+        let output = run(input)?;
+        assert_eq!(output.iter().filter(|o| o.done).count(), 1);
+        assert_eq!(output.iter().position(|o| o.done), Some(15));
+        assert_eq!(output.iter().filter(|o| o.busy).count(), 15);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_mid_stream() -> miette::Result<()> {
+        let mut input = vec![load(0b1111_0000)];
+        input.extend(std::iter::repeat_n(SHIFT, 3));
+        // Load and enable together: the load wins, and the count restarts
+        input.push((true, true, b8(0b0101_0101)));
+        input.extend(std::iter::repeat_n(SHIFT, 8));
+        let output = run(input)?;
+        let serial = output[5..13].iter().map(|o| o.serial).collect::<Vec<_>>();
+        assert_eq!(serial, [false, true, false, true, false, true, false, true]);
+        // The first word is abandoned without a done pulse
+        assert!(!output[4].done);
+        assert!(output[1..13].iter().all(|o| o.busy));
+        assert_eq!(output.iter().position(|o| o.done), Some(12));
+        assert_eq!(output.iter().filter(|o| o.done).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_after_completion() -> miette::Result<()> {
+        let mut input = vec![load(0b1000_0001)];
+        input.extend(std::iter::repeat_n(SHIFT, 12));
+        input.extend(std::iter::repeat_n((false, false, b8(0)), 4));
+        let output = run(input)?;
+        // Once the word has been sent, the core is idle, and further
+        // shifts only send zeros
+        assert!(output[9..].iter().all(|o| !o.serial && !o.busy && !o.done));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_clears_count() -> miette::Result<()> {
+        let uut = ShiftOutCounted::<U8>::default();
+        let input = [load(0xFF), SHIFT, SHIFT]
+            .into_iter()
+            .with_reset(1)
+            .chain(std::iter::repeat_n(SHIFT, 8).with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert!(output[2..4].iter().all(|o| o.busy && o.serial));
+        assert!(output[5..].iter().all(|o| !o.busy && !o.serial && !o.done));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_counted_hdl() -> miette::Result<()> {
+        let uut = ShiftOutCounted::<U8>::default();
+        let input = (0..60).map(|n| (n % 3 != 0, n % 13 == 0, b8((n * 7 + 3) % 256)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+//! Recirculating Shift Out Register (parallel in, serial out)
+//!
+//! A [ShiftOut](super::shift_out::ShiftOut) that rotates rather than
+//! shifts.  A word is loaded in parallel, and sent serially, MSB first,
+//! one bit per enabled clock.  On each enabled clock, the outgoing MSB
+//! is fed back into the LSB (instead of a zero), so that the loaded
+//! word plays back continuously, with a period of `N` enabled clocks.
+//! This is handy for repeating test patterns (e.g., driving a scope
+//! with a fixed bit sequence).
+//!
+//! If `load` and `enable` are both asserted, the load takes priority.
+//! On reset, the register is cleared (and so sends zeros until the
+//! next load).
+//!
+//! The input is a tuple of `(enable, load, data)`, and the output is
+//! the serial bit.
+//!
+//!# Example
+//!
+//! Playing back a 4 bit pattern.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out_rotate::ShiftOutRotate;
+//!
+//! let uut = ShiftOutRotate::<U4>::default();
+//! let input = std::iter::once((false, true, b4(0b1101)))
+//!     .chain(std::iter::repeat_n((true, false, b4(0)), 12));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output[0..4], [true, true, false, true]);
+//! assert_eq!(output[0..4], output[4..8]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The recirculating shift out core
+///   `N` is the number of bits in the register
+pub struct ShiftOutRotate<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for ShiftOutRotate<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftOutRotate<N> {
+    type I = (bool, bool, Bits<N>);
+    type O = bool;
+    type Kernel = shift_out_rotate_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_out_rotate_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, Bits<N>),
+    q: Q<N>,
+) -> (bool, D<N>) {
+    let (enable, load, data) = i;
+    let mut d = D::<N> { reg: q.reg };
+    let msb = q.reg & (1 << (N::BITS - 1)) != 0;
+    if load {
+        d.reg = data;
+    } else if enable {
+        d.reg = q.reg << 1;
+        if msb {
+            d.reg |= 1;
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+    }
+    (msb, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIFT: (bool, bool, b8) = (true, false, b8(0));
+
+    #[test]
+    fn test_register_returns_after_n_shifts() -> miette::Result<()> {
+        let uut = ShiftOutRotate::<U8>::default();
+        let mut session = SimSession::new(&uut);
+        session.reset();
+        session.step((false, true, b8(0b1001_0110)));
+        for n in 1..=16 {
+            session.step(SHIFT);
+            let reg = session.peek("top.reg.dff.output").unwrap().as_i64()?;
+            assert_eq!(reg, 0b1001_0110_u8.rotate_left(n) as i64);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serial_repeats_with_period_n() -> miette::Result<()> {
+        let uut = ShiftOutRotate::<U8>::default();
+        let mut input = vec![(false, true, b8(0b1110_0100))];
+        // Idle clocks hold the pattern, without advancing it
+        for n in 0..40 {
+            input.push(if n % 5 == 4 {
+                (false, false, b8(0))
+            } else {
+                SHIFT
+            });
+        }
+        let output = uut
+            .run(input.clone().into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The bits sent on the enabled clocks
+        let sent = output
+            .iter()
+            .zip(input)
+            .filter(|(_, i)| i.0)
+            .map(|(o, _)| *o)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sent[0..8],
+            [true, true, true, false, false, true, false, false]
+        );
+        for (n, bit) in sent.iter().enumerate().skip(8) {
+            assert_eq!(*bit, sent[n - 8]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_and_reset() -> miette::Result<()> {
+        let uut = ShiftOutRotate::<U8>::default();
+        let input = [
+            (false, true, b8(0xF0)),
+            SHIFT,
+            (true, true, b8(0x01)),
+            SHIFT,
+        ]
+        .into_iter()
+        .with_reset(1)
+        .chain(std::iter::repeat_n(SHIFT, 9).with_reset(1))
+        .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The load wins over the enable, replacing the rotating word
+        assert_eq!(output[1..5], [false, true, true, false]);
+        // After the reset, the register is empty and only zeros circulate
+        assert!(output[6..].iter().all(|o| !o));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_out_rotate_hdl() -> miette::Result<()> {
+        let uut = ShiftOutRotate::<U8>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 17 == 0, b8((n * 7 + 3) % 256)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! Wide Shift Out Register (parallel in, `K` bits out per clock)
+//!
+//! A [ShiftOut](super::shift_out::ShiftOut) that sends `K` bits per
+//! enabled clock, for interfaces that take more than one bit per clock
+//! (e.g., a DDR output, which takes two).  A word is loaded in
+//! parallel, and the output is the top `K` bits of the register (with
+//! the MSB of the output being the earlier one in the serial stream).
+//! On each enabled clock, the register shifts left by `K`, with zeros
+//! shifted in at the bottom.  The result is the same as taking `K`
+//! bits at a time from a [ShiftOut](super::shift_out::ShiftOut).  If
+//! `load` and `enable` are both asserted, the load takes priority.  On
+//! reset, the register is cleared.
+//!
+//! `K` must be no larger than `N`.  If `N` is not a multiple of `K`,
+//! the last `N % K` bits of a word are sent in the top of the output
+//! on the last clock, with zeros below them.
+//!
+//! The input is a tuple of `(enable, load, data)`, and the output is
+//! the next `K` bits.
+//!
+//!# Example
+//!
+//! Sending a byte, two bits at a time.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_out_wide::ShiftOutWide;
+//!
+//! let uut = ShiftOutWide::<U8, U2>::default();
+//! let input = std::iter::once((false, true, b8(0b1011_0010)))
+//!     .chain(std::iter::repeat_n((true, false, b8(0)), 4));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [b2(0b10), b2(0b11), b2(0b00), b2(0b10)]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The wide shift out core
+///   `N` is the number of bits in the register
+///   `K` is the number of bits sent per clock
+pub struct ShiftOutWide<N: BitWidth, K: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    width: std::marker::PhantomData<Bits<K>>,
+}
+
+impl<N: BitWidth, K: BitWidth> Default for ShiftOutWide<N, K> {
+    fn default() -> Self {
+        assert!(
+            K::BITS <= N::BITS,
+            "Can not shift more bits per clock than the register holds"
+        );
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+            width: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<N: BitWidth, K: BitWidth> SynchronousIO for ShiftOutWide<N, K> {
+    type I = (bool, bool, Bits<N>);
+    type O = Bits<K>;
+    type Kernel = shift_out_wide_kernel<N, K>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_out_wide_kernel<N: BitWidth, K: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, Bits<N>),
+    q: Q<N, K>,
+) -> (Bits<K>, D<N, K>) {
+    let (enable, load, data) = i;
+    let mut d = D::<N, K> {
+        reg: q.reg,
+        width: (),
+    };
+    if load {
+        d.reg = data;
+    } else if enable {
+        d.reg = q.reg << (K::BITS as u128);
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+    }
+    let o = (q.reg >> ((N::BITS - K::BITS) as u128)).resize::<K>();
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::shift_out::ShiftOut;
+
+    fn words() -> Vec<u8> {
+        (0..12_u32).map(|n| ((n * 97 + 13) % 256) as u8).collect()
+    }
+
+    // Load each word, and send it with `shifts` enabled clocks
+    fn input(shifts: usize) -> Vec<(bool, bool, b8)> {
+        words()
+            .into_iter()
+            .flat_map(|word| {
+                std::iter::once((false, true, b8(word as u128)))
+                    .chain(std::iter::repeat_n((true, false, b8(0)), shifts))
+            })
+            .collect()
+    }
+
+    // Check the wide core against the single bit one, which sends
+    // each word with `K` times as many clocks
+    fn check_equivalence<K: BitWidth>() -> miette::Result<()> {
+        let uut = ShiftOutWide::<U8, K>::default();
+        let wide = uut
+            .run(
+                input(8 / K::BITS)
+                    .into_iter()
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let serial = ShiftOut::<U8>::default()
+            .run(input(8).into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The bits sent while shifting (skipping the load clocks)
+        let wide = wide
+            .chunks(8 / K::BITS + 1)
+            .flat_map(|clocks| clocks[1..].to_vec())
+            .flat_map(|chunk| (0..K::BITS).rev().map(move |n| chunk.raw() & (1 << n) != 0))
+            .collect::<Vec<_>>();
+        let serial = serial
+            .chunks(9)
+            .flat_map(|clocks| clocks[1..].to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(wide, serial);
+        assert_eq!(wide.len(), words().len() * 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_bits_per_clock() -> miette::Result<()> {
+        check_equivalence::<U2>()
+    }
+
+    #[test]
+    fn test_four_bits_per_clock() -> miette::Result<()> {
+        check_equivalence::<U4>()
+    }
+
+    #[test]
+    fn test_uneven_width() -> miette::Result<()> {
+        // With 3 bits per clock, the last 2 bits of the byte are sent
+        // in the top of the output, with a zero below them
+        let uut = ShiftOutWide::<U8, U3>::default();
+        let input = std::iter::once((false, true, b8(0b1011_0011)))
+            .chain(std::iter::repeat_n((true, false, b8(0)), 3));
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output, [b3(0b101), b3(0b100), b3(0b110)]);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_wide() {
+        let _ = ShiftOutWide::<U4, U8>::default();
+    }
+
+    #[test]
+    fn test_shift_out_wide_hdl() -> miette::Result<()> {
+        let uut = ShiftOutWide::<U8, U2>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 7 == 0, b8((n * 7 + 3) % 256)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
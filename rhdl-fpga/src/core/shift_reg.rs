@@ -0,0 +1,303 @@
+//! Shift Register (serial in, parallel out)
+//!
+//! A shift register that shifts in one bit per enabled clock.
+//! The register shifts left, with the serial bit entering at the
+//! LSB, so that after `N` enabled clocks, the first bit shifted
+//! in is the MSB of the parallel output.  When `enable` is low,
+//! the register holds its value.  On reset, the register is cleared.
+//!
+//! The register can also be loaded in parallel (to preload a sync
+//! pattern, for example).  When `load` is asserted, the register is
+//! set to `data_in`, and the new contents appear on the output on the
+//! next clock.  If `load` and `enable` are both asserted, the load
+//! takes priority.
+//!
+//! The input is a tuple of `(enable, load, serial_in, data_in)`, and
+//! the output is the contents of the register.  The register is
+//! [Scannable], so it can be wrapped in a
+//! [ScanWrapper](super::scan::ScanWrapper).
+//!
+//!# Example
+//!
+//! Shifting in a byte, MSB first.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_reg::ShiftRegister;
+//!
+//! let uut = ShiftRegister::<U8>::default();
+//! let input = [1, 0, 1, 1, 0, 0, 1, 0]
+//!     .into_iter()
+//!     .map(|bit| (true, false, bit == 1, b8(0)))
+//!     .chain(std::iter::once((false, false, false, b8(0))));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2;
+//! assert_eq!(output, b8(0b1011_0010));
+//!```
+use rhdl::prelude::*;
+
+use super::{dff, scan::Scannable};
+
+#[doc_symbol(ShiftRegister<U8>, table)]
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The shift register core
+///   `N` is the number of bits in the register
+///
+/// Here is the schematic symbol (for `N = 8`)
+#[doc = badascii_doc::badascii_formal!("
+      +-+ShiftRegister+---+
+ bool |                   | b8
++---->| i.0        output +---->
+ bool |                   |
++---->| i.1               |
+ bool |                   |
++---->| i.2               |
+ b8   |                   |
++---->| i.3               |
+      +-------------------+
+")]
+///
+/// | Port | Direction | Type | Bits |
+/// |------|-----------|------|------|
+/// | `i.0` | input | `bool` | 1 |
+/// | `i.1` | input | `bool` | 1 |
+/// | `i.2` | input | `bool` | 1 |
+/// | `i.3` | input | `b8` | 8 |
+/// | `output` | output | `b8` | 8 |
+///
+/// Here `i.0` is the enable, `i.1` is the load, `i.2` is the serial
+/// input, and `i.3` is the data to load.
+pub struct ShiftRegister<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for ShiftRegister<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftRegister<N> {
+    type I = (bool, bool, bool, Bits<N>);
+    type O = Bits<N>;
+    type Kernel = shift_reg_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_reg_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, bool, Bits<N>),
+    q: Q<N>,
+) -> (Bits<N>, D<N>) {
+    let (enable, load, serial_in, data_in) = i;
+    let mut d = D::<N> { reg: q.reg };
+    if load {
+        d.reg = data_in;
+    } else if enable {
+        d.reg = q.reg << 1;
+        if serial_in {
+            d.reg |= 1;
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+    }
+    (q.reg, d)
+}
+
+impl<N: BitWidth> Scannable for ShiftRegister<N> {
+    type W = N;
+    type Next = shift_reg_scan_kernel<N>;
+    fn reset_state(&self) -> Bits<N> {
+        self.reg.reset_value()
+    }
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_reg_scan_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: ((bool, bool, bool, Bits<N>), Bits<N>),
+) -> (Bits<N>, Bits<N>) {
+    let (i, reg) = i;
+    let (o, d) = shift_reg_kernel::<N>(cr, i, Q::<N> { reg });
+    (o, d.reg)
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, expect_file};
+    use rhdl::core::{
+        circuit::doc_symbol::{port_table, schematic_symbol},
+        sim::vector::VectorTable,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_shift_in_byte() -> miette::Result<()> {
+        let uut = ShiftRegister::<U8>::default();
+        let input = [1, 1, 0, 1, 0, 0, 0, 1]
+            .into_iter()
+            .map(|bit| (true, false, bit == 1, b8(0)))
+            .chain(std::iter::repeat_n((false, false, true, b8(0)), 3));
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.raw())
+            .collect::<Vec<_>>();
+        // The output lags the input by one clock, and
+        // holds once the enable drops
+        assert_eq!(
+            output,
+            [
+                0, 0b1, 0b11, 0b110, 0b1101, 0b11010, 0b110100, 0b1101000, 0b11010001, 0b11010001,
+                0b11010001
+            ]
+        );
+        Ok(())
+    }
+
+    type In<N> = (bool, bool, bool, Bits<N>);
+    type Step<N> = (In<N>, Bits<N>);
+
+    fn serial(uut: &ShiftRegister<U8>, input: Vec<In<U8>>) -> miette::Result<Vec<u128>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.raw())
+            .collect())
+    }
+
+    #[test]
+    fn test_shift_reg_priority() -> miette::Result<()> {
+        let uut = ShiftRegister::<U8>::default();
+        let input = vec![
+            (false, true, false, b8(0b1000_0000)),
+            // Load and enable together: the load wins
+            (true, true, true, b8(0b0100_0000)),
+            (false, false, false, b8(0)),
+            // Shift a 1 in at the LSB
+            (true, false, true, b8(0)),
+            (false, false, false, b8(0)),
+        ];
+        let output = serial(&uut, input)?;
+        assert_eq!(
+            output,
+            [0, 0b1000_0000, 0b0100_0000, 0b0100_0000, 0b1000_0001]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_reg_preload_then_shift() -> miette::Result<()> {
+        let uut = ShiftRegister::<U8>::default();
+        // Preload a sync pattern, and start shifting on the very next clock
+        let mut input = vec![(false, true, false, b8(0b1010_0101))];
+        input.extend(
+            [1, 1, 0, 1]
+                .into_iter()
+                .map(|bit| (true, false, bit == 1, b8(0))),
+        );
+        input.push((false, false, false, b8(0)));
+        let output = serial(&uut, input)?;
+        assert_eq!(
+            output,
+            [
+                0,
+                0b1010_0101,
+                0b0100_1011,
+                0b1001_0111,
+                0b0010_1110,
+                0b0101_1101
+            ]
+        );
+        Ok(())
+    }
+
+    // Run the shift register on a table of inputs, and collect
+    // the inputs and outputs of each clock
+    fn run_vectors<N: BitWidth>(input: Vec<In<N>>) -> miette::Result<Vec<Step<N>>> {
+        let uut = ShiftRegister::<N>::default();
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| (t.value.1, t.value.2))
+            .collect())
+    }
+
+    #[test]
+    fn test_shift_reg_golden_vectors() -> miette::Result<()> {
+        // The vectors are recorded from the widest register.  The narrower
+        // ones hold the low bits of the same contents, and so are checked
+        // against the same vectors, as long as the loaded values fit in
+        // the narrowest register.
+        let input = (0..48).map(|n| {
+            (
+                n % 7 != 3,
+                n % 13 == 5,
+                (n * n + n / 3) % 5 < 2,
+                bits(n % 16),
+            )
+        });
+        let table = VectorTable::record(run_vectors::<U32>(input.collect())?);
+        expect_file!["shift_reg.vectors.expect"].assert_eq(&table.to_string());
+        let golden = include_str!("shift_reg.vectors.expect").parse::<VectorTable>()?;
+        golden.check(run_vectors::<U4>(golden.inputs()?)?)?;
+        golden.check(run_vectors::<U8>(golden.inputs()?)?)?;
+        golden.check(run_vectors::<U16>(golden.inputs()?)?)?;
+        golden.check(run_vectors::<U32>(golden.inputs()?)?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_reg_hdl() -> miette::Result<()> {
+        let uut = ShiftRegister::<U4>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 11 == 0, n % 5 < 2, bits((n * 7 + 3) % 16)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_reg_symbol() {
+        let expect = expect![[r#"
+                  +-+ShiftRegister+---+
+             bool |                   | b8
+            +---->| i.0        output +---->
+             bool |                   |
+            +---->| i.1               |
+             bool |                   |
+            +---->| i.2               |
+             b8   |                   |
+            +---->| i.3               |
+                  +-------------------+"#]];
+        expect.assert_eq(&schematic_symbol::<ShiftRegister<U8>>("ShiftRegister"));
+        let expect = expect![[r#"
+            | Port | Direction | Type | Bits |
+            |------|-----------|------|------|
+            | `i.0` | input | `bool` | 1 |
+            | `i.1` | input | `bool` | 1 |
+            | `i.2` | input | `bool` | 1 |
+            | `i.3` | input | `b8` | 8 |
+            | `output` | output | `b8` | 8 |"#]];
+        expect.assert_eq(&port_table::<ShiftRegister<U8>>());
+    }
+}
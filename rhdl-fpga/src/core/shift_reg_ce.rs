@@ -0,0 +1,147 @@
+//! Shift Register with a clock enable (serial in, parallel out)
+//!
+//! A [ShiftRegister](super::shift_reg::ShiftRegister) for serial
+//! interfaces whose bit clock is a clock enable strobe (from a
+//! [StrobeDiv](super::strobe_div::StrobeDiv), for example), rather than
+//! a clock of its own.  The strobe is a dedicated input, `ce`, so it does
+//! not have to be combined with `enable` at each use.  The register only
+//! shifts (sampling `serial_in`) on clocks when both `ce` and `enable`
+//! are high.  The `load` is sampled on every system clock, whether or
+//! not the strobe is high.  As for the
+//! [ShiftRegister](super::shift_reg::ShiftRegister), a load takes
+//! priority over a shift, and the register is cleared on reset.
+//!
+//!# Example
+//!
+//! Receiving a nibble, with a strobe every third clock.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_reg_ce::{In, ShiftRegisterCe};
+//!
+//! let uut = ShiftRegisterCe::<U4>::default();
+//! // Each bit is on the line for 3 clocks
+//! let line = [1, 1, 0, 1].into_iter().flat_map(|b| [b == 1; 3]);
+//! let input = line.enumerate().map(|(n, serial_in)| In {
+//!     ce: n % 3 == 1,
+//!     enable: true,
+//!     load: false,
+//!     serial_in,
+//!     data_in: b4(0),
+//! });
+//! let data = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2;
+//! assert_eq!(data, b4(0b1101));
+//!```
+use rhdl::prelude::*;
+
+use super::shift_reg::ShiftRegister;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [ShiftRegisterCe]
+pub struct In<N: BitWidth> {
+    /// The clock enable strobe
+    pub ce: bool,
+    /// Shift on this clock (if the strobe is high)
+    pub enable: bool,
+    /// Load the data on this clock (regardless of the strobe)
+    pub load: bool,
+    /// The serial input
+    pub serial_in: bool,
+    /// The data to load
+    pub data_in: Bits<N>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+/// The shift register core with a clock enable
+///   `N` is the number of bits in the register
+pub struct ShiftRegisterCe<N: BitWidth> {
+    reg: ShiftRegister<N>,
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftRegisterCe<N> {
+    type I = In<N>;
+    type O = Bits<N>;
+    type Kernel = shift_reg_ce_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_reg_ce_kernel<N: BitWidth>(_cr: ClockReset, i: In<N>, q: Q<N>) -> (Bits<N>, D<N>) {
+    let d = D::<N> {
+        reg: (i.ce && i.enable, i.load, i.serial_in, i.data_in),
+    };
+    (q.reg, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shifts_only_on_strobe() -> miette::Result<()> {
+        let uut = ShiftRegisterCe::<U8>::default();
+        // The line is one on every clock, but the strobe only comes
+        // every 4th clock, so a one is shifted in every 4 clocks
+        let input = (0..17).map(|n| In {
+            ce: n % 4 == 3,
+            enable: true,
+            serial_in: true,
+            ..Default::default()
+        });
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(output, [0, 0, 0, 0, 1, 1, 1, 1, 3, 3, 3, 3, 7, 7, 7, 7, 15]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_between_strobes() -> miette::Result<()> {
+        let uut = ShiftRegisterCe::<U8>::default();
+        let input = (0..8).map(|n| In {
+            ce: n % 4 == 3,
+            enable: true,
+            load: n == 1,
+            serial_in: false,
+            data_in: b8(0b0000_0101),
+        });
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(output, [0, 0, 5, 5, 10, 10, 10, 10]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_reg_ce_hdl() -> miette::Result<()> {
+        let uut = ShiftRegisterCe::<U8>::default();
+        let input = (0..50).map(|n| In {
+            ce: n % 3 == 0,
+            enable: n % 7 != 6,
+            load: n == 20,
+            serial_in: (n * 5) % 7 < 3,
+            data_in: b8(0xC3),
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
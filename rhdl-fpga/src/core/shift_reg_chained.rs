@@ -0,0 +1,161 @@
+//! Shift Register with a serial output, for chaining
+//!
+//! A [ShiftRegister](super::shift_reg::ShiftRegister) that also brings
+//! out the bit that falls off the MSB end of the register, so that
+//! registers can be daisy chained (like a string of 74HC595s).  The
+//! serial output is the MSB of the register before shifting, so when it
+//! drives the serial input of the next register in the chain, and both
+//! are enabled together, the chain behaves as a single, longer register.
+//!
+//! The register behaves exactly as the [ShiftRegister](super::shift_reg::ShiftRegister)
+//! (which it contains), and the input is the same tuple of
+//! `(enable, load, serial_in, data_in)`.  The output is the contents of
+//! the register, and the serial output.
+//!
+//!# Example
+//!
+//! A one passing out of the end of a nibble wide register.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_reg_chained::ShiftRegisterChained;
+//!
+//! let uut = ShiftRegisterChained::<U4>::default();
+//! let input = [1, 0, 0, 0, 0, 0]
+//!     .into_iter()
+//!     .map(|bit| (true, false, bit == 1, b4(0)));
+//! let serial_out = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.serial_out)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(serial_out, [false, false, false, false, true, false]);
+//!```
+use rhdl::prelude::*;
+
+use super::shift_reg::ShiftRegister;
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ShiftRegisterChained]
+pub struct Out<N: BitWidth> {
+    /// The contents of the register
+    pub data: Bits<N>,
+    /// The MSB of the register (shifted out on the next enabled clock)
+    pub serial_out: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+/// The shift register core with a serial output
+///   `N` is the number of bits in the register
+pub struct ShiftRegisterChained<N: BitWidth> {
+    reg: ShiftRegister<N>,
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftRegisterChained<N> {
+    type I = (bool, bool, bool, Bits<N>);
+    type O = Out<N>;
+    type Kernel = shift_reg_chained_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_reg_chained_kernel<N: BitWidth>(
+    _cr: ClockReset,
+    i: (bool, bool, bool, Bits<N>),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let d = D::<N> { reg: i };
+    let o = Out::<N> {
+        data: q.reg,
+        serial_out: q.reg & (1 << (N::BITS - 1)) != 0,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two nibble registers, with the serial output of the low one
+    // driving the serial input of the high one
+    #[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+    struct Cascade {
+        lo: ShiftRegisterChained<U4>,
+        hi: ShiftRegisterChained<U4>,
+    }
+
+    impl SynchronousIO for Cascade {
+        type I = (bool, bool, bool, b8);
+        type O = b8;
+        type Kernel = cascade_kernel;
+    }
+
+    #[kernel]
+    fn cascade_kernel(_cr: ClockReset, i: (bool, bool, bool, b8), q: Q) -> (b8, D) {
+        let (enable, load, serial_in, data_in) = i;
+        let d = D {
+            lo: (enable, load, serial_in, data_in.resize::<U4>()),
+            hi: (enable, load, q.lo.serial_out, (data_in >> 4).resize::<U4>()),
+        };
+        let o = (q.hi.data.resize::<U8>() << 4) | q.lo.data.resize::<U8>();
+        (o, d)
+    }
+
+    fn stimulus() -> Vec<(bool, bool, bool, b8)> {
+        (0..60_u32)
+            .map(|n| {
+                (
+                    n % 7 != 4,
+                    n == 23 || n == 41,
+                    (n * 13 + n / 3) % 5 < 2,
+                    b8(((n * 37) % 256) as u128),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cascade_matches_byte_register() -> miette::Result<()> {
+        let input = stimulus();
+        let expected = ShiftRegister::<U8>::default()
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let output = Cascade::default()
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_serial_out_is_msb() -> miette::Result<()> {
+        let output = ShiftRegisterChained::<U8>::default()
+            .run(stimulus().into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        for o in output {
+            assert_eq!(o.serial_out, o.data.raw() & 0x80 != 0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cascade_hdl() -> miette::Result<()> {
+        let uut = Cascade::default();
+        let tb = uut
+            .run(stimulus().into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
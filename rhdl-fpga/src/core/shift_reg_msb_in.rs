@@ -0,0 +1,192 @@
+//! Shift Register, MSB in (serial in, parallel out)
+//!
+//! A shift register for serial streams that arrive LSB first.
+//! The register shifts right, with the serial bit entering at the
+//! MSB, so that after `N` enabled clocks, the first bit shifted
+//! in is the LSB of the parallel output.  Otherwise, it works like
+//! the [ShiftRegister](super::shift_reg::ShiftRegister) (without the
+//! parallel load): when `enable` is low, the register holds its value,
+//! and on reset, the register is cleared.
+//!
+//! The input is a tuple of `(enable, serial_in)`, and the output
+//! is the contents of the register.
+//!
+//!# Example
+//!
+//! Shifting in a byte, LSB first.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_reg_msb_in::ShiftRegisterMsbIn;
+//!
+//! let uut = ShiftRegisterMsbIn::<U8>::default();
+//! let input = [0, 1, 0, 0, 1, 1, 0, 1]
+//!     .into_iter()
+//!     .map(|bit| (true, bit == 1))
+//!     .chain(std::iter::once((false, false)));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2;
+//! assert_eq!(output, b8(0b1011_0010));
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[doc_symbol(ShiftRegisterMsbIn<U8>, table)]
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The MSB in shift register core
+///   `N` is the number of bits in the register
+///
+/// Here is the schematic symbol (for `N = 8`)
+#[doc = badascii_doc::badascii_formal!("
+      +-+ShiftRegisterMsbIn+---+
+ bool |                        | b8
++---->| i.0             output +---->
+ bool |                        |
++---->| i.1                    |
+      +------------------------+
+")]
+///
+/// | Port | Direction | Type | Bits |
+/// |------|-----------|------|------|
+/// | `i.0` | input | `bool` | 1 |
+/// | `i.1` | input | `bool` | 1 |
+/// | `output` | output | `b8` | 8 |
+///
+/// Here `i.0` is the enable, and `i.1` is the serial input.
+pub struct ShiftRegisterMsbIn<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for ShiftRegisterMsbIn<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftRegisterMsbIn<N> {
+    type I = (bool, bool);
+    type O = Bits<N>;
+    type Kernel = shift_reg_msb_in_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_reg_msb_in_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Bits<N>, D<N>) {
+    let (enable, serial_in) = i;
+    let mut d = D::<N> { reg: q.reg };
+    if enable {
+        d.reg = q.reg >> 1;
+        if serial_in {
+            d.reg |= 1 << (N::BITS - 1);
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+    }
+    (q.reg, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shift the bits in, and return the final contents
+    fn shift_in<N: BitWidth>(input: &[u8]) -> miette::Result<Bits<N>> {
+        let uut = ShiftRegisterMsbIn::<N>::default();
+        let input = input
+            .iter()
+            .map(|&bit| (true, bit == 1))
+            .chain(std::iter::repeat_n((false, true), 3));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .last()
+            .unwrap()
+            .value
+            .2)
+    }
+
+    #[test]
+    fn test_shift_in_byte() -> miette::Result<()> {
+        let uut = ShiftRegisterMsbIn::<U8>::default();
+        let input = [1, 1, 0, 1, 0, 0, 0, 1]
+            .into_iter()
+            .map(|bit| (true, bit == 1))
+            .chain(std::iter::repeat_n((false, true), 3));
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.raw())
+            .collect::<Vec<_>>();
+        // The output lags the input by one clock, and
+        // holds once the enable drops
+        assert_eq!(
+            output,
+            [
+                0, 0b10000000, 0b11000000, 0b01100000, 0b10110000, 0b01011000, 0b00101100,
+                0b00010110, 0b10001011, 0b10001011, 0b10001011
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_widths() -> miette::Result<()> {
+        assert_eq!(shift_in::<U4>(&[0, 1, 1, 1])?, b4(0b1110));
+        assert_eq!(shift_in::<U8>(&[0, 1, 1, 1, 0, 0, 1, 0])?, b8(0b0100_1110));
+        // Shifting in more than N bits keeps the last N, and the
+        // earlier bits fall off the LSB
+        let input = [1, 0, 1, 1, 0, 0, 1, 0, 1, 1, 1, 0];
+        assert_eq!(shift_in::<U4>(&input)?, b4(0b0111));
+        assert_eq!(shift_in::<U8>(&input)?, b8(0b0111_0100));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_clears() -> miette::Result<()> {
+        let uut = ShiftRegisterMsbIn::<U4>::default();
+        let input = std::iter::repeat_n((true, true), 6)
+            .with_reset(1)
+            .chain(std::iter::repeat_n((true, false), 2).with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2.raw())
+            .collect::<Vec<_>>();
+        // The register is cleared on the clock after the reset
+        assert_eq!(
+            output,
+            [0, 0, 0b1000, 0b1100, 0b1110, 0b1111, 0b1111, 0b1111, 0, 0]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_reg_msb_in_hdl() -> miette::Result<()> {
+        let uut = ShiftRegisterMsbIn::<U4>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 5 < 2));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
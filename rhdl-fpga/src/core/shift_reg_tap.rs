@@ -0,0 +1,180 @@
+//! Shift Register with a tap output
+//!
+//! A [ShiftRegister](super::shift_reg::ShiftRegister) that also brings
+//! out a single bit of the shift chain, for sequence detectors and the
+//! like, which need to look at an intermediate stage.  The tap position
+//! (from `0`, the LSB where bits enter, to `N - 1`, the MSB) is chosen
+//! when the core is constructed.  The bit entering the register shows
+//! up at tap `k` after `k + 1` enabled clocks.
+//!
+//! The register behaves exactly as the [ShiftRegister](super::shift_reg::ShiftRegister)
+//! (which it contains), and the input is the same tuple of
+//! `(enable, load, serial_in, data_in)`.  The output is the contents of
+//! the register, and the tapped bit.
+//!
+//!# Example
+//!
+//! Tapping bit 2 of a byte wide shift register.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_reg_tap::ShiftRegisterTap;
+//!
+//! let uut = ShiftRegisterTap::<U8>::new(2);
+//! let input = [1, 0, 0, 0, 0]
+//!     .into_iter()
+//!     .map(|bit| (true, false, bit == 1, b8(0)));
+//! let taps = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.tap)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(taps, [false, false, false, true, false]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, shift_reg::ShiftRegister};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ShiftRegisterTap]
+pub struct Out<N: BitWidth> {
+    /// The contents of the register
+    pub data: Bits<N>,
+    /// The bit of the register at the tap position
+    pub tap: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The shift register core with a tap
+///   `N` is the number of bits in the register
+pub struct ShiftRegisterTap<N: BitWidth> {
+    reg: ShiftRegister<N>,
+    tap: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> ShiftRegisterTap<N> {
+    /// Create a shift register that taps bit `tap` (which
+    /// must be less than `N`)
+    pub fn new(tap: usize) -> Self {
+        assert!(tap < N::BITS, "The tap must be within the register");
+        Self {
+            reg: ShiftRegister::default(),
+            tap: constant::Constant::new(bits(tap as u128)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftRegisterTap<N> {
+    type I = (bool, bool, bool, Bits<N>);
+    type O = Out<N>;
+    type Kernel = shift_reg_tap_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_reg_tap_kernel<N: BitWidth>(
+    _cr: ClockReset,
+    i: (bool, bool, bool, Bits<N>),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let d = D::<N> { reg: i, tap: () };
+    let o = Out::<N> {
+        data: q.reg,
+        tap: (q.reg >> q.tap) & 1 != 0,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(tap: usize, input: &[(bool, bool, bool, b8)]) -> miette::Result<Vec<Out<U8>>> {
+        let uut = ShiftRegisterTap::<U8>::new(tap);
+        Ok(uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn stimulus() -> Vec<(bool, bool, bool, b8)> {
+        (0..40_u32)
+            .map(|n| {
+                (
+                    n % 5 != 3,
+                    n == 17,
+                    (n * 11 + n / 4) % 3 == 0,
+                    b8(0b1010_0101),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tap_tracks_bit() -> miette::Result<()> {
+        let input = stimulus();
+        for tap in 0..8 {
+            let output = run(tap, &input)?;
+            for o in &output {
+                assert_eq!(o.tap, o.data.raw() & (1 << tap) != 0);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tap_extremes() -> miette::Result<()> {
+        // A single one, shifted along the register
+        let input = std::iter::once((true, false, true, b8(0)))
+            .chain(std::iter::repeat_n((true, false, false, b8(0)), 8))
+            .collect::<Vec<_>>();
+        let lsb = run(0, &input)?;
+        let msb = run(7, &input)?;
+        let taps = |output: &[Out<U8>]| output.iter().position(|o| o.tap);
+        assert_eq!(taps(&lsb), Some(1));
+        assert_eq!(taps(&msb), Some(8));
+        assert_eq!(lsb.iter().filter(|o| o.tap).count(), 1);
+        assert_eq!(msb.iter().filter(|o| o.tap).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_matches_shift_register() -> miette::Result<()> {
+        let input = stimulus();
+        let plain = ShiftRegister::<U8>::default()
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let tapped = run(3, &input)?
+            .into_iter()
+            .map(|o| o.data)
+            .collect::<Vec<_>>();
+        assert_eq!(plain, tapped);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tap_out_of_range() {
+        let _ = ShiftRegisterTap::<U8>::new(8);
+    }
+
+    #[test]
+    fn test_shift_reg_tap_hdl() -> miette::Result<()> {
+        let uut = ShiftRegisterTap::<U8>::new(5);
+        let tb = uut
+            .run(stimulus().into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
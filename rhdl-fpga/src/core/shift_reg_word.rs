@@ -0,0 +1,239 @@
+//! Shift Register with a word strobe (serial in, parallel out)
+//!
+//! A [ShiftRegister](super::shift_reg::ShiftRegister) for deserializing
+//! a stream of `N` bit words.  It shifts left, with the serial bit
+//! entering at the LSB, one bit per enabled clock.  An internal counter
+//! counts the enabled shifts, and the `valid` output pulses for one
+//! clock each time `N` more bits have been shifted in.  The pulse is on
+//! the same clock as the `N`th bit appears in the parallel output, so
+//! the `data` is the whole word when `valid` is high.
+//!
+//! The counter only advances when `enable` is high, so words may be
+//! separated by any number of idle clocks, or may follow each other
+//! with no gap.  On reset, the register and the counter are cleared,
+//! and the next bit shifted in is the first bit of a word.
+//!
+//! The input is a tuple of `(enable, serial_in)`, and the output is
+//! the contents of the register and the word strobe.
+//!
+//! For frames whose length is chosen at run time, use the
+//! [VarShiftIn](super::var_shift_in::VarShiftIn).  For an output that
+//! only changes when a word is complete, use the
+//! [LatchedShiftIn](super::latched_shift_in::LatchedShiftIn).
+//!
+//!# Example
+//!
+//! Deserializing two bytes, MSB first.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::shift_reg_word::ShiftRegisterWord;
+//!
+//! let uut = ShiftRegisterWord::<U8>::default();
+//! let input = [0xA5_u8, 0x3C]
+//!     .into_iter()
+//!     .flat_map(|byte| (0..8).rev().map(move |n| (true, byte & (1 << n) != 0)))
+//!     .chain(std::iter::once((false, false)));
+//! let words = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .filter(|t| t.value.2.valid)
+//!     .map(|t| t.value.2.data)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(words, [b8(0xA5), b8(0x3C)]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [ShiftRegisterWord]
+pub struct Out<N: BitWidth> {
+    /// The contents of the register
+    pub data: Bits<N>,
+    /// High for one clock when `data` holds a complete word
+    pub valid: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The shift register with a word strobe
+///   `N` is the number of bits in the register (and in each word)
+pub struct ShiftRegisterWord<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    count: dff::DFF<b8>,
+    valid: dff::DFF<bool>,
+    last: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> Default for ShiftRegisterWord<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+            count: dff::DFF::new(b8(0)),
+            valid: dff::DFF::new(false),
+            last: constant::Constant::new(bits(N::BITS as u128 - 1)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for ShiftRegisterWord<N> {
+    type I = (bool, bool);
+    type O = Out<N>;
+    type Kernel = shift_reg_word_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn shift_reg_word_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (enable, serial_in) = i;
+    let mut d = D::<N> {
+        reg: q.reg,
+        count: q.count,
+        valid: false,
+        last: (),
+    };
+    if enable {
+        d.reg = q.reg << 1;
+        if serial_in {
+            d.reg |= 1;
+        }
+        if q.count == q.last {
+            d.count = bits(0);
+            d.valid = true;
+        } else {
+            d.count = q.count + 1;
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+        d.count = bits(0);
+        d.valid = false;
+    }
+    let o = Out::<N> {
+        data: q.reg,
+        valid: q.valid,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bits of the bytes, MSB first
+    fn serialize(bytes: &[u8]) -> Vec<(bool, bool)> {
+        bytes
+            .iter()
+            .flat_map(|&byte| (0..8).rev().map(move |n| (true, byte & (1 << n) != 0)))
+            .collect()
+    }
+
+    fn run(input: Vec<(bool, bool)>) -> miette::Result<Vec<Out<U8>>> {
+        let uut = ShiftRegisterWord::<U8>::default();
+        Ok(uut
+            .run(
+                input
+                    .into_iter()
+                    .chain(std::iter::once((false, false)))
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn words(output: &[Out<U8>]) -> Vec<(usize, u8)> {
+        output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.valid)
+            .map(|(n, o)| (n, o.data.raw() as u8))
+            .collect()
+    }
+
+    #[test]
+    fn test_back_to_back_words() -> miette::Result<()> {
+        let output = run(serialize(&[0xA5, 0x3C, 0xFF, 0x00]))?;
+        // The output lags the input by a clock, so each word is
+        // complete one clock after its last bit is presented
+        assert_eq!(
+            words(&output),
+            [(8, 0xA5), (16, 0x3C), (24, 0xFF), (32, 0x00)]
+        );
+        // The strobe is a single clock wide
+        assert_eq!(output.iter().filter(|o| o.valid).count(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_words_separated_by_idle_clocks() -> miette::Result<()> {
+        let mut input = vec![(false, true); 3];
+        for (gap, byte) in [(0, 0x81), (5, 0x7E), (1, 0xC3)] {
+            let mut bits = serialize(&[byte]);
+            // Disabled clocks in the middle of a word do not count,
+            // whatever the serial input
+            bits.insert(4, (false, true));
+            bits.insert(4, (false, true));
+            input.extend(bits);
+            input.extend(std::iter::repeat_n((false, false), gap));
+        }
+        let output = run(input)?;
+        assert_eq!(words(&output), [(13, 0x81), (23, 0x7E), (38, 0xC3)]);
+        // The data holds while the enable is low
+        assert!(output[13..23].iter().all(|o| o.data.raw() != 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_restarts_word() -> miette::Result<()> {
+        let uut = ShiftRegisterWord::<U4>::default();
+        // Three bits, then a reset, then a whole word.  Each
+        // reset is a clock of its own, before the bits.
+        let input = [(true, true); 3]
+            .into_iter()
+            .with_reset(1)
+            .chain(
+                [(true, true), (true, false), (true, true), (true, false)]
+                    .into_iter()
+                    .with_reset(1),
+            )
+            .chain(std::iter::once((false, false)).without_reset())
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let valid = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.valid)
+            .map(|(n, o)| (n, o.data.raw()))
+            .collect::<Vec<_>>();
+        // The reset discards the partial word, so the bits after
+        // it form a whole word, rather than completing the first one
+        assert_eq!(valid, [(9, 0b1010)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_reg_word_hdl() -> miette::Result<()> {
+        let uut = ShiftRegisterWord::<U4>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 5 < 2));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
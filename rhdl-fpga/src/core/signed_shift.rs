@@ -0,0 +1,188 @@
+//! Signed Shift Right Register
+//!
+//! A shift register over [SignedBits], for serial receivers and
+//! transmitters of two's complement values.  A word is loaded in
+//! parallel, and on each enabled clock the register shifts right
+//! arithmetically: the sign bit is replicated into the MSB (rather
+//! than zero filled), so that the parallel output is always the loaded
+//! value divided by `2^k` (rounded towards minus infinity) after `k`
+//! shifts.  The serial output is the LSB of the register, so the word
+//! is sent LSB first, followed by copies of the sign bit.
+//!
+//! If `load` and `enable` are both asserted, the load takes priority.
+//! On reset, the register is cleared.
+//!
+//! The input is a tuple of `(enable, load, data)`.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::signed_shift::SignedShiftRight;
+//!
+//! let uut = SignedShiftRight::<U8>::default();
+//! let input = std::iter::once((false, true, s8(-100)))
+//!     .chain(std::iter::repeat_n((true, false, s8(0)), 3));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2.data)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, [s8(-100), s8(-50), s8(-25)]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [SignedShiftRight]
+pub struct Out<N: BitWidth> {
+    /// The contents of the register
+    pub data: SignedBits<N>,
+    /// The serial output (the LSB of the register)
+    pub serial: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The signed shift right core
+///   `N` is the number of bits in the register
+pub struct SignedShiftRight<N: BitWidth> {
+    reg: dff::DFF<SignedBits<N>>,
+}
+
+impl<N: BitWidth> Default for SignedShiftRight<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(SignedBits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for SignedShiftRight<N> {
+    type I = (bool, bool, SignedBits<N>);
+    type O = Out<N>;
+    type Kernel = signed_shift_right_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn signed_shift_right_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool, SignedBits<N>),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (enable, load, data) = i;
+    let mut d = D::<N> { reg: q.reg };
+    if load {
+        d.reg = data;
+    } else if enable {
+        // The shift of a signed value is arithmetic
+        d.reg = q.reg >> 1;
+    }
+    if cr.reset.any() {
+        d.reg = signed(0);
+    }
+    let o = Out::<N> {
+        data: q.reg,
+        serial: q.reg.as_unsigned() & 1 != 0,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: Vec<(bool, bool, s8)>) -> miette::Result<Vec<Out<U8>>> {
+        let uut = SignedShiftRight::<U8>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn load_and_shift(value: i8, shifts: usize) -> Vec<(bool, bool, s8)> {
+        std::iter::once((false, true, s8(value as i128)))
+            .chain(std::iter::repeat_n((true, false, s8(0)), shifts))
+            .collect()
+    }
+
+    // The bits of the two's complement value, LSB first, followed
+    // by copies of the sign bit
+    fn expected_serial(value: i8, len: usize) -> Vec<bool> {
+        (0..len).map(|n| (value >> n.min(7)) & 1 != 0).collect()
+    }
+
+    #[test]
+    fn test_negative_value() -> miette::Result<()> {
+        let output = run(load_and_shift(-38, 12))?;
+        let serial = output.iter().map(|o| o.serial).collect::<Vec<_>>();
+        // -38 is 0b1101_1010
+        assert_eq!(
+            serial[..8],
+            [false, true, false, true, true, false, true, true]
+        );
+        assert_eq!(serial, expected_serial(-38, 12));
+        // The register fills with ones, and settles at -1
+        let data = output.iter().map(|o| o.data.raw()).collect::<Vec<_>>();
+        assert_eq!(data[..4], [-38, -19, -10, -5]);
+        assert!(data[8..].iter().all(|&x| x == -1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_positive_value() -> miette::Result<()> {
+        let output = run(load_and_shift(0x5A, 12))?;
+        let serial = output.iter().map(|o| o.serial).collect::<Vec<_>>();
+        assert_eq!(serial, expected_serial(0x5A, 12));
+        let data = output.iter().map(|o| o.data.raw()).collect::<Vec<_>>();
+        assert_eq!(data[..4], [90, 45, 22, 11]);
+        assert!(data[7..].iter().all(|&x| x == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_values_divide() -> miette::Result<()> {
+        // After k shifts, the register holds the value divided by
+        // 2^k, rounded towards minus infinity
+        for value in (-128..=127).step_by(7) {
+            let output = run(load_and_shift(value, 8))?;
+            for (k, o) in output.iter().enumerate() {
+                assert_eq!(o.data.raw(), (value as i128).div_euclid(1 << k));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_and_hold() -> miette::Result<()> {
+        let output = run(vec![
+            (false, true, s8(-128)),
+            (true, false, s8(0)),
+            (false, false, s8(0)),
+            (true, true, s8(3)),
+            (true, false, s8(0)),
+        ])?;
+        let data = output.iter().map(|o| o.data.raw()).collect::<Vec<_>>();
+        assert_eq!(data, [-128, -64, -64, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed_shift_right_hdl() -> miette::Result<()> {
+        let uut = SignedShiftRight::<U8>::default();
+        let input = (0..40).map(|n| (n % 3 != 0, n % 11 == 0, s8((n * 37 % 256) - 128)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+//! Clock Enable Strobe Divider
+//!
+//! Generates a clock enable strobe: a pulse, one clock long, every
+//! `divisor` clocks (e.g., every 16 clocks for the bit clock of a UART
+//! with 16x oversampling).  Serial cores that run slower than the system
+//! clock take the strobe as their clock enable, as the
+//! [ShiftOutCe](super::shift_out_ce::ShiftOutCe) and
+//! [ShiftRegisterCe](super::shift_reg_ce::ShiftRegisterCe) do.
+//!
+//! The divisor is an input, so it can be changed at run time.  A divisor
+//! of `0` or `1` gives a strobe on every clock.  An internal counter
+//! counts the clocks since the last strobe, and the strobe is output
+//! when it reaches `divisor - 1`.  If the divisor is reduced below the
+//! count, the strobe is output on the next clock, and the count starts
+//! over, so that a change of divisor takes effect within one period of
+//! the old or new divisor.  On reset, the counter is cleared, so the
+//! first strobe comes `divisor` clocks after reset.
+//! To hold the divisor in a register instead, use the
+//! [StrobeGen](super::strobe_gen::StrobeGen).
+//!
+//!# Example
+//!
+//! Dividing by 4.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::strobe_div::StrobeDiv;
+//!
+//! let uut = StrobeDiv::<U4>::default();
+//! let strobes = uut
+//!     .run(std::iter::repeat_n(b4(4), 12).with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2 as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(strobes, [0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1]);
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The strobe divider core
+///   `N` is the number of bits in the divisor
+pub struct StrobeDiv<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for StrobeDiv<N> {
+    fn default() -> Self {
+        Self {
+            count: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for StrobeDiv<N> {
+    type I = Bits<N>;
+    type O = bool;
+    type Kernel = strobe_div_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn strobe_div_kernel<N: BitWidth>(cr: ClockReset, divisor: Bits<N>, q: Q<N>) -> (bool, D<N>) {
+    let strobe = divisor == 0 || q.count >= divisor - 1;
+    let mut d = D::<N> { count: q.count + 1 };
+    if strobe || cr.reset.any() {
+        d.count = bits(0);
+    }
+    (strobe, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strobes(input: Vec<b8>) -> miette::Result<Vec<bool>> {
+        Ok(StrobeDiv::<U8>::default()
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn positions(strobes: &[bool]) -> Vec<usize> {
+        strobes
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| **s)
+            .map(|(ndx, _)| ndx)
+            .collect()
+    }
+
+    #[test]
+    fn test_divisors() -> miette::Result<()> {
+        for divisor in 1..20 {
+            let output = strobes(vec![b8(divisor as u128); 100])?;
+            let expected = (1..=100 / divisor)
+                .map(|n| n * divisor - 1)
+                .collect::<Vec<_>>();
+            assert_eq!(positions(&output), expected);
+        }
+        // Zero is treated as one
+        assert!(strobes(vec![b8(0); 10])?.iter().all(|s| *s));
+        Ok(())
+    }
+
+    #[test]
+    fn test_divisor_change() -> miette::Result<()> {
+        // Dividing by 10, and then by 3 part way through a period
+        let mut input = vec![b8(10); 16];
+        input.extend([b8(3); 10]);
+        let output = strobes(input)?;
+        assert_eq!(positions(&output), [9, 16, 19, 22, 25]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strobe_div_hdl() -> miette::Result<()> {
+        let uut = StrobeDiv::<U8>::default();
+        let input = (0..60).map(|n| b8(if n < 30 { 4 } else { 7 }));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! Programmable Strobe Generator
+//!
+//! Generates a clock enable strobe: a pulse, one clock long, every
+//! `period` clocks.  Unlike the [StrobeDiv](super::strobe_div::StrobeDiv),
+//! which must be given its divisor on every clock, the period is held
+//! in a register.  It is set at construction, and can be changed at run
+//! time by asserting `set_period` with the new `period`.  The new period
+//! is registered, and is used from the following clock on.  On reset,
+//! the period returns to the one given at construction, and the first
+//! strobe comes `period` clocks after reset.
+//!
+//! A period of `1` gives a strobe on every clock.  A period of `0` is
+//! treated as `1` (as it is by the [StrobeDiv](super::strobe_div::StrobeDiv)),
+//! so the strobe never stops.
+//!
+//! The input is a tuple of `(set_period, period)`, and the output is
+//! the strobe.
+//!
+//!# Example
+//!
+//! A strobe every 3 clocks, and then every 2 clocks.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::strobe_gen::StrobeGen;
+//!
+//! let uut = StrobeGen::<U4>::new(b4(3));
+//! let input = (0..10).map(|n| (n == 5, b4(2)));
+//! let strobes = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2 as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(strobes, [0, 0, 1, 0, 0, 1, 0, 1, 0, 1]);
+//!```
+//!
+//! There is also an example of a [ShiftOut](super::shift_out::ShiftOut)
+//! sending a byte at the rate of the strobe in `examples/strobe_gen.rs`.
+use rhdl::prelude::*;
+
+use super::{dff, strobe_div::StrobeDiv};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The strobe generator core
+///   `N` is the number of bits in the period
+pub struct StrobeGen<N: BitWidth> {
+    period: dff::DFF<Bits<N>>,
+    div: StrobeDiv<N>,
+}
+
+impl<N: BitWidth> StrobeGen<N> {
+    /// Create a strobe generator with the given period (which it
+    /// returns to on reset)
+    pub fn new(period: Bits<N>) -> Self {
+        Self {
+            period: dff::DFF::new(period),
+            div: StrobeDiv::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for StrobeGen<N> {
+    fn default() -> Self {
+        Self::new(bits(1))
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for StrobeGen<N> {
+    type I = (bool, Bits<N>);
+    type O = bool;
+    type Kernel = strobe_gen_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn strobe_gen_kernel<N: BitWidth>(
+    _cr: ClockReset,
+    i: (bool, Bits<N>),
+    q: Q<N>,
+) -> (bool, D<N>) {
+    let (set_period, period) = i;
+    let mut d = D::<N> {
+        period: q.period,
+        div: q.period,
+    };
+    if set_period {
+        d.period = period;
+    }
+    (q.div, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(uut: &StrobeGen<U8>, input: Vec<(bool, b8)>) -> miette::Result<Vec<usize>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .enumerate()
+            .filter(|(_, t)| t.value.2)
+            .map(|(ndx, _)| ndx)
+            .collect())
+    }
+
+    const HOLD: (bool, b8) = (false, b8(0));
+
+    #[test]
+    fn test_default_period() -> miette::Result<()> {
+        let uut = StrobeGen::<U8>::new(b8(5));
+        let strobes = positions(&uut, vec![HOLD; 20])?;
+        assert_eq!(strobes, [4, 9, 14, 19]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_period_one_and_zero() -> miette::Result<()> {
+        for period in [0, 1] {
+            let uut = StrobeGen::<U8>::new(b8(period));
+            assert_eq!(
+                positions(&uut, vec![HOLD; 10])?,
+                (0..10).collect::<Vec<_>>()
+            );
+        }
+        // Setting the period to zero at run time does not stop the strobe
+        let uut = StrobeGen::<U8>::new(b8(4));
+        let mut input = vec![HOLD; 4];
+        input.push((true, b8(0)));
+        input.extend([HOLD; 4]);
+        assert_eq!(positions(&uut, input)?, [3, 5, 6, 7, 8]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_period_and_reset() -> miette::Result<()> {
+        let uut = StrobeGen::<U8>::new(b8(3));
+        let input = std::iter::once((true, b8(6)))
+            .chain(std::iter::repeat_n(HOLD, 13))
+            .with_reset(1)
+            .chain(std::iter::repeat_n(HOLD, 6).with_reset(1))
+            .clock_pos_edge(100);
+        let strobes = uut
+            .run(input)?
+            .synchronous_sample()
+            .skip(1)
+            .enumerate()
+            .filter(|(_, t)| t.value.2)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        // Every 6 clocks once set, and every 3 after the reset
+        assert_eq!(strobes, [5, 11, 17, 20]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strobe_gen_hdl() -> miette::Result<()> {
+        let uut = StrobeGen::<U8>::new(b8(4));
+        let input = (0..60).map(|n| (n == 30, b8(7)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
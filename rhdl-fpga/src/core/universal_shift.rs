@@ -0,0 +1,288 @@
+//! Universal Shift Register
+//!
+//! A shift register in the style of the 74194, that can hold its
+//! value, shift left, shift right, or load a word in parallel, as
+//! selected by the `mode` input on each clock.  The modes are checked
+//! in the order:
+//!
+//! - [Mode::Load] loads `data` into the register.
+//! - [Mode::ShiftLeft] shifts the register left, with `serial_left`
+//!   entering at the LSB.
+//! - [Mode::ShiftRight] shifts the register right, with `serial_right`
+//!   entering at the MSB.
+//! - [Mode::Hold] (the default) keeps the register as it is.
+//!
+//! On reset, the register is cleared.
+//!
+//! The output is the contents of the register, along with the bits at
+//! each end of it, so that instances can be chained into a wider
+//! register.  To do so, feed the `msb` of each instance into the
+//! `serial_left` input of the next more significant one, and the `lsb`
+//! of each instance into the `serial_right` input of the next less
+//! significant one, and drive all of them with the same `mode`.
+//!
+//!# Example
+//!
+//! Loading a nibble and moving it around.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::universal_shift::{In, Mode, UniversalShift};
+//!
+//! let uut = UniversalShift::<U8>::default();
+//! let step = |mode, serial_left| In::<U8> {
+//!     mode,
+//!     serial_left,
+//!     serial_right: false,
+//!     data: b8(0b0000_1011),
+//! };
+//! let input = [
+//!     step(Mode::Load, false),
+//!     step(Mode::ShiftLeft, true),
+//!     step(Mode::Hold, false),
+//!     step(Mode::ShiftRight, false),
+//!     step(Mode::ShiftRight, false),
+//!     step(Mode::Hold, false),
+//! ];
+//! let output = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2.data)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(
+//!     output,
+//!     [b8(0b0000_1011), b8(0b0001_0111), b8(0b0001_0111), b8(0b0000_1011), b8(0b0000_0101)]
+//! );
+//!```
+use rhdl::prelude::*;
+
+use super::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The operation of the [UniversalShift] on each clock
+pub enum Mode {
+    /// Keep the register as it is
+    #[default]
+    Hold,
+    /// Shift left, with `serial_left` entering at the LSB
+    ShiftLeft,
+    /// Shift right, with `serial_right` entering at the MSB
+    ShiftRight,
+    /// Load the register with `data`
+    Load,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The inputs of the [UniversalShift]
+pub struct In<N: BitWidth> {
+    /// The operation to perform
+    pub mode: Mode,
+    /// The bit to enter at the LSB when shifting left
+    pub serial_left: bool,
+    /// The bit to enter at the MSB when shifting right
+    pub serial_right: bool,
+    /// The word to load
+    pub data: Bits<N>,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [UniversalShift]
+pub struct Out<N: BitWidth> {
+    /// The contents of the register
+    pub data: Bits<N>,
+    /// The MSB of the register (to chain into the next more significant stage)
+    pub msb: bool,
+    /// The LSB of the register (to chain into the next less significant stage)
+    pub lsb: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The universal shift register core
+///   `N` is the number of bits in the register
+pub struct UniversalShift<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+}
+
+impl<N: BitWidth> Default for UniversalShift<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for UniversalShift<N> {
+    type I = In<N>;
+    type O = Out<N>;
+    type Kernel = universal_shift_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn universal_shift_kernel<N: BitWidth>(cr: ClockReset, i: In<N>, q: Q<N>) -> (Out<N>, D<N>) {
+    let mut d = D::<N> { reg: q.reg };
+    match i.mode {
+        Mode::Load => {
+            d.reg = i.data;
+        }
+        Mode::ShiftLeft => {
+            d.reg = q.reg << 1;
+            if i.serial_left {
+                d.reg |= 1;
+            }
+        }
+        Mode::ShiftRight => {
+            d.reg = q.reg >> 1;
+            if i.serial_right {
+                d.reg |= 1 << (N::BITS - 1);
+            }
+        }
+        Mode::Hold => {}
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+    }
+    let o = Out::<N> {
+        data: q.reg,
+        msb: q.reg & (1 << (N::BITS - 1)) != 0,
+        lsb: q.reg & 1 != 0,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(mode: Mode, serial_left: bool, serial_right: bool, data: u8) -> In<U8> {
+        In::<U8> {
+            mode,
+            serial_left,
+            serial_right,
+            data: b8(data as u128),
+        }
+    }
+
+    fn run(input: Vec<In<U8>>) -> miette::Result<Vec<Out<U8>>> {
+        let uut = UniversalShift::<U8>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_modes() -> miette::Result<()> {
+        let output = run(vec![
+            step(Mode::Load, true, true, 0b1000_0001),
+            step(Mode::ShiftLeft, false, true, 0xFF),
+            step(Mode::ShiftLeft, true, false, 0xFF),
+            step(Mode::Hold, true, true, 0xFF),
+            step(Mode::ShiftRight, true, true, 0xFF),
+            step(Mode::ShiftRight, false, false, 0xFF),
+            step(Mode::Hold, false, false, 0),
+        ])?;
+        let data = output.iter().map(|o| o.data.raw()).collect::<Vec<_>>();
+        // The serial input for the other direction, and the data when
+        // not loading, have no effect
+        assert_eq!(
+            data,
+            [
+                0b1000_0001,
+                0b0000_0010,
+                0b0000_0101,
+                0b0000_0101,
+                0b1000_0010,
+                0b0100_0001
+            ]
+        );
+        // The end bits follow the register
+        assert!(output.iter().all(|o| o.msb == (o.data.raw() & 0x80 != 0)));
+        assert!(output.iter().all(|o| o.lsb == (o.data.raw() & 1 != 0)));
+        Ok(())
+    }
+
+    // Two 4 bit registers, chained into an 8 bit one
+    #[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+    struct Chained {
+        lo: UniversalShift<U4>,
+        hi: UniversalShift<U4>,
+    }
+
+    impl SynchronousIO for Chained {
+        type I = In<U8>;
+        type O = b8;
+        type Kernel = chained_kernel;
+    }
+
+    #[kernel]
+    fn chained_kernel(_cr: ClockReset, i: In<U8>, q: Q) -> (b8, D) {
+        let lo = In::<U4> {
+            mode: i.mode,
+            serial_left: i.serial_left,
+            serial_right: q.hi.lsb,
+            data: (i.data & 0xF).resize(),
+        };
+        let hi = In::<U4> {
+            mode: i.mode,
+            serial_left: q.lo.msb,
+            serial_right: i.serial_right,
+            data: (i.data >> 4).resize(),
+        };
+        let o = (q.hi.data.resize::<U8>() << 4) | q.lo.data.resize::<U8>();
+        (o, D { lo, hi })
+    }
+
+    #[test]
+    fn test_chained_instances() -> miette::Result<()> {
+        let modes = [Mode::Load, Mode::ShiftLeft, Mode::ShiftRight, Mode::Hold];
+        let input = (0..100_u32)
+            .map(|n| {
+                step(
+                    modes[((n * 7 + n / 5) % 4) as usize],
+                    n % 3 == 0,
+                    n % 5 < 2,
+                    ((n * 37 + 11) % 256) as u8,
+                )
+            })
+            .collect::<Vec<_>>();
+        let wide = run(input.clone())?
+            .into_iter()
+            .map(|o| o.data)
+            .collect::<Vec<_>>();
+        let chained = Chained::default()
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(wide, chained);
+        Ok(())
+    }
+
+    #[test]
+    fn test_universal_shift_hdl() -> miette::Result<()> {
+        let uut = UniversalShift::<U8>::default();
+        let modes = [Mode::Hold, Mode::ShiftLeft, Mode::ShiftRight, Mode::Load];
+        let input = (0..40_u32).map(|n| {
+            step(
+                modes[(n % 4) as usize],
+                n % 3 == 0,
+                n % 5 == 0,
+                ((n * 7 + 3) % 256) as u8,
+            )
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+//! Up/Down Counter
+//!
+//! A counter that counts up on clocks when `up` is high, and down on
+//! clocks when `down` is high (e.g., to track the occupancy of a FIFO,
+//! or the position of a motor from its step pulses).  When both (or
+//! neither) are high, the count holds.  On reset, the count is cleared.
+//!
+//! What happens at the ends of the range is chosen at construction with
+//! a [Limit]:
+//!
+//! - With [Limit::Wrap] (the [Default]), counting up from all ones gives
+//!   zero, and counting down from zero gives all ones.
+//! - With [Limit::Saturate], the count holds at all ones when counting
+//!   up, and at zero when counting down.
+//!
+//! In either case, `overflow` is high on the clock that the counter
+//! is asked to count up from all ones, and `underflow` is high on the
+//! clock that it is asked to count down from zero.
+//!
+//! The input is a tuple of `(up, down)`.
+//!
+//!# Example
+//!
+//! A 2 bit counter that saturates.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::up_down_counter::{Limit, UpDownCounter};
+//!
+//! let uut = UpDownCounter::<U2>::new(Limit::Saturate);
+//! let input = [(true, false); 4]
+//!     .into_iter()
+//!     .chain([(true, true), (false, true)]);
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| (t.value.2.count.raw(), t.value.2.overflow))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(
+//!     output,
+//!     [(0, false), (1, false), (2, false), (3, true), (3, false), (3, false)]
+//! );
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// What an [UpDownCounter] does at the ends of its range
+pub enum Limit {
+    /// Wrap around to the other end of the range
+    #[default]
+    Wrap,
+    /// Hold at the end of the range
+    Saturate,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [UpDownCounter]
+pub struct Out<N: BitWidth> {
+    /// The current count
+    pub count: Bits<N>,
+    /// High on the clock the count is stepped up from all ones
+    pub overflow: bool,
+    /// High on the clock the count is stepped down from zero
+    pub underflow: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+#[doc(hidden)]
+pub struct Config<N: BitWidth> {
+    limit: Limit,
+    max: Bits<N>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The up/down counter core
+///   `N` is the bitwidth of the counter
+pub struct UpDownCounter<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+    config: constant::Constant<Config<N>>,
+}
+
+impl<N: BitWidth> UpDownCounter<N> {
+    /// Create a counter with the given behavior at the ends of its range
+    pub fn new(limit: Limit) -> Self {
+        Self {
+            count: dff::DFF::new(Bits::<N>::default()),
+            config: constant::Constant::new(Config {
+                limit,
+                max: Bits::<N>::MAX,
+            }),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for UpDownCounter<N> {
+    fn default() -> Self {
+        Self::new(Limit::Wrap)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for UpDownCounter<N> {
+    type I = (bool, bool);
+    type O = Out<N>;
+    type Kernel = up_down_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn up_down_counter_kernel<N: BitWidth>(
+    cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (up, down) = i;
+    let step_up = up && !down;
+    let step_down = down && !up;
+    let overflow = step_up && q.count == q.config.max;
+    let underflow = step_down && q.count == 0;
+    let saturate = match q.config.limit {
+        Limit::Wrap => false,
+        Limit::Saturate => true,
+    };
+    let mut d = D::<N> {
+        count: q.count,
+        config: (),
+    };
+    if step_up && !(overflow && saturate) {
+        d.count = q.count + 1;
+    }
+    if step_down && !(underflow && saturate) {
+        d.count = q.count - 1;
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+    }
+    let o = Out::<N> {
+        count: q.count,
+        overflow,
+        underflow,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::random;
+
+    use super::*;
+
+    fn run(uut: &UpDownCounter<U4>, input: &[(bool, bool)]) -> miette::Result<Vec<Out<U4>>> {
+        Ok(uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    const UP: (bool, bool) = (true, false);
+    const DOWN: (bool, bool) = (false, true);
+
+    // The software model of the counter: the count (before the step),
+    // and the flags
+    fn model(limit: Limit, input: &[(bool, bool)]) -> Vec<(u128, bool, bool)> {
+        let mut count = 0;
+        input
+            .iter()
+            .map(|&(up, down)| {
+                let overflow = up && !down && count == 15;
+                let underflow = down && !up && count == 0;
+                let now = count;
+                count = match (up, down, &limit) {
+                    (true, false, Limit::Wrap) => (count + 1) % 16,
+                    (true, false, Limit::Saturate) => (count + 1).min(15),
+                    (false, true, Limit::Wrap) => (count + 15) % 16,
+                    (false, true, Limit::Saturate) => count.max(1) - 1,
+                    _ => count,
+                };
+                (now, overflow, underflow)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap_at_extremes() -> miette::Result<()> {
+        let uut = UpDownCounter::<U4>::new(Limit::Wrap);
+        let mut input = vec![DOWN, DOWN, UP, UP];
+        input.extend([UP; 16]);
+        input.push(DOWN);
+        let output = run(&uut, &input)?;
+        let counts = output.iter().map(|o| o.count.raw()).collect::<Vec<_>>();
+        assert_eq!(counts[..5], [0, 15, 14, 15, 0]);
+        assert_eq!(counts[19..], [15, 0]);
+        assert!(output[0].underflow && !output[1].underflow);
+        assert!(output[3].overflow);
+        assert_eq!(output.iter().filter(|o| o.overflow).count(), 2);
+        assert_eq!(output.iter().filter(|o| o.underflow).count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_saturate_at_extremes() -> miette::Result<()> {
+        let uut = UpDownCounter::<U4>::new(Limit::Saturate);
+        let mut input = vec![DOWN, DOWN];
+        input.extend([UP; 18]);
+        input.push(DOWN);
+        let output = run(&uut, &input)?;
+        let counts = output.iter().map(|o| o.count.raw()).collect::<Vec<_>>();
+        assert_eq!(counts[..3], [0, 0, 0]);
+        assert_eq!(counts[17..], [15, 15, 15, 15]);
+        // The flags are raised on each step that is refused
+        let overflows = output.iter().filter(|o| o.overflow).count();
+        let underflows = output.iter().filter(|o| o.underflow).count();
+        assert_eq!((overflows, underflows), (3, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_up_and_down_holds() -> miette::Result<()> {
+        let uut = UpDownCounter::<U4>::default();
+        let input = [UP, UP, (true, true), (true, true), DOWN, (false, false)];
+        let output = run(&uut, &input)?;
+        let counts = output.iter().map(|o| o.count.raw()).collect::<Vec<_>>();
+        assert_eq!(counts, [0, 1, 2, 2, 2, 1]);
+        // Holding at zero is not an underflow
+        let output = run(&uut, &[(true, true); 4])?;
+        assert!(output.iter().all(|o| !o.overflow && !o.underflow));
+        Ok(())
+    }
+
+    #[test]
+    fn test_against_model() -> miette::Result<()> {
+        for limit in [Limit::Wrap, Limit::Saturate] {
+            let input = (0..400)
+                .map(|_| (random::<bool>(), random::<bool>()))
+                .collect::<Vec<_>>();
+            let uut = UpDownCounter::<U4>::new(limit);
+            let output = run(&uut, &input)?
+                .into_iter()
+                .map(|o| (o.count.raw(), o.overflow, o.underflow))
+                .collect::<Vec<_>>();
+            assert_eq!(output, model(limit, &input));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_up_down_counter_hdl() -> miette::Result<()> {
+        for limit in [Limit::Wrap, Limit::Saturate] {
+            let uut = UpDownCounter::<U4>::new(limit);
+            let input = (0..80).map(|n| (n % 3 != 2 && n < 50, n % 5 == 1 || n > 60));
+            let tb = uut
+                .run(input.with_reset(1).clock_pos_edge(100))?
+                .collect::<SynchronousTestBench<_, _>>();
+            let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+            let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+        }
+        Ok(())
+    }
+}
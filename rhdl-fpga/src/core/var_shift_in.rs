@@ -0,0 +1,250 @@
+//! Variable Length Shift In Register (serial in, parallel out)
+//!
+//! A [ShiftRegisterWord](super::shift_reg_word::ShiftRegisterWord) for
+//! frames whose length is chosen at run time.  Bits are shifted in,
+//! MSB first, one per enabled clock, and the `valid` output pulses for
+//! one clock when a whole frame has been received.  At that point the
+//! `data` output holds the frame right aligned (the last bit received
+//! is bit 0), with zeros above it.
+//!
+//! The `length` input is sampled on the first bit of each frame, and
+//! holds for the rest of the frame, so that changing it between frames
+//! takes effect at the next frame boundary, and changing it in the
+//! middle of a frame has no effect until the frame ends.  Lengths are
+//! saturated to the range `1..=N`: a length of zero is treated as one,
+//! and a length larger than the register is treated as `N` (so the
+//! frame ends after `N` bits).  The length of the frame is also output,
+//! along with the data.
+//!
+//! On reset, the register and the bit counter are cleared, and the
+//! next bit shifted in is the first bit of a frame.
+//!
+//!# Example
+//!
+//! Receiving a frame of 5 bits, and then one of 3 bits.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::var_shift_in::{In, VarShiftIn};
+//!
+//! let uut = VarShiftIn::<U8>::default();
+//! let bit = |serial, length| In {
+//!     enable: true,
+//!     serial,
+//!     length: b8(length),
+//! };
+//! let input = [1, 0, 1, 1, 0]
+//!     .map(|b| bit(b == 1, 5))
+//!     .into_iter()
+//!     .chain([1, 1, 0].map(|b| bit(b == 1, 3)))
+//!     .chain(std::iter::once(In::default()));
+//! let words = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .filter(|t| t.value.2.valid)
+//!     .map(|t| (t.value.2.data, t.value.2.length))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(words, [(b8(0b10110), b8(5)), (b8(0b110), b8(3))]);
+//!```
+use rhdl::prelude::*;
+
+use super::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs of the [VarShiftIn]
+pub struct In {
+    /// Shift in the serial bit on this clock
+    pub enable: bool,
+    /// The serial bit
+    pub serial: bool,
+    /// The length of the frame (sampled on its first bit)
+    pub length: b8,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [VarShiftIn]
+pub struct Out<N: BitWidth> {
+    /// The bits of the frame, right aligned
+    pub data: Bits<N>,
+    /// High for one clock when `data` holds a complete frame
+    pub valid: bool,
+    /// The length of the frame in `data`
+    pub length: b8,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The variable length shift in core
+///   `N` is the number of bits in the register (the longest frame)
+pub struct VarShiftIn<N: BitWidth> {
+    reg: dff::DFF<Bits<N>>,
+    count: dff::DFF<b8>,
+    length: dff::DFF<b8>,
+    valid: dff::DFF<bool>,
+    width: constant::Constant<b8>,
+}
+
+impl<N: BitWidth> Default for VarShiftIn<N> {
+    fn default() -> Self {
+        Self {
+            reg: dff::DFF::new(Bits::<N>::default()),
+            count: dff::DFF::new(b8(0)),
+            length: dff::DFF::new(b8(0)),
+            valid: dff::DFF::new(false),
+            width: constant::Constant::new(bits(N::BITS as u128)),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for VarShiftIn<N> {
+    type I = In;
+    type O = Out<N>;
+    type Kernel = var_shift_in_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn var_shift_in_kernel<N: BitWidth>(cr: ClockReset, i: In, q: Q<N>) -> (Out<N>, D<N>) {
+    let mut d = D::<N> {
+        reg: q.reg,
+        count: q.count,
+        length: q.length,
+        valid: false,
+        width: (),
+    };
+    if i.enable {
+        let mut length = q.length;
+        if q.count == 0 {
+            // The first bit of a frame starts a new word, and
+            // samples the length
+            length = i.length;
+            if length > q.width {
+                length = q.width;
+            }
+            if length == 0 {
+                length = bits(1);
+            }
+            d.length = length;
+            d.reg = bits(0);
+        } else {
+            d.reg = q.reg << 1;
+        }
+        if i.serial {
+            d.reg |= 1;
+        }
+        if q.count + 1 == length {
+            d.count = bits(0);
+            d.valid = true;
+        } else {
+            d.count = q.count + 1;
+        }
+    }
+    if cr.reset.any() {
+        d.reg = bits(0);
+        d.count = bits(0);
+        d.length = bits(0);
+        d.valid = false;
+    }
+    let o = Out::<N> {
+        data: q.reg,
+        valid: q.valid,
+        length: q.length,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bits of a frame, MSB first, each with the given length
+    fn frame(word: u16, bits: usize, length: u8) -> impl Iterator<Item = In> {
+        (0..bits).rev().map(move |n| In {
+            enable: true,
+            serial: word & (1 << n) != 0,
+            length: b8(length as u128),
+        })
+    }
+
+    fn words(input: impl Iterator<Item = In>) -> miette::Result<Vec<(u128, u128)>> {
+        let uut = VarShiftIn::<U16>::default();
+        Ok(uut
+            .run(
+                input
+                    .chain(std::iter::once(In::default()))
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .filter(|t| t.value.2.valid)
+            .map(|t| (t.value.2.data.raw(), t.value.2.length.raw()))
+            .collect())
+    }
+
+    #[test]
+    fn test_back_to_back_frames() -> miette::Result<()> {
+        let input = frame(0b10011, 5, 5)
+            .chain(frame(0b1111000, 7, 7))
+            .chain(frame(0b101010101, 9, 9))
+            .chain(frame(0b01011, 5, 5));
+        assert_eq!(
+            words(input)?,
+            [(0b10011, 5), (0b1111000, 7), (0b101010101, 9), (0b01011, 5)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_changes_at_frame_boundary() -> miette::Result<()> {
+        // The length changes in the middle of the first frame, but
+        // the frame still ends after 7 bits
+        let input = frame(0b110, 3, 7)
+            .chain(frame(0b1001, 4, 5))
+            .chain(frame(0b11111, 5, 5));
+        assert_eq!(words(input)?, [(0b1101001, 7), (0b11111, 5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_clocks_within_frame() -> miette::Result<()> {
+        let idle = In {
+            enable: false,
+            serial: true,
+            length: b8(3),
+        };
+        let input = frame(0b10, 2, 5)
+            .chain(std::iter::repeat_n(idle, 4))
+            .chain(frame(0b011, 3, 3));
+        assert_eq!(words(input)?, [(0b10011, 5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_saturates() -> miette::Result<()> {
+        // A length of 20 is too long for a 16 bit register, and
+        // is treated as 16.  A length of zero is treated as 1.
+        let input = frame(0xBEEF, 16, 20)
+            .chain(frame(0b1, 1, 0))
+            .chain(frame(0b0, 1, 0));
+        assert_eq!(words(input)?, [(0xBEEF, 16), (1, 1), (0, 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_var_shift_in_hdl() -> miette::Result<()> {
+        let uut = VarShiftIn::<U8>::default();
+        let input = (0..60).map(|n| In {
+            enable: n % 4 != 0,
+            serial: n % 3 == 0,
+            length: b8([5, 7, 9, 0][n / 15]),
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
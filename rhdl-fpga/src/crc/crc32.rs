@@ -0,0 +1,60 @@
+//! CRC-32
+//!
+//! The CRC-32 used by Ethernet, zlib and PNG (among many others).
+//! The polynomial is `0x04C11DB7`, processed LSB first (so that the
+//! reflected form `0xEDB88320` is used in the shift register).  The
+//! register starts at [CRC32_INIT], and the check value is the final
+//! register inverted.
+//!
+//! [crc32_update] folds a single byte into the register, and can be
+//! called from any kernel.  For wide datapaths, see [wide](super::wide).
+//! [crc32] is the software model, for use in tests.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::crc::crc32::{crc32, crc32_update, CRC32_INIT};
+//!
+//! let crc = b"123456789"
+//!     .iter()
+//!     .fold(CRC32_INIT, |crc, &b| crc32_update(crc, b8(b as u128)));
+//! assert_eq!(!crc, b32(0xCBF4_3926));
+//! assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+//!```
+use rhdl::prelude::*;
+
+/// The initial value of the CRC register
+pub const CRC32_INIT: b32 = bits(0xFFFF_FFFF);
+
+/// The reflected polynomial
+pub const CRC32_POLY: b32 = bits(0xEDB8_8320);
+
+#[kernel]
+/// Fold a byte into the CRC register (LSB first)
+pub fn crc32_update(crc: b32, data: b8) -> b32 {
+    let wide: b32 = data.resize();
+    let mut c = crc ^ wide;
+    for _bit in 0..8 {
+        c = if (c & 1) != 0 {
+            (c >> 1) ^ CRC32_POLY
+        } else {
+            c >> 1
+        };
+    }
+    c
+}
+
+/// Compute the CRC-32 of a block of bytes (in software)
+pub fn crc32(data: &[u8]) -> u32 {
+    let crc = data.iter().fold(0xFFFF_FFFF_u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |c, _| {
+            if c & 1 != 0 {
+                (c >> 1) ^ 0xEDB8_8320
+            } else {
+                c >> 1
+            }
+        })
+    });
+    !crc
+}
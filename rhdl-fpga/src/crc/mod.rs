@@ -0,0 +1,3 @@
+//! Cyclic redundancy check functions and cores
+pub mod crc32;
+pub mod wide;
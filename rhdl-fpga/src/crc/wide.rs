@@ -0,0 +1,246 @@
+//! Wide CRC updates
+//!
+//!# Purpose
+//!
+//! Folding one byte per clock into a CRC cannot keep up with a
+//! stream datapath that moves 32 or 64 bits per beat.  The functions
+//! here fold a whole beat of `D` bits into a `W` bit CRC register in a
+//! single clock.
+//!
+//! The CRCs are processed LSB first (reflected), as is the case for
+//! CRC-32.  The bytes of a beat are packed little endian, so that byte
+//! 0 of the beat is in bits `0..8` (as in an AXI stream).  Folding a
+//! beat is then exactly the same as folding its bytes in order with the
+//! byte-serial engine (such as [crc32_update]).
+//!
+//! The update is written as a loop over the bits of the beat.  The loop
+//! is unrolled when the kernel is compiled, and with a constant
+//! polynomial, the shifts and conditional XORs reduce to the usual
+//! XOR network (where each bit of the next state is the parity of
+//! a fixed set of state and data bits).  There is no table to
+//! generate by hand.
+//!
+//! The last beat of a frame is often only partly filled.  The number of
+//! valid bytes (which are always the low bytes of the beat) is passed
+//! to [crc_update_partial], and the unused bytes do not change the CRC.
+//! If the stream carries a `tkeep` mask instead, the count is the
+//! number of set bits in the mask.
+//!
+//!# Example
+//!
+//! Fold the check string in two 32 bit beats and a partial beat.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::crc::{crc32::CRC32_INIT, wide::{crc32_update_partial, crc32_update_wide}};
+//!
+//! let crc = crc32_update_wide::<U32>(CRC32_INIT, b32(0x3433_3231));
+//! let crc = crc32_update_wide::<U32>(crc, b32(0x3837_3635));
+//! let crc = crc32_update_partial::<U32>(crc, b32(0xFFFF_FF39), b8(1));
+//! assert_eq!(!crc, b32(0xCBF4_3926));
+//!```
+use rhdl::prelude::*;
+
+#[cfg(doc)]
+use super::crc32::crc32_update;
+use super::crc32::CRC32_POLY;
+
+#[kernel]
+/// Fold all `D` bits of `data` into the `W` bit CRC register `crc`,
+/// LSB first, using the reflected polynomial `poly`.
+pub fn crc_update_wide<W: BitWidth, D: BitWidth>(
+    poly: Bits<W>,
+    crc: Bits<W>,
+    data: Bits<D>,
+) -> Bits<W> {
+    let mut c = crc;
+    let mut x = data;
+    for _bit in 0..D::BITS {
+        let feedback = ((c & 1) != 0) ^ ((x & 1) != 0);
+        c >>= 1;
+        if feedback {
+            c ^= poly;
+        }
+        x >>= 1;
+    }
+    c
+}
+
+#[kernel]
+/// Fold the low `bytes` bytes of `data` into the `W` bit CRC register
+/// `crc`.  The rest of the beat is ignored.  The beat must be at most
+/// 255 bytes.
+pub fn crc_update_partial<W: BitWidth, D: BitWidth>(
+    poly: Bits<W>,
+    crc: Bits<W>,
+    data: Bits<D>,
+    bytes: b8,
+) -> Bits<W> {
+    let valid: b16 = bytes.resize();
+    let valid = valid << 3;
+    let mut c = crc;
+    let mut x = data;
+    for bit in 0..D::BITS {
+        let feedback = ((c & 1) != 0) ^ ((x & 1) != 0);
+        if b16(bit as u128) < valid {
+            c >>= 1;
+            if feedback {
+                c ^= poly;
+            }
+        }
+        x >>= 1;
+    }
+    c
+}
+
+#[kernel]
+/// Fold a beat of `D` bits into a CRC-32 register
+pub fn crc32_update_wide<D: BitWidth>(crc: b32, data: Bits<D>) -> b32 {
+    crc_update_wide::<U32, D>(CRC32_POLY, crc, data)
+}
+
+#[kernel]
+/// Fold the low `bytes` bytes of a beat into a CRC-32 register
+pub fn crc32_update_partial<D: BitWidth>(crc: b32, data: Bits<D>, bytes: b8) -> b32 {
+    crc_update_partial::<U32, D>(CRC32_POLY, crc, data, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        core::dff,
+        crc::crc32::{crc32, crc32_update, CRC32_INIT},
+    };
+
+    // Pack up to D/8 bytes into a beat, filling the unused
+    // bytes with junk
+    fn beat<D: BitWidth>(bytes: &[u8], rng: &mut StdRng) -> Bits<D> {
+        let mut x = 0_u128;
+        for n in (0..D::BITS / 8).rev() {
+            let b = bytes.get(n).copied().unwrap_or_else(|| rng.random());
+            x = (x << 8) | b as u128;
+        }
+        bits(x)
+    }
+
+    // Compute the CRC of a frame using D bit beats, and compare it
+    // with the byte-serial engine and the software model
+    fn check_frame<D: BitWidth>(frame: &[u8], rng: &mut StdRng) {
+        let lanes = D::BITS / 8;
+        let mut crc = CRC32_INIT;
+        for chunk in frame.chunks(lanes) {
+            let x = beat::<D>(chunk, rng);
+            // Full beats may use either function
+            crc = if chunk.len() == lanes && rng.random() {
+                crc32_update_wide::<D>(crc, x)
+            } else {
+                crc32_update_partial::<D>(crc, x, bits(chunk.len() as u128))
+            };
+        }
+        let serial = frame
+            .iter()
+            .fold(CRC32_INIT, |crc, &b| crc32_update(crc, b8(b as u128)));
+        assert_eq!(crc, serial);
+        assert_eq!((!crc).raw() as u32, crc32(frame));
+    }
+
+    fn check_width<D: BitWidth>() {
+        let mut rng = StdRng::seed_from_u64(0xC4C_0000 + D::BITS as u64);
+        let lanes = D::BITS / 8;
+        // Every occupancy of the final beat, with a few frame lengths
+        for beats in 0..5 {
+            for last in 1..=lanes {
+                for _ in 0..4 {
+                    let len = beats * lanes + last;
+                    let frame = (0..len).map(|_| rng.random()).collect::<Vec<u8>>();
+                    check_frame::<D>(&frame, &mut rng);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_equivalence_16() {
+        check_width::<U16>();
+    }
+
+    #[test]
+    fn test_equivalence_32() {
+        check_width::<U32>();
+    }
+
+    #[test]
+    fn test_equivalence_64() {
+        check_width::<U64>();
+    }
+
+    #[test]
+    fn test_empty_beat_is_ignored() {
+        let crc = b32(0x1234_5678);
+        assert_eq!(crc32_update_partial::<U64>(crc, !bits(0), b8(0)), crc);
+    }
+
+    // A core that folds (beat, valid bytes) pairs into a CRC, to
+    // check that the functions compile to hardware
+    #[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+    struct Accumulator {
+        crc: dff::DFF<b32>,
+    }
+
+    impl Default for Accumulator {
+        fn default() -> Self {
+            Self {
+                crc: dff::DFF::new(CRC32_INIT),
+            }
+        }
+    }
+
+    impl SynchronousIO for Accumulator {
+        type I = Option<(b64, b8)>;
+        type O = b32;
+        type Kernel = accumulator_kernel;
+    }
+
+    #[kernel]
+    fn accumulator_kernel(_cr: ClockReset, i: Option<(b64, b8)>, q: Q) -> (b32, D) {
+        let mut d = D::dont_care();
+        d.crc = q.crc;
+        if let Some((data, bytes)) = i {
+            d.crc = if bytes == 8 {
+                crc32_update_wide::<U64>(q.crc, data)
+            } else {
+                crc32_update_partial::<U64>(q.crc, data, bytes)
+            };
+        }
+        (!q.crc, d)
+    }
+
+    #[test]
+    fn test_wide_crc_core() -> miette::Result<()> {
+        let mut rng = StdRng::seed_from_u64(64);
+        let frame = (0..45).map(|_| rng.random()).collect::<Vec<u8>>();
+        let input = frame
+            .chunks(8)
+            .map(|c| Some((beat::<U64>(c, &mut rng), b8(c.len() as u128))))
+            .chain(std::iter::repeat_n(None, 2))
+            .collect::<Vec<_>>();
+        let uut = Accumulator::default();
+        let last = uut
+            .run(input.clone().into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .last()
+            .unwrap();
+        assert_eq!(last.value.2.raw() as u32, crc32(&frame));
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+//! Mailbox Registers
+//!
+//! A mailbox is a bank of `N` registers shared between a host (for
+//! example, a PC talking through a debug bridge) and the fabric
+//! logic.  Either side can write any slot, and each write raises a
+//! "new data" flag for the *other* side.  The flag stays set until
+//! the other side acknowledges it by reading the slot, so that a
+//! write is never missed, even if the reader polls slowly.
+//!
+//! - A host write to a slot sets its `for_fabric` flag, which is
+//!   cleared by a fabric read of the slot.
+//! - A fabric write to a slot sets its `for_host` flag, which is
+//!   cleared by a host read of the slot.
+//!
+//! If a read acknowledges a slot in the same clock as a new write
+//! to it from the other side, the write wins, and the flag stays set
+//! (the reader will see the new data on its next poll).  Both sides
+//! see the register contents at all times, so a "read" here only
+//! acknowledges the flag.
+//!
+//! If the host and the fabric write the same slot in the same clock,
+//! the [Policy] given to the constructor resolves the conflict.  Either
+//! the host or the fabric write is applied (and only its flag is
+//! raised), or both writes are dropped.  In every case, the `conflict`
+//! output pulses, so the losing side can retry.  Writes to different
+//! slots in the same clock do not conflict.
+//!
+//! The doorbell outputs are set while any flag is set for that side.
+//! They are levels, suitable for a level sensitive interrupt input,
+//! or for a [PulseMatrix](crate::event::matrix::PulseMatrix) source
+//! with edge detection.
+//!
+//! All of the outputs are registered, so a write is visible on the
+//! clock after it is presented.  Slot indices must be less than `N`.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+          +-+Mailbox+-----------------+
+ ?(b8,T)  |                           | [T;N]
++-------->| host_write           data +------->
+ ?b8      |                           | [bool;N]
++-------->| host_read        for_host +------->
+ ?(b8,T)  |                           | [bool;N]
++-------->| fabric_write   for_fabric +------->
+ ?b8      |                           | bool
++-------->| fabric_read  host_doorbell+------->
+          |                           | bool
+          |           fabric_doorbell +------->
+          |                           | bool
+          |                  conflict +------->
+          +---------------------------+
+")]
+//!
+//!# Example
+//!
+//! A four slot mailbox of 32 bit words, in which the host wins
+//! write conflicts.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::csr::mailbox::{In, Mailbox, Policy};
+//!
+//! let uut = Mailbox::<b32, 4>::new([b32(0); 4], Policy::HostWins);
+//! let idle = In::<b32> {
+//!     host_write: None,
+//!     host_read: None,
+//!     fabric_write: None,
+//!     fabric_read: None,
+//! };
+//! let input = [
+//!     In { host_write: Some((b8(2), b32(0xCAFE))), ..idle },
+//!     idle,
+//!     In { fabric_read: Some(b8(2)), ..idle },
+//!     idle,
+//! ];
+//! let output = uut
+//!     .run(input.into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert!(output[2].for_fabric[2] && output[2].fabric_doorbell);
+//! assert_eq!(output[2].data[2], b32(0xCAFE));
+//! assert!(!output[4].fabric_doorbell);
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// How to resolve a host and fabric write to the same
+/// slot in the same clock
+pub enum Policy {
+    /// Apply the host write
+    #[default]
+    HostWins,
+    /// Apply the fabric write
+    FabricWins,
+    /// Drop both writes
+    Error,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [Mailbox]
+pub struct In<T: Digital> {
+    /// Write a value to a slot from the host
+    pub host_write: Option<(b8, T)>,
+    /// Acknowledge a slot written by the fabric
+    pub host_read: Option<b8>,
+    /// Write a value to a slot from the fabric
+    pub fabric_write: Option<(b8, T)>,
+    /// Acknowledge a slot written by the host
+    pub fabric_read: Option<b8>,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [Mailbox]
+pub struct Out<T: Digital, const N: usize> {
+    /// The contents of the slots
+    pub data: [T; N],
+    /// Slots written by the fabric, and not yet read by the host
+    pub for_host: [bool; N],
+    /// Slots written by the host, and not yet read by the fabric
+    pub for_fabric: [bool; N],
+    /// Set while any slot has new data for the host
+    pub host_doorbell: bool,
+    /// Set while any slot has new data for the fabric
+    pub fabric_doorbell: bool,
+    /// Pulses after the host and fabric wrote the same slot
+    /// in the same clock
+    pub conflict: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The mailbox core.  Here `T` is the type of each slot, and
+/// `N` is the number of slots (at most 256).
+pub struct Mailbox<T: Digital, const N: usize> {
+    policy: constant::Constant<Policy>,
+    data: dff::DFF<[T; N]>,
+    for_host: dff::DFF<[bool; N]>,
+    for_fabric: dff::DFF<[bool; N]>,
+    conflict: dff::DFF<bool>,
+}
+
+impl<T: Digital, const N: usize> Mailbox<T, N> {
+    /// Create a [Mailbox] with the given contents (which are
+    /// restored on reset), and policy for conflicting writes.
+    pub fn new(initial: [T; N], policy: Policy) -> Self {
+        assert!(N <= 256, "The mailbox supports at most 256 slots");
+        Self {
+            policy: constant::Constant::new(policy),
+            data: dff::DFF::new(initial),
+            for_host: dff::DFF::new([false; N]),
+            for_fabric: dff::DFF::new([false; N]),
+            conflict: dff::DFF::new(false),
+        }
+    }
+}
+
+impl<T: Digital, const N: usize> SynchronousIO for Mailbox<T, N> {
+    type I = In<T>;
+    type O = Out<T, N>;
+    type Kernel = mailbox_kernel<T, N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+#[allow(clippy::needless_range_loop)]
+pub fn mailbox_kernel<T: Digital, const N: usize>(
+    _cr: ClockReset,
+    i: In<T>,
+    q: Q<T, N>,
+) -> (Out<T, N>, D<T, N>) {
+    let mut d = D::<T, N> {
+        policy: (),
+        data: q.data,
+        for_host: q.for_host,
+        for_fabric: q.for_fabric,
+        conflict: false,
+    };
+    // Acknowledge first, so that a write in the same
+    // clock sets the flag again
+    if let Some(slot) = i.host_read {
+        d.for_host[slot] = false;
+    }
+    if let Some(slot) = i.fabric_read {
+        d.for_fabric[slot] = false;
+    }
+    let mut host = i.host_write;
+    let mut fabric = i.fabric_write;
+    if let Some((host_slot, _)) = i.host_write {
+        if let Some((fabric_slot, _)) = i.fabric_write {
+            if host_slot == fabric_slot {
+                d.conflict = true;
+                match q.policy {
+                    Policy::HostWins => fabric = None,
+                    Policy::FabricWins => host = None,
+                    Policy::Error => {
+                        host = None;
+                        fabric = None;
+                    }
+                }
+            }
+        }
+    }
+    if let Some((slot, value)) = host {
+        d.data[slot] = value;
+        d.for_fabric[slot] = true;
+    }
+    if let Some((slot, value)) = fabric {
+        d.data[slot] = value;
+        d.for_host[slot] = true;
+    }
+    let mut host_doorbell = false;
+    let mut fabric_doorbell = false;
+    for n in 0..N {
+        host_doorbell |= q.for_host[n];
+        fabric_doorbell |= q.for_fabric[n];
+    }
+    let o = Out::<T, N> {
+        data: q.data,
+        for_host: q.for_host,
+        for_fabric: q.for_fabric,
+        host_doorbell,
+        fabric_doorbell,
+        conflict: q.conflict,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::tb::{
+        irq::IrqMonitor,
+        sequencer::{all, pulse, wait_for, SequenceError, TbSequencer},
+    };
+
+    fn idle() -> In<b16> {
+        In {
+            host_write: None,
+            host_read: None,
+            fabric_write: None,
+            fabric_read: None,
+        }
+    }
+
+    // Run the mailbox, skipping the reset and the register delay,
+    // so that each output shows the effect of the matching input
+    fn run(uut: &Mailbox<b16, 4>, input: Vec<In<b16>>) -> miette::Result<Vec<Out<b16, 4>>> {
+        let input = input.into_iter().chain(std::iter::once(idle()));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn mailbox(policy: Policy) -> Mailbox<b16, 4> {
+        Mailbox::new([b16(0); 4], policy)
+    }
+
+    #[test]
+    fn test_flag_handshake() -> miette::Result<()> {
+        let input = vec![
+            In {
+                host_write: Some((b8(1), b16(0x1111))),
+                ..idle()
+            },
+            In {
+                fabric_write: Some((b8(3), b16(0x3333))),
+                ..idle()
+            },
+            In {
+                fabric_read: Some(b8(1)),
+                ..idle()
+            },
+            In {
+                host_read: Some(b8(3)),
+                ..idle()
+            },
+        ];
+        let output = run(&mailbox(Policy::HostWins), input)?;
+        assert_eq!(output[0].data[1], b16(0x1111));
+        assert_eq!(output[0].for_fabric, [false, true, false, false]);
+        assert!(output[0].fabric_doorbell && !output[0].host_doorbell);
+        assert_eq!(output[1].data[3], b16(0x3333));
+        assert_eq!(output[1].for_host, [false, false, false, true]);
+        assert!(output[1].fabric_doorbell && output[1].host_doorbell);
+        assert!(!output[2].fabric_doorbell && output[2].host_doorbell);
+        assert!(!output[3].fabric_doorbell && !output[3].host_doorbell);
+        assert!(output.iter().all(|o| !o.conflict));
+        Ok(())
+    }
+
+    #[test]
+    fn test_doorbell_sequence() -> miette::Result<()> {
+        let uut = mailbox(Policy::HostWins);
+        let host_irq = IrqMonitor::new("host_irq", |o: &Out<b16, 4>| o.host_doorbell);
+        let fabric_irq = IrqMonitor::new("fabric_irq", |o: &Out<b16, 4>| o.fabric_doorbell);
+        let mut seq = TbSequencer::new(&uut)
+            .with_idle(idle())
+            .with_driver(host_irq)
+            .with_driver(fabric_irq);
+        seq.run([
+            all([host_irq.expect_low_for(4), fabric_irq.expect_low_for(4)]),
+            pulse("host writes slot 1", |i: &mut In<b16>| {
+                i.host_write = Some((b8(1), b16(0x1111)))
+            }),
+            fabric_irq.wait_high(),
+            wait_for("slot 1 holds the data", |o: &Out<b16, 4>| {
+                o.data[1] == b16(0x1111)
+            }),
+            all([fabric_irq.expect_high_for(8), host_irq.expect_low_for(8)]),
+            pulse("fabric acknowledges slot 1", |i: &mut In<b16>| {
+                i.fabric_read = Some(b8(1))
+            }),
+            fabric_irq.wait_low(),
+            pulse("fabric writes slot 3", |i: &mut In<b16>| {
+                i.fabric_write = Some((b8(3), b16(0x3333)))
+            }),
+            host_irq.wait_high(),
+            pulse("host acknowledges slot 3", |i: &mut In<b16>| {
+                i.host_read = Some(b8(3))
+            }),
+            all([host_irq.expect_low_for(4), fabric_irq.expect_low_for(4)]),
+        ])?;
+        // Acknowledging the wrong slot leaves the doorbell ringing
+        let err = seq
+            .with_timeout(20)
+            .run([
+                pulse("host writes slot 2", |i: &mut In<b16>| {
+                    i.host_write = Some((b8(2), b16(0x2222)))
+                }),
+                pulse("fabric acknowledges slot 0", |i: &mut In<b16>| {
+                    i.fabric_read = Some(b8(0))
+                }),
+                fabric_irq.wait_low(),
+            ])
+            .unwrap_err();
+        assert!(matches!(err, SequenceError::Timeout { step: 2, .. }));
+        Ok(())
+    }
+
+    fn collide(policy: Policy) -> miette::Result<Out<b16, 4>> {
+        let input = vec![
+            In {
+                host_write: Some((b8(2), b16(0xAAAA))),
+                fabric_write: Some((b8(2), b16(0xBBBB))),
+                ..idle()
+            },
+            idle(),
+        ];
+        let output = run(&mailbox(policy), input)?;
+        assert!(output[0].conflict);
+        assert!(!output[1].conflict);
+        Ok(output[0])
+    }
+
+    #[test]
+    fn test_conflict_host_wins() -> miette::Result<()> {
+        let o = collide(Policy::HostWins)?;
+        assert_eq!(o.data[2], b16(0xAAAA));
+        assert!(o.for_fabric[2] && !o.for_host[2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflict_fabric_wins() -> miette::Result<()> {
+        let o = collide(Policy::FabricWins)?;
+        assert_eq!(o.data[2], b16(0xBBBB));
+        assert!(!o.for_fabric[2] && o.for_host[2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflict_error() -> miette::Result<()> {
+        let o = collide(Policy::Error)?;
+        assert_eq!(o.data[2], b16(0));
+        assert!(!o.for_fabric[2] && !o.for_host[2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_different_slots_do_not_conflict() -> miette::Result<()> {
+        let input = vec![In {
+            host_write: Some((b8(0), b16(1))),
+            fabric_write: Some((b8(1), b16(2))),
+            ..idle()
+        }];
+        let output = run(&mailbox(Policy::Error), input)?;
+        assert!(!output[0].conflict);
+        assert_eq!(output[0].data[..2], [b16(1), b16(2)]);
+        assert!(output[0].for_fabric[0] && output[0].for_host[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_beats_acknowledge() -> miette::Result<()> {
+        // The fabric acknowledges the first write in the same clock
+        // as the host writes again, so the flag must stay set
+        let input = vec![
+            In {
+                host_write: Some((b8(0), b16(1))),
+                ..idle()
+            },
+            In {
+                host_write: Some((b8(0), b16(2))),
+                fabric_read: Some(b8(0)),
+                ..idle()
+            },
+            idle(),
+        ];
+        let output = run(&mailbox(Policy::HostWins), input)?;
+        assert!(output[1].for_fabric[0]);
+        assert_eq!(output[2].data[0], b16(2));
+        assert!(output[2].for_fabric[0]);
+        Ok(())
+    }
+
+    type Write = Option<(b8, b16)>;
+
+    // The writes that take effect in a clock, after applying the policy
+    fn applied(i: &In<b16>, policy: Policy) -> (Write, Write) {
+        match (i.host_write, i.fabric_write) {
+            (Some((h, _)), Some((f, _))) if h == f => match policy {
+                Policy::HostWins => (i.host_write, None),
+                Policy::FabricWins => (None, i.fabric_write),
+                Policy::Error => (None, None),
+            },
+            pair => pair,
+        }
+    }
+
+    // Both sides write and acknowledge at random.  After each write
+    // that takes effect, the other side's flag for the slot must be
+    // set from the next clock until (and including) the clock in which
+    // the other side acknowledges it, and the data must stay put until
+    // the slot is written again.
+    fn soak(policy: Policy, seed: u64) -> miette::Result<()> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let slot = |rng: &mut StdRng| bits(rng.random_range(0..4));
+        let input = (0..5000_u128)
+            .map(|n| In {
+                host_write: rng.random_bool(0.2).then(|| (slot(&mut rng), bits(n))),
+                host_read: rng.random_bool(0.3).then(|| slot(&mut rng)),
+                fabric_write: rng
+                    .random_bool(0.2)
+                    .then(|| (slot(&mut rng), bits(0x8000 | n))),
+                fabric_read: rng.random_bool(0.3).then(|| slot(&mut rng)),
+            })
+            .collect::<Vec<_>>();
+        let output = run(&mailbox(policy), input.clone())?;
+        let mut writes = 0;
+        for (n, (i, o)) in input.iter().zip(&output).enumerate() {
+            let pair = (i.host_write, i.fabric_write);
+            let collided = matches!(pair, (Some((h, _)), Some((f, _))) if h == f);
+            assert_eq!(o.conflict, collided);
+            let (host, fabric) = applied(i, policy);
+            for (write, host_side) in [(host, true), (fabric, false)] {
+                let Some((s, value)) = write else {
+                    continue;
+                };
+                writes += 1;
+                let s = s.raw() as usize;
+                for (m, o) in output.iter().enumerate().skip(n) {
+                    let flag = if host_side {
+                        o.for_fabric[s]
+                    } else {
+                        o.for_host[s]
+                    };
+                    assert!(
+                        flag,
+                        "seed {seed}: the write to slot {s} in clock {n} was lost"
+                    );
+                    assert_eq!(o.data[s], value);
+                    // Stop when the slot is acknowledged or rewritten
+                    let Some(later) = input.get(m + 1) else {
+                        break;
+                    };
+                    let ack = if host_side {
+                        later.fabric_read
+                    } else {
+                        later.host_read
+                    };
+                    let (host, fabric) = applied(later, policy);
+                    let rewritten = [host, fabric]
+                        .into_iter()
+                        .flatten()
+                        .any(|(slot, _)| slot.raw() as usize == s);
+                    if ack == Some(bits(s as u128)) || rewritten {
+                        break;
+                    }
+                }
+            }
+        }
+        assert!(writes > 1000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_lost_notifications() -> miette::Result<()> {
+        for (seed, policy) in [Policy::HostWins, Policy::FabricWins, Policy::Error]
+            .into_iter()
+            .enumerate()
+        {
+            soak(policy, seed as u64)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mailbox_hdl() -> miette::Result<()> {
+        let uut = mailbox(Policy::Error);
+        let input = (0..40_u128).map(|n| In {
+            host_write: (n % 3 == 0).then(|| (bits(n % 4), bits(n))),
+            host_read: (n % 5 == 0).then(|| bits((n / 5) % 4)),
+            fabric_write: (n % 4 == 0).then(|| (bits((n / 4) % 4), bits(0x100 + n))),
+            fabric_read: (n % 7 == 0).then(|| bits((n / 7) % 4)),
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
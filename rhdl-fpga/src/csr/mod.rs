@@ -0,0 +1,3 @@
+//! Cores for configuration and status registers
+pub mod mailbox;
+pub mod shadow;
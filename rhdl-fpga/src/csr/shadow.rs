@@ -0,0 +1,467 @@
+//! Shadow Configuration Registers
+//!
+//! Multi-field configuration (like a video mode line, or a set
+//! of filter coefficients) is often updated one field at a time
+//! through a narrow control bus.  If the consumer uses the
+//! configuration while it is being updated, it can see a torn
+//! combination of old and new fields.  The [ShadowConfig] core
+//! avoids this by accumulating the field writes into a shadow
+//! copy of the configuration, and transferring the whole struct
+//! to the live register in a single clock cycle when the update
+//! is committed.
+//!
+//! The core is generic over any [Digital] configuration type `T`,
+//! and any write command type `W` (typically an enum with one
+//! variant per field).  The field writes are applied to the shadow
+//! copy by a user supplied (synthesizable) function with the
+//! signature `fn(ClockReset, (T, W)) -> (T, bool)`.  It receives
+//! the current shadow value and the write command, and returns
+//! the updated shadow value, and a flag that indicates that the
+//! command wrote the last field of the set.
+//!
+//! The update is committed when:
+//!
+//! - the `commit` input is strobed, or
+//! - the automatic commit option is enabled, and a write
+//!   flagged as the last field is applied.
+//!
+//! If the boundary option is enabled, a commit does not take
+//! effect until the consumer signals a safe boundary (like the
+//! start of a video frame) on the `boundary` input.  The value is
+//! captured when the commit fires, so writes for the next update
+//! can begin while a commit is waiting for the boundary.  A second
+//! commit before the boundary replaces the first.  The `pending`
+//! output is set while there are writes that have not yet been
+//! transferred to the live register.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+ShadowConfig+--+
+ ?W   |                 | T
++---->| write      live +---->
+ bool |                 | bool
++---->| commit  pending +---->
+ bool |                 |
++---->| boundary        |
+      +-----------------+
+")]
+//!
+//!# Internals
+//!
+//! The shadow register is updated by the write function.  When
+//! the commit fires, it is captured in the staged register, which
+//! is copied into the live register at the next boundary (or
+//! immediately if the boundary option is not enabled).
+#![doc = badascii_doc::badascii!("
+           +-+Func+-+      +-+Shadow+-+     +-+Staged+-+     +-+Live+-+
+  write    |        | new  |          |     |          |     |        | live
+ +-------->|        +--+-->|d        q+-+   |          |     |        +------>
+           |        |  |   +----------+ |   |          |     |        |
+     +---->|        |  +----------------+-->|d        q+---->|d      q|
+     |     +--------+                   |   +----------+     +--------+
+     +----------------------------------+         ^               ^
+                                         commit +-+    boundary +-+
+")]
+//!
+//!# Example
+//!
+//! A configuration with three fields, in which the auto commit
+//! fires when the `V` field is written.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::csr::shadow::{Options, ShadowConfig};
+//!
+//! #[derive(PartialEq, Debug, Digital, Default)]
+//! pub struct Mode {
+//!     h: b8,
+//!     v: b8,
+//!     pol: bool,
+//! }
+//!
+//! #[derive(PartialEq, Debug, Digital)]
+//! pub enum Field {
+//!     H(b8),
+//!     V(b8),
+//!     Pol(bool),
+//! }
+//!
+//! impl Default for Field {
+//!     fn default() -> Self {
+//!         Field::H(b8(0))
+//!     }
+//! }
+//!
+//! #[kernel]
+//! pub fn update(_cr: ClockReset, i: (Mode, Field)) -> (Mode, bool) {
+//!     let (mut mode, field) = i;
+//!     let mut last = false;
+//!     match field {
+//!         Field::H(h) => mode.h = h,
+//!         Field::V(v) => {
+//!             mode.v = v;
+//!             last = true;
+//!         }
+//!         Field::Pol(pol) => mode.pol = pol,
+//!     }
+//!     (mode, last)
+//! }
+//!
+//! let uut = ShadowConfig::<Mode, Field>::try_new::<update>(
+//!     Mode::default(),
+//!     Options {
+//!         at_boundary: false,
+//!         auto_commit: true,
+//!     },
+//! )
+//! .unwrap();
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// Options that control when the shadow copy is
+/// transferred to the live register.
+pub struct Options {
+    /// Hold commits until the `boundary` input is asserted.
+    pub at_boundary: bool,
+    /// Commit automatically when a write flagged as the
+    /// last field is applied.
+    pub auto_commit: bool,
+}
+
+#[derive(Clone, Synchronous, SynchronousDQ)]
+/// The shadow configuration core
+///
+/// Here `T` is the configuration type, and `W` is the type
+/// of the field write commands.
+pub struct ShadowConfig<T: Digital, W: Digital> {
+    options: constant::Constant<Options>,
+    shadow: dff::DFF<T>,
+    staged: dff::DFF<T>,
+    live: dff::DFF<T>,
+    dirty: dff::DFF<bool>,
+    armed: dff::DFF<bool>,
+    update: Func<(T, W), (T, bool)>,
+}
+
+impl<T: Digital, W: Digital> ShadowConfig<T, W> {
+    /// Construct a [ShadowConfig] core.
+    ///
+    /// The `initial` value is loaded into both the shadow and the
+    /// live registers on reset.  The kernel `K` applies a field write
+    /// to the shadow copy, and must have the signature
+    /// `fn(ClockReset, (T, W)) -> (T, bool)`.
+    pub fn try_new<K>(initial: T, options: Options) -> Result<Self, RHDLError>
+    where
+        K: DigitalFn,
+        K: DigitalFn2<A0 = ClockReset, A1 = (T, W), O = (T, bool)>,
+    {
+        Ok(Self {
+            options: constant::Constant::new(options),
+            shadow: dff::DFF::new(initial),
+            staged: dff::DFF::new(initial),
+            live: dff::DFF::new(initial),
+            dirty: dff::DFF::new(false),
+            armed: dff::DFF::new(false),
+            update: Func::try_new::<K>()?,
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [ShadowConfig] core
+pub struct In<W: Digital> {
+    /// A field write to apply to the shadow copy
+    pub write: Option<W>,
+    /// Strobe to commit the shadow copy
+    pub commit: bool,
+    /// Signals a safe point for the consumer to
+    /// switch configurations
+    pub boundary: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [ShadowConfig] core
+pub struct Out<T: Digital> {
+    /// The live configuration
+    pub live: T,
+    /// Set if the shadow copy has writes that have
+    /// not been transferred to the live configuration
+    pub pending: bool,
+}
+
+impl<T: Digital, W: Digital> SynchronousIO for ShadowConfig<T, W> {
+    type I = In<W>;
+    type O = Out<T>;
+    type Kernel = shadow_kernel<T, W>;
+}
+
+#[kernel(allow_weak_partial)]
+#[doc(hidden)]
+pub fn shadow_kernel<T: Digital, W: Digital>(
+    _cr: ClockReset,
+    i: In<W>,
+    q: Q<T, W>,
+) -> (Out<T>, D<T, W>) {
+    let mut d = D::<T, W>::dont_care();
+    d.update = (q.shadow, W::dont_care());
+    d.shadow = q.shadow;
+    d.staged = q.staged;
+    d.live = q.live;
+    d.dirty = q.dirty;
+    let mut commit = i.commit;
+    if let Some(write) = i.write {
+        d.update = (q.shadow, write);
+        let (shadow, last) = q.update;
+        d.shadow = shadow;
+        d.dirty = true;
+        if last && q.options.auto_commit {
+            commit = true;
+        }
+    }
+    // Capture the shadow copy (including any write in
+    // this cycle) when the commit fires
+    if commit {
+        d.staged = d.shadow;
+        d.dirty = false;
+    }
+    // Transfer the staged copy immediately, or hold it
+    // until the consumer signals a boundary
+    let armed = commit || q.armed;
+    d.armed = armed;
+    if armed && (!q.options.at_boundary || i.boundary) {
+        d.live = d.staged;
+        d.armed = false;
+    }
+    let o = Out::<T> {
+        live: q.live,
+        pending: q.dirty || q.armed,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, Rng, SeedableRng};
+
+    use super::*;
+
+    #[derive(PartialEq, Debug, Digital, Default)]
+    struct Mode {
+        h: b8,
+        v: b8,
+        pol: bool,
+    }
+
+    impl Mode {
+        // Every consistent configuration is derived from
+        // a single generation number.
+        fn generation(g: u128) -> Self {
+            Self {
+                h: bits(g & 0xFF),
+                v: bits((g * 3) & 0xFF),
+                pol: g % 2 == 1,
+            }
+        }
+        fn consistent(&self) -> bool {
+            *self == Self::generation(self.h.raw())
+        }
+    }
+
+    #[derive(PartialEq, Debug, Digital)]
+    enum Field {
+        H(b8),
+        V(b8),
+        Pol(bool),
+    }
+
+    impl Default for Field {
+        fn default() -> Self {
+            Field::H(b8(0))
+        }
+    }
+
+    #[kernel]
+    fn update(_cr: ClockReset, i: (Mode, Field)) -> (Mode, bool) {
+        let (mut mode, field) = i;
+        let mut last = false;
+        match field {
+            Field::H(h) => mode.h = h,
+            Field::V(v) => {
+                mode.v = v;
+                last = true;
+            }
+            Field::Pol(pol) => mode.pol = pol,
+        }
+        (mode, last)
+    }
+
+    fn idle() -> In<Field> {
+        In {
+            write: None,
+            commit: false,
+            boundary: false,
+        }
+    }
+
+    fn fields(g: u128) -> [Field; 3] {
+        let mode = Mode::generation(g);
+        [Field::H(mode.h), Field::V(mode.v), Field::Pol(mode.pol)]
+    }
+
+    // Write a series of configurations, with the fields in random
+    // order, random idle cycles, and a periodic boundary signal.  If
+    // `last_is_v` is set, then the V field is always written last.
+    fn scenario(seed: u64, explicit_commit: bool, last_is_v: bool) -> Vec<In<Field>> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut ops = vec![];
+        for g in 1..100 {
+            let mut fields = fields(g);
+            fields.shuffle(&mut rng);
+            if last_is_v {
+                let ndx = fields
+                    .iter()
+                    .position(|f| matches!(f, Field::V(_)))
+                    .unwrap();
+                fields.swap(ndx, 2);
+            }
+            for field in fields {
+                ops.push(In {
+                    write: Some(field),
+                    ..idle()
+                });
+                for _ in 0..rng.random_range(0..3) {
+                    ops.push(idle());
+                }
+            }
+            if explicit_commit {
+                ops.push(In {
+                    commit: true,
+                    ..idle()
+                });
+            }
+            for _ in 0..rng.random_range(0..10) {
+                ops.push(idle());
+            }
+        }
+        ops.extend((0..20).map(|_| idle()));
+        ops.iter_mut()
+            .enumerate()
+            .for_each(|(n, op)| op.boundary = n % 7 == 0);
+        ops
+    }
+
+    fn run(options: Options, ops: Vec<In<Field>>) -> miette::Result<Vec<(In<Field>, Out<Mode>)>> {
+        let uut = ShadowConfig::<Mode, Field>::try_new::<update>(Mode::default(), options)?;
+        Ok(uut
+            .run(ops.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| (t.value.1, t.value.2))
+            .collect())
+    }
+
+    fn check_no_tearing(output: &[(In<Field>, Out<Mode>)]) -> Vec<b8> {
+        let mut generations = vec![];
+        for (_, out) in output {
+            assert!(out.live.consistent(), "Torn configuration {:?}", out.live);
+            if generations.last() != Some(&out.live.h) {
+                generations.push(out.live.h);
+            }
+        }
+        // The configurations must be seen in order, ending with the last one
+        assert!(generations.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(output.last().unwrap().1.live, Mode::generation(99));
+        assert!(!output.last().unwrap().1.pending);
+        generations
+    }
+
+    #[test]
+    fn test_strobe_commit_is_atomic() -> miette::Result<()> {
+        let options = Options::default();
+        for seed in 0..5 {
+            let generations = check_no_tearing(&run(options, scenario(seed, true, false))?);
+            assert_eq!(generations.len(), 100);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_commit_on_last_field() -> miette::Result<()> {
+        let options = Options {
+            at_boundary: false,
+            auto_commit: true,
+        };
+        for seed in 0..5 {
+            let generations = check_no_tearing(&run(options, scenario(seed, false, true))?);
+            assert_eq!(generations.len(), 100);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_waits_for_boundary() -> miette::Result<()> {
+        let options = Options {
+            at_boundary: true,
+            auto_commit: false,
+        };
+        for seed in 0..5 {
+            let output = run(options, scenario(seed, true, false))?;
+            // Commits that are replaced before the boundary are never seen
+            check_no_tearing(&output);
+            // The live value only changes on the cycle following a boundary
+            for w in output.windows(2) {
+                if w[0].1.live != w[1].1.live {
+                    assert!(w[0].0.boundary);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_status() -> miette::Result<()> {
+        let ops = vec![
+            idle(),
+            In {
+                write: Some(Field::H(b8(1))),
+                ..idle()
+            },
+            idle(),
+            In {
+                commit: true,
+                ..idle()
+            },
+            idle(),
+        ];
+        let output = run(Options::default(), ops)?;
+        let pending = output.iter().map(|(_, o)| o.pending).collect::<Vec<_>>();
+        // The reset cycle comes first
+        assert_eq!(pending, [false, false, false, true, true, false]);
+        assert_eq!(output.last().unwrap().1.live.h, b8(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shadow_config_hdl() -> miette::Result<()> {
+        let uut = ShadowConfig::<Mode, Field>::try_new::<update>(
+            Mode::default(),
+            Options {
+                at_boundary: true,
+                auto_commit: true,
+            },
+        )?;
+        let input = scenario(0, false, true)
+            .into_iter()
+            .take(200)
+            .with_reset(1)
+            .clock_pos_edge(100);
+        let tb = uut.run(input)?.collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,530 @@
+//! Address Decoder
+//!
+//! An address decoder takes an address and determines which
+//! of a set of regions (if any) it falls into.  The result
+//! is a one-hot select vector (one entry per region), and a
+//! `none` flag that is asserted when the address does not hit
+//! any region.  Address decoders are used to generate chip
+//! selects, and to route bus transactions in interconnects.
+//!
+//! The regions are described either by a base address and a
+//! size (which must be a power of two), or by a base address
+//! and a mask.  In both cases, an address `a` hits the region
+//! when `a & mask == base`.  The regions are collected in a
+//! [Config], and an [AddressMap] can only be built from a
+//! validated one (see [crate::config]).  So an empty map, or
+//! overlapping or misaligned regions, are rejected with a
+//! descriptive [ConfigError], instead of producing a decoder in
+//! which two selects can be active at the same time.
+//!
+//! Here is the schematic symbol for the registered form
+//! of the decoder.
+#![doc = badascii_doc::badascii_formal!("
+     +-+AddressDecoder+------+
+ bA  |                       | [bool; R]
++--->| addr           select +--------->
+     |                       |
+     |                  none +--------->
+     |                       |  bool
+     +-----------------------+
+")]
+//!
+//! The decoding logic is also available as a kernel function
+//! [address_decode], for use in combinatorial paths within other
+//! cores.
+//!
+//!# Example
+//!
+//! Here is a simple decoder with two regions in an 8 bit address space.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::config::Validate;
+//! use rhdl_fpga::decode::address::{address_decode, AddressMap, Config, Region};
+//!
+//! let config = Config::<U8, 2>::new([
+//!     Region::Sized { base: 0x00, size: 0x40 },
+//!     Region::Masked { base: 0x80, mask: 0xF0 },
+//! ]);
+//! let map = AddressMap::new(config.validate().unwrap());
+//! let sel = address_decode::<U8, 2>(bits(0x84), map);
+//! assert_eq!(sel.select, [false, true]);
+//! assert!(!sel.none);
+//! ```
+use miette::Diagnostic;
+use rhdl::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    config::{Validate, Validated},
+    core::{constant, dff},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A region in the address space
+pub enum Region {
+    /// A region that starts at `base` and spans `size`
+    /// addresses.  The `size` must be a power of two, and
+    /// `base` must be aligned to `size`.
+    Sized {
+        /// The first address in the region
+        base: u128,
+        /// The number of addresses in the region
+        size: u128,
+    },
+    /// A region that contains all addresses `a` for
+    /// which `a & mask == base`.  The `base` must not
+    /// have any bits set outside of `mask`.
+    Masked {
+        /// The base address of the region
+        base: u128,
+        /// The mask of address bits that are compared
+        mask: u128,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The regions of an [AddressMap] in an `A` bit address space
+pub struct Config<A: BitWidth, const R: usize> {
+    /// The regions, in the order of the select outputs
+    pub regions: [Region; R],
+    marker: std::marker::PhantomData<A>,
+}
+
+impl<A: BitWidth, const R: usize> Config<A, R> {
+    /// Decode the given list of regions
+    pub fn new(regions: [Region; R]) -> Self {
+        Self {
+            regions,
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors that can arise when validating a [Config]
+pub enum ConfigError {
+    /// There are no regions to decode
+    #[error("The address map has no regions")]
+    #[diagnostic(help("Every address would decode to none, so at least one region is needed"))]
+    Empty,
+    /// The size of the region is not a power of two
+    #[error("Region {index} has a size of {size:#x}, which is not a non-zero power of two")]
+    #[diagnostic(help("Regions described by a size must span a power of two addresses"))]
+    SizeNotPowerOfTwo {
+        /// The index of the offending region
+        index: usize,
+        /// The size provided
+        size: u128,
+    },
+    /// The region does not fit in the address space
+    #[error("Region {index} (base {base:#x}, mask {mask:#x}) does not fit in a {width} bit address space")]
+    OutOfRange {
+        /// The index of the offending region
+        index: usize,
+        /// The base address of the region
+        base: u128,
+        /// The mask of the region
+        mask: u128,
+        /// The width of the address space in bits
+        width: usize,
+    },
+    /// The base of the region is not aligned
+    #[error("Region {index} has base {base:#x} which is not aligned to its mask {mask:#x}")]
+    #[diagnostic(help("The base address must not have bits set outside of the mask"))]
+    Misaligned {
+        /// The index of the offending region
+        index: usize,
+        /// The base address of the region
+        base: u128,
+        /// The mask of the region
+        mask: u128,
+    },
+    /// Two regions overlap
+    #[error("Region {first} and region {second} overlap (for example at address {example:#x})")]
+    #[diagnostic(help("Each address may only decode to a single region"))]
+    Overlap {
+        /// The index of the first region
+        first: usize,
+        /// The index of the second region
+        second: usize,
+        /// An address that hits both regions
+        example: u128,
+    },
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// A validated set of regions, stored as base and mask
+/// pairs.  This can only be constructed via [AddressMap::new]
+/// from a validated [Config], which guarantees that the regions
+/// do not overlap.
+pub struct AddressMap<A: BitWidth, const R: usize> {
+    base: [Bits<A>; R],
+    mask: [Bits<A>; R],
+}
+
+impl<A: BitWidth, const R: usize> Config<A, R> {
+    // Convert the regions to base and mask pairs, checking
+    // that each is well formed, and that no two overlap
+    fn resolve(&self) -> Result<([u128; R], [u128; R]), ConfigError> {
+        if R == 0 {
+            return Err(ConfigError::Empty);
+        }
+        let full = Bits::<A>::mask().raw();
+        let mut base = [0; R];
+        let mut mask = [0; R];
+        for (index, region) in self.regions.into_iter().enumerate() {
+            let (b, m, fits) = match region {
+                Region::Sized { base, size } => {
+                    if !size.is_power_of_two() {
+                        return Err(ConfigError::SizeNotPowerOfTwo { index, size });
+                    }
+                    // The mask for a sized region is the complement of
+                    // the offset bits, restricted to the address space
+                    let mask = !(size - 1) & full;
+                    (base, mask, size - 1 <= full)
+                }
+                Region::Masked { base, mask } => (base, mask, mask <= full),
+            };
+            if !fits || b > full {
+                return Err(ConfigError::OutOfRange {
+                    index,
+                    base: b,
+                    mask: m,
+                    width: A::BITS,
+                });
+            }
+            if b & !m != 0 {
+                return Err(ConfigError::Misaligned {
+                    index,
+                    base: b,
+                    mask: m,
+                });
+            }
+            base[index] = b;
+            mask[index] = m;
+        }
+        // Two regions overlap if their bases agree on all of the
+        // bits that both of them compare.  In that case, the
+        // address formed by combining the two bases hits both.
+        for first in 0..R {
+            for second in first + 1..R {
+                let common = mask[first] & mask[second];
+                if (base[first] ^ base[second]) & common == 0 {
+                    return Err(ConfigError::Overlap {
+                        first,
+                        second,
+                        example: base[first] | base[second],
+                    });
+                }
+            }
+        }
+        Ok((base, mask))
+    }
+}
+
+impl<A: BitWidth, const R: usize> Validate for Config<A, R> {
+    type Error = ConfigError;
+    fn check(&self) -> Result<(), ConfigError> {
+        self.resolve().map(|_| ())
+    }
+}
+
+impl<A: BitWidth, const R: usize> AddressMap<A, R> {
+    /// Build an address map from a validated [Config]
+    pub fn new(config: Validated<Config<A, R>>) -> Self {
+        let (base, mask) = config
+            .resolve()
+            .expect("A validated address map should resolve");
+        Self {
+            base: base.map(bits),
+            mask: mask.map(bits),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The result of decoding an address
+pub struct Select<const R: usize> {
+    /// One-hot select vector, with one entry per region
+    pub select: [bool; R],
+    /// Set if the address does not hit any region
+    pub none: bool,
+}
+
+#[kernel]
+/// Decode an address against the given [AddressMap].
+pub fn address_decode<A: BitWidth, const R: usize>(
+    addr: Bits<A>,
+    map: AddressMap<A, R>,
+) -> Select<R> {
+    let mut o = Select::<R> {
+        select: [false; R],
+        none: true,
+    };
+    for i in 0..R {
+        let hit = (addr & map.mask[i]) == map.base[i];
+        o.select[i] = hit;
+        if hit {
+            o.none = false;
+        }
+    }
+    o
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// A registered address decoder.  The select outputs
+/// are delayed by one clock cycle relative to the address.
+pub struct AddressDecoder<A: BitWidth, const R: usize> {
+    map: constant::Constant<AddressMap<A, R>>,
+    select: dff::DFF<Select<R>>,
+}
+
+impl<A: BitWidth, const R: usize> AddressDecoder<A, R> {
+    /// Create a registered decoder for the given [AddressMap]
+    pub fn new(map: AddressMap<A, R>) -> Self {
+        Self {
+            map: constant::Constant::new(map),
+            select: dff::DFF::new(Select {
+                select: [false; R],
+                none: true,
+            }),
+        }
+    }
+}
+
+impl<A: BitWidth, const R: usize> SynchronousIO for AddressDecoder<A, R> {
+    type I = Bits<A>;
+    type O = Select<R>;
+    type Kernel = decoder_kernel<A, R>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn decoder_kernel<A: BitWidth, const R: usize>(
+    cr: ClockReset,
+    i: Bits<A>,
+    q: Q<A, R>,
+) -> (Select<R>, D<A, R>) {
+    let mut d = D::<A, R>::dont_care();
+    d.select = address_decode::<A, R>(i, q.map);
+    if cr.reset.any() {
+        d.select = Select::<R> {
+            select: [false; R],
+            none: true,
+        };
+    }
+    (q.select, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_map() -> AddressMap<U8, 3> {
+        let config = Config::new([
+            Region::Sized {
+                base: 0x00,
+                size: 0x20,
+            },
+            Region::Sized {
+                base: 0x40,
+                size: 0x10,
+            },
+            Region::Masked {
+                base: 0x81,
+                mask: 0x83,
+            },
+        ]);
+        AddressMap::new(config.validate().unwrap())
+    }
+
+    fn model(addr: u128) -> Select<3> {
+        let select = [
+            addr < 0x20,
+            (0x40..0x50).contains(&addr),
+            addr & 0x83 == 0x81,
+        ];
+        Select {
+            select,
+            none: !select.iter().any(|x| *x),
+        }
+    }
+
+    #[test]
+    fn test_overlapping_regions_rejected() {
+        let err = Config::<U8, 2>::new([
+            Region::Sized {
+                base: 0x00,
+                size: 0x40,
+            },
+            Region::Sized {
+                base: 0x20,
+                size: 0x10,
+            },
+        ])
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError::Overlap {
+                first: 0,
+                second: 1,
+                example: 0x20
+            }
+        );
+        let err = Config::<U8, 3>::new([
+            Region::Sized {
+                base: 0x00,
+                size: 0x10,
+            },
+            Region::Masked {
+                base: 0x80,
+                mask: 0x80,
+            },
+            Region::Masked {
+                base: 0x01,
+                mask: 0x01,
+            },
+        ])
+        .validate()
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Overlap {
+                first: 0,
+                second: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_misaligned_regions_rejected() {
+        let err = Config::<U8, 1>::new([Region::Sized {
+            base: 0x08,
+            size: 0x10,
+        }])
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError::Misaligned {
+                index: 0,
+                base: 0x08,
+                mask: 0xF0
+            }
+        );
+        let err = Config::<U8, 2>::new([
+            Region::Sized {
+                base: 0x00,
+                size: 0x10,
+            },
+            Region::Masked {
+                base: 0x81,
+                mask: 0xF0,
+            },
+        ])
+        .validate()
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::Misaligned { index: 1, .. }));
+    }
+
+    #[test]
+    fn test_malformed_regions_rejected() {
+        let err = Config::<U8, 1>::new([Region::Sized {
+            base: 0x00,
+            size: 0x30,
+        }])
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError::SizeNotPowerOfTwo {
+                index: 0,
+                size: 0x30
+            }
+        );
+        let err = Config::<U8, 1>::new([Region::Sized {
+            base: 0x00,
+            size: 0,
+        }])
+        .validate()
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::SizeNotPowerOfTwo { .. }));
+        let err = Config::<U8, 1>::new([Region::Sized {
+            base: 0x100,
+            size: 0x100,
+        }])
+        .validate()
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::OutOfRange { .. }));
+        let err = Config::<U8, 1>::new([Region::Masked {
+            base: 0x00,
+            mask: 0x1F0,
+        }])
+        .validate()
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::OutOfRange { .. }));
+    }
+
+    #[test]
+    fn test_empty_map_rejected() {
+        let err = Config::<U8, 0>::new([]).validate().unwrap_err();
+        assert_eq!(err, ConfigError::Empty);
+    }
+
+    #[test]
+    fn test_whole_space_region() {
+        let config = Config::<U8, 1>::new([Region::Sized {
+            base: 0,
+            size: 0x100,
+        }]);
+        let map = AddressMap::new(config.validate().unwrap());
+        assert!((0..256).all(|a| address_decode::<U8, 1>(bits(a), map).select[0]));
+    }
+
+    #[test]
+    fn test_exhaustive_decode() {
+        let map = test_map();
+        for addr in 0..256 {
+            let sel = address_decode::<U8, 3>(bits(addr), map);
+            assert_eq!(sel, model(addr), "Decode mismatch at address {addr:#x}");
+            assert!(sel.select.iter().filter(|x| **x).count() <= 1);
+        }
+    }
+
+    fn test_stream() -> impl Iterator<Item = TimedSample<(ClockReset, Bits<U8>)>> + Clone {
+        (0..256).map(bits).with_reset(1).clock_pos_edge(100)
+    }
+
+    #[test]
+    fn test_registered_decode() -> miette::Result<()> {
+        let uut = AddressDecoder::new(test_map());
+        let output = uut
+            .run(test_stream())?
+            .synchronous_sample()
+            .map(|t| (t.value.1, t.value.2))
+            .collect::<Vec<_>>();
+        // The output lags the input by one clock
+        for window in output.windows(2).skip(1) {
+            let (addr, _) = window[0];
+            let (_, sel) = window[1];
+            assert_eq!(sel, model(addr.raw()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoder_hdl() -> miette::Result<()> {
+        let uut = AddressDecoder::new(test_map());
+        let tb = uut
+            .run(test_stream())?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+//! Address decoding cores
+pub mod address;
@@ -0,0 +1,521 @@
+//! Gain and offset correction for sensor channels
+//!
+//!# Purpose
+//!
+//! The [GainOffset] core applies a per-channel calibration to a
+//! stream of samples from a multi-channel ADC.  Each sample carries
+//! the channel it came from, and is corrected with the coefficients
+//! of that channel:
+//!
+//!```text
+//!   y = ((x - offset) * gain) >> shift
+//!```
+//!
+//! The gain is a signed fixed point value, with `shift` fractional
+//! bits, so that a gain of `1 << shift` is unity.  The shift is
+//! arithmetic, and the result is saturated to the range of the output
+//! sample.  A channel can also be put into bypass, in which case its
+//! samples pass through unchanged.  Samples for channels at or above
+//! `CH` are always passed through.
+//!
+//! The coefficients are written one field at a time, through a
+//! [ShadowConfig] register.  The writes collect in a shadow copy, and
+//! only take effect (for all channels at once) when they are committed.
+//! Each sample picks up the coefficients of its channel in a single
+//! clock, so that no sample is ever corrected with a mix of old and new
+//! coefficients.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+GainOffset+------+
+ ?CS  |                   | ?CS
++---->| data         data +---->
+ ?W   |                   | bool
++---->| write     pending +---->
+ bool |                   |
++---->| commit            |
+      +-------------------+
+")]
+//!
+//! where `CS` is a [ChannelSample], and `W` is a [CoeffWrite].
+//!
+//!# Timing
+//!
+//! The core is a two stage pipeline.  The corrected sample appears
+//! on the output two clocks after the sample is presented on the input.
+//! The core does not apply backpressure, as ADC samples arrive at a
+//! fixed (and slow) rate.
+//!
+//!# Example
+//!
+//! A 4 channel corrector, with channel 2 set to a gain of 1.5
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::dsp::gain_offset::{Coeffs, GainOffset};
+//!
+//! let mut coeffs = [Coeffs::default(); 4];
+//! coeffs[2] = Coeffs {
+//!     gain: s16(3),
+//!     shift: b5(1),
+//!     ..Default::default()
+//! };
+//! let uut = GainOffset::<4>::try_new(coeffs).unwrap();
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    core::dff,
+    csr::shadow::{self, Options, ShadowConfig},
+};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// A sample from one channel of a multi-channel ADC
+pub struct ChannelSample {
+    /// The channel the sample came from
+    pub channel: b8,
+    /// The sample
+    pub sample: s16,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The calibration coefficients for a channel
+pub struct Coeffs {
+    /// The gain, as a fixed point value with `shift` fractional bits
+    pub gain: s16,
+    /// The offset, which is subtracted before the gain is applied
+    pub offset: s16,
+    /// The number of fractional bits in the gain
+    pub shift: b5,
+    /// Pass the samples through unchanged
+    pub bypass: bool,
+}
+
+impl Default for Coeffs {
+    /// Unity gain, and no offset
+    fn default() -> Self {
+        Self {
+            gain: s16(1),
+            offset: s16(0),
+            shift: b5(0),
+            bypass: false,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// A coefficient field
+pub enum Field {
+    /// Set the gain
+    Gain(s16),
+    /// Set the offset
+    Offset(s16),
+    /// Set the number of fractional bits in the gain
+    Shift(b5),
+    /// Set the bypass flag
+    Bypass(bool),
+}
+
+impl Default for Field {
+    fn default() -> Self {
+        Field::Bypass(false)
+    }
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// A write to one of the coefficients of a channel
+pub struct CoeffWrite {
+    /// The channel to update
+    pub channel: b8,
+    /// The field to write
+    pub field: Field,
+}
+
+#[kernel]
+#[doc(hidden)]
+#[allow(clippy::needless_range_loop)]
+pub fn update_coeffs<const CH: usize>(
+    _cr: ClockReset,
+    i: ([Coeffs; CH], CoeffWrite),
+) -> ([Coeffs; CH], bool) {
+    let (mut coeffs, write) = i;
+    for ch in 0..CH {
+        if write.channel == bits(ch as u128) {
+            match write.field {
+                Field::Gain(gain) => coeffs[ch].gain = gain,
+                Field::Offset(offset) => coeffs[ch].offset = offset,
+                Field::Shift(shift) => coeffs[ch].shift = shift,
+                Field::Bypass(bypass) => coeffs[ch].bypass = bypass,
+            }
+        }
+    }
+    // Updates are only committed explicitly
+    (coeffs, false)
+}
+
+#[kernel]
+/// Apply the coefficients to a sample
+pub fn correct(x: s16, c: Coeffs) -> s16 {
+    let diff = x.dyn_bits().xsub(c.offset.dyn_bits()); // Size 17
+    let y = diff.xmul(c.gain.dyn_bits()); // Size 33
+    let y: s33 = y.as_signed_bits();
+    let y = y >> c.shift;
+    // Saturate to the range of the output
+    let max: s33 = signed(32767);
+    let min: s33 = signed(-32768);
+    let mut out = y.resize::<U16>();
+    if y > max {
+        out = s16(32767);
+    }
+    if y < min {
+        out = s16(-32768);
+    }
+    if c.bypass {
+        out = x;
+    }
+    out
+}
+
+#[derive(Clone, Synchronous, SynchronousDQ)]
+/// The gain and offset correction core
+///
+///   `CH` is the number of calibrated channels
+pub struct GainOffset<const CH: usize> {
+    csr: ShadowConfig<[Coeffs; CH], CoeffWrite>,
+    stage: dff::DFF<Option<(ChannelSample, Coeffs)>>,
+    out: dff::DFF<Option<ChannelSample>>,
+}
+
+impl<const CH: usize> GainOffset<CH> {
+    /// Construct a [GainOffset] core with the given
+    /// initial coefficients for each channel
+    pub fn try_new(initial: [Coeffs; CH]) -> Result<Self, RHDLError> {
+        Ok(Self {
+            csr: ShadowConfig::try_new::<update_coeffs<CH>>(initial, Options::default())?,
+            stage: dff::DFF::new(None),
+            out: dff::DFF::new(None),
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [GainOffset] core
+pub struct In {
+    /// The sample to correct
+    pub data: Option<ChannelSample>,
+    /// A coefficient write, which goes to the shadow copy
+    pub write: Option<CoeffWrite>,
+    /// Strobe to commit the shadow copy of the coefficients
+    pub commit: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [GainOffset] core
+pub struct Out {
+    /// The corrected sample
+    pub data: Option<ChannelSample>,
+    /// There are coefficient writes that have not been committed
+    pub pending: bool,
+}
+
+impl<const CH: usize> SynchronousIO for GainOffset<CH> {
+    type I = In;
+    type O = Out;
+    type Kernel = gain_offset_kernel<CH>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn gain_offset_kernel<const CH: usize>(_cr: ClockReset, i: In, q: Q<CH>) -> (Out, D<CH>) {
+    let mut d = D::<{ CH }>::dont_care();
+    d.csr = shadow::In::<CoeffWrite> {
+        write: i.write,
+        commit: i.commit,
+        boundary: false,
+    };
+    // Capture the coefficients along with the sample
+    d.stage = None;
+    if let Some(s) = i.data {
+        let mut c = Coeffs {
+            gain: s16(1),
+            offset: s16(0),
+            shift: b5(0),
+            bypass: true,
+        };
+        for ch in 0..CH {
+            if s.channel == bits(ch as u128) {
+                c = q.csr.live[ch];
+            }
+        }
+        d.stage = Some((s, c));
+    }
+    d.out = None;
+    if let Some((s, c)) = q.stage {
+        d.out = Some(ChannelSample {
+            channel: s.channel,
+            sample: correct(s.sample, c),
+        });
+    }
+    let o = Out {
+        data: q.out,
+        pending: q.csr.pending,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::dsp::model::{correct as model, verify_dsp, GainOffsetModel};
+
+    fn coeffs(gain: i16, offset: i16, shift: u8) -> Coeffs {
+        Coeffs {
+            gain: s16(gain as i128),
+            offset: s16(offset as i128),
+            shift: b5(shift as u128),
+            bypass: false,
+        }
+    }
+
+    fn idle() -> In {
+        In {
+            data: None,
+            write: None,
+            commit: false,
+        }
+    }
+
+    fn sample(channel: u8, x: i16) -> In {
+        In {
+            data: Some(ChannelSample {
+                channel: bits(channel as u128),
+                sample: s16(x as i128),
+            }),
+            ..idle()
+        }
+    }
+
+    fn write(channel: u8, field: Field) -> In {
+        In {
+            write: Some(CoeffWrite {
+                channel: bits(channel as u128),
+                field,
+            }),
+            ..idle()
+        }
+    }
+
+    fn initial() -> [Coeffs; 4] {
+        [
+            coeffs(1, 0, 0),
+            coeffs(3, 100, 1),
+            coeffs(-200, -12, 4),
+            Coeffs {
+                bypass: true,
+                ..coeffs(7, 7, 7)
+            },
+        ]
+    }
+
+    fn run(uut: &GainOffset<4>, input: Vec<In>) -> miette::Result<Vec<(u8, i16)>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .filter_map(|t| t.value.2.data)
+            .map(|s| (s.channel.raw() as u8, s.sample.raw() as i16))
+            .collect())
+    }
+
+    #[test]
+    fn test_matches_model() -> miette::Result<()> {
+        let uut = GainOffset::<4>::try_new(initial())?;
+        let mut rng = StdRng::seed_from_u64(1);
+        // Channel interleaved samples, with some gaps, and some
+        // samples for channels that are not calibrated
+        let samples = (0..500)
+            .map(|n| ((n % 5) as u8, rng.random::<i16>()))
+            .collect::<Vec<_>>();
+        let mut input = vec![];
+        for (channel, x) in &samples {
+            input.push(sample(*channel, *x));
+            if rng.random::<u8>() < 50 {
+                input.push(idle());
+            }
+        }
+        input.extend([idle(), idle()]);
+        let output = run(&uut, input)?;
+        let coeffs = initial();
+        let expected = samples
+            .iter()
+            .map(|&(channel, x)| match coeffs.get(channel as usize) {
+                Some(c) => (channel, model(x, c)),
+                None => (channel, x),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bit_exact_with_writes() -> miette::Result<()> {
+        let uut = GainOffset::<4>::try_new(initial())?;
+        let mut rng = StdRng::seed_from_u64(2);
+        // Random samples, with random coefficient writes and commits
+        let input = (0..2000)
+            .map(|n| {
+                let mut op = sample((n % 5) as u8, rng.random::<i16>());
+                if rng.random::<u8>() < 30 {
+                    op.data = None;
+                }
+                if rng.random::<u8>() < 20 {
+                    let field = match rng.random_range(0..4) {
+                        0 => Field::Gain(s16(rng.random::<i16>() as i128)),
+                        1 => Field::Offset(s16(rng.random::<i16>() as i128)),
+                        2 => Field::Shift(b5(rng.random_range(0..32))),
+                        _ => Field::Bypass(rng.random::<u8>() < 20),
+                    };
+                    op.write = write(rng.random_range(0..5), field).write;
+                }
+                op.commit = rng.random::<u8>() < 5;
+                op
+            })
+            .collect::<Vec<_>>();
+        let checked = verify_dsp(&uut, GainOffsetModel::new(initial()), input)?;
+        assert_eq!(checked, 2000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_within_float_tolerance() -> miette::Result<()> {
+        let uut = GainOffset::<4>::try_new(initial())?;
+        let input = (0..400)
+            .map(|n| sample((n % 3) as u8, (n as i16 - 200) * 163))
+            .chain([idle(), idle()])
+            .collect::<Vec<_>>();
+        let samples = input
+            .iter()
+            .filter_map(|i| i.data)
+            .map(|s| s.sample.raw() as f64)
+            .collect::<Vec<_>>();
+        let output = run(&uut, input)?;
+        let coeffs = initial();
+        for ((channel, y), x) in output.into_iter().zip(samples) {
+            let c = coeffs[channel as usize];
+            let exact = (x - c.offset.raw() as f64) * c.gain.raw() as f64
+                / 2.0_f64.powi(c.shift.raw() as i32);
+            let exact = exact.clamp(i16::MIN as f64, i16::MAX as f64);
+            // Rounding down loses less than one LSB
+            let error = y as f64 - exact;
+            assert!(error <= 0.0 && error > -1.0, "{channel} {x} {y} {exact}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_saturation() -> miette::Result<()> {
+        let uut = GainOffset::<4>::try_new(initial())?;
+        let input = vec![
+            // Gain of -200/16 on channel 2
+            sample(2, 30000),
+            sample(2, -30000),
+            sample(2, 100),
+            // Gain of 3/2 on channel 1
+            sample(1, i16::MAX),
+            sample(1, i16::MIN),
+            idle(),
+            idle(),
+        ];
+        let output = run(&uut, input)?;
+        assert_eq!(
+            output,
+            [
+                (2, i16::MIN),
+                (2, i16::MAX),
+                (2, -1400),
+                (1, i16::MAX),
+                (1, i16::MIN)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_torn_coefficients() -> miette::Result<()> {
+        let uut = GainOffset::<4>::try_new(initial())?;
+        let old = initial()[1];
+        let new = coeffs(-5, -300, 2);
+        // Stream samples on channel 1, while the gain, offset and shift
+        // are written in separate clocks, and then committed.
+        let mut input = vec![];
+        for n in 0..60 {
+            let mut op = sample(1, 1000 + n);
+            match n {
+                20 => op.write = write(1, Field::Gain(new.gain)).write,
+                25 => op.write = write(1, Field::Offset(new.offset)).write,
+                30 => op.write = write(1, Field::Shift(new.shift)).write,
+                40 => op.commit = true,
+                _ => {}
+            }
+            input.push(op);
+        }
+        input.extend([idle(), idle()]);
+        let output = run(&uut, input)?;
+        assert_eq!(output.len(), 60);
+        let mut switched = None;
+        for (n, (_, y)) in output.iter().enumerate() {
+            let x = 1000 + n as i16;
+            if *y == model(x, &new) {
+                switched.get_or_insert(n);
+            } else {
+                assert_eq!(*y, model(x, &old), "Torn result for sample {n}");
+                assert!(switched.is_none(), "Old coefficients used after the switch");
+            }
+        }
+        // The new coefficients take effect for the samples after the commit
+        assert_eq!(switched, Some(41));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bypass_write() -> miette::Result<()> {
+        let uut = GainOffset::<4>::try_new(initial())?;
+        let input = vec![
+            sample(3, 1234),
+            write(3, Field::Bypass(false)),
+            In {
+                commit: true,
+                ..idle()
+            },
+            idle(),
+            sample(3, 1234),
+            idle(),
+            idle(),
+        ];
+        let output = run(&uut, input)?;
+        assert_eq!(output, [(3, 1234), (3, model(1234, &coeffs(7, 7, 7)))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gain_offset_hdl() -> miette::Result<()> {
+        let uut = GainOffset::<4>::try_new(initial())?;
+        let mut input = (0..40)
+            .map(|n| sample((n % 5) as u8, (n as i16 - 20) * 1500))
+            .collect::<Vec<_>>();
+        input[10].write = Some(CoeffWrite {
+            channel: b8(0),
+            field: Field::Gain(s16(-3)),
+        });
+        input[15].commit = true;
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
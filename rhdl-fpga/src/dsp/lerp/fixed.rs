@@ -133,6 +133,7 @@ mod tests {
     use rhdl::core::sim::testbench::kernel::test_kernel_vm_and_verilog_synchronous;
 
     use super::*;
+    use crate::dsp::model::{self, verify_dsp};
 
     fn lerp_i32(a: i32, b: i32, f: i32, shift: u8) -> i32 {
         ((a << shift) + (b - a) * f) >> shift
@@ -206,4 +207,34 @@ mod tests {
         )?;
         Ok(())
     }
+
+    #[kernel]
+    pub fn wrap_lerp_signed(_cr: ClockReset, i: (s16, s16, b8)) -> s16 {
+        lerp_signed::<U16, U8>(i.0, i.1, i.2)
+    }
+
+    #[test]
+    fn test_signed_lerp_bit_exact_and_float() -> miette::Result<()> {
+        let uut: Func<(s16, s16, b8), s16> = Func::try_new::<wrap_lerp_signed>()?;
+        let stimulus = [(-32768, 32767), (1000, -1000), (-5, 3), (12345, 12345)]
+            .into_iter()
+            .flat_map(|(a, b)| (0..256).step_by(7).map(move |f| (s16(a), s16(b), b8(f))))
+            .collect::<Vec<_>>();
+        // The core matches the model exactly
+        let checked = verify_dsp(
+            &uut,
+            |&(a, b, f): &(s16, s16, b8)| s16(model::lerp_signed(a.raw(), b.raw(), f.raw(), 16, 8)),
+            stimulus.clone(),
+        )?;
+        assert_eq!(checked, stimulus.len());
+        // And is within an LSB of the exact interpolation
+        for (a, b, f) in stimulus {
+            let (a, b, f) = (a.raw() as f64, b.raw() as f64, f.raw() as f64);
+            let exact = a + (b - a) * f / 256.0;
+            let y = lerp_signed::<U16, U8>(s16(a as i128), s16(b as i128), b8(f as u128));
+            let error = y.raw() as f64 - exact;
+            assert!(error <= 0.0 && error > -1.0, "{a} {b} {f}");
+        }
+        Ok(())
+    }
 }
@@ -1,2 +1,6 @@
 //! DSP Related Cores
+pub mod gain_offset;
 pub mod lerp;
+pub mod model;
+pub mod sample_bridge;
+pub mod sample_servo;
@@ -0,0 +1,359 @@
+//! Bit accurate models of the DSP cores
+//!
+//!# Purpose
+//!
+//! Checking a DSP core against a floating point calculation needs a
+//! tolerance, and a tolerance loose enough to cover the quantization of
+//! the core will also hide an off-by-one in its rounding, or a missing
+//! saturation.  This module holds software models of the DSP cores that
+//! use the same quantization, rounding and saturation as the hardware, so
+//! that the output of a core can be compared against its model exactly.
+//!
+//! The models are:
+//!
+//! - [correct] and [GainOffsetModel] for the [GainOffset] core.
+//! - [lerp_unsigned] and [lerp_signed] for the functions in
+//!   [fixed](crate::dsp::lerp::fixed), which are also used by the
+//!   interpolating [sample_bridge](crate::dsp::sample_bridge).
+//!
+//! Each model is checked against a double precision calculation, and the
+//! error of the model (and hence of the core) is documented on the model.
+//!
+//! A core is checked against a model with [verify_dsp], which runs the
+//! core on a stimulus, and compares its output on every clock with the
+//! output of a [DspModel].  A model of a core with no state (such as a
+//! kernel wrapped in a [Func]) can be given as a closure.
+//!
+//!# Example
+//!
+//! Checking the unsigned `lerp` against its model
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::dsp::{lerp::fixed::lerp_unsigned, model};
+//!
+//! #[kernel]
+//! pub fn wrap(_cr: ClockReset, i: (b8, b8, b4)) -> b8 {
+//!     lerp_unsigned::<U8, U4>(i.0, i.1, i.2)
+//! }
+//!
+//! let uut: Func<(b8, b8, b4), b8> = Func::try_new::<wrap>().unwrap();
+//! let stimulus = (0..16).map(|f| (b8(10), b8(250), b4(f)));
+//! let checked = model::verify_dsp(
+//!     &uut,
+//!     |&(a, b, f): &(b8, b8, b4)| b8(model::lerp_unsigned(a.raw(), b.raw(), f.raw(), 8, 4)),
+//!     stimulus,
+//! )
+//! .unwrap();
+//! assert_eq!(checked, 16);
+//!```
+use miette::Diagnostic;
+use rhdl::prelude::*;
+use thiserror::Error;
+
+use crate::dsp::gain_offset::{ChannelSample, CoeffWrite, Coeffs, Field, In, Out};
+
+/// A cycle accurate model of a core with inputs `I`
+/// and outputs `O`
+pub trait DspModel<I, O> {
+    /// Advance the model by one clock, and return the output of the
+    /// core during that clock, given the input presented to it.
+    fn step(&mut self, input: &I) -> O;
+}
+
+impl<I, O, F: FnMut(&I) -> O> DspModel<I, O> for F {
+    fn step(&mut self, input: &I) -> O {
+        self(input)
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+/// Errors reported by [verify_dsp]
+pub enum ModelError {
+    /// The core and the model disagree
+    #[error(
+        "Mismatch on clock {clock}: the core produced {actual}, but the model expected {expected}"
+    )]
+    Mismatch {
+        /// The clock (after reset) of the first mismatch
+        clock: usize,
+        /// The output of the model
+        expected: String,
+        /// The output of the core
+        actual: String,
+    },
+    /// The simulation failed
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Simulation(#[from] RHDLError),
+}
+
+/// Run `uut` on the `stimulus` (one input per clock, after a single
+/// reset clock), and check that its output on every clock is exactly
+/// that of the `model`.  Returns the number of clocks checked, or the
+/// first mismatch.
+pub fn verify_dsp<T, M>(
+    uut: &T,
+    mut model: M,
+    stimulus: impl IntoIterator<Item = T::I>,
+) -> Result<usize, ModelError>
+where
+    T: Synchronous,
+    T::O: std::fmt::Debug,
+    M: DspModel<T::I, T::O>,
+{
+    let stimulus = stimulus.into_iter().collect::<Vec<_>>();
+    let outputs = uut
+        .run(stimulus.iter().copied().with_reset(1).clock_pos_edge(100))?
+        .synchronous_sample()
+        .skip(1)
+        .map(|t| t.value.2);
+    let mut checked = 0;
+    for (clock, (input, actual)) in stimulus.iter().zip(outputs).enumerate() {
+        let expected = model.step(input);
+        if actual != expected {
+            return Err(ModelError::Mismatch {
+                clock,
+                expected: format!("{expected:?}"),
+                actual: format!("{actual:?}"),
+            });
+        }
+        checked += 1;
+    }
+    Ok(checked)
+}
+
+/// The correction applied by the [GainOffset] core to a sample `x`
+///
+/// The product is shifted right arithmetically, so the result is
+/// rounded towards negative infinity.  Before saturation, the result is
+/// within `(-1, 0]` of the exact value `(x - offset) * gain / 2^shift`.
+/// After saturation, it is within the same bound of the exact value
+/// clamped to the range of an `i16`.
+pub fn correct(x: i16, c: &Coeffs) -> i16 {
+    if c.bypass {
+        return x;
+    }
+    // The product fits in 33 bits, so nothing is lost in an i64
+    let y = ((x as i64 - c.offset.raw() as i64) * c.gain.raw() as i64) >> c.shift.raw();
+    y.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+}
+
+/// The unsigned linear interpolation of [lerp_unsigned](crate::dsp::lerp::fixed::lerp_unsigned),
+/// for `n` bit values and an `m` bit factor
+///
+/// The result is rounded towards negative infinity, so it is within
+/// `(-1, 0]` of the exact value `lower + (upper - lower) * factor / 2^m`.
+pub fn lerp_unsigned(lower: u128, upper: u128, factor: u128, n: usize, m: usize) -> u128 {
+    let y = ((lower as i128) << m) + (upper as i128 - lower as i128) * factor as i128;
+    // The result always fits in N bits, so the truncation is free
+    ((y >> m) as u128) & mask(n)
+}
+
+/// The signed linear interpolation of [lerp_signed](crate::dsp::lerp::fixed::lerp_signed),
+/// for `n` bit values and an `m` bit factor
+///
+/// The result is rounded towards negative infinity, so it is within
+/// `(-1, 0]` of the exact value `lower + (upper - lower) * factor / 2^m`.
+pub fn lerp_signed(lower: i128, upper: i128, factor: u128, n: usize, m: usize) -> i128 {
+    let y = ((lower << m) + (upper - lower) * factor as i128) >> m;
+    // Truncate to N bits, and sign extend
+    let shift = 128 - n;
+    (y << shift) >> shift
+}
+
+fn mask(n: usize) -> u128 {
+    if n >= 128 {
+        !0
+    } else {
+        (1 << n) - 1
+    }
+}
+
+/// A cycle accurate model of the [GainOffset] core, including
+/// the coefficient writes, the commit, and the pipeline latency.
+pub struct GainOffsetModel<const CH: usize> {
+    shadow: [Coeffs; CH],
+    live: [Coeffs; CH],
+    dirty: bool,
+    stage: Option<(ChannelSample, Coeffs)>,
+    out: Option<ChannelSample>,
+}
+
+impl<const CH: usize> GainOffsetModel<CH> {
+    /// A model of a core constructed with the `initial` coefficients
+    pub fn new(initial: [Coeffs; CH]) -> Self {
+        Self {
+            shadow: initial,
+            live: initial,
+            dirty: false,
+            stage: None,
+            out: None,
+        }
+    }
+    fn write(&mut self, write: &CoeffWrite) {
+        let Some(c) = self.shadow.get_mut(write.channel.raw() as usize) else {
+            return;
+        };
+        match write.field {
+            Field::Gain(gain) => c.gain = gain,
+            Field::Offset(offset) => c.offset = offset,
+            Field::Shift(shift) => c.shift = shift,
+            Field::Bypass(bypass) => c.bypass = bypass,
+        }
+    }
+}
+
+impl<const CH: usize> DspModel<In, Out> for GainOffsetModel<CH> {
+    fn step(&mut self, input: &In) -> Out {
+        let o = Out {
+            data: self.out,
+            pending: self.dirty,
+        };
+        self.out = self.stage.map(|(s, c)| ChannelSample {
+            channel: s.channel,
+            sample: s16(correct(s.sample.raw() as i16, &c) as i128),
+        });
+        // Uncalibrated channels pass through
+        self.stage = input.data.map(|s| {
+            let c = self.live.get(s.channel.raw() as usize).copied();
+            (
+                s,
+                c.unwrap_or(Coeffs {
+                    bypass: true,
+                    ..Default::default()
+                }),
+            )
+        });
+        if let Some(write) = &input.write {
+            self.write(write);
+            self.dirty = true;
+        }
+        if input.commit {
+            self.live = self.shadow;
+            self.dirty = false;
+        }
+        o
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::dsp::gain_offset::GainOffset;
+
+    fn coeffs(gain: i16, offset: i16, shift: u8) -> Coeffs {
+        Coeffs {
+            gain: s16(gain as i128),
+            offset: s16(offset as i128),
+            shift: b5(shift as u128),
+            bypass: false,
+        }
+    }
+
+    // The error bound is (-1, 0] in units of the output LSB
+    fn assert_floor_of(model: f64, exact: f64, context: &str) {
+        let error = model - exact;
+        assert!(
+            error <= 0.0 && error > -1.0,
+            "{context}: {model} vs {exact}"
+        );
+    }
+
+    #[test]
+    fn test_correct_against_float() {
+        let mut rng = StdRng::seed_from_u64(241);
+        for _ in 0..20000 {
+            let c = coeffs(rng.random(), rng.random(), rng.random_range(0..32));
+            let x = rng.random::<i16>();
+            let exact = (x as f64 - c.offset.raw() as f64) * c.gain.raw() as f64
+                / 2.0_f64.powi(c.shift.raw() as i32);
+            let exact = exact.clamp(i16::MIN as f64, i16::MAX as f64);
+            assert_floor_of(correct(x, &c) as f64, exact, &format!("{x} {c:?}"));
+        }
+    }
+
+    #[test]
+    fn test_correct_bypass() {
+        let c = Coeffs {
+            bypass: true,
+            ..coeffs(-7, 100, 3)
+        };
+        assert_eq!(correct(1234, &c), 1234);
+    }
+
+    #[test]
+    fn test_lerp_against_float() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for (n, m) in [(4, 5), (8, 4), (16, 8), (24, 1), (32, 16)] {
+            for _ in 0..5000 {
+                let a = rng.random::<u64>() as u128 & mask(n);
+                let b = rng.random::<u64>() as u128 & mask(n);
+                let f = rng.random::<u64>() as u128 & mask(m);
+                let exact = a as f64 + (b as f64 - a as f64) * f as f64 / 2.0_f64.powi(m as i32);
+                let y = lerp_unsigned(a, b, f, n, m);
+                assert_floor_of(y as f64, exact, &format!("unsigned {n} {m} {a} {b} {f}"));
+                // Reinterpret the values as signed
+                let sa = ((a << (128 - n)) as i128) >> (128 - n);
+                let sb = ((b << (128 - n)) as i128) >> (128 - n);
+                let exact = sa as f64 + (sb as f64 - sa as f64) * f as f64 / 2.0_f64.powi(m as i32);
+                let y = lerp_signed(sa, sb, f, n, m);
+                assert_floor_of(y as f64, exact, &format!("signed {n} {m} {sa} {sb} {f}"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lerp_matches_kernels() {
+        use crate::dsp::lerp::fixed;
+        for a in 0..16 {
+            for b in 0..16 {
+                for f in 0..32 {
+                    assert_eq!(
+                        fixed::lerp_unsigned::<U4, U5>(b4(a), b4(b), b5(f)).raw(),
+                        lerp_unsigned(a, b, f, 4, 5)
+                    );
+                    let (sa, sb) = (a as i128 - 8, b as i128 - 8);
+                    assert_eq!(
+                        fixed::lerp_signed::<U4, U5>(s4(sa), s4(sb), b5(f)).raw(),
+                        lerp_signed(sa, sb, f, 4, 5)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_reports_mismatch() -> miette::Result<()> {
+        let uut = GainOffset::<1>::try_new([coeffs(3, 0, 1)])?;
+        // A model that applies the gain of 3/2 with a division, which
+        // truncates towards zero instead of rounding down
+        let mut unscaled = GainOffsetModel::new([coeffs(3, 0, 0)]);
+        let stimulus = [-3, 5, -1, 0, 0]
+            .into_iter()
+            .map(|x| In {
+                data: Some(ChannelSample {
+                    channel: b8(0),
+                    sample: s16(x),
+                }),
+                write: None,
+                commit: false,
+            })
+            .collect::<Vec<_>>();
+        let truncating = |i: &In| {
+            let mut o = unscaled.step(i);
+            if let Some(s) = &mut o.data {
+                s.sample = s16(s.sample.raw() / 2);
+            }
+            o
+        };
+        let Err(ModelError::Mismatch { clock, .. }) = verify_dsp(&uut, truncating, stimulus) else {
+            panic!("Expected a mismatch");
+        };
+        // -3 * 3 / 2 = -4.5, which rounds down to -5, not -4
+        assert_eq!(clock, 2);
+        Ok(())
+    }
+}
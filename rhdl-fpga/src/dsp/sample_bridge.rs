@@ -0,0 +1,360 @@
+//! Elastic sample stream bridge between unlocked clock domains
+//!
+//!# Purpose
+//!
+//! A stream of samples (audio from an I2S receiver, say) produced at a
+//! fixed rate in one clock domain often has to be consumed at the
+//! "same" rate in another domain, whose clock is close to, but not
+//! locked to, the first.  The two rates drift apart by a few hundred
+//! parts per million, so a plain FIFO between them will eventually
+//! overflow or run dry, and drop or duplicate samples at random times.
+//!
+//! The [AsyncSampleBridge] carries the samples across an [AsyncFIFO],
+//! and reads them out on a strobe from the sink domain.  A servo on the
+//! read side watches the fill level of the FIFO, and when the drift has
+//! pushed it close to either end, makes a single adjustment:
+//!
+//! - if the FIFO is nearly full (the source is faster), one sample is
+//!   dropped,
+//! - if the FIFO is nearly empty (the sink is faster), one sample is
+//!   repeated.
+//!
+//! Each adjustment is counted in the `drops` or `repeats` output.  After
+//! an adjustment, the servo waits for a number of strobes (equal to the
+//! capacity of the FIFO) before making another, so that the fill level
+//! (and the flags it is judged from) can settle.
+//!
+//! In [Mode::Interp], the adjustment is hidden by linear interpolation.
+//! A repeated sample is replaced by the midpoint of its neighbors, and a
+//! dropped sample and its successor are replaced by their midpoint, so
+//! the output never jumps by more than the input does.  The midpoint
+//! is rounded toward negative infinity.
+//!
+//! On reset, the bridge waits until the FIFO is half full (by counting
+//! strobes after the first sample arrives) before it starts to emit
+//! samples, so that it starts in the middle of its elastic range.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +--+AsyncSampleBridge+-----------+
+  ?S  |              +                 | ?S
++---->| data     W   |    R       data +---->
+      |       domain<+>domain          | b16
+<-----+ overflow     |         repeats +---->
+      |              +                 | b16
++---->| cr_w                     drops +---->
+      |                                |
++---->| cr_r                    strobe |<---+
+      +--------------------------------+
+")]
+//!
+//! where `S` is a `SignedBits<B>` sample.
+//!
+//!# Timing
+//!
+//! Each strobe (once the bridge has started) produces exactly one
+//! output sample, two clocks after the strobe.  Strobes must be at
+//! least two clocks apart.  The source must not write samples faster
+//! than the FIFO can absorb, which is not a concern for audio rates.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::dsp::sample_bridge::{AsyncSampleBridge, Mode};
+//!
+//! // A bridge for 16 bit samples from Red to Blue, with a 15 entry FIFO
+//! let uut = AsyncSampleBridge::<U16, Red, Blue, 4>::new(Mode::Interp);
+//! let _hdl = uut.hdl("top").unwrap();
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    cdc::synchronizer::Sync1Bit,
+    dsp::sample_servo::{Servo, ServoIn},
+    fifo::asynchronous::AsyncFIFO,
+};
+
+/// How the bridge hides its adjustments
+#[derive(PartialEq, Debug, Digital, Default)]
+pub enum Mode {
+    /// Drop or repeat whole samples
+    #[default]
+    Simple,
+    /// Replace the samples around the adjustment with midpoints
+    Interp,
+}
+
+/// The [AsyncSampleBridge] core.  `B` is the width of the (signed)
+/// samples, which are written in domain `W` and read in domain `R`.
+/// The FIFO has `N` address bits, and holds `2^N - 1` samples.
+#[derive(Clone, Circuit, CircuitDQ)]
+pub struct AsyncSampleBridge<B: BitWidth, W: Domain, R: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    fifo: AsyncFIFO<SignedBits<B>, W, R, N>,
+    high: Sync1Bit<W, R>,
+    servo: Adapter<Servo<B>, R>,
+}
+
+impl<B: BitWidth, W: Domain, R: Domain, const N: usize> AsyncSampleBridge<B, W, R, N>
+where
+    Const<N>: BitWidth,
+{
+    /// Create a bridge that hides adjustments according to `mode`
+    pub fn new(mode: Mode) -> Self {
+        assert!(
+            (3..=8).contains(&N),
+            "The FIFO must have between 3 and 8 address bits"
+        );
+        let capacity = (1_usize << N) - 1;
+        Self {
+            fifo: AsyncFIFO::default(),
+            high: Sync1Bit::default(),
+            servo: Adapter::new(Servo::new(mode, (capacity / 2) as u8, capacity as u8)),
+        }
+    }
+}
+
+impl<B: BitWidth, W: Domain, R: Domain, const N: usize> Default for AsyncSampleBridge<B, W, R, N>
+where
+    Const<N>: BitWidth,
+{
+    fn default() -> Self {
+        Self::new(Mode::Simple)
+    }
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Inputs to the [AsyncSampleBridge]
+pub struct In<B: BitWidth, W: Domain, R: Domain> {
+    /// The samples from the source, in the W domain
+    pub data: Signal<Option<SignedBits<B>>, W>,
+    /// The sink wants a sample, in the R domain
+    pub strobe: Signal<bool, R>,
+    /// The clock and reset for the W domain
+    pub cr_w: Signal<ClockReset, W>,
+    /// The clock and reset for the R domain
+    pub cr_r: Signal<ClockReset, R>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Outputs from the [AsyncSampleBridge]
+pub struct Out<B: BitWidth, W: Domain, R: Domain> {
+    /// The samples for the sink, in the R domain
+    pub data: Signal<Option<SignedBits<B>>, R>,
+    /// The number of samples repeated (or interpolated) to make up
+    /// for a slow source
+    pub repeats: Signal<b16, R>,
+    /// The number of samples dropped (or interpolated) to make up
+    /// for a fast source
+    pub drops: Signal<b16, R>,
+    /// The FIFO overflowed (the servo could not keep up)
+    pub overflow: Signal<bool, W>,
+}
+
+impl<B: BitWidth, W: Domain, R: Domain, const N: usize> CircuitIO for AsyncSampleBridge<B, W, R, N>
+where
+    Const<N>: BitWidth,
+{
+    type I = In<B, W, R>;
+    type O = Out<B, W, R>;
+    type Kernel = sample_bridge_kernel<B, W, R, N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn sample_bridge_kernel<B: BitWidth, W: Domain, R: Domain, const N: usize>(
+    i: In<B, W, R>,
+    q: Q<B, W, R, N>,
+) -> (Out<B, W, R>, D<B, W, R, N>)
+where
+    Const<N>: BitWidth,
+{
+    let mut d = D::<B, W, R, N>::dont_care();
+    d.fifo.data = i.data;
+    d.fifo.cr_w = i.cr_w;
+    d.fifo.cr_r = i.cr_r;
+    d.fifo.next = signal(q.servo.val().next);
+    // The nearly full flag is crossed into the read domain
+    d.high.data = q.fifo.almost_full;
+    d.high.cr = i.cr_r;
+    d.servo.clock_reset = i.cr_r;
+    d.servo.input = signal(ServoIn::<B> {
+        head: q.fifo.data.val(),
+        low: q.fifo.almost_empty.val(),
+        high: q.high.val(),
+        strobe: i.strobe.val(),
+    });
+    let o = Out::<B, W, R> {
+        data: signal(q.servo.val().data),
+        repeats: signal(q.servo.val().repeats),
+        drops: signal(q.servo.val().drops),
+        overflow: q.fifo.overflow,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 7 entry FIFO, so that the drift reaches the thresholds quickly
+    type Bridge = AsyncSampleBridge<U24, Red, Blue, 3>;
+
+    // The input is a ramp with this step, so that midpoints are exact
+    const STEP: i64 = 16;
+
+    // The sink strobes (and the source writes) every this many clocks
+    const EVERY: usize = 3;
+
+    // The number of strobes to run for.  At 500 ppm, the clocks
+    // drift apart by 12 samples over the run
+    const COUNT: usize = 24_000;
+
+    struct Results {
+        samples: Vec<i64>,
+        repeats: u64,
+        drops: u64,
+        overflow: bool,
+    }
+
+    // Run the bridge with a ramp from a source clock of period `w`,
+    // and a sink clock of period `r`
+    fn run(uut: &Bridge, w: u64, r: u64) -> miette::Result<Results> {
+        // A ramp sample (or a strobe) every few clocks
+        let source = (0..(COUNT + 100) * EVERY)
+            .map(|n| (n % EVERY == 0).then(|| signed((n / EVERY) as i128 * STEP as i128)))
+            .with_reset(1)
+            .clock_pos_edge(w);
+        let sink = (0..COUNT * EVERY)
+            .map(|n| n % EVERY == 0)
+            .with_reset(1)
+            .clock_pos_edge(r);
+        let input = source.merge(sink, |w, r| In {
+            data: signal(w.1),
+            strobe: signal(r.1),
+            cr_w: signal(w.0),
+            cr_r: signal(r.0),
+        });
+        let mut samples = vec![];
+        let mut last = None;
+        let mut prev_clock = false;
+        let mut overflow = false;
+        for t in uut.run(input)? {
+            let clock = t.value.0.cr_r.val().clock.raw();
+            // Sample the outputs just before each rising edge of the read clock
+            if clock && !prev_clock {
+                if let Some(o) = last {
+                    let o: Out<U24, Red, Blue> = o;
+                    if let Some(x) = o.data.val() {
+                        samples.push(x.raw() as i64);
+                    }
+                }
+            }
+            prev_clock = clock;
+            overflow |= t.value.1.overflow.val();
+            last = Some(t.value.1);
+        }
+        let last = last.unwrap();
+        Ok(Results {
+            samples,
+            repeats: last.repeats.val().raw() as u64,
+            drops: last.drops.val().raw() as u64,
+            overflow,
+        })
+    }
+
+    // Check the number of samples (one for every strobe after the
+    // prefill of 3), and that the adjustments track the drift
+    fn check_counts(results: &Results, w: u64, r: u64) {
+        assert!(!results.overflow);
+        let strobes = COUNT - 3;
+        let emitted = results.samples.len();
+        assert!(
+            (strobes - 2..=strobes).contains(&emitted),
+            "{emitted} samples for {strobes} strobes"
+        );
+        // The drift (in samples) over the run
+        let drift = (COUNT as f64 * (w as f64 - r as f64).abs() / w as f64) as u64;
+        let adjustments = results.repeats + results.drops;
+        assert!(
+            adjustments + 4 >= drift && adjustments <= drift + 1,
+            "{adjustments} adjustments for a drift of {drift} samples"
+        );
+    }
+
+    fn steps(results: &Results) -> Vec<i64> {
+        results.samples.windows(2).map(|w| w[1] - w[0]).collect()
+    }
+
+    #[test]
+    fn test_fast_source_drops_samples() -> miette::Result<()> {
+        let uut = Bridge::new(Mode::Simple);
+        // The sink clock is 500 ppm slow
+        let results = run(&uut, 4000, 4002)?;
+        check_counts(&results, 4000, 4002);
+        assert_eq!(results.repeats, 0);
+        assert!(results.drops >= 4);
+        // The output is the ramp, with a double step at each drop
+        let steps = steps(&results);
+        assert!(steps.iter().all(|s| *s == STEP || *s == 2 * STEP));
+        assert_eq!(
+            steps.iter().filter(|s| **s == 2 * STEP).count() as u64,
+            results.drops
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_slow_source_repeats_samples() -> miette::Result<()> {
+        let uut = Bridge::new(Mode::Simple);
+        // The source clock is 500 ppm slow
+        let results = run(&uut, 4002, 4000)?;
+        check_counts(&results, 4002, 4000);
+        assert_eq!(results.drops, 0);
+        assert!(results.repeats >= 4);
+        // The output is the ramp, with a flat step at each repeat
+        let steps = steps(&results);
+        assert!(steps.iter().all(|s| *s == 0 || *s == STEP));
+        assert_eq!(
+            steps.iter().filter(|s| **s == 0).count() as u64,
+            results.repeats
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_interpolation_stays_in_bounds() -> miette::Result<()> {
+        let uut = Bridge::new(Mode::Interp);
+        for (w, r) in [(4000, 4002), (4002, 4000)] {
+            let results = run(&uut, w, r)?;
+            check_counts(&results, w, r);
+            let adjustments = results.repeats + results.drops;
+            assert!(adjustments >= 4);
+            // An interpolated sample lies strictly between its neighbors,
+            // so the ramp never stalls or jumps by a whole step.  A repeat
+            // is two half steps, and a drop is two steps of one and a half.
+            let steps = steps(&results);
+            let half = STEP / 2;
+            assert!(steps.iter().all(|s| [half, STEP, STEP + half].contains(s)));
+            assert_eq!(
+                steps.iter().filter(|s| **s == half).count() as u64,
+                2 * results.repeats
+            );
+            assert_eq!(
+                steps.iter().filter(|s| **s == STEP + half).count() as u64,
+                2 * results.drops
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_bridge_hdl() -> miette::Result<()> {
+        let uut = Bridge::new(Mode::Interp);
+        let _ = uut.hdl("top")?;
+        Ok(())
+    }
+}
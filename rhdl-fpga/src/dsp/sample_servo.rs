@@ -0,0 +1,188 @@
+//! Read side servo of the [AsyncSampleBridge]
+//!
+//! The servo runs in the sink domain.  On each strobe it decides
+//! whether to pass the sample at the head of the FIFO, to repeat the
+//! last sample, or to drop one, based on the fill level flags of the
+//! FIFO.  The decision is carried out on the following clock, and the
+//! output sample is registered.  See [AsyncSampleBridge] for details.
+//!
+//! [AsyncSampleBridge]: crate::dsp::sample_bridge::AsyncSampleBridge
+use rhdl::prelude::*;
+
+use crate::{
+    core::{constant, dff},
+    dsp::{lerp::fixed::lerp_signed, sample_bridge::Mode},
+};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum Action {
+    #[default]
+    Idle,
+    Normal,
+    Repeat,
+    Drop,
+}
+
+/// The read side servo of the sample bridge.  It decides
+/// on each strobe whether to pass, repeat or drop a sample.
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+pub struct Servo<B: BitWidth> {
+    mode: constant::Constant<Mode>,
+    holdoff_strobes: constant::Constant<b8>,
+    started: dff::DFF<bool>,
+    // Counts down the prefill strobes, and then the holdoff strobes
+    count: dff::DFF<b8>,
+    action: dff::DFF<Action>,
+    held: dff::DFF<SignedBits<B>>,
+    last: dff::DFF<SignedBits<B>>,
+    out: dff::DFF<Option<SignedBits<B>>>,
+    repeats: dff::DFF<b16>,
+    drops: dff::DFF<b16>,
+}
+
+impl<B: BitWidth> Servo<B> {
+    /// Create a servo that starts after `prefill` strobes, and
+    /// waits `holdoff` strobes between adjustments
+    pub fn new(mode: Mode, prefill: u8, holdoff: u8) -> Self {
+        Self {
+            mode: constant::Constant::new(mode),
+            holdoff_strobes: constant::Constant::new(bits(holdoff as u128)),
+            started: dff::DFF::new(false),
+            count: dff::DFF::new(bits(prefill as u128)),
+            action: dff::DFF::new(Action::Idle),
+            held: dff::DFF::new(SignedBits::<B>::default()),
+            last: dff::DFF::new(SignedBits::<B>::default()),
+            out: dff::DFF::new(None),
+            repeats: dff::DFF::new(b16(0)),
+            drops: dff::DFF::new(b16(0)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [Servo]
+pub struct ServoIn<B: BitWidth> {
+    /// The sample at the head of the FIFO (if any)
+    pub head: Option<SignedBits<B>>,
+    /// The FIFO is nearly empty
+    pub low: bool,
+    /// The FIFO is nearly full (synchronized from the write side)
+    pub high: bool,
+    /// The sink wants a sample
+    pub strobe: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [Servo]
+pub struct ServoOut<B: BitWidth> {
+    /// Pop the head of the FIFO
+    pub next: bool,
+    /// The output sample
+    pub data: Option<SignedBits<B>>,
+    /// The number of repeated samples
+    pub repeats: b16,
+    /// The number of dropped samples
+    pub drops: b16,
+}
+
+impl<B: BitWidth> SynchronousIO for Servo<B> {
+    type I = ServoIn<B>;
+    type O = ServoOut<B>;
+    type Kernel = servo_kernel<B>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn servo_kernel<B: BitWidth>(_cr: ClockReset, i: ServoIn<B>, q: Q<B>) -> (ServoOut<B>, D<B>) {
+    let mut d = D::<B> {
+        mode: (),
+        holdoff_strobes: (),
+        started: q.started,
+        count: q.count,
+        action: Action::Idle,
+        held: q.held,
+        last: q.last,
+        out: None,
+        repeats: q.repeats,
+        drops: q.drops,
+    };
+    let interp = q.mode == Mode::Interp;
+    let mut next = false;
+    // Carry out the action decided on the previous strobe
+    match q.action {
+        Action::Idle => {}
+        Action::Normal => {
+            if let Some(x) = i.head {
+                d.out = Some(x);
+                d.last = x;
+                next = true;
+            } else {
+                // The FIFO ran dry, so all we can do is repeat
+                d.out = Some(q.last);
+                d.repeats = q.repeats + 1;
+            }
+        }
+        Action::Repeat => {
+            let mut y = q.last;
+            if interp {
+                if let Some(x) = i.head {
+                    y = lerp_signed::<B, U1>(q.last, x, b1(1));
+                }
+            }
+            d.out = Some(y);
+            d.last = y;
+        }
+        Action::Drop => {
+            let mut y = q.held;
+            if let Some(x) = i.head {
+                y = if interp {
+                    lerp_signed::<B, U1>(q.held, x, b1(1))
+                } else {
+                    x
+                };
+                next = true;
+            }
+            d.out = Some(y);
+            d.last = y;
+        }
+    }
+    // Decide what to do for this strobe
+    if i.strobe {
+        if !q.started {
+            if let Some(_x) = i.head {
+                d.count = q.count - 1;
+                if q.count == 1 {
+                    d.started = true;
+                }
+            }
+        } else {
+            let ready = q.count == 0;
+            if !ready {
+                d.count = q.count - 1;
+            }
+            d.action = Action::Normal;
+            if ready && i.high {
+                if let Some(x) = i.head {
+                    // Pop this sample now, and use the next one
+                    d.action = Action::Drop;
+                    d.held = x;
+                    d.drops = q.drops + 1;
+                    d.count = q.holdoff_strobes;
+                    next = true;
+                }
+            } else if ready && i.low {
+                d.action = Action::Repeat;
+                d.repeats = q.repeats + 1;
+                d.count = q.holdoff_strobes;
+            }
+        }
+    }
+    let o = ServoOut::<B> {
+        next,
+        data: q.out,
+        repeats: q.repeats,
+        drops: q.drops,
+    };
+    (o, d)
+}
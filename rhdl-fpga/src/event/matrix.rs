@@ -0,0 +1,323 @@
+//! Pulse Routing Matrix
+//!
+//! Testbenches and trigger logic often need to route events between
+//! cores, like starting a logic analyzer capture when a UART receives
+//! a particular byte, or starting a scheduler on a GPIO edge.  The
+//! [PulseMatrix] is a small crossbar that connects `SRC` event sources
+//! to `DST` destinations.  Each (destination, source) pair has a
+//! [Route], which controls:
+//!
+//! - whether the source is connected to the destination at all,
+//! - whether the source is treated as a level, and converted into a
+//!   single clock pulse on its rising edge, or used as is (in which
+//!   case every clock on which the source is high is an event),
+//! - a divider, so that only every `divide`-th event is passed on.
+//!   A `divide` of `0` or `1` passes every event.
+//!
+//! A destination pulses if any of the sources routed to it produces
+//! an event, so sources can fan out to several destinations, and
+//! several sources can be combined into one destination.  The outputs
+//! are registered, so each destination pulses one clock after the
+//! source event.
+//!
+//! The routes are written one at a time with the `write` input (for
+//! example from a CSR bus), and take effect on the next clock.  Changing
+//! the routes does not cause spurious pulses.  The edge detectors track
+//! the sources whether or not they are routed, so connecting a source
+//! that is already high does not produce an edge.  Writing a route
+//! also restarts its divider.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+           +-+PulseMatrix+-----+
+ [bool;SRC]|                   | [bool;DST]
+ +-------->| sources       out +----------->
+ ?Write    |                   |
+ +-------->| write             |
+           +-------------------+
+")]
+//!
+//!# Example
+//!
+//! Source 0 drives destinations 0 and 1, with the latter only
+//! seeing every 4th rising edge.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::event::matrix::{PulseMatrix, Route};
+//!
+//! let mut routes = [[Route::default(); 2]; 2];
+//! routes[0][0] = Route { enable: true, edge: false, divide: b8(0) };
+//! routes[1][0] = Route { enable: true, edge: true, divide: b8(4) };
+//! let uut = PulseMatrix::<2, 2>::new(routes);
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The connection from a source to a destination
+pub struct Route {
+    /// Connect the source to the destination
+    pub enable: bool,
+    /// Convert rising edges of the source into pulses
+    pub edge: bool,
+    /// Only pass every `divide`-th event
+    pub divide: b8,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// A write to the routing matrix
+pub struct Write {
+    /// The destination index
+    pub dst: b8,
+    /// The source index
+    pub src: b8,
+    /// The new route
+    pub route: Route,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [PulseMatrix]
+pub struct In<const SRC: usize> {
+    /// The event sources
+    pub sources: [bool; SRC],
+    /// Update one of the routes
+    pub write: Option<Write>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The pulse routing matrix.  Here `SRC` is the number of
+/// sources, and `DST` is the number of destinations (both
+/// at most 256).
+pub struct PulseMatrix<const SRC: usize, const DST: usize> {
+    routes: dff::DFF<[[Route; SRC]; DST]>,
+    counts: dff::DFF<[[b8; SRC]; DST]>,
+    prev: dff::DFF<[bool; SRC]>,
+    out: dff::DFF<[bool; DST]>,
+}
+
+impl<const SRC: usize, const DST: usize> PulseMatrix<SRC, DST> {
+    /// Create a [PulseMatrix] with the given routes, indexed
+    /// as `routes[dst][src]`.  The routes are restored on reset.
+    pub fn new(routes: [[Route; SRC]; DST]) -> Self {
+        assert!(
+            SRC <= 256 && DST <= 256,
+            "The pulse matrix supports at most 256 sources and destinations"
+        );
+        Self {
+            routes: dff::DFF::new(routes),
+            counts: dff::DFF::new([[b8(0); SRC]; DST]),
+            prev: dff::DFF::new([false; SRC]),
+            out: dff::DFF::new([false; DST]),
+        }
+    }
+}
+
+impl<const SRC: usize, const DST: usize> Default for PulseMatrix<SRC, DST> {
+    fn default() -> Self {
+        Self::new([[Route::default(); SRC]; DST])
+    }
+}
+
+impl<const SRC: usize, const DST: usize> SynchronousIO for PulseMatrix<SRC, DST> {
+    type I = In<SRC>;
+    type O = [bool; DST];
+    type Kernel = pulse_matrix_kernel<SRC, DST>;
+}
+
+#[kernel]
+#[doc(hidden)]
+#[allow(clippy::needless_range_loop)]
+pub fn pulse_matrix_kernel<const SRC: usize, const DST: usize>(
+    cr: ClockReset,
+    i: In<SRC>,
+    q: Q<SRC, DST>,
+) -> ([bool; DST], D<SRC, DST>) {
+    let mut d = D::<SRC, DST> {
+        routes: q.routes,
+        counts: q.counts,
+        prev: i.sources,
+        out: [false; DST],
+    };
+    for dst in 0..DST {
+        for src in 0..SRC {
+            let route = q.routes[dst][src];
+            let event = if route.edge {
+                i.sources[src] && !q.prev[src]
+            } else {
+                i.sources[src]
+            };
+            if route.enable && event {
+                let count = q.counts[dst][src] + 1;
+                if count >= route.divide {
+                    d.out[dst] = true;
+                    d.counts[dst][src] = bits(0);
+                } else {
+                    d.counts[dst][src] = count;
+                }
+            }
+        }
+    }
+    if let Some(write) = i.write {
+        d.routes[write.dst][write.src] = write.route;
+        d.counts[write.dst][write.src] = bits(0);
+    }
+    if cr.reset.any() {
+        d.counts = [[b8(0); SRC]; DST];
+        d.out = [false; DST];
+    }
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(edge: bool, divide: u128) -> Route {
+        Route {
+            enable: true,
+            edge,
+            divide: bits(divide),
+        }
+    }
+
+    fn idle() -> In<4> {
+        In {
+            sources: [false; 4],
+            write: None,
+        }
+    }
+
+    fn run(uut: &PulseMatrix<4, 3>, input: Vec<In<4>>) -> miette::Result<Vec<[bool; 3]>> {
+        // Skip the reset, and the register delay, so that the
+        // outputs line up with the inputs
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn pulses(output: &[[bool; 3]], dst: usize) -> Vec<usize> {
+        output
+            .iter()
+            .enumerate()
+            .filter_map(|(n, o)| o[dst].then_some(n))
+            .collect()
+    }
+
+    // Source 0 fans out to destinations 0 and 1, and destination 2
+    // combines sources 1, 2 and 3 (with 3 edge detected and divided)
+    fn test_routes() -> [[Route; 4]; 3] {
+        let mut routes = [[Route::default(); 4]; 3];
+        routes[0][0] = route(false, 0);
+        routes[1][0] = route(true, 1);
+        routes[2][1] = route(false, 0);
+        routes[2][2] = route(false, 0);
+        routes[2][3] = route(true, 3);
+        routes
+    }
+
+    #[test]
+    fn test_fan_out_and_fan_in() -> miette::Result<()> {
+        let uut = PulseMatrix::new(test_routes());
+        let mut input = vec![idle(); 40];
+        // A three clock pulse on source 0
+        for x in &mut input[2..5] {
+            x.sources[0] = true;
+        }
+        input[10].sources[1] = true;
+        input[12].sources[2] = true;
+        // Sources 1 and 2 together give a single pulse
+        input[14].sources[1] = true;
+        input[14].sources[2] = true;
+        // Six edges on source 3, each two clocks long
+        for n in 0..6 {
+            input[20 + 3 * n].sources[3] = true;
+            input[21 + 3 * n].sources[3] = true;
+        }
+        let output = run(&uut, input)?;
+        assert_eq!(pulses(&output, 0), [2, 3, 4]);
+        assert_eq!(pulses(&output, 1), [2]);
+        assert_eq!(pulses(&output, 2), [10, 12, 14, 26, 35]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reprogram_without_glitches() -> miette::Result<()> {
+        let uut = PulseMatrix::new(test_routes());
+        let mut input = vec![idle(); 60];
+        // Source 1 is held high, and routed to destination 0
+        // part way through.  As an edge route, it must not fire.
+        for x in &mut input[5..30] {
+            x.sources[1] = true;
+        }
+        input[10].write = Some(Write {
+            dst: bits(0),
+            src: bits(1),
+            route: route(true, 0),
+        });
+        // Source 3 feeds a divide by 3 route to destination 2.  After
+        // two edges, the route is rewritten, which restarts the divider.
+        for n in [32, 34, 40, 42, 44] {
+            input[n].sources[3] = true;
+        }
+        input[36].write = Some(Write {
+            dst: bits(2),
+            src: bits(3),
+            route: route(true, 3),
+        });
+        // Destination 0 is disconnected from source 0 while it is high
+        for x in &mut input[48..56] {
+            x.sources[0] = true;
+        }
+        input[51].write = Some(Write {
+            dst: bits(0),
+            src: bits(0),
+            route: Route::default(),
+        });
+        let output = run(&uut, input)?;
+        assert_eq!(pulses(&output, 0), [48, 49, 50, 51]);
+        assert_eq!(pulses(&output, 1), [48]);
+        // Source 1 is also routed to destination 2 as a level
+        let mut expected = (5..30).collect::<Vec<_>>();
+        expected.push(44);
+        assert_eq!(pulses(&output, 2), expected);
+        // The source 1 edge after the write does fire
+        let mut input = vec![idle(); 20];
+        input[3].write = Some(Write {
+            dst: bits(0),
+            src: bits(1),
+            route: route(true, 0),
+        });
+        input[8].sources[1] = true;
+        let output = run(&uut, input)?;
+        assert_eq!(pulses(&output, 0), [8]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pulse_matrix_hdl() -> miette::Result<()> {
+        let uut = PulseMatrix::new(test_routes());
+        let mut input = vec![idle(); 60];
+        for n in 0..20 {
+            input[2 * n + 1].sources[n % 4] = true;
+            input[2 * n + 2].sources[(n + 1) % 4] = true;
+        }
+        input[15].write = Some(Write {
+            dst: bits(1),
+            src: bits(2),
+            route: route(true, 2),
+        });
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+//! Event routing cores
+pub mod matrix;
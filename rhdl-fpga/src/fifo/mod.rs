@@ -1,6 +1,8 @@
 #![warn(missing_docs)]
 //! Various FIFO related cores
 pub mod asynchronous;
+pub mod primitive;
+pub mod priority;
 #[doc(hidden)]
 pub mod read_logic;
 pub mod synchronous;
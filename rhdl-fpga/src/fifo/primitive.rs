@@ -0,0 +1,340 @@
+//! Vendor FIFO Primitive
+//!
+//! FPGA vendors supply hard FIFO primitives (and FIFO IP) as Verilog
+//! modules, which RHDL cannot simulate.  This module shows how to use
+//! such a primitive through the black box model registry.  The
+//! [FIFO_SYNC_PRIM](VERILOG) module is written in the style of a vendor
+//! primitive, with upper case port names, a `DEPTH_LOG2` parameter,
+//! and a registered read port.  The [FifoPrimitiveModel] is a Rust
+//! model of the same behavior, which is used in simulation.
+//!
+//! - A write (`WREN`) is ignored while the FIFO is `FULL`.
+//! - A read (`RDEN`) is ignored while the FIFO is `EMPTY`.  Otherwise,
+//!   the word at the head of the FIFO appears on `DO` on the next clock,
+//!   and stays there until the next read.
+//! - The reset is synchronous, and empties the FIFO.
+//!
+//! The primitive holds 16 bytes.  Use [fifo_primitive] to register the
+//! primitive and create a circuit that instantiates it.  The model is
+//! checked against the Verilog with `verify_black_box_model`.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+FIFO_SYNC_PRIM+---+
+ bool |                    | b8
++---->| write         data +---->
+ b8   |                    | bool
++---->| data          full +---->
+ bool |                    | bool
++---->| read         empty +---->
+      +--------------------+
+")]
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::fifo::primitive::{fifo_primitive, In};
+//!
+//! let uut = fifo_primitive().unwrap();
+//! let write = |data| In { write: true, data: b8(data), read: false };
+//! let read = In { write: false, data: b8(0), read: true };
+//! let output = uut
+//!     .run([write(3), write(4), read, read].into_iter().with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2.data)
+//!     .last()
+//!     .unwrap();
+//! assert_eq!(output, b8(3));
+//! ```
+use rhdl::prelude::*;
+
+/// The name the primitive is registered under
+pub const NAME: &str = "fifo_sync_prim_8x16";
+
+/// The number of words held by the primitive
+pub const DEPTH: usize = 16;
+
+/// The Verilog source of the primitive
+pub const VERILOG: &str = r#"
+module FIFO_SYNC_PRIM #(parameter WIDTH = 8, parameter DEPTH_LOG2 = 4) (
+    input wire CLK,
+    input wire RST,
+    input wire WREN,
+    input wire [WIDTH-1:0] DI,
+    input wire RDEN,
+    output reg [WIDTH-1:0] DO,
+    output wire FULL,
+    output wire EMPTY
+);
+    reg [WIDTH-1:0] mem [0:(1 << DEPTH_LOG2) - 1];
+    reg [DEPTH_LOG2-1:0] wptr;
+    reg [DEPTH_LOG2-1:0] rptr;
+    reg [DEPTH_LOG2:0] count;
+    wire do_write;
+    wire do_read;
+    initial begin
+        wptr = 0;
+        rptr = 0;
+        count = 0;
+        DO = 0;
+    end
+    assign FULL = count == (1 << DEPTH_LOG2);
+    assign EMPTY = count == 0;
+    assign do_write = WREN && !FULL;
+    assign do_read = RDEN && !EMPTY;
+    always @(posedge CLK) begin
+        if (RST) begin
+            wptr <= 0;
+            rptr <= 0;
+            count <= 0;
+            DO <= 0;
+        end else begin
+            if (do_write) begin
+                mem[wptr] <= DI;
+                wptr <= wptr + 1;
+            end
+            if (do_read) begin
+                DO <= mem[rptr];
+                rptr <= rptr + 1;
+            end
+            count <= count + do_write - do_read;
+        end
+    end
+endmodule
+"#;
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the primitive
+pub struct In {
+    /// Write `data` into the FIFO
+    pub write: bool,
+    /// The data to write
+    pub data: b8,
+    /// Read the word at the head of the FIFO
+    pub read: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the primitive
+pub struct Out {
+    /// The last word read from the FIFO
+    pub data: b8,
+    /// Set when the FIFO holds [DEPTH] words
+    pub full: bool,
+    /// Set when the FIFO holds no words
+    pub empty: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+/// The behavioral model of the primitive
+pub struct FifoPrimitiveModel {
+    mem: [u8; DEPTH],
+    write_ptr: usize,
+    read_ptr: usize,
+    count: usize,
+    data: u8,
+}
+
+impl BlackBoxModel<In, Out> for FifoPrimitiveModel {
+    fn reset(&mut self) {
+        self.write_ptr = 0;
+        self.read_ptr = 0;
+        self.count = 0;
+        self.data = 0;
+    }
+
+    fn output(&self, _input: In) -> Out {
+        Out {
+            data: b8(self.data as u128),
+            full: self.count == DEPTH,
+            empty: self.count == 0,
+        }
+    }
+
+    fn tick(&mut self, input: In) {
+        let write = input.write && self.count != DEPTH;
+        let read = input.read && self.count != 0;
+        if write {
+            self.mem[self.write_ptr] = input.data.raw() as u8;
+            self.write_ptr = (self.write_ptr + 1) % DEPTH;
+        }
+        if read {
+            self.data = self.mem[self.read_ptr];
+            self.read_ptr = (self.read_ptr + 1) % DEPTH;
+        }
+        self.count = self.count + write as usize - read as usize;
+    }
+}
+
+// Connect the fields of the input and output to the ports of the primitive
+fn template() -> Result<String, RHDLError> {
+    let slice = |name: &str, kind: Kind, field: &str| -> Result<String, RHDLError> {
+        let (range, _) = bit_range(kind, &Path::default().field(field))?;
+        Ok(format!("{name}[{}:{}]", range.end - 1, range.start))
+    };
+    let i = |field| slice("i", In::static_kind(), field);
+    let o = |field| slice("o", Out::static_kind(), field);
+    Ok(format!(
+        "FIFO_SYNC_PRIM #(.WIDTH(8), .DEPTH_LOG2(4)) {{instance}} (.CLK(clock), .RST(reset), \
+         .WREN({}), .DI({}), .RDEN({}), .DO({}), .FULL({}), .EMPTY({}));",
+        i("write")?,
+        i("data")?,
+        i("read")?,
+        o("data")?,
+        o("full")?,
+        o("empty")?,
+    ))
+}
+
+/// Register the primitive (under [NAME]), and create a
+/// circuit that instantiates it.
+pub fn fifo_primitive() -> Result<ModeledBlackBox<In, Out>, RHDLError> {
+    register_black_box(
+        NAME,
+        BlackBoxSpec::new("FIFO_SYNC_PRIM", VERILOG, &template()?, || {
+            Box::new(FifoPrimitiveModel::default())
+        }),
+    );
+    ModeledBlackBox::new(NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(data: u8) -> In {
+        In {
+            write: true,
+            data: b8(data as u128),
+            read: false,
+        }
+    }
+
+    fn read() -> In {
+        In {
+            write: false,
+            data: b8(0),
+            read: true,
+        }
+    }
+
+    fn idle() -> In {
+        In {
+            write: false,
+            data: b8(0),
+            read: false,
+        }
+    }
+
+    // Fill the FIFO past full, drain it past empty, and then
+    // stream through it with simultaneous reads and writes
+    fn stimulus() -> Vec<In> {
+        let mut input = (0..20).map(write).collect::<Vec<_>>();
+        input.extend(std::iter::repeat_n(read(), 20));
+        input.push(write(100));
+        input.extend((101..120).map(|n| In {
+            read: true,
+            ..write(n)
+        }));
+        input.extend([read(), read(), idle(), idle()]);
+        input
+    }
+
+    fn simulate(uut: &ModeledBlackBox<In, Out>) -> miette::Result<Vec<Out>> {
+        Ok(uut
+            .run(stimulus().into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_model_behavior() -> miette::Result<()> {
+        let output = simulate(&fifo_primitive()?)?;
+        // The output reflects the inputs of the previous clock
+        assert!(output[0].empty);
+        assert!(output[16].full);
+        // The writes of words 16 to 19 are dropped
+        let reads = output[21..37]
+            .iter()
+            .map(|o| o.data.raw() as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(reads, (0..16).collect::<Vec<_>>());
+        assert!(output[36].empty);
+        // Simultaneous reads and writes pass the words through
+        assert_eq!(output[43].data, b8(101));
+        assert!(output[42..60].iter().all(|o| !o.empty && !o.full));
+        assert_eq!(output.last().unwrap().data, b8(119));
+        assert!(output.last().unwrap().empty);
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_matches_verilog() -> miette::Result<()> {
+        fifo_primitive()?;
+        verify_black_box_model::<In, Out>(NAME, stimulus())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_primitive_hdl() -> miette::Result<()> {
+        let uut = fifo_primitive()?;
+        let hdl = uut.hdl("top")?.as_module().to_string();
+        assert!(hdl.contains("module FIFO_SYNC_PRIM"));
+        assert!(hdl.contains("FIFO_SYNC_PRIM #(.WIDTH(8), .DEPTH_LOG2(4)) top_FIFO_SYNC_PRIM"));
+        let tb = uut
+            .run(stimulus().into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+
+    // A faulty primitive, that corrupts every word it reads
+    struct Corrupting(FifoPrimitiveModel);
+
+    impl BlackBoxModel<In, Out> for Corrupting {
+        fn reset(&mut self) {
+            self.0.reset()
+        }
+
+        fn output(&self, input: In) -> Out {
+            let mut out = self.0.output(input);
+            out.data ^= 0x80;
+            out
+        }
+
+        fn tick(&mut self, input: In) {
+            self.0.tick(input)
+        }
+    }
+
+    #[test]
+    fn test_model_override() -> miette::Result<()> {
+        let uut = fifo_primitive()?;
+        let good = simulate(&uut)?;
+        {
+            let _fault = override_black_box_model(NAME, || {
+                Box::new(Corrupting(FifoPrimitiveModel::default()))
+            })?;
+            let bad = simulate(&uut)?;
+            for (good, bad) in good.iter().zip(&bad) {
+                assert_eq!(good.data ^ 0x80, bad.data);
+                assert_eq!((good.full, good.empty), (bad.full, bad.empty));
+            }
+        }
+        // Dropping the override restores the registered model
+        assert_eq!(simulate(&uut)?, good);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unregistered_black_box() {
+        assert!(ModeledBlackBox::<In, Out>::new("no_such_black_box").is_err());
+        fifo_primitive().unwrap();
+        assert!(ModeledBlackBox::<b8, b8>::new(NAME).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+//! Two Class Priority FIFO
+//!
+//! Network style designs often need to prioritize control traffic
+//! over bulk data.  The [PriorityFifo] holds two [SyncFIFO]s, one for
+//! each class of traffic.  Each word written is placed in the high or
+//! low class FIFO, either according to the `high` flag on the input,
+//! or (if the core is built with [PriorityFifo::try_with_classifier])
+//! according to a synthesizable predicate on the data.
+//!
+//! The read side always presents a high class word if there is one,
+//! with one exception.  To keep the low class from starving, the core
+//! can be given a `share` of `K`, in which case at least one in every
+//! `K` reads goes to the low class, whenever the low class has data
+//! waiting.  A `share` of `0` gives strict priority.
+//!
+//! The read interface is the same as the [SyncFIFO], with the
+//! `high` output flag indicating the class of the presented word.
+//! Writes to a full class are dropped, and counted (saturating) in the
+//! drop counter for that class.  The number of words held by each class
+//! is also presented.  Each class holds up to `2^N-1` words.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+PriorityFifo+-------+
+ ?T   |                      | ?T
++---->| data            data +---->
+ bool |                      | bool
++---->| high            high +---->
+ bool |                      | [bN;2]
++---->| next           level +---->
+      |                      | [b16;2]
+      |                drops +---->
+      +----------------------+
+")]
+//!
+//!# Example
+//!
+//! A FIFO of bytes, in which the low class is guaranteed
+//! one read in four.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::fifo::priority::PriorityFifo;
+//!
+//! let uut = PriorityFifo::<b8, U4>::new(4);
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+use super::synchronous::{self, SyncFIFO};
+
+/// The index of the high class in the `level` and `drops` outputs
+pub const HIGH: usize = 0;
+/// The index of the low class in the `level` and `drops` outputs
+pub const LOW: usize = 1;
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [PriorityFifo]
+pub struct In<T: Digital> {
+    /// The data to write
+    pub data: Option<T>,
+    /// Write the data to the high class (ignored if the
+    /// core has a classifier)
+    pub high: bool,
+    /// Advance to the next word on the read side
+    pub next: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [PriorityFifo]
+pub struct Out<T: Digital, N: BitWidth> {
+    /// The word at the head of the FIFO
+    pub data: Option<T>,
+    /// Set if the presented word is from the high class
+    pub high: bool,
+    /// The number of words held by each class
+    pub level: [Bits<N>; 2],
+    /// The number of words dropped by each class
+    pub drops: [b16; 2],
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn no_classifier<T: Digital>(_cr: ClockReset, _t: T) -> bool {
+    false
+}
+
+#[derive(Clone, Synchronous, SynchronousDQ)]
+/// The two class priority FIFO.  Here `T` is the type
+/// of the data, and each class holds `2^N-1` words.
+pub struct PriorityFifo<T: Digital, N: BitWidth> {
+    classify: Func<T, bool>,
+    use_classifier: constant::Constant<bool>,
+    share: constant::Constant<b8>,
+    high: SyncFIFO<T, N>,
+    low: SyncFIFO<T, N>,
+    streak: dff::DFF<b8>,
+    level: dff::DFF<[Bits<N>; 2]>,
+    drops: dff::DFF<[b16; 2]>,
+}
+
+impl<T: Digital, N: BitWidth> PriorityFifo<T, N> {
+    fn build(classify: Func<T, bool>, use_classifier: bool, share: u8) -> Self {
+        Self {
+            classify,
+            use_classifier: constant::Constant::new(use_classifier),
+            share: constant::Constant::new(b8(share as u128)),
+            high: SyncFIFO::default(),
+            low: SyncFIFO::default(),
+            streak: dff::DFF::new(b8(0)),
+            level: dff::DFF::new([Bits::<N>::default(); 2]),
+            drops: dff::DFF::new([b16(0); 2]),
+        }
+    }
+
+    /// Create a [PriorityFifo] in which the class of each word is
+    /// given by the `high` input.  The low class is guaranteed one in
+    /// every `share` reads (or none, if `share` is `0`).
+    pub fn new(share: u8) -> Self {
+        let classify =
+            Func::try_new::<no_classifier<T>>().expect("The built in classifier should compile");
+        Self::build(classify, false, share)
+    }
+
+    /// Create a [PriorityFifo] in which the class of each word is
+    /// given by a synthesizable function with the signature
+    /// `fn(ClockReset, T) -> bool`, which returns `true` for the
+    /// high class.
+    pub fn try_with_classifier<S>(share: u8) -> Result<Self, RHDLError>
+    where
+        S: DigitalFn,
+        S: DigitalFn2<A0 = ClockReset, A1 = T, O = bool>,
+    {
+        Ok(Self::build(Func::try_new::<S>()?, true, share))
+    }
+}
+
+impl<T: Digital, N: BitWidth> SynchronousIO for PriorityFifo<T, N> {
+    type I = In<T>;
+    type O = Out<T, N>;
+    type Kernel = priority_fifo_kernel<T, N>;
+}
+
+#[kernel(allow_weak_partial)]
+#[doc(hidden)]
+pub fn priority_fifo_kernel<T: Digital, N: BitWidth>(
+    cr: ClockReset,
+    i: In<T>,
+    q: Q<T, N>,
+) -> (Out<T, N>, D<T, N>) {
+    let mut d = D::<T, N>::dont_care();
+    d.streak = q.streak;
+    d.level = q.level;
+    d.drops = q.drops;
+    d.classify = T::dont_care();
+    d.high = synchronous::In::<T> {
+        data: None,
+        next: false,
+    };
+    d.low = synchronous::In::<T> {
+        data: None,
+        next: false,
+    };
+    // Route the write to one of the classes
+    if let Some(data) = i.data {
+        d.classify = data;
+        let high = if q.use_classifier { q.classify } else { i.high };
+        if high {
+            if q.high.full {
+                if q.drops[0] != 65535 {
+                    d.drops[0] = q.drops[0] + 1;
+                }
+            } else {
+                d.high.data = Some(data);
+                d.level[0] += 1;
+            }
+        } else if q.low.full {
+            if q.drops[1] != 65535 {
+                d.drops[1] = q.drops[1] + 1;
+            }
+        } else {
+            d.low.data = Some(data);
+            d.level[1] += 1;
+        }
+    }
+    // Pick the class to present.  The low class gets its turn
+    // once the high class has had `share - 1` reads in a row.
+    let high_ready = match q.high.data {
+        Some(_) => true,
+        None => false,
+    };
+    let low_ready = match q.low.data {
+        Some(_) => true,
+        None => false,
+    };
+    let low_turn = q.share != 0 && q.streak + 1 >= q.share;
+    let pick_high = high_ready && !(low_ready && low_turn);
+    let mut o = Out::<T, N> {
+        data: q.low.data,
+        high: false,
+        level: q.level,
+        drops: q.drops,
+    };
+    if pick_high {
+        o.data = q.high.data;
+        o.high = true;
+    }
+    if i.next {
+        if pick_high {
+            d.high.next = true;
+            d.level[0] -= 1;
+            d.streak = if low_ready { q.streak + 1 } else { bits(0) };
+        } else if low_ready {
+            d.low.next = true;
+            d.level[1] -= 1;
+            d.streak = bits(0);
+        }
+    }
+    if cr.reset.any() {
+        d.streak = bits(0);
+        d.level = [bits(0); 2];
+        d.drops = [bits(0); 2];
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(data: u8, high: bool) -> In<b8> {
+        In {
+            data: Some(b8(data as u128)),
+            high,
+            next: false,
+        }
+    }
+
+    fn read() -> In<b8> {
+        In {
+            data: None,
+            high: false,
+            next: true,
+        }
+    }
+
+    // The words read (with their class), and the outputs
+    type Reads<N> = (Vec<(u8, bool)>, Vec<Out<b8, N>>);
+
+    fn run<N: BitWidth>(uut: &PriorityFifo<b8, N>, input: Vec<In<b8>>) -> miette::Result<Reads<N>> {
+        let samples = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| (t.value.1, t.value.2))
+            .collect::<Vec<_>>();
+        let reads = samples
+            .iter()
+            .filter(|(i, _)| i.next)
+            .filter_map(|(_, o)| o.data.map(|d| (d.raw() as u8, o.high)))
+            .collect();
+        Ok((reads, samples.into_iter().map(|(_, o)| o).collect()))
+    }
+
+    #[test]
+    fn test_strict_priority() -> miette::Result<()> {
+        let uut = PriorityFifo::<b8, U4>::new(0);
+        // Interleave the classes, overfilling both
+        let mut input = (0..40).map(|n| write(n, n % 2 == 0)).collect::<Vec<_>>();
+        input.extend(std::iter::repeat_n(read(), 40));
+        let (reads, output) = run(&uut, input)?;
+        let expected = (0..30)
+            .step_by(2)
+            .map(|n| (n, true))
+            .chain((1..30).step_by(2).map(|n| (n, false)))
+            .collect::<Vec<_>>();
+        assert_eq!(reads, expected);
+        // Each class held 15 words, and dropped 5
+        assert_eq!(output[40].level, [b4(15), b4(15)]);
+        assert_eq!(output.last().unwrap().drops, [b16(5), b16(5)]);
+        assert_eq!(output.last().unwrap().level, [b4(0), b4(0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_anti_starvation() -> miette::Result<()> {
+        let uut = PriorityFifo::<b8, U4>::new(4);
+        // Both classes are written faster than they are read,
+        // so that both stay saturated
+        let input = (0..4000)
+            .map(|n| match n % 4 {
+                0 => write(n as u8, true),
+                1 => In {
+                    next: true,
+                    ..write(n as u8, false)
+                },
+                2 => write(n as u8, true),
+                _ => In {
+                    next: true,
+                    ..write(n as u8, false)
+                },
+            })
+            .collect::<Vec<_>>();
+        let (reads, output) = run(&uut, input)?;
+        // The first read comes before the FIFOs have any data
+        assert_eq!(reads.len(), 1999);
+        // After the first few reads, both classes are always waiting
+        let window = &reads[20..];
+        for chunk in window.windows(4) {
+            assert_eq!(chunk.iter().filter(|(_, high)| !high).count(), 1);
+        }
+        let last = output.last().unwrap();
+        assert!(last.drops[HIGH] > b16(0) && last.drops[LOW] > b16(0));
+        // No word is read twice in a row
+        for class in [true, false] {
+            let words = reads.iter().filter(|(_, high)| *high == class);
+            assert!(words.clone().zip(words.skip(1)).all(|(a, b)| a.0 != b.0));
+        }
+        Ok(())
+    }
+
+    #[kernel]
+    fn is_control(_cr: ClockReset, t: b8) -> bool {
+        (t & 0x80) != 0
+    }
+
+    #[test]
+    fn test_classifier() -> miette::Result<()> {
+        let uut = PriorityFifo::<b8, U3>::try_with_classifier::<is_control>(0)?;
+        // The high flag is ignored
+        let mut input = [1, 0x81, 2, 0x82, 3]
+            .into_iter()
+            .map(|n| write(n, false))
+            .collect::<Vec<_>>();
+        input.extend(std::iter::repeat_n(read(), 6));
+        let (reads, _) = run(&uut, input)?;
+        assert_eq!(
+            reads,
+            [
+                (0x81, true),
+                (0x82, true),
+                (1, false),
+                (2, false),
+                (3, false)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_fifo_hdl() -> miette::Result<()> {
+        let uut = PriorityFifo::<b8, U3>::new(3);
+        let input = (0..80).map(|n| match n % 5 {
+            0 | 1 => write(n, n % 3 == 0),
+            2 => In {
+                next: true,
+                ..write(n, true)
+            },
+            _ => read(),
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
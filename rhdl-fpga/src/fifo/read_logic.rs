@@ -35,16 +35,38 @@ FIFOWriteCore     |                        almost_empty +---->
 //! The [FIFOReadCore] is more of an internal component, that you are
 //! unlikely to use directly.  Instead, use a [SyncFIFO] or [AsyncFIFO]
 //! instead.
-use crate::core::dff;
+use crate::core::{constant, dff};
 use rhdl::prelude::*;
 
-#[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
 /// The FIFO read logic as a core
 pub struct FIFOReadCore<N: BitWidth> {
+    // The fill level at (or below) which the FIFO is almost empty.
+    // It is fixed at construction, so it is left out of the trace.
+    almost_empty: constant::Constant<Bits<N>>,
     ram_read_address: dff::DFF<Bits<N>>,
     underflow: dff::DFF<bool>,
 }
 
+impl<N: BitWidth> Default for FIFOReadCore<N> {
+    fn default() -> Self {
+        // Almost empty when there is at most one element left
+        Self::new(bits(1))
+    }
+}
+
+impl<N: BitWidth> FIFOReadCore<N> {
+    /// Create a read core that signals almost empty once the
+    /// FIFO holds `almost_empty` elements or fewer
+    pub fn new(almost_empty: Bits<N>) -> Self {
+        Self {
+            almost_empty: constant::Constant::untraced(almost_empty),
+            ram_read_address: dff::DFF::default(),
+            underflow: dff::DFF::default(),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Digital)]
 /// The inputs to the [FIFOReadCore]
 pub struct In<N: BitWidth> {
@@ -80,8 +102,9 @@ impl<N: BitWidth> SynchronousIO for FIFOReadCore<N> {
 pub fn read_logic<N: BitWidth>(cr: ClockReset, i: In<N>, q: Q<N>) -> (Out<N>, D<N>) {
     // Compute the empty flag
     let empty = i.write_address == q.ram_read_address;
-    // Compute the almost empty flag
-    let almost_empty = empty || (q.ram_read_address + 1) == i.write_address;
+    // Compute the almost empty flag from the fill level
+    let fill = i.write_address - q.ram_read_address;
+    let almost_empty = empty || fill <= q.almost_empty;
     // Check for an underflow condition
     // We will underflow if we try to read when the FIFO is empty
     // and the condition is latching.
@@ -121,6 +144,7 @@ mod tests {
     #[test]
     fn test_empty_condition() {
         let q = Q::<U4> {
+            almost_empty: bits(1),
             ram_read_address: bits(0),
             underflow: false,
         };
@@ -140,6 +164,7 @@ mod tests {
     #[test]
     fn test_next_increments_read_address() {
         let q = Q::<U4> {
+            almost_empty: bits(1),
             ram_read_address: bits(0),
             underflow: false,
         };
@@ -159,6 +184,7 @@ mod tests {
     #[test]
     fn test_empty_with_read_leads_to_underflow() {
         let q = Q::<U4> {
+            almost_empty: bits(1),
             ram_read_address: bits(0),
             underflow: false,
         };
@@ -178,6 +204,7 @@ mod tests {
     #[test]
     fn test_underflow_is_latching() {
         let q = Q::<U4> {
+            almost_empty: bits(1),
             ram_read_address: bits(0),
             underflow: true,
         };
@@ -197,6 +224,7 @@ mod tests {
     #[test]
     fn test_almost_empty_flag_is_clear_with_at_least_2_elements() {
         let q = Q::<U4> {
+            almost_empty: bits(1),
             ram_read_address: bits(0),
             underflow: false,
         };
@@ -216,6 +244,7 @@ mod tests {
     #[test]
     fn test_reset_condition() {
         let q = Q::<U4> {
+            almost_empty: bits(1),
             ram_read_address: bits(3),
             underflow: true,
         };
@@ -41,8 +41,12 @@
 //! The trace below demonstrates the result.
 #![doc = include_str!("../../doc/sync_fifo.md")]
 
+use crate::config::{Validate, Validated};
 use crate::core::ram;
+use miette::Diagnostic;
+use rhdl::core::report::Description;
 use rhdl::prelude::*;
+use thiserror::Error;
 
 use super::read_logic;
 use super::write_logic;
@@ -61,14 +65,144 @@ pub struct SyncFIFO<T: Digital, N: BitWidth> {
 
 impl<T: Digital, N: BitWidth> Default for SyncFIFO<T, N> {
     fn default() -> Self {
+        Self::new(
+            Config::default()
+                .validate()
+                .expect("The default FIFO thresholds should be valid"),
+        )
+    }
+}
+
+impl<T: Digital, N: BitWidth> SyncFIFO<T, N> {
+    /// Create a FIFO with the thresholds of a validated [Config]
+    pub fn new(config: Validated<Config<N>>) -> Self {
         Self {
-            write_logic: write_logic::FIFOWriteCore::default(),
-            read_logic: read_logic::FIFOReadCore::default(),
+            write_logic: write_logic::FIFOWriteCore::new(bits(config.almost_full as u128)),
+            read_logic: read_logic::FIFOReadCore::new(bits(config.almost_empty as u128)),
             ram: ram::option_sync::OptionSyncBRAM::default(),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The thresholds of a [SyncFIFO] with `N` address bits
+pub struct Config<N: BitWidth> {
+    /// The number of elements at (or above) which `almost_full` is asserted
+    pub almost_full: usize,
+    /// The number of elements at (or below) which `almost_empty` is asserted
+    pub almost_empty: usize,
+    marker: std::marker::PhantomData<N>,
+}
+
+impl<N: BitWidth> Default for Config<N> {
+    /// The thresholds of [SyncFIFO::default], which are one
+    /// element from full and one element from empty.  Shallow
+    /// FIFOs clamp them, so that `almost_full` is at least 1 and
+    /// `almost_empty` stays below it.
+    fn default() -> Self {
+        let depth = (1_usize << N::BITS) - 1;
+        let almost_full = (depth - 1).max(1);
+        Self {
+            almost_full,
+            almost_empty: (almost_full - 1).min(1),
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<N: BitWidth> Config<N> {
+    /// Set the almost full threshold
+    pub fn with_almost_full(self, almost_full: usize) -> Self {
+        Self {
+            almost_full,
+            ..self
+        }
+    }
+    /// Set the almost empty threshold
+    pub fn with_almost_empty(self, almost_empty: usize) -> Self {
+        Self {
+            almost_empty,
+            ..self
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors that can arise when validating a [Config]
+pub enum ConfigError {
+    /// The almost full threshold can never be reached
+    #[error("almost_full is {almost_full}, but the FIFO only holds {depth} elements")]
+    #[diagnostic(help("almost_full must be between 1 and the depth of the FIFO ({depth})"))]
+    AlmostFullTooDeep {
+        /// The threshold provided
+        almost_full: usize,
+        /// The depth of the FIFO
+        depth: usize,
+    },
+    /// The almost full threshold of zero is always met
+    #[error("almost_full is 0, so the FIFO would always be almost full")]
+    #[diagnostic(help("almost_full must be between 1 and the depth of the FIFO"))]
+    AlmostFullZero,
+    /// The almost empty threshold is always met
+    #[error("almost_empty is {almost_empty}, but the FIFO only holds {depth} elements")]
+    #[diagnostic(help("almost_empty must be less than the depth of the FIFO ({depth})"))]
+    AlmostEmptyTooDeep {
+        /// The threshold provided
+        almost_empty: usize,
+        /// The depth of the FIFO
+        depth: usize,
+    },
+    /// The thresholds overlap
+    #[error("almost_empty ({almost_empty}) is not below almost_full ({almost_full})")]
+    #[diagnostic(help(
+        "A FIFO can not be almost empty and almost full at once, so almost_empty must be less than almost_full"
+    ))]
+    ThresholdsCrossed {
+        /// The almost empty threshold
+        almost_empty: usize,
+        /// The almost full threshold
+        almost_full: usize,
+    },
+}
+
+impl<N: BitWidth> Validate for Config<N> {
+    type Error = ConfigError;
+    fn check(&self) -> Result<(), ConfigError> {
+        let depth = (1 << N::BITS) - 1;
+        if self.almost_full == 0 {
+            return Err(ConfigError::AlmostFullZero);
+        }
+        if self.almost_full > depth {
+            return Err(ConfigError::AlmostFullTooDeep {
+                almost_full: self.almost_full,
+                depth,
+            });
+        }
+        if self.almost_empty >= depth {
+            return Err(ConfigError::AlmostEmptyTooDeep {
+                almost_empty: self.almost_empty,
+                depth,
+            });
+        }
+        if self.almost_empty >= self.almost_full {
+            return Err(ConfigError::ThresholdsCrossed {
+                almost_empty: self.almost_empty,
+                almost_full: self.almost_full,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T: Digital, N: BitWidth> Describe for SyncFIFO<T, N> {
+    fn describe(&self) -> Description {
+        Description::new("SyncFIFO")
+            .param("data_bits", T::BITS)
+            .param("address_bits", N::BITS)
+            .param("depth", (1_usize << N::BITS) - 1)
+    }
+}
+
 #[derive(PartialEq, Debug, Digital)]
 /// Inputs for the FIFO
 pub struct In<T: Digital> {
@@ -185,6 +319,109 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_config_constraints() {
+        let config = Config::<U4>::default;
+        assert!(config().validate().is_ok());
+        assert_eq!(
+            config().with_almost_full(16).validate().unwrap_err(),
+            ConfigError::AlmostFullTooDeep {
+                almost_full: 16,
+                depth: 15
+            }
+        );
+        assert_eq!(
+            config().with_almost_full(0).validate().unwrap_err(),
+            ConfigError::AlmostFullZero
+        );
+        assert_eq!(
+            config()
+                .with_almost_full(15)
+                .with_almost_empty(15)
+                .validate()
+                .unwrap_err(),
+            ConfigError::AlmostEmptyTooDeep {
+                almost_empty: 15,
+                depth: 15
+            }
+        );
+        assert_eq!(
+            config()
+                .with_almost_full(4)
+                .with_almost_empty(4)
+                .validate()
+                .unwrap_err(),
+            ConfigError::ThresholdsCrossed {
+                almost_empty: 4,
+                almost_full: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_default_config_for_shallow_fifos() {
+        let config = Config::<U1>::default();
+        assert_eq!((config.almost_full, config.almost_empty), (1, 0));
+        assert!(config.validate().is_ok());
+        let config = Config::<U2>::default();
+        assert_eq!((config.almost_full, config.almost_empty), (2, 1));
+        assert!(config.validate().is_ok());
+    }
+
+    fn write_then_read<N: BitWidth>() -> miette::Result<Vec<b8>> {
+        let uut = SyncFIFO::<b8, N>::default();
+        let input = [write(bits(42)), read(), read()];
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .filter_map(|x| x.value.2.data)
+            .collect())
+    }
+
+    #[test]
+    fn test_default_shallow_fifos() -> miette::Result<()> {
+        assert_eq!(write_then_read::<U1>()?, vec![bits(42)]);
+        assert_eq!(write_then_read::<U2>()?, vec![bits(42)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_configured_thresholds() -> miette::Result<()> {
+        let config = Config::<U4>::default()
+            .with_almost_full(10)
+            .with_almost_empty(3)
+            .validate()?;
+        let uut = SyncFIFO::<b8, U4>::new(config);
+        // Fill the FIFO, and then drain it
+        let input = (0..15)
+            .map(|i| write(bits(i + 1)))
+            .chain((0..15).map(|_| read()));
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|x| x.value.2)
+            .collect::<Vec<_>>();
+        let asserted = |flag: fn(&Out<b8>) -> bool| {
+            output
+                .iter()
+                .enumerate()
+                .filter(|(_, o)| flag(o))
+                .map(|(n, _)| n)
+                .collect::<Vec<_>>()
+        };
+        // Almost full once 10 elements have been written, until the
+        // fill drops below 10 again
+        assert_eq!(asserted(|o| o.almost_full), (10..20).collect::<Vec<_>>());
+        // Almost empty until the fill (as seen by the read side, a clock
+        // after the write) exceeds 3, and again once the fill drops to 3
+        assert_eq!(
+            asserted(|o| o.almost_empty),
+            (0..5).chain(27..30).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
     #[test]
     fn basic_write_then_read_test() -> miette::Result<()> {
         let uut = SyncFIFO::<Bits<U8>, U3>::default();
@@ -260,4 +497,26 @@ mod tests {
         assert_eq!(data, read_back);
         Ok(())
     }
+
+    #[test]
+    fn test_fifo_symbol() {
+        let expect = expect![[r#"
+                  +-+SyncFIFO+-----------+
+             ?b8  |                      | ?b8
+            +---->| data            data +---->
+             bool |                      | bool
+            +---->| next            full +---->
+                  |                      | bool
+                  |         almost_empty +---->
+                  |                      | bool
+                  |          almost_full +---->
+                  |                      | bool
+                  |             overflow +---->
+                  |                      | bool
+                  |            underflow +---->
+                  +----------------------+"#]];
+        expect.assert_eq(&rhdl::core::circuit::doc_symbol::schematic_symbol::<
+            SyncFIFO<b8, U4>,
+        >("SyncFIFO"));
+    }
 }
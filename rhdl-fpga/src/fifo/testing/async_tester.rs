@@ -152,6 +152,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_async_fifo_works_with_metastability() -> miette::Result<()> {
+        use crate::cdc::metastability::{inject, Metastability};
+        for seed in 0..4 {
+            let uut: AsyncFIFOTester<Red, Blue, U16, 4> = Default::default();
+            let red_input = std::iter::repeat(()).with_reset(1).clock_pos_edge(50);
+            let blue_input = std::iter::repeat(()).with_reset(1).clock_pos_edge(78);
+            let input = red_input.merge(blue_input, |r, b| In {
+                cr_w: signal(r.0),
+                cr_r: signal(b.0),
+            });
+            let last = inject(Metastability::new(seed).probability(0.5), || {
+                uut.run(input.take(10_000)).map(|o| o.last().unwrap())
+            })?;
+            assert!(last.value.1.val());
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_async_fifo_test_hdl() -> miette::Result<()> {
         let uut: AsyncFIFOTester<Red, Blue, U16, 4> = Default::default();
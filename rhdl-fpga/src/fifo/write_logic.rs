@@ -36,13 +36,16 @@
 //!
 //! The write core keeps track of the write pointer of the FIFO.
 
-use crate::core::dff;
+use crate::core::{constant, dff};
 use badascii_doc::badascii_formal;
 use rhdl::prelude::*;
 
-#[derive(Clone, Debug, Synchronous, SynchronousDQ, Default)]
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
 /// The FIFO write logic as a core
 pub struct FIFOWriteCore<N: BitWidth> {
+    // The fill level at which the FIFO is almost full.  It is
+    // fixed at construction, so it is left out of the trace.
+    almost_full: constant::Constant<Bits<N>>,
     write_address: dff::DFF<Bits<N>>,
     // We delay the write address by one clock before sending
     // it to the read side of the FIFO.  This is because it will
@@ -53,6 +56,26 @@ pub struct FIFOWriteCore<N: BitWidth> {
     overflow: dff::DFF<bool>,
 }
 
+impl<N: BitWidth> Default for FIFOWriteCore<N> {
+    fn default() -> Self {
+        // Almost full when there is only one free slot left
+        Self::new(bits(Bits::<N>::mask().raw() - 1))
+    }
+}
+
+impl<N: BitWidth> FIFOWriteCore<N> {
+    /// Create a write core that signals almost full once the
+    /// FIFO holds at least `almost_full` elements
+    pub fn new(almost_full: Bits<N>) -> Self {
+        Self {
+            almost_full: constant::Constant::untraced(almost_full),
+            write_address: dff::DFF::default(),
+            write_address_delayed: dff::DFF::default(),
+            overflow: dff::DFF::default(),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Digital)]
 /// The inputs to the [FIFOWriteCore]
 pub struct In<N: BitWidth> {
@@ -88,8 +111,9 @@ impl<N: BitWidth> SynchronousIO for FIFOWriteCore<N> {
 pub fn write_logic<N: BitWidth>(cr: ClockReset, i: In<N>, q: Q<N>) -> (Out<N>, D<N>) {
     // Compute the full flag
     let full = (q.write_address + 1) == i.read_address;
-    // Compute the almost full flag
-    let almost_full = full || (q.write_address + 2) == i.read_address;
+    // Compute the almost full flag from the fill level
+    let fill = q.write_address - i.read_address;
+    let almost_full = full || fill >= q.almost_full;
     // Check for an overflow condition
     // We will overflow if we try to write when the FIFO is full
     // and the condition is latching.
@@ -135,6 +159,7 @@ mod tests {
             write_enable: false,
         };
         let q = Q::<U4> {
+            almost_full: bits(0b1110),
             write_address: bits(0b1111),
             write_address_delayed: bits(0b1111),
             overflow: false,
@@ -156,6 +181,7 @@ mod tests {
             write_enable: false,
         };
         let q = Q::<U4> {
+            almost_full: bits(0b1110),
             write_address: bits(0b1110),
             write_address_delayed: bits(0b1110),
             overflow: false,
@@ -177,6 +203,7 @@ mod tests {
             write_enable: true,
         };
         let q = Q::<U4> {
+            almost_full: bits(0b1110),
             write_address: bits(0b1100),
             write_address_delayed: bits(0b1100),
             overflow: false,
@@ -198,6 +225,7 @@ mod tests {
             write_enable: true,
         };
         let q = Q::<U4> {
+            almost_full: bits(0b1110),
             write_address: bits(0b1111),
             write_address_delayed: bits(0b1111),
             overflow: false,
@@ -219,6 +247,7 @@ mod tests {
             write_enable: false,
         };
         let q = Q::<U4> {
+            almost_full: bits(0b1110),
             write_address: bits(0b1111),
             write_address_delayed: bits(0b1111),
             overflow: true,
@@ -240,6 +269,7 @@ mod tests {
             write_enable: false,
         };
         let q = Q::<U4> {
+            almost_full: bits(0b1110),
             write_address: bits(0b1100),
             write_address_delayed: bits(0b1100),
             overflow: false,
@@ -261,6 +291,7 @@ mod tests {
             write_enable: false,
         };
         let q = Q::<U4> {
+            almost_full: bits(0b1110),
             write_address: bits(0b1111),
             write_address_delayed: bits(0b1111),
             overflow: true,
@@ -0,0 +1,120 @@
+//! Four phase handshake transmitter
+//!
+//!# Purpose
+//!
+//! The [FourPhaseTx] core sends a stream of words to a
+//! [FourPhaseRx](super::FourPhaseRx) in an unrelated clock domain
+//! using a four phase (return to zero) handshake.  For each transfer,
+//! `req` is raised, the receiver raises `ack` in response, `req` is
+//! then lowered, and finally the receiver lowers `ack`.  The data bundle
+//! is held stable from the time `req` is raised until `ack` is raised.
+#![doc = badascii_doc::badascii!("
+          +--------------+                +-------
+req  +----+              +----------------+
+                  +---------------+
+ack  +------------+               +---------------
+     +-----------++--------------------------+---+
+data |    X      |            A              | B |
+     +-----------++--------------------------+---+
+")]
+//!
+//!# Connections
+//!
+//! Here is the schematic symbol for the transmitter.
+#![doc = badascii_doc::badascii_formal!("
+      +-+FourPhaseTx+---+
+  ?bN |                | bool
++---->| data       req +---->
+ bool |                | bN
+<-----+ ready     data +---->
+      |                | bool
+      |            ack |<---+
+      |                |
++---->| cr             |
+      +----------------+
+")]
+//!
+//! The `data` and `ready` signals are in the `W` domain, and
+//! follow the FIFO convention.  A word is accepted in any cycle
+//! where `data` is `Some` and `ready` is asserted.  The `ack`
+//! input comes from the receiver in the `R` domain.
+//!
+//!# Internals
+//!
+//! The request and the data bundle are held in registers.  The
+//! acknowledge is brought into the `W` domain with a synchronizer.
+//! The transmitter is ready when both `req` and the synchronized `ack`
+//! are low, and it lowers `req` once the synchronized `ack` is high.
+//!
+//!# Example
+//!
+//! See [FourPhaseRx](super::FourPhaseRx) for an example of a transmitter
+//! and receiver connected together.
+use rhdl::prelude::*;
+
+use crate::{cdc::synchronizer::Sync1Bit, core::dff};
+
+use super::{TxIn, TxOut};
+
+#[derive(Clone, Circuit, CircuitDQ, Default)]
+/// The transmitter for the four phase handshake.
+///
+/// The type parameters are:
+///   - `W`: The domain of the transmitter
+///   - `R`: The domain of the receiver
+///   - `N`: The number of bits in each word
+pub struct FourPhaseTx<W: Domain, R: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    req: Adapter<dff::DFF<bool>, W>,
+    data: Adapter<dff::DFF<Bits<Const<N>>>, W>,
+    ack: Sync1Bit<R, W>,
+}
+
+impl<W: Domain, R: Domain, const N: usize> CircuitIO for FourPhaseTx<W, R, N>
+where
+    Const<N>: BitWidth,
+{
+    type I = TxIn<W, R, N>;
+    type O = TxOut<W, N>;
+    type Kernel = four_phase_tx_kernel<W, R, N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn four_phase_tx_kernel<W: Domain, R: Domain, const N: usize>(
+    i: TxIn<W, R, N>,
+    q: Q<W, R, N>,
+) -> (TxOut<W, N>, D<W, R, N>)
+where
+    Const<N>: BitWidth,
+{
+    let mut d = D::<W, R, { N }>::dont_care();
+    d.req.clock_reset = i.cr;
+    d.data.clock_reset = i.cr;
+    d.ack.data = i.ack;
+    d.ack.cr = i.cr;
+    // The handshake is idle once both wires have returned to zero
+    let mut req = q.req.val();
+    let mut data = q.data.val();
+    let ack = q.ack.val();
+    let ready = !req && !ack;
+    if ready {
+        if let Some(word) = i.data.val() {
+            data = word;
+            req = true;
+        }
+    }
+    if req && ack {
+        req = false;
+    }
+    d.req.input = signal(req);
+    d.data.input = signal(data);
+    let o = TxOut::<W, { N }> {
+        ready: signal(ready),
+        req: q.req,
+        data: q.data,
+    };
+    (o, d)
+}
@@ -0,0 +1,120 @@
+#![warn(missing_docs)]
+//! Handshake cores for GALS designs
+//!
+//! In a globally asynchronous, locally synchronous (GALS) design,
+//! independently clocked islands talk to each other over request and
+//! acknowledge wires, with the data on a bundle of wires that is held
+//! stable while the request is pending.  The cores in this module convert
+//! between a valid/ready stream in one clock domain and such a handshake
+//! port, so that a transmitter in the `W` domain can talk to a receiver
+//! in the `R` domain.
+//!
+//! Two protocols are provided:
+//!   - Two phase ([TwoPhaseTx] and [TwoPhaseRx]), where each transfer
+//!     is marked by a _transition_ of `req`, and is acknowledged by a
+//!     transition of `ack`.  This takes one round trip per transfer.
+//!   - Four phase ([FourPhaseTx] and [FourPhaseRx]), where `req` is
+//!     raised for a transfer, `ack` is raised in response, and both then
+//!     return to zero.  This takes two round trips per transfer, but the
+//!     wires idle low between transfers.
+//!
+//! In both cases, the stream side follows the convention of the FIFOs
+//! in this crate.  The data is an `Option<Bits<N>>`, and it is accepted
+//! in any cycle in which `ready` is also asserted.
+//!
+//! All of the wires that cross between the domains are brought into
+//! the receiving domain by [Sync1Bit](crate::cdc::synchronizer::Sync1Bit)
+//! synchronizers.  This includes each bit of the data bundle.  The
+//! receiver waits an extra clock after it sees `req` before it samples
+//! the data, so that the data synchronizers have settled to the (stable)
+//! value sent with the request.
+use rhdl::prelude::*;
+
+pub mod four_phase_rx;
+pub mod four_phase_tx;
+pub mod two_phase_rx;
+pub mod two_phase_tx;
+
+pub use four_phase_rx::FourPhaseRx;
+pub use four_phase_tx::FourPhaseTx;
+pub use two_phase_rx::TwoPhaseRx;
+pub use two_phase_tx::TwoPhaseTx;
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Inputs to a handshake transmitter
+pub struct TxIn<W: Domain, R: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    /// The data to send (from the W domain)
+    pub data: Signal<Option<Bits<Const<N>>>, W>,
+    /// The acknowledge wire from the receiver (in the R domain)
+    pub ack: Signal<bool, R>,
+    /// The clock and reset for the W domain
+    pub cr: Signal<ClockReset, W>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Outputs from a handshake transmitter
+pub struct TxOut<W: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    /// The transmitter can accept data this cycle
+    pub ready: Signal<bool, W>,
+    /// The request wire to the receiver
+    pub req: Signal<bool, W>,
+    /// The data bundle to the receiver.  Stable while
+    /// the request is pending.
+    pub data: Signal<Bits<Const<N>>, W>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Inputs to a handshake receiver
+pub struct RxIn<W: Domain, R: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    /// The request wire from the transmitter (in the W domain)
+    pub req: Signal<bool, W>,
+    /// The data bundle from the transmitter (in the W domain)
+    pub data: Signal<Bits<Const<N>>, W>,
+    /// The downstream logic accepts the data this cycle
+    pub ready: Signal<bool, R>,
+    /// The clock and reset for the R domain
+    pub cr: Signal<ClockReset, R>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Outputs from a handshake receiver
+pub struct RxOut<R: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    /// The data received (in the R domain)
+    pub data: Signal<Option<Bits<Const<N>>>, R>,
+    /// The acknowledge wire to the transmitter
+    pub ack: Signal<bool, R>,
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use rhdl::prelude::*;
+
+    use crate::fifo::testing::async_tester::In;
+
+    // Two free running clocks, that are not related to each other
+    pub fn clocks<W: Domain, R: Domain>(
+        period_w: u64,
+        period_r: u64,
+        samples: usize,
+    ) -> impl Iterator<Item = TimedSample<In<W, R>>> {
+        let w = std::iter::repeat(()).with_reset(1).clock_pos_edge(period_w);
+        let r = std::iter::repeat(()).with_reset(1).clock_pos_edge(period_r);
+        w.merge(r, |w, r| In {
+            cr_w: signal(w.0),
+            cr_r: signal(r.0),
+        })
+        .take(samples)
+    }
+}
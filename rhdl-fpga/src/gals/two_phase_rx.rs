@@ -0,0 +1,295 @@
+//! Two phase handshake receiver
+//!
+//!# Purpose
+//!
+//! The [TwoPhaseRx] core receives a stream of words from a
+//! [TwoPhaseTx](super::TwoPhaseTx) in an unrelated clock domain,
+//! and presents them as a stream in its own clock domain.  A new
+//! word is pending whenever `req != ack`.  Once the word has been
+//! accepted downstream, the receiver toggles `ack`.
+//!
+//!# Connections
+//!
+//! Here is the schematic symbol for the receiver.
+#![doc = badascii_doc::badascii_formal!("
+      +-+TwoPhaseRx+---+
+ bool |                | ?bN
++---->| req       data +---->
+ bN   |                | bool
++---->| data     ready |<---+
+ bool |                |
+<-----+ ack            |
+      |                |
+      |             cr |<---+
+      +----------------+
+")]
+//!
+//! The `req` and `data` inputs come from the transmitter in the
+//! `W` domain.  The output `data` and the `ready` input are in the `R`
+//! domain, and follow the FIFO convention.
+//!
+//!# Internals
+//!
+//! The receiver brings `req` and each bit of `data` into the `R`
+//! domain with synchronizers.  When the synchronized `req` differs
+//! from `ack`, the receiver waits a clock for the data synchronizers
+//! to settle, and then presents the data on its output until it
+//! is accepted.
+#![doc = badascii_doc::badascii!("
+        +------+ req != ack  +--------+
+   +--->| Idle +------------>| Settle |
+   |    +------+             +---+----+
+   |                             |
+   |    +------+                 |
+   +----+ Hold |<----------------+
+ready   +------+
+")]
+//!
+//!# Example
+//!
+//! A transmitter in the `Red` domain, sending words to a
+//! receiver in the `Blue` domain.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::gals::{TwoPhaseRx, TwoPhaseTx};
+//!
+//! let tx = TwoPhaseTx::<Red, Blue, 8>::default();
+//! let rx = TwoPhaseRx::<Red, Blue, 8>::default();
+//! // Connect tx.req -> rx.req, tx.data -> rx.data and rx.ack -> tx.ack
+//! // in the kernel of an enclosing circuit.
+//!```
+use rhdl::prelude::*;
+
+use crate::{cdc::synchronizer::Sync1Bit, core::dff};
+
+use super::{RxIn, RxOut};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum State {
+    #[default]
+    Idle,
+    Settle,
+    Hold,
+}
+
+#[derive(Clone, Circuit, CircuitDQ)]
+/// The receiver for the two phase handshake.
+///
+/// The type parameters are:
+///   - `W`: The domain of the transmitter
+///   - `R`: The domain of the receiver
+///   - `N`: The number of bits in each word
+pub struct TwoPhaseRx<W: Domain, R: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    state: Adapter<dff::DFF<State>, R>,
+    ack: Adapter<dff::DFF<bool>, R>,
+    req: Sync1Bit<W, R>,
+    data: [Sync1Bit<W, R>; N],
+}
+
+impl<W: Domain, R: Domain, const N: usize> Default for TwoPhaseRx<W, R, N>
+where
+    Const<N>: BitWidth,
+{
+    fn default() -> Self {
+        Self {
+            state: Adapter::new(dff::DFF::default()),
+            ack: Adapter::new(dff::DFF::default()),
+            req: Sync1Bit::default(),
+            data: array_init::array_init(|_| Sync1Bit::default()),
+        }
+    }
+}
+
+impl<W: Domain, R: Domain, const N: usize> CircuitIO for TwoPhaseRx<W, R, N>
+where
+    Const<N>: BitWidth,
+{
+    type I = RxIn<W, R, N>;
+    type O = RxOut<R, N>;
+    type Kernel = two_phase_rx_kernel<W, R, N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn two_phase_rx_kernel<W: Domain, R: Domain, const N: usize>(
+    i: RxIn<W, R, N>,
+    q: Q<W, R, N>,
+) -> (RxOut<R, N>, D<W, R, N>)
+where
+    Const<N>: BitWidth,
+{
+    let mut d = D::<W, R, { N }>::dont_care();
+    d.state.clock_reset = i.cr;
+    d.ack.clock_reset = i.cr;
+    d.req.data = i.req;
+    d.req.cr = i.cr;
+    // Each bit of the data bundle gets its own synchronizer
+    for b in 0..N {
+        d.data[b].data = signal((i.data.val() & (1 << b)) != 0);
+        d.data[b].cr = i.cr;
+    }
+    let mut data = bits(0);
+    for b in 0..N {
+        if q.data[b].val() {
+            data |= bits(1 << b);
+        }
+    }
+    let mut state = q.state.val();
+    let mut ack = q.ack.val();
+    let mut word = None;
+    match q.state.val() {
+        State::Idle => {
+            if q.req.val() != ack {
+                state = State::Settle;
+            }
+        }
+        State::Settle => {
+            state = State::Hold;
+        }
+        State::Hold => {
+            word = Some(data);
+            if i.ready.val() {
+                ack = !ack;
+                state = State::Idle;
+            }
+        }
+    }
+    d.state.input = signal(state);
+    d.ack.input = signal(ack);
+    let o = RxOut::<R, { N }> {
+        data: signal(word),
+        ack: q.ack,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fifo::testing::{
+            async_tester::In, drainer, drainer::FIFODrainer, filler, filler::FIFOFiller,
+        },
+        gals::{testing::clocks, TwoPhaseTx},
+    };
+
+    #[derive(Clone, Circuit, CircuitDQ)]
+    struct Fixture<W: Domain, R: Domain> {
+        filler: Adapter<FIFOFiller<Const<8>>, W>,
+        tx: TwoPhaseTx<W, R, 8>,
+        rx: TwoPhaseRx<W, R, 8>,
+        drainer: Adapter<FIFODrainer<Const<8>>, R>,
+    }
+
+    impl<W: Domain, R: Domain> Fixture<W, R> {
+        fn new(drainer: FIFODrainer<Const<8>>) -> Self {
+            Self {
+                filler: Adapter::new(FIFOFiller::default()),
+                tx: TwoPhaseTx::default(),
+                rx: TwoPhaseRx::default(),
+                drainer: Adapter::new(drainer),
+            }
+        }
+    }
+
+    #[derive(PartialEq, Debug, Digital, Timed)]
+    struct Out<R: Domain> {
+        // The words received so far have all been correct
+        valid: Signal<bool, R>,
+        // A word was received this cycle
+        xfer: Signal<bool, R>,
+    }
+
+    impl<W: Domain, R: Domain> CircuitIO for Fixture<W, R> {
+        type I = In<W, R>;
+        type O = Out<R>;
+        type Kernel = fixture_kernel<W, R>;
+    }
+
+    #[kernel]
+    fn fixture_kernel<W: Domain, R: Domain>(i: In<W, R>, q: Q<W, R>) -> (Out<R>, D<W, R>) {
+        let mut d = D::<W, R>::dont_care();
+        d.filler.clock_reset = i.cr_w;
+        d.filler.input = signal(filler::In {
+            full: !q.tx.ready.val(),
+        });
+        d.tx.cr = i.cr_w;
+        d.tx.data = signal(q.filler.val().data);
+        d.tx.ack = q.rx.ack;
+        d.rx.cr = i.cr_r;
+        d.rx.req = q.tx.req;
+        d.rx.data = q.tx.data;
+        d.rx.ready = signal(q.drainer.val().next);
+        d.drainer.clock_reset = i.cr_r;
+        d.drainer.input = signal(drainer::In::<Const<8>> {
+            data: q.rx.data.val(),
+        });
+        let o = Out::<R> {
+            valid: signal(q.drainer.val().valid),
+            xfer: signal(q.drainer.val().next),
+        };
+        (o, d)
+    }
+
+    // Run the fixture, and return the number of words received
+    fn transfer(uut: &Fixture<Red, Blue>, period_w: u64, period_r: u64) -> miette::Result<usize> {
+        let output = uut
+            .run(clocks(period_w, period_r, 20_000))?
+            .sample_at_pos_edge(|t| t.value.0.cr_r.val().clock)
+            .map(|t| t.value.1)
+            .collect::<Vec<_>>();
+        assert!(output.iter().all(|o| o.valid.val()));
+        Ok(output.iter().filter(|o| o.xfer.val()).count())
+    }
+
+    #[test]
+    fn test_two_phase_transfer() -> miette::Result<()> {
+        let uut = Fixture::new(FIFODrainer::default());
+        assert!(transfer(&uut, 50, 78)? > 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_phase_fast_receiver() -> miette::Result<()> {
+        let uut = Fixture::new(FIFODrainer::default());
+        assert!(transfer(&uut, 77, 26)? > 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_phase_slow_receiver() -> miette::Result<()> {
+        // A slow clock, and a receiver that mostly sleeps
+        let uut = Fixture::new(FIFODrainer::new(15, 0.1));
+        assert!(transfer(&uut, 50, 313)? > 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_phase_metastability() -> miette::Result<()> {
+        use crate::cdc::metastability::{inject, Metastability};
+        for seed in 0..4 {
+            let uut = Fixture::new(FIFODrainer::default());
+            let count = inject(Metastability::new(seed).probability(0.5), || {
+                transfer(&uut, 50, 78)
+            })?;
+            assert!(count > 100);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_phase_hdl() -> miette::Result<()> {
+        let uut = Fixture::<Red, Blue>::new(FIFODrainer::default());
+        let test_bench = uut.run(clocks(50, 78, 1_000))?.collect::<TestBench<_, _>>();
+        let tm = test_bench.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = test_bench.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
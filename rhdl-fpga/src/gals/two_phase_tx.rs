@@ -0,0 +1,116 @@
+//! Two phase handshake transmitter
+//!
+//!# Purpose
+//!
+//! The [TwoPhaseTx] core sends a stream of words to a
+//! [TwoPhaseRx](super::TwoPhaseRx) in an unrelated clock domain
+//! using a two phase handshake.  Each transfer is signalled by a
+//! transition on `req`, and acknowledged by a transition on `ack`.
+//! A transfer is pending whenever `req != ack`, and the data bundle
+//! is held stable for that entire time.
+#![doc = badascii_doc::badascii!("
+          +---------------------+
+req  +----+                     +-------------------
+                   +----------------------+
+ack  +-------------+                      +---------
+     +------------++----------------------+--------+
+data |    X       |           A           |   B    |
+     +------------++----------------------+--------+
+")]
+//!
+//!# Connections
+//!
+//! Here is the schematic symbol for the transmitter.
+#![doc = badascii_doc::badascii_formal!("
+      +-+TwoPhaseTx+---+
+  ?bN |                | bool
++---->| data       req +---->
+ bool |                | bN
+<-----+ ready     data +---->
+      |                | bool
+      |            ack |<---+
+      |                |
++---->| cr             |
+      +----------------+
+")]
+//!
+//! The `data` and `ready` signals are in the `W` domain, and
+//! follow the FIFO convention.  A word is accepted in any cycle
+//! where `data` is `Some` and `ready` is asserted.  The `ack`
+//! input comes from the receiver in the `R` domain.
+//!
+//!# Internals
+//!
+//! The request and the data bundle are held in registers.  The
+//! acknowledge is brought into the `W` domain with a synchronizer,
+//! and the transmitter is ready whenever the synchronized `ack` has
+//! caught up with `req`.
+//!
+//!# Example
+//!
+//! See [TwoPhaseRx](super::TwoPhaseRx) for an example of a transmitter
+//! and receiver connected together.
+use rhdl::prelude::*;
+
+use crate::{cdc::synchronizer::Sync1Bit, core::dff};
+
+use super::{TxIn, TxOut};
+
+#[derive(Clone, Circuit, CircuitDQ, Default)]
+/// The transmitter for the two phase handshake.
+///
+/// The type parameters are:
+///   - `W`: The domain of the transmitter
+///   - `R`: The domain of the receiver
+///   - `N`: The number of bits in each word
+pub struct TwoPhaseTx<W: Domain, R: Domain, const N: usize>
+where
+    Const<N>: BitWidth,
+{
+    req: Adapter<dff::DFF<bool>, W>,
+    data: Adapter<dff::DFF<Bits<Const<N>>>, W>,
+    ack: Sync1Bit<R, W>,
+}
+
+impl<W: Domain, R: Domain, const N: usize> CircuitIO for TwoPhaseTx<W, R, N>
+where
+    Const<N>: BitWidth,
+{
+    type I = TxIn<W, R, N>;
+    type O = TxOut<W, N>;
+    type Kernel = two_phase_tx_kernel<W, R, N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn two_phase_tx_kernel<W: Domain, R: Domain, const N: usize>(
+    i: TxIn<W, R, N>,
+    q: Q<W, R, N>,
+) -> (TxOut<W, N>, D<W, R, N>)
+where
+    Const<N>: BitWidth,
+{
+    let mut d = D::<W, R, { N }>::dont_care();
+    d.req.clock_reset = i.cr;
+    d.data.clock_reset = i.cr;
+    d.ack.data = i.ack;
+    d.ack.cr = i.cr;
+    // Nothing is pending once the ack has caught up
+    let mut req = q.req.val();
+    let mut data = q.data.val();
+    let ready = req == q.ack.val();
+    if ready {
+        if let Some(word) = i.data.val() {
+            data = word;
+            req = !req;
+        }
+    }
+    d.req.input = signal(req);
+    d.data.input = signal(data);
+    let o = TxOut::<W, { N }> {
+        ready: signal(ready),
+        req: q.req,
+        data: q.data,
+    };
+    (o, d)
+}
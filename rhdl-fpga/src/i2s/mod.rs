@@ -0,0 +1,123 @@
+#![warn(missing_docs)]
+//! Serial audio cores
+//!
+//! Audio codecs move samples over a serial link with a bit clock
+//! (`BCLK`), a frame sync (`FS`, also known as the word select or `LRCLK`)
+//! and a serial data line (`SD`).  The cores in this module are
+//! clocked by the bit clock, so that one bit is moved on each clock.
+//!
+//! A multi-channel codec uses time division multiplexed (TDM) frames.
+//! Each frame is split into `S` slots of (typically) 16 or 32 bits,
+//! with one channel in each slot.  The frame sync marks the start of
+//! the frame.  The [TdmTx] core serializes an array of `S` channel
+//! samples into a frame, and the [TdmRx] core recovers them.
+//!
+//! The details of the frame vary between codecs, and so they are
+//! described by a [TdmConfig]:
+//!   - The number of bits in each slot, which must be at least as wide
+//!     as the sample.
+//!   - Where the sample sits in a slot that is wider than the sample
+//!     ([Justify]).
+//!   - The shape of the frame sync ([FrameSync]).  This is either a
+//!     single bit clock wide pulse, or a 50% duty cycle square wave.
+//!   - Whether the first bit of slot 0 coincides with the start of
+//!     the frame sync, or follows it by one bit clock ([SlotAlign]).  The
+//!     latter is the convention of I2S (and of many TDM codecs), and
+//!     getting this wrong shifts every channel by a bit.
+//!
+//! Samples are sent MSB first.
+use rhdl::prelude::*;
+
+pub mod tdm_rx;
+pub mod tdm_tx;
+
+pub use tdm_rx::TdmRx;
+pub use tdm_tx::TdmTx;
+
+/// The position of a sample within a slot that is
+/// wider than the sample.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Justify {
+    /// The sample is at the start of the slot, and is followed by zeros
+    #[default]
+    Left,
+    /// The sample is at the end of the slot, and is preceded by zeros
+    Right,
+}
+
+/// The shape of the frame sync
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum FrameSync {
+    /// A pulse, one bit clock wide, at the start of each frame
+    #[default]
+    Pulse,
+    /// High for the first half of each frame, and low for the second
+    Duty50,
+}
+
+/// The alignment of slot 0 relative to the frame sync
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum SlotAlign {
+    /// The first bit of slot 0 is sent with the start of the frame sync
+    #[default]
+    WithSync,
+    /// The first bit of slot 0 is sent one bit clock after the start
+    /// of the frame sync (as in I2S)
+    Delayed,
+}
+
+/// The layout of a TDM frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TdmConfig {
+    /// The number of bits in each slot
+    pub slot_bits: u8,
+    /// The position of the sample within each slot
+    pub justify: Justify,
+    /// The shape of the frame sync
+    pub sync: FrameSync,
+    /// The alignment of slot 0 relative to the frame sync
+    pub align: SlotAlign,
+}
+
+impl Default for TdmConfig {
+    fn default() -> Self {
+        Self {
+            slot_bits: 32,
+            justify: Justify::default(),
+            sync: FrameSync::default(),
+            align: SlotAlign::default(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub struct Layout {
+    pub slot_bits: b8,
+    pub width: b8,
+    pub frame_bits: b16,
+    pub half: b16,
+    pub right: bool,
+    pub pulse: bool,
+    pub delay: bool,
+}
+
+impl TdmConfig {
+    // The layout of the frame, as seen by the hardware
+    pub(crate) fn layout(&self, width: usize, slots: usize) -> Layout {
+        assert!(
+            self.slot_bits as usize >= width,
+            "The slots must be at least as wide as the samples"
+        );
+        let frame_bits = self.slot_bits as u128 * slots as u128;
+        Layout {
+            slot_bits: bits(self.slot_bits as u128),
+            width: bits(width as u128),
+            frame_bits: bits(frame_bits),
+            half: bits(frame_bits / 2),
+            right: self.justify == Justify::Right,
+            pulse: self.sync == FrameSync::Pulse,
+            delay: self.align == SlotAlign::Delayed,
+        }
+    }
+}
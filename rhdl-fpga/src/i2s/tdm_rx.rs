@@ -0,0 +1,391 @@
+//! TDM Frame Receiver
+//!
+//!# Purpose
+//!
+//! The [TdmRx] core recovers frames of `S` channel samples (each
+//! of `N` bits) from a TDM serial link.  The layout of the frame is
+//! given by a [TdmConfig], and must match that of the transmitter.
+//!
+//!# Connections
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+TdmRx+------------+
+ bool |                    | ?[bN; S]
++---->| sd            data +-------->
+ bool |                    | bool
++---->| fs         overrun +---->
+ bool |                    |
++---->| next               |
+      +--------------------+
+")]
+//!
+//! The core is clocked by the bit clock, and samples `sd` and `fs`
+//! on each clock.  The start of a frame is marked by the rising edge of
+//! `fs`, so that either shape of frame sync can be used.  The receiver
+//! ignores the link until it has seen the start of a frame.
+//!
+//! Each received frame is held on the `data` output until `next` is
+//! asserted, following the FIFO convention.  If a new frame arrives
+//! before the previous one was taken, the previous frame is replaced,
+//! and `overrun` is asserted for a clock.
+//!
+//!# Example
+//!
+//! See the [TdmTx](super::TdmTx) for the frame timing.  A receiver for
+//! 4 channels of 16 bit samples in 16 bit slots.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::i2s::{TdmConfig, TdmRx};
+//!
+//! let uut = TdmRx::<U16, 4>::new(TdmConfig {
+//!     slot_bits: 16,
+//!     ..Default::default()
+//! });
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+use super::{Layout, TdmConfig};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The TDM receiver
+///
+/// The type parameters are:
+///   - `N`: The number of bits in each sample
+///   - `S`: The number of slots in each frame
+pub struct TdmRx<N: BitWidth, const S: usize> {
+    layout: constant::Constant<Layout>,
+    fs: dff::DFF<bool>,
+    start: dff::DFF<bool>,
+    synced: dff::DFF<bool>,
+    bit: dff::DFF<b8>,
+    pos: dff::DFF<b16>,
+    sample: dff::DFF<Bits<N>>,
+    slots: dff::DFF<[Bits<N>; S]>,
+    frame: dff::DFF<[Bits<N>; S]>,
+    full: dff::DFF<bool>,
+}
+
+impl<N: BitWidth, const S: usize> TdmRx<N, S> {
+    /// Create a new [TdmRx] with the given frame layout
+    pub fn new(config: TdmConfig) -> Self {
+        Self {
+            layout: constant::Constant::new(config.layout(N::BITS, S)),
+            fs: dff::DFF::new(false),
+            start: dff::DFF::new(false),
+            synced: dff::DFF::new(false),
+            bit: dff::DFF::new(b8(0)),
+            pos: dff::DFF::new(b16(0)),
+            sample: dff::DFF::new(Bits::<N>::default()),
+            slots: dff::DFF::new([Bits::<N>::default(); S]),
+            frame: dff::DFF::new([Bits::<N>::default(); S]),
+            full: dff::DFF::new(false),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The inputs to the [TdmRx]
+pub struct In {
+    /// The serial data
+    pub sd: bool,
+    /// The frame sync
+    pub fs: bool,
+    /// The frame on the output is taken in this clock
+    pub next: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [TdmRx]
+pub struct Out<N: BitWidth, const S: usize> {
+    /// The most recently received frame
+    pub data: Option<[Bits<N>; S]>,
+    /// A frame was dropped, because it was not taken in time
+    pub overrun: bool,
+}
+
+impl<N: BitWidth, const S: usize> SynchronousIO for TdmRx<N, S> {
+    type I = In;
+    type O = Out<N, S>;
+    type Kernel = tdm_rx_kernel<N, S>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn tdm_rx_kernel<N: BitWidth, const S: usize>(
+    _cr: ClockReset,
+    i: In,
+    q: Q<N, S>,
+) -> (Out<N, S>, D<N, S>) {
+    let l = q.layout;
+    let mut d = D::<N, { S }>::dont_care();
+    // A frame starts on the rising edge of the frame sync, and
+    // slot 0 starts then, or a clock later.
+    let start = i.fs && !q.fs;
+    d.fs = i.fs;
+    d.start = start;
+    let sof = if l.delay { q.start } else { start };
+    let synced = q.synced || sof;
+    d.synced = synced;
+    // The position of the current bit
+    let bit = if sof { bits(0) } else { q.bit };
+    let pos = if sof { bits(0) } else { q.pos };
+    let last_bit = bit == l.slot_bits - 1;
+    let last = pos == l.frame_bits - 1;
+    d.bit = if last_bit { bits(0) } else { bit + 1 };
+    d.pos = if last { bits(0) } else { pos + 1 };
+    // The bits of the slot that carry the sample
+    let active = if l.right {
+        bit >= l.slot_bits - l.width
+    } else {
+        bit < l.width
+    };
+    let mut sample = q.sample;
+    if active {
+        sample <<= 1;
+        if i.sd {
+            sample |= bits(1);
+        }
+    }
+    d.sample = sample;
+    // Completed slots are shifted in at the end
+    d.slots = q.slots;
+    if last_bit {
+        for j in 1..S {
+            d.slots[j - 1] = q.slots[j];
+        }
+        d.slots[S - 1] = sample;
+        d.sample = bits(0);
+    }
+    d.frame = q.frame;
+    d.full = q.full && !i.next;
+    let mut overrun = false;
+    if synced && last {
+        overrun = d.full;
+        d.frame = d.slots;
+        d.full = true;
+    }
+    let mut data = None;
+    if q.full {
+        data = Some(q.frame);
+    }
+    (Out::<N, { S }> { data, overrun }, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rhdl::core::sim::ResetOrData;
+
+    use super::*;
+    use crate::i2s::{FrameSync, Justify, SlotAlign, TdmTx};
+
+    #[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+    struct Loopback<N: BitWidth, const S: usize> {
+        tx: TdmTx<N, S>,
+        rx: TdmRx<N, S>,
+    }
+
+    #[derive(PartialEq, Debug, Digital)]
+    struct LoopOut<N: BitWidth, const S: usize> {
+        next: bool,
+        data: Option<[Bits<N>; S]>,
+    }
+
+    impl<N: BitWidth, const S: usize> SynchronousIO for Loopback<N, S> {
+        type I = Option<[Bits<N>; S]>;
+        type O = LoopOut<N, S>;
+        type Kernel = loopback_kernel<N, S>;
+    }
+
+    #[kernel]
+    fn loopback_kernel<N: BitWidth, const S: usize>(
+        _cr: ClockReset,
+        i: Option<[Bits<N>; S]>,
+        q: Q<N, S>,
+    ) -> (LoopOut<N, S>, D<N, S>) {
+        let mut d = D::<N, { S }>::dont_care();
+        d.tx = i;
+        d.rx = In {
+            sd: q.tx.sd,
+            fs: q.tx.fs,
+            next: true,
+        };
+        let o = LoopOut::<N, { S }> {
+            next: q.tx.next,
+            data: q.rx.data,
+        };
+        (o, d)
+    }
+
+    impl<N: BitWidth, const S: usize> Loopback<N, S> {
+        fn new(config: TdmConfig) -> Self {
+            Self {
+                tx: TdmTx::new(config),
+                rx: TdmRx::new(config),
+            }
+        }
+    }
+
+    // The sample for a channel in a frame.  Each has a distinct
+    // MSB and LSB, so that an off by one shows up.
+    fn sample(frame: usize, channel: usize) -> b16 {
+        bits(0x8001 | ((frame as u128) << 8) | ((channel as u128) << 4))
+    }
+
+    fn frame<const S: usize>(frame: usize) -> [b16; S] {
+        std::array::from_fn(|channel| sample(frame, channel))
+    }
+
+    // Send the frames through a transmitter and receiver, and return what was received
+    fn loopback<const S: usize>(config: TdmConfig, count: usize) -> miette::Result<Vec<[b16; S]>> {
+        let uut = Loopback::<U16, S>::new(config);
+        let mut need_reset = true;
+        let mut index = 0;
+        let frame_bits = config.slot_bits as usize * S;
+        let mut clocks = 0;
+        Ok(uut
+            .run_fn(
+                |o| {
+                    if need_reset {
+                        need_reset = false;
+                        return Some(ResetOrData::Reset);
+                    }
+                    clocks += 1;
+                    if clocks > (count + 3) * frame_bits {
+                        return None;
+                    }
+                    // The transmitter output is registered, so the
+                    // frame is taken at the end of this clock
+                    let input = (index < count).then(|| frame::<S>(index));
+                    if o.next {
+                        index += 1;
+                    }
+                    Some(ResetOrData::Data(input))
+                },
+                100,
+            )
+            .synchronous_sample()
+            .filter_map(|t| t.value.2.data)
+            .collect())
+    }
+
+    fn check<const S: usize>(config: TdmConfig) -> miette::Result<()> {
+        let received = loopback::<S>(config, 4)?;
+        // The frames are followed by frames of zeros, once the
+        // transmitter runs out of data
+        let mut expected = (0..4).map(frame::<S>).collect::<Vec<_>>();
+        expected.push([bits(0); S]);
+        assert_eq!(received[0..5], expected, "{config:?}");
+        Ok(())
+    }
+
+    fn configs() -> impl Iterator<Item = TdmConfig> {
+        [FrameSync::Pulse, FrameSync::Duty50]
+            .into_iter()
+            .flat_map(|sync| {
+                [SlotAlign::WithSync, SlotAlign::Delayed]
+                    .into_iter()
+                    .map(move |align| (sync, align))
+            })
+            .flat_map(|(sync, align)| {
+                [
+                    (16, Justify::Left),
+                    (32, Justify::Left),
+                    (32, Justify::Right),
+                ]
+                .into_iter()
+                .map(move |(slot_bits, justify)| TdmConfig {
+                    slot_bits,
+                    justify,
+                    sync,
+                    align,
+                })
+            })
+    }
+
+    #[test]
+    fn test_loopback_2_slots() -> miette::Result<()> {
+        configs().try_for_each(check::<2>)
+    }
+
+    #[test]
+    fn test_loopback_4_slots() -> miette::Result<()> {
+        configs().try_for_each(check::<4>)
+    }
+
+    #[test]
+    fn test_loopback_8_slots() -> miette::Result<()> {
+        configs().try_for_each(check::<8>)
+    }
+
+    #[test]
+    fn test_misaligned_slots() -> miette::Result<()> {
+        // A receiver expecting the I2S alignment, fed by a transmitter
+        // without the delay, sees every sample shifted by a bit.
+        let tx = TdmConfig {
+            slot_bits: 16,
+            ..Default::default()
+        };
+        let rx = TdmConfig {
+            align: SlotAlign::Delayed,
+            ..tx
+        };
+        let uut = Loopback::<U16, 2> {
+            tx: TdmTx::new(tx),
+            rx: TdmRx::new(rx),
+        };
+        let input = std::iter::repeat_n(Some(frame::<2>(1)), 100);
+        let received = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .filter_map(|t| t.value.2.data)
+            .last()
+            .unwrap();
+        assert_eq!(received[0], (sample(1, 0) << 1) | (sample(1, 1) >> 15));
+        Ok(())
+    }
+
+    #[test]
+    fn test_overrun() -> miette::Result<()> {
+        let uut = TdmRx::<U8, 2>::new(TdmConfig {
+            slot_bits: 8,
+            ..Default::default()
+        });
+        // A frame sync every 16 bits, and nobody taking the frames
+        let input = (0..64).map(|n| In {
+            sd: n % 3 == 0,
+            fs: n % 16 == 0,
+            next: false,
+        });
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let overruns = output.iter().filter(|o| o.overrun).count();
+        assert_eq!(overruns, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tdm_loopback_hdl() -> miette::Result<()> {
+        let uut = Loopback::<U16, 4>::new(TdmConfig {
+            slot_bits: 16,
+            sync: FrameSync::Duty50,
+            align: SlotAlign::Delayed,
+            ..Default::default()
+        });
+        let input = (0..300).map(|n| Some(frame::<4>(n / 64)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+//! TDM Frame Transmitter
+//!
+//!# Purpose
+//!
+//! The [TdmTx] core serializes frames of `S` channel samples (each
+//! of `N` bits) onto a TDM serial link, generating the frame sync
+//! as it goes.  The layout of the frame is given by a [TdmConfig].
+//!
+//!# Connections
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+           +-+TdmTx+------+
+ ?[bN; S]  |              | bool
+ +-------->| input     sd +---->
+           |              | bool
+           |           fs +---->
+           |              | bool
+           |         next +---->
+           |              | bool
+           |     underrun +---->
+           +--------------+
+")]
+//!
+//! The core is clocked by the bit clock.  The next frame is taken
+//! from the input in the clock where `next` is asserted (which is the
+//! last bit clock of the current frame).  If no frame is available
+//! then, a frame of zeros is sent instead, and `underrun` is asserted.
+//!
+//!# Timing
+//!
+//! Here is a frame with 2 slots of 4 bits (and 3 bit samples), a
+//! pulsed frame sync, and slot 0 delayed by one bit clock.
+#![doc = badascii_doc::badascii!("
+       +-+ +-+ +-+ +-+ +-+ +-+ +-+ +-+ +-+ +-+
+bclk +-+ +-+ +-+ +-+ +-+ +-+ +-+ +-+ +-+ +-+ +
+     +---+                           +---+
+fs   +   +---------------------------+   +----
+     +---+---+---+---+---+---+---+---+---+---+
+sd   |   |A2 |A1 |A0 | 0 |B2 |B1 |B0 | 0 |A2 |
+     +---+---+---+---+---+---+---+---+---+---+
+")]
+//!
+//!# Example
+//!
+//! A transmitter for 8 channels of 24 bit samples in 32 bit slots,
+//! with a 50% duty frame sync, and the I2S convention for slot 0.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::i2s::{FrameSync, SlotAlign, TdmConfig, TdmTx};
+//!
+//! let uut = TdmTx::<U24, 8>::new(TdmConfig {
+//!     slot_bits: 32,
+//!     sync: FrameSync::Duty50,
+//!     align: SlotAlign::Delayed,
+//!     ..Default::default()
+//! });
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+use super::{Layout, TdmConfig};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The TDM transmitter
+///
+/// The type parameters are:
+///   - `N`: The number of bits in each sample
+///   - `S`: The number of slots in each frame
+pub struct TdmTx<N: BitWidth, const S: usize> {
+    layout: constant::Constant<Layout>,
+    slots: dff::DFF<[Bits<N>; S]>,
+    bit: dff::DFF<b8>,
+    pos: dff::DFF<b16>,
+}
+
+impl<N: BitWidth, const S: usize> TdmTx<N, S> {
+    /// Create a new [TdmTx] with the given frame layout
+    pub fn new(config: TdmConfig) -> Self {
+        let layout = config.layout(N::BITS, S);
+        Self {
+            layout: constant::Constant::new(layout),
+            slots: dff::DFF::new([Bits::<N>::default(); S]),
+            bit: dff::DFF::new(layout.slot_bits - 1),
+            // Start on the last bit, so that the first frame is loaded
+            // as soon as reset is released
+            pos: dff::DFF::new(layout.frame_bits - 1),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [TdmTx]
+pub struct Out {
+    /// The serial data
+    pub sd: bool,
+    /// The frame sync
+    pub fs: bool,
+    /// The input frame is taken in this clock
+    pub next: bool,
+    /// No frame was available when one was needed
+    pub underrun: bool,
+}
+
+impl<N: BitWidth, const S: usize> SynchronousIO for TdmTx<N, S> {
+    type I = Option<[Bits<N>; S]>;
+    type O = Out;
+    type Kernel = tdm_tx_kernel<N, S>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn tdm_tx_kernel<N: BitWidth, const S: usize>(
+    _cr: ClockReset,
+    i: Option<[Bits<N>; S]>,
+    q: Q<N, S>,
+) -> (Out, D<N, S>) {
+    let l = q.layout;
+    let mut d = D::<N, { S }>::dont_care();
+    let last_bit = q.bit == l.slot_bits - 1;
+    let last = q.pos == l.frame_bits - 1;
+    // The bits of the slot that carry the sample
+    let active = if l.right {
+        q.bit >= l.slot_bits - l.width
+    } else {
+        q.bit < l.width
+    };
+    // The current slot is always in slot 0, and is shifted out MSB first
+    let sd = active && (q.slots[0] & (1 << (N::BITS - 1))) != 0;
+    d.slots = q.slots;
+    if active {
+        d.slots[0] = q.slots[0] << 1;
+    }
+    if last_bit {
+        for j in 1..S {
+            d.slots[j - 1] = q.slots[j];
+        }
+        d.slots[S - 1] = bits(0);
+    }
+    let mut underrun = false;
+    if last {
+        if let Some(frame) = i {
+            d.slots = frame;
+        } else {
+            for j in 0..S {
+                d.slots[j] = bits(0);
+            }
+            underrun = true;
+        }
+    }
+    d.bit = if last_bit { bits(0) } else { q.bit + 1 };
+    d.pos = if last { bits(0) } else { q.pos + 1 };
+    // The position in the frame, as marked by the frame sync
+    let mut sync_pos = q.pos;
+    if l.delay {
+        sync_pos = d.pos;
+    }
+    let fs = if l.pulse {
+        sync_pos == 0
+    } else {
+        sync_pos < l.half
+    };
+    let o = Out {
+        sd,
+        fs,
+        next: last,
+        underrun,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i2s::{FrameSync, Justify, SlotAlign};
+
+    // Send a frame of two 3 bit samples in 4 bit slots, and return the
+    // (fs, sd) bits of the second frame, and one bit either side
+    fn waveform(config: TdmConfig) -> miette::Result<Vec<(bool, bool)>> {
+        let uut = TdmTx::<U3, 2>::new(TdmConfig {
+            slot_bits: 4,
+            ..config
+        });
+        let input = std::iter::repeat_n(Some([b3(0b101), b3(0b011)]), 20);
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            // Skip the reset, and the first frame (less a bit)
+            .skip(9)
+            .map(|t| (t.value.2.fs, t.value.2.sd))
+            .take(10)
+            .collect())
+    }
+
+    fn fs(w: &[(bool, bool)]) -> String {
+        w.iter()
+            .map(|(fs, _)| if *fs { '1' } else { '0' })
+            .collect()
+    }
+
+    fn sd(w: &[(bool, bool)]) -> String {
+        w.iter()
+            .map(|(_, sd)| if *sd { '1' } else { '0' })
+            .collect()
+    }
+
+    #[test]
+    fn test_pulse_with_sync() -> miette::Result<()> {
+        let w = waveform(TdmConfig::default())?;
+        assert_eq!(fs(&w), "0100000001");
+        assert_eq!(sd(&w), "0101001101");
+        Ok(())
+    }
+
+    #[test]
+    fn test_pulse_delayed() -> miette::Result<()> {
+        let w = waveform(TdmConfig {
+            align: SlotAlign::Delayed,
+            ..Default::default()
+        })?;
+        assert_eq!(fs(&w), "1000000010");
+        assert_eq!(sd(&w), "0101001101");
+        Ok(())
+    }
+
+    #[test]
+    fn test_duty_right_justified() -> miette::Result<()> {
+        let w = waveform(TdmConfig {
+            sync: FrameSync::Duty50,
+            justify: Justify::Right,
+            ..Default::default()
+        })?;
+        assert_eq!(fs(&w), "0111100001");
+        assert_eq!(sd(&w), "1010100110");
+        Ok(())
+    }
+
+    #[test]
+    fn test_underrun() -> miette::Result<()> {
+        let uut = TdmTx::<U8, 2>::new(TdmConfig {
+            slot_bits: 8,
+            ..Default::default()
+        });
+        let input =
+            std::iter::once(Some([b8(0xFF), b8(0xFF)])).chain(std::iter::repeat_n(None, 40));
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The first frame is sent, and the rest are zeros
+        assert!(output[1..17].iter().all(|o| o.sd));
+        assert!(output[17..].iter().all(|o| !o.sd));
+        let underruns = output.iter().filter(|o| o.underrun).count();
+        assert_eq!(underruns, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tdm_tx_hdl() -> miette::Result<()> {
+        let uut = TdmTx::<U3, 2>::new(TdmConfig {
+            slot_bits: 4,
+            sync: FrameSync::Duty50,
+            align: SlotAlign::Delayed,
+            ..Default::default()
+        });
+        let input = (0..50).map(|n| (n % 7 != 0).then(|| [bits(n % 8), bits((n + 3) % 8)]));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
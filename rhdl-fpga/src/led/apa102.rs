@@ -0,0 +1,435 @@
+//! APA102/SK9822 LED Strip Driver
+//!
+//! The APA102 (and the compatible SK9822) are addressable RGB
+//! LEDs that are daisy chained with a clock and a data line.  Unlike
+//! the single wire WS2812, the data is sampled on the rising edge of
+//! the clock, so there are no tight timing requirements.  An update
+//! of a strip of `n` LEDs consists of:
+//!
+//! - a start frame of 32 zero bits,
+//! - `n` LED frames of 32 bits each.  Each frame is `0b111`, followed
+//!   by a 5 bit global brightness, and then the blue, green and red
+//!   values (8 bits each), all sent MSB first.
+//! - an end frame.  Each LED delays the data by half a clock, so
+//!   at least `n/2` further clocks are needed to push the data to
+//!   the end of the strip.  The [Apa102Driver] sends `ceil(n/64)`
+//!   words of zeros.  Because these are zeros, the first word also
+//!   serves as the latch (reset) frame needed by the SK9822.
+//!
+//! The [Apa102Driver] takes a stream of [Pixel]s, with a ready/valid
+//! handshake (see [crate::stream]).  The number of LEDs in the strip is
+//! provided when the core is constructed.  An update starts when the first
+//! pixel arrives, and ends after the end frame.  The clock runs at
+//! `1/(2*half_period)` of the system clock, with each half of the clock
+//! lasting exactly `half_period` clocks.  The data changes on the falling
+//! edge of the clock.
+//!
+//! If the stream stalls part way through a strip, the driver stalls the
+//! clock (holding it low) until the next pixel arrives.  The LEDs simply
+//! wait, so the update completes correctly once the stream resumes.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+Apa102Driver+---+
+ ?Pixel |                  | bool
+ +----->| data        sclk +----->
+ R<Pix> |                  | bool
+ <------+ ready        sdo +----->
+        +------------------+
+")]
+//!
+//!# Example
+//!
+//! Refreshing a strip of 3 LEDs with a single color, with a clock
+//! of 1/4 the system clock.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::led::apa102::{Apa102Driver, Pixel};
+//!
+//! let uut = Apa102Driver::<U8>::new(3, 2);
+//! let pixel = Pixel { brightness: b5(31), red: b8(255), green: b8(0), blue: b8(0) };
+//! let input = std::iter::repeat_n(Some(pixel), 1000);
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! // Each update is 6 words (start, 3 pixels and the end frame)
+//! let edges = output.windows(2).filter(|w| !w[0].sclk && w[1].sclk).count();
+//! assert!(edges > 32 * 6);
+//! ```
+use rhdl::prelude::*;
+
+use crate::{
+    core::{constant, dff},
+    stream::{ready, Ready},
+};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The color and brightness of a single LED
+pub struct Pixel {
+    /// The global brightness (0 is off, 31 is full)
+    pub brightness: b5,
+    /// The red value
+    pub red: b8,
+    /// The green value
+    pub green: b8,
+    /// The blue value
+    pub blue: b8,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [Apa102Driver]
+pub struct Out {
+    /// The clock to the strip
+    pub sclk: bool,
+    /// The data to the strip
+    pub sdo: bool,
+    /// The ready signal to the pixel stream
+    pub ready: Ready<Pixel>,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum State {
+    #[default]
+    Idle,
+    Start,
+    WaitPixel,
+    Pixel,
+    End,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The APA102 driver core.  Here `N` is the width of
+/// the LED counter, so the strip can hold up to `2^N` LEDs.
+pub struct Apa102Driver<N: BitWidth> {
+    last_pixel: constant::Constant<Bits<N>>,
+    last_end_word: constant::Constant<Bits<N>>,
+    last_phase: constant::Constant<b16>,
+    state: dff::DFF<State>,
+    word: dff::DFF<b32>,
+    bit: dff::DFF<b5>,
+    count: dff::DFF<Bits<N>>,
+    phase: dff::DFF<b16>,
+    sclk: dff::DFF<bool>,
+}
+
+impl<N: BitWidth> Apa102Driver<N> {
+    /// Create a driver for a strip of `pixels` LEDs, with each
+    /// half of the clock lasting `half_period` system clocks.
+    pub fn new(pixels: usize, half_period: u16) -> Self {
+        assert!(
+            pixels > 0 && pixels <= 1 << N::BITS,
+            "The strip must have between 1 and 2^N LEDs"
+        );
+        assert!(half_period > 0, "The half period must be at least 1");
+        Self {
+            last_pixel: constant::Constant::new(bits(pixels as u128 - 1)),
+            last_end_word: constant::Constant::new(bits(pixels.div_ceil(64) as u128 - 1)),
+            last_phase: constant::Constant::new(bits(half_period as u128 - 1)),
+            state: dff::DFF::default(),
+            word: dff::DFF::default(),
+            bit: dff::DFF::default(),
+            count: dff::DFF::default(),
+            phase: dff::DFF::default(),
+            sclk: dff::DFF::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for Apa102Driver<N> {
+    type I = Option<Pixel>;
+    type O = Out;
+    type Kernel = apa102_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+/// The 32 bit LED frame for a pixel
+pub fn led_frame(p: Pixel) -> b32 {
+    let brightness: b32 = p.brightness.resize();
+    let blue: b32 = p.blue.resize();
+    let green: b32 = p.green.resize();
+    let red: b32 = p.red.resize();
+    bits(0xE0000000) | (brightness << 24) | (blue << 16) | (green << 8) | red
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn apa102_kernel<N: BitWidth>(cr: ClockReset, i: Option<Pixel>, q: Q<N>) -> (Out, D<N>) {
+    let mut d = D::<N>::dont_care();
+    d.state = q.state;
+    d.word = q.word;
+    d.bit = q.bit;
+    d.count = q.count;
+    d.sclk = q.sclk;
+    // The divider is restarted whenever the clock resumes,
+    // so that every half of the clock has the full length
+    let tick = q.phase == q.last_phase;
+    d.phase = if tick { bits(0) } else { q.phase + 1 };
+    let waiting = q.state == State::WaitPixel;
+    match q.state {
+        State::Idle => {
+            if let Some(_p) = i {
+                d.state = State::Start;
+                d.word = bits(0);
+                d.bit = bits(0);
+                d.phase = bits(0);
+            }
+        }
+        State::WaitPixel => {
+            if let Some(p) = i {
+                d.state = State::Pixel;
+                d.word = led_frame(p);
+                d.bit = bits(0);
+                d.phase = bits(0);
+            }
+        }
+        _ => {
+            if tick {
+                // The LEDs sample the data on the rising edge,
+                // and the data changes on the falling edge
+                d.sclk = !q.sclk;
+                if q.sclk {
+                    d.word = q.word << 1;
+                    d.bit = q.bit + 1;
+                    if q.bit.all() {
+                        // The end of a 32 bit word
+                        match q.state {
+                            State::Start => {
+                                d.state = State::WaitPixel;
+                                d.count = bits(0);
+                            }
+                            State::Pixel => {
+                                d.count = q.count + 1;
+                                d.state = State::WaitPixel;
+                                if q.count == q.last_pixel {
+                                    d.count = bits(0);
+                                    d.state = State::End;
+                                }
+                            }
+                            _ => {
+                                d.count = q.count + 1;
+                                if q.count == q.last_end_word {
+                                    d.state = State::Idle;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let o = Out {
+        sclk: q.sclk,
+        sdo: (q.word & bits(0x80000000)) != 0,
+        ready: ready::<Pixel>(waiting),
+    };
+    if cr.reset.any() {
+        d.state = State::Idle;
+        d.word = bits(0);
+        d.bit = bits(0);
+        d.count = bits(0);
+        d.phase = bits(0);
+        d.sclk = false;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rhdl::core::sim::ResetOrData;
+
+    use super::*;
+
+    fn pixel(n: u128) -> Pixel {
+        Pixel {
+            brightness: bits(n % 32),
+            red: bits((n * 7) % 256),
+            green: bits((n * 13 + 5) % 256),
+            blue: bits((n * 29 + 11) % 256),
+        }
+    }
+
+    // Run the driver, offering pixels from the stream and holding
+    // off for `stall` clocks after every `stall_every` pixels
+    fn run(
+        uut: &Apa102Driver<U8>,
+        pixels: &[Pixel],
+        stall_every: usize,
+        stall: usize,
+        len: usize,
+    ) -> miette::Result<Vec<Out>> {
+        let mut next = 0;
+        let mut hold = 0;
+        let mut clocks = 0;
+        let output = uut
+            .run_fn(
+                |out: Out| {
+                    clocks += 1;
+                    if clocks == 1 {
+                        return Some(ResetOrData::Reset);
+                    }
+                    if clocks > len {
+                        return None;
+                    }
+                    if hold > 0 {
+                        hold -= 1;
+                        return Some(ResetOrData::Data(None));
+                    }
+                    let offer = pixels.get(next).copied();
+                    // The pixel is accepted if the driver is ready
+                    if out.ready.raw && offer.is_some() {
+                        next += 1;
+                        if stall_every > 0 && next % stall_every == 0 {
+                            hold = stall;
+                        }
+                    }
+                    Some(ResetOrData::Data(offer))
+                },
+                100,
+            )
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect();
+        Ok(output)
+    }
+
+    // The data bits sampled on each rising edge of the clock
+    fn sampled(output: &[Out]) -> Vec<bool> {
+        output
+            .windows(2)
+            .filter(|w| !w[0].sclk && w[1].sclk)
+            .map(|w| w[1].sdo)
+            .collect()
+    }
+
+    fn words(sampled: &[bool]) -> Vec<u32> {
+        sampled
+            .chunks(32)
+            .map(|c| c.iter().fold(0, |w, b| (w << 1) | (*b as u32)))
+            .collect()
+    }
+
+    // Decode a strip update into the start word, the pixels
+    // and the number of end bits
+    fn decode(sampled: &[bool], pixels: usize) -> (u32, Vec<Pixel>, usize) {
+        let words = words(&sampled[..32 * (pixels + 1)]);
+        let decoded = words[1..]
+            .iter()
+            .map(|w| {
+                assert_eq!(w >> 29, 0b111);
+                Pixel {
+                    brightness: bits(((w >> 24) & 0x1f) as u128),
+                    blue: bits(((w >> 16) & 0xff) as u128),
+                    green: bits(((w >> 8) & 0xff) as u128),
+                    red: bits((w & 0xff) as u128),
+                }
+            })
+            .collect();
+        let end = &sampled[32 * (pixels + 1)..];
+        assert!(end.iter().all(|b| !b));
+        (words[0], decoded, end.len())
+    }
+
+    #[test]
+    fn test_short_strip_decodes() -> miette::Result<()> {
+        let uut = Apa102Driver::<U8>::new(5, 2);
+        let pixels = (0..5).map(pixel).collect::<Vec<_>>();
+        let output = run(&uut, &pixels, 0, 0, 2000)?;
+        let (start, decoded, end) = decode(&sampled(&output), 5);
+        assert_eq!(start, 0);
+        assert_eq!(decoded, pixels);
+        assert_eq!(end, 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clock_rate() -> miette::Result<()> {
+        let uut = Apa102Driver::<U8>::new(2, 3);
+        let output = run(&uut, &[pixel(1), pixel(2)], 0, 0, 1000)?;
+        // Every half of the clock lasts exactly 3 clocks
+        let mut runs = vec![];
+        let mut len = 1;
+        for w in output.windows(2) {
+            if w[0].sclk == w[1].sclk {
+                len += 1;
+            } else {
+                runs.push((w[0].sclk, len));
+                len = 1;
+            }
+        }
+        assert!(runs.iter().filter(|(high, _)| *high).all(|(_, n)| *n == 3));
+        assert_eq!(runs.iter().filter(|(high, _)| *high).count(), 32 * 4);
+        // The data only changes while the clock is low
+        for w in output.windows(2) {
+            if w[0].sclk && w[1].sclk {
+                assert_eq!(w[0].sdo, w[1].sdo);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_end_frame_length() -> miette::Result<()> {
+        for (leds, end_bits) in [(1, 32), (10, 32), (64, 32), (65, 64), (128, 64), (130, 96)] {
+            let uut = Apa102Driver::<U8>::new(leds, 1);
+            let pixels = (0..leds as u128).map(pixel).collect::<Vec<_>>();
+            let output = run(&uut, &pixels, 0, 0, 70 * (leds + 4))?;
+            let (_, decoded, end) = decode(&sampled(&output), leds);
+            assert_eq!(decoded, pixels);
+            assert_eq!(end, end_bits, "End frame for {leds} LEDs");
+            // At least n/2 clocks follow the last LED frame
+            assert!(end * 2 >= leds);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stalled_stream_stops_the_clock() -> miette::Result<()> {
+        let uut = Apa102Driver::<U8>::new(6, 2);
+        let pixels = (0..6).map(pixel).collect::<Vec<_>>();
+        let output = run(&uut, &pixels, 2, 300, 3000)?;
+        let (_, decoded, end) = decode(&sampled(&output), 6);
+        assert_eq!(decoded, pixels);
+        assert_eq!(end, 32);
+        // There are two stalls, during which the clock is held low
+        let idle = output
+            .windows(40)
+            .filter(|w| w.iter().all(|o| !o.sclk) && w[0].ready.raw)
+            .count();
+        assert!(idle > 0);
+        // The second update starts with a new start frame
+        let pixels = (0..12).map(pixel).collect::<Vec<_>>();
+        let output = run(&uut, &pixels, 0, 0, 6000)?;
+        let stream = sampled(&output);
+        let (_, first, _) = decode(&stream[..32 * 8], 6);
+        let (start, second, end) = decode(&stream[32 * 8..], 6);
+        assert_eq!(first, pixels[..6]);
+        assert_eq!(start, 0);
+        assert_eq!(second, pixels[6..]);
+        assert_eq!(end, 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apa102_hdl() -> miette::Result<()> {
+        let uut = Apa102Driver::<U8>::new(3, 2);
+        let mut input = vec![None; 3];
+        input.extend([Some(pixel(1)); 40]);
+        input.extend([None; 200]);
+        input.extend([Some(pixel(2)); 40]);
+        input.extend([None; 800]);
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
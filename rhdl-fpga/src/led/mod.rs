@@ -0,0 +1,3 @@
+//! LED Drivers
+pub mod apa102;
+pub mod pattern;
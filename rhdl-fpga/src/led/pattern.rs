@@ -0,0 +1,221 @@
+//! LED Pattern Generator
+//!
+//! Dims a single LED through a pattern of brightness levels.  The
+//! pattern is a program for the microcoded
+//! [Sequencer](crate::micro::sequencer::Sequencer), which sets the
+//! brightness in the low 8 bits of its outputs, and waits between
+//! steps.  A PWM dimmer with a period of 256 clocks turns the brightness
+//! into the LED drive, which is high for `brightness` clocks out of
+//! every 256.
+//!
+//! The [breathe] function builds the usual pattern, which steps through
+//! a list of levels, holding each one for a fixed time, and repeats
+//! forever.  Since the pattern is a program, it can also wait on the
+//! condition inputs of the sequencer (for example, a button), or halt
+//! after a single pass.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+          +-+LedPattern+------+
+ [bool;8] |                   | bool
+ +------->| conditions     led+----->
+          |                   | b8
+          |         brightness+----->
+          |                   | bool
+          |             halted+----->
+          +-------------------+
+")]
+//!
+//! The LED output is registered, and the brightness reported is the
+//! one in effect for the LED on the next clock.
+//!
+//!# Example
+//!
+//! A pattern that ramps up and down, with each level held for 4
+//! PWM periods.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::led::pattern::{breathe, LedPattern};
+//! use rhdl_fpga::micro::sequencer::In;
+//!
+//! let program = breathe(&[0, 64, 255, 64], 4 * 256 - 1);
+//! let uut = LedPattern::<9>::new(program.assemble().unwrap());
+//! let input = std::iter::repeat_n(In { conditions: [false; 8] }, 5000);
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert!(output.iter().any(|o| o.brightness == 255));
+//! assert!(output.iter().all(|o| !o.halted));
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    core::dff,
+    micro::{
+        program::Program,
+        sequencer::{self, Sequencer, Uop},
+    },
+};
+
+/// A program that steps through the brightness `levels`, holding each
+/// one for `dwell + 1` clocks, and then repeats
+pub fn breathe(levels: &[u8], dwell: u16) -> Program {
+    let mut program = Program::new();
+    program.label("top");
+    for &level in levels {
+        program.set(0x00FF, level as u16).wait(dwell);
+    }
+    program.jump("top");
+    program
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [LedPattern] core
+pub struct Out {
+    /// The LED drive
+    pub led: bool,
+    /// The current brightness
+    pub brightness: b8,
+    /// Set when the pattern program has halted
+    pub halted: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The LED pattern generator.  Here `UOPS` is the size of the
+/// ROM that holds the pattern program.
+pub struct LedPattern<const UOPS: usize> {
+    seq: Sequencer<UOPS>,
+    phase: dff::DFF<b8>,
+    led: dff::DFF<bool>,
+}
+
+impl<const UOPS: usize> LedPattern<UOPS> {
+    /// Create a [LedPattern] that runs the given (assembled) pattern
+    /// program.  The LED is dark until the program sets a brightness.
+    pub fn new(program: [Uop; UOPS]) -> Self {
+        Self {
+            seq: Sequencer::new(program, b16(0)),
+            phase: dff::DFF::default(),
+            led: dff::DFF::default(),
+        }
+    }
+}
+
+impl<const UOPS: usize> SynchronousIO for LedPattern<UOPS> {
+    type I = sequencer::In;
+    type O = Out;
+    type Kernel = led_pattern_kernel<UOPS>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn led_pattern_kernel<const UOPS: usize>(
+    _cr: ClockReset,
+    i: sequencer::In,
+    q: Q<UOPS>,
+) -> (Out, D<UOPS>) {
+    let brightness: b8 = q.seq.outputs.resize();
+    let d = D::<UOPS> {
+        seq: i,
+        phase: q.phase + 1,
+        led: q.phase < brightness,
+    };
+    let o = Out {
+        led: q.led,
+        brightness,
+        halted: q.seq.halted,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micro::sequencer::In;
+
+    const LEVELS: [u8; 6] = [0, 32, 128, 255, 128, 32];
+
+    fn run(program: Program, clocks: usize) -> miette::Result<Vec<Out>> {
+        let uut = LedPattern::<16>::new(program.assemble().unwrap());
+        let input = std::iter::repeat_n(
+            In {
+                conditions: [false; 8],
+            },
+            clocks,
+        );
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_led_duty_follows_pattern() -> miette::Result<()> {
+        let dwell = 3 * 256;
+        let output = run(
+            breathe(&LEVELS, dwell - 1),
+            2 * LEVELS.len() * dwell as usize,
+        )?;
+        // The steps of the pattern, in order, and twice over
+        let mut steps = output
+            .iter()
+            .map(|o| o.brightness.raw() as u8)
+            .collect::<Vec<_>>();
+        steps.dedup();
+        assert_eq!(steps[1..], [LEVELS, LEVELS].concat()[1..steps.len()]);
+        // The LED lags the brightness by a clock.  Over any 256 clocks
+        // with a steady brightness, it is lit for `brightness` clocks.
+        let mut windows = 0;
+        for start in (1..output.len() - 256).step_by(97) {
+            let brightness = output[start - 1].brightness;
+            if output[start - 1..start + 255]
+                .iter()
+                .all(|o| o.brightness == brightness)
+            {
+                let lit = output[start..start + 256].iter().filter(|o| o.led).count();
+                assert_eq!(lit as u128, brightness.raw());
+                windows += 1;
+            }
+        }
+        assert!(windows > 20);
+        assert!(output.iter().all(|o| !o.halted));
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_pass_halts_dark() -> miette::Result<()> {
+        // A pattern that flashes once, and then goes dark
+        let mut program = Program::new();
+        program.set(0x00FF, 200).wait(9).set(0x00FF, 0).halt();
+        let output = run(program, 40)?;
+        assert!(output[1..11].iter().all(|o| o.brightness == 200));
+        assert!(output[2..12].iter().all(|o| o.led));
+        assert!(output[13..].iter().all(|o| o.halted && !o.led));
+        Ok(())
+    }
+
+    #[test]
+    fn test_led_pattern_hdl() -> miette::Result<()> {
+        let uut = LedPattern::<16>::new(breathe(&[3, 250, 17], 40).assemble().unwrap());
+        let input = std::iter::repeat_n(
+            In {
+                conditions: [false; 8],
+            },
+            300,
+        );
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
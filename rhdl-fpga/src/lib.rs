@@ -1,16 +1,40 @@
 //! FPGA Support for RHDL
 #![warn(missing_docs)]
 pub mod axi4lite;
+pub mod bitops;
+pub mod boot;
 pub mod cdc;
+pub mod config;
+pub mod conformance;
+pub mod convert;
 pub mod core;
+pub mod crc;
+pub mod csr;
+pub mod decode;
 #[doc(hidden)]
 pub mod doc;
 pub mod dsp;
+pub mod event;
 pub mod fifo;
+pub mod gals;
 pub mod gray;
+pub mod i2s;
+pub mod led;
 pub mod lid;
+pub mod line;
+pub mod mem;
+pub mod micro;
+pub mod motion;
 pub mod pipe;
+pub mod primitive;
 pub mod reset;
 pub mod rng;
+pub mod sample;
+pub mod spi;
 pub mod stream;
+pub mod tb;
+pub mod timer;
+pub mod timing;
 pub mod tristate;
+pub mod uart;
+pub mod wishbone;
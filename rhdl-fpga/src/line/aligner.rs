@@ -0,0 +1,427 @@
+//! Word Aligner
+//!
+//!# Purpose
+//!
+//! Data from a deserializer arrives as `W` bit words, but the word
+//! boundaries of the deserializer bear no relation to those of the
+//! transmitter.  The transmitter periodically sends an alignment
+//! pattern (such as a comma, or a sync word), and the [WordAligner]
+//! searches the stream for it.  Once the pattern is found, the aligner
+//! applies the offset at which it was found (the bit slip) to the
+//! stream, so that the words it emits match those that were sent.
+//!
+//! The bits of the stream are sent MSB first.  The aligner keeps the
+//! previous word, and forms a two word window from it and the current
+//! word.  The word at offset `k` starts `k` bits into the previous word,
+//! so an offset of 0 emits the previous word unchanged.  Every offset is
+//! compared against the pattern on each word.
+//!
+//! - A hit is the pattern appearing at the current offset.  After
+//!   `lock_hits` consecutive hits, the `aligned` flag is set.
+//! - A miss is the pattern appearing at a different offset.  While the
+//!   aligner is not aligned, it moves to that offset immediately (which
+//!   counts as the first hit there).  Once aligned, it takes `loss_misses`
+//!   consecutive misses to drop the alignment, and move to the new offset.
+//!
+//! Words that do not contain the pattern at any offset are neither hits
+//! nor misses.  If `auto` is cleared in the [Config], the offset is only
+//! moved by the `bitslip` input, which advances it by one bit (wrapping
+//! at `W`), as for the bit slip control of a deserializer.  The hits and
+//! misses are still counted, so that `aligned` reports when the manual
+//! offset is correct.  A bit slip always clears the alignment.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+WordAligner+------+
+ ?bW  |                    | ?bW
++---->| data          data +---->
+ bool |                    | bool
++---->| bitslip    aligned +---->
+ Cfg  |                    | b8
++---->| config      offset +---->
+      +--------------------+
+")]
+//!
+//!# Timing
+//!
+//! Each word on the input produces an aligned word on the output one
+//! clock later.  The aligned word ends with bits of the word that was
+//! just presented, so the first word out after reset is not meaningful.
+//! The `aligned` flag and the `offset` apply to the word on the output.
+//!
+//!# Example
+//!
+//! An aligner for 10 bit words, looking for the K28.5 comma.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::line::aligner::{Config, WordAligner};
+//!
+//! let uut = WordAligner::<10>::default();
+//! let config = Config::<Const<10>> {
+//!     pattern: bits(0b0011111010),
+//!     lock_hits: b8(2),
+//!     loss_misses: b8(4),
+//!     auto: true,
+//! };
+//!```
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The configuration of the [WordAligner]
+pub struct Config<N: BitWidth> {
+    /// The alignment pattern
+    pub pattern: Bits<N>,
+    /// The number of consecutive hits needed to align
+    pub lock_hits: b8,
+    /// The number of consecutive misses that drop the alignment
+    pub loss_misses: b8,
+    /// Move to the offset of the pattern automatically
+    pub auto: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [WordAligner]
+pub struct In<N: BitWidth> {
+    /// The unaligned words
+    pub data: Option<Bits<N>>,
+    /// Strobe to advance the offset by one bit
+    pub bitslip: bool,
+    /// The configuration
+    pub config: Config<N>,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [WordAligner]
+pub struct Out<N: BitWidth> {
+    /// The aligned words
+    pub data: Option<Bits<N>>,
+    /// The pattern has been found at the current offset
+    pub aligned: bool,
+    /// The offset (in bits) applied to the stream
+    pub offset: b8,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The word aligner core
+///
+///   `W` is the number of bits in a word
+pub struct WordAligner<const W: usize>
+where
+    Const<W>: BitWidth,
+{
+    prev: dff::DFF<Bits<Const<W>>>,
+    offset: dff::DFF<b8>,
+    aligned: dff::DFF<bool>,
+    hits: dff::DFF<b8>,
+    misses: dff::DFF<b8>,
+    out: dff::DFF<Option<Bits<Const<W>>>>,
+}
+
+impl<const W: usize> Default for WordAligner<W>
+where
+    Const<W>: BitWidth,
+{
+    fn default() -> Self {
+        assert!(
+            (2..=64).contains(&W),
+            "The word aligner supports words of 2 to 64 bits"
+        );
+        Self {
+            prev: dff::DFF::new(bits(0)),
+            offset: dff::DFF::new(b8(0)),
+            aligned: dff::DFF::new(false),
+            hits: dff::DFF::new(b8(0)),
+            misses: dff::DFF::new(b8(0)),
+            out: dff::DFF::new(None),
+        }
+    }
+}
+
+impl<const W: usize> SynchronousIO for WordAligner<W>
+where
+    Const<W>: BitWidth,
+{
+    type I = In<Const<W>>;
+    type O = Out<Const<W>>;
+    type Kernel = word_aligner_kernel<W>;
+}
+
+#[kernel]
+/// Extract the word that starts `offset` bits into `prev`,
+/// from the window formed by `prev` and `cur`
+pub fn barrel<N: BitWidth>(prev: Bits<N>, cur: Bits<N>, offset: b8) -> Bits<N> {
+    let width: b8 = bits(N::BITS as u128);
+    let window = (prev.resize::<U128>() << width) | cur.resize::<U128>();
+    let shift = width - offset;
+    (window >> shift).resize::<N>()
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn word_aligner_kernel<const W: usize>(
+    _cr: ClockReset,
+    i: In<Const<W>>,
+    q: Q<W>,
+) -> (Out<Const<W>>, D<W>)
+where
+    Const<W>: BitWidth,
+{
+    let mut d = D::<W>::dont_care();
+    d.prev = q.prev;
+    d.offset = q.offset;
+    d.aligned = q.aligned;
+    d.hits = q.hits;
+    d.misses = q.misses;
+    d.out = None;
+    let config = i.config;
+    if let Some(word) = i.data {
+        d.prev = word;
+        d.out = Some(barrel::<Const<W>>(q.prev, word, q.offset));
+        // Search every offset, preferring the lowest
+        let mut found = false;
+        let mut at = b8(0);
+        for k in 0..W {
+            let k = W - 1 - k;
+            if barrel::<Const<W>>(q.prev, word, bits(k as u128)) == config.pattern {
+                found = true;
+                at = bits(k as u128);
+            }
+        }
+        let hit = barrel::<Const<W>>(q.prev, word, q.offset) == config.pattern;
+        if hit {
+            if q.hits != b8(255) {
+                d.hits = q.hits + 1;
+            }
+            d.misses = b8(0);
+            if d.hits >= config.lock_hits {
+                d.aligned = true;
+            }
+        } else if found {
+            d.hits = b8(0);
+            if q.misses != b8(255) {
+                d.misses = q.misses + 1;
+            }
+            if q.aligned && d.misses >= config.loss_misses {
+                d.aligned = false;
+            }
+            // Move to the new offset if the alignment is not
+            // (or no longer) held
+            if config.auto && !d.aligned {
+                d.offset = at;
+                d.hits = b8(1);
+                d.misses = b8(0);
+                d.aligned = d.hits >= config.lock_hits;
+            }
+        }
+    }
+    if i.bitslip {
+        d.offset = q.offset + 1;
+        if q.offset == bits((W - 1) as u128) {
+            d.offset = b8(0);
+        }
+        d.aligned = false;
+        d.hits = b8(0);
+        d.misses = b8(0);
+    }
+    let o = Out::<Const<W>> {
+        data: q.out,
+        aligned: q.aligned,
+        offset: q.offset,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    // The K28.5 comma (RD-)
+    const COMMA: u128 = 0b0011111010;
+    // Filler words without three ones in a row, so that
+    // the comma cannot appear in the stream by accident
+    const FILLER: [u128; 3] = [0b0101010101, 0b1001001001, 0b0110101101];
+
+    fn config(auto: bool) -> Config<Const<10>> {
+        Config {
+            pattern: bits(COMMA),
+            lock_hits: b8(3),
+            loss_misses: b8(2),
+            auto,
+        }
+    }
+
+    // Frames of a comma followed by filler words
+    fn frames(rng: &mut StdRng, count: usize) -> Vec<u128> {
+        (0..count)
+            .flat_map(|_| {
+                std::iter::once(COMMA)
+                    .chain((0..4).map(|_| FILLER[rng.random_range(0..3)]))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // Serialize the words MSB first, drop the first `skip` bits, and
+    // deserialize into words again
+    fn slip(words: &[u128], skip: usize) -> Vec<u128> {
+        let bits = words
+            .iter()
+            .flat_map(|w| (0..10).rev().map(move |n| (w >> n) & 1))
+            .skip(skip)
+            .collect::<Vec<_>>();
+        bits.chunks_exact(10)
+            .map(|c| c.iter().fold(0, |w, b| (w << 1) | b))
+            .collect()
+    }
+
+    fn input(words: &[u128], config: Config<Const<10>>) -> Vec<In<Const<10>>> {
+        words
+            .iter()
+            .map(|w| In {
+                data: Some(bits(*w)),
+                bitslip: false,
+                config,
+            })
+            .collect()
+    }
+
+    fn run(input: Vec<In<Const<10>>>) -> miette::Result<Vec<Out<Const<10>>>> {
+        let uut = WordAligner::<10>::default();
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_locks_at_every_offset() -> miette::Result<()> {
+        let mut rng = StdRng::seed_from_u64(242);
+        let sent = frames(&mut rng, 8);
+        for skip in 0..10 {
+            // Dropping `skip` bits puts the comma `10 - skip` bits into a word
+            let expected = (10 - skip) % 10;
+            let output = run(input(&slip(&sent, skip), config(true)))?;
+            let aligned = output
+                .iter()
+                .position(|o| o.aligned)
+                .expect("The aligner should lock");
+            // Three commas are needed to lock, and the first shows up
+            // on the output one word after it was found
+            assert!(aligned <= 16, "Locked late at {aligned} for {skip}");
+            assert!(output[aligned..]
+                .iter()
+                .all(|o| o.aligned && o.offset == b8(expected as u128)));
+            // The aligned words are those that were sent
+            let words = output[aligned..]
+                .iter()
+                .map(|o| o.data.unwrap().raw())
+                .collect::<Vec<_>>();
+            assert!(
+                sent.windows(words.len()).any(|w| w == words),
+                "Offset {skip}: {words:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_loss_and_relock() -> miette::Result<()> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let sent = frames(&mut rng, 10);
+        // The stream slips by 3 bits half way through
+        let mut words = slip(&sent[..25], 4);
+        words.extend(slip(&sent[25..], 7));
+        let output = run(input(&words, config(true)))?;
+        let states = output
+            .iter()
+            .map(|o| (o.aligned, o.offset.raw()))
+            .collect::<Vec<_>>();
+        let mut runs = states.clone();
+        runs.dedup();
+        // Hunting at offset 0, moving to the comma at 6, and locking.
+        // After the slip, two misses drop the lock, and it moves to 3,
+        // and locks again.
+        assert_eq!(
+            runs,
+            [(false, 0), (false, 6), (true, 6), (false, 3), (true, 3)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_miss_keeps_lock() -> miette::Result<()> {
+        let mut rng = StdRng::seed_from_u64(3);
+        let sent = frames(&mut rng, 8);
+        let mut words = slip(&sent, 2);
+        // A comma appears once at the wrong offset
+        words[21] = COMMA;
+        let output = run(input(&words, config(true)))?;
+        let aligned = output.iter().position(|o| o.aligned).unwrap();
+        assert!(output[aligned..]
+            .iter()
+            .all(|o| o.aligned && o.offset == b8(8)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_manual_bitslip() -> miette::Result<()> {
+        let mut rng = StdRng::seed_from_u64(4);
+        let sent = frames(&mut rng, 12);
+        let words = slip(&sent, 5);
+        let mut input = input(&words, config(false));
+        // Slip the offset to 5, one bit at a time
+        for n in 0..5 {
+            input[2 * n + 1].bitslip = true;
+        }
+        let output = run(input)?;
+        let aligned = output.iter().position(|o| o.aligned).unwrap();
+        assert!(output[aligned..]
+            .iter()
+            .all(|o| o.aligned && o.offset == b8(5)));
+        // Without a bit slip, a manual aligner never moves
+        let output = run(self::input(&words, config(false)))?;
+        assert!(output.iter().all(|o| !o.aligned && o.offset == b8(0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitslip_wraps() -> miette::Result<()> {
+        let input = (0..12)
+            .map(|_| In {
+                data: None,
+                bitslip: true,
+                config: config(false),
+            })
+            .collect();
+        let offsets = run(input)?
+            .iter()
+            .map(|o| o.offset.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_aligner_hdl() -> miette::Result<()> {
+        let uut = WordAligner::<10>::default();
+        let mut rng = StdRng::seed_from_u64(5);
+        let sent = frames(&mut rng, 4);
+        let mut input = input(&slip(&sent, 3), config(true));
+        input[12].bitslip = true;
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
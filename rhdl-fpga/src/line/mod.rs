@@ -0,0 +1,7 @@
+//! Line coding cores
+//!
+//! Cores that recover the framing of a serial line, such as
+//! finding the word boundaries of deserialized data.
+pub mod aligner;
+
+pub use aligner::WordAligner;
@@ -0,0 +1,377 @@
+//! Memory Images
+//!
+//!# Purpose
+//!
+//! A boot ROM (or RAM) in an SoC is initialized from a compiled
+//! firmware binary.  The [load_image] function takes the bytes of the
+//! binary (usually from `include_bytes!`), and packs them into the words
+//! of a memory with `2^A` words of `W` bits, in the byte order given.
+//! The resulting [Image] provides:
+//!
+//! - The initial contents for the RAM and ROM cores, via [Image::init],
+//!   which can be handed to the `new` function of (for example) a
+//!   [SyncBRAM](crate::core::ram::synchronous::SyncBRAM) or a
+//!   [RamSlave](crate::wishbone::RamSlave).
+//! - The same contents as a `$readmemh` file, via [Image::readmemh],
+//!   for use with other tools.
+//! - The offsets of the symbols in the firmware, if a map file is
+//!   provided with [Image::with_map].
+//!
+//! The image is padded with zeros to fill the memory.  The last word
+//! is padded if the binary is not a whole number of words, and the
+//! words after the end of the binary are zero.  A binary that does not
+//! fit in the memory is an error, rather than being truncated.
+//!
+//! The map file is a list of symbols, one per line, in the format
+//! produced by `nm`.  Each line is a hex byte offset, an optional
+//! symbol type, and the name of the symbol.  Blank lines and lines
+//! starting with `#` are ignored.
+//!
+//!```text
+//! 00000000 T _start
+//! 00000040 T main
+//! 00000100 D message
+//!```
+//!
+//!# Example
+//!
+//! Loading a little endian image into a Wishbone slave with 64 words.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::mem::image::{load_image, Endian};
+//! use rhdl_fpga::wishbone::RamSlave;
+//!
+//! let firmware = [0x13, 0x05, 0x10, 0x00, 0x6f, 0x00, 0x00, 0x00];
+//! let image = load_image::<U6, U32>(&firmware, Endian::Little).unwrap();
+//! assert_eq!(image.words()[0], b32(0x0010_0513));
+//! let uut = RamSlave::<U6>::new(image.init());
+//!```
+use std::collections::BTreeMap;
+
+use miette::Diagnostic;
+use rhdl::prelude::*;
+use thiserror::Error;
+
+/// The order in which the bytes of the binary are packed into
+/// each word of the memory
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endian {
+    /// The first byte is the least significant byte of the word
+    Little,
+    /// The first byte is the most significant byte of the word
+    Big,
+}
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors that can arise when loading a memory image
+pub enum ImageError {
+    /// The word width is not a whole number of bytes
+    #[error("A memory with {bits} bit words can not be loaded with bytes")]
+    #[diagnostic(help("The word width must be a multiple of 8 bits"))]
+    Width {
+        /// The width of the words of the memory
+        bits: usize,
+    },
+    /// The image does not fit in the memory
+    #[error("The image is {bytes} bytes, but the memory only holds {capacity} bytes")]
+    #[diagnostic(help("Use a larger memory, or a smaller image"))]
+    TooLarge {
+        /// The size of the image in bytes
+        bytes: usize,
+        /// The size of the memory in bytes
+        capacity: usize,
+    },
+    /// A line of the map file could not be parsed
+    #[error("Line {line} of the map file ({text:?}) is not an offset and a symbol name")]
+    Map {
+        /// The line number (starting at 1)
+        line: usize,
+        /// The text of the line
+        text: String,
+    },
+    /// A symbol in the map file is outside of the memory
+    #[error(
+        "Symbol {name} is at offset {offset:#x}, but the memory only holds {capacity:#x} bytes"
+    )]
+    SymbolOutOfRange {
+        /// The name of the symbol
+        name: String,
+        /// The byte offset of the symbol
+        offset: usize,
+        /// The size of the memory in bytes
+        capacity: usize,
+    },
+}
+
+/// A memory image, holding the `2^A` words of `W` bits of a memory,
+/// and the symbols of the firmware loaded into it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image<A: BitWidth, W: BitWidth> {
+    words: Vec<Bits<W>>,
+    bytes: usize,
+    symbols: BTreeMap<String, usize>,
+    _addr: std::marker::PhantomData<A>,
+}
+
+/// Pack the `bytes` of a binary into an [Image] of a memory with `2^A`
+/// words of `W` bits, in the given byte order.  The image is padded with
+/// zeros to fill the memory.
+pub fn load_image<A: BitWidth, W: BitWidth>(
+    bytes: &[u8],
+    endian: Endian,
+) -> Result<Image<A, W>, ImageError> {
+    if W::BITS % 8 != 0 {
+        return Err(ImageError::Width { bits: W::BITS });
+    }
+    let word_bytes = W::BITS / 8;
+    let capacity = (1 << A::BITS) * word_bytes;
+    if bytes.len() > capacity {
+        return Err(ImageError::TooLarge {
+            bytes: bytes.len(),
+            capacity,
+        });
+    }
+    let mut words = bytes
+        .chunks(word_bytes)
+        .map(|chunk| {
+            let word = (0..word_bytes).fold(0_u128, |word, lane| {
+                let byte = chunk.get(lane).copied().unwrap_or_default() as u128;
+                match endian {
+                    Endian::Little => word | (byte << (8 * lane)),
+                    Endian::Big => (word << 8) | byte,
+                }
+            });
+            bits(word)
+        })
+        .collect::<Vec<_>>();
+    words.resize(1 << A::BITS, bits(0));
+    Ok(Image {
+        words,
+        bytes: bytes.len(),
+        symbols: BTreeMap::new(),
+        _addr: std::marker::PhantomData,
+    })
+}
+
+impl<A: BitWidth, W: BitWidth> Image<A, W> {
+    /// Add the symbols listed in a map file to the image.  Every
+    /// symbol must lie inside the memory.
+    pub fn with_map(mut self, map: &str) -> Result<Self, ImageError> {
+        let capacity = self.words.len() * W::BITS / 8;
+        for (ndx, text) in map.lines().enumerate() {
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let fields = trimmed.split_whitespace().collect::<Vec<_>>();
+            let offset = match fields.as_slice() {
+                [offset, _name] | [offset, _, _name] => {
+                    let offset = offset.trim_start_matches("0x");
+                    usize::from_str_radix(offset, 16).ok()
+                }
+                _ => None,
+            };
+            let Some(offset) = offset else {
+                return Err(ImageError::Map {
+                    line: ndx + 1,
+                    text: text.into(),
+                });
+            };
+            let name = fields[fields.len() - 1].to_string();
+            if offset >= capacity {
+                return Err(ImageError::SymbolOutOfRange {
+                    name,
+                    offset,
+                    capacity,
+                });
+            }
+            self.symbols.insert(name, offset);
+        }
+        Ok(self)
+    }
+    /// The words of the memory, including the padding
+    pub fn words(&self) -> &[Bits<W>] {
+        &self.words
+    }
+    /// The size of the binary loaded, in bytes
+    pub fn len(&self) -> usize {
+        self.bytes
+    }
+    /// True if the image was loaded from an empty binary
+    pub fn is_empty(&self) -> bool {
+        self.bytes == 0
+    }
+    /// The initial contents of the memory, as address and
+    /// word pairs, for the `new` function of the RAM and ROM cores
+    pub fn init(&self) -> impl Iterator<Item = (Bits<A>, Bits<W>)> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .map(|(addr, word)| (bits(addr as u128), *word))
+    }
+    /// The byte offsets of the symbols, by name
+    pub fn symbols(&self) -> &BTreeMap<String, usize> {
+        &self.symbols
+    }
+    /// The byte offset of the named symbol
+    pub fn symbol(&self, name: &str) -> Option<usize> {
+        self.symbols.get(name).copied()
+    }
+    /// The address of the word that holds the named symbol
+    pub fn symbol_address(&self, name: &str) -> Option<Bits<A>> {
+        self.symbol(name)
+            .map(|offset| bits((offset / (W::BITS / 8)) as u128))
+    }
+    /// The contents of the memory in the format read by `$readmemh`,
+    /// with one word per line
+    pub fn readmemh(&self) -> String {
+        let digits = W::BITS.div_ceil(4);
+        let mut text = format!(
+            "// {} words of {} bits ({} bytes loaded)\n",
+            self.words.len(),
+            W::BITS,
+            self.bytes
+        );
+        for word in &self.words {
+            text += &format!("{:0digits$x}\n", word.raw());
+        }
+        text
+    }
+    /// Write the contents of the memory to a `$readmemh` file
+    pub fn write_readmemh(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.readmemh())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tb::{sequencer::TbSequencer, wishbone::WishboneMaster},
+        wishbone::{
+            ram_slave::{Out, RamSlave},
+            types::ToSlave,
+        },
+    };
+
+    // A small firmware image, which is not a whole number of words
+    fn firmware() -> Vec<u8> {
+        (0..37_u32).map(|n| (n * 37 + 11) as u8).collect()
+    }
+
+    const MAP: &str = "
+# Symbols from nm
+00000000 T _start
+0x00000010 main
+00000022 D message
+";
+
+    // Parse a $readmemh file back into words
+    fn parse_readmemh(text: &str) -> Vec<u128> {
+        text.lines()
+            .filter(|line| !line.starts_with("//"))
+            .map(|line| u128::from_str_radix(line, 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_byte_order_and_padding() -> miette::Result<()> {
+        let bytes = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66];
+        let little = load_image::<U2, U32>(&bytes, Endian::Little)?;
+        assert_eq!(
+            little.words(),
+            [b32(0x4433_2211), b32(0x6655), b32(0), b32(0)]
+        );
+        let big = load_image::<U2, U32>(&bytes, Endian::Big)?;
+        assert_eq!(
+            big.words(),
+            [b32(0x1122_3344), b32(0x5566_0000), b32(0), b32(0)]
+        );
+        let wide = load_image::<U1, U16>(&bytes[..3], Endian::Big)?;
+        assert_eq!(wide.words(), [b16(0x1122), b16(0x3300)]);
+        assert_eq!(wide.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounds_are_checked() {
+        let bytes = vec![0xAA; 65];
+        assert_eq!(
+            load_image::<U4, U32>(&bytes, Endian::Little).unwrap_err(),
+            ImageError::TooLarge {
+                bytes: 65,
+                capacity: 64
+            }
+        );
+        // An image that exactly fills the memory is fine
+        assert!(load_image::<U4, U32>(&bytes[..64], Endian::Little).is_ok());
+        assert_eq!(
+            load_image::<U4, U12>(&bytes[..4], Endian::Little).unwrap_err(),
+            ImageError::Width { bits: 12 }
+        );
+    }
+
+    #[test]
+    fn test_symbol_map() -> miette::Result<()> {
+        let image = load_image::<U4, U32>(&firmware(), Endian::Little)?.with_map(MAP)?;
+        assert_eq!(image.symbols().len(), 3);
+        assert_eq!(image.symbol("main"), Some(0x10));
+        assert_eq!(image.symbol_address("message"), Some(b4(8)));
+        assert_eq!(image.symbol("missing"), None);
+        let err = load_image::<U4, U32>(&firmware(), Endian::Little)?
+            .with_map("00000040 T past_the_end")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ImageError::SymbolOutOfRange {
+                name: "past_the_end".into(),
+                offset: 0x40,
+                capacity: 0x40
+            }
+        );
+        let err = load_image::<U4, U32>(&firmware(), Endian::Little)?
+            .with_map("_start\nmain")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ImageError::Map {
+                line: 1,
+                text: "_start".into()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_readmemh_matches_image() -> miette::Result<()> {
+        let image = load_image::<U4, U32>(&firmware(), Endian::Big)?;
+        let text = image.readmemh();
+        assert!(text.starts_with("// 16 words of 32 bits (37 bytes loaded)\n"));
+        let words = parse_readmemh(&text);
+        assert_eq!(words.len(), 16);
+        assert!(words.iter().zip(image.words()).all(|(a, b)| *a == b.raw()));
+        assert_eq!(text.lines().nth(1), Some("0b30557a"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rom_reads_back_image_over_wishbone() -> miette::Result<()> {
+        let bytes = firmware();
+        let image = load_image::<U4, U32>(&bytes, Endian::Little)?;
+        let uut = RamSlave::<U4>::new(image.init());
+        let bus = WishboneMaster::new("bus", |i: &mut ToSlave<U4>| i, |o: &Out<U4>| o.bus);
+        let mut seq = TbSequencer::new(&uut).with_driver(bus);
+        // Every word of the memory (including the padding) reads back as
+        // the bytes of the binary, and matches the exported hex
+        let hex = parse_readmemh(&image.readmemh());
+        let mut padded = bytes.clone();
+        padded.resize(64, 0);
+        seq.run(padded.chunks(4).enumerate().map(|(adr, chunk)| {
+            let word = u32::from_le_bytes(chunk.try_into().unwrap());
+            assert_eq!(word as u128, hex[adr]);
+            bus.read_expect(adr as u128, word)
+        }))?;
+        Ok(())
+    }
+}
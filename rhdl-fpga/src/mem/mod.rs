@@ -0,0 +1,8 @@
+//! Memory based cores
+//!
+//! Cores built on the block RAMs in [ram](crate::core::ram), such
+//! as tables that can be reloaded while they are in use.
+pub mod image;
+pub mod ping_pong;
+
+pub use ping_pong::PingPongTable;
@@ -0,0 +1,279 @@
+//! Ping-Pong Table
+//!
+//!# Purpose
+//!
+//! Filters and lookup table based cores need their tables updated
+//! while they run, without any sample being processed with a mix of old
+//! and new entries.  A [ShadowConfig](crate::csr::shadow::ShadowConfig)
+//! does this for a handful of fields, but a table with hundreds of
+//! entries belongs in block RAM.  The [PingPongTable] holds two banks of
+//! RAM.  One bank is live, and is read by the datapath.  The other is
+//! loaded through the `load` port.  A `commit` strobe swaps the banks,
+//! once the consumer signals a safe `boundary` (such as the start of a
+//! frame, or of a block of samples).
+//!
+//! - The swap takes effect on the clock on which `boundary` is asserted
+//!   (which may be the same clock as the `commit`).  Reads presented
+//!   on that clock, and later, come from the new bank.
+//! - A second `commit` before the boundary is absorbed by the first.
+//! - Loads always write the bank that is not live.  After a swap, that
+//!   bank holds the previous table, so a reload must write every entry
+//!   that is to change.  Loads issued between the commit and the boundary
+//!   land in the bank that is about to go live, and should be avoided.
+//!
+//! The `live` output reports which bank is live (`false` for bank 0),
+//! `pending` is set while a commit waits for its boundary, and `loading`
+//! is set once the inactive bank has been written, until the next swap.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+PingPongTable+------+
+ bA   |                      | T
++---->| read_addr       data +---->
+?bA,T |                      | bool
++---->| load            live +---->
+ bool |                      | bool
++---->| commit       pending +---->
+ bool |                      | bool
++---->| boundary     loading +---->
+      +----------------------+
+")]
+//!
+//!# Timing
+//!
+//! As with the [OptionSyncBRAM], the data for a read address appears
+//! on the clock after the address is presented.
+//!
+//!# Example
+//!
+//! A table of 16 coefficients, starting as all ones.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::mem::ping_pong::PingPongTable;
+//!
+//! let uut = PingPongTable::<s16, U4>::new((0..16).map(|ndx| (bits(ndx), s16(1))));
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{dff, ram::option_sync::OptionSyncBRAM};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The ping-pong table core
+///
+///   `T` is the type of the entries, and `A` is the
+///   number of address bits
+pub struct PingPongTable<T: Digital + Default, A: BitWidth> {
+    bank0: OptionSyncBRAM<T, A>,
+    bank1: OptionSyncBRAM<T, A>,
+    live: dff::DFF<bool>,
+    armed: dff::DFF<bool>,
+    loading: dff::DFF<bool>,
+    // The bank the read in flight comes from
+    select: dff::DFF<bool>,
+}
+
+impl<T: Digital + Default, A: BitWidth> PingPongTable<T, A> {
+    /// Create a table with both banks holding `initial`
+    pub fn new(initial: impl IntoIterator<Item = (Bits<A>, T)>) -> Self {
+        let initial = initial.into_iter().collect::<Vec<_>>();
+        Self {
+            bank0: OptionSyncBRAM::new(initial.clone()),
+            bank1: OptionSyncBRAM::new(initial),
+            live: dff::DFF::new(false),
+            armed: dff::DFF::new(false),
+            loading: dff::DFF::new(false),
+            select: dff::DFF::new(false),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [PingPongTable]
+pub struct In<T: Digital, A: BitWidth> {
+    /// The address to read from the live bank
+    pub read_addr: Bits<A>,
+    /// A write to the inactive bank
+    pub load: Option<(Bits<A>, T)>,
+    /// Strobe to swap the banks at the next boundary
+    pub commit: bool,
+    /// The consumer is at a safe point to swap the banks
+    pub boundary: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [PingPongTable]
+pub struct Out<T: Digital> {
+    /// The entry read from the live bank
+    pub data: T,
+    /// The live bank (`false` for bank 0, `true` for bank 1)
+    pub live: bool,
+    /// A commit is waiting for the boundary
+    pub pending: bool,
+    /// The inactive bank has been loaded since the last swap
+    pub loading: bool,
+}
+
+impl<T: Digital + Default, A: BitWidth> SynchronousIO for PingPongTable<T, A> {
+    type I = In<T, A>;
+    type O = Out<T>;
+    type Kernel = ping_pong_kernel<T, A>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn ping_pong_kernel<T: Digital + Default, A: BitWidth>(
+    _cr: ClockReset,
+    i: In<T, A>,
+    q: Q<T, A>,
+) -> (Out<T>, D<T, A>) {
+    let mut d = D::<T, A>::dont_care();
+    // Swap the banks at the boundary, if a commit is armed
+    // (or arrives on the same clock)
+    let armed = i.commit || q.armed;
+    let swap = armed && i.boundary;
+    let live = q.live ^ swap;
+    d.live = live;
+    d.armed = armed && !swap;
+    d.select = live;
+    d.loading = q.loading && !swap;
+    // Both banks are read, and the output is picked
+    // from the one that was live for the read
+    d.bank0.read_addr = i.read_addr;
+    d.bank1.read_addr = i.read_addr;
+    d.bank0.write = None;
+    d.bank1.write = None;
+    if let Some(load) = i.load {
+        if live {
+            d.bank0.write = Some(load);
+        } else {
+            d.bank1.write = Some(load);
+        }
+        d.loading = true;
+    }
+    let o = Out::<T> {
+        data: if q.select { q.bank1 } else { q.bank0 },
+        live: q.live,
+        pending: q.armed,
+        loading: q.loading,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A table of 8 gains, used as a lookup table by a consumer that
+    // processes frames of 8 samples, each of which reads one entry
+    type Table = PingPongTable<s16, U3>;
+
+    fn table(scale: i128) -> impl Iterator<Item = (b3, s16)> {
+        (0..8).map(move |ndx| (bits(ndx as u128), s16(scale * (ndx + 1))))
+    }
+
+    fn read(addr: u128, boundary: bool) -> In<s16, U3> {
+        In {
+            read_addr: bits(addr),
+            load: None,
+            commit: false,
+            boundary,
+        }
+    }
+
+    fn run(input: Vec<In<s16, U3>>) -> miette::Result<Vec<Out<s16>>> {
+        let uut = Table::new(table(1));
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_reload_switches_at_boundary() -> miette::Result<()> {
+        // 12 frames of 8 reads, with the boundary at the start of each
+        let mut input = (0..12 * 8)
+            .map(|n| read(n % 8, n % 8 == 0))
+            .collect::<Vec<_>>();
+        // Load a table 10 times larger during frames 2 and 3, while the
+        // datapath is reading, and commit in the middle of frame 4
+        for (n, (addr, value)) in table(10).enumerate() {
+            input[2 * 8 + 2 * n + 1].load = Some((addr, value));
+        }
+        input[4 * 8 + 5].commit = true;
+        input.push(read(0, false));
+        let output = run(input)?;
+        assert_eq!(output.len(), 12 * 8);
+        for (n, o) in output.iter().enumerate() {
+            let (frame, addr) = (n / 8, (n % 8) as i128);
+            // The commit takes effect at the start of frame 5
+            let scale = if frame < 5 { 1 } else { 10 };
+            assert_eq!(o.data, s16(scale * (addr + 1)), "Sample {n}");
+            assert_eq!(o.live, frame >= 5, "Sample {n}");
+        }
+        // The commit is pending until the boundary
+        let pending = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.pending)
+            .map(|(n, _)| n)
+            .collect::<Vec<_>>();
+        assert_eq!(pending, (4 * 8 + 5..5 * 8).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_loading_status() -> miette::Result<()> {
+        let mut input = (0..10).map(|_| read(0, false)).collect::<Vec<_>>();
+        input[2].load = Some((b3(0), s16(-5)));
+        input[5].commit = true;
+        input[5].boundary = true;
+        let output = run(input)?;
+        let loading = output.iter().map(|o| o.loading).collect::<Vec<_>>();
+        // Set once the load is registered, and cleared by the swap
+        assert_eq!(
+            loading,
+            [false, false, true, true, true, false, false, false, false]
+        );
+        // The commit and the boundary on the same clock swap at once
+        assert_eq!(output[5].data, s16(-5));
+        assert!(output[5].live);
+        Ok(())
+    }
+
+    #[test]
+    fn test_double_swap_keeps_old_table() -> miette::Result<()> {
+        // Swapping twice without a load brings back the first table,
+        // and swapping once shows the (unchanged) copy in the other bank
+        let mut input = (0..8).map(|n| read(n % 8, true)).collect::<Vec<_>>();
+        input[2].commit = true;
+        input[5].commit = true;
+        let output = run(input)?;
+        for (n, o) in output.iter().enumerate() {
+            assert_eq!(o.data, s16(n as i128 + 1), "Sample {n}");
+        }
+        let live = output.iter().map(|o| o.live).collect::<Vec<_>>();
+        assert_eq!(live, [false, false, true, true, true, false, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ping_pong_hdl() -> miette::Result<()> {
+        let uut = Table::new(table(1));
+        let mut input = (0..40).map(|n| read(n % 8, n % 8 == 0)).collect::<Vec<_>>();
+        for (n, (addr, value)) in table(-3).enumerate() {
+            input[2 * n + 3].load = Some((addr, value));
+        }
+        input[20].commit = true;
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default().skip(2))?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default().skip(2))?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,365 @@
+//! SPI LCD Init Script Engine
+//!
+//! Small SPI displays (like the ST7735 or ILI9341) need a script of
+//! commands (with delays) after power up, before they will display
+//! anything.  The [LcdInit] core runs such a script on a microcoded
+//! [Sequencer], which bit-bangs the 4 wire SPI interface (SPI mode 0,
+//! MSB first), and drives the hardware reset line.  The script is a
+//! list of [Command]s, which is translated into a [Program] when the
+//! core is constructed.
+//!
+//! - [Command::Reset] pulls the reset line low for `hold` clocks,
+//!   and then waits for `recover` clocks.
+//! - [Command::Write] sends a command byte (with `dc` low), followed
+//!   by the data bytes (with `dc` high), in a single chip select frame.
+//! - [Command::Delay] waits for the given number of clocks.
+//!
+//! Delays are at least as long as requested (they may be a few
+//! clocks longer, as long delays use a loop counter).  Each half of
+//! the SPI clock lasts at least `half_period + 1` clocks.  The `done`
+//! output is set once the script has completed.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+     +-+LcdInit+-------+
+     |                 | bool
+     |             sck +----->
+     |                 | bool
+     |            mosi +----->
+     |                 | bool
+     |            cs_n +----->
+     |                 | bool
+     |              dc +----->
+     |                 | bool
+     |           rst_n +----->
+     |                 | bool
+     |            done +----->
+     +-----------------+
+")]
+//!
+//!# Example
+//!
+//! A (shortened) ST7735 init script.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::micro::lcd::{Command, LcdInit};
+//!
+//! let uut = LcdInit::<128>::new(
+//!     &[
+//!         Command::Reset { hold: 1000, recover: 12_000_000 },
+//!         Command::Write { command: 0x11, data: vec![] },
+//!         Command::Delay(12_000_000),
+//!         Command::Write { command: 0x3A, data: vec![0x05] },
+//!         Command::Write { command: 0x29, data: vec![] },
+//!     ],
+//!     4,
+//! )
+//! .unwrap();
+//! ```
+use rhdl::prelude::*;
+
+use super::{
+    program::{AssemblyError, Program},
+    sequencer::{self, Sequencer},
+};
+
+/// The sequencer output bit that drives the SPI clock
+pub const SCK: u16 = 1 << 0;
+/// The sequencer output bit that drives the SPI data
+pub const MOSI: u16 = 1 << 1;
+/// The sequencer output bit that drives the chip select (active low)
+pub const CS_N: u16 = 1 << 2;
+/// The sequencer output bit that drives the data/command select
+pub const DC: u16 = 1 << 3;
+/// The sequencer output bit that drives the reset (active low)
+pub const RST_N: u16 = 1 << 4;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A step in the init script
+pub enum Command {
+    /// Pulse the hardware reset
+    Reset {
+        /// The number of clocks to hold the reset low
+        hold: u32,
+        /// The number of clocks to wait after the reset
+        recover: u32,
+    },
+    /// Send a command byte and its data bytes
+    Write {
+        /// The command byte
+        command: u8,
+        /// The data bytes that follow the command
+        data: Vec<u8>,
+    },
+    /// Wait for the given number of clocks
+    Delay(u32),
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [LcdInit] core
+pub struct Pins {
+    /// The SPI clock
+    pub sck: bool,
+    /// The SPI data
+    pub mosi: bool,
+    /// The chip select (active low)
+    pub cs_n: bool,
+    /// The data/command select (high for data)
+    pub dc: bool,
+    /// The reset line (active low)
+    pub rst_n: bool,
+    /// Set when the script is complete
+    pub done: bool,
+}
+
+fn delay(program: &mut Program, clocks: u32) {
+    let (loops, rest) = (clocks >> 16, (clocks & 0xFFFF) as u16);
+    if loops > 0 {
+        // Each iteration of the loop takes 2^16 clocks
+        let label = format!("delay{}", program.len());
+        program
+            .load(0, loops as u16)
+            .label(&label)
+            .wait(0xFFFF)
+            .loop_to(0, &label);
+    }
+    if rest > 0 {
+        program.wait(rest);
+    }
+}
+
+fn byte(program: &mut Program, value: u8) {
+    for bit in (0..8).rev() {
+        let mosi = if value & (1 << bit) != 0 { MOSI } else { 0 };
+        program.set(MOSI, mosi).call("clock");
+    }
+}
+
+/// Translate an init script into a [Program] for the [Sequencer].
+/// The outputs are assigned as described by [SCK], [MOSI], [CS_N],
+/// [DC] and [RST_N].
+pub fn init_program(script: &[Command], half_period: u16) -> Program {
+    let mut program = Program::new();
+    for command in script {
+        match command {
+            Command::Reset { hold, recover } => {
+                program.set(RST_N, 0);
+                delay(&mut program, *hold);
+                program.set(RST_N, RST_N);
+                delay(&mut program, *recover);
+            }
+            Command::Write { command, data } => {
+                program.set(CS_N | DC, 0);
+                byte(&mut program, *command);
+                if !data.is_empty() {
+                    program.set(DC, DC);
+                }
+                for value in data {
+                    byte(&mut program, *value);
+                }
+                program.set(CS_N, CS_N);
+            }
+            Command::Delay(clocks) => delay(&mut program, *clocks),
+        }
+    }
+    program.halt();
+    // Pulse the SPI clock, with the data set up by the caller
+    program
+        .label("clock")
+        .wait(half_period)
+        .set(SCK, SCK)
+        .wait(half_period)
+        .set(SCK, 0)
+        .ret();
+    program
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The LCD init script engine.  Here `UOPS` is the size of the
+/// sequencer ROM that holds the translated script.
+pub struct LcdInit<const UOPS: usize> {
+    seq: Sequencer<UOPS>,
+}
+
+impl<const UOPS: usize> LcdInit<UOPS> {
+    /// Create an [LcdInit] core that runs the given script, with
+    /// each half of the SPI clock lasting at least `half_period`
+    /// clocks.  Fails if the script does not fit in the ROM.
+    pub fn new(script: &[Command], half_period: u16) -> Result<Self, AssemblyError> {
+        let rom = init_program(script, half_period).assemble()?;
+        Ok(Self {
+            seq: Sequencer::new(rom, bits((CS_N | RST_N) as u128)),
+        })
+    }
+}
+
+impl<const UOPS: usize> SynchronousIO for LcdInit<UOPS> {
+    type I = ();
+    type O = Pins;
+    type Kernel = lcd_init_kernel<UOPS>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn lcd_init_kernel<const UOPS: usize>(_cr: ClockReset, _i: (), q: Q<UOPS>) -> (Pins, D<UOPS>) {
+    let d = D::<UOPS> {
+        seq: sequencer::In {
+            conditions: [false; 8],
+        },
+    };
+    let pins = q.seq.outputs;
+    let o = Pins {
+        sck: pins & 1 != 0,
+        mosi: pins & 2 != 0,
+        cs_n: pins & 4 != 0,
+        dc: pins & 8 != 0,
+        rst_n: pins & 16 != 0,
+        done: q.seq.halted,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script() -> Vec<Command> {
+        vec![
+            Command::Reset {
+                hold: 10,
+                recover: 20,
+            },
+            Command::Write {
+                command: 0x01,
+                data: vec![],
+            },
+            Command::Delay(100),
+            Command::Write {
+                command: 0x11,
+                data: vec![],
+            },
+            Command::Delay(70_000),
+            Command::Write {
+                command: 0x3A,
+                data: vec![0x05],
+            },
+            Command::Write {
+                command: 0x2A,
+                data: vec![0x00, 0x02, 0x00, 0x81],
+            },
+            Command::Write {
+                command: 0x29,
+                data: vec![],
+            },
+        ]
+    }
+
+    // A frame decoded from the SPI lines, with the clocks on which
+    // the chip select was asserted and released
+    #[derive(Debug, PartialEq)]
+    struct Frame {
+        start: usize,
+        end: usize,
+        command: u8,
+        data: Vec<u8>,
+    }
+
+    fn decode(output: &[Pins]) -> Vec<Frame> {
+        let mut frames = vec![];
+        let mut bits: Vec<(bool, bool)> = vec![];
+        let mut start = 0;
+        for (n, w) in output.windows(2).enumerate() {
+            if w[0].cs_n && !w[1].cs_n {
+                start = n + 1;
+                bits.clear();
+            }
+            if !w[1].cs_n && !w[0].sck && w[1].sck {
+                bits.push((w[1].dc, w[1].mosi));
+            }
+            if !w[0].cs_n && w[1].cs_n {
+                let bytes = bits
+                    .chunks(8)
+                    .map(|c| {
+                        assert!(c.iter().all(|b| b.0 == c[0].0));
+                        (c[0].0, c.iter().fold(0, |v, b| (v << 1) | b.1 as u8))
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(bits.len() % 8, 0);
+                assert!(!bytes[0].0);
+                assert!(bytes[1..].iter().all(|b| b.0));
+                frames.push(Frame {
+                    start,
+                    end: n + 1,
+                    command: bytes[0].1,
+                    data: bytes[1..].iter().map(|b| b.1).collect(),
+                });
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn test_init_script() -> miette::Result<()> {
+        let uut = LcdInit::<256>::new(&script(), 2)?;
+        let output = uut
+            .run(
+                std::iter::repeat_n((), 80_000)
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        // The reset pulse comes first
+        let low = output.iter().filter(|o| !o.rst_n).count();
+        let released = output.iter().rposition(|o| !o.rst_n).unwrap() + 1;
+        assert!(low >= 10);
+        let frames = decode(&output);
+        let expected = script()
+            .into_iter()
+            .filter_map(|c| match c {
+                Command::Write { command, data } => Some((command, data)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frames
+                .iter()
+                .map(|f| (f.command, f.data.clone()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+        // The delays are at least as long as requested
+        assert!(frames[0].start - released >= 20);
+        assert!(frames[1].start - frames[0].end >= 100);
+        assert!(frames[2].start - frames[1].end >= 70_000);
+        // The SPI clock has a period of at least 6 clocks
+        let rising = output
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| !w[0].sck && w[1].sck)
+            .map(|(n, _)| n)
+            .collect::<Vec<_>>();
+        assert!(rising.windows(2).all(|w| w[1] - w[0] >= 6));
+        assert!(output.last().unwrap().done);
+        assert!(output.last().unwrap().cs_n);
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_too_long() {
+        let script = vec![
+            Command::Write {
+                command: 0x2C,
+                data: vec![0; 20],
+            };
+            2
+        ];
+        assert!(matches!(
+            LcdInit::<256>::new(&script, 1),
+            Err(AssemblyError::TooLong { .. })
+        ));
+    }
+}
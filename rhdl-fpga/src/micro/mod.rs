@@ -0,0 +1,4 @@
+//! Microcoded engines
+pub mod lcd;
+pub mod program;
+pub mod sequencer;
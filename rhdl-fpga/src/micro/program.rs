@@ -0,0 +1,378 @@
+//! Micro-op Assembler
+//!
+//! Writing [Uop]s by hand means computing jump addresses by hand,
+//! which is error prone.  The [Program] builder provides a small
+//! assembler, in which jump targets are named labels.  The program
+//! is built in Rust (usually when the design is constructed), and
+//! then [assembled](Program::assemble) into a ROM image for the
+//! [Sequencer](super::sequencer::Sequencer).  Unused ROM entries are
+//! filled with [Uop::Halt].  So a program that fills the ROM must end
+//! with a micro-op that does not continue to the next address (a
+//! halt, jump or return), or the sequencer would run off the end.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::micro::program::Program;
+//! use rhdl_fpga::micro::sequencer::Uop;
+//!
+//! let mut program = Program::new();
+//! program.label("start").set(0xFF, 0x01).wait(10).jump("start");
+//! let rom = program.assemble::<4>().unwrap();
+//! assert_eq!(rom[2], Uop::Jump(b8(0)));
+//! assert_eq!(rom[3], Uop::Halt);
+//! ```
+use std::collections::HashMap;
+
+use miette::Diagnostic;
+use rhdl::prelude::*;
+use thiserror::Error;
+
+use super::sequencer::{Condition, CounterLoad, CounterLoop, Outputs, Uop};
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors that can arise when assembling a [Program]
+pub enum AssemblyError {
+    /// A label was defined more than once
+    #[error("Label {0} is defined more than once")]
+    DuplicateLabel(String),
+    /// A label was used but never defined
+    #[error("Label {0} is used, but never defined")]
+    UnknownLabel(String),
+    /// The program does not fit in the ROM
+    #[error("The program has {len} micro-ops, but the ROM only holds {capacity}")]
+    #[diagnostic(help("The sequencer can address at most 256 micro-ops"))]
+    TooLong {
+        /// The number of micro-ops in the program
+        len: usize,
+        /// The size of the ROM
+        capacity: usize,
+    },
+    /// A condition input that does not exist was used
+    #[error("Condition input {0} does not exist (there are 8)")]
+    InvalidInput(usize),
+    /// A loop counter that does not exist was used
+    #[error("Loop counter {0} does not exist (there are 2)")]
+    InvalidCounter(usize),
+    /// A loop counter was loaded with zero
+    #[error("Loop counter {0} is loaded with zero, which would loop 65536 times")]
+    ZeroCount(usize),
+    /// The program fills the ROM, and can run past its end
+    #[error("The program fills the ROM of {capacity} micro-ops, but can continue past its end")]
+    #[diagnostic(help("End the program with a halt, jump or return, or use a larger ROM"))]
+    RunsOffEnd {
+        /// The size of the ROM
+        capacity: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+enum Item {
+    Uop(Uop),
+    WaitFor {
+        input: usize,
+        level: bool,
+        timeout: u16,
+        target: String,
+    },
+    Load {
+        counter: usize,
+        value: u16,
+    },
+    Loop {
+        counter: usize,
+        target: String,
+    },
+    Jump(String),
+    Call(String),
+}
+
+#[derive(Clone, Debug, Default)]
+/// A program for the [Sequencer](super::sequencer::Sequencer)
+pub struct Program {
+    items: Vec<Item>,
+    labels: HashMap<String, usize>,
+    duplicates: Vec<String>,
+}
+
+impl Program {
+    /// Create an empty program
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of micro-ops in the program
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the program has no micro-ops
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn push(&mut self, item: Item) -> &mut Self {
+        self.items.push(item);
+        self
+    }
+
+    /// Define a label at the current position
+    pub fn label(&mut self, name: &str) -> &mut Self {
+        if self
+            .labels
+            .insert(name.to_string(), self.items.len())
+            .is_some()
+        {
+            self.duplicates.push(name.to_string());
+        }
+        self
+    }
+
+    /// Set the outputs selected by `mask` to `value`
+    pub fn set(&mut self, mask: u16, value: u16) -> &mut Self {
+        self.push(Item::Uop(Uop::Set(Outputs {
+            mask: bits(mask as u128),
+            value: bits(value as u128),
+        })))
+    }
+
+    /// Wait for the given number of clocks
+    pub fn wait(&mut self, clocks: u16) -> &mut Self {
+        self.push(Item::Uop(Uop::Wait(bits(clocks as u128))))
+    }
+
+    /// Wait for condition `input` to reach `level`.  If it does not
+    /// do so within `timeout` clocks, continue at `on_timeout`.
+    pub fn wait_for(
+        &mut self,
+        input: usize,
+        level: bool,
+        timeout: u16,
+        on_timeout: &str,
+    ) -> &mut Self {
+        self.push(Item::WaitFor {
+            input,
+            level,
+            timeout,
+            target: on_timeout.to_string(),
+        })
+    }
+
+    /// Load a loop counter with a (non-zero) value
+    pub fn load(&mut self, counter: usize, value: u16) -> &mut Self {
+        self.push(Item::Load { counter, value })
+    }
+
+    /// Decrement a loop counter, and jump to `target` if it is not zero
+    pub fn loop_to(&mut self, counter: usize, target: &str) -> &mut Self {
+        self.push(Item::Loop {
+            counter,
+            target: target.to_string(),
+        })
+    }
+
+    /// Jump to a label
+    pub fn jump(&mut self, target: &str) -> &mut Self {
+        self.push(Item::Jump(target.to_string()))
+    }
+
+    /// Call the subroutine at a label
+    pub fn call(&mut self, target: &str) -> &mut Self {
+        self.push(Item::Call(target.to_string()))
+    }
+
+    /// Return from a subroutine
+    pub fn ret(&mut self) -> &mut Self {
+        self.push(Item::Uop(Uop::Return))
+    }
+
+    /// Stop execution
+    pub fn halt(&mut self) -> &mut Self {
+        self.push(Item::Uop(Uop::Halt))
+    }
+
+    /// Assemble the program into a ROM image of `UOPS` micro-ops
+    pub fn assemble<const UOPS: usize>(&self) -> Result<[Uop; UOPS], AssemblyError> {
+        if let Some(name) = self.duplicates.first() {
+            return Err(AssemblyError::DuplicateLabel(name.clone()));
+        }
+        if self.items.len() > UOPS.min(256) {
+            return Err(AssemblyError::TooLong {
+                len: self.items.len(),
+                capacity: UOPS.min(256),
+            });
+        }
+        // Only a full ROM lacks the trailing halt
+        let ends = matches!(
+            self.items.last(),
+            Some(Item::Uop(Uop::Halt | Uop::Return) | Item::Jump(_))
+        );
+        if self.items.len() == UOPS.min(256) && !ends {
+            return Err(AssemblyError::RunsOffEnd {
+                capacity: UOPS.min(256),
+            });
+        }
+        let address = |name: &String| -> Result<b8, AssemblyError> {
+            self.labels
+                .get(name)
+                .map(|a| bits(*a as u128))
+                .ok_or_else(|| AssemblyError::UnknownLabel(name.clone()))
+        };
+        let counter = |counter: usize| -> Result<b1, AssemblyError> {
+            if counter < 2 {
+                Ok(bits(counter as u128))
+            } else {
+                Err(AssemblyError::InvalidCounter(counter))
+            }
+        };
+        let mut rom = [Uop::Halt; UOPS];
+        for (slot, item) in rom.iter_mut().zip(&self.items) {
+            *slot = match item {
+                Item::Uop(uop) => *uop,
+                Item::WaitFor {
+                    input,
+                    level,
+                    timeout,
+                    target,
+                } => {
+                    if *input >= 8 {
+                        return Err(AssemblyError::InvalidInput(*input));
+                    }
+                    Uop::WaitFor(Condition {
+                        input: bits(*input as u128),
+                        level: *level,
+                        timeout: bits(*timeout as u128),
+                        target: address(target)?,
+                    })
+                }
+                Item::Load { counter: c, value } => {
+                    if *value == 0 {
+                        return Err(AssemblyError::ZeroCount(*c));
+                    }
+                    Uop::Load(CounterLoad {
+                        counter: counter(*c)?,
+                        value: bits(*value as u128),
+                    })
+                }
+                Item::Loop { counter: c, target } => Uop::Loop(CounterLoop {
+                    counter: counter(*c)?,
+                    target: address(target)?,
+                }),
+                Item::Jump(target) => Uop::Jump(address(target)?),
+                Item::Call(target) => Uop::Call(address(target)?),
+            };
+        }
+        Ok(rom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_are_resolved() {
+        let mut program = Program::new();
+        program
+            .call("sub")
+            .label("wait")
+            .wait_for(3, false, 100, "wait")
+            .load(1, 4)
+            .label("sub")
+            .loop_to(1, "sub")
+            .ret();
+        let rom = program.assemble::<8>().unwrap();
+        assert_eq!(rom[0], Uop::Call(b8(3)));
+        assert_eq!(
+            rom[1],
+            Uop::WaitFor(Condition {
+                input: b3(3),
+                level: false,
+                timeout: b16(100),
+                target: b8(1),
+            })
+        );
+        assert_eq!(
+            rom[3],
+            Uop::Loop(CounterLoop {
+                counter: b1(1),
+                target: b8(3)
+            })
+        );
+        assert_eq!(rom[4], Uop::Return);
+        assert!(rom[5..].iter().all(|u| *u == Uop::Halt));
+    }
+
+    #[test]
+    fn test_assembly_errors() {
+        let mut program = Program::new();
+        program.label("a").label("a").halt();
+        assert_eq!(
+            program.assemble::<4>(),
+            Err(AssemblyError::DuplicateLabel("a".into()))
+        );
+        let mut program = Program::new();
+        program.jump("nowhere");
+        assert_eq!(
+            program.assemble::<4>(),
+            Err(AssemblyError::UnknownLabel("nowhere".into()))
+        );
+        let mut program = Program::new();
+        program.halt().halt().halt();
+        assert_eq!(
+            program.assemble::<2>(),
+            Err(AssemblyError::TooLong {
+                len: 3,
+                capacity: 2
+            })
+        );
+        let mut program = Program::new();
+        program.label("x").wait_for(8, true, 1, "x");
+        assert_eq!(program.assemble::<4>(), Err(AssemblyError::InvalidInput(8)));
+        let mut program = Program::new();
+        program.load(2, 1);
+        assert_eq!(
+            program.assemble::<4>(),
+            Err(AssemblyError::InvalidCounter(2))
+        );
+        let mut program = Program::new();
+        program.load(0, 0);
+        assert_eq!(program.assemble::<4>(), Err(AssemblyError::ZeroCount(0)));
+    }
+
+    #[test]
+    fn test_full_rom_must_not_run_off_end() {
+        let mut program = Program::new();
+        program.label("top").set(1, 1).wait(4).set(1, 0);
+        // There is room for the trailing halt
+        assert!(program.assemble::<4>().is_ok());
+        assert_eq!(
+            program.assemble::<3>(),
+            Err(AssemblyError::RunsOffEnd { capacity: 3 })
+        );
+        // But a full ROM can end in anything that does not continue
+        let mut jump = program.clone();
+        jump.jump("top");
+        assert!(jump.assemble::<4>().is_ok());
+        let mut ret = program.clone();
+        ret.ret();
+        assert!(ret.assemble::<4>().is_ok());
+        let mut halt = program.clone();
+        halt.halt();
+        assert!(halt.assemble::<4>().is_ok());
+        // A loop or call falls through to the next address
+        let mut call = program.clone();
+        call.call("top");
+        assert_eq!(
+            call.assemble::<4>(),
+            Err(AssemblyError::RunsOffEnd { capacity: 4 })
+        );
+        let mut lp = program;
+        lp.loop_to(0, "top");
+        assert_eq!(
+            lp.assemble::<4>(),
+            Err(AssemblyError::RunsOffEnd { capacity: 4 })
+        );
+    }
+}
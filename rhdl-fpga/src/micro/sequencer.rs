@@ -0,0 +1,612 @@
+//! Microcoded Sequencer
+//!
+//! Many protocol engines (display init scripts, codec setup,
+//! bit-banged buses) are a fixed list of "drive these pins, wait a
+//! while, wait for that input" steps.  Rather than writing a state
+//! machine for each one, the [Sequencer] executes a ROM of [Uop]s,
+//! so that the protocol becomes data.  The ROM is usually built with
+//! the [Program](super::program::Program) assembler.
+//!
+//! The sequencer drives 16 output bits, and can test 8 condition
+//! inputs.  It has two 16 bit loop counters, and a return stack that
+//! holds 4 addresses.  The micro-ops are:
+//!
+//! - [Uop::Set] sets the outputs selected by a `mask` to a `value`,
+//!   leaving the others unchanged.
+//! - [Uop::Wait] waits for `n` clocks.
+//! - [Uop::WaitFor] waits for a condition input to reach a level.  If
+//!   it does not do so within `timeout` clocks, execution continues at
+//!   the `target` address instead.
+//! - [Uop::Load] loads a loop counter.
+//! - [Uop::Loop] decrements a loop counter, and jumps to `target`
+//!   if the result is not zero.  So a body ending in a `Loop` runs as
+//!   many times as the value loaded into the counter.  A `Loop` on a
+//!   counter that is already zero is a [Fault].
+//! - [Uop::Jump], [Uop::Call] and [Uop::Return] transfer control.
+//! - [Uop::Halt] stops execution.
+//!
+//! The timing is cycle exact.  Every micro-op takes a single clock,
+//! except for `Wait(n)`, which takes `n` clocks (a count of zero is
+//! treated as one), and `WaitFor`, which takes one clock more than the
+//! number of clocks it spends waiting (up to `timeout`).  The outputs
+//! are registered, so the effect of a `Set` is seen on the next clock.
+//!
+//! A `Call` with 4 return addresses already on the stack, or a
+//! `Return` with none, is a [Fault].  So is a micro-op that would move
+//! the program counter past the end of the ROM (by running off the
+//! end, or by a jump to an address outside it), unless the ROM holds
+//! all 256 addresses.  The faulting micro-op
+//! is not executed, and the sequencer halts (with the program counter
+//! left on that micro-op) and reports the fault until it is reset.  The
+//! current program counter and loop counters are available on the
+//! output for debug.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+          +-+Sequencer+-------+
+ [bool;8] |                   | b16
+ +------->| conditions outputs+----->
+          |                   | bool
+          |             halted+----->
+          |                   | Fault
+          |              fault+----->
+          |                   | b8
+          |                 pc+----->
+          |                   | [b16;2]
+          |           counters+----->
+          +-------------------+
+")]
+//!
+//!# Example
+//!
+//! Toggle output 0 five times, and then halt.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::micro::program::Program;
+//! use rhdl_fpga::micro::sequencer::{In, Sequencer};
+//!
+//! let mut program = Program::new();
+//! program
+//!     .load(0, 5)
+//!     .label("top")
+//!     .set(1, 1)
+//!     .set(1, 0)
+//!     .loop_to(0, "top")
+//!     .halt();
+//! let uut = Sequencer::<8>::new(program.assemble().unwrap(), b16(0));
+//! let input = std::iter::repeat_n(In { conditions: [false; 8] }, 20);
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! let pulses = output.windows(2).filter(|w| w[0].outputs == 0 && w[1].outputs == 1);
+//! assert_eq!(pulses.count(), 5);
+//! assert!(output.last().unwrap().halted);
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The payload of [Uop::Set]
+pub struct Outputs {
+    /// The outputs to change
+    pub mask: b16,
+    /// The new values of the outputs
+    pub value: b16,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The payload of [Uop::WaitFor]
+pub struct Condition {
+    /// The condition input to test
+    pub input: b3,
+    /// The level to wait for
+    pub level: bool,
+    /// The maximum number of clocks to wait
+    pub timeout: b16,
+    /// Where to continue if the wait times out
+    pub target: b8,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The payload of [Uop::Load]
+pub struct CounterLoad {
+    /// The counter to load
+    pub counter: b1,
+    /// The value to load
+    pub value: b16,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The payload of [Uop::Loop]
+pub struct CounterLoop {
+    /// The counter to decrement
+    pub counter: b1,
+    /// Where to jump
+    pub target: b8,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// A single micro-op
+pub enum Uop {
+    #[default]
+    /// Stop execution
+    Halt,
+    /// Set some of the outputs
+    Set(Outputs),
+    /// Wait for the given number of clocks
+    Wait(b16),
+    /// Wait for a condition input to reach a level
+    WaitFor(Condition),
+    /// Load a loop counter
+    Load(CounterLoad),
+    /// Decrement a loop counter, and jump if it is not zero
+    Loop(CounterLoop),
+    /// Jump to an address
+    Jump(b8),
+    /// Call a subroutine
+    Call(b8),
+    /// Return from a subroutine
+    Return,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// A fault that halts the [Sequencer]
+pub enum Fault {
+    #[default]
+    /// No fault
+    None,
+    /// A [Uop::Call] was made with the return stack full
+    StackOverflow,
+    /// A [Uop::Return] was made with the return stack empty
+    StackUnderflow,
+    /// A [Uop::Loop] was made with its counter at zero
+    LoopCountZero,
+    /// The program counter would leave the ROM
+    PcOutOfRange,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [Sequencer]
+pub struct In {
+    /// The condition inputs tested by [Uop::WaitFor]
+    pub conditions: [bool; 8],
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [Sequencer]
+pub struct Out {
+    /// The outputs driven by [Uop::Set]
+    pub outputs: b16,
+    /// Set when the sequencer has halted (on a
+    /// [Uop::Halt], or a fault)
+    pub halted: bool,
+    /// The fault that halted the sequencer, if any
+    pub fault: Fault,
+    /// The address of the current micro-op
+    pub pc: b8,
+    /// The loop counters
+    pub counters: [b16; 2],
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The microcoded sequencer.  Here `UOPS` is the size of the
+/// program ROM, which must be between 1 and 256.
+pub struct Sequencer<const UOPS: usize> {
+    rom: constant::Constant<[Uop; UOPS]>,
+    last: constant::Constant<b8>,
+    pc: dff::DFF<b8>,
+    timer: dff::DFF<b16>,
+    outputs: dff::DFF<b16>,
+    counters: dff::DFF<[b16; 2]>,
+    stack: dff::DFF<[b8; 4]>,
+    sp: dff::DFF<b3>,
+    fault: dff::DFF<Fault>,
+}
+
+impl<const UOPS: usize> Sequencer<UOPS> {
+    /// Create a [Sequencer] that runs the given program from
+    /// address zero.  The outputs take the `initial` value on reset.
+    pub fn new(program: [Uop; UOPS], initial: b16) -> Self {
+        assert!(
+            (1..=256).contains(&UOPS),
+            "The sequencer ROM must hold between 1 and 256 micro-ops"
+        );
+        Self {
+            rom: constant::Constant::new(program),
+            last: constant::Constant::new(bits(UOPS as u128 - 1)),
+            pc: dff::DFF::default(),
+            timer: dff::DFF::default(),
+            outputs: dff::DFF::new(initial),
+            counters: dff::DFF::default(),
+            stack: dff::DFF::default(),
+            sp: dff::DFF::default(),
+            fault: dff::DFF::default(),
+        }
+    }
+}
+
+impl<const UOPS: usize> SynchronousIO for Sequencer<UOPS> {
+    type I = In;
+    type O = Out;
+    type Kernel = sequencer_kernel<UOPS>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn sequencer_kernel<const UOPS: usize>(cr: ClockReset, i: In, q: Q<UOPS>) -> (Out, D<UOPS>) {
+    let mut d = D::<UOPS> {
+        rom: (),
+        last: (),
+        pc: q.pc + 1,
+        timer: bits(0),
+        outputs: q.outputs,
+        counters: q.counters,
+        stack: q.stack,
+        sp: q.sp,
+        fault: q.fault,
+    };
+    let mut halted = q.fault != Fault::None;
+    // The stack holds `sp` return addresses, with the
+    // most recent one at `sp - 1`
+    let sp: b2 = q.sp.resize();
+    let top: b2 = (q.sp - 1).resize();
+    match q.rom[q.pc] {
+        Uop::Halt => {
+            d.pc = q.pc;
+            halted = true;
+        }
+        Uop::Set(set) => {
+            d.outputs = (q.outputs & !set.mask) | (set.value & set.mask);
+        }
+        Uop::Wait(n) => {
+            if q.timer + 1 < n {
+                d.pc = q.pc;
+                d.timer = q.timer + 1;
+            }
+        }
+        Uop::WaitFor(wait) => {
+            if i.conditions[wait.input] != wait.level {
+                if q.timer + 1 < wait.timeout {
+                    d.pc = q.pc;
+                    d.timer = q.timer + 1;
+                } else {
+                    d.pc = wait.target;
+                }
+            }
+        }
+        Uop::Load(load) => {
+            d.counters[load.counter] = load.value;
+        }
+        Uop::Loop(lp) => {
+            if q.counters[lp.counter] == 0 {
+                d.fault = Fault::LoopCountZero;
+            } else {
+                let count = q.counters[lp.counter] - 1;
+                d.counters[lp.counter] = count;
+                if count != 0 {
+                    d.pc = lp.target;
+                }
+            }
+        }
+        Uop::Jump(target) => {
+            d.pc = target;
+        }
+        Uop::Call(target) => {
+            if q.sp == 4 {
+                d.fault = Fault::StackOverflow;
+            } else {
+                d.stack[sp] = q.pc + 1;
+                d.sp = q.sp + 1;
+                d.pc = target;
+            }
+        }
+        Uop::Return => {
+            if q.sp == 0 {
+                d.fault = Fault::StackUnderflow;
+            } else {
+                d.sp = q.sp - 1;
+                d.pc = q.stack[top];
+            }
+        }
+    }
+    if d.pc > q.last {
+        d.fault = Fault::PcOutOfRange;
+    }
+    // A faulting micro-op is not executed, and does not advance, so
+    // it faults again on every clock until the reset
+    if d.fault != Fault::None {
+        d.pc = q.pc;
+        d.timer = q.timer;
+        d.outputs = q.outputs;
+        d.counters = q.counters;
+        d.stack = q.stack;
+        d.sp = q.sp;
+    }
+    let o = Out {
+        outputs: q.outputs,
+        halted,
+        fault: q.fault,
+        pc: q.pc,
+        counters: q.counters,
+    };
+    if cr.reset.any() {
+        d.pc = bits(0);
+        d.timer = bits(0);
+        d.counters = [bits(0); 2];
+        d.sp = bits(0);
+        d.fault = Fault::None;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micro::program::Program;
+
+    // A program that uses every micro-op, with a nested call,
+    // and a wait with a timeout
+    fn test_program() -> [Uop; 24] {
+        let mut program = Program::new();
+        program
+            .set(0xFFFF, 0x01)
+            .wait(3)
+            .set(0x02, 0x02)
+            .load(0, 2)
+            .label("top")
+            .set(0x04, 0x04)
+            .set(0x04, 0x00)
+            .loop_to(0, "top")
+            .call("outer")
+            .wait_for(0, true, 5, "timeout")
+            .set(0xFFFF, 0x80)
+            .halt()
+            .label("outer")
+            .set(0x10, 0x10)
+            .call("inner")
+            .ret()
+            .label("inner")
+            .set(0x20, 0x20)
+            .ret()
+            .label("timeout")
+            .set(0xFFFF, 0xFF)
+            .jump("end")
+            .wait(100)
+            .label("end")
+            .halt();
+        program.assemble().unwrap()
+    }
+
+    // Run the program, with condition 0 asserted from clock `ready`
+    // on.  Element `n` of the output is the output on clock `n`
+    // after the reset.
+    fn run(ready: usize) -> miette::Result<Vec<Out>> {
+        let uut = Sequencer::new(test_program(), b16(0));
+        let input = (0..40).map(|n| In {
+            conditions: [n >= ready, false, false, false, false, false, false, false],
+        });
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // The clocks on which the outputs change, and their new values
+    fn changes(output: &[Out]) -> Vec<(usize, u128)> {
+        output
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0].outputs != w[1].outputs)
+            .map(|(n, w)| (n + 1, w[1].outputs.raw()))
+            .collect()
+    }
+
+    #[test]
+    fn test_cycle_exact_timing() -> miette::Result<()> {
+        let output = run(20)?;
+        // Each set is seen on the clock after it executes, and the
+        // wait for condition 0 (asserted on clock 20) completes on
+        // clock 20.
+        assert_eq!(
+            changes(&output),
+            [
+                (1, 0x01),
+                (5, 0x03),
+                (7, 0x07),
+                (8, 0x03),
+                (10, 0x07),
+                (11, 0x03),
+                (14, 0x13),
+                (16, 0x33),
+                (22, 0x80),
+            ]
+        );
+        // The loop counter counts down through the loop
+        assert_eq!(output[6].counters[0], 2);
+        assert_eq!(output[9].counters[0], 1);
+        assert_eq!(output[12].counters[0], 0);
+        // The program counter is visible while waiting
+        assert!(output[18..21].iter().all(|o| o.pc == 8));
+        assert!(!output[21].halted);
+        assert!(output[22..].iter().all(|o| o.halted && o.pc == 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_for_timeout() -> miette::Result<()> {
+        // The condition is never asserted, so the wait times out
+        // after 5 clocks, and the jump skips the wait
+        let output = run(1000)?;
+        let timeline = changes(&output);
+        assert_eq!(timeline[7], (16, 0x33));
+        assert_eq!(timeline[8], (24, 0xFF));
+        assert!(output[25..].iter().all(|o| o.halted && o.pc == 19));
+        // A condition that is already met does not wait at all
+        let output = run(0)?;
+        assert_eq!(changes(&output)[8], (20, 0x80));
+        Ok(())
+    }
+
+    // Run a program with no conditions asserted
+    fn run_program<const UOPS: usize>(
+        program: &Program,
+        clocks: usize,
+    ) -> miette::Result<Vec<Out>> {
+        run_rom(program.assemble::<UOPS>().unwrap(), clocks)
+    }
+
+    // Run a ROM (which need not come from the assembler) with no
+    // conditions asserted
+    fn run_rom<const UOPS: usize>(rom: [Uop; UOPS], clocks: usize) -> miette::Result<Vec<Out>> {
+        let uut = Sequencer::<UOPS>::new(rom, b16(0));
+        let input = std::iter::repeat_n(
+            In {
+                conditions: [false; 8],
+            },
+            clocks,
+        );
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_calls_four_deep() -> miette::Result<()> {
+        let mut program = Program::new();
+        program
+            .call("a")
+            .set(0xFFFF, 0x02)
+            .halt()
+            .label("a")
+            .call("b")
+            .ret()
+            .label("b")
+            .call("c")
+            .ret()
+            .label("c")
+            .call("d")
+            .ret()
+            .label("d")
+            .set(0xFFFF, 0x01)
+            .ret();
+        let output = run_program::<16>(&program, 20)?;
+        assert_eq!(changes(&output), [(5, 0x01), (10, 0x02)]);
+        assert!(output[10..].iter().all(|o| o.halted && o.pc == 2));
+        assert!(output.iter().all(|o| o.fault == Fault::None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_overflow_faults() -> miette::Result<()> {
+        // Unbounded recursion
+        let mut program = Program::new();
+        program
+            .set(0xFFFF, 0x01)
+            .label("rec")
+            .call("rec")
+            .set(0xFFFF, 0x02)
+            .halt();
+        let output = run_program::<4>(&program, 20)?;
+        // Four calls fill the stack, and the fifth faults
+        assert!(output[..6].iter().all(|o| !o.halted));
+        assert!(output[..6].iter().all(|o| o.fault == Fault::None));
+        assert!(output[6..]
+            .iter()
+            .all(|o| o.halted && o.fault == Fault::StackOverflow && o.pc == 1));
+        // And the sequencer stays stopped
+        assert_eq!(changes(&output), [(1, 0x01)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_underflow_faults() -> miette::Result<()> {
+        let mut program = Program::new();
+        program.set(0xFFFF, 0x05).ret().set(0xFFFF, 0x0A).halt();
+        let output = run_program::<4>(&program, 10)?;
+        assert!(output[..2].iter().all(|o| o.fault == Fault::None));
+        assert!(output[2..]
+            .iter()
+            .all(|o| o.halted && o.fault == Fault::StackUnderflow && o.pc == 1));
+        assert_eq!(changes(&output), [(1, 0x05)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop_on_zero_counter_faults() -> miette::Result<()> {
+        // The counter is never loaded
+        let mut program = Program::new();
+        program
+            .set(0xFFFF, 0x01)
+            .label("top")
+            .set(0xFFFF, 0x02)
+            .loop_to(0, "top")
+            .halt();
+        let output = run_program::<8>(&program, 10)?;
+        assert!(output[..3].iter().all(|o| o.fault == Fault::None));
+        assert!(output[3..]
+            .iter()
+            .all(|o| o.halted && o.fault == Fault::LoopCountZero && o.pc == 2));
+        assert!(output.iter().all(|o| o.counters == [b16(0); 2]));
+        assert_eq!(changes(&output), [(1, 0x01), (2, 0x02)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_running_off_the_rom_faults() -> miette::Result<()> {
+        let set = |value| {
+            Uop::Set(Outputs {
+                mask: b16(0xFFFF),
+                value: b16(value),
+            })
+        };
+        // The second set would leave the ROM, so it is not executed
+        let output = run_rom([set(0x01), set(0x02)], 10)?;
+        assert!(output[..2].iter().all(|o| o.fault == Fault::None));
+        assert!(output[2..]
+            .iter()
+            .all(|o| o.halted && o.fault == Fault::PcOutOfRange && o.pc == 1));
+        assert_eq!(changes(&output), [(1, 0x01)]);
+        // As does a jump outside the ROM
+        let output = run_rom([set(0x01), Uop::Jump(b8(9)), set(0x02), Uop::Halt], 10)?;
+        assert!(output[2..]
+            .iter()
+            .all(|o| o.halted && o.fault == Fault::PcOutOfRange && o.pc == 1));
+        assert_eq!(changes(&output), [(1, 0x01)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequencer_hdl() -> miette::Result<()> {
+        let uut = Sequencer::new(test_program(), b16(0));
+        let input = (0..60).map(|n| In {
+            conditions: [
+                (n / 7) % 2 == 1,
+                false,
+                false,
+                false,
+                false,
+                false,
+                false,
+                false,
+            ],
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
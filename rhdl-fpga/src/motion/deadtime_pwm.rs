@@ -0,0 +1,443 @@
+//! Complementary PWM with Dead Time
+//!
+//! Motor drives and power converters switch a pair of transistors
+//! (a high side and a low side) in a half bridge.  The two switches
+//! must never conduct at the same time, or they short the supply.
+//! The [DeadtimePwm] core generates a complementary pair of outputs
+//! from a single duty setting.  The nominal PWM signal is high for
+//! the first `duty` counts of each `2^N` count period.  The `high`
+//! output follows the nominal signal, and the `low` output follows
+//! its complement, except that each output only asserts after the
+//! nominal signal has been stable for `deadtime` clocks.  So after
+//! one output deasserts, the other asserts exactly `deadtime`
+//! clocks later, and a pulse shorter than the dead time is dropped
+//! entirely.
+//!
+//! The `duty` and `deadtime` inputs are sampled at the end of
+//! each period (and on the first clock after reset), so that
+//! changes never cause partial pulses or overlaps.  The `fault` input forces both
+//! outputs inactive combinatorially, so it can be wired directly
+//! to an over-current comparator.
+//!
+//! When the dead time comes from a register that software can write,
+//! build the core with [DeadtimePwm::new] and a validated [Config],
+//! which limits the dead time to a safe range.  The `deadtime` input
+//! is clamped to that range as it is loaded.  The [Default] core uses
+//! the default [Config], which allows any dead time that is less than
+//! half the PWM period.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+DeadtimePwm+-----+
+  bN  |                   | bool
++---->| duty         high +---->
+  bN  |                   | bool
++---->| deadtime      low +---->
+ bool |                   |
++---->| fault             |
+      +-------------------+
+")]
+//!
+//! Internally, the core tracks how long the nominal signal has
+//! been stable, and gates each output on that count.  The outputs
+//! are registered, so they are glitch free (apart from the fault).
+//!
+//!# Example
+//!
+//! A 25% duty cycle with a dead time of 3 clocks.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::motion::deadtime_pwm::{DeadtimePwm, In};
+//!
+//! let uut = DeadtimePwm::<U4>::default();
+//! let input = std::iter::repeat_n(
+//!     In { duty: b4(4), deadtime: b4(3), fault: false },
+//!     64,
+//! );
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert!(output.iter().all(|o| !(o.high && o.low)));
+//! ```
+use miette::Diagnostic;
+use rhdl::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    config::{Validate, Validated},
+    core::{constant, dff},
+};
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [DeadtimePwm] core
+pub struct In<N: BitWidth> {
+    /// The number of counts in each period that the
+    /// nominal PWM signal is high
+    pub duty: Bits<N>,
+    /// The number of clocks both outputs are held
+    /// inactive around each edge
+    pub deadtime: Bits<N>,
+    /// Force both outputs inactive
+    pub fault: bool,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// Outputs from the [DeadtimePwm] core
+pub struct Out {
+    /// The high side switch
+    pub high: bool,
+    /// The low side switch
+    pub low: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The complementary PWM core.  Here `N` is the
+/// number of bits in the period counter.
+pub struct DeadtimePwm<N: BitWidth> {
+    min_deadtime: constant::Constant<Bits<N>>,
+    max_deadtime: constant::Constant<Bits<N>>,
+    count: dff::DFF<Bits<N>>,
+    duty: dff::DFF<Bits<N>>,
+    deadtime: dff::DFF<Bits<N>>,
+    nominal: dff::DFF<bool>,
+    since: dff::DFF<Bits<N>>,
+    out: dff::DFF<Out>,
+}
+
+impl<N: BitWidth> Default for DeadtimePwm<N> {
+    fn default() -> Self {
+        Self::new(
+            Config::default()
+                .validate()
+                .expect("The default dead time limits should be valid"),
+        )
+    }
+}
+
+impl<N: BitWidth> DeadtimePwm<N> {
+    /// Create a core that limits the `deadtime` input to
+    /// the range allowed by a validated [Config]
+    pub fn new(config: Validated<Config<N>>) -> Self {
+        Self {
+            min_deadtime: constant::Constant::new(bits(config.min_deadtime as u128)),
+            max_deadtime: constant::Constant::new(bits(config.max_deadtime as u128)),
+            // Start with the last count of an empty period, so
+            // that the settings are loaded on the first clock
+            count: dff::DFF::new(!Bits::<N>::default()),
+            duty: dff::DFF::default(),
+            deadtime: dff::DFF::default(),
+            nominal: dff::DFF::default(),
+            since: dff::DFF::default(),
+            out: dff::DFF::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The limits on the dead time of a [DeadtimePwm] with an
+/// `N` bit period counter.  A `deadtime` input outside of
+/// these limits is clamped to them when it is loaded.
+pub struct Config<N: BitWidth> {
+    /// The shortest dead time (in clocks) allowed
+    pub min_deadtime: usize,
+    /// The longest dead time (in clocks) allowed
+    pub max_deadtime: usize,
+    marker: std::marker::PhantomData<N>,
+}
+
+impl<N: BitWidth> Default for Config<N> {
+    /// The widest range allowed, from no dead time up to
+    /// just under half the PWM period
+    fn default() -> Self {
+        Self::new(0, (1 << N::BITS) / 2 - 1)
+    }
+}
+
+impl<N: BitWidth> Config<N> {
+    /// Limit the dead time to the range `min_deadtime..=max_deadtime`
+    pub fn new(min_deadtime: usize, max_deadtime: usize) -> Self {
+        Self {
+            min_deadtime,
+            max_deadtime,
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors that can arise when validating a [Config]
+pub enum ConfigError {
+    /// The dead time could take up half the period
+    #[error(
+        "max_deadtime is {max_deadtime}, which is not less than half the PWM period of {period}"
+    )]
+    #[diagnostic(help(
+        "The dead time is inserted twice per period, so max_deadtime must be less than {period}/2"
+    ))]
+    DeadtimeTooLong {
+        /// The longest dead time provided
+        max_deadtime: usize,
+        /// The PWM period in clocks
+        period: usize,
+    },
+    /// The range of dead times is empty
+    #[error("min_deadtime ({min_deadtime}) is greater than max_deadtime ({max_deadtime})")]
+    #[diagnostic(help("min_deadtime must not exceed max_deadtime"))]
+    MinAboveMax {
+        /// The shortest dead time provided
+        min_deadtime: usize,
+        /// The longest dead time provided
+        max_deadtime: usize,
+    },
+}
+
+impl<N: BitWidth> Validate for Config<N> {
+    type Error = ConfigError;
+    fn check(&self) -> Result<(), ConfigError> {
+        let period = 1 << N::BITS;
+        if self.max_deadtime * 2 >= period {
+            return Err(ConfigError::DeadtimeTooLong {
+                max_deadtime: self.max_deadtime,
+                period,
+            });
+        }
+        if self.min_deadtime > self.max_deadtime {
+            return Err(ConfigError::MinAboveMax {
+                min_deadtime: self.min_deadtime,
+                max_deadtime: self.max_deadtime,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for DeadtimePwm<N> {
+    type I = In<N>;
+    type O = Out;
+    type Kernel = deadtime_pwm_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn deadtime_pwm_kernel<N: BitWidth>(cr: ClockReset, i: In<N>, q: Q<N>) -> (Out, D<N>) {
+    let mut d = D::<N> {
+        min_deadtime: (),
+        max_deadtime: (),
+        count: q.count + 1,
+        duty: q.duty,
+        deadtime: q.deadtime,
+        nominal: q.nominal,
+        since: q.since,
+        out: q.out,
+    };
+    // The settings take effect at the start of the next period
+    if q.count.all() {
+        d.duty = i.duty;
+        d.deadtime = if i.deadtime > q.max_deadtime {
+            q.max_deadtime
+        } else if i.deadtime < q.min_deadtime {
+            q.min_deadtime
+        } else {
+            i.deadtime
+        };
+    }
+    let nominal = q.count < q.duty;
+    d.nominal = nominal;
+    // The number of clocks the nominal signal has been stable,
+    // saturating at the maximum
+    let since = if nominal != q.nominal {
+        bits(0)
+    } else if q.since.all() {
+        q.since
+    } else {
+        q.since + 1
+    };
+    d.since = since;
+    let settled = since >= q.deadtime;
+    d.out = Out {
+        high: nominal && settled,
+        low: !nominal && settled,
+    };
+    let mut o = q.out;
+    if i.fault {
+        o = Out::default();
+    }
+    if cr.reset.any() {
+        d.count = !bits(0);
+        d.duty = bits(0);
+        d.deadtime = bits(0);
+        d.nominal = false;
+        d.since = bits(0);
+        d.out = Out::default();
+        o = Out::default();
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: Vec<In<U6>>) -> miette::Result<Vec<Out>> {
+        run_with(DeadtimePwm::<U6>::default(), input)
+    }
+
+    fn run_with(uut: DeadtimePwm<U6>, input: Vec<In<U6>>) -> miette::Result<Vec<Out>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn setting(duty: u128, deadtime: u128) -> In<U6> {
+        In {
+            duty: bits(duty),
+            deadtime: bits(deadtime),
+            fault: false,
+        }
+    }
+
+    // The gaps between one output deasserting and the other asserting
+    fn dead_times(output: &[Out]) -> Vec<usize> {
+        let mut gaps = vec![];
+        let mut last_off = None;
+        for (n, w) in output.windows(2).enumerate() {
+            let edges = [(w[0].high, w[1].high, true), (w[0].low, w[1].low, false)];
+            for (was, is, high) in edges {
+                if was && !is {
+                    last_off = Some((n, high));
+                }
+            }
+            for (was, is, high) in edges {
+                if !was && is {
+                    if let Some((start, other)) = last_off {
+                        if other != high {
+                            gaps.push(n - start);
+                        }
+                    }
+                }
+            }
+        }
+        gaps
+    }
+
+    #[test]
+    fn test_duty_scan_never_overlaps() -> miette::Result<()> {
+        for deadtime in [0, 1, 3, 7] {
+            // Each duty setting is held for a few periods
+            let input = (0..64)
+                .flat_map(|duty| std::iter::repeat_n(setting(duty, deadtime), 64 * 3))
+                .collect::<Vec<_>>();
+            let output = run(input)?;
+            assert!(output.iter().all(|o| !(o.high && o.low)));
+            let gaps = dead_times(&output);
+            assert!(gaps.len() > 100);
+            assert!(gaps.iter().all(|g| *g == deadtime as usize), "{gaps:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_constraints() {
+        assert!(Config::<U6>::new(1, 31).validate().is_ok());
+        assert_eq!(
+            Config::<U6>::new(1, 32).validate().unwrap_err(),
+            ConfigError::DeadtimeTooLong {
+                max_deadtime: 32,
+                period: 64
+            }
+        );
+        assert_eq!(
+            Config::<U6>::new(6, 5).validate().unwrap_err(),
+            ConfigError::MinAboveMax {
+                min_deadtime: 6,
+                max_deadtime: 5
+            }
+        );
+        // The default is the widest range that is valid
+        assert_eq!(Config::<U6>::default(), Config::new(0, 31));
+        assert!(Config::<U6>::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_deadtime_is_clamped() -> miette::Result<()> {
+        let config = Config::<U6>::new(2, 5).validate()?;
+        for (deadtime, expected) in [(0, 2), (4, 4), (9, 5)] {
+            let output = run_with(
+                DeadtimePwm::new(config),
+                vec![setting(20, deadtime); 64 * 4],
+            )?;
+            let gaps = dead_times(&output);
+            assert!(!gaps.is_empty());
+            assert!(gaps.iter().all(|g| *g == expected), "{gaps:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pulse_widths() -> miette::Result<()> {
+        let output = run(vec![setting(20, 4); 64 * 4])?;
+        // Skip the first period, which starts with the reset
+        let period = &output[65..129];
+        let high = period.iter().filter(|o| o.high).count();
+        let low = period.iter().filter(|o| o.low).count();
+        // Each output loses the dead time from its pulse
+        assert_eq!(high, 20 - 4);
+        assert_eq!(low, 64 - 20 - 4);
+        // A pulse shorter than the dead time is dropped
+        let output = run(vec![setting(3, 4); 64 * 4])?;
+        assert!(output.iter().all(|o| !o.high));
+        assert!(output.iter().any(|o| o.low));
+        Ok(())
+    }
+
+    #[test]
+    fn test_updates_are_period_buffered() -> miette::Result<()> {
+        // Change the duty cycle part way through a period
+        let mut input = vec![setting(10, 2); 64 * 2 + 30];
+        input.extend(vec![setting(40, 2); 64 * 2]);
+        let output = run(input)?;
+        // Every complete period (the outputs are delayed a clock
+        // from the counter) has a high pulse of one of the two widths
+        for period in output[3..].chunks_exact(64) {
+            let high = period.iter().filter(|o| o.high).count();
+            assert!(high == 8 || high == 38, "{high}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fault_forces_outputs_inactive() -> miette::Result<()> {
+        let mut input = vec![setting(32, 2); 64 * 3];
+        for x in &mut input[70..150] {
+            x.fault = true;
+        }
+        let output = run(input)?;
+        // The reset cycle is first in the output
+        assert!(output[71..151].iter().all(|o| !o.high && !o.low));
+        assert!(output[151..].iter().any(|o| o.high));
+        assert!(output[151..].iter().any(|o| o.low));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deadtime_pwm_hdl() -> miette::Result<()> {
+        let uut = DeadtimePwm::<U6>::default();
+        let mut input = vec![setting(20, 3); 64 * 2];
+        input.extend(vec![setting(50, 5); 64 * 2]);
+        input[100].fault = true;
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
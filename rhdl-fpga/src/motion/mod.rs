@@ -0,0 +1,4 @@
+//! Motion control cores
+pub mod deadtime_pwm;
+pub mod quad_decoder;
+pub mod rotary_encoder;
@@ -0,0 +1,338 @@
+//! Quadrature Decoder
+//!
+//! Rotary encoders (and linear scales) report motion on a pair of
+//! signals, `A` and `B`, that are square waves a quarter of a cycle
+//! apart.  Which one leads gives the direction of motion.  Going
+//! forwards, the pair steps through the sequence `00`, `10`, `11`,
+//! `01` (writing `AB`), and back to `00`, and going in reverse, it steps
+//! through the same sequence backwards.
+//!
+//! The [QuadDecoder] core holds the previous levels of `A` and `B` in a
+//! pair of history registers, and compares them against the current
+//! levels to find each step.  A step changes exactly one of the two
+//! signals.  If both change between one clock and the next, the motion
+//! was too fast to follow (or there was a glitch), and the `error` flag
+//! is strobed instead, with the position unchanged.  The history is
+//! empty after reset, so no step is decoded on the first clock after
+//! reset, whatever the levels of `A` and `B`.
+//!
+//! The [Mode] given at construction sets how many counts there are per
+//! cycle of the inputs:
+//! - [Mode::X4] counts every step, so 4 counts per cycle.
+//! - [Mode::X2] counts the steps that change `A`, so 2 counts per cycle.
+//! - [Mode::X1] counts the steps between `00` and `10`, so 1 count per
+//!   cycle.
+//!
+//! In each mode, moving forwards and then back by the same amount
+//! returns to the same position.  The `position` wraps around in `N`
+//! bits.  The `step` flag is strobed whenever the position changes, and
+//! `dir` gives the direction of the last step (`true` for forwards).
+//! The outputs are registered.
+//!
+//! The input is a tuple of `(a, b)`, and the inputs are assumed to be
+//! synchronous to the clock (for example, using a
+//! [Sync1Bit](crate::cdc::synchronizer::Sync1Bit) core for each).
+//! For a mechanical encoder with detents, use the
+//! [RotaryEncoder](super::rotary_encoder::RotaryEncoder), which
+//! debounces the inputs, and gives one strobe per detent.
+//!
+//!# Example
+//!
+//! One full cycle forwards (plus a clock for the last step to
+//! reach the output).
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::motion::quad_decoder::{Mode, QuadDecoder};
+//!
+//! let uut = QuadDecoder::<U8>::new(Mode::X4);
+//! let input = [(false, false), (true, false), (true, true), (false, true), (false, false)];
+//! let input = input.into_iter().chain([(false, false)]);
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .last()
+//!     .unwrap()
+//!     .value
+//!     .2;
+//! assert_eq!(output.position, s8(4));
+//! assert!(output.dir);
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The number of counts per cycle of the inputs
+pub enum Mode {
+    /// One count per cycle
+    X1,
+    /// Two counts per cycle
+    X2,
+    /// Four counts per cycle
+    #[default]
+    X4,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [QuadDecoder] core
+pub struct Out<N: BitWidth> {
+    /// The position, in counts
+    pub position: SignedBits<N>,
+    /// Strobed when the position changes
+    pub step: bool,
+    /// The direction of the last step (`true` for forwards)
+    pub dir: bool,
+    /// Strobed when both inputs change at once
+    pub error: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The quadrature decoder.  Here `N` is the width
+/// of the position counter.
+pub struct QuadDecoder<N: BitWidth> {
+    mode: constant::Constant<Mode>,
+    prev_a: dff::DFF<bool>,
+    prev_b: dff::DFF<bool>,
+    primed: dff::DFF<bool>,
+    out: dff::DFF<Out<N>>,
+}
+
+impl<N: BitWidth> QuadDecoder<N> {
+    /// Create a new [QuadDecoder] core, that counts
+    /// according to the given [Mode]
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode: constant::Constant::new(mode),
+            prev_a: dff::DFF::default(),
+            prev_b: dff::DFF::default(),
+            primed: dff::DFF::default(),
+            out: dff::DFF::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for QuadDecoder<N> {
+    fn default() -> Self {
+        Self::new(Mode::default())
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for QuadDecoder<N> {
+    type I = (bool, bool);
+    type O = Out<N>;
+    type Kernel = quad_decoder_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn quad_decoder_kernel<N: BitWidth>(
+    _cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (a, b) = i;
+    let mut d = D::<N> {
+        mode: (),
+        prev_a: a,
+        prev_b: b,
+        // The history is filled by the first clock after reset
+        primed: true,
+        out: q.out,
+    };
+    d.out.step = false;
+    d.out.error = false;
+    let a_changed = q.primed && a != q.prev_a;
+    let b_changed = q.primed && b != q.prev_b;
+    // Going forwards, the new A differs from the old B
+    let forward = a != q.prev_b;
+    let count = match q.mode {
+        Mode::X1 => a_changed && !b_changed && !b,
+        Mode::X2 => a_changed && !b_changed,
+        Mode::X4 => a_changed != b_changed,
+    };
+    if a_changed && b_changed {
+        d.out.error = true;
+    } else if count {
+        d.out.step = true;
+        d.out.dir = forward;
+        d.out.position = if forward {
+            q.out.position + 1
+        } else {
+            q.out.position - 1
+        };
+    }
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One cycle of the inputs going forwards, starting from `00`
+    const CYCLE: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+
+    fn forward(cycles: usize) -> Vec<(bool, bool)> {
+        CYCLE.iter().copied().cycle().take(4 * cycles).collect()
+    }
+
+    fn reverse(cycles: usize) -> Vec<(bool, bool)> {
+        CYCLE
+            .iter()
+            .rev()
+            .copied()
+            .cycle()
+            .skip(1)
+            .take(4 * cycles)
+            .collect()
+    }
+
+    // Hold each state of the inputs for a few clocks, as a slowly
+    // turning encoder would
+    fn slow(input: &[(bool, bool)], hold: usize) -> Vec<(bool, bool)> {
+        input
+            .iter()
+            .flat_map(|s| std::iter::repeat_n(*s, hold))
+            .collect()
+    }
+
+    fn run(mode: Mode, input: &[(bool, bool)]) -> miette::Result<Vec<Out<U8>>> {
+        let uut = QuadDecoder::<U8>::new(mode);
+        // Start from 00, and hold the last state for a clock so that
+        // the last step reaches the output
+        let input = std::iter::once((false, false))
+            .chain(input.iter().copied())
+            .chain(input.last().copied());
+        // Skip the reset, and the clock that fills the history
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn position(output: &[Out<U8>]) -> i128 {
+        output.last().unwrap().position.raw()
+    }
+
+    #[test]
+    fn test_counts_per_cycle() -> miette::Result<()> {
+        for (mode, counts) in [(Mode::X1, 1), (Mode::X2, 2), (Mode::X4, 4)] {
+            let output = run(mode, &slow(&forward(5), 3))?;
+            assert_eq!(position(&output), 5 * counts);
+            assert_eq!(
+                output.iter().filter(|o| o.step).count(),
+                5 * counts as usize
+            );
+            assert!(output.iter().all(|o| !o.error));
+            assert!(output.iter().filter(|o| o.step).all(|o| o.dir));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_four_steps_per_cycle() -> miette::Result<()> {
+        // At full speed, one step per clock
+        let output = run(Mode::X4, &forward(3))?;
+        let positions = output.iter().map(|o| o.position.raw()).collect::<Vec<_>>();
+        assert_eq!(positions, (0..=12).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_forward_and_reverse() -> miette::Result<()> {
+        for (mode, counts) in [(Mode::X1, 1), (Mode::X2, 2), (Mode::X4, 4)] {
+            let mut input = forward(3);
+            input.extend(reverse(5));
+            let output = run(mode, &slow(&input, 2))?;
+            assert_eq!(position(&output), -2 * counts);
+            assert!(output.iter().all(|o| !o.error));
+            // The direction changes once
+            let dirs = output
+                .iter()
+                .filter(|o| o.step)
+                .map(|o| o.dir)
+                .collect::<Vec<_>>();
+            assert_eq!(dirs.windows(2).filter(|w| w[0] != w[1]).count(), 1);
+            // And going back over the same ground returns to zero
+            let mut input = forward(4);
+            input.extend(reverse(4));
+            let output = run(mode, &input)?;
+            assert_eq!(position(&output), 0);
+            assert!(output.iter().map(|o| o.position.raw()).all(|p| p >= 0));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_glitch_is_an_error() -> miette::Result<()> {
+        // Both lines change together, from 11 straight to 00
+        let input = [
+            (true, false),
+            (true, true),
+            (false, false),
+            (true, false),
+            (true, true),
+        ];
+        let output = run(Mode::X4, &slow(&input, 2))?;
+        let errors = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.error)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        assert_eq!(errors, [5]);
+        // And the position is left alone
+        assert_eq!(output[4].position, output[5].position);
+        assert_eq!(position(&output), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_step_after_reset() -> miette::Result<()> {
+        // Coming out of reset with the inputs at 11 is not a glitch
+        let uut = QuadDecoder::<U8>::default();
+        let input = [(true, true), (true, true), (false, true)];
+        let output = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert!(output.iter().all(|o| !o.error));
+        assert_eq!(output.last().unwrap().position, s8(0));
+        // But the first real step is counted
+        let output = uut
+            .run(
+                input
+                    .into_iter()
+                    .chain([(false, false)])
+                    .with_reset(1)
+                    .clock_pos_edge(100),
+            )?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert_eq!(output.last().unwrap().position, s8(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quad_decoder_hdl() -> miette::Result<()> {
+        for mode in [Mode::X1, Mode::X2, Mode::X4] {
+            let uut = QuadDecoder::<U8>::new(mode);
+            let mut input = slow(&forward(4), 2);
+            input.extend(slow(&reverse(2), 3));
+            input.extend([(true, true), (false, false)]);
+            let tb = uut
+                .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+                .collect::<SynchronousTestBench<_, _>>();
+            let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+            let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+            tm.run_iverilog()?;
+        }
+        Ok(())
+    }
+}
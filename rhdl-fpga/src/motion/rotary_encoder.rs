@@ -0,0 +1,269 @@
+//! Rotary Encoder Interface
+//!
+//! Cheap mechanical rotary encoders (the kind with a knob and clicks)
+//! have contacts that bounce, and a knob that rocks back and forth a
+//! little as it sits in a detent.  The [RotaryEncoder] core turns the
+//! raw `A` and `B` lines into clean `cw` and `ccw` strobes, one per
+//! detent.  Each line goes through a [Debounce], and the debounced
+//! lines through a [QuadDecoder] in [Mode::X4].  The quadrature steps
+//! are then counted, and a strobe is only issued once `steps` of them
+//! (given at construction, and 4 by default) have been made in the
+//! same direction.  The count then starts again from zero.
+//!
+//! So rocking back and forth across a detent boundary does not give
+//! alternating strobes.  After a `cw` strobe, going back by one step and
+//! forwards again just returns the count to zero, and a `ccw` strobe
+//! needs a full `steps` steps back.  The count starts from zero on
+//! reset, so the knob is assumed to be resting in a detent then.  If
+//! both lines change together (e.g., when the knob rests with both lines
+//! high through reset), the [QuadDecoder] reports an error rather than a
+//! step, and the change is ignored.
+//!
+//! The input is a tuple of `(a, b)`, with `cw` being the direction in
+//! which `A` leads `B`.  The inputs are assumed to be synchronous to the
+//! clock (for example, using a
+//! [Sync1Bit](crate::cdc::synchronizer::Sync1Bit) core for each).  The
+//! `hold` time of the debouncers is given at construction, and `N` is
+//! the width of their counters.  The outputs are registered.
+//!
+//!# Example
+//!
+//! One detent clockwise, with a debounce hold of 2 clocks.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::motion::rotary_encoder::RotaryEncoder;
+//!
+//! let uut = RotaryEncoder::<U4>::new(b4(2), 4);
+//! let states = [(true, false), (true, true), (false, true), (false, false)];
+//! let input = states
+//!     .into_iter()
+//!     .flat_map(|s| std::iter::repeat_n(s, 5))
+//!     .chain(std::iter::repeat_n((false, false), 10));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output.iter().filter(|o| o.cw).count(), 1);
+//! assert_eq!(output.iter().filter(|o| o.ccw).count(), 0);
+//!```
+use rhdl::prelude::*;
+
+use super::quad_decoder::{Mode, QuadDecoder};
+use crate::core::{constant, debounce::Debounce, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [RotaryEncoder] core
+pub struct Out {
+    /// Strobed for each detent clockwise
+    pub cw: bool,
+    /// Strobed for each detent counter clockwise
+    pub ccw: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The rotary encoder interface.  Here `N` is the
+/// width of the debounce counters.
+pub struct RotaryEncoder<N: BitWidth> {
+    steps: constant::Constant<s5>,
+    debounce_a: Debounce<N>,
+    debounce_b: Debounce<N>,
+    decoder: QuadDecoder<U2>,
+    count: dff::DFF<s5>,
+    out: dff::DFF<Out>,
+}
+
+impl<N: BitWidth> RotaryEncoder<N> {
+    /// Create a new [RotaryEncoder] core, that debounces each line
+    /// for `hold` clocks, and issues a strobe for every `steps`
+    /// quadrature steps (which must be between 1 and 15).
+    pub fn new(hold: Bits<N>, steps: u8) -> Self {
+        assert!(
+            (1..=15).contains(&steps),
+            "The steps per detent must be between 1 and 15"
+        );
+        Self {
+            steps: constant::Constant::new(s5(steps as i128)),
+            debounce_a: Debounce::new(hold),
+            debounce_b: Debounce::new(hold),
+            decoder: QuadDecoder::new(Mode::X4),
+            count: dff::DFF::default(),
+            out: dff::DFF::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for RotaryEncoder<N> {
+    fn default() -> Self {
+        Self::new(Bits::<N>::MAX, 4)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for RotaryEncoder<N> {
+    type I = (bool, bool);
+    type O = Out;
+    type Kernel = rotary_encoder_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn rotary_encoder_kernel<N: BitWidth>(
+    _cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Out, D<N>) {
+    let (a, b) = i;
+    let mut d = D::<N> {
+        steps: (),
+        debounce_a: a,
+        debounce_b: b,
+        decoder: (q.debounce_a.level, q.debounce_b.level),
+        count: q.count,
+        out: Out::default(),
+    };
+    if q.decoder.step {
+        let count = if q.decoder.dir {
+            q.count + 1
+        } else {
+            q.count - 1
+        };
+        d.count = count;
+        // A whole detent in either direction
+        if count == q.steps {
+            d.out.cw = true;
+            d.count = s5(0);
+        } else if count == -q.steps {
+            d.out.ccw = true;
+            d.count = s5(0);
+        }
+    }
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOLD: usize = 6;
+
+    // The states of the lines for one detent clockwise, from 00
+    const DETENT: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+
+    // The sequence of states for the given moves (positive for
+    // clockwise), starting from a detent at 00
+    fn moves(steps: &[i32]) -> Vec<(bool, bool)> {
+        let mut phase = 0_i32;
+        let mut states = vec![];
+        for &step in steps {
+            for _ in 0..step.abs() {
+                phase = (phase + step.signum()).rem_euclid(4);
+                states.push(DETENT[((phase + 3) % 4) as usize]);
+            }
+        }
+        states
+    }
+
+    // Play the states as a mechanical encoder would.  Each change of a
+    // contact bounces for a while before it settles, and the states
+    // are held for a while (with some variety to the timing).
+    fn recorded(states: &[(bool, bool)]) -> Vec<(bool, bool)> {
+        let bounces: [&[usize]; 4] = [&[1, 2, 1, 1], &[2, 1, 3], &[], &[1, 1, 1, 3, 2]];
+        let mut wave = vec![(false, false); 20];
+        let mut last = (false, false);
+        for (n, &state) in states.iter().enumerate() {
+            // Alternate between the new state and the old one
+            for (k, &len) in bounces[n % 4].iter().enumerate() {
+                let level = if k % 2 == 0 { state } else { last };
+                wave.extend(std::iter::repeat_n(level, len));
+            }
+            wave.extend(std::iter::repeat_n(state, 2 * HOLD + (n * 7) % 11));
+            last = state;
+        }
+        wave.extend(std::iter::repeat_n(last, 20));
+        wave
+    }
+
+    fn strobes(uut: &RotaryEncoder<U4>, input: &[(bool, bool)]) -> miette::Result<(usize, usize)> {
+        let output = uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert!(output.iter().all(|o| !(o.cw && o.ccw)));
+        Ok((
+            output.iter().filter(|o| o.cw).count(),
+            output.iter().filter(|o| o.ccw).count(),
+        ))
+    }
+
+    fn uut() -> RotaryEncoder<U4> {
+        RotaryEncoder::new(bits(HOLD as u128), 4)
+    }
+
+    #[test]
+    fn test_detents() -> miette::Result<()> {
+        let input = recorded(&moves(&[12, -8, 4]));
+        assert_eq!(strobes(&uut(), &input)?, (4, 2));
+        let input = recorded(&moves(&[-20]));
+        assert_eq!(strobes(&uut(), &input)?, (0, 5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounce_is_filtered() -> miette::Result<()> {
+        // Without the debouncing, the bounces alone would
+        // be far more than 3 detents worth of steps
+        let input = recorded(&moves(&[12]));
+        let changes = input.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(changes > 30);
+        assert_eq!(strobes(&uut(), &input)?, (3, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dither_across_detent() -> miette::Result<()> {
+        // Rocking back and forth across the boundary of a detent
+        // after reaching it gives only the one strobe
+        let mut steps = vec![4];
+        steps.extend([-1, 1].repeat(10));
+        let input = recorded(&moves(&steps));
+        assert_eq!(strobes(&uut(), &input)?, (1, 0));
+        // Just short of the next detent, and just past it
+        let mut steps = vec![3];
+        steps.extend([1, -1].repeat(10));
+        let input = recorded(&moves(&steps));
+        assert_eq!(strobes(&uut(), &input)?, (1, 0));
+        // And rocking while resting in a detent gives nothing
+        let mut steps = vec![];
+        steps.extend([1, -1, -1, 1].repeat(10));
+        let input = recorded(&moves(&steps));
+        assert_eq!(strobes(&uut(), &input)?, (0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_steps_per_detent() -> miette::Result<()> {
+        let input = recorded(&moves(&[12, -6]));
+        for (steps, expected) in [(1, (12, 6)), (2, (6, 3)), (3, (4, 2))] {
+            let uut = RotaryEncoder::<U4>::new(bits(HOLD as u128), steps);
+            assert_eq!(strobes(&uut, &input)?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotary_encoder_hdl() -> miette::Result<()> {
+        let uut = uut();
+        let input = recorded(&moves(&[5, -2, -4, 3]));
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+//! Lint for asynchronous primitive outputs
+//!
+//! The outputs of the primitives in this module are not
+//! synchronous to any clock.  If one of them reaches a register
+//! through combinatorial logic only, that register can go
+//! metastable.  The [unsynchronized_outputs] check walks the
+//! netlist of a circuit, starting at the output of each primitive,
+//! and reports every synchronous element it reaches before
+//! passing through another asynchronous element (like a
+//! [Sync1Bit](crate::cdc::synchronizer::Sync1Bit)).
+//!
+//! Paths that leave the circuit through its outputs are not
+//! reported, since the check cannot see where they end up.
+use std::collections::{BTreeSet, HashMap};
+
+use miette::Diagnostic;
+use rhdl::{
+    core::ntl::{object::BlackBoxMode, spec::OpCode, visit::visit_wires},
+    prelude::*,
+};
+use thiserror::Error;
+
+use super::ASYNC_PRIMITIVE;
+
+/// A warning that the output of an asynchronous primitive
+/// feeds synchronous logic without a synchronizer.
+#[derive(Debug, Error, Clone, PartialEq)]
+#[error("Output of asynchronous primitive {primitive} reaches synchronous element {sink} without a synchronizer")]
+pub struct UnsynchronizedOutput {
+    /// The instance name of the primitive
+    pub primitive: String,
+    /// The instance name of the synchronous element
+    pub sink: String,
+}
+
+impl Diagnostic for UnsynchronizedOutput {
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette::Severity::Warning)
+    }
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(
+            "Pass the output through a synchronizer in the clock domain of the element that uses it",
+        ))
+    }
+}
+
+/// Find the paths in `uut` from the output of an asynchronous
+/// primitive to a synchronous element that do not pass through
+/// a synchronizer.  An empty list means the circuit is clean.
+pub fn unsynchronized_outputs<C: Circuit>(uut: &C) -> Result<Vec<UnsynchronizedOutput>, RHDLError> {
+    let descriptor = uut.descriptor("top")?;
+    let ntl = &descriptor.ntl;
+    // For each register, the ops that read it
+    let mut readers = HashMap::<_, Vec<usize>>::new();
+    for (ndx, lop) in ntl.ops.iter().enumerate() {
+        visit_wires(&lop.op, |sense, wire| {
+            if let Some(reg) = wire.reg() {
+                if sense.is_read() {
+                    readers.entry(reg).or_default().push(ndx);
+                }
+            }
+        });
+    }
+    let mut warnings = vec![];
+    for lop in &ntl.ops {
+        let OpCode::BlackBox(source) = &lop.op else {
+            continue;
+        };
+        let primitive = &ntl.black_boxes[source.code.raw()].code;
+        if !primitive.body.description.starts_with(ASYNC_PRIMITIVE) {
+            continue;
+        }
+        let mut sinks = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        let mut pending = source.lhs.iter().flat_map(|w| w.reg()).collect::<Vec<_>>();
+        while let Some(reg) = pending.pop() {
+            for &ndx in readers.get(&reg).into_iter().flatten() {
+                if !visited.insert(ndx) {
+                    continue;
+                }
+                match &ntl.ops[ndx].op {
+                    OpCode::BlackBox(sink) => {
+                        let sink = &ntl.black_boxes[sink.code.raw()];
+                        if sink.mode == BlackBoxMode::Synchronous {
+                            sinks.insert(sink.code.name.clone());
+                        }
+                    }
+                    op => visit_wires(op, |sense, wire| {
+                        if let Some(reg) = wire.reg() {
+                            if sense.is_write() {
+                                pending.push(reg);
+                            }
+                        }
+                    }),
+                }
+            }
+        }
+        warnings.extend(sinks.into_iter().map(|sink| UnsynchronizedOutput {
+            primitive: primitive.name.clone(),
+            sink,
+        }));
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cdc::synchronizer::Sync1Bit, core::dff::DFF, primitive::sr_latch::SrLatch};
+
+    #[derive(PartialEq, Debug, Digital, Timed)]
+    pub struct In {
+        set: Signal<bool, Red>,
+        reset: Signal<bool, Red>,
+        cr: Signal<ClockReset, Red>,
+        cr_blue: Signal<ClockReset, Blue>,
+    }
+
+    mod into_register {
+        use super::*;
+
+        #[derive(Clone, Circuit, CircuitDQ)]
+        pub struct LatchToRegister {
+            pub latch: SrLatch<Red>,
+            pub reg: Adapter<DFF<bool>, Red>,
+        }
+
+        impl CircuitIO for LatchToRegister {
+            type I = In;
+            type O = Signal<bool, Red>;
+            type Kernel = latch_to_register_kernel;
+        }
+
+        #[kernel]
+        pub fn latch_to_register_kernel(i: In, q: Q) -> (Signal<bool, Red>, D) {
+            let mut d = D::dont_care();
+            d.latch.set = i.set;
+            d.latch.reset = i.reset;
+            d.reg.clock_reset = i.cr;
+            d.reg.input = signal(!q.latch.q.val());
+            (q.reg, d)
+        }
+    }
+
+    mod into_synchronizer {
+        use super::*;
+
+        #[derive(Clone, Circuit, CircuitDQ)]
+        pub struct LatchToSynchronizer {
+            pub latch: SrLatch<Red>,
+            pub sync: Sync1Bit<Red, Blue>,
+        }
+
+        #[derive(PartialEq, Debug, Digital, Timed)]
+        pub struct Out {
+            synced: Signal<bool, Blue>,
+            raw: Signal<bool, Red>,
+        }
+
+        impl CircuitIO for LatchToSynchronizer {
+            type I = In;
+            type O = Out;
+            type Kernel = latch_to_synchronizer_kernel;
+        }
+
+        #[kernel]
+        pub fn latch_to_synchronizer_kernel(i: In, q: Q) -> (Out, D) {
+            let mut d = D::dont_care();
+            d.latch.set = i.set;
+            d.latch.reset = i.reset;
+            d.sync.data = q.latch.q;
+            d.sync.cr = i.cr_blue;
+            let o = Out {
+                synced: q.sync,
+                raw: q.latch.q_n,
+            };
+            (o, d)
+        }
+    }
+
+    #[test]
+    fn test_latch_into_register_is_flagged() -> miette::Result<()> {
+        let uut = into_register::LatchToRegister {
+            latch: SrLatch::default(),
+            reg: Adapter::new(DFF::new(false)),
+        };
+        let warnings = unsynchronized_outputs(&uut)?;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].primitive, "top_latch");
+        assert!(warnings[0].sink.starts_with("top_reg"));
+        assert_eq!(warnings[0].severity(), Some(miette::Severity::Warning));
+        Ok(())
+    }
+
+    #[test]
+    fn test_latch_through_synchronizer_is_clean() -> miette::Result<()> {
+        let uut = into_synchronizer::LatchToSynchronizer {
+            latch: SrLatch::default(),
+            sync: Sync1Bit::default(),
+        };
+        assert!(unsynchronized_outputs(&uut)?.is_empty());
+        Ok(())
+    }
+}
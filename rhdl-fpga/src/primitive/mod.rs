@@ -0,0 +1,51 @@
+//! Expert-level asynchronous primitives
+//!
+//! The cores in this module are built from cross-coupled gates
+//! rather than registers, and are not synchronous to any clock.
+//! They are intended for the small number of places where an
+//! asynchronous storage element is unavoidable (arbitration
+//! between clock domains, capturing a pulse with no clock, etc.),
+//! and should only be used by someone who knows why they need one.
+//!
+//! Each primitive has a defined simulation model, including the
+//! resolution used when both of its inputs are asserted, and emits
+//! a structural Verilog netlist.  Every net and gate in that netlist
+//! is marked `(* dont_touch = "true" *)` so that synthesis does not
+//! collapse the feedback loop into something with different timing.
+//!
+//! The outputs of these primitives can change at any time.  Feeding
+//! them into synchronous logic without passing them through a
+//! synchronizer (like [Sync1Bit](crate::cdc::synchronizer::Sync1Bit))
+//! first is almost always a mistake.  Use [lint::unsynchronized_outputs]
+//! on the enclosing circuit to find such paths.
+use rhdl::{core::hdl::ast::Statement, prelude::*};
+
+pub mod lint;
+pub mod mutex;
+pub mod sr_latch;
+
+/// The description prefix given to the HDL of every primitive
+/// in this module.  The [lint] uses it to find them in a netlist.
+pub(crate) const ASYNC_PRIMITIVE: &str = "Asynchronous primitive";
+
+// Declare a wire that synthesis must keep
+pub(crate) fn keep_wire(name: &str) -> Statement {
+    Statement::Custom(format!("(* dont_touch = \"true\" *) wire {name};"))
+}
+
+// Instantiate a Verilog gate primitive that synthesis must keep
+pub(crate) fn keep_gate(gate: &str, instance: &str, output: &str, inputs: &[&str]) -> Statement {
+    Statement::Custom(format!(
+        "(* dont_touch = \"true\" *) {gate} {instance}({output}, {});",
+        inputs.join(", ")
+    ))
+}
+
+// Drive the bits of the output port `o` that hold the value at `path`
+pub(crate) fn drive_output(kind: Kind, path: Path, wire: &str) -> Result<Statement, RHDLError> {
+    let (range, _) = bit_range(kind, &path)?;
+    Ok(Statement::Custom(format!(
+        "assign o[{}] = {wire};",
+        range.start
+    )))
+}
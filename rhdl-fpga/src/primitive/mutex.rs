@@ -0,0 +1,321 @@
+//! Mutual exclusion element
+//!
+//!# Purpose
+//!
+//! A mutual exclusion (mutex) element arbitrates between two
+//! asynchronous requests.  Requests `r1` and `r2` may come from
+//! different clock domains (or no clock at all).  At most one of the
+//! grants `g1` and `g2` is asserted at any time.  A grant is held for
+//! as long as its request is held, even if the other request arrives
+//! in the meantime.  When the granted request is released, a waiting
+//! request is granted.
+//!
+//! The element is a cross-coupled NAND pair, followed by a filter
+//! that only asserts a grant once the pair has settled to opposite
+//! values.  In hardware, when both requests arrive together the pair
+//! can go metastable, and the filter holds both grants low until it
+//! resolves, in favor of either request.  The simulation model cannot
+//! go metastable, and resolves a tie in favor of `r1`.  Designs that
+//! depend on which request wins a tie are wrong.
+//!
+//! A gate level filter is only an approximation of the analog filter
+//! found in a full custom mutex.  In FPGA fabric, the `dont_touch`
+//! attributes keep the structure intact, but the place and route
+//! should still keep the two NAND gates close together.
+//!
+//! This is an expert-level primitive.  See the [module](super) level
+//! documentation for the precautions needed when using its outputs.
+//!
+//!# Schematic Symbol
+//!
+#![doc = badascii_doc::badascii_formal!(r"
+      +--+MutexElement+--+
+      |                  |
+ +--->| r1            g1 +--->
+      |                  |
+ +--->| r2            g2 +--->
+      |                  |
+      +------------------+
+")]
+//!
+//!# Structure
+//!
+//! The emitted Verilog is equivalent to
+//!
+#![doc = badascii_doc::badascii!(r"
+          +-----+  x1                   +-----+
+ r1 +---->|     +------+--------------->|o    |
+          |NAND |      |             +->| AND +---> g1
+     +--->|     |      |             |  +-----+
+     |    +-----+      |             |
+     |           +-----+             |
+     |           |                   |  +-----+
+     |           |   +-----+  x2     |  |     |
+     |           +-->|     +---------+->| AND +---> g2
+     |               |NAND |         |  |    o|<-+
+ r2 +------------+-->|     |         |  +-----+  |
+     |               +-----+         |           |
+     +-------------------------------+    x1 +---+
+")]
+//!
+//! with every net and gate marked `dont_touch`.
+use rhdl::{
+    core::hdl::ast::{index, unsigned_wire_decl},
+    prelude::*,
+};
+
+use super::{drive_output, keep_gate, keep_wire, ASYNC_PRIMITIVE};
+
+/// The [MutexElement] primitive.  Request `r1` (and grant
+/// `g1`) is nominally in domain `A`, and request `r2` (and
+/// grant `g2`) in domain `B`.  The grants are not synchronous
+/// to either clock.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct MutexElement<A: Domain, B: Domain> {
+    _a: std::marker::PhantomData<A>,
+    _b: std::marker::PhantomData<B>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Inputs to the [MutexElement]
+pub struct In<A: Domain, B: Domain> {
+    /// The first request
+    pub r1: Signal<bool, A>,
+    /// The second request
+    pub r2: Signal<bool, B>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Outputs from the [MutexElement]
+pub struct Out<A: Domain, B: Domain> {
+    /// The grant for the first request
+    pub g1: Signal<bool, A>,
+    /// The grant for the second request
+    pub g2: Signal<bool, B>,
+}
+
+impl<A: Domain, B: Domain> CircuitDQ for MutexElement<A, B> {
+    type D = ();
+    type Q = ();
+}
+
+impl<A: Domain, B: Domain> CircuitIO for MutexElement<A, B> {
+    type I = In<A, B>;
+    type O = Out<A, B>;
+    type Kernel = NoKernel2<Self::I, (), (Self::O, ())>;
+}
+
+#[derive(PartialEq, Debug, Digital)]
+#[doc(hidden)]
+pub struct S {
+    g1: bool,
+    g2: bool,
+}
+
+impl<A: Domain, B: Domain> Circuit for MutexElement<A, B> {
+    type S = S;
+
+    fn init(&self) -> Self::S {
+        S {
+            g1: false,
+            g2: false,
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("{ASYNC_PRIMITIVE}: mutual exclusion element")
+    }
+
+    fn sim(&self, input: Self::I, state: &mut Self::S) -> Self::O {
+        let r1 = input.r1.val();
+        let r2 = input.r2.val();
+        trace("r1", &r1);
+        trace("r2", &r2);
+        // A held grant stays with its request.  Otherwise, grant
+        // a pending request, with r1 winning a tie.
+        let g1 = r1 && !(state.g2 && r2);
+        let g2 = r2 && !g1;
+        state.g1 = g1;
+        state.g2 = g2;
+        trace("g1", &g1);
+        trace("g2", &g2);
+        Out {
+            g1: signal(g1),
+            g2: signal(g2),
+        }
+    }
+
+    fn descriptor(&self, name: &str) -> Result<CircuitDescriptor, RHDLError> {
+        Ok(CircuitDescriptor {
+            unique_name: name.to_string(),
+            input_kind: <Self::I as Timed>::static_kind(),
+            output_kind: <Self::O as Timed>::static_kind(),
+            d_kind: Kind::Empty,
+            q_kind: Kind::Empty,
+            children: Default::default(),
+            rtl: None,
+            ntl: rhdl::core::ntl::builder::circuit_black_box(self, name)?,
+        })
+    }
+
+    fn hdl(&self, name: &str) -> Result<HDLDescriptor, RHDLError> {
+        let mut module = Module {
+            name: name.to_owned(),
+            description: self.description(),
+            ..Default::default()
+        };
+        let i_kind = <Self::I as Timed>::static_kind();
+        let o_kind = <Self::O as Timed>::static_kind();
+        module.ports = vec![
+            port("i", Direction::Input, HDLKind::Wire, unsigned_width(2)),
+            port("o", Direction::Output, HDLKind::Wire, unsigned_width(2)),
+        ];
+        module
+            .declarations
+            .extend([unsigned_wire_decl("r1", 1), unsigned_wire_decl("r2", 1)]);
+        let reassign = |name: &str, path: Path| {
+            continuous_assignment(name, index("i", bit_range(i_kind, &path).unwrap().0))
+        };
+        module.statements.extend([
+            keep_wire("x1"),
+            keep_wire("x2"),
+            keep_wire("x1_n"),
+            keep_wire("x2_n"),
+            keep_wire("g1"),
+            keep_wire("g2"),
+            reassign("r1", Path::default().field("r1").signal_value()),
+            reassign("r2", Path::default().field("r2").signal_value()),
+            keep_gate("nand", "u_x1", "x1", &["r1", "x2"]),
+            keep_gate("nand", "u_x2", "x2", &["r2", "x1"]),
+            keep_gate("not", "u_x1_n", "x1_n", &["x1"]),
+            keep_gate("not", "u_x2_n", "x2_n", &["x2"]),
+            keep_gate("and", "u_g1", "g1", &["x1_n", "x2"]),
+            keep_gate("and", "u_g2", "g2", &["x2_n", "x1"]),
+            drive_output(o_kind, Path::default().field("g1").signal_value(), "g1")?,
+            drive_output(o_kind, Path::default().field("g2").signal_value(), "g2")?,
+        ]);
+        Ok(HDLDescriptor {
+            name: name.into(),
+            body: module,
+            children: Default::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+
+    fn grants(pattern: &[(bool, bool)]) -> miette::Result<Vec<(bool, bool)>> {
+        let uut = MutexElement::<Red, Blue>::default();
+        let input = pattern.iter().enumerate().map(|(ndx, &(r1, r2))| {
+            timed_sample(
+                ndx as u64 * 10,
+                In {
+                    r1: signal(r1),
+                    r2: signal(r2),
+                },
+            )
+        });
+        Ok(uut
+            .run(input)?
+            .map(|s| (s.value.1.g1.val(), s.value.1.g2.val()))
+            .collect())
+    }
+
+    #[test]
+    fn test_uncontested_requests() -> miette::Result<()> {
+        let pattern = [
+            (false, false),
+            (true, false),
+            (false, false),
+            (false, true),
+            (false, false),
+        ];
+        let expected = [
+            (false, false),
+            (true, false),
+            (false, false),
+            (false, true),
+            (false, false),
+        ];
+        assert_eq!(grants(&pattern)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_held_grant_blocks_other_request() -> miette::Result<()> {
+        // r2 holds the grant while r1 waits, and r1 gets it when r2 lets go
+        let pattern = [(false, true), (true, true), (true, true), (true, false)];
+        assert_eq!(
+            grants(&pattern)?,
+            [(false, true), (false, true), (false, true), (true, false)]
+        );
+        // And the same with the roles swapped
+        let pattern = [(true, false), (true, true), (false, true), (false, false)];
+        assert_eq!(
+            grants(&pattern)?,
+            [(true, false), (true, false), (false, true), (false, false)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_simultaneous_requests_resolve_to_r1() -> miette::Result<()> {
+        let pattern = [(false, false), (true, true), (true, true), (false, true)];
+        assert_eq!(
+            grants(&pattern)?,
+            [(false, false), (true, false), (true, false), (false, true)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_grants_are_exclusive() -> miette::Result<()> {
+        let pattern = (0..64)
+            .map(|n| (n & 1 != 0, n & 2 != 0))
+            .chain((0..64).map(|n| (n % 3 == 0, n % 5 < 2)))
+            .collect::<Vec<_>>();
+        for ((r1, r2), (g1, g2)) in pattern.iter().zip(grants(&pattern)?) {
+            assert!(!(g1 && g2));
+            assert!(!g1 || *r1);
+            assert!(!g2 || *r2);
+            // Some request is always granted if one is pending
+            assert!(!(*r1 || *r2) || g1 || g2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verilog_structure() -> miette::Result<()> {
+        let uut = MutexElement::<Red, Blue>::default();
+        let hdl = uut.hdl("top")?.as_module().as_verilog();
+        let expect = expect![[r#"
+            // Asynchronous primitive: mutual exclusion element
+            module top(input wire [1:0] i, output wire [1:0] o);
+                wire [0:0] r1;
+                wire [0:0] r2;
+                (* dont_touch = "true" *) wire x1;
+                (* dont_touch = "true" *) wire x2;
+                (* dont_touch = "true" *) wire x1_n;
+                (* dont_touch = "true" *) wire x2_n;
+                (* dont_touch = "true" *) wire g1;
+                (* dont_touch = "true" *) wire g2;
+                assign r1 = i[0];
+                assign r2 = i[1];
+                (* dont_touch = "true" *) nand u_x1(x1, r1, x2);
+                (* dont_touch = "true" *) nand u_x2(x2, r2, x1);
+                (* dont_touch = "true" *) not u_x1_n(x1_n, x1);
+                (* dont_touch = "true" *) not u_x2_n(x2_n, x2);
+                (* dont_touch = "true" *) and u_g1(g1, x1_n, x2);
+                (* dont_touch = "true" *) and u_g2(g2, x2_n, x1);
+                assign o[0] = g1;
+                assign o[1] = g2;
+            endmodule
+        "#]];
+        expect.assert_eq(&hdl);
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+//! Set/reset latch
+//!
+//!# Purpose
+//!
+//! An asynchronous set/reset latch, built from a pair of cross-coupled
+//! NOR gates.  Asserting `set` drives `q` high, asserting `reset` drives
+//! `q` low, and with neither asserted the latch holds its value.  It
+//! has no clock, so it can capture a pulse that is too short for any
+//! register in the design to see.
+//!
+//! The latch is reset dominant.  The `set` input is gated by `reset`
+//! before it reaches the NOR pair, so that when both inputs are asserted
+//! `q` is low and `q_n` is high.  The outputs are always complementary,
+//! unlike a bare NOR latch which drives both low in that case.  When both
+//! inputs are released together, the simulation model holds the cleared
+//! state.  In hardware that is only guaranteed if `reset` is released no
+//! earlier than `set`, since otherwise the gated set can glitch high.
+//!
+//! This is an expert-level primitive.  See the [module](super) level
+//! documentation for the precautions needed when using its outputs.
+//!
+//!# Schematic Symbol
+//!
+#![doc = badascii_doc::badascii_formal!(r"
+      +--+SrLatch+-----+
+      |                |
+ +--->| set          q +--->
+      |                |
+ +--->| reset      q_n +--->
+      |                |
+      +----------------+
+")]
+//!
+//!# Structure
+//!
+//! The emitted Verilog is equivalent to
+//!
+#![doc = badascii_doc::badascii!(r"
+             +----+
+ set +------>|    | set_gated  +----+
+             |AND +----------->|    |
+ reset +--+->|o   |            |NOR +-----+-> q_n
+          |  +----+        +-->|    |     |
+          |                |   +----+     |
+          |     +----------+              |
+          |     |  +----------------------+
+          |     |  |   +----+
+          |     |  +-->|    |
+          |     |      |NOR +--------+-----> q
+          +--------+-->|    |        |
+                |      +----+        |
+                +--------------------+
+")]
+//!
+//! with every net and gate marked `dont_touch`.
+use rhdl::{
+    core::hdl::ast::{index, unsigned_wire_decl},
+    prelude::*,
+};
+
+use super::{drive_output, keep_gate, keep_wire, ASYNC_PRIMITIVE};
+
+/// The [SrLatch] primitive.  The inputs come from domain
+/// `D`, but the outputs change whenever the inputs do, and
+/// are not synchronous to the clock of `D`.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SrLatch<D: Domain> {
+    _d: std::marker::PhantomData<D>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Inputs to the [SrLatch]
+pub struct In<D: Domain> {
+    /// Drive the latch output high
+    pub set: Signal<bool, D>,
+    /// Drive the latch output low (dominates `set`)
+    pub reset: Signal<bool, D>,
+}
+
+#[derive(PartialEq, Debug, Digital, Timed)]
+/// Outputs from the [SrLatch]
+pub struct Out<D: Domain> {
+    /// The stored value
+    pub q: Signal<bool, D>,
+    /// The complement of the stored value
+    pub q_n: Signal<bool, D>,
+}
+
+impl<D: Domain> CircuitDQ for SrLatch<D> {
+    type D = ();
+    type Q = ();
+}
+
+impl<D: Domain> CircuitIO for SrLatch<D> {
+    type I = In<D>;
+    type O = Out<D>;
+    type Kernel = NoKernel2<Self::I, (), (Self::O, ())>;
+}
+
+impl<D: Domain> Circuit for SrLatch<D> {
+    type S = bool;
+
+    fn init(&self) -> Self::S {
+        false
+    }
+
+    fn description(&self) -> String {
+        format!("{ASYNC_PRIMITIVE}: reset dominant SR latch")
+    }
+
+    fn sim(&self, input: Self::I, state: &mut Self::S) -> Self::O {
+        let set = input.set.val();
+        let reset = input.reset.val();
+        trace("set", &set);
+        trace("reset", &reset);
+        if reset {
+            *state = false;
+        } else if set {
+            *state = true;
+        }
+        trace("q", state);
+        Out {
+            q: signal(*state),
+            q_n: signal(!*state),
+        }
+    }
+
+    fn descriptor(&self, name: &str) -> Result<CircuitDescriptor, RHDLError> {
+        Ok(CircuitDescriptor {
+            unique_name: name.to_string(),
+            input_kind: <Self::I as Timed>::static_kind(),
+            output_kind: <Self::O as Timed>::static_kind(),
+            d_kind: Kind::Empty,
+            q_kind: Kind::Empty,
+            children: Default::default(),
+            rtl: None,
+            ntl: rhdl::core::ntl::builder::circuit_black_box(self, name)?,
+        })
+    }
+
+    fn hdl(&self, name: &str) -> Result<HDLDescriptor, RHDLError> {
+        let mut module = Module {
+            name: name.to_owned(),
+            description: self.description(),
+            ..Default::default()
+        };
+        let i_kind = <Self::I as Timed>::static_kind();
+        let o_kind = <Self::O as Timed>::static_kind();
+        module.ports = vec![
+            port("i", Direction::Input, HDLKind::Wire, unsigned_width(2)),
+            port("o", Direction::Output, HDLKind::Wire, unsigned_width(2)),
+        ];
+        module
+            .declarations
+            .extend([unsigned_wire_decl("set", 1), unsigned_wire_decl("reset", 1)]);
+        let reassign = |name: &str, path: Path| {
+            continuous_assignment(name, index("i", bit_range(i_kind, &path).unwrap().0))
+        };
+        module.statements.extend([
+            keep_wire("reset_n"),
+            keep_wire("set_gated"),
+            keep_wire("q"),
+            keep_wire("q_n"),
+            reassign("set", Path::default().field("set").signal_value()),
+            reassign("reset", Path::default().field("reset").signal_value()),
+            keep_gate("not", "u_reset_n", "reset_n", &["reset"]),
+            keep_gate("and", "u_set_gate", "set_gated", &["set", "reset_n"]),
+            keep_gate("nor", "u_q", "q", &["reset", "q_n"]),
+            keep_gate("nor", "u_q_n", "q_n", &["set_gated", "q"]),
+            drive_output(o_kind, Path::default().field("q").signal_value(), "q")?,
+            drive_output(o_kind, Path::default().field("q_n").signal_value(), "q_n")?,
+        ]);
+        Ok(HDLDescriptor {
+            name: name.into(),
+            body: module,
+            children: Default::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+
+    fn latch_stream(
+        pattern: &[(bool, bool)],
+    ) -> impl Iterator<Item = TimedSample<In<Red>>> + use<'_> {
+        pattern.iter().enumerate().map(|(ndx, &(set, reset))| {
+            timed_sample(
+                ndx as u64 * 10,
+                In {
+                    set: signal(set),
+                    reset: signal(reset),
+                },
+            )
+        })
+    }
+
+    fn q_values(pattern: &[(bool, bool)]) -> miette::Result<Vec<(bool, bool)>> {
+        let uut = SrLatch::<Red>::default();
+        Ok(uut
+            .run(latch_stream(pattern))?
+            .map(|s| (s.value.1.q.val(), s.value.1.q_n.val()))
+            .collect())
+    }
+
+    #[test]
+    fn test_set_hold_reset() -> miette::Result<()> {
+        let pattern = [
+            (false, false),
+            (true, false),
+            (false, false),
+            (false, true),
+            (false, false),
+        ];
+        let expected = [
+            (false, true),
+            (true, false),
+            (true, false),
+            (false, true),
+            (false, true),
+        ];
+        assert_eq!(q_values(&pattern)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_contested_inputs_reset_wins() -> miette::Result<()> {
+        // Both asserted while set, and then both released together
+        let pattern = [(true, false), (true, true), (false, false)];
+        assert_eq!(
+            q_values(&pattern)?,
+            [(true, false), (false, true), (false, true)]
+        );
+        // Releasing reset first while set is held sets the latch
+        let pattern = [(true, true), (true, false), (false, false)];
+        assert_eq!(
+            q_values(&pattern)?,
+            [(false, true), (true, false), (true, false)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verilog_structure() -> miette::Result<()> {
+        let uut = SrLatch::<Red>::default();
+        let hdl = uut.hdl("top")?.as_module().as_verilog();
+        let expect = expect![[r#"
+            // Asynchronous primitive: reset dominant SR latch
+            module top(input wire [1:0] i, output wire [1:0] o);
+                wire [0:0] set;
+                wire [0:0] reset;
+                (* dont_touch = "true" *) wire reset_n;
+                (* dont_touch = "true" *) wire set_gated;
+                (* dont_touch = "true" *) wire q;
+                (* dont_touch = "true" *) wire q_n;
+                assign set = i[0];
+                assign reset = i[1];
+                (* dont_touch = "true" *) not u_reset_n(reset_n, reset);
+                (* dont_touch = "true" *) and u_set_gate(set_gated, set, reset_n);
+                (* dont_touch = "true" *) nor u_q(q, reset, q_n);
+                (* dont_touch = "true" *) nor u_q_n(q_n, set_gated, q);
+                assign o[0] = q;
+                assign o[1] = q_n;
+            endmodule
+        "#]];
+        expect.assert_eq(&hdl);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hdl_generation() -> miette::Result<()> {
+        let uut = SrLatch::<Red>::default();
+        let pattern = [
+            (false, false),
+            (true, false),
+            (false, false),
+            (true, true),
+            (true, false),
+            (false, true),
+            (false, false),
+        ];
+        let tb = uut
+            .run(latch_stream(&pattern))?
+            .collect::<TestBench<_, _>>();
+        let test_mod = tb.rtl(&uut, &TestBenchOptions::default())?;
+        test_mod.run_iverilog()?;
+        Ok(())
+    }
+}
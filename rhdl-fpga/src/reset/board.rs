@@ -0,0 +1,428 @@
+//! Board Reset
+//!
+//!# Purpose
+//!
+//! Board reset buttons and brown-out detectors are noisy and
+//! asynchronous, and feeding them straight into a [ClockReset] can
+//! reset part of a design, and not the rest.  The [BoardReset] core
+//! conditions the board level reset sources into a single clean reset
+//! for the logic of a clock domain:
+//!
+//! - The external reset `pin` is sampled by a two flop synchronizer,
+//!   and then passed through a glitch filter, so that a pulse of
+//!   `filter` clocks or less is ignored.
+//! - The `watchdog` and `debug` requests are already synchronous, and
+//!   are used as is.
+//! - The sources are OR-ed together, and the reset is stretched, so that
+//!   it is asserted for at least `stretch` clocks, however short the
+//!   request.
+//! - After configuration, a power-on-reset counter holds the reset for
+//!   `por` clocks.  The `por_done` output is set once it has expired.
+//!
+//! Each source sets a sticky flag in the `causes` output, which stays set
+//! until cleared by writing a one to the matching bit of the `clear`
+//! input.  The flags are meant to be mapped into a status register, so
+//! that firmware can find out why it was reset.  The `power_on` flag is
+//! set after configuration.
+//!
+//! The registers of the core take their power on values when the device
+//! is configured, and when the core itself is reset (which is how a
+//! simulation models the configuration).  Otherwise, the reset of its own
+//! [ClockReset] should be held low, since the core is the source of the
+//! reset.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+       +-+BoardReset+--------+
+ bool  |                     | Reset
++----->| pin           reset +----->
+ bool  |                     | bool
++----->| watchdog   por_done +----->
+ bool  |                     | Causes
++----->| debug         causes+----->
+Causes |                     |
++----->| clear               |
+       +---------------------+
+")]
+//!
+//!# Timing
+//!
+//! The pin passes through the 2 synchronizer flops, and must then be
+//! seen for `filter + 1` clocks by the glitch filter before it causes a
+//! reset.  The watchdog and debug requests cause a reset on the next
+//! clock.  The reset output is registered.
+//!
+//!# Example
+//!
+//! A debug request stretched to 8 clocks, after a power on reset of 16
+//! clocks.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::reset::board::{BoardReset, Causes, In};
+//!
+//! let uut = BoardReset::new(3, 8, 16);
+//! let input = (0..40).map(|n| In {
+//!     pin: false,
+//!     watchdog: false,
+//!     debug: n == 25,
+//!     clear: Causes::default(),
+//! });
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! let resets = output.iter().filter(|o| o.reset.any()).count();
+//! assert_eq!(resets, 16 + 8);
+//! assert!(output.last().unwrap().causes.debug);
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The sticky flags that record the sources of a reset
+pub struct Causes {
+    /// The device was configured (powered on)
+    pub power_on: bool,
+    /// The external reset pin was asserted
+    pub pin: bool,
+    /// The watchdog timed out
+    pub watchdog: bool,
+    /// The debug bridge requested a reset
+    pub debug: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The inputs to the [BoardReset]
+pub struct In {
+    /// The external reset pin (active high, and asynchronous)
+    pub pin: bool,
+    /// The watchdog timeout (synchronous)
+    pub watchdog: bool,
+    /// The reset request from the debug bridge (synchronous)
+    pub debug: bool,
+    /// The cause flags to clear (write one to clear)
+    pub clear: Causes,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [BoardReset]
+pub struct Out {
+    /// The conditioned reset
+    pub reset: Reset,
+    /// Set once the power on reset has expired
+    pub por_done: bool,
+    /// The sticky cause flags
+    pub causes: Causes,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+#[doc(hidden)]
+pub struct Config {
+    filter: b8,
+    stretch: b16,
+    por_len: b16,
+    asserted: Reset,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The board reset conditioner
+pub struct BoardReset {
+    sync1: dff::DFF<bool>,
+    sync2: dff::DFF<bool>,
+    glitch: dff::DFF<b8>,
+    hold: dff::DFF<b16>,
+    por: dff::DFF<b16>,
+    done: dff::DFF<bool>,
+    reset: dff::DFF<bool>,
+    causes: dff::DFF<Causes>,
+    config: constant::Constant<Config>,
+}
+
+impl BoardReset {
+    /// Create a [BoardReset] that ignores pulses on the pin of
+    /// `filter` clocks or less, asserts the reset for at least
+    /// `stretch` clocks, and holds it for `por` clocks after
+    /// configuration.
+    pub fn new(filter: u8, stretch: u16, por: u16) -> Self {
+        assert!(
+            stretch > 0 && por > 0,
+            "The reset must be stretched to at least one clock, and held for at least one clock"
+        );
+        Self {
+            sync1: dff::DFF::new(false),
+            sync2: dff::DFF::new(false),
+            glitch: dff::DFF::new(b8(0)),
+            hold: dff::DFF::new(b16(0)),
+            por: dff::DFF::new(b16(0)),
+            done: dff::DFF::new(false),
+            reset: dff::DFF::new(true),
+            causes: dff::DFF::new(Causes {
+                power_on: true,
+                ..Default::default()
+            }),
+            config: constant::Constant::new(Config {
+                filter: bits(filter as u128),
+                stretch: bits(stretch as u128 - 1),
+                por_len: bits(por as u128 - 1),
+                asserted: reset(true),
+            }),
+        }
+    }
+}
+
+impl Default for BoardReset {
+    fn default() -> Self {
+        Self::new(4, 16, 1024)
+    }
+}
+
+impl SynchronousIO for BoardReset {
+    type I = In;
+    type O = Out;
+    type Kernel = board_reset_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn board_reset_kernel(_cr: ClockReset, i: In, q: Q) -> (Out, D) {
+    let mut d = D::dont_care();
+    // Synchronize the pin
+    d.sync1 = i.pin;
+    d.sync2 = q.sync1;
+    // The glitch filter counts the clocks the pin has been
+    // asserted, up to the filter length
+    let pin = q.sync2 && q.glitch == q.config.filter;
+    d.glitch = q.glitch;
+    if !q.sync2 {
+        d.glitch = bits(0);
+    } else if !pin {
+        d.glitch = q.glitch + 1;
+    }
+    // The power on reset counter
+    let por_done = q.por == q.config.por_len;
+    d.por = q.por;
+    if !por_done {
+        d.por = q.por + 1;
+    }
+    d.done = por_done;
+    // Stretch the requests
+    let request = pin || i.watchdog || i.debug;
+    d.hold = q.hold;
+    if request {
+        d.hold = q.config.stretch;
+    } else if q.hold != 0 {
+        d.hold = q.hold - 1;
+    }
+    d.reset = !por_done || request || q.hold != 0;
+    // The sticky cause flags
+    d.causes = Causes {
+        power_on: q.causes.power_on && !i.clear.power_on,
+        pin: (q.causes.pin && !i.clear.pin) || pin,
+        watchdog: (q.causes.watchdog && !i.clear.watchdog) || i.watchdog,
+        debug: (q.causes.debug && !i.clear.debug) || i.debug,
+    };
+    let mut rst = Reset::default();
+    if q.reset {
+        rst = q.config.asserted;
+    }
+    let o = Out {
+        reset: rst,
+        por_done: q.done,
+        causes: q.causes,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTER: u8 = 3;
+    const STRETCH: u16 = 8;
+    const POR: u16 = 16;
+
+    fn idle() -> In {
+        In {
+            pin: false,
+            watchdog: false,
+            debug: false,
+            clear: Causes::default(),
+        }
+    }
+
+    fn run(input: impl Fn(usize) -> In, clocks: usize) -> miette::Result<Vec<Out>> {
+        let uut = BoardReset::new(FILTER, STRETCH, POR);
+        Ok(uut
+            .run((0..clocks).map(input).with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // The clocks on which the reset is asserted
+    fn resets(output: &[Out]) -> Vec<usize> {
+        output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.reset.any())
+            .map(|(n, _)| n)
+            .collect()
+    }
+
+    #[test]
+    fn test_power_on_reset() -> miette::Result<()> {
+        let output = run(|_| idle(), 40)?;
+        assert_eq!(resets(&output), (0..POR as usize).collect::<Vec<_>>());
+        assert!(output[..POR as usize].iter().all(|o| !o.por_done));
+        assert!(output[POR as usize..].iter().all(|o| o.por_done));
+        assert!(output.iter().all(|o| o.causes
+            == Causes {
+                power_on: true,
+                ..Default::default()
+            }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_each_source_is_stretched() -> miette::Result<()> {
+        let por = 0..POR as usize;
+        // The watchdog and debug requests reset the logic from the next
+        // clock, for the stretched duration
+        for (source, fire) in [
+            (
+                Causes {
+                    watchdog: true,
+                    ..Default::default()
+                },
+                (|n| In {
+                    watchdog: n == 30,
+                    ..idle()
+                }) as fn(usize) -> In,
+            ),
+            (
+                Causes {
+                    debug: true,
+                    ..Default::default()
+                },
+                |n| In {
+                    debug: n == 30,
+                    ..idle()
+                },
+            ),
+        ] {
+            let output = run(fire, 60)?;
+            let expected = por.clone().chain(31..31 + STRETCH as usize);
+            assert_eq!(resets(&output), expected.collect::<Vec<_>>());
+            assert_eq!(
+                output.last().unwrap().causes,
+                Causes {
+                    power_on: true,
+                    ..source
+                }
+            );
+            assert_eq!(
+                output[30].causes,
+                Causes {
+                    power_on: true,
+                    ..Default::default()
+                }
+            );
+        }
+        // The pin passes through the synchronizer, and the glitch
+        // filter, before the reset is stretched
+        let output = run(
+            |n| In {
+                pin: (30..35).contains(&n),
+                ..idle()
+            },
+            60,
+        )?;
+        let start = 30 + 2 + FILTER as usize + 1;
+        let expected = por.chain(start..start + 1 + STRETCH as usize);
+        assert_eq!(resets(&output), expected.collect::<Vec<_>>());
+        assert!(output.last().unwrap().causes.pin);
+        Ok(())
+    }
+
+    #[test]
+    fn test_glitch_never_resets() -> miette::Result<()> {
+        // Pulses of up to FILTER clocks, and gaps between them, are ignored
+        let glitches = [30..31, 40..42, 50..53, 60..63];
+        let output = run(
+            |n| In {
+                pin: glitches.iter().any(|g| g.contains(&n)),
+                ..idle()
+            },
+            80,
+        )?;
+        assert_eq!(resets(&output), (0..POR as usize).collect::<Vec<_>>());
+        assert!(!output.last().unwrap().causes.pin);
+        Ok(())
+    }
+
+    #[test]
+    fn test_causes_are_sticky_until_cleared() -> miette::Result<()> {
+        let output = run(
+            |n| In {
+                watchdog: n == 20,
+                debug: n == 25,
+                clear: Causes {
+                    power_on: n == 40,
+                    watchdog: n == 40,
+                    debug: n == 45 || n == 25,
+                    ..Default::default()
+                },
+                ..idle()
+            },
+            60,
+        )?;
+        let causes = |n: usize| output[n].causes;
+        assert_eq!(
+            causes(39),
+            Causes {
+                power_on: true,
+                pin: false,
+                watchdog: true,
+                debug: true
+            }
+        );
+        // A new cause wins over a clear on the same clock
+        assert!(causes(26).debug);
+        assert_eq!(
+            causes(41),
+            Causes {
+                debug: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(causes(46), Causes::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_board_reset_hdl() -> miette::Result<()> {
+        let uut = BoardReset::new(2, 3, 5);
+        let input = (0..60).map(|n| In {
+            pin: n % 17 > 11,
+            watchdog: n == 30,
+            debug: n == 44,
+            clear: Causes {
+                watchdog: n == 40,
+                ..Default::default()
+            },
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
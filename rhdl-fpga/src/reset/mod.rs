@@ -1,4 +1,5 @@
 //! Cores that help with Reset handling
+pub mod board;
 pub mod conditioner;
 pub mod negating_conditioner;
 pub mod negation;
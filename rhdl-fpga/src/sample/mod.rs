@@ -0,0 +1,2 @@
+//! Cores for sampling serial lines
+pub mod oversampler;
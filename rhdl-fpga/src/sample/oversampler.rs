@@ -0,0 +1,408 @@
+//! Majority Voting Oversampler
+//!
+//! Receivers for asynchronous serial lines (like a UART, or
+//! Manchester and IR decoders) typically sample the line `K`
+//! times per nominal bit, and decide the value of the bit by
+//! a majority vote of the samples taken around the center of
+//! the bit.  The [Oversampler] provides this logic as a building
+//! block.
+//!
+//! On each `strobe` (which should arrive `K` times per bit, e.g.,
+//! from a fractional divider), the line is sampled.  After `K`
+//! samples, the bit is complete, and the core reports:
+//!
+//! - the value of the bit, which is the majority of the three
+//!   samples at the center of the bit (`K/2 - 1`, `K/2`, `K/2 + 1`),
+//! - a `boundary` strobe, which is asserted for one clock,
+//! - a `unanimous` flag, which is set if the three center samples
+//!   agree.  This can be used as a measure of confidence in the bit.
+//! - the position of the first edge seen within the bit, if any.
+//!
+//! If the sampling window is aligned with the transmitter, edges
+//! are seen at position `0`.  An edge in the first half of the bit
+//! means the window is starting too early, and an edge in the second
+//! half of the bit means it is starting too late.  Downstream cores
+//! can use the `adjust` input to stretch or shrink the next bit by
+//! one sample to re-center their timing on a transmitter whose clock
+//! is skewed.  The adjustment is held until it is applied at the
+//! next bit boundary.
+//!
+//! The line is assumed to be synchronous to the clock (for
+//! example, after a synchronizer).
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+Oversampler+-----+
+  bool  |                   | bool
+ +----->| strobe        bit +------>
+  bool  |                   | bool
+ +----->| line     boundary +------>
+ ?Adj   |                   | bool
+ +----->| adjust  unanimous +------>
+        |                   | ?b8
+        |              edge +------>
+        +-------------------+
+")]
+//!
+//!# Example
+//!
+//! Recovering bits with 8 samples per bit.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::sample::oversampler::{In, Oversampler};
+//!
+//! let uut = Oversampler::<8>::default();
+//! let bits = [true, false, false, true, true, false];
+//! let input = bits.iter().flat_map(|b| std::iter::repeat_n(*b, 8)).map(|line| In {
+//!     strobe: true,
+//!     line,
+//!     adjust: None,
+//! });
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .filter(|t| t.value.2.boundary)
+//!     .map(|t| t.value.2.bit)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(output, bits[..bits.len() - 1]);
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// An adjustment to the length of the next bit
+pub enum Adjust {
+    #[default]
+    /// Take one extra sample in the next bit
+    Stretch,
+    /// Take one less sample in the next bit
+    Shrink,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The inputs to the [Oversampler]
+pub struct In {
+    /// Sample the line on this clock
+    pub strobe: bool,
+    /// The (synchronized) line
+    pub line: bool,
+    /// Adjust the timing of the next bit
+    pub adjust: Option<Adjust>,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs from the [Oversampler]
+pub struct Out {
+    /// The voted value of the last bit
+    pub bit: bool,
+    /// Strobed when a bit is complete
+    pub boundary: bool,
+    /// Set if the center samples of the bit agree
+    pub unanimous: bool,
+    /// The position of the first edge in the bit (if any)
+    pub edge: Option<b8>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The oversampling core.  Here `K` is the number of
+/// samples per bit, which must be between 3 and 128.
+pub struct Oversampler<const K: usize> {
+    first: constant::Constant<b8>,
+    last: constant::Constant<b8>,
+    phase: dff::DFF<b8>,
+    prev: dff::DFF<bool>,
+    votes: dff::DFF<b2>,
+    edge: dff::DFF<Option<b8>>,
+    adjust: dff::DFF<Option<Adjust>>,
+    out: dff::DFF<Out>,
+}
+
+impl<const K: usize> Default for Oversampler<K> {
+    fn default() -> Self {
+        assert!(
+            (3..=128).contains(&K),
+            "The oversampler needs between 3 and 128 samples per bit"
+        );
+        Self {
+            first: constant::Constant::new(bits((K / 2 - 1) as u128)),
+            last: constant::Constant::new(bits((K - 1) as u128)),
+            phase: dff::DFF::default(),
+            prev: dff::DFF::default(),
+            votes: dff::DFF::default(),
+            edge: dff::DFF::new(None),
+            adjust: dff::DFF::new(None),
+            out: dff::DFF::default(),
+        }
+    }
+}
+
+impl<const K: usize> SynchronousIO for Oversampler<K> {
+    type I = In;
+    type O = Out;
+    type Kernel = oversampler_kernel<K>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn oversampler_kernel<const K: usize>(cr: ClockReset, i: In, q: Q<K>) -> (Out, D<K>) {
+    let mut d = D::<K>::dont_care();
+    d.phase = q.phase;
+    d.prev = q.prev;
+    d.votes = q.votes;
+    d.edge = q.edge;
+    d.adjust = q.adjust;
+    d.out = q.out;
+    d.out.boundary = false;
+    if let Some(adjust) = i.adjust {
+        d.adjust = Some(adjust);
+    }
+    if i.strobe {
+        let sample = i.line;
+        d.prev = sample;
+        // Tally the samples around the center of the bit
+        let center = q.phase >= q.first && q.phase <= q.first + 2;
+        if center && sample {
+            d.votes = q.votes + 1;
+        }
+        // Record the position of the first edge.  The phase is
+        // only outside of the bit when it has been stretched.
+        let seen = match q.edge {
+            Some(_) => true,
+            None => false,
+        };
+        if sample != q.prev && !seen && q.phase <= q.last {
+            d.edge = Some(q.phase);
+        }
+        d.phase = q.phase + 1;
+        if q.phase == q.last {
+            d.out = Out {
+                bit: d.votes >= 2,
+                boundary: true,
+                unanimous: d.votes == 0 || d.votes == 3,
+                edge: d.edge,
+            };
+            d.votes = bits(0);
+            d.edge = None;
+            // Start the next bit one sample early or late, as requested
+            d.phase = bits(0);
+            if let Some(adjust) = d.adjust {
+                d.phase = match adjust {
+                    Adjust::Stretch => bits(255),
+                    Adjust::Shrink => bits(1),
+                };
+            }
+            d.adjust = None;
+        }
+    }
+    if cr.reset.any() {
+        d.phase = bits(0);
+        d.votes = bits(0);
+        d.edge = None;
+        d.adjust = None;
+        d.out = Out::default();
+    }
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    const K: usize = 16;
+
+    // A transmitter sending `bits`, with `period` samples per bit.
+    // Each edge is displaced by up to `jitter` samples, and single
+    // sample glitches are injected with the given probability.
+    fn transmit(seed: u64, bits: &[bool], period: f64, jitter: i64, glitch: f64) -> Vec<bool> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let len = (bits.len() as f64 * period) as usize;
+        let edges = (0..=bits.len())
+            .map(|n| (n as f64 * period) as i64 + rng.random_range(-jitter..=jitter))
+            .collect::<Vec<_>>();
+        let mut line = (0..len as i64)
+            .map(|t| {
+                let n = edges.iter().rposition(|e| *e <= t).unwrap_or(0);
+                bits[n.min(bits.len() - 1)]
+            })
+            .collect::<Vec<_>>();
+        for sample in line.iter_mut() {
+            if rng.random_bool(glitch) {
+                *sample = !*sample;
+            }
+        }
+        line
+    }
+
+    fn random_bits(seed: u64, count: usize) -> Vec<bool> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        (0..count).map(|_| rng.random()).collect()
+    }
+
+    fn receive(uut: &Oversampler<K>, line: &[bool]) -> miette::Result<Vec<Out>> {
+        let input = line.iter().map(|&line| In {
+            strobe: true,
+            line,
+            adjust: None,
+        });
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .filter(|o| o.boundary)
+            .collect())
+    }
+
+    #[test]
+    fn test_clean_line() -> miette::Result<()> {
+        let uut = Oversampler::<K>::default();
+        let bits = random_bits(0, 200);
+        let output = receive(&uut, &transmit(0, &bits, K as f64, 0, 0.0))?;
+        assert!(output.iter().all(|o| o.unanimous));
+        assert!(output.iter().all(|o| o.edge.unwrap_or(b8(0)) == b8(0)));
+        let voted = output.iter().map(|o| o.bit).collect::<Vec<_>>();
+        assert_eq!(voted, bits[..voted.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strobe_rate() -> miette::Result<()> {
+        let uut = Oversampler::<K>::default();
+        let bits = random_bits(1, 50);
+        // Sample on every third clock
+        let line = transmit(1, &bits, K as f64, 0, 0.0);
+        let input = line.iter().flat_map(|&line| {
+            [true, false, false].map(|strobe| In {
+                strobe,
+                line,
+                adjust: None,
+            })
+        });
+        let voted = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .filter(|o| o.boundary)
+            .map(|o| o.bit)
+            .collect::<Vec<_>>();
+        assert_eq!(voted, bits[..voted.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jitter_and_glitches() -> miette::Result<()> {
+        let uut = Oversampler::<K>::default();
+        for seed in 0..5 {
+            let bits = random_bits(seed, 300);
+            let output = receive(&uut, &transmit(seed, &bits, K as f64, 3, 0.02))?;
+            let voted = output.iter().map(|o| o.bit).collect::<Vec<_>>();
+            let errors = voted.iter().zip(&bits).filter(|(a, b)| a != b).count();
+            // Two glitches in the center samples of a single bit are
+            // very rare at this rate, so the vote should be reliable.
+            assert!(errors <= 1, "{errors} errors with seed {seed}");
+            // Glitches in the center reduce the confidence
+            assert!(output.iter().any(|o| !o.unanimous));
+        }
+        Ok(())
+    }
+
+    // A receiver that uses the edge position to re-center on
+    // the transmitter.
+    #[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+    struct Recentering {
+        sampler: Oversampler<K>,
+    }
+
+    impl SynchronousIO for Recentering {
+        type I = bool;
+        type O = Out;
+        type Kernel = recentering_kernel;
+    }
+
+    #[kernel]
+    fn recentering_kernel(_cr: ClockReset, i: bool, q: Q) -> (Out, D) {
+        let mut d = D::dont_care();
+        d.sampler = In {
+            strobe: true,
+            line: i,
+            adjust: None,
+        };
+        if q.sampler.boundary {
+            if let Some(edge) = q.sampler.edge {
+                if edge != 0 {
+                    d.sampler.adjust = if edge < 8 {
+                        Some(Adjust::Stretch)
+                    } else {
+                        Some(Adjust::Shrink)
+                    };
+                }
+            }
+        }
+        (q.sampler, d)
+    }
+
+    fn count_errors(output: &[Out], bits: &[bool]) -> usize {
+        output
+            .iter()
+            .zip(bits)
+            .filter(|(o, b)| o.bit != **b)
+            .count()
+    }
+
+    #[test]
+    fn test_recentering_on_skewed_transmitter() -> miette::Result<()> {
+        // The transmitter runs about 2% slow or fast
+        for period in [16.3, 15.7] {
+            let bits = random_bits(2, 500);
+            let line = transmit(2, &bits, period, 1, 0.0);
+            // Without re-centering, the sampling window drifts off the bits
+            let uut = Oversampler::<K>::default();
+            let output = receive(&uut, &line)?;
+            assert!(count_errors(&output, &bits) > 50);
+            // With re-centering, every bit is recovered
+            let uut = Recentering {
+                sampler: Oversampler::default(),
+            };
+            let output = uut
+                .run(line.iter().copied().with_reset(1).clock_pos_edge(100))?
+                .synchronous_sample()
+                .map(|t| t.value.2)
+                .filter(|o| o.boundary)
+                .collect::<Vec<_>>();
+            assert!(output.len() >= 490);
+            assert_eq!(count_errors(&output, &bits), 0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_oversampler_hdl() -> miette::Result<()> {
+        let uut = Oversampler::<K>::default();
+        let bits = random_bits(3, 20);
+        let line = transmit(3, &bits, K as f64, 2, 0.02);
+        let input = line
+            .iter()
+            .enumerate()
+            .map(|(n, &line)| In {
+                strobe: true,
+                line,
+                adjust: match n % 50 {
+                    10 => Some(Adjust::Stretch),
+                    30 => Some(Adjust::Shrink),
+                    _ => None,
+                },
+            })
+            .with_reset(1)
+            .clock_pos_edge(100);
+        let tb = uut.run(input)?.collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,371 @@
+//! SPI Master
+//!
+//! Exchanges bytes with an SPI device, in SPI mode 0.  The clock (`sclk`)
+//! idles low, the device samples `mosi` on the rising edge of the clock,
+//! and both sides change their data on the falling edge.  Bytes are sent
+//! MSB first.  Each half of the clock lasts exactly `half_period` clocks,
+//! which is provided when the core is constructed.
+//!
+//! The [SpiMaster] takes a stream of bytes, with a ready/valid handshake
+//! (see [crate::stream]).  Each byte is sent in its own frame: the chip
+//! select (`cs_n`) is asserted half a clock before the first rising edge,
+//! and released half a clock after the last falling edge.  The byte
+//! received from the device on `miso` during the frame is presented on
+//! `reply` for a single clock, as the chip select is released.  The core is
+//! ready for the next byte half a clock after that.
+//!
+//! The `miso` line is sampled at the end of each high phase of the clock,
+//! rather than at the rising edge, so that the device (and any
+//! synchronizer on the way back) has up to half a clock to respond.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+SpiMaster+------+
+  ?b8   |                  | bool
+ +----->| data        sclk +----->
+ R<b8>  |                  | bool
+ <------+ ready       mosi +----->
+  bool  |                  | bool
+ +----->| miso        cs_n +----->
+        |                  | ?b8
+        |            reply +----->
+        +------------------+
+")]
+//!
+//!# Example
+//!
+//! Sending a byte, with a clock of 1/4 the system clock.  The device
+//! is not connected, so the reply is all ones.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::spi::master::{In, SpiMaster};
+//!
+//! let uut = SpiMaster::new(2);
+//! let input = (0..60).map(|n| In {
+//!     data: (n == 0).then_some(b8(0xA5)),
+//!     miso: true,
+//! });
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! // The data on each rising edge of the clock
+//! let sent = output
+//!     .windows(2)
+//!     .filter(|w| !w[0].sclk && w[1].sclk)
+//!     .map(|w| w[1].mosi)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(sent, [true, false, true, false, false, true, false, true]);
+//! let reply = output.iter().filter_map(|o| o.reply).collect::<Vec<_>>();
+//! assert_eq!(reply, [b8(0xFF)]);
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    core::{constant, dff},
+    stream::{ready, Ready},
+};
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [SpiMaster]
+pub struct In {
+    /// The stream of bytes to send
+    pub data: Option<b8>,
+    /// The data from the device
+    pub miso: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [SpiMaster]
+pub struct Out {
+    /// The clock to the device
+    pub sclk: bool,
+    /// The data to the device
+    pub mosi: bool,
+    /// The chip select to the device (active low)
+    pub cs_n: bool,
+    /// The byte received from the device, for a single clock
+    pub reply: Option<b8>,
+    /// The ready signal to the byte stream
+    pub ready: Ready<b8>,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum State {
+    #[default]
+    Idle,
+    Select,
+    Transfer,
+    Release,
+    Gap,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The SPI master core
+pub struct SpiMaster {
+    last_phase: constant::Constant<b16>,
+    state: dff::DFF<State>,
+    phase: dff::DFF<b16>,
+    sclk: dff::DFF<bool>,
+    cs_n: dff::DFF<bool>,
+    send: dff::DFF<b8>,
+    receive: dff::DFF<b8>,
+    bit: dff::DFF<b3>,
+    reply: dff::DFF<Option<b8>>,
+}
+
+impl SpiMaster {
+    /// Create a master with each half of the clock lasting
+    /// `half_period` system clocks
+    pub fn new(half_period: u16) -> Self {
+        assert!(half_period > 0, "The half period must be at least 1");
+        Self {
+            last_phase: constant::Constant::new(bits(half_period as u128 - 1)),
+            state: dff::DFF::default(),
+            phase: dff::DFF::default(),
+            sclk: dff::DFF::default(),
+            cs_n: dff::DFF::new(true),
+            send: dff::DFF::default(),
+            receive: dff::DFF::default(),
+            bit: dff::DFF::default(),
+            reply: dff::DFF::new(None),
+        }
+    }
+}
+
+impl SynchronousIO for SpiMaster {
+    type I = In;
+    type O = Out;
+    type Kernel = spi_master_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn spi_master_kernel(cr: ClockReset, i: In, q: Q) -> (Out, D) {
+    let mut d = D::dont_care();
+    d.state = q.state;
+    d.sclk = q.sclk;
+    d.cs_n = q.cs_n;
+    d.send = q.send;
+    d.receive = q.receive;
+    d.bit = q.bit;
+    d.reply = None;
+    let tick = q.phase == q.last_phase;
+    d.phase = if tick { bits(0) } else { q.phase + 1 };
+    let idle = q.state == State::Idle;
+    match q.state {
+        State::Idle => {
+            d.phase = bits(0);
+            if let Some(byte) = i.data {
+                // Select the device, with the first bit on the line
+                d.cs_n = false;
+                d.send = byte;
+                d.bit = bits(0);
+                d.state = State::Select;
+            }
+        }
+        State::Select => {
+            if tick {
+                d.sclk = true;
+                d.state = State::Transfer;
+            }
+        }
+        State::Transfer => {
+            if tick {
+                d.sclk = !q.sclk;
+                if q.sclk {
+                    // The end of the high phase, so the data
+                    // from the device is sampled, and the next
+                    // bit goes out with the falling edge
+                    let bit: b8 = if i.miso { bits(1) } else { bits(0) };
+                    d.receive = (q.receive << 1) | bit;
+                    d.send = q.send << 1;
+                    d.bit = q.bit + 1;
+                    if q.bit == 7 {
+                        d.state = State::Release;
+                    }
+                }
+            }
+        }
+        State::Release => {
+            if tick {
+                d.cs_n = true;
+                d.reply = Some(q.receive);
+                d.state = State::Gap;
+            }
+        }
+        State::Gap => {
+            if tick {
+                d.state = State::Idle;
+            }
+        }
+    }
+    let o = Out {
+        sclk: q.sclk,
+        mosi: (q.send & bits(0x80)) != 0,
+        cs_n: q.cs_n,
+        reply: q.reply,
+        ready: ready::<b8>(idle),
+    };
+    if cr.reset.any() {
+        d.state = State::Idle;
+        d.phase = bits(0);
+        d.sclk = false;
+        d.cs_n = true;
+        d.reply = None;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rhdl::core::sim::ResetOrData;
+
+    use super::*;
+    use crate::conformance::spi::{SpiPins, SpiTargets};
+
+    // A device that replies to each byte with the complement
+    // of the byte before it (starting from 0xFF)
+    #[derive(Default)]
+    struct Device {
+        sclk: bool,
+        cs_n: bool,
+        shift_in: u8,
+        shift_out: u8,
+        bits: usize,
+        received: Vec<u8>,
+    }
+
+    impl Device {
+        fn step(&mut self, out: &Out) -> bool {
+            if self.cs_n && !out.cs_n {
+                let last = self.received.last().copied().unwrap_or(0);
+                self.shift_out = !last;
+                self.bits = 0;
+            }
+            if !out.cs_n && !self.sclk && out.sclk {
+                self.shift_in = (self.shift_in << 1) | out.mosi as u8;
+                self.bits += 1;
+                if self.bits == 8 {
+                    self.received.push(self.shift_in);
+                }
+            }
+            if !out.cs_n && self.sclk && !out.sclk {
+                self.shift_out <<= 1;
+            }
+            self.sclk = out.sclk;
+            self.cs_n = out.cs_n;
+            self.shift_out & 0x80 != 0
+        }
+    }
+
+    // Offer the bytes to the master, in order, as fast as it takes
+    // them, with `stall` idle clocks after each one, and return the
+    // outputs and the bytes seen by the device
+    fn run(
+        uut: &SpiMaster,
+        bytes: &[u8],
+        stall: usize,
+        len: usize,
+    ) -> miette::Result<(Vec<Out>, Vec<u8>)> {
+        let mut device = Device {
+            cs_n: true,
+            ..Default::default()
+        };
+        let mut next = 0;
+        let mut clocks = 0;
+        let mut wait = 0;
+        let output = uut
+            .run_fn(
+                |out: Out| {
+                    clocks += 1;
+                    if clocks == 1 {
+                        return Some(ResetOrData::Reset);
+                    }
+                    if clocks > len {
+                        return None;
+                    }
+                    let miso = device.step(&out);
+                    if wait > 0 {
+                        wait -= 1;
+                        return Some(ResetOrData::Data(In { data: None, miso }));
+                    }
+                    let data = bytes.get(next).map(|b| b8(*b as u128));
+                    if out.ready.raw && data.is_some() {
+                        next += 1;
+                        wait = stall;
+                    }
+                    Some(ResetOrData::Data(In { data, miso }))
+                },
+                100,
+            )
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect();
+        Ok((output, device.received))
+    }
+
+    #[test]
+    fn test_bytes_are_exchanged() -> miette::Result<()> {
+        let bytes = [0x00, 0xFF, 0x5A, 0x81, b'R', b'H', b'D', b'L'];
+        for half_period in [1, 2, 5] {
+            for stall in [0, 7] {
+                let uut = SpiMaster::new(half_period);
+                let len = 200 * half_period as usize;
+                let (output, received) = run(&uut, &bytes, stall, len)?;
+                assert_eq!(received, bytes);
+                let replies = output.iter().filter_map(|o| o.reply).collect::<Vec<_>>();
+                let expected = std::iter::once(0xFF)
+                    .chain(bytes.iter().map(|b| !b))
+                    .take(bytes.len())
+                    .map(|b| b8(b as u128))
+                    .collect::<Vec<_>>();
+                assert_eq!(replies, expected);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_spi_master_conforms() -> miette::Result<()> {
+        let (output, _) = run(&SpiMaster::new(2), &[0xA5, 0x3C, 0x0F], 0, 200)?;
+        let pins = output
+            .iter()
+            .map(|o| SpiPins {
+                sclk: o.sclk,
+                mosi: o.mosi,
+                cs_n: Some(o.cs_n),
+            })
+            .collect::<Vec<_>>();
+        // Between frames the clock is low for the release, the gap,
+        // the select and at least a clock in between
+        let report = SpiTargets::new(2, 7).check(&pins);
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.get("sclk high").unwrap().count, 24);
+        assert_eq!(report.get("cs lead").unwrap().min, 2);
+        assert_eq!(report.get("cs lag").unwrap().min, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spi_master_hdl() -> miette::Result<()> {
+        let uut = SpiMaster::new(2);
+        let input = (0..300).map(|n| In {
+            data: (n % 50 == 1).then_some(b8(n as u128 / 2 + 3)),
+            miso: n % 7 < 3,
+        });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+//! SPI cores
+pub mod master;
+pub mod uart_bridge;
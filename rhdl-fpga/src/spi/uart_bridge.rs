@@ -0,0 +1,347 @@
+//! SPI to UART Bridge
+//!
+//! Lets a host on a serial line talk to an SPI device.  Each byte
+//! received by the [UartRx] is sent to the device by the [SpiMaster]
+//! (in a frame of its own), and the byte that the device sends back
+//! during that frame is returned to the host by the [UartTx].  So the
+//! host sees one byte back for every byte it sends, just as if it
+//! were clocking the SPI device itself.
+//!
+//! The bytes from the host are queued in a [SyncFIFO] in front of the
+//! SPI master, and the replies are queued in a second [SyncFIFO] in
+//! front of the transmitter.  Each core pops its FIFO when it is ready
+//! for the next byte.  The first FIFO takes up the slack when the SPI
+//! frames take longer than the serial bytes, and the second when the
+//! host sends slightly faster than the replies can be returned.  If
+//! either FIFO overflows, the byte is lost, and `overflow` is pulsed.
+//!
+//! Each FIFO holds `2^N - 1` bytes.  Both serial lines use the same
+//! `clocks_per_bit`, and each half of the SPI clock lasts
+//! `half_period` clocks.  These are provided when the core is
+//! constructed.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+SpiToUartBridge+-----+
+  bool  |                       | bool
+ +----->| rx                 tx +----->
+  bool  |                       | bool
+ +----->| miso             sclk +----->
+        |                       | bool
+        |                  mosi +----->
+        |                       | bool
+        |                  cs_n +----->
+        |                       | bool
+        |              overflow +----->
+        |                       | bool
+        |           frame_error +----->
+        +-----------------------+
+")]
+//!
+//!# Example
+//!
+//! A bridge with 15 byte FIFOs, from 115200 baud to a 10MHz SPI
+//! clock, with a 100MHz system clock.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::spi::uart_bridge::SpiToUartBridge;
+//!
+//! let uut = SpiToUartBridge::<U4>::new(868, 5);
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    core::option::is_some,
+    fifo::synchronous::{self, SyncFIFO},
+    uart::{
+        rx::{self, UartRx},
+        tx::{self, UartTx},
+    },
+};
+
+use super::master::{self, SpiMaster};
+
+#[derive(PartialEq, Debug, Digital)]
+/// Inputs to the [SpiToUartBridge]
+pub struct In {
+    /// The serial line from the host
+    pub rx: bool,
+    /// The data from the SPI device
+    pub miso: bool,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [SpiToUartBridge]
+pub struct Out {
+    /// The serial line to the host
+    pub tx: bool,
+    /// The clock to the SPI device
+    pub sclk: bool,
+    /// The data to the SPI device
+    pub mosi: bool,
+    /// The chip select to the SPI device (active low)
+    pub cs_n: bool,
+    /// Pulsed when a byte is lost because a FIFO is full
+    pub overflow: bool,
+    /// Pulsed when a frame is received without a stop bit
+    pub frame_error: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The SPI to UART bridge.  Here `N` is the number of
+/// address bits of each FIFO.
+pub struct SpiToUartBridge<N: BitWidth> {
+    rx: UartRx,
+    request: SyncFIFO<b8, N>,
+    spi: SpiMaster,
+    reply: SyncFIFO<b8, N>,
+    tx: UartTx,
+}
+
+impl<N: BitWidth> SpiToUartBridge<N> {
+    /// Create a bridge with each serial bit lasting `clocks_per_bit`
+    /// clocks, and each half of the SPI clock lasting `half_period`
+    /// clocks
+    pub fn new(clocks_per_bit: u16, half_period: u16) -> Self {
+        Self {
+            rx: UartRx::new(clocks_per_bit),
+            request: SyncFIFO::default(),
+            spi: SpiMaster::new(half_period),
+            reply: SyncFIFO::default(),
+            tx: UartTx::new(clocks_per_bit),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for SpiToUartBridge<N> {
+    type I = In;
+    type O = Out;
+    type Kernel = spi_to_uart_bridge_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn spi_to_uart_bridge_kernel<N: BitWidth>(_cr: ClockReset, i: In, q: Q<N>) -> (Out, D<N>) {
+    let rx: rx::Out = q.rx;
+    let spi: master::Out = q.spi;
+    let tx: tx::Out = q.tx;
+    let request: synchronous::Out<b8> = q.request;
+    let reply: synchronous::Out<b8> = q.reply;
+    // The head of each FIFO is offered to the core behind it, and
+    // is taken out of the FIFO when that core accepts it
+    let d = D::<N> {
+        rx: i.rx,
+        request: synchronous::In::<b8> {
+            data: rx.data,
+            next: spi.ready.raw && is_some::<b8>(request.data),
+        },
+        spi: master::In {
+            data: request.data,
+            miso: i.miso,
+        },
+        reply: synchronous::In::<b8> {
+            data: spi.reply,
+            next: tx.ready.raw && is_some::<b8>(reply.data),
+        },
+        tx: reply.data,
+    };
+    let o = Out {
+        tx: tx.line,
+        sclk: spi.sclk,
+        mosi: spi.mosi,
+        cs_n: spi.cs_n,
+        overflow: request.overflow || reply.overflow,
+        frame_error: rx.frame_error,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect_file;
+    use rhdl::core::sim::ResetOrData;
+
+    use super::*;
+
+    // The line for a sequence of bytes, sent back to back
+    fn line(bytes: &[u8], clocks_per_bit: usize) -> Vec<bool> {
+        bytes
+            .iter()
+            .flat_map(|byte| {
+                let data = (0..8).map(move |k| byte & (1 << k) != 0);
+                std::iter::once(false)
+                    .chain(data)
+                    .chain(std::iter::once(true))
+            })
+            .flat_map(|b| std::iter::repeat_n(b, clocks_per_bit))
+            .collect()
+    }
+
+    // Decode the line as 8N1 frames, sampled in the middle of each bit
+    fn decode(line: &[bool], clocks_per_bit: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut n = 1;
+        while n + 10 * clocks_per_bit < line.len() {
+            if line[n - 1] && !line[n] {
+                let center = n + clocks_per_bit / 2;
+                let bit = |k: usize| line[center + k * clocks_per_bit];
+                let byte = (1..=8).fold(0, |b, k| b | (bit(k) as u8) << (k - 1));
+                assert!(bit(9), "Missing stop bit after {n}");
+                bytes.push(byte);
+                n = center + 9 * clocks_per_bit;
+            } else {
+                n += 1;
+            }
+        }
+        bytes
+    }
+
+    // An SPI device (in mode 0) that replies to each byte with
+    // the byte plus one, in the following frame
+    struct Device {
+        sclk: bool,
+        cs_n: bool,
+        shift_in: u8,
+        shift_out: u8,
+        bits: usize,
+        received: Vec<u8>,
+    }
+
+    impl Device {
+        fn new() -> Self {
+            Self {
+                sclk: false,
+                cs_n: true,
+                shift_in: 0,
+                shift_out: 0,
+                bits: 0,
+                received: vec![],
+            }
+        }
+        fn step(&mut self, out: &Out) -> bool {
+            if self.cs_n && !out.cs_n {
+                let last = self.received.last().copied().unwrap_or(0xFF);
+                self.shift_out = last.wrapping_add(1);
+                self.bits = 0;
+            }
+            if !out.cs_n && !self.sclk && out.sclk {
+                self.shift_in = (self.shift_in << 1) | out.mosi as u8;
+                self.bits += 1;
+                if self.bits == 8 {
+                    self.received.push(self.shift_in);
+                }
+            }
+            if !out.cs_n && self.sclk && !out.sclk {
+                self.shift_out <<= 1;
+            }
+            self.sclk = out.sclk;
+            self.cs_n = out.cs_n;
+            self.shift_out & 0x80 != 0
+        }
+    }
+
+    // Send the bytes from the host, with the device attached to the
+    // SPI side, and return the outputs and the bytes seen by the device
+    fn run<N: BitWidth>(
+        uut: &SpiToUartBridge<N>,
+        bytes: &[u8],
+        clocks_per_bit: usize,
+    ) -> miette::Result<(Vec<Out>, Vec<u8>)> {
+        // Leave time for the replies to drain at the end
+        let rx = std::iter::repeat_n(true, 100)
+            .chain(line(bytes, clocks_per_bit))
+            .chain(std::iter::repeat_n(true, 2000))
+            .collect::<Vec<_>>();
+        let mut device = Device::new();
+        let mut clocks = 0;
+        let output = uut
+            .run_fn(
+                |out: Out| {
+                    clocks += 1;
+                    if clocks == 1 {
+                        return Some(ResetOrData::Reset);
+                    }
+                    let rx = *rx.get(clocks - 2)?;
+                    let miso = device.step(&out);
+                    Some(ResetOrData::Data(In { rx, miso }))
+                },
+                100,
+            )
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect();
+        Ok((output, device.received))
+    }
+
+    #[test]
+    fn test_bytes_are_bridged() -> miette::Result<()> {
+        let message = b"Hello, RHDL!";
+        for half_period in [1, 2, 7] {
+            let uut = SpiToUartBridge::<U4>::new(16, half_period);
+            let (output, received) = run(&uut, message, 16)?;
+            assert_eq!(received, message);
+            // The host gets back the reply to each byte, which is
+            // the byte before it, plus one
+            let tx = output.iter().map(|o| o.tx).collect::<Vec<_>>();
+            let expected = std::iter::once(0xFF)
+                .chain(message.iter().copied())
+                .take(message.len())
+                .map(|b: u8| b.wrapping_add(1))
+                .collect::<Vec<_>>();
+            assert_eq!(decode(&tx, 16), expected);
+            assert!(output.iter().all(|o| !o.overflow && !o.frame_error));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_slow_spi_clock_is_queued() -> miette::Result<()> {
+        // Each SPI frame takes longer than a serial byte, so the
+        // requests queue up in front of the SPI master
+        let message = (0..20).map(|n| n * 11 + 3).collect::<Vec<u8>>();
+        let uut = SpiToUartBridge::<U5>::new(4, 4);
+        let (output, received) = run(&uut, &message, 4)?;
+        assert_eq!(received, message);
+        let tx = output.iter().map(|o| o.tx).collect::<Vec<_>>();
+        assert_eq!(decode(&tx, 4).len(), message.len());
+        assert!(output.iter().all(|o| !o.overflow));
+        // With too small a FIFO, requests are lost
+        let uut = SpiToUartBridge::<U2>::new(4, 4);
+        let (output, received) = run(&uut, &message, 4)?;
+        assert!(received.len() < message.len());
+        assert!(output.iter().any(|o| o.overflow));
+        Ok(())
+    }
+
+    #[test]
+    fn test_spi_to_uart_bridge_verilog() -> miette::Result<()> {
+        let uut = SpiToUartBridge::<U4>::new(868, 5);
+        let verilog = uut.hdl("top")?.as_module().as_verilog();
+        expect_file!["uart_bridge.v.expect"].assert_eq(&verilog);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spi_to_uart_bridge_hdl() -> miette::Result<()> {
+        let uut = SpiToUartBridge::<U2>::new(3, 1);
+        let rx = line(&[0x12, 0xC3, 0x7E], 3);
+        let input = std::iter::repeat_n(true, 10)
+            .chain(rx)
+            .chain(std::iter::repeat_n(true, 60))
+            .enumerate()
+            .map(|(n, rx)| In {
+                rx,
+                miso: n % 5 < 2,
+            });
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -261,7 +261,12 @@ pub fn kernel<T: Digital>(_cr: ClockReset, i: In<T>, q: Q<T>) -> (Out<T>, D<T>)
 
 #[cfg(test)]
 mod tests {
-    use crate::{rng::xorshift::XorShift128, stream::ready};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        rng::xorshift::XorShift128,
+        stream::{ready, testing::coverage::Coverage},
+    };
 
     use super::*;
 
@@ -307,4 +312,60 @@ mod tests {
         .for_each(drop);
         Ok(())
     }
+
+    #[test]
+    fn test_coverage() -> miette::Result<()> {
+        let uut = FIFOToStream::<b4>::default();
+        let mut coverage = Coverage::<(In<b4>, Out<b4>)>::default()
+            .channel("out", |(i, o)| (o.data.is_some(), i.ready.raw))
+            .bin("full", |(_, o)| o.full)
+            .bin("empty", |(_, o)| o.data.is_none())
+            .bin("stall_while_valid", |(i, o)| {
+                o.data.is_some() && !i.ready.raw
+            })
+            .bin("error", |(_, o)| o.error);
+        for seed in 0..8 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            // Each seed uses a different amount of backpressure
+            let pause = rng.random::<u8>();
+            let mut need_reset = true;
+            let mut source_rng = XorShift128::default().map(|x| bits((x & 0xF) as u128));
+            let mut dest_rng = source_rng.clone();
+            let samples = uut
+                .run_fn(
+                    |out| {
+                        if need_reset {
+                            need_reset = false;
+                            return Some(rhdl::core::sim::ResetOrData::Reset);
+                        }
+                        let mut input = super::In::<b4>::dont_care();
+                        input.ready = ready(rng.random::<u8>() >= pause);
+                        input.data = None;
+                        if !out.full && rng.random::<u8>() < 200 {
+                            input.data = source_rng.next();
+                        }
+                        if out.data.is_some() && input.ready.raw {
+                            assert_eq!(out.data, dest_rng.next());
+                        }
+                        Some(rhdl::core::sim::ResetOrData::Data(input))
+                    },
+                    100,
+                )
+                .take_while(|t| t.time < 20_000)
+                .synchronous_sample()
+                .map(|t| (t.value.1, t.value.2));
+            coverage.run(seed, samples);
+        }
+        coverage.check(&[
+            "out.idle",
+            "out.transfer",
+            "out.stall",
+            "full",
+            "empty",
+            "stall_while_valid",
+        ])?;
+        // The producer respects the full flag, so the buffer never overflows
+        assert_eq!(coverage.hits("error"), 0);
+        Ok(())
+    }
 }
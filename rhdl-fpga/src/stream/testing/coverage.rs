@@ -0,0 +1,369 @@
+//! Coverage of stream interface states
+//!
+//! Randomized tests of stream cores (with random backpressure from
+//! upstream and downstream) check that the data arrives intact.  They
+//! do not, by themselves, show that the interesting states of the core
+//! were actually reached.  A [Coverage] collects a set of named bins,
+//! each of which is a predicate over the signals observed in a single
+//! clock.  Each run of the test is fed through the [Coverage], which
+//! counts the clocks in which each bin was hit.  At the end of the test,
+//! [Coverage::check] fails if any of the required bins were never hit.
+//!
+//! Bins are either user defined (closures over the observed signals),
+//! or are the predefined handshake states of a channel (see
+//! [ChannelState] and [Coverage::channel]).
+//!
+//! A run fed through [Coverage::run_recorded] also keeps its stimulus.
+//! If the [Coverage] was given a corpus directory (with
+//! [Coverage::with_corpus]) and [Coverage::check] fails, the stimulus
+//! of each of the closest seeds is saved there as a corpus file (see
+//! [rhdl::core::sim::corpus]), named after the seed.  The stimulus is
+//! first shrunk to the shortest prefix that still hits all of the
+//! required bins that the full run hit.  The saved files can then be
+//! replayed by a `corpus_tests!` test, without the random generator.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl_fpga::stream::testing::coverage::Coverage;
+//!
+//! // The observation is a (valid, ready) pair
+//! let mut coverage = Coverage::<(bool, bool)>::default()
+//!     .channel("out", |&(valid, ready)| (valid, ready))
+//!     .bin("busy", |&(valid, _)| valid);
+//! coverage.run(1, [(true, true), (false, true)]);
+//! coverage.run(2, [(true, false)]);
+//! assert_eq!(coverage.hits("busy"), 2);
+//! assert!(coverage.check(&["out.transfer", "out.stall"]).is_ok());
+//! assert!(coverage.check(&["out.idle", "busy"]).is_ok());
+//!```
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use miette::Diagnostic;
+use rhdl::core::{
+    sim::corpus::{record_corpus, CorpusError},
+    Digital,
+};
+use serde::Serialize;
+use thiserror::Error;
+
+/// The handshake state of a channel in a single clock
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelState {
+    /// No data is offered
+    Idle,
+    /// Data is offered and accepted
+    Transfer,
+    /// Data is offered, but not accepted
+    Stall,
+}
+
+impl ChannelState {
+    /// Classify a clock from the `valid` and `ready` signals of the channel
+    pub fn classify(valid: bool, ready: bool) -> Self {
+        match (valid, ready) {
+            (false, _) => ChannelState::Idle,
+            (true, true) => ChannelState::Transfer,
+            (true, false) => ChannelState::Stall,
+        }
+    }
+    /// The name of the bin for this state
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChannelState::Idle => "idle",
+            ChannelState::Transfer => "transfer",
+            ChannelState::Stall => "stall",
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors reported by [Coverage::check]
+pub enum CoverageError {
+    /// Some of the required bins were never hit
+    #[error(
+        "Coverage bins {bins:?} were not hit in {runs} runs.  The closest seeds were {closest:?} (saved to {saved:?})"
+    )]
+    #[diagnostic(help("Run more seeds, or change the stimulus to reach these states"))]
+    Unhit {
+        /// The bins that were not hit
+        bins: Vec<String>,
+        /// The number of runs
+        runs: usize,
+        /// The seeds that hit the most required bins (best first)
+        closest: Vec<u64>,
+        /// The corpus files written for the closest seeds
+        saved: Vec<PathBuf>,
+    },
+    /// A required bin was not defined
+    #[error("No coverage bin named {0}")]
+    UnknownBin(String),
+    /// The stimulus of a run could not be saved
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Corpus(#[from] CorpusError),
+}
+
+type Predicate<S> = Rc<dyn Fn(&S) -> bool>;
+
+// Writes the first `len` clocks of the stimulus of a run to a file
+type Recorder = Box<dyn Fn(&Path, usize) -> Result<usize, CorpusError>>;
+
+struct Run {
+    seed: u64,
+    // The number of clocks that hit each bin
+    hits: Vec<u64>,
+    // The first clock that hit each bin
+    first: Vec<Option<usize>>,
+    recorder: Option<Recorder>,
+}
+
+/// A collection of coverage bins, and the hits in each run.
+///
+/// `S` is the type of the signals observed in each clock.
+pub struct Coverage<S> {
+    bins: Vec<(String, Predicate<S>)>,
+    runs: Vec<Run>,
+    corpus: Option<PathBuf>,
+}
+
+impl<S> Default for Coverage<S> {
+    fn default() -> Self {
+        Self {
+            bins: vec![],
+            runs: vec![],
+            corpus: None,
+        }
+    }
+}
+
+impl<S> Coverage<S> {
+    /// Add a user defined bin, which is hit in every clock
+    /// for which `predicate` returns `true`.
+    pub fn bin(mut self, name: &str, predicate: impl Fn(&S) -> bool + 'static) -> Self {
+        self.bins.push((name.into(), Rc::new(predicate)));
+        self
+    }
+    /// Add a bin for each [ChannelState] of a channel, named
+    /// `name.idle`, `name.transfer` and `name.stall`.  The
+    /// `handshake` function returns the `(valid, ready)` signals of
+    /// the channel.
+    pub fn channel(mut self, name: &str, handshake: impl Fn(&S) -> (bool, bool) + 'static) -> Self {
+        let handshake = Rc::new(handshake);
+        for state in [
+            ChannelState::Idle,
+            ChannelState::Transfer,
+            ChannelState::Stall,
+        ] {
+            let handshake = handshake.clone();
+            self = self.bin(&format!("{name}.{}", state.name()), move |s| {
+                let (valid, ready) = handshake(s);
+                ChannelState::classify(valid, ready) == state
+            });
+        }
+        self
+    }
+    /// Save the stimulus of the closest seeds to corpus files in `dir`
+    /// when [Coverage::check] fails.  Only runs fed through
+    /// [Coverage::run_recorded] have their stimulus saved.
+    pub fn with_corpus(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.corpus = Some(dir.into());
+        self
+    }
+    fn classify(&self, seed: u64, samples: &[S], recorder: Option<Recorder>) -> Run {
+        let mut hits = vec![0; self.bins.len()];
+        let mut first = vec![None; self.bins.len()];
+        for (clock, sample) in samples.iter().enumerate() {
+            for (bin, (_, predicate)) in self.bins.iter().enumerate() {
+                if predicate(sample) {
+                    hits[bin] += 1;
+                    first[bin].get_or_insert(clock);
+                }
+            }
+        }
+        Run {
+            seed,
+            hits,
+            first,
+            recorder,
+        }
+    }
+    /// Classify each clock of a run, and record the hits against the seed
+    pub fn run(&mut self, seed: u64, samples: impl IntoIterator<Item = S>) {
+        let samples = samples.into_iter().collect::<Vec<_>>();
+        let run = self.classify(seed, &samples, None);
+        self.runs.push(run);
+    }
+    /// Classify each clock of a run, and record the hits against the
+    /// seed.  The `stimulus` of each clock (as extracted from the
+    /// observed signals) is kept, so that it can be saved to the corpus
+    /// if the check fails.
+    pub fn run_recorded<I: Digital + Serialize + 'static>(
+        &mut self,
+        seed: u64,
+        samples: impl IntoIterator<Item = S>,
+        stimulus: impl Fn(&S) -> I,
+    ) {
+        let samples = samples.into_iter().collect::<Vec<_>>();
+        let inputs = samples.iter().map(stimulus).collect::<Vec<_>>();
+        let recorder: Recorder =
+            Box::new(move |path, len| record_corpus(path, inputs.iter().copied().take(len)));
+        let run = self.classify(seed, &samples, Some(recorder));
+        self.runs.push(run);
+    }
+    fn index(&self, name: &str) -> Result<usize, CoverageError> {
+        self.bins
+            .iter()
+            .position(|(bin, _)| bin == name)
+            .ok_or_else(|| CoverageError::UnknownBin(name.into()))
+    }
+    /// The total number of hits on a bin, across all runs.
+    ///
+    /// # Panics
+    /// If there is no bin with that name.
+    pub fn hits(&self, name: &str) -> u64 {
+        let index = self.index(name).unwrap();
+        self.runs.iter().map(|run| run.hits[index]).sum()
+    }
+    /// A table of the bins, and the total hits on each
+    pub fn report(&self) -> String {
+        self.bins
+            .iter()
+            .map(|(name, _)| format!("{name:>24} {}\n", self.hits(name)))
+            .collect()
+    }
+    /// Check that each of the required bins was hit in at least one run.
+    /// If not, the error lists the missing bins, and the seeds that hit
+    /// the most of the required bins.  With a corpus directory, the
+    /// (shrunk) stimulus of each of those seeds is saved there.
+    pub fn check(&self, required: &[&str]) -> Result<(), CoverageError> {
+        let required = required
+            .iter()
+            .map(|name| self.index(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let unhit = required
+            .iter()
+            .filter(|&&index| self.runs.iter().all(|run| run.hits[index] == 0))
+            .map(|&index| self.bins[index].0.clone())
+            .collect::<Vec<_>>();
+        if unhit.is_empty() {
+            return Ok(());
+        }
+        let mut ranked = self
+            .runs
+            .iter()
+            .map(|run| {
+                let count = required
+                    .iter()
+                    .filter(|&&index| run.hits[index] > 0)
+                    .count();
+                (count, run)
+            })
+            .collect::<Vec<_>>();
+        // Stable, so that ties stay in the order they were run
+        ranked.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+        let closest = ranked.into_iter().take(3).map(|(_, run)| run);
+        let mut saved = vec![];
+        if let Some(dir) = &self.corpus {
+            for run in closest.clone() {
+                if let Some(path) = save(dir, run, &required)? {
+                    saved.push(path);
+                }
+            }
+        }
+        Err(CoverageError::Unhit {
+            bins: unhit,
+            runs: self.runs.len(),
+            closest: closest.map(|run| run.seed).collect(),
+            saved,
+        })
+    }
+}
+
+// Save the stimulus of a run, shrunk to the shortest prefix that
+// hits all of the required bins that the whole run hit.  Runs without
+// a recorded stimulus, or that hit none of the bins, are skipped.
+fn save(dir: &Path, run: &Run, required: &[usize]) -> Result<Option<PathBuf>, CorpusError> {
+    let Some(recorder) = &run.recorder else {
+        return Ok(None);
+    };
+    let Some(last) = required.iter().filter_map(|&index| run.first[index]).max() else {
+        return Ok(None);
+    };
+    std::fs::create_dir_all(dir).map_err(|err| CorpusError::Io {
+        path: dir.display().to_string(),
+        message: err.to_string(),
+    })?;
+    let path = dir.join(format!("seed_{}.json.gz", run.seed));
+    recorder(&path, last + 1)?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unhit_bins_report_closest_seeds() {
+        let mut coverage = Coverage::<(bool, bool)>::default()
+            .channel("ch", |&s| s)
+            .bin("never", |_| false);
+        coverage.run(10, [(false, false)]);
+        coverage.run(11, [(true, true), (true, false)]);
+        coverage.run(12, [(true, true)]);
+        assert_eq!(coverage.hits("ch.transfer"), 2);
+        assert_eq!(
+            coverage.check(&["ch.idle", "ch.transfer", "ch.stall", "never"]),
+            Err(CoverageError::Unhit {
+                bins: vec!["never".into()],
+                runs: 3,
+                closest: vec![11, 10, 12],
+                saved: vec![],
+            })
+        );
+        assert_eq!(
+            coverage.check(&["missing"]),
+            Err(CoverageError::UnknownBin("missing".into()))
+        );
+    }
+
+    #[test]
+    fn test_closest_seeds_saved_to_corpus() {
+        use rhdl::core::sim::corpus::replay_corpus;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut coverage = Coverage::<(bool, bool)>::default()
+            .channel("ch", |&s| s)
+            .bin("never", |_| false)
+            .with_corpus(dir.path());
+        // A stall on the third clock, then nothing new
+        let samples = [
+            (false, false),
+            (true, true),
+            (true, false),
+            (true, true),
+            (false, true),
+        ];
+        coverage.run_recorded(7, samples, |&(valid, _)| valid);
+        // Unrecorded runs are not saved
+        coverage.run(8, [(true, true)]);
+        let err = coverage
+            .check(&["ch.idle", "ch.transfer", "ch.stall", "never"])
+            .unwrap_err();
+        let path = dir.path().join("seed_7.json.gz");
+        assert_eq!(
+            err,
+            CoverageError::Unhit {
+                bins: vec!["never".into()],
+                runs: 2,
+                closest: vec![7, 8],
+                saved: vec![path.clone()],
+            }
+        );
+        // Shrunk to the clocks needed to hit the same bins
+        assert_eq!(replay_corpus::<bool>(&path).unwrap(), [false, true, true]);
+    }
+}
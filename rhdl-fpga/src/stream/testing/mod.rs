@@ -1,4 +1,5 @@
 //! Cores useful for testing streams.
+pub mod coverage;
 #[doc(hidden)]
 pub mod double;
 pub mod lazy_random;
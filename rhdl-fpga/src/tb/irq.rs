@@ -0,0 +1,103 @@
+//! An interrupt line monitor
+//!
+//! The [IrqMonitor] watches a level sensitive interrupt output of the
+//! circuit.  It drives no inputs.  Its steps wait for the line to
+//! change, or check that it stays put for a number of clocks.
+use std::marker::PhantomData;
+
+use super::sequencer::{Driver, Poll, Step, Transaction};
+
+/// A monitor for the interrupt line read from the outputs by `line`
+pub struct IrqMonitor<I, O> {
+    name: &'static str,
+    line: fn(&O) -> bool,
+    _i: PhantomData<fn(&mut I)>,
+}
+
+impl<I, O> Clone for IrqMonitor<I, O> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I, O> Copy for IrqMonitor<I, O> {}
+
+impl<I, O> Driver<I> for IrqMonitor<I, O> {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn idle(&self, _input: &mut I) {}
+}
+
+impl<I: 'static, O: 'static> IrqMonitor<I, O> {
+    /// Create a monitor for the interrupt line called `name`
+    pub fn new(name: &'static str, line: fn(&O) -> bool) -> Self {
+        Self {
+            name,
+            line,
+            _i: PhantomData,
+        }
+    }
+    fn watch(&self, level: bool, hold: Option<u64>) -> Step<I, O> {
+        Box::new(Watch {
+            monitor: *self,
+            level,
+            hold,
+            seen: 0,
+        })
+    }
+    /// Wait for the line to be asserted
+    pub fn wait_high(&self) -> Step<I, O> {
+        self.watch(true, None)
+    }
+    /// Wait for the line to be deasserted
+    pub fn wait_low(&self) -> Step<I, O> {
+        self.watch(false, None)
+    }
+    /// Check that the line stays deasserted for `cycles` clocks
+    pub fn expect_low_for(&self, cycles: u64) -> Step<I, O> {
+        self.watch(false, Some(cycles))
+    }
+    /// Check that the line stays asserted for `cycles` clocks
+    pub fn expect_high_for(&self, cycles: u64) -> Step<I, O> {
+        self.watch(true, Some(cycles))
+    }
+}
+
+struct Watch<I, O> {
+    monitor: IrqMonitor<I, O>,
+    level: bool,
+    // If set, the line must hold the level for this many clocks.
+    // Otherwise, wait for the line to reach the level.
+    hold: Option<u64>,
+    seen: u64,
+}
+
+impl<I, O> Transaction<I, O> for Watch<I, O> {
+    fn describe(&self) -> String {
+        let level = if self.level { "high" } else { "low" };
+        match self.hold {
+            Some(cycles) => format!("{}: stays {level} for {cycles} clocks", self.monitor.name),
+            None => format!("{}: wait for {level}", self.monitor.name),
+        }
+    }
+    fn drive(&mut self, _input: &mut I) {}
+    fn check(&mut self, output: &O) -> Poll {
+        let line = (self.monitor.line)(output);
+        match self.hold {
+            None if line == self.level => Poll::Done,
+            None => Poll::Pending,
+            Some(_) if line != self.level => {
+                Poll::Failed(format!("the line changed after {} clocks", self.seen))
+            }
+            Some(cycles) => {
+                self.seen += 1;
+                if self.seen >= cycles {
+                    Poll::Done
+                } else {
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
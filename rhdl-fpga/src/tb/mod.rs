@@ -0,0 +1,23 @@
+//! Transactional testbenches
+//!
+//! Testing an assembly of cores with raw streams of timed samples gets
+//! unmanageable once stimulus on several interfaces has to be coordinated
+//! with the responses on others.  The [TbSequencer](sequencer::TbSequencer)
+//! instead runs a script of transactions ("write this register", "wait
+//! for the interrupt", "expect these bytes on the stream") against named
+//! interface drivers, scheduling them against a cycle by cycle
+//! simulation, and reports which step failed, and in which cycle.
+//!
+//! The drivers provided are
+//!
+//! - a Wishbone bus functional model ([WishboneMaster](wishbone::WishboneMaster)),
+//! - a stream source and sink ([StreamSource](stream::StreamSource) and
+//!   [StreamSink](stream::StreamSink)),
+//! - an interrupt line monitor ([IrqMonitor](irq::IrqMonitor)).
+//!
+//! Other interfaces can be driven with the generic steps in
+//! [sequencer], or by implementing [Transaction](sequencer::Transaction).
+pub mod irq;
+pub mod sequencer;
+pub mod stream;
+pub mod wishbone;
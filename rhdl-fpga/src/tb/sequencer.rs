@@ -0,0 +1,325 @@
+//! The test sequence engine
+//!
+//! A [TbSequencer] runs a script of [Step]s against a synchronous
+//! circuit, one step after another.  Each step is a [Transaction], which
+//! drives some of the inputs of the circuit in each clock, and checks the
+//! outputs in that clock, until it reports that it is done (or has
+//! failed).  Inputs that are not driven by the active step are driven to
+//! their idle values by the [Driver]s registered with the sequencer, so a
+//! step only needs to deal with the interface it acts on.
+//!
+//! Steps run back to back: the clock after a step finishes is the first
+//! clock of the next step.  To run steps at the same time (for example, to
+//! feed a stream while waiting for an interrupt), combine them with [all].
+//! A step that does not finish within the timeout of the sequencer fails.
+//!
+//! When a step fails, [TbSequencer::run] returns a [SequenceError] that
+//! names the step (by its index in the script and its description) and
+//! the clock cycle it failed in.  The underlying [SimSession] is available
+//! with [TbSequencer::session] for peeking at internal signals.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::core::constant::Constant;
+//! use rhdl_fpga::tb::sequencer::{wait_for, TbSequencer};
+//!
+//! let uut = Constant::new(b8(42));
+//! let mut seq = TbSequencer::new(&uut);
+//! seq.run([wait_for("the constant", |o: &b8| *o == b8(42))]).unwrap();
+//! let err = seq
+//!     .with_timeout(10)
+//!     .run([wait_for("a different constant", |o: &b8| *o == b8(7))])
+//!     .unwrap_err();
+//! assert!(err.to_string().contains("Step 0 (a different constant)"));
+//!```
+use miette::Diagnostic;
+use rhdl::prelude::*;
+use thiserror::Error;
+
+/// The result of checking the outputs of a clock cycle
+#[derive(Clone, Debug, PartialEq)]
+pub enum Poll {
+    /// The transaction needs more clock cycles
+    Pending,
+    /// The transaction has completed
+    Done,
+    /// The transaction has failed, for the given reason
+    Failed(String),
+}
+
+/// A transaction that drives and checks the circuit over
+/// one or more clock cycles.
+///
+/// In each clock cycle, the sequencer calls [drive](Transaction::drive)
+/// with the inputs for that cycle (already set to the idle values), and
+/// then [check](Transaction::check) with the outputs the circuit
+/// produced in that cycle (before the clock edge).
+pub trait Transaction<I, O> {
+    /// A description of the transaction, used in error reports
+    fn describe(&self) -> String;
+    /// Drive the inputs for this clock cycle
+    fn drive(&mut self, input: &mut I);
+    /// Check the outputs for this clock cycle
+    fn check(&mut self, output: &O) -> Poll;
+}
+
+/// A step in a test sequence
+pub type Step<I, O> = Box<dyn Transaction<I, O>>;
+
+/// A driver for one interface of the circuit.  When no step is
+/// using the interface, the driver holds it in its idle state.
+pub trait Driver<I> {
+    /// The name of the interface
+    fn name(&self) -> &str;
+    /// Drive the idle values of the interface
+    fn idle(&self, input: &mut I);
+}
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors reported by [TbSequencer::run]
+pub enum SequenceError {
+    /// A step detected a mismatch
+    #[error("Step {step} ({description}) failed at cycle {cycle}: {reason}")]
+    Failed {
+        /// The index of the step in the script
+        step: usize,
+        /// The description of the step
+        description: String,
+        /// The clock cycle in which the step failed
+        cycle: u64,
+        /// Why the step failed
+        reason: String,
+    },
+    /// A step did not finish in time
+    #[error(
+        "Step {step} ({description}) did not finish within {max_cycles} cycles (at cycle {cycle})"
+    )]
+    #[diagnostic(help("Check the circuit, or allow more cycles with `with_timeout`"))]
+    Timeout {
+        /// The index of the step in the script
+        step: usize,
+        /// The description of the step
+        description: String,
+        /// The clock cycle in which the step was abandoned
+        cycle: u64,
+        /// The timeout of the sequencer
+        max_cycles: u64,
+    },
+}
+
+/// Runs scripts of [Step]s against a synchronous circuit
+pub struct TbSequencer<'a, T: Synchronous> {
+    session: SimSession<'a, T>,
+    drivers: Vec<Box<dyn Driver<T::I> + 'a>>,
+    idle: T::I,
+    timeout: u64,
+}
+
+impl<'a, T: Synchronous> TbSequencer<'a, T> {
+    /// Create a sequencer for `uut`.  The circuit is reset for one
+    /// clock before the first step is run.
+    pub fn new(uut: &'a T) -> Self {
+        Self {
+            session: SimSession::new(uut),
+            drivers: vec![],
+            idle: T::I::dont_care(),
+            timeout: 1000,
+        }
+    }
+    /// Register a driver for an interface
+    pub fn with_driver(mut self, driver: impl Driver<T::I> + 'a) -> Self {
+        assert!(
+            self.drivers.iter().all(|d| d.name() != driver.name()),
+            "A driver named {} is already registered",
+            driver.name()
+        );
+        self.drivers.push(Box::new(driver));
+        self
+    }
+    /// The value of the inputs not covered by any driver (the
+    /// default is `dont_care`)
+    pub fn with_idle(self, idle: T::I) -> Self {
+        Self { idle, ..self }
+    }
+    /// The maximum number of clock cycles a step may take (the
+    /// default is 1000)
+    pub fn with_timeout(self, timeout: u64) -> Self {
+        Self { timeout, ..self }
+    }
+    /// Record the samples of the underlying session
+    pub fn recorded(self) -> Self {
+        Self {
+            session: self.session.recorded(),
+            ..self
+        }
+    }
+    /// The underlying simulation session
+    pub fn session(&self) -> &SimSession<'a, T> {
+        &self.session
+    }
+    /// The names of the registered drivers
+    pub fn driver_names(&self) -> Vec<&str> {
+        self.drivers.iter().map(|d| d.name()).collect()
+    }
+    /// Run the steps of `script` in order.  The sequencer can be
+    /// run more than once, and the circuit is not reset between runs.
+    pub fn run(
+        &mut self,
+        script: impl IntoIterator<Item = Step<T::I, T::O>>,
+    ) -> Result<(), SequenceError> {
+        if self.session.cycle() == 0 {
+            self.session.reset();
+        }
+        for (step, mut transaction) in script.into_iter().enumerate() {
+            let mut cycles = 0;
+            loop {
+                if cycles == self.timeout {
+                    return Err(SequenceError::Timeout {
+                        step,
+                        description: transaction.describe(),
+                        cycle: self.session.cycle(),
+                        max_cycles: self.timeout,
+                    });
+                }
+                let mut input = self.idle;
+                for driver in &self.drivers {
+                    driver.idle(&mut input);
+                }
+                transaction.drive(&mut input);
+                let output = self.session.step(input);
+                cycles += 1;
+                match transaction.check(&output) {
+                    Poll::Pending => {}
+                    Poll::Done => break,
+                    Poll::Failed(reason) => {
+                        return Err(SequenceError::Failed {
+                            step,
+                            description: transaction.describe(),
+                            cycle: self.session.cycle() - 1,
+                            reason,
+                        })
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct All<I, O> {
+    steps: Vec<(Step<I, O>, bool)>,
+}
+
+impl<I, O> Transaction<I, O> for All<I, O> {
+    fn describe(&self) -> String {
+        let steps = self
+            .steps
+            .iter()
+            .map(|(step, _)| step.describe())
+            .collect::<Vec<_>>();
+        format!("all of [{}]", steps.join(", "))
+    }
+    fn drive(&mut self, input: &mut I) {
+        for (step, done) in &mut self.steps {
+            if !*done {
+                step.drive(input);
+            }
+        }
+    }
+    fn check(&mut self, output: &O) -> Poll {
+        for (step, done) in &mut self.steps {
+            if *done {
+                continue;
+            }
+            match step.check(output) {
+                Poll::Pending => {}
+                Poll::Done => *done = true,
+                Poll::Failed(reason) => {
+                    return Poll::Failed(format!("{}: {reason}", step.describe()))
+                }
+            }
+        }
+        if self.steps.iter().all(|(_, done)| *done) {
+            Poll::Done
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Run `steps` at the same time.  The step is done when all of them
+/// are, and fails as soon as any of them does.  The steps should act
+/// on different interfaces.
+pub fn all<I: 'static, O: 'static>(steps: impl IntoIterator<Item = Step<I, O>>) -> Step<I, O> {
+    Box::new(All {
+        steps: steps.into_iter().map(|step| (step, false)).collect(),
+    })
+}
+
+struct Custom<F, P> {
+    description: String,
+    drive: F,
+    predicate: P,
+}
+
+impl<I, O, F: FnMut(&mut I), P: FnMut(&O) -> Poll> Transaction<I, O> for Custom<F, P> {
+    fn describe(&self) -> String {
+        self.description.clone()
+    }
+    fn drive(&mut self, input: &mut I) {
+        (self.drive)(input)
+    }
+    fn check(&mut self, output: &O) -> Poll {
+        (self.predicate)(output)
+    }
+}
+
+/// A step that drives the inputs with `drive` for a single clock cycle
+pub fn pulse<I: 'static, O: 'static>(
+    description: &str,
+    drive: impl FnMut(&mut I) + 'static,
+) -> Step<I, O> {
+    Box::new(Custom {
+        description: description.into(),
+        drive,
+        predicate: |_: &O| Poll::Done,
+    })
+}
+
+/// A step that waits (with the idle inputs) until `predicate` is
+/// true of the outputs
+pub fn wait_for<I: 'static, O: 'static>(
+    description: &str,
+    mut predicate: impl FnMut(&O) -> bool + 'static,
+) -> Step<I, O> {
+    Box::new(Custom {
+        description: description.into(),
+        drive: |_: &mut I| {},
+        predicate: move |o: &O| {
+            if predicate(o) {
+                Poll::Done
+            } else {
+                Poll::Pending
+            }
+        },
+    })
+}
+
+/// A step that waits (with the idle inputs) for `cycles` clock cycles
+pub fn delay<I: 'static, O: 'static>(cycles: u64) -> Step<I, O> {
+    let mut count = 0;
+    Box::new(Custom {
+        description: format!("delay {cycles} cycles"),
+        drive: |_: &mut I| {},
+        predicate: move |_: &O| {
+            count += 1;
+            if count >= cycles {
+                Poll::Done
+            } else {
+                Poll::Pending
+            }
+        },
+    })
+}
@@ -0,0 +1,213 @@
+//! Stream drivers and monitors
+//!
+//! A [StreamSource] feeds items into an `Option<T>` plus `ready`
+//! stream input of the circuit, and a [StreamSink] accepts items from a
+//! stream output, checking them against the expected values.  An item
+//! is transferred in a clock in which it is offered, and `ready` is
+//! asserted.  When idle, the source offers nothing, and the sink holds
+//! `ready` low (so that the circuit backs up).
+use std::{collections::VecDeque, fmt::Debug, marker::PhantomData};
+
+use rhdl::prelude::*;
+
+use super::sequencer::{Driver, Poll, Step, Transaction};
+
+/// A driver that sends items into the circuit.  The `data` function
+/// selects the `Option<T>` input, and `ready` reads the ready output
+/// that goes with it.
+pub struct StreamSource<I, O, T> {
+    name: &'static str,
+    data: fn(&mut I) -> &mut Option<T>,
+    ready: fn(&O) -> bool,
+}
+
+impl<I, O, T> Clone for StreamSource<I, O, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I, O, T> Copy for StreamSource<I, O, T> {}
+
+impl<I, O, T> Driver<I> for StreamSource<I, O, T> {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn idle(&self, input: &mut I) {
+        *(self.data)(input) = None;
+    }
+}
+
+impl<I: 'static, O: 'static, T: Digital + Debug> StreamSource<I, O, T> {
+    /// Create a source for the stream called `name`
+    pub fn new(
+        name: &'static str,
+        data: fn(&mut I) -> &mut Option<T>,
+        ready: fn(&O) -> bool,
+    ) -> Self {
+        Self { name, data, ready }
+    }
+    /// Send `items` (in order), waiting for the circuit to accept each one
+    pub fn send(&self, items: impl IntoIterator<Item = T>) -> Step<I, O> {
+        Box::new(Send {
+            source: *self,
+            items: items.into_iter().collect(),
+            sent: 0,
+        })
+    }
+}
+
+struct Send<I, O, T> {
+    source: StreamSource<I, O, T>,
+    items: VecDeque<T>,
+    sent: usize,
+}
+
+impl<I, O, T: Digital + Debug> Transaction<I, O> for Send<I, O, T> {
+    fn describe(&self) -> String {
+        format!(
+            "{}: send {} items",
+            self.source.name,
+            self.items.len() + self.sent
+        )
+    }
+    fn drive(&mut self, input: &mut I) {
+        *(self.source.data)(input) = self.items.front().copied();
+    }
+    fn check(&mut self, output: &O) -> Poll {
+        if !self.items.is_empty() && (self.source.ready)(output) {
+            self.items.pop_front();
+            self.sent += 1;
+        }
+        if self.items.is_empty() {
+            Poll::Done
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// A monitor that accepts items from the circuit.  The `data` function
+/// reads the `Option<T>` output, and `ready` selects the ready input
+/// that goes with it.
+pub struct StreamSink<I, O, T> {
+    name: &'static str,
+    data: fn(&O) -> Option<T>,
+    ready: fn(&mut I) -> &mut bool,
+}
+
+impl<I, O, T> Clone for StreamSink<I, O, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I, O, T> Copy for StreamSink<I, O, T> {}
+
+impl<I, O, T> Driver<I> for StreamSink<I, O, T> {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn idle(&self, input: &mut I) {
+        *(self.ready)(input) = false;
+    }
+}
+
+impl<I: 'static, O: 'static, T: Digital + Debug> StreamSink<I, O, T> {
+    /// Create a sink for the stream called `name`
+    pub fn new(
+        name: &'static str,
+        data: fn(&O) -> Option<T>,
+        ready: fn(&mut I) -> &mut bool,
+    ) -> Self {
+        Self { name, data, ready }
+    }
+    /// Accept items until `expected` have been received, failing
+    /// if any of them differ
+    pub fn expect(&self, expected: impl IntoIterator<Item = T>) -> Step<I, O> {
+        Box::new(Expect {
+            sink: *self,
+            expected: expected.into_iter().collect(),
+            received: 0,
+        })
+    }
+    /// Accept items (and discard them) until the circuit has
+    /// offered nothing for `quiet` clocks in a row
+    pub fn drain(&self, quiet: u64) -> Step<I, O> {
+        Box::new(Drain {
+            sink: *self,
+            quiet,
+            idle: 0,
+            _t: PhantomData,
+        })
+    }
+}
+
+struct Expect<I, O, T> {
+    sink: StreamSink<I, O, T>,
+    expected: VecDeque<T>,
+    received: usize,
+}
+
+impl<I, O, T: Digital + Debug> Transaction<I, O> for Expect<I, O, T> {
+    fn describe(&self) -> String {
+        format!(
+            "{}: expect {} items",
+            self.sink.name,
+            self.expected.len() + self.received
+        )
+    }
+    fn drive(&mut self, input: &mut I) {
+        *(self.sink.ready)(input) = true;
+    }
+    fn check(&mut self, output: &O) -> Poll {
+        if let Some(item) = (self.sink.data)(output) {
+            match self.expected.pop_front() {
+                Some(expected) if expected == item => self.received += 1,
+                Some(expected) => {
+                    return Poll::Failed(format!(
+                        "item {} was {item:?}, expected {expected:?}",
+                        self.received
+                    ))
+                }
+                None => unreachable!("The transaction is done once all items are received"),
+            }
+        }
+        if self.expected.is_empty() {
+            Poll::Done
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+struct Drain<I, O, T> {
+    sink: StreamSink<I, O, T>,
+    quiet: u64,
+    idle: u64,
+    _t: PhantomData<T>,
+}
+
+impl<I, O, T> Transaction<I, O> for Drain<I, O, T> {
+    fn describe(&self) -> String {
+        format!(
+            "{}: drain until quiet for {} clocks",
+            self.sink.name, self.quiet
+        )
+    }
+    fn drive(&mut self, input: &mut I) {
+        *(self.sink.ready)(input) = true;
+    }
+    fn check(&mut self, output: &O) -> Poll {
+        if (self.sink.data)(output).is_some() {
+            self.idle = 0;
+        } else {
+            self.idle += 1;
+        }
+        if self.idle >= self.quiet {
+            Poll::Done
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! A Wishbone bus functional model
+//!
+//! The [WishboneMaster] drives a Wishbone slave interface of the
+//! circuit with classic (single beat) bus cycles.  Each cycle holds
+//! the beat on the bus until the slave acknowledges it, and is
+//! followed by one idle clock.  When no cycle is in progress, the bus
+//! is idle (all signals zero).
+use std::marker::PhantomData;
+
+use rhdl::prelude::*;
+
+use crate::wishbone::types::{burst_types, cycle_types, FromSlave, ToSlave};
+
+use super::sequencer::{Driver, Poll, Step, Transaction};
+
+/// A Wishbone master driving the `ToSlave` signals selected by
+/// `bus` from the inputs `I`, and watching the `FromSlave` signals
+/// selected by `reply` from the outputs `O`.
+pub struct WishboneMaster<I, O, A: BitWidth> {
+    name: &'static str,
+    bus: fn(&mut I) -> &mut ToSlave<A>,
+    reply: fn(&O) -> FromSlave,
+}
+
+impl<I, O, A: BitWidth> Clone for WishboneMaster<I, O, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I, O, A: BitWidth> Copy for WishboneMaster<I, O, A> {}
+
+impl<I, O, A: BitWidth> Driver<I> for WishboneMaster<I, O, A> {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn idle(&self, input: &mut I) {
+        *(self.bus)(input) = ToSlave::default();
+    }
+}
+
+impl<I: 'static, O: 'static, A: BitWidth> WishboneMaster<I, O, A> {
+    /// Create a master for the interface called `name`
+    pub fn new(
+        name: &'static str,
+        bus: fn(&mut I) -> &mut ToSlave<A>,
+        reply: fn(&O) -> FromSlave,
+    ) -> Self {
+        Self { name, bus, reply }
+    }
+    fn cycle(&self, adr: u128, write: Option<(u32, u8)>, check: Check) -> Step<I, O> {
+        let (we, dat, sel) = match write {
+            Some((dat, sel)) => (true, dat, sel),
+            None => (false, 0, 0xF),
+        };
+        Box::new(BusCycle {
+            master: *self,
+            beat: ToSlave {
+                cyc: true,
+                stb: true,
+                we,
+                adr: bits(adr),
+                dat: bits(dat as u128),
+                sel: bits(sel as u128),
+                cti: cycle_types::CLASSIC,
+                bte: burst_types::LINEAR,
+            },
+            check,
+            phase: Phase::Beat,
+            _o: PhantomData,
+        })
+    }
+    /// Write `dat` to the word at `adr`
+    pub fn write(&self, adr: u128, dat: u32) -> Step<I, O> {
+        self.cycle(adr, Some((dat, 0xF)), Check::None)
+    }
+    /// Write the byte lanes of `dat` selected by `sel` to the word at `adr`
+    pub fn write_sel(&self, adr: u128, dat: u32, sel: u8) -> Step<I, O> {
+        self.cycle(adr, Some((dat, sel)), Check::None)
+    }
+    /// Read the word at `adr`, and fail if it is not `expected`
+    pub fn read_expect(&self, adr: u128, expected: u32) -> Step<I, O> {
+        self.cycle(adr, None, Check::Equal(expected))
+    }
+    /// Read the word at `adr` repeatedly, until the bits selected
+    /// by `mask` are equal to `value`
+    pub fn poll(&self, adr: u128, mask: u32, value: u32) -> Step<I, O> {
+        self.cycle(adr, None, Check::Until { mask, value })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Check {
+    None,
+    Equal(u32),
+    Until { mask: u32, value: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    // The beat is on the bus, waiting for the ack
+    Beat,
+    // The idle clock after the ack, and whether to repeat the beat
+    Gap { again: bool },
+}
+
+struct BusCycle<I, O, A: BitWidth> {
+    master: WishboneMaster<I, O, A>,
+    beat: ToSlave<A>,
+    check: Check,
+    phase: Phase,
+    _o: PhantomData<O>,
+}
+
+impl<I, O, A: BitWidth> Transaction<I, O> for BusCycle<I, O, A> {
+    fn describe(&self) -> String {
+        let name = self.master.name;
+        let adr = self.beat.adr.raw();
+        match self.check {
+            Check::None => format!(
+                "{name}: write {adr:#x} <- {:#010x} (sel {:#06b})",
+                self.beat.dat.raw(),
+                self.beat.sel.raw()
+            ),
+            Check::Equal(dat) => format!("{name}: read {adr:#x} expecting {dat:#010x}"),
+            Check::Until { mask, value } => {
+                format!("{name}: poll {adr:#x} until (data & {mask:#010x}) == {value:#010x}")
+            }
+        }
+    }
+    fn drive(&mut self, input: &mut I) {
+        if self.phase == Phase::Beat {
+            *(self.master.bus)(input) = self.beat;
+        }
+    }
+    fn check(&mut self, output: &O) -> Poll {
+        match self.phase {
+            Phase::Beat => {
+                let reply = (self.master.reply)(output);
+                if !reply.ack {
+                    return Poll::Pending;
+                }
+                let dat = reply.dat.raw() as u32;
+                let again = match self.check {
+                    Check::None => false,
+                    Check::Equal(expected) if dat != expected => {
+                        return Poll::Failed(format!("read {dat:#010x}, expected {expected:#010x}"))
+                    }
+                    Check::Equal(_) => false,
+                    Check::Until { mask, value } => dat & mask != value,
+                };
+                self.phase = Phase::Gap { again };
+                Poll::Pending
+            }
+            Phase::Gap { again: true } => {
+                self.phase = Phase::Beat;
+                Poll::Pending
+            }
+            Phase::Gap { again: false } => Poll::Done,
+        }
+    }
+}
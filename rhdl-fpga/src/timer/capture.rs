@@ -0,0 +1,278 @@
+//! Capture-Compare Timer
+//!
+//! The [CaptureCompare] core measures a PWM signal, such as
+//! the output of an RC receiver, or the tachometer from a fan.
+//! It timestamps the rising and falling edges of the input
+//! against a free running `N` bit counter, and from those
+//! computes the period of the signal and the time it spends
+//! high (and hence the duty cycle).  The differences are taken
+//! modulo `2^N`, so that the counter wrapping around between
+//! edges is handled correctly, provided the period is less than
+//! `2^N` clock cycles.
+//!
+//! The results are double buffered, so that the `period` and
+//! `high` outputs always belong to the same cycle of the input.
+//! They are updated together on each rising edge, at which point
+//! the `update` flag is strobed for one clock.  The `valid` flag
+//! indicates that at least one complete cycle has been measured.
+//!
+//! If the input does not change for `timeout` clocks, the
+//! `stuck` output reports the level it is stuck at, and the
+//! measurement is invalidated.  A new measurement is made once
+//! the input starts toggling again.
+//!
+//! The input is assumed to be synchronous to the clock (for
+//! example, using a [Sync1Bit](crate::cdc::synchronizer::Sync1Bit)
+//! core).
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +-+CaptureCompare+--+
+ bool |                   | bN
++---->| input      period +---->
+      |                   | bN
+      |              high +---->
+      |                   | bool
+      |            update +---->
+      |                   | bool
+      |             valid +---->
+      |                   | ?bool
+      |             stuck +---->
+      +-------------------+
+")]
+//!
+//!# Example
+//!
+//! Measuring a signal that is high for 3 clocks out of 10.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::timer::capture::CaptureCompare;
+//!
+//! let uut = CaptureCompare::<U8>::new(bits(100));
+//! let input = (0..100).map(|n| n % 10 < 3);
+//! let input = input.with_reset(1).clock_pos_edge(100);
+//! let last = uut.run(input).unwrap().synchronous_sample().last().unwrap();
+//! assert!(last.value.2.valid);
+//! assert_eq!(last.value.2.period, b8(10));
+//! assert_eq!(last.value.2.high, b8(3));
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The capture-compare timer.  Here `N` is the
+/// width of the free running counter.
+pub struct CaptureCompare<N: BitWidth> {
+    timeout: constant::Constant<Bits<N>>,
+    count: dff::DFF<Bits<N>>,
+    prev: dff::DFF<bool>,
+    rise: dff::DFF<Option<Bits<N>>>,
+    high: dff::DFF<Option<Bits<N>>>,
+    since: dff::DFF<Bits<N>>,
+    result: dff::DFF<Out<N>>,
+}
+
+impl<N: BitWidth> CaptureCompare<N> {
+    /// Create a new [CaptureCompare] core, that reports
+    /// a stuck input after `timeout` clocks without an edge.
+    pub fn new(timeout: Bits<N>) -> Self {
+        Self {
+            timeout: constant::Constant::new(timeout),
+            count: dff::DFF::default(),
+            prev: dff::DFF::default(),
+            rise: dff::DFF::new(None),
+            high: dff::DFF::new(None),
+            since: dff::DFF::default(),
+            result: dff::DFF::default(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [CaptureCompare] core
+pub struct Out<N: BitWidth> {
+    /// The period of the input in clocks
+    pub period: Bits<N>,
+    /// The number of clocks the input was high
+    pub high: Bits<N>,
+    /// Strobed when a new measurement is available
+    pub update: bool,
+    /// Set when the measurement is valid
+    pub valid: bool,
+    /// The level of the input if it is stuck
+    pub stuck: Option<bool>,
+}
+
+impl<N: BitWidth> SynchronousIO for CaptureCompare<N> {
+    type I = bool;
+    type O = Out<N>;
+    type Kernel = capture_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn capture_kernel<N: BitWidth>(cr: ClockReset, i: bool, q: Q<N>) -> (Out<N>, D<N>) {
+    let mut d = D::<N>::dont_care();
+    let now = q.count;
+    d.count = q.count + 1;
+    d.prev = i;
+    d.rise = q.rise;
+    d.high = q.high;
+    d.result = q.result;
+    d.result.update = false;
+    let rising = i && !q.prev;
+    let falling = !i && q.prev;
+    // Count the time since the last edge, saturating
+    // at the timeout
+    d.since = if q.since >= q.timeout {
+        q.since
+    } else {
+        q.since + 1
+    };
+    if rising || falling {
+        d.since = bits(0);
+    }
+    if falling {
+        if let Some(rise) = q.rise {
+            d.high = Some(now - rise);
+        }
+    }
+    if rising {
+        if let Some(rise) = q.rise {
+            if let Some(high) = q.high {
+                d.result = Out::<N> {
+                    period: now - rise,
+                    high,
+                    update: true,
+                    valid: true,
+                    stuck: None,
+                };
+            }
+        }
+        d.rise = Some(now);
+        d.high = None;
+    }
+    // A stuck input invalidates the measurement, and any
+    // partial one in progress
+    if q.since >= q.timeout {
+        d.rise = None;
+        d.high = None;
+        d.result.valid = false;
+        d.result.stuck = Some(q.prev);
+    }
+    if cr.reset.any() {
+        d.rise = None;
+        d.high = None;
+        d.since = bits(0);
+        d.result = Out::<N>::default();
+    }
+    (q.result, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pwm(period: usize, high: usize, cycles: usize) -> impl Iterator<Item = bool> + Clone {
+        (0..period * cycles).map(move |n| n % period < high)
+    }
+
+    fn run(
+        uut: &CaptureCompare<U8>,
+        input: impl Iterator<Item = bool> + Clone,
+    ) -> miette::Result<Vec<Out<U8>>> {
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_duty_cycles_and_frequencies() -> miette::Result<()> {
+        let uut = CaptureCompare::<U8>::new(bits(255));
+        for (period, high) in [(10, 1), (10, 5), (10, 9), (37, 12), (100, 75), (200, 20)] {
+            // Enough cycles to wrap the 8 bit counter several times
+            let output = run(&uut, pwm(period, high, 20))?;
+            let updates = output.iter().filter(|o| o.update).collect::<Vec<_>>();
+            assert!(updates.len() >= 18);
+            for o in updates {
+                assert_eq!(o.period.raw() as usize, period);
+                assert_eq!(o.high.raw() as usize, high);
+                assert!(o.valid);
+                assert!(o.stuck.is_none());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_results_are_double_buffered() -> miette::Result<()> {
+        let uut = CaptureCompare::<U8>::new(bits(255));
+        // Switch from one duty cycle to another part way through
+        let input = pwm(20, 5, 5).chain(pwm(30, 25, 5));
+        let output = run(&uut, input)?;
+        let valid = output.iter().filter(|o| o.valid).collect::<Vec<_>>();
+        // Each result belongs to a single cycle of the input
+        assert!(valid.iter().all(
+            |o| (o.period, o.high) == (b8(20), b8(5)) || (o.period, o.high) == (b8(30), b8(25))
+        ));
+        // The update flag only strobes when the result changes
+        for w in output.windows(2) {
+            if w[1].period != w[0].period || w[1].high != w[0].high {
+                assert!(w[1].update);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stuck_input() -> miette::Result<()> {
+        let uut = CaptureCompare::<U8>::new(bits(50));
+        // Stuck high, then stuck low, then toggling again
+        let input = pwm(10, 4, 5)
+            .chain(std::iter::repeat_n(true, 100))
+            .chain(std::iter::repeat_n(false, 100))
+            .chain(pwm(12, 3, 5));
+        let output = run(&uut, input)?;
+        // Measuring correctly before the input gets stuck
+        assert!(output[40].valid);
+        assert_eq!((output[40].period, output[40].high), (b8(10), b8(4)));
+        // Stuck high, and the measurement is no longer valid
+        assert_eq!(output[140].stuck, Some(true));
+        assert!(!output[140].valid);
+        // The stuck flag is only raised after the timeout
+        assert!(output[80].stuck.is_none());
+        assert_eq!(output[240].stuck, Some(false));
+        assert!(!output[240].valid);
+        // And recovers once the input toggles
+        let last = output.last().unwrap();
+        assert!(last.valid);
+        assert!(last.stuck.is_none());
+        assert_eq!((last.period, last.high), (b8(12), b8(3)));
+        // The stuck period is never reported as a measurement
+        assert!(output
+            .iter()
+            .filter(|o| o.update)
+            .all(|o| o.period < b8(20)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_hdl() -> miette::Result<()> {
+        let uut = CaptureCompare::<U8>::new(bits(50));
+        let input = pwm(37, 12, 4)
+            .chain(std::iter::repeat_n(true, 60))
+            .chain(pwm(10, 3, 4))
+            .with_reset(1)
+            .clock_pos_edge(100);
+        let tb = uut.run(input)?.collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! Input Capture Timer
+//!
+//! The [CaptureTimer] core timestamps events against a free running
+//! `N` bit counter, like the input capture unit of a microcontroller
+//! timer.  The counter starts from `0` on reset, counts up on every
+//! clock, and wraps around from all ones back to `0`.  Its value is
+//! always available on the `now` output.
+//!
+//! When `capture` is high, the value of `now` on that clock is written
+//! to the `captured` register, and the `capture_valid` flag is strobed
+//! on the following clock (when the new value appears).  The `captured`
+//! value is held until the next capture.  When `clear` is high, the
+//! counter restarts from `0` on the next clock.  If `capture` and
+//! `clear` are both high, the value captured is the one from before the
+//! clear (i.e., the final count).
+//!
+//! The input is a tuple of `(capture, clear)`.  The `now` output is the
+//! counter itself, and the others are registered.  Differences between
+//! timestamps should be taken modulo `2^N`, so that they are correct
+//! across a wrap of the counter.  For measuring the period and high time
+//! of a signal directly, use the [CaptureCompare](super::capture::CaptureCompare).
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::timer::capture_timer::CaptureTimer;
+//!
+//! let uut = CaptureTimer::<U8>::default();
+//! let input = (0..10).map(|n| (n == 3 || n == 7, false));
+//! let captured = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .filter(|o| o.capture_valid)
+//!     .map(|o| o.captured)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(captured, [b8(3), b8(7)]);
+//!```
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [CaptureTimer] core
+pub struct Out<N: BitWidth> {
+    /// The current value of the counter
+    pub now: Bits<N>,
+    /// The value of the counter at the last capture
+    pub captured: Bits<N>,
+    /// Strobed when a new value is captured
+    pub capture_valid: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The input capture timer.  Here `N` is the
+/// width of the free running counter.
+pub struct CaptureTimer<N: BitWidth> {
+    count: dff::DFF<Bits<N>>,
+    captured: dff::DFF<Bits<N>>,
+    capture_valid: dff::DFF<bool>,
+}
+
+impl<N: BitWidth> Default for CaptureTimer<N> {
+    fn default() -> Self {
+        Self {
+            count: dff::DFF::default(),
+            captured: dff::DFF::default(),
+            capture_valid: dff::DFF::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for CaptureTimer<N> {
+    type I = (bool, bool);
+    type O = Out<N>;
+    type Kernel = capture_timer_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn capture_timer_kernel<N: BitWidth>(
+    _cr: ClockReset,
+    i: (bool, bool),
+    q: Q<N>,
+) -> (Out<N>, D<N>) {
+    let (capture, clear) = i;
+    let mut d = D::<N> {
+        count: q.count + 1,
+        captured: q.captured,
+        capture_valid: capture,
+    };
+    // The capture always takes the count from before any clear
+    if capture {
+        d.captured = q.count;
+    }
+    if clear {
+        d.count = bits(0);
+    }
+    let o = Out::<N> {
+        now: q.count,
+        captured: q.captured,
+        capture_valid: q.capture_valid,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: impl Iterator<Item = (bool, bool)>) -> miette::Result<Vec<Out<U4>>> {
+        let uut = CaptureTimer::<U4>::default();
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn captures(output: &[Out<U4>]) -> Vec<(usize, u128)> {
+        output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.capture_valid)
+            .map(|(ndx, o)| (ndx, o.captured.raw()))
+            .collect()
+    }
+
+    #[test]
+    fn test_timestamps() -> miette::Result<()> {
+        let at = [0, 2, 3, 9, 14];
+        let output = run((0..20).map(|n| (at.contains(&n), false)))?;
+        assert!(output
+            .iter()
+            .enumerate()
+            .all(|(n, o)| o.now.raw() as usize == n % 16));
+        // Each capture appears on the following clock
+        assert_eq!(
+            captures(&output),
+            [(1, 0), (3, 2), (4, 3), (10, 9), (15, 14)]
+        );
+        // And is held until the next one
+        assert!(output[10..15].iter().all(|o| o.captured == 9));
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_on_wrap() -> miette::Result<()> {
+        // The counter wraps from 15 to 0 between clocks 15 and 16
+        let at = [14, 15, 16, 17];
+        let output = run((0..20).map(|n| (at.contains(&n), false)))?;
+        assert_eq!(captures(&output), [(15, 14), (16, 15), (17, 0), (18, 1)]);
+        // Intervals are correct modulo 2^N across the wrap
+        let output = run((0..40).map(|n| (n == 12 || n == 21, false)))?;
+        let stamps = captures(&output);
+        assert_eq!(stamps, [(13, 12), (22, 5)]);
+        assert_eq!((stamps[1].1 + 16 - stamps[0].1) % 16, 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clear() -> miette::Result<()> {
+        let input = (0..20).map(|n| (n == 6 || n == 12, n == 6 || n == 10));
+        let output = run(input)?;
+        let now = output.iter().map(|o| o.now.raw()).collect::<Vec<_>>();
+        assert_eq!(
+            now,
+            [0, 1, 2, 3, 4, 5, 6, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 6, 7, 8]
+        );
+        // Capturing and clearing on the same clock takes the count
+        // from before the clear
+        assert_eq!(captures(&output), [(7, 6), (13, 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_timer_hdl() -> miette::Result<()> {
+        let uut = CaptureTimer::<U4>::default();
+        let input = (0..60)
+            .map(|n| (n % 7 == 0, n % 23 == 0))
+            .with_reset(1)
+            .clock_pos_edge(100);
+        let tb = uut.run(input)?.collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! Compare Match Timer
+//!
+//! The [Timer] core is a counter with `CHANNELS` compare channels, like
+//! the timers in a microcontroller.  The counter counts from `0` up to
+//! `top` (given at construction), and then wraps back to `0`, so each
+//! period is `top + 1` clocks long.  The default `top` is all ones, so
+//! the counter is free running.
+//!
+//! The input is an array of compare values, one per channel.  On each
+//! clock the count equals the compare value of a channel, its `matched`
+//! output is high for that clock, and its `toggle` output changes
+//! level.  So each channel gives a one clock strobe once per period, at
+//! a phase set by its compare value, and a square wave with a period of
+//! two periods of the counter.  A compare value of `0` matches on the
+//! first clock of each period, and one of `top` on the last.  A compare
+//! value greater than `top` never matches.
+//!
+//! The outputs are registered, and include the count, so the `matched`
+//! flags line up with the `count` they matched.  The compare values are
+//! sampled on the clock before the count reaches them.  On reset, the
+//! count restarts from `0` (on the first clock after reset), and all of
+//! the `toggle` outputs are cleared.
+//!
+//!# Example
+//!
+//! Two strobes, a quarter of a period apart.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::timer::compare::Timer;
+//!
+//! let uut = Timer::<U3, 2>::default();
+//! let input = std::iter::repeat_n([b3(1), b3(3)], 17);
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(2)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! let a = output.iter().map(|o| o.matched[0] as u8).collect::<Vec<_>>();
+//! let b = output.iter().map(|o| o.matched[1] as u8).collect::<Vec<_>>();
+//! assert_eq!(a, [0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+//! assert_eq!(b, [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [Timer] core
+pub struct Out<N: BitWidth, const CHANNELS: usize> {
+    /// The count
+    pub count: Bits<N>,
+    /// High for each channel whose compare value matches the count
+    pub matched: [bool; CHANNELS],
+    /// Changes level for each channel on each match
+    pub toggle: [bool; CHANNELS],
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The compare match timer.  Here `N` is the width
+/// of the counter, and `CHANNELS` is the number of
+/// compare channels.
+pub struct Timer<N: BitWidth, const CHANNELS: usize> {
+    top: constant::Constant<Bits<N>>,
+    out: dff::DFF<Out<N, CHANNELS>>,
+}
+
+impl<N: BitWidth, const CHANNELS: usize> Timer<N, CHANNELS> {
+    /// Create a new [Timer] core, with a counter that
+    /// counts from `0` to `top`.
+    pub fn new(top: Bits<N>) -> Self {
+        Self {
+            top: constant::Constant::new(top),
+            // Start at the top, so that the first clock
+            // after reset is the start of a period
+            out: dff::DFF::new(Out::<N, CHANNELS> {
+                count: top,
+                matched: [false; CHANNELS],
+                toggle: [false; CHANNELS],
+            }),
+        }
+    }
+}
+
+impl<N: BitWidth, const CHANNELS: usize> Default for Timer<N, CHANNELS> {
+    fn default() -> Self {
+        Self::new(Bits::<N>::MAX)
+    }
+}
+
+impl<N: BitWidth, const CHANNELS: usize> SynchronousIO for Timer<N, CHANNELS> {
+    type I = [Bits<N>; CHANNELS];
+    type O = Out<N, CHANNELS>;
+    type Kernel = timer_kernel<N, CHANNELS>;
+}
+
+#[kernel]
+#[doc(hidden)]
+#[allow(clippy::needless_range_loop)]
+pub fn timer_kernel<N: BitWidth, const CHANNELS: usize>(
+    _cr: ClockReset,
+    compare: [Bits<N>; CHANNELS],
+    q: Q<N, CHANNELS>,
+) -> (Out<N, CHANNELS>, D<N, CHANNELS>) {
+    let count = if q.out.count >= q.top {
+        bits(0)
+    } else {
+        q.out.count + 1
+    };
+    let mut out = q.out;
+    out.count = count;
+    for c in 0..CHANNELS {
+        let matched = compare[c] == count;
+        out.matched[c] = matched;
+        out.toggle[c] = q.out.toggle[c] ^ matched;
+    }
+    let d = D::<N, CHANNELS> { top: (), out };
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: usize = 16;
+
+    fn run<const CHANNELS: usize>(
+        uut: &Timer<U8, CHANNELS>,
+        compare: [u128; CHANNELS],
+        len: usize,
+    ) -> miette::Result<Vec<Out<U8, CHANNELS>>> {
+        // Skip the reset, and the clock that holds the count at the top
+        let input = std::iter::repeat_n(compare.map(b8), len + 1);
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(2)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn positions<const CHANNELS: usize>(
+        output: &[Out<U8, CHANNELS>],
+        channel: usize,
+    ) -> Vec<usize> {
+        output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.matched[channel])
+            .map(|(ndx, _)| ndx)
+            .collect()
+    }
+
+    #[test]
+    fn test_three_channels() -> miette::Result<()> {
+        let uut = Timer::<U8, 3>::new(b8(PERIOD as u128 - 1));
+        let output = run(&uut, [2, 7, 12], PERIOD * 4)?;
+        assert!(output
+            .iter()
+            .enumerate()
+            .all(|(n, o)| o.count.raw() as usize == n % PERIOD));
+        assert_eq!(positions(&output, 0), [2, 18, 34, 50]);
+        assert_eq!(positions(&output, 1), [7, 23, 39, 55]);
+        assert_eq!(positions(&output, 2), [12, 28, 44, 60]);
+        // The toggle outputs change level on each match
+        for c in 0..3 {
+            for w in output.windows(2) {
+                assert_eq!(w[1].toggle[c] != w[0].toggle[c], w[1].matched[c]);
+            }
+        }
+        let toggle = output.iter().map(|o| o.toggle[1]).collect::<Vec<_>>();
+        assert!(toggle[..7].iter().all(|t| !*t));
+        assert!(toggle[7..23].iter().all(|t| *t));
+        assert!(toggle[23..39].iter().all(|t| !*t));
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_and_last_count() -> miette::Result<()> {
+        let uut = Timer::<U8, 3>::new(b8(PERIOD as u128 - 1));
+        let output = run(&uut, [0, 15, 16], PERIOD * 3)?;
+        assert_eq!(positions(&output, 0), [0, 16, 32]);
+        assert_eq!(positions(&output, 1), [15, 31, 47]);
+        // Beyond the top, so never matched
+        assert!(positions(&output, 2).is_empty());
+        assert!(output.iter().all(|o| !o.toggle[2]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_free_running() -> miette::Result<()> {
+        let uut = Timer::<U8, 2>::default();
+        let output = run(&uut, [0, 255], 256 * 2)?;
+        assert_eq!(positions(&output, 0), [0, 256]);
+        assert_eq!(positions(&output, 1), [255, 511]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timer_hdl() -> miette::Result<()> {
+        let uut = Timer::<U8, 3>::new(b8(PERIOD as u128 - 1));
+        let input = (0..100).map(|n| [b8(0), b8(15), b8((n / 20) * 3)]);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Frequency Counter
+//!
+//! The [FreqCounter] core measures the frequency of its input by
+//! counting the rising edges in a fixed gate interval.  The gate is
+//! timed by a [StrobeDiv](crate::core::strobe_div::StrobeDiv), and is
+//! `gate` clocks long (given at construction).  The edges are found by
+//! an [EdgeDetect](crate::core::edge_detect::EdgeDetect).  At the end of
+//! each gate, the count is written to the output register, and the
+//! `valid` flag is strobed for one clock.  The frequency of the input is
+//! then `count / gate` times the clock frequency.
+//!
+//! The count is captured and cleared on the same clock, and an edge on
+//! that clock is included in the count that is captured.  So each edge
+//! is counted in exactly one gate, and none are lost across the gate
+//! boundaries.  The first gate starts on the clock after reset, and the
+//! first edge can only be detected on the clock after that.  The count
+//! cannot overflow, since there are at most `gate / 2` (rounded up)
+//! rising edges in a gate.
+//!
+//! The input is assumed to be synchronous to the clock (for example,
+//! using a [Sync1Bit](crate::cdc::synchronizer::Sync1Bit) core).  The
+//! fastest input is then one that toggles on every clock (i.e., at half
+//! the clock frequency), which gives a rising edge on every other clock.
+//! Anything faster is aliased by the synchronizer before it gets here.
+//! A `gate` of `0` acts as `1`.
+//!
+//!# Example
+//!
+//! A signal with a period of 4 clocks, measured with a gate of 20.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::timer::freq_counter::FreqCounter;
+//!
+//! let uut = FreqCounter::<U8>::new(b8(20));
+//! let input = (0..80).map(|n| n % 4 >= 2);
+//! let valid = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .filter(|o| o.valid)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(valid.len(), 3);
+//! assert!(valid.iter().all(|o| o.count == b8(5)));
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff, edge_detect::EdgeDetect, strobe_div::StrobeDiv};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [FreqCounter] core
+pub struct Out<N: BitWidth> {
+    /// The number of rising edges in the last gate
+    pub count: Bits<N>,
+    /// Strobed when a new count is available
+    pub valid: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The frequency counter.  Here `N` is the width
+/// of the gate timer, and the edge counter.
+pub struct FreqCounter<N: BitWidth> {
+    gate_len: constant::Constant<Bits<N>>,
+    gate: StrobeDiv<N>,
+    edge: EdgeDetect,
+    count: dff::DFF<Bits<N>>,
+    result: dff::DFF<Out<N>>,
+}
+
+impl<N: BitWidth> FreqCounter<N> {
+    /// Create a new [FreqCounter] core, that counts
+    /// edges over a gate of `gate` clocks.
+    pub fn new(gate: Bits<N>) -> Self {
+        Self {
+            gate_len: constant::Constant::new(gate),
+            gate: StrobeDiv::default(),
+            edge: EdgeDetect::default(),
+            count: dff::DFF::default(),
+            result: dff::DFF::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for FreqCounter<N> {
+    fn default() -> Self {
+        Self::new(Bits::<N>::MAX)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for FreqCounter<N> {
+    type I = bool;
+    type O = Out<N>;
+    type Kernel = freq_counter_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn freq_counter_kernel<N: BitWidth>(cr: ClockReset, i: bool, q: Q<N>) -> (Out<N>, D<N>) {
+    let mut d = D::<N> {
+        gate_len: (),
+        gate: q.gate_len,
+        edge: i,
+        count: q.count,
+        result: q.result,
+    };
+    d.result.valid = false;
+    let count = if q.edge.rising { q.count + 1 } else { q.count };
+    // Capture and clear on the same clock, so that an edge
+    // at the end of the gate is counted in this gate
+    if q.gate {
+        d.result = Out::<N> { count, valid: true };
+        d.count = bits(0);
+    } else {
+        d.count = count;
+    }
+    if cr.reset.any() {
+        d.count = bits(0);
+        d.result = Out::<N>::default();
+    }
+    (q.result, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GATE: usize = 120;
+
+    fn run(input: &[bool]) -> miette::Result<Vec<Out<U8>>> {
+        let uut = FreqCounter::<U8>::new(bits(GATE as u128));
+        Ok(uut
+            .run(input.iter().copied().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn counts(output: &[Out<U8>]) -> Vec<u128> {
+        output
+            .iter()
+            .filter(|o| o.valid)
+            .map(|o| o.count.raw())
+            .collect()
+    }
+
+    // A square wave with the given period, that starts low
+    fn square(period: usize, len: usize) -> Vec<bool> {
+        (0..len).map(|n| n % period >= period / 2).collect()
+    }
+
+    #[test]
+    fn test_known_rates() -> miette::Result<()> {
+        for period in [2, 3, 4, 5, 6, 8, 10, 12, 24, 40, 120] {
+            let output = run(&square(period, GATE * 5))?;
+            assert_eq!(counts(&output), [(GATE / period) as u128; 4]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_half_clock_rate() -> miette::Result<()> {
+        // Toggling on every clock is the fastest possible input, and
+        // gives a rising edge on every other clock
+        let output = run(&square(2, GATE * 5))?;
+        assert_eq!(counts(&output), [GATE as u128 / 2; 4]);
+        // With the edges landing on the gate boundaries
+        let input = (0..GATE * 5).map(|n| n % 2 == 0).collect::<Vec<_>>();
+        let output = run(&input)?;
+        assert_eq!(counts(&output), [GATE as u128 / 2 - 1, 60, 60, 60]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_edges_lost() -> miette::Result<()> {
+        // A random input, so that edges land on every part of the gate
+        let input = (0..GATE * 10)
+            .map(|_| rand::random::<u8>() < 80)
+            .collect::<Vec<_>>();
+        let output = run(&input)?;
+        let rising = (0..input.len())
+            .map(|n| n > 0 && input[n] && !input[n - 1])
+            .collect::<Vec<_>>();
+        let expected = rising
+            .chunks_exact(GATE)
+            .map(|gate| gate.iter().filter(|r| **r).count() as u128)
+            .collect::<Vec<_>>();
+        // The last gate closes on the last clock, so its count
+        // is not on the output yet
+        assert_eq!(counts(&output), expected[..9]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_freq_counter_hdl() -> miette::Result<()> {
+        let uut = FreqCounter::<U8>::new(b8(30));
+        let mut input = square(3, 100);
+        input.extend(square(7, 100));
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+//! Timer and counter cores
+pub mod capture;
+pub mod capture_timer;
+pub mod compare;
+pub mod freq_counter;
+pub mod pulse_measure;
+pub mod watchdog;
+pub mod tick_gen;
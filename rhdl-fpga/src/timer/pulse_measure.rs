@@ -0,0 +1,264 @@
+//! Pulse Width and Period Measurement
+//!
+//! The [PulseMeasure] core measures how long its input is high, and the
+//! period between rising edges (e.g., for reading the pulses from an RC
+//! servo receiver, or the output of a tachometer).  The edges of the
+//! input are detected against a register holding its previous value, and
+//! a counter counts the clocks since the last rising edge.  The count is
+//! captured as the high time on each falling edge, and as the period on
+//! each rising edge.  Both are then written to the output registers
+//! together, and the `valid` flag is strobed for one clock.  So the first
+//! measurement is made on the second rising edge after reset.
+//!
+//! The counter saturates rather than wrapping.  If the period (or the
+//! high time) is `2^N` clocks or more, the measurement is still reported,
+//! but with the `overflow` flag set, and the saturated counts should not
+//! be trusted.  An input that stops toggling produces no measurements at
+//! all.  To detect a stuck input, use the
+//! [CaptureCompare](super::capture::CaptureCompare).
+//!
+//! The input is assumed to be synchronous to the clock (for
+//! example, using a [Sync1Bit](crate::cdc::synchronizer::Sync1Bit)
+//! core).
+//!
+//!# Example
+//!
+//! Measuring a signal that is high for 3 clocks out of 10.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::timer::pulse_measure::PulseMeasure;
+//!
+//! let uut = PulseMeasure::<U8>::default();
+//! let input = (0..40).map(|n| n % 10 < 3);
+//! let valid = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2)
+//!     .filter(|o| o.valid)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(valid.len(), 3);
+//! assert!(valid.iter().all(|o| o.high == b8(3) && o.period == b8(10)));
+//! assert!(valid.iter().all(|o| !o.overflow));
+//!```
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [PulseMeasure] core
+pub struct Out<N: BitWidth> {
+    /// The number of clocks the input was high
+    pub high: Bits<N>,
+    /// The number of clocks between the last two rising edges
+    pub period: Bits<N>,
+    /// Strobed when a new measurement is available
+    pub valid: bool,
+    /// Set if the high time or the period did not fit in `N` bits
+    pub overflow: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The pulse measurement core.  Here `N` is the
+/// width of the counter.
+pub struct PulseMeasure<N: BitWidth> {
+    prev: dff::DFF<bool>,
+    armed: dff::DFF<bool>,
+    elapsed: dff::DFF<Bits<N>>,
+    saturated: dff::DFF<bool>,
+    high: dff::DFF<Bits<N>>,
+    high_overflow: dff::DFF<bool>,
+    result: dff::DFF<Out<N>>,
+}
+
+impl<N: BitWidth> Default for PulseMeasure<N> {
+    fn default() -> Self {
+        Self {
+            prev: dff::DFF::default(),
+            armed: dff::DFF::default(),
+            elapsed: dff::DFF::default(),
+            saturated: dff::DFF::default(),
+            high: dff::DFF::default(),
+            high_overflow: dff::DFF::default(),
+            result: dff::DFF::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for PulseMeasure<N> {
+    type I = bool;
+    type O = Out<N>;
+    type Kernel = pulse_measure_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn pulse_measure_kernel<N: BitWidth>(cr: ClockReset, i: bool, q: Q<N>) -> (Out<N>, D<N>) {
+    let mut d = D::<N> {
+        prev: i,
+        armed: q.armed,
+        elapsed: q.elapsed,
+        saturated: q.saturated,
+        high: q.high,
+        high_overflow: q.high_overflow,
+        result: q.result,
+    };
+    d.result.valid = false;
+    let rising = i && !q.prev;
+    let falling = !i && q.prev;
+    if rising {
+        // A full cycle has been seen, unless this is the first edge
+        if q.armed {
+            d.result = Out::<N> {
+                high: q.high,
+                period: q.elapsed,
+                valid: true,
+                overflow: q.saturated || q.high_overflow,
+            };
+        }
+        d.armed = true;
+        // The rising edge itself is the first clock of the next period
+        d.elapsed = bits(1);
+        d.saturated = false;
+    } else if q.elapsed.all() {
+        // Saturate, and remember that the count is no longer exact
+        d.saturated = true;
+    } else {
+        d.elapsed = q.elapsed + 1;
+    }
+    if falling {
+        d.high = q.elapsed;
+        d.high_overflow = q.saturated;
+    }
+    if cr.reset.any() {
+        d.armed = false;
+        d.result = Out::<N>::default();
+    }
+    (q.result, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A pulse train with the given period and high time
+    fn pulses(period: usize, high: usize, count: usize) -> impl Iterator<Item = bool> {
+        (0..period * count).map(move |n| n % period < high)
+    }
+
+    fn run(
+        uut: &PulseMeasure<U8>,
+        input: impl Iterator<Item = bool>,
+    ) -> miette::Result<Vec<Out<U8>>> {
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn measurements(output: &[Out<U8>]) -> Vec<(u128, u128, bool)> {
+        output
+            .iter()
+            .filter(|o| o.valid)
+            .map(|o| (o.high.raw(), o.period.raw(), o.overflow))
+            .collect()
+    }
+
+    #[test]
+    fn test_known_widths() -> miette::Result<()> {
+        let uut = PulseMeasure::<U8>::default();
+        for (period, high) in [(2, 1), (10, 3), (50, 49), (100, 20), (255, 128)] {
+            let output = run(&uut, pulses(period, high, 5))?;
+            // The first rising edge only starts the measurement
+            assert_eq!(
+                measurements(&output),
+                vec![(high as u128, period as u128, false); 4]
+            );
+            // The valid flag is a strobe
+            assert_eq!(output.iter().filter(|o| o.valid).count(), 4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_result_is_held_between_measurements() -> miette::Result<()> {
+        let uut = PulseMeasure::<U8>::default();
+        let input = pulses(12, 5, 3).chain(pulses(20, 15, 3));
+        let output = run(&uut, input)?;
+        assert_eq!(
+            measurements(&output),
+            [
+                (5, 12, false),
+                (5, 12, false),
+                (5, 12, false),
+                (15, 20, false),
+                (15, 20, false)
+            ]
+        );
+        // The values stay on the outputs until the next measurement
+        let first = output.iter().position(|o| o.valid).unwrap();
+        assert!(output[first..]
+            .iter()
+            .all(|o| o.period == b8(12) || o.period == b8(20)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflow() -> miette::Result<()> {
+        let uut = PulseMeasure::<U8>::default();
+        // A period of exactly 255 clocks still fits
+        let output = run(&uut, pulses(255, 10, 3))?;
+        assert_eq!(measurements(&output), [(10, 255, false); 2]);
+        // But 256 does not, and neither does a high time of 300
+        let input = pulses(10, 4, 2)
+            .chain(pulses(256, 10, 1))
+            .chain(pulses(400, 300, 1))
+            .chain(pulses(10, 4, 2));
+        let output = run(&uut, input)?;
+        assert_eq!(
+            measurements(&output),
+            [
+                (4, 10, false),
+                (4, 10, false),
+                (10, 255, true),
+                (255, 255, true),
+                (4, 10, false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_high_at_reset() -> miette::Result<()> {
+        let uut = PulseMeasure::<U8>::default();
+        // The input starts high, so the first rising edge is on the first clock
+        let input = std::iter::repeat_n(true, 7)
+            .chain(std::iter::repeat_n(false, 5))
+            .chain(pulses(9, 2, 3));
+        let output = run(&uut, input)?;
+        assert_eq!(
+            measurements(&output),
+            [(7, 12, false), (2, 9, false), (2, 9, false)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pulse_measure_hdl() -> miette::Result<()> {
+        let uut = PulseMeasure::<U8>::default();
+        let input = pulses(17, 6, 4)
+            .chain(pulses(300, 100, 1))
+            .chain(pulses(9, 4, 4))
+            .with_reset(1)
+            .clock_pos_edge(100);
+        let tb = uut.run(input)?.collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
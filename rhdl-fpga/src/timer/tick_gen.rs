@@ -0,0 +1,313 @@
+//! Microsecond, Millisecond and Second Ticks
+//!
+//! The [TickGen] core produces single clock strobes once every
+//! microsecond (`us`), millisecond (`ms`) and second (`s`), from a
+//! clock of any frequency (given in Hz at construction).  Few clock
+//! frequencies are a whole number of MHz, so the microsecond tick is
+//! not made by dividing the clock.  Instead, a fractional accumulator
+//! adds `1_000_000` on every clock, and when it reaches the clock
+//! frequency, the frequency is subtracted and a microsecond tick is
+//! issued.  The remainder is kept, so the ticks are spaced by either
+//! the whole number of clocks below the ratio or the one above it,
+//! with the long term rate exact.  At 12.288 MHz, for example, the
+//! ticks are 12 or 13 clocks apart, in a pattern that gives exactly
+//! 1_000_000 ticks in 12_288_000 clocks.
+//!
+//! The millisecond tick is made by counting 1000 microsecond ticks,
+//! and the second tick by counting 1000 millisecond ticks, so the
+//! ticks are aligned: each `s` strobe comes with an `ms` strobe, and
+//! each `ms` strobe with a `us` strobe.  The first of each tick comes
+//! at the end of the first full interval after reset.  The outputs are
+//! registered.
+//!
+//! The clock frequency is given in a validated [Config].  It must be at
+//! least 1 MHz (so that there is at most one microsecond tick per
+//! clock), and less than `2^32` Hz (the width of the accumulator).
+//!
+//!# Example
+//!
+//! A 2.5 MHz clock gives microsecond ticks alternately 2 and 3
+//! clocks apart.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::config::Validate;
+//! use rhdl_fpga::timer::tick_gen::{Config, TickGen};
+//!
+//! let uut = TickGen::new(Config::new(2_500_000).validate().unwrap());
+//! let input = std::iter::repeat_n((), 21);
+//! let us = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2.us as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(us, [0, 0, 0, 1, 0, 1, 0, 0, 1, 0, 1, 0, 0, 1, 0, 1, 0, 0, 1, 0, 1]);
+//!```
+use miette::Diagnostic;
+use rhdl::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    config::{Validate, Validated},
+    core::{constant, dff},
+};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [TickGen] core
+pub struct Out {
+    /// Strobed once per microsecond
+    pub us: bool,
+    /// Strobed once per millisecond
+    pub ms: bool,
+    /// Strobed once per second
+    pub s: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The tick generator
+pub struct TickGen {
+    clock_hz: constant::Constant<b33>,
+    top: constant::Constant<b10>,
+    acc: dff::DFF<b33>,
+    us_count: dff::DFF<b10>,
+    ms_count: dff::DFF<b10>,
+    out: dff::DFF<Out>,
+}
+
+impl TickGen {
+    /// Create a new [TickGen] core, for the clock
+    /// frequency in a validated [Config]
+    pub fn new(config: Validated<Config>) -> Self {
+        Self {
+            clock_hz: constant::Constant::new(b33(config.clock_hz as u128)),
+            top: constant::Constant::new(b10((1000 / config.divider) as u128 - 1)),
+            acc: dff::DFF::default(),
+            us_count: dff::DFF::default(),
+            ms_count: dff::DFF::default(),
+            out: dff::DFF::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The clock frequency of a [TickGen]
+pub struct Config {
+    /// The frequency of the clock in Hz
+    pub clock_hz: u64,
+    // Shortens the millisecond and second (but not the
+    // microsecond), so that tests can run for whole seconds
+    divider: u64,
+}
+
+impl Config {
+    /// Generate ticks from a clock of `clock_hz` Hz
+    pub fn new(clock_hz: u64) -> Self {
+        Self {
+            clock_hz,
+            divider: 1,
+        }
+    }
+    // Count `1000 / divider` ticks to the next longer tick,
+    // rather than 1000
+    #[cfg(test)]
+    fn with_divider(self, divider: u64) -> Self {
+        assert!(
+            divider > 0 && 1000 % divider == 0,
+            "The divider must be a factor of 1000"
+        );
+        Self { divider, ..self }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+/// Errors that can arise when validating a [Config]
+pub enum ConfigError {
+    /// The clock is too slow for a tick every microsecond
+    #[error("clock_hz is {clock_hz}, which is below 1 MHz")]
+    #[diagnostic(help(
+        "The microsecond tick is at most one per clock, so clock_hz must be at least 1_000_000"
+    ))]
+    ClockTooSlow {
+        /// The clock frequency provided
+        clock_hz: u64,
+    },
+    /// The clock is too fast for the accumulator
+    #[error("clock_hz is {clock_hz}, which does not fit in 32 bits")]
+    #[diagnostic(help("The accumulator is 32 bits wide, so clock_hz must be less than 2^32"))]
+    ClockTooFast {
+        /// The clock frequency provided
+        clock_hz: u64,
+    },
+}
+
+impl Validate for Config {
+    type Error = ConfigError;
+    fn check(&self) -> Result<(), ConfigError> {
+        if self.clock_hz < 1_000_000 {
+            return Err(ConfigError::ClockTooSlow {
+                clock_hz: self.clock_hz,
+            });
+        }
+        if self.clock_hz >= 1 << 32 {
+            return Err(ConfigError::ClockTooFast {
+                clock_hz: self.clock_hz,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl SynchronousIO for TickGen {
+    type I = ();
+    type O = Out;
+    type Kernel = tick_gen_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn tick_gen_kernel(_cr: ClockReset, _i: (), q: Q) -> (Out, D) {
+    let mut d = D {
+        clock_hz: (),
+        top: (),
+        acc: q.acc,
+        us_count: q.us_count,
+        ms_count: q.ms_count,
+        out: Out::default(),
+    };
+    // The accumulator is below the clock frequency, so there is
+    // room for the sum in the extra bit
+    let sum = q.acc + bits(1000000);
+    let us = sum >= q.clock_hz;
+    let ms = us && q.us_count == q.top;
+    let s = ms && q.ms_count == q.top;
+    if us {
+        d.acc = sum - q.clock_hz;
+        d.us_count = if ms { bits(0) } else { q.us_count + 1 };
+    } else {
+        d.acc = sum;
+    }
+    if ms {
+        d.ms_count = if s { bits(0) } else { q.ms_count + 1 };
+    }
+    d.out = Out { us, ms, s };
+    (q.out, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each millisecond is 25 microseconds, and each second 25
+    // milliseconds, so a second is 625 microseconds
+    const DIVIDER: u64 = 40;
+
+    fn run(clock_hz: u64, seconds: u64) -> miette::Result<Vec<Out>> {
+        let config = Config::new(clock_hz).with_divider(DIVIDER).validate()?;
+        let uut = TickGen::new(config);
+        // The clocks in the given number of (shortened) seconds,
+        // plus one for the last ticks to reach the output
+        let clocks = clock_hz * seconds / (DIVIDER * DIVIDER);
+        let input = std::iter::repeat_n((), clocks as usize + 1);
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn counts(output: &[Out]) -> (usize, usize, usize) {
+        (
+            output.iter().filter(|o| o.us).count(),
+            output.iter().filter(|o| o.ms).count(),
+            output.iter().filter(|o| o.s).count(),
+        )
+    }
+
+    fn spacing(output: &[Out]) -> Vec<usize> {
+        let at = output
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.us)
+            .map(|(ndx, _)| ndx)
+            .collect::<Vec<_>>();
+        at.windows(2).map(|w| w[1] - w[0]).collect()
+    }
+
+    #[test]
+    fn test_12mhz() -> miette::Result<()> {
+        let output = run(12_000_000, 2)?;
+        assert_eq!(counts(&output), (1250, 50, 2));
+        assert!(spacing(&output).iter().all(|s| *s == 12));
+        // The last second ends on the last clock
+        assert!(output.last().unwrap().s);
+        Ok(())
+    }
+
+    #[test]
+    fn test_100mhz() -> miette::Result<()> {
+        let output = run(100_000_000, 1)?;
+        assert_eq!(counts(&output), (625, 25, 1));
+        assert!(spacing(&output).iter().all(|s| *s == 100));
+        assert!(output.last().unwrap().s);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fractional_ratio() -> miette::Result<()> {
+        // 12.288 and 7.3728 MHz are not whole numbers of MHz, but the
+        // long term rate is still exact
+        for (clock_hz, apart) in [(12_288_000, [12, 13]), (7_372_800, [7, 8])] {
+            let output = run(clock_hz, 3)?;
+            assert_eq!(counts(&output), (1875, 75, 3));
+            assert!(spacing(&output).iter().all(|s| apart.contains(s)));
+            assert!(output.last().unwrap().s);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ticks_are_aligned() -> miette::Result<()> {
+        let output = run(7_372_800, 2)?;
+        assert!(output.iter().all(|o| !o.ms || o.us));
+        assert!(output.iter().all(|o| !o.s || o.ms));
+        // And each is a single clock strobe
+        assert!(output.windows(2).all(|w| !(w[0].us && w[1].us)));
+        // Except at 1 MHz, where there is a tick on every clock
+        let output = run(1_000_000, 1)?;
+        assert_eq!(counts(&output), (625, 25, 1));
+        assert!(output[1..].iter().all(|o| o.us));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unrepresentable_clocks() {
+        assert_eq!(
+            Config::new(999_999).validate().unwrap_err(),
+            ConfigError::ClockTooSlow { clock_hz: 999_999 }
+        );
+        assert_eq!(
+            Config::new(1 << 32).validate().unwrap_err(),
+            ConfigError::ClockTooFast { clock_hz: 1 << 32 }
+        );
+        assert!(Config::new(1_000_000).validate().is_ok());
+        assert!(Config::new((1 << 32) - 1).validate().is_ok());
+    }
+
+    #[test]
+    fn test_tick_gen_hdl() -> miette::Result<()> {
+        let config = Config::new(2_500_000).with_divider(200).validate()?;
+        let uut = TickGen::new(config);
+        let input = std::iter::repeat_n((), 200);
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! Watchdog Timer
+//!
+//! The [Watchdog] core raises its `bark` output if it is not kicked
+//! often enough.  While `enable` is high, it counts the clocks since the
+//! last `kick`, and barks once `timeout` clocks have passed without one.
+//! So kicking on every `timeout`th clock (or more often) keeps it quiet
+//! indefinitely.  While `enable` is low, the count is held at zero, so
+//! enabling the watchdog starts a fresh timeout.
+//!
+//! A kick on the same clock the watchdog would time out takes priority,
+//! since it arrived in time.  Once the watchdog barks, `bark` stays high
+//! (and the count stops) until the core is reset.  Neither a kick nor
+//! dropping `enable` clears it, so a late kick cannot hide a timeout.
+//!
+//! The input is a tuple of `(kick, enable)`, and the outputs are
+//! registered.  A `timeout` of `0` acts as `1`.
+//!
+//!# Example
+//!
+//! A timeout of 4 clocks, with kicks that get further apart.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::timer::watchdog::Watchdog;
+//!
+//! let uut = Watchdog::<U4>::new(b4(4));
+//! let kicks = [0, 4, 7, 11, 16];
+//! let input = (0..24).map(|n| (kicks.contains(&n), true));
+//! let output = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .skip(1)
+//!     .map(|t| t.value.2)
+//!     .collect::<Vec<_>>();
+//! // The gap from 11 to 16 is too long, so it barks after clock 15
+//! let bark = output.iter().position(|o| o.bark).unwrap();
+//! assert_eq!(bark, 16);
+//! assert!(output[16..].iter().all(|o| o.bark && o.count == b4(4)));
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The outputs of the [Watchdog] core
+pub struct Out<N: BitWidth> {
+    /// Set (and latched until reset) when the watchdog times out
+    pub bark: bool,
+    /// The number of clocks since the last kick
+    pub count: Bits<N>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The watchdog timer.  Here `N` is the
+/// width of the counter.
+pub struct Watchdog<N: BitWidth> {
+    timeout: constant::Constant<Bits<N>>,
+    count: dff::DFF<Bits<N>>,
+    bark: dff::DFF<bool>,
+}
+
+impl<N: BitWidth> Watchdog<N> {
+    /// Create a new [Watchdog] core, that barks if it is not
+    /// kicked for `timeout` clocks.
+    pub fn new(timeout: Bits<N>) -> Self {
+        Self {
+            timeout: constant::Constant::new(timeout),
+            count: dff::DFF::default(),
+            bark: dff::DFF::default(),
+        }
+    }
+}
+
+impl<N: BitWidth> Default for Watchdog<N> {
+    fn default() -> Self {
+        Self::new(Bits::<N>::MAX)
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for Watchdog<N> {
+    type I = (bool, bool);
+    type O = Out<N>;
+    type Kernel = watchdog_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn watchdog_kernel<N: BitWidth>(_cr: ClockReset, i: (bool, bool), q: Q<N>) -> (Out<N>, D<N>) {
+    let (kick, enable) = i;
+    let mut d = D::<N> {
+        timeout: (),
+        count: q.count,
+        bark: q.bark,
+    };
+    // Once barked, everything is frozen until reset.  Otherwise
+    // a kick wins over a timeout on the same clock.
+    if !q.bark {
+        if !enable || kick {
+            d.count = bits(0);
+        } else {
+            d.count = q.count + 1;
+            if d.count >= q.timeout {
+                d.bark = true;
+            }
+        }
+    }
+    (
+        Out::<N> {
+            bark: q.bark,
+            count: q.count,
+        },
+        d,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: usize = 10;
+
+    fn run(input: impl Iterator<Item = (bool, bool)>) -> miette::Result<Vec<Out<U8>>> {
+        let uut = Watchdog::<U8>::new(bits(TIMEOUT as u128));
+        Ok(uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    fn kicked_at(kicks: &[usize], len: usize) -> impl Iterator<Item = (bool, bool)> + '_ {
+        (0..len).map(move |n| (kicks.contains(&n), true))
+    }
+
+    #[test]
+    fn test_exact_timeout() -> miette::Result<()> {
+        // Never kicked, so it barks TIMEOUT clocks after it is enabled
+        let output = run(kicked_at(&[], 30))?;
+        assert_eq!(output.iter().position(|o| o.bark), Some(TIMEOUT));
+        for (n, o) in output.iter().enumerate().take(TIMEOUT + 1) {
+            assert_eq!(o.count.raw() as usize, n);
+        }
+        // And TIMEOUT clocks after the last kick
+        let output = run(kicked_at(&[5], 30))?;
+        assert_eq!(output.iter().position(|o| o.bark), Some(5 + TIMEOUT + 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_regular_kicks() -> miette::Result<()> {
+        // A kick every TIMEOUT clocks keeps it quiet, even when the
+        // kick lands on the clock it would have timed out
+        let kicks = (0..1000).step_by(TIMEOUT).collect::<Vec<_>>();
+        let output = run(kicked_at(&kicks, 1000))?;
+        assert!(output.iter().all(|o| !o.bark));
+        assert!(output.iter().all(|o| o.count.raw() < TIMEOUT as u128));
+        // But every TIMEOUT + 1 clocks is too slow
+        let kicks = (0..1000).step_by(TIMEOUT + 1).collect::<Vec<_>>();
+        let output = run(kicked_at(&kicks, 1000))?;
+        assert_eq!(output.iter().position(|o| o.bark), Some(TIMEOUT + 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bark_is_latched_until_reset() -> miette::Result<()> {
+        let uut = Watchdog::<U8>::new(bits(TIMEOUT as u128));
+        // Time out, then kick (and disable) to try to clear it
+        let input = std::iter::repeat_n((false, true), 15)
+            .chain(std::iter::repeat_n((true, true), 5))
+            .chain(std::iter::repeat_n((false, false), 5))
+            .with_reset(1)
+            .chain(std::iter::repeat_n((false, true), 5).with_reset(1))
+            .clock_pos_edge(100);
+        let output = uut
+            .run(input)?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        assert!(output[..TIMEOUT].iter().all(|o| !o.bark));
+        assert!(output[TIMEOUT..25].iter().all(|o| o.bark));
+        assert!(output[TIMEOUT..25]
+            .iter()
+            .all(|o| o.count.raw() as usize == TIMEOUT));
+        // Only the reset clears it, and the count restarts
+        let after = &output[26..];
+        assert!(after.iter().all(|o| !o.bark));
+        assert_eq!(
+            after.iter().map(|o| o.count.raw()).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_disabled() -> miette::Result<()> {
+        // Never barks while disabled, and enabling starts a fresh timeout
+        let input = std::iter::repeat_n((false, false), 50).chain(kicked_at(&[], 20));
+        let output = run(input)?;
+        assert!(output[..50].iter().all(|o| !o.bark && o.count == 0));
+        assert_eq!(output.iter().position(|o| o.bark), Some(50 + TIMEOUT));
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchdog_hdl() -> miette::Result<()> {
+        let uut = Watchdog::<U8>::new(bits(TIMEOUT as u128));
+        let kicks = [3, 10, 20, 28, 45];
+        let input = (0..80)
+            .map(|n| (kicks.contains(&n), !(35..=40).contains(&n)))
+            .with_reset(1)
+            .clock_pos_edge(100);
+        let tb = uut.run(input)?.collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! Digital Delay Generator
+//!
+//! Test and measurement applications often need a set of outputs
+//! that fire a programmable number of clock cycles after a trigger.
+//! The [DelayGenerator] provides `CH` such channels.  Each channel
+//! has a [Channel] configuration, consisting of:
+//!
+//! - a `delay`, which is the number of clocks from the trigger to
+//!   the start of the output pulse.  A delay of zero means that the
+//!   output fires on the same clock as the trigger.
+//! - a `width`, which is the length of the output pulse in clocks.
+//!   A width of zero disables the channel.
+//! - a [Retrigger] policy, which determines what happens if a trigger
+//!   arrives while the channel is still counting or pulsing.  The
+//!   trigger is either ignored, or restarts the channel.
+//!
+//! A common `trigger` input starts the countdown on all channels.
+//! The configuration of a channel can be changed by a register
+//! write (for example from a CSR bus) on the `write` input.  Changes
+//! take effect on the next clock, including for a channel that
+//! is already running.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+           +-+DelayGenerator+--+
+  bool     |                   | [bool; CH]
+ +-------->| trigger       out +----------->
+ ?(b8,Ch)  |                   |
+ +-------->| write             |
+           +-------------------+
+")]
+//!
+//! Note that there is a combinatorial path from the `trigger`
+//! input to the output of any channel with a delay of zero.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::timing::delay_gen::{Channel, DelayGenerator, Retrigger};
+//!
+//! // Two channels, one firing immediately, and one 10 clocks later
+//! let uut = DelayGenerator::<2, U16>::new([
+//!     Channel { delay: bits(0), width: bits(1), retrigger: Retrigger::Ignore },
+//!     Channel { delay: bits(10), width: bits(4), retrigger: Retrigger::Restart },
+//! ]);
+//! ```
+use rhdl::prelude::*;
+
+use crate::core::dff;
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// What to do with a trigger that arrives while
+/// a channel is running
+pub enum Retrigger {
+    #[default]
+    /// Ignore the trigger
+    Ignore,
+    /// Restart the channel
+    Restart,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The configuration of a single channel
+pub struct Channel<N: BitWidth> {
+    /// The number of clocks from the trigger to the pulse
+    pub delay: Bits<N>,
+    /// The width of the pulse in clocks
+    pub width: Bits<N>,
+    /// The retrigger policy
+    pub retrigger: Retrigger,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The inputs to the [DelayGenerator]
+pub struct In<const CH: usize, N: BitWidth> {
+    /// Start all of the channels
+    pub trigger: bool,
+    /// Update the configuration of a channel
+    pub write: Option<(b8, Channel<N>)>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The delay generator core.  Here `CH` is the number of
+/// channels (at most 256), and `N` is the width of the
+/// delay and width counters.
+pub struct DelayGenerator<const CH: usize, N: BitWidth> {
+    config: dff::DFF<[Channel<N>; CH]>,
+    elapsed: dff::DFF<[Option<Bits<N>>; CH]>,
+}
+
+impl<const CH: usize, N: BitWidth> DelayGenerator<CH, N> {
+    /// Create a [DelayGenerator] with the given configuration
+    /// for each channel.  The configuration is restored on reset.
+    pub fn new(config: [Channel<N>; CH]) -> Self {
+        assert!(
+            CH <= 256,
+            "The delay generator supports at most 256 channels"
+        );
+        Self {
+            config: dff::DFF::new(config),
+            elapsed: dff::DFF::new([None; CH]),
+        }
+    }
+}
+
+impl<const CH: usize, N: BitWidth> SynchronousIO for DelayGenerator<CH, N> {
+    type I = In<CH, N>;
+    type O = [bool; CH];
+    type Kernel = delay_gen_kernel<CH, N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+#[allow(clippy::needless_range_loop)]
+pub fn delay_gen_kernel<const CH: usize, N: BitWidth>(
+    cr: ClockReset,
+    i: In<CH, N>,
+    q: Q<CH, N>,
+) -> ([bool; CH], D<CH, N>) {
+    let mut d = D::<CH, N> {
+        config: q.config,
+        elapsed: q.elapsed,
+    };
+    let mut o = [false; CH];
+    for c in 0..CH {
+        let config = q.config[c];
+        // Determine if the channel (re)starts on this clock
+        let mut elapsed = q.elapsed[c];
+        if i.trigger {
+            match q.elapsed[c] {
+                Some(_) => {
+                    if config.retrigger == Retrigger::Restart {
+                        elapsed = Some(bits(0));
+                    }
+                }
+                None => elapsed = Some(bits(0)),
+            }
+        }
+        d.elapsed[c] = None;
+        if let Some(t) = elapsed {
+            let started = t >= config.delay;
+            let offset = t - config.delay;
+            o[c] = started && offset < config.width;
+            // The channel is finished after the last clock of the pulse
+            let finished = started && (config.width == 0 || offset >= config.width - 1);
+            if !finished {
+                d.elapsed[c] = Some(t + 1);
+            }
+        }
+    }
+    if let Some((channel, config)) = i.write {
+        d.config[channel] = config;
+    }
+    if cr.reset.any() {
+        d.elapsed = [None; CH];
+        o = [false; CH];
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(delay: u128, width: u128, retrigger: Retrigger) -> Channel<U8> {
+        Channel {
+            delay: bits(delay),
+            width: bits(width),
+            retrigger,
+        }
+    }
+
+    fn test_config() -> [Channel<U8>; 4] {
+        [
+            channel(0, 1, Retrigger::Ignore),
+            channel(5, 3, Retrigger::Ignore),
+            channel(2, 6, Retrigger::Restart),
+            channel(0, 0, Retrigger::Restart),
+        ]
+    }
+
+    fn idle() -> In<4, U8> {
+        In {
+            trigger: false,
+            write: None,
+        }
+    }
+
+    fn trigger() -> In<4, U8> {
+        In {
+            trigger: true,
+            write: None,
+        }
+    }
+
+    fn run(uut: &DelayGenerator<4, U8>, input: Vec<In<4, U8>>) -> miette::Result<Vec<[bool; 4]>> {
+        Ok(uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    // The clocks on which a channel is high
+    fn firing(output: &[[bool; 4]], channel: usize) -> Vec<usize> {
+        output
+            .iter()
+            .enumerate()
+            .filter_map(|(n, o)| o[channel].then_some(n))
+            .collect()
+    }
+
+    // A software model of a channel, given the trigger times
+    fn model(config: Channel<U8>, triggers: &[usize], len: usize) -> Vec<usize> {
+        let delay = config.delay.raw() as usize;
+        let width = config.width.raw() as usize;
+        let mut start: Option<usize> = None;
+        let mut out = vec![];
+        for n in 0..len {
+            let running = start.is_some_and(|s| n < s + delay + width);
+            if triggers.contains(&n) && (!running || config.retrigger == Retrigger::Restart) {
+                start = Some(n);
+            }
+            if let Some(s) = start {
+                if n >= s + delay && n < s + delay + width {
+                    out.push(n);
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_single_trigger() -> miette::Result<()> {
+        let uut = DelayGenerator::new(test_config());
+        let mut input = vec![idle(); 30];
+        input[3] = trigger();
+        let output = run(&uut, input)?;
+        // Delay zero fires on the trigger cycle
+        assert_eq!(firing(&output, 0), [3]);
+        assert_eq!(firing(&output, 1), [8, 9, 10]);
+        assert_eq!(firing(&output, 2), [5, 6, 7, 8, 9, 10]);
+        // A zero width channel never fires
+        assert!(firing(&output, 3).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retrigger_policies() -> miette::Result<()> {
+        let uut = DelayGenerator::new(test_config());
+        let triggers = [2, 4, 9, 30, 31, 32];
+        let mut input = vec![idle(); 60];
+        for t in triggers {
+            input[t] = trigger();
+        }
+        let output = run(&uut, input)?;
+        for (ch, config) in test_config().into_iter().enumerate() {
+            assert_eq!(
+                firing(&output, ch),
+                model(config, &triggers, 60),
+                "Mismatch on channel {ch}"
+            );
+        }
+        // Channel 1 ignores the triggers at 4 and 9 (the last clock
+        // of its pulse), and those at 31 and 32
+        assert_eq!(firing(&output, 1), [7, 8, 9, 35, 36, 37]);
+        // Channel 2 restarts on each trigger, cutting short the pulse
+        assert_eq!(
+            firing(&output, 2),
+            [6, 7, 8, 11, 12, 13, 14, 15, 16, 34, 35, 36, 37, 38, 39]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_writes() -> miette::Result<()> {
+        let uut = DelayGenerator::new(test_config());
+        let mut input = vec![idle(); 40];
+        input[0].write = Some((b8(3), channel(4, 2, Retrigger::Ignore)));
+        input[1].write = Some((b8(0), channel(1, 2, Retrigger::Ignore)));
+        input[5] = trigger();
+        let output = run(&uut, input)?;
+        assert_eq!(firing(&output, 0), [6, 7]);
+        assert_eq!(firing(&output, 3), [9, 10]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delay_gen_hdl() -> miette::Result<()> {
+        let uut = DelayGenerator::new(test_config());
+        let mut input = vec![idle(); 60];
+        for t in [2, 4, 9, 30, 31, 32] {
+            input[t] = trigger();
+        }
+        input[20].write = Some((b8(1), channel(0, 4, Retrigger::Restart)));
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
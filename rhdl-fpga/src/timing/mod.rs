@@ -0,0 +1,2 @@
+//! Cores for generating precisely timed signals
+pub mod delay_gen;
@@ -0,0 +1,205 @@
+//! UART Echo
+//!
+//! Sends every byte received on a serial line back on another one.  The
+//! bytes received by a [UartRx] are written into a [SyncFIFO], and the
+//! [UartTx] takes them out of the FIFO as fast as it can send them.  The
+//! transmitter provides the flow control: a byte is only taken from the
+//! FIFO when the transmitter is ready for it.  So the FIFO absorbs any
+//! bursts that arrive faster than they can be sent (for example, when
+//! the far end runs at a slightly faster baud rate).  If it overflows,
+//! the byte is lost, and `overflow` is pulsed.
+//!
+//! The FIFO holds `2^N - 1` bytes.  Both lines use the same
+//! `clocks_per_bit`, which is provided when the core is constructed.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+UartEcho+-----------+
+  bool  |                      | bool
+ +----->| rx                tx +----->
+        |                      | bool
+        |             overflow +----->
+        |                      | bool
+        |          frame_error +----->
+        +----------------------+
+")]
+//!
+//!# Example
+//!
+//! An echo with a 15 byte FIFO, running at 115200 baud from a
+//! 100MHz clock.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::uart::echo::UartEcho;
+//!
+//! let uut = UartEcho::<U4>::new(868);
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    core::option::is_some,
+    fifo::synchronous::{self, SyncFIFO},
+};
+
+use super::{
+    rx::{self, UartRx},
+    tx::{self, UartTx},
+};
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [UartEcho]
+pub struct Out {
+    /// The serial line the bytes are echoed on
+    pub tx: bool,
+    /// Pulsed when a byte is lost because the FIFO is full
+    pub overflow: bool,
+    /// Pulsed when a frame is received without a stop bit
+    pub frame_error: bool,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The UART echo.  Here `N` is the number of address bits
+/// of the FIFO.
+pub struct UartEcho<N: BitWidth> {
+    rx: UartRx,
+    fifo: SyncFIFO<b8, N>,
+    tx: UartTx,
+}
+
+impl<N: BitWidth> UartEcho<N> {
+    /// Create an echo with each bit lasting `clocks_per_bit` clocks
+    pub fn new(clocks_per_bit: u16) -> Self {
+        Self {
+            rx: UartRx::new(clocks_per_bit),
+            fifo: SyncFIFO::default(),
+            tx: UartTx::new(clocks_per_bit),
+        }
+    }
+}
+
+impl<N: BitWidth> SynchronousIO for UartEcho<N> {
+    type I = bool;
+    type O = Out;
+    type Kernel = uart_echo_kernel<N>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn uart_echo_kernel<N: BitWidth>(_cr: ClockReset, i: bool, q: Q<N>) -> (Out, D<N>) {
+    // The head of the FIFO is offered to the transmitter, and is
+    // taken out of the FIFO when the transmitter accepts it
+    let rx: rx::Out = q.rx;
+    let tx: tx::Out = q.tx;
+    let fifo: synchronous::Out<b8> = q.fifo;
+    let d = D::<N> {
+        rx: i,
+        fifo: synchronous::In::<b8> {
+            data: rx.data,
+            next: tx.ready.raw && is_some::<b8>(fifo.data),
+        },
+        tx: fifo.data,
+    };
+    let o = Out {
+        tx: tx.line,
+        overflow: fifo.overflow,
+        frame_error: rx.frame_error,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The line for a sequence of bytes, sent back to back
+    fn line(bytes: &[u8], clocks_per_bit: usize) -> Vec<bool> {
+        bytes
+            .iter()
+            .flat_map(|byte| {
+                let data = (0..8).map(move |k| byte & (1 << k) != 0);
+                std::iter::once(false)
+                    .chain(data)
+                    .chain(std::iter::once(true))
+            })
+            .flat_map(|b| std::iter::repeat_n(b, clocks_per_bit))
+            .collect()
+    }
+
+    // Decode the line as 8N1 frames, sampled in the middle of each bit
+    fn decode(line: &[bool], clocks_per_bit: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut n = 1;
+        while n + 10 * clocks_per_bit < line.len() {
+            if line[n - 1] && !line[n] {
+                let center = n + clocks_per_bit / 2;
+                let bit = |k: usize| line[center + k * clocks_per_bit];
+                let byte = (1..=8).fold(0, |b, k| b | (bit(k) as u8) << (k - 1));
+                assert!(bit(9), "Missing stop bit after {n}");
+                bytes.push(byte);
+                n = center + 9 * clocks_per_bit;
+            } else {
+                n += 1;
+            }
+        }
+        bytes
+    }
+
+    fn run<N: BitWidth>(uut: &UartEcho<N>, rx: Vec<bool>) -> miette::Result<Vec<Out>> {
+        // Leave time for the FIFO to drain at the end
+        let rx = std::iter::repeat_n(true, 200)
+            .chain(rx)
+            .chain(std::iter::repeat_n(true, 10000));
+        Ok(uut
+            .run(rx.with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_bytes_are_echoed() -> miette::Result<()> {
+        let message = b"Hello, RHDL!";
+        let uut = UartEcho::<U4>::new(8);
+        let output = run(&uut, line(message, 8))?;
+        let tx = output.iter().map(|o| o.tx).collect::<Vec<_>>();
+        assert_eq!(decode(&tx, 8), message);
+        assert!(output.iter().all(|o| !o.overflow && !o.frame_error));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_sender_fills_the_fifo() -> miette::Result<()> {
+        // The far end is 2.5% fast, so bytes arrive faster than they
+        // can be echoed, and the FIFO takes up the slack
+        let message = (0..200).map(|n| (n * 3 + 1) as u8).collect::<Vec<_>>();
+        let uut = UartEcho::<U4>::new(40);
+        let output = run(&uut, line(&message, 39))?;
+        let tx = output.iter().map(|o| o.tx).collect::<Vec<_>>();
+        assert_eq!(decode(&tx, 40), message);
+        assert!(output.iter().all(|o| !o.overflow && !o.frame_error));
+        // A smaller FIFO cannot take up all of the slack
+        let uut = UartEcho::<U2>::new(40);
+        let output = run(&uut, line(&message, 39))?;
+        assert!(output.iter().any(|o| o.overflow));
+        Ok(())
+    }
+
+    #[test]
+    fn test_uart_echo_hdl() -> miette::Result<()> {
+        let uut = UartEcho::<U2>::new(3);
+        let rx = line(&[0x12, 0xC3, 0x7E], 3);
+        let input = std::iter::repeat_n(true, 10)
+            .chain(rx)
+            .chain(std::iter::repeat_n(true, 60));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+//! Serial (UART) cores
+pub mod echo;
+pub mod rx;
+pub mod tx;
@@ -0,0 +1,275 @@
+//! UART Receiver
+//!
+//! Receives bytes from a serial line, with the usual 8N1 framing: a
+//! start bit (low), the 8 data bits (LSB first), and a stop bit (high).
+//! The line idles high.  Each bit lasts `clocks_per_bit` clocks, which
+//! is provided when the core is constructed, and must match the
+//! transmitter (see [UartTx](super::tx::UartTx)).
+//!
+//! The receiver waits for the line to fall, and then samples it in the
+//! middle of each bit.  If the line is high again in the middle of the
+//! start bit, the fall was a glitch, and the receiver goes back to
+//! waiting.  Each byte received is presented on `data` for a single
+//! clock, after the middle of its stop bit.  If the stop bit is low,
+//! the byte is dropped, `frame_error` is pulsed instead, and the
+//! receiver waits for the line to return high before looking for the
+//! next start bit.
+//!
+//! The line is sampled directly, so it must be synchronous to the
+//! clock.  An external line should pass through a synchronizer first
+//! (the board fixtures in `rhdl-bsp` do this for their inputs).
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+UartRx+-----------+
+  bool  |                    | ?b8
+ +----->| line          data +----->
+        |                    | bool
+        |        frame_error +----->
+        +--------------------+
+")]
+//!
+//!# Example
+//!
+//! Receiving a byte, with 4 clocks per bit.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::uart::rx::UartRx;
+//!
+//! let uut = UartRx::new(4);
+//! // The start bit, 0x5A (LSB first), and the stop bit
+//! let frame = [0, 0, 1, 0, 1, 1, 0, 1, 0, 1];
+//! let line = [1, 1].iter().chain(frame.iter()).chain([1, 1].iter());
+//! let input = line.flat_map(|b| std::iter::repeat_n(*b == 1, 4));
+//! let data = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .filter_map(|t| t.value.2.data)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(data, [b8(0x5A)]);
+//!```
+use rhdl::prelude::*;
+
+use crate::core::{constant, dff};
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [UartRx]
+pub struct Out {
+    /// A byte received, for a single clock
+    pub data: Option<b8>,
+    /// Pulsed when a frame is received without a stop bit
+    pub frame_error: bool,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+#[doc(hidden)]
+pub enum State {
+    #[default]
+    Idle,
+    Start,
+    Data,
+    Stop,
+    Break,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The UART receiver core
+pub struct UartRx {
+    last_phase: constant::Constant<b16>,
+    half_phase: constant::Constant<b16>,
+    state: dff::DFF<State>,
+    phase: dff::DFF<b16>,
+    shift: dff::DFF<b8>,
+    count: dff::DFF<b3>,
+    data: dff::DFF<Option<b8>>,
+    frame_error: dff::DFF<bool>,
+}
+
+impl UartRx {
+    /// Create a receiver with each bit lasting `clocks_per_bit` clocks
+    pub fn new(clocks_per_bit: u16) -> Self {
+        assert!(clocks_per_bit >= 2, "A bit must last at least 2 clocks");
+        Self {
+            last_phase: constant::Constant::new(bits(clocks_per_bit as u128 - 1)),
+            half_phase: constant::Constant::new(bits(clocks_per_bit as u128 / 2 - 1)),
+            state: dff::DFF::default(),
+            phase: dff::DFF::default(),
+            shift: dff::DFF::default(),
+            count: dff::DFF::default(),
+            data: dff::DFF::new(None),
+            frame_error: dff::DFF::default(),
+        }
+    }
+}
+
+impl SynchronousIO for UartRx {
+    type I = bool;
+    type O = Out;
+    type Kernel = uart_rx_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn uart_rx_kernel(cr: ClockReset, i: bool, q: Q) -> (Out, D) {
+    let mut d = D::dont_care();
+    d.state = q.state;
+    d.shift = q.shift;
+    d.count = q.count;
+    d.data = None;
+    d.frame_error = false;
+    let tick = q.phase == q.last_phase;
+    d.phase = if tick { bits(0) } else { q.phase + 1 };
+    match q.state {
+        State::Idle => {
+            d.phase = bits(0);
+            if !i {
+                d.state = State::Start;
+            }
+        }
+        State::Start => {
+            // The middle of the start bit
+            if q.phase == q.half_phase {
+                d.phase = bits(0);
+                d.count = bits(0);
+                d.state = if i { State::Idle } else { State::Data };
+            }
+        }
+        State::Data => {
+            if tick {
+                let bit: b8 = if i { bits(0x80) } else { bits(0) };
+                d.shift = (q.shift >> 1) | bit;
+                d.count = q.count + 1;
+                if q.count == 7 {
+                    d.state = State::Stop;
+                }
+            }
+        }
+        State::Stop => {
+            if tick {
+                if i {
+                    d.data = Some(q.shift);
+                    d.state = State::Idle;
+                } else {
+                    d.frame_error = true;
+                    d.state = State::Break;
+                }
+            }
+        }
+        State::Break => {
+            if i {
+                d.state = State::Idle;
+            }
+        }
+    }
+    let o = Out {
+        data: q.data,
+        frame_error: q.frame_error,
+    };
+    if cr.reset.any() {
+        d.state = State::Idle;
+        d.phase = bits(0);
+        d.data = None;
+        d.frame_error = false;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The line for a sequence of frames, each given as a byte and
+    // whether the stop bit is present, with `gap` idle bit times
+    // before each frame
+    fn line(frames: &[(u8, bool)], clocks_per_bit: usize, gap: usize) -> Vec<bool> {
+        frames
+            .iter()
+            .flat_map(|(byte, stop)| {
+                let idle = std::iter::repeat_n(true, gap);
+                let data = (0..8).map(move |k| byte & (1 << k) != 0);
+                idle.chain(std::iter::once(false))
+                    .chain(data)
+                    .chain(std::iter::once(*stop))
+            })
+            .chain(std::iter::repeat_n(true, 4))
+            .flat_map(|b| std::iter::repeat_n(b, clocks_per_bit))
+            .collect()
+    }
+
+    fn run(uut: &UartRx, line: Vec<bool>) -> miette::Result<Vec<Out>> {
+        Ok(uut
+            .run(line.into_iter().with_reset(1).clock_pos_edge(100))?
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2)
+            .collect())
+    }
+
+    #[test]
+    fn test_bytes_are_received() -> miette::Result<()> {
+        let bytes = [0x00, 0xFF, 0x5A, 0x81, b'R', b'H', b'D', b'L'];
+        let frames = bytes.map(|b| (b, true));
+        for clocks_per_bit in [2, 3, 5, 16] {
+            let uut = UartRx::new(clocks_per_bit as u16);
+            for gap in [1, 3] {
+                let output = run(&uut, line(&frames, clocks_per_bit, gap))?;
+                let data = output.iter().filter_map(|o| o.data).collect::<Vec<_>>();
+                assert_eq!(data, bytes.map(|b| b8(b as u128)));
+                assert!(output.iter().all(|o| !o.frame_error));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_follows_middle_of_stop_bit() -> miette::Result<()> {
+        let output = run(&UartRx::new(8), line(&[(0xA5, true)], 8, 1))?;
+        // The start bit falls on clock 8, so the middle of the stop
+        // bit is clock 8 + 9 * 8 + 4, and the byte follows it
+        let n = output.iter().position(|o| o.data.is_some()).unwrap();
+        assert_eq!(n, 8 + 9 * 8 + 4 + 1);
+        assert_eq!(output[n].data, Some(b8(0xA5)));
+        assert_eq!(output.iter().filter(|o| o.data.is_some()).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_stop_bit_is_a_frame_error() -> miette::Result<()> {
+        let frames = [(0x12, true), (0x00, false), (0x34, true)];
+        let output = run(&UartRx::new(4), line(&frames, 4, 2))?;
+        let data = output.iter().filter_map(|o| o.data).collect::<Vec<_>>();
+        assert_eq!(data, [b8(0x12), b8(0x34)]);
+        assert_eq!(output.iter().filter(|o| o.frame_error).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_glitches_are_ignored() -> miette::Result<()> {
+        let mut line = line(&[(0x3C, true)], 8, 2);
+        // A low pulse shorter than half a bit on the idle line
+        line[3] = false;
+        line[4] = false;
+        let output = run(&UartRx::new(8), line)?;
+        let data = output.iter().filter_map(|o| o.data).collect::<Vec<_>>();
+        assert_eq!(data, [b8(0x3C)]);
+        assert!(output.iter().all(|o| !o.frame_error));
+        Ok(())
+    }
+
+    #[test]
+    fn test_uart_rx_hdl() -> miette::Result<()> {
+        let uut = UartRx::new(3);
+        let frames = [(0x12, true), (0x00, false), (0xC3, true)];
+        let input = line(&frames, 3, 2);
+        let tb = uut
+            .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
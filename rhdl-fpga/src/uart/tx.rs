@@ -0,0 +1,228 @@
+//! UART Transmitter
+//!
+//! Sends bytes on a serial line, with the usual 8N1 framing: a start
+//! bit (low), the 8 data bits (LSB first), and a stop bit (high).  The
+//! line idles high.  Each bit lasts `clocks_per_bit` clocks, which is
+//! provided when the core is constructed (for example, 868 for 115200
+//! baud with a 100MHz clock).
+//!
+//! The [UartTx] takes a stream of bytes, with a ready/valid handshake
+//! (see [crate::stream]).  It is ready when the line is idle, and a byte
+//! that is accepted starts on the line on the next clock.  The stop bit
+//! lasts for a full bit time before the next byte can start, so a
+//! stream of bytes is sent back to back, at the full rate of the line.
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+        +-+UartTx+-------+
+  ?b8   |                | bool
+ +----->| data      line +----->
+ R<b8>  |                |
+ <------+ ready          |
+        +----------------+
+")]
+//!
+//!# Example
+//!
+//! Sending a byte, with 4 clocks per bit.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::uart::tx::UartTx;
+//!
+//! let uut = UartTx::new(4);
+//! let input = (0..50).map(|n| (n == 0).then_some(b8(0x5A)));
+//! let line = uut
+//!     .run(input.with_reset(1).clock_pos_edge(100))
+//!     .unwrap()
+//!     .synchronous_sample()
+//!     .map(|t| t.value.2.line)
+//!     .collect::<Vec<_>>();
+//! // The start bit, and then 0x5A, LSB first, and the stop bit
+//! let bits = line.iter().skip(2).step_by(4).take(10).collect::<Vec<_>>();
+//! assert_eq!(
+//!     bits,
+//!     [false, false, true, false, true, true, false, true, false, true].iter().collect::<Vec<_>>()
+//! );
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    core::{constant, dff},
+    stream::{ready, Ready},
+};
+
+#[derive(PartialEq, Debug, Digital)]
+/// Outputs from the [UartTx]
+pub struct Out {
+    /// The serial line
+    pub line: bool,
+    /// The ready signal to the byte stream
+    pub ready: Ready<b8>,
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The UART transmitter core
+pub struct UartTx {
+    last_phase: constant::Constant<b16>,
+    phase: dff::DFF<b16>,
+    shift: dff::DFF<b9>,
+    remaining: dff::DFF<b4>,
+    line: dff::DFF<bool>,
+}
+
+impl UartTx {
+    /// Create a transmitter with each bit lasting `clocks_per_bit` clocks
+    pub fn new(clocks_per_bit: u16) -> Self {
+        assert!(clocks_per_bit > 0, "A bit must last at least 1 clock");
+        Self {
+            last_phase: constant::Constant::new(bits(clocks_per_bit as u128 - 1)),
+            phase: dff::DFF::default(),
+            shift: dff::DFF::default(),
+            remaining: dff::DFF::default(),
+            line: dff::DFF::new(true),
+        }
+    }
+}
+
+impl SynchronousIO for UartTx {
+    type I = Option<b8>;
+    type O = Out;
+    type Kernel = uart_tx_kernel;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn uart_tx_kernel(cr: ClockReset, i: Option<b8>, q: Q) -> (Out, D) {
+    let mut d = D::dont_care();
+    d.shift = q.shift;
+    d.remaining = q.remaining;
+    d.line = q.line;
+    let tick = q.phase == q.last_phase;
+    d.phase = if tick { bits(0) } else { q.phase + 1 };
+    // The line is free once the last bit time (of the stop bit) is over
+    let idle = q.remaining == 0 || (q.remaining == 1 && tick);
+    if idle {
+        d.phase = bits(0);
+        d.remaining = bits(0);
+        if let Some(byte) = i {
+            // The start bit goes out now, and the shift register
+            // holds the data bits and the stop bit
+            let data: b9 = byte.resize();
+            d.line = false;
+            d.shift = data | bits(0x100);
+            d.remaining = bits(10);
+        }
+    } else if tick {
+        d.line = q.shift & 1 != 0;
+        d.shift = q.shift >> 1;
+        d.remaining = q.remaining - 1;
+    }
+    let o = Out {
+        line: q.line,
+        ready: ready::<b8>(idle),
+    };
+    if cr.reset.any() {
+        d.phase = bits(0);
+        d.shift = bits(0);
+        d.remaining = bits(0);
+        d.line = true;
+    }
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rhdl::core::sim::ResetOrData;
+
+    use super::*;
+
+    // Offer the bytes to the transmitter, in order, as fast as it takes
+    // them, and return the line on each clock after the reset
+    fn run(uut: &UartTx, bytes: &[u8], len: usize) -> miette::Result<Vec<bool>> {
+        let mut next = 0;
+        let mut clocks = 0;
+        Ok(uut
+            .run_fn(
+                |out: Out| {
+                    clocks += 1;
+                    if clocks == 1 {
+                        return Some(ResetOrData::Reset);
+                    }
+                    if clocks > len {
+                        return None;
+                    }
+                    let offer = bytes.get(next).map(|b| b8(*b as u128));
+                    if out.ready.raw && offer.is_some() {
+                        next += 1;
+                    }
+                    Some(ResetOrData::Data(offer))
+                },
+                100,
+            )
+            .synchronous_sample()
+            .skip(1)
+            .map(|t| t.value.2.line)
+            .collect())
+    }
+
+    // Decode the line as 8N1 frames, sampled in the middle of each bit
+    fn decode(line: &[bool], clocks_per_bit: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut n = 1;
+        while n < line.len() {
+            if line[n - 1] && !line[n] {
+                let center = n + clocks_per_bit / 2;
+                let bit = |k: usize| line[center + k * clocks_per_bit];
+                assert!(!bit(0), "Start bit at {n} is too short");
+                let byte = (1..=8).fold(0, |b, k| b | (bit(k) as u8) << (k - 1));
+                assert!(bit(9), "Missing stop bit after {n}");
+                bytes.push(byte);
+                n = center + 9 * clocks_per_bit;
+            } else {
+                n += 1;
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_bytes_are_framed() -> miette::Result<()> {
+        let bytes = [0x00, 0xFF, 0x5A, 0x81, b'R', b'H', b'D', b'L'];
+        for clocks_per_bit in [1, 2, 5, 16] {
+            let uut = UartTx::new(clocks_per_bit as u16);
+            let line = run(&uut, &bytes, 12 * clocks_per_bit * bytes.len())?;
+            assert!(line[0], "The line idles high");
+            assert_eq!(decode(&line, clocks_per_bit), bytes);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_back_to_back_timing() -> miette::Result<()> {
+        let uut = UartTx::new(3);
+        let line = run(&uut, &[0x00, 0x00], 100)?;
+        // Each byte is 9 bit times low, and 1 bit time high, with the
+        // second start bit right after the first stop bit
+        let lows = line.iter().position(|b| !b).unwrap();
+        assert!(line[lows..lows + 27].iter().all(|b| !b));
+        assert!(line[lows + 27..lows + 30].iter().all(|b| *b));
+        assert!(line[lows + 30..lows + 57].iter().all(|b| !b));
+        assert!(line[lows + 57..].iter().all(|b| *b));
+        Ok(())
+    }
+
+    #[test]
+    fn test_uart_tx_hdl() -> miette::Result<()> {
+        let uut = UartTx::new(3);
+        let input = (0..200).map(|n| (n % 40 == 1).then_some(b8(n as u128 + 7)));
+        let tb = uut
+            .run(input.with_reset(1).clock_pos_edge(100))?
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+#![warn(missing_docs)]
+//! Wishbone bus cores
+//!
+//! The cores in this module speak the Wishbone B4 protocol
+//! (in its non-pipelined form), with a 32-bit data bus and
+//! a byte select line per byte lane.  Addresses are word addresses,
+//! so that the width of the address bus is a type parameter on
+//! each core.
+//!
+//! Registered feedback bus cycles are supported through the
+//! cycle type identifier (`cti`) and burst type extension (`bte`)
+//! signals.  These allow a slave with a registered `ack` to
+//! complete one beat per clock during a burst.
+pub mod ram_slave;
+pub mod types;
+
+pub use ram_slave::RamSlave;
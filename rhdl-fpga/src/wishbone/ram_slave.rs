@@ -0,0 +1,586 @@
+//! Wishbone Block RAM Slave
+//!
+//!# Purpose
+//!
+//! The [RamSlave] exposes a block of RAM (with `2^A` words of
+//! 32 bits) on a Wishbone bus.  It is intended primarily as a test
+//! target for masters and caches, and so it can be configured to insert
+//! wait states before each beat is acknowledged.  The wait states are
+//! either a fixed number per beat, or a pseudo-random number drawn from
+//! a [XorShift] generator (see [WaitStates]).
+//!
+//! The slave supports:
+//!   - Classic cycles, where each beat is acknowledged separately.
+//!   - Registered feedback bursts (`cti` of [INCREMENTING](cycle_types::INCREMENTING)),
+//!     with linear or wrapping addresses, as given by the `bte` signal.
+//!     Without wait states, a burst completes one beat per clock.
+//!   - Byte selects.  Only the byte lanes selected by `sel` are written.
+//!
+//! Each accepted beat is also reported on an access-log output, which
+//! can be used for scoreboarding in tests.
+//!
+//!# Connections
+//!
+//! Here is the schematic symbol
+#![doc = badascii_doc::badascii_formal!("
+      +--+RamSlave+------+
+ bool |                  | bool
++---->| cyc      bus.ack +---->
+ bool |                  | b32
++---->| stb      bus.dat +---->
+ bool |                  | ?Access
++---->| we           log +---->
+ bA   |                  |
++---->| adr              |
+ b32  |                  |
++---->| dat              |
+ b4   |                  |
++---->| sel              |
+ b3   |                  |
++---->| cti              |
+ b2   |                  |
++---->| bte              |
+      +------------------+
+")]
+//!
+//!# Timing
+//!
+//! The `ack` is registered.  For a classic cycle, the slave sees the
+//! strobe in one clock, and acknowledges the beat in the next, so that
+//! each beat takes `2 + N` clocks, where `N` is the number of wait states.
+//! In a burst, the slave starts the next beat in the same clock as it
+//! acknowledges the current one, reading ahead at the next address.
+//! Each beat after the first then takes `1 + N` clocks.
+#![doc = badascii_doc::badascii!("
+        +--+  +--+  +--+  +--+  +--+  +--+
+clk   +-+  +--+  +--+  +--+  +--+  +--+  +-
+        :     :     :     :     :     :
+       ++-----+-----+-----+-----+     :
+adr    +|  A0 |  A0 |  A1 |  A2 |     :
+       ++-----+-----+-----+-----+     :
+       ++-----+-----+-----+-----+     :
+cti    +| INC | INC | INC | END |     :
+       ++-----+-----+-----+-----+     :
+              +-----------------+     :
+ack    +------+                 +------
+")]
+//!
+//! The master must keep `stb` asserted for the whole of a burst.  If
+//! the cycle is abandoned (`cyc` or `stb` are deasserted), any beat in
+//! progress is dropped.
+//!
+//! The address is a word address.  Wrapping bursts wrap on 4, 8 or 16
+//! word boundaries, and so the address must be at least 4 bits wide.
+//!
+//!# Internals
+//!
+//! The RAM is split into 4 byte lanes, each of which is a
+//! [SyncBRAM] of `b8`.  The read address presented to the RAMs is the
+//! current address, except in the clock in which a burst beat is
+//! acknowledged.  In that clock, the next address in the burst is read
+//! instead, so that the data is ready when the master moves on.
+//!
+//!# Example
+//!
+//! A slave with 256 words that adds 2 wait states to each beat.
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl_fpga::wishbone::{ram_slave::WaitStates, RamSlave};
+//!
+//! let uut = RamSlave::<U8>::new((0..256).map(|n| (bits(n), bits(n << 8))))
+//!     .with_waits(WaitStates::Fixed(2));
+//!```
+use rhdl::prelude::*;
+
+use crate::{
+    core::{constant, dff, ram::synchronous::SyncBRAM},
+    rng::xorshift::XorShift,
+};
+
+use super::types::{burst_types, cycle_types, FromSlave, ToSlave, WbData, WbSel};
+
+/// The wait states inserted before each beat is acknowledged
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WaitStates {
+    /// The same number of wait states for every beat
+    Fixed(u8),
+    /// A pseudo-random number of wait states for each beat.
+    /// The number is the output of the random number generator
+    /// masked with the given value (so that `Random(7)` gives
+    /// between 0 and 7 wait states).
+    Random(u8),
+}
+
+#[derive(Clone, Debug, Synchronous, SynchronousDQ)]
+/// The Wishbone RAM slave
+///
+///   `A` is the number of bits in the (word) address
+pub struct RamSlave<A: BitWidth> {
+    ram: [SyncBRAM<b8, A>; 4],
+    rng: XorShift,
+    fixed: constant::Constant<b8>,
+    mask: constant::Constant<b8>,
+    ack: dff::DFF<bool>,
+    busy: dff::DFF<bool>,
+    wait: dff::DFF<b8>,
+}
+
+impl<A: BitWidth> Default for RamSlave<A> {
+    fn default() -> Self {
+        Self::new(std::iter::empty())
+    }
+}
+
+impl<A: BitWidth> RamSlave<A> {
+    /// Create a new [RamSlave] with the provided initial contents,
+    /// and no wait states.
+    pub fn new(initial: impl IntoIterator<Item = (Bits<A>, WbData)>) -> Self {
+        let initial = initial.into_iter().collect::<Vec<_>>();
+        Self {
+            ram: array_init::array_init(|lane| {
+                SyncBRAM::new(
+                    initial
+                        .iter()
+                        .map(|(adr, dat)| (*adr, (*dat >> (8 * lane as u128)).resize())),
+                )
+            }),
+            rng: XorShift::default(),
+            fixed: constant::Constant::new(b8(0)),
+            mask: constant::Constant::new(b8(0)),
+            ack: dff::DFF::new(false),
+            busy: dff::DFF::new(false),
+            wait: dff::DFF::new(b8(0)),
+        }
+    }
+    /// Use the given wait states for each beat.
+    pub fn with_waits(self, waits: WaitStates) -> Self {
+        let (fixed, mask) = match waits {
+            WaitStates::Fixed(n) => (n, 0),
+            WaitStates::Random(mask) => (0, mask),
+        };
+        Self {
+            fixed: constant::Constant::new(b8(fixed as u128)),
+            mask: constant::Constant::new(b8(mask as u128)),
+            ..self
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// An entry in the access log
+pub struct Access<A: BitWidth> {
+    /// The (word) address of the beat
+    pub adr: Bits<A>,
+    /// The beat was a write
+    pub we: bool,
+    /// The byte selects of the beat
+    pub sel: WbSel,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+/// The outputs of the [RamSlave]
+pub struct Out<A: BitWidth> {
+    /// The bus signals to the master
+    pub bus: FromSlave,
+    /// The beat accepted in this cycle (if any)
+    pub log: Option<Access<A>>,
+}
+
+impl<A: BitWidth> SynchronousIO for RamSlave<A> {
+    type I = ToSlave<A>;
+    type O = Out<A>;
+    type Kernel = ram_slave_kernel<A>;
+}
+
+#[kernel]
+#[doc(hidden)]
+pub fn ram_slave_kernel<A: BitWidth>(_cr: ClockReset, i: ToSlave<A>, q: Q<A>) -> (Out<A>, D<A>) {
+    let mut d = D::<A>::dont_care();
+    let active = i.cyc && i.stb;
+    // The beat presented in this cycle is complete
+    let ack = q.ack && active;
+    let burst = i.cti == cycle_types::INCREMENTING;
+    // A new beat starts if the bus was idle, or if a burst beat is complete
+    let start = active && if q.ack { burst } else { !q.busy };
+    let remaining = if start {
+        q.fixed + (q.rng.resize::<U8>() & q.mask)
+    } else {
+        q.wait
+    };
+    let fire = active && (start || q.busy) && remaining == 0;
+    d.rng = start;
+    d.ack = fire;
+    d.busy = active && (start || q.busy) && !fire;
+    d.wait = q.wait;
+    if remaining != 0 {
+        d.wait = remaining - 1;
+    }
+    // The address of the next beat in a burst
+    let next = i.adr + 1;
+    let mut wrap = !bits(0);
+    if i.bte == burst_types::WRAP4 {
+        wrap = bits(3);
+    } else if i.bte == burst_types::WRAP8 {
+        wrap = bits(7);
+    } else if i.bte == burst_types::WRAP16 {
+        wrap = bits(15);
+    }
+    let next = (i.adr & !wrap) | (next & wrap);
+    let read_addr = if q.ack { next } else { i.adr };
+    // Each lane takes the next byte of the data and the next byte select
+    let mut wdat = i.dat;
+    let mut sel = i.sel;
+    for lane in 0..4 {
+        d.ram[lane].read_addr = read_addr;
+        d.ram[lane].write.addr = i.adr;
+        d.ram[lane].write.value = wdat.resize::<U8>();
+        d.ram[lane].write.enable = ack && i.we && (sel & 1) != 0;
+        wdat >>= 8;
+        sel >>= 1;
+    }
+    let mut dat = b32(0);
+    for lane in 0..4 {
+        dat = (dat << 8) | q.ram[3 - lane].resize::<U32>();
+    }
+    let log = if ack {
+        Some(Access::<A> {
+            adr: i.adr,
+            we: i.we,
+            sel: i.sel,
+        })
+    } else {
+        None
+    };
+    let o = Out::<A> {
+        bus: FromSlave { ack, dat },
+        log,
+    };
+    (o, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use rhdl::core::sim::ResetOrData;
+
+    use super::*;
+    use crate::tb::{
+        sequencer::{SequenceError, TbSequencer},
+        wishbone::WishboneMaster,
+    };
+
+    // A beat of a bus cycle
+    fn beat(adr: u128, we: bool, dat: u128, sel: u128, cti: b3, bte: b2) -> ToSlave<U8> {
+        ToSlave {
+            cyc: true,
+            stb: true,
+            we,
+            adr: bits(adr),
+            dat: bits(dat),
+            sel: bits(sel),
+            cti,
+            bte,
+        }
+    }
+
+    fn write(adr: u128, dat: u128, sel: u128) -> Vec<ToSlave<U8>> {
+        vec![beat(
+            adr,
+            true,
+            dat,
+            sel,
+            cycle_types::CLASSIC,
+            burst_types::LINEAR,
+        )]
+    }
+
+    fn read(adr: u128) -> Vec<ToSlave<U8>> {
+        vec![beat(
+            adr,
+            false,
+            0,
+            0xF,
+            cycle_types::CLASSIC,
+            burst_types::LINEAR,
+        )]
+    }
+
+    // An incrementing burst, writing the data if provided, and reading `len` words otherwise
+    fn burst(start: u128, len: usize, bte: b2, data: Option<&[u128]>) -> Vec<ToSlave<U8>> {
+        let wrap = match bte {
+            burst_types::WRAP4 => 3,
+            burst_types::WRAP8 => 7,
+            burst_types::WRAP16 => 15,
+            _ => 0xFF,
+        };
+        (0..len)
+            .map(|n| {
+                let adr = (start & !wrap) | ((start + n as u128) & wrap);
+                let cti = if n + 1 == len {
+                    cycle_types::END_OF_BURST
+                } else {
+                    cycle_types::INCREMENTING
+                };
+                match data {
+                    Some(data) => beat(adr, true, data[n], 0xF, cti, bte),
+                    None => beat(adr, false, 0, 0xF, cti, bte),
+                }
+            })
+            .collect()
+    }
+
+    fn pattern(n: u128) -> u128 {
+        (n * 0x0101_0101) ^ 0xA5C3_0000
+    }
+
+    fn ram() -> RamSlave<U8> {
+        RamSlave::new((0..256).map(|n| (bits(n), bits(pattern(n)))))
+    }
+
+    struct Results {
+        // The data read, in order
+        reads: Vec<u128>,
+        // The number of clocks taken by each bus cycle
+        clocks: Vec<usize>,
+        // The access log
+        log: Vec<Access<U8>>,
+    }
+
+    // A simple master that runs each of the bus cycles in turn,
+    // with an idle clock between them.  The ack is registered, so
+    // the output handed to the closure is the ack for the beat
+    // that it presents.
+    fn run_cycles(uut: &RamSlave<U8>, cycles: &[Vec<ToSlave<U8>>]) -> miette::Result<Results> {
+        let mut need_reset = true;
+        let mut cycle = 0;
+        let mut index = 0;
+        let mut done = false;
+        let mut clocks = vec![0; cycles.len()];
+        let mut reads = vec![];
+        let samples = uut
+            .run_fn(
+                |o| {
+                    if need_reset {
+                        need_reset = false;
+                        return Some(ResetOrData::Reset);
+                    }
+                    if done {
+                        done = false;
+                        index += 1;
+                        if index == cycles[cycle].len() {
+                            index = 0;
+                            cycle += 1;
+                            return Some(ResetOrData::Data(ToSlave::default()));
+                        }
+                    }
+                    if cycle == cycles.len() {
+                        return None;
+                    }
+                    let beat = cycles[cycle][index];
+                    clocks[cycle] += 1;
+                    if o.bus.ack {
+                        done = true;
+                        if !beat.we {
+                            reads.push(o.bus.dat.raw());
+                        }
+                    }
+                    Some(ResetOrData::Data(beat))
+                },
+                100,
+            )
+            .synchronous_sample()
+            .collect::<Vec<_>>();
+        let log = samples.iter().filter_map(|t| t.value.2.log).collect();
+        Ok(Results { reads, clocks, log })
+    }
+
+    #[test]
+    fn test_classic_write_read() -> miette::Result<()> {
+        let uut = ram();
+        let results = run_cycles(
+            &uut,
+            &[
+                write(5, 0xAABB_CCDD, 0b1111),
+                read(5),
+                write(5, 0x0000_1100, 0b0010),
+                read(5),
+                read(6),
+            ],
+        )?;
+        assert_eq!(results.reads, [0xAABB_CCDD, 0xAABB_11DD, pattern(6)]);
+        assert_eq!(results.clocks, [2; 5]);
+        let sel = results.log.iter().map(|a| a.sel.raw()).collect::<Vec<_>>();
+        assert_eq!(sel, [0b1111, 0b1111, 0b0010, 0b1111, 0b1111]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_classic_write_read_sequence() -> miette::Result<()> {
+        let uut = ram().with_waits(WaitStates::Fixed(2));
+        let bus = WishboneMaster::new("bus", |i: &mut ToSlave<U8>| i, |o: &Out<U8>| o.bus);
+        let mut seq = TbSequencer::new(&uut).with_driver(bus);
+        seq.run([
+            bus.write(5, 0xAABB_CCDD),
+            bus.read_expect(5, 0xAABB_CCDD),
+            bus.write_sel(5, 0x0000_1100, 0b0010),
+            bus.read_expect(5, 0xAABB_11DD),
+            bus.read_expect(6, pattern(6) as u32),
+            bus.poll(7, 0xFF, pattern(7) as u32 & 0xFF),
+        ])?;
+        // A bad read names the step, and the cycle of the ack.  Each
+        // bus cycle takes the beat, two waits, the ack and an idle clock
+        let err = seq
+            .run([bus.write(9, 1), bus.read_expect(9, 2)])
+            .unwrap_err();
+        let SequenceError::Failed {
+            step,
+            cycle,
+            reason,
+            ..
+        } = err
+        else {
+            panic!("Expected a failed step, got {err:?}");
+        };
+        assert_eq!(step, 1);
+        assert_eq!(cycle, 1 + 6 * 5 + 5 + 3);
+        assert_eq!(reason, "read 0x00000001, expected 0x00000002");
+        Ok(())
+    }
+
+    #[test]
+    fn test_burst_no_waits() -> miette::Result<()> {
+        let uut = ram();
+        let data = (0..8).map(|n| 0xDEAD_0000 | n).collect::<Vec<_>>();
+        let results = run_cycles(
+            &uut,
+            &[
+                burst(16, 8, burst_types::LINEAR, Some(&data)),
+                burst(16, 8, burst_types::LINEAR, None),
+                burst(28, 8, burst_types::LINEAR, None),
+            ],
+        )?;
+        assert_eq!(results.reads[0..8], data);
+        assert_eq!(
+            results.reads[8..],
+            (28..36).map(pattern).collect::<Vec<_>>()
+        );
+        // One clock for the first beat to start, and then one per beat
+        assert_eq!(results.clocks, [9, 9, 9]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrapping_burst() -> miette::Result<()> {
+        let uut = ram();
+        let results = run_cycles(
+            &uut,
+            &[
+                burst(6, 4, burst_types::WRAP4, None),
+                burst(13, 8, burst_types::WRAP8, None),
+                burst(30, 16, burst_types::WRAP16, None),
+            ],
+        )?;
+        let expected = [6, 7, 4, 5]
+            .into_iter()
+            .chain([13, 14, 15, 8, 9, 10, 11, 12])
+            .chain((30..32).chain(16..30))
+            .map(pattern)
+            .collect::<Vec<_>>();
+        assert_eq!(results.reads, expected);
+        assert_eq!(results.clocks, [5, 9, 17]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_waits() -> miette::Result<()> {
+        let data = (0..8).map(|n| 0x1234_0000 | n).collect::<Vec<_>>();
+        for waits in [1, 2, 5] {
+            let uut = ram().with_waits(WaitStates::Fixed(waits as u8));
+            let results = run_cycles(
+                &uut,
+                &[
+                    read(3),
+                    write(3, 0x5555_AAAA, 0b1001),
+                    read(3),
+                    burst(64, 8, burst_types::LINEAR, Some(&data)),
+                    burst(64, 8, burst_types::LINEAR, None),
+                ],
+            )?;
+            assert_eq!(results.reads[0], pattern(3));
+            assert_eq!(results.reads[1], (pattern(3) & 0x00FF_FF00) | 0x5500_00AA);
+            assert_eq!(results.reads[2..], data);
+            let burst = 8 * (waits + 1) + 1;
+            assert_eq!(
+                results.clocks,
+                [waits + 2, waits + 2, waits + 2, burst, burst]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_waits() -> miette::Result<()> {
+        let uut = ram().with_waits(WaitStates::Random(7));
+        let data = (0..16).map(|n| 0xF00D_0000 | (n * 3)).collect::<Vec<_>>();
+        let mut cycles = vec![burst(128, 16, burst_types::LINEAR, Some(&data))];
+        cycles.extend((0..16).map(|n| read(128 + n)));
+        cycles.push(burst(136, 8, burst_types::WRAP16, None));
+        let results = run_cycles(&uut, &cycles)?;
+        assert_eq!(results.reads[0..16], data);
+        assert_eq!(results.reads[16..24], data[8..16]);
+        // Every beat is logged once, in order
+        let beats = cycles.iter().flatten().collect::<Vec<_>>();
+        assert_eq!(results.log.len(), beats.len());
+        for (access, beat) in results.log.iter().zip(beats) {
+            assert_eq!(access.adr, beat.adr);
+            assert_eq!(access.we, beat.we);
+        }
+        // The waits are random, but there are at most 7 per beat
+        assert!(results.clocks[0] >= 17 && results.clocks[0] <= 16 * 8 + 1);
+        assert!(results.clocks[1..17].iter().all(|c| (2..=9).contains(c)));
+        assert!(results.clocks[1..17]
+            .iter()
+            .any(|c| *c != results.clocks[1]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ram_slave_hdl() -> miette::Result<()> {
+        let uut = ram().with_waits(WaitStates::Random(3));
+        let mut cycles = vec![burst(
+            40,
+            8,
+            burst_types::WRAP4,
+            Some(&[1, 2, 3, 4, 5, 6, 7, 8]),
+        )];
+        cycles.extend((0..8).map(|n| write(n, 0x0102_0304 * n, n)));
+        cycles.extend((0..8).map(read));
+        cycles.push(burst(38, 8, burst_types::LINEAR, None));
+        let mut need_reset = true;
+        let mut beats = cycles.concat().into_iter();
+        let mut beat = beats.next();
+        let mut done = false;
+        let tb = uut
+            .run_fn(
+                |o| {
+                    if need_reset {
+                        need_reset = false;
+                        return Some(ResetOrData::Reset);
+                    }
+                    if done {
+                        done = false;
+                        beat = beats.next();
+                    }
+                    done = o.bus.ack;
+                    beat.map(ResetOrData::Data)
+                },
+                100,
+            )
+            .collect::<SynchronousTestBench<_, _>>();
+        let tm = tb.rtl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        let tm = tb.ntl(&uut, &TestBenchOptions::default())?;
+        tm.run_iverilog()?;
+        Ok(())
+    }
+}
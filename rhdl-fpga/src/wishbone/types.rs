@@ -0,0 +1,82 @@
+//! The Wishbone bus types
+//!
+//! As with the AXI types, we use raw bit types for the bus
+//! signals (rather than `enum`s), since these signals are likely
+//! to be connected to cores that are not implemented in `rhdl`.
+use rhdl::prelude::*;
+
+/// Wishbone Data type
+///
+/// We implement the 32-bit data bus width.
+pub type WbData = Bits<U32>;
+/// Wishbone Byte Select type
+///
+/// One bit per byte lane of [WbData].  Bit `n` selects
+/// bits `8n..8n+8` of the data.
+pub type WbSel = Bits<U4>;
+/// The cycle type identifier (`CTI_O`) from the specification
+pub type CycleType = Bits<U3>;
+/// The burst type extension (`BTE_O`) from the specification
+pub type BurstType = Bits<U2>;
+
+/// This module [cycle_types] includes the values of
+/// [CycleType] defined in the specification
+pub mod cycle_types {
+    use rhdl::prelude::*;
+
+    /// A classic cycle - each beat is acknowledged separately
+    pub const CLASSIC: b3 = bits(0);
+    /// A constant address burst
+    pub const CONSTANT: b3 = bits(1);
+    /// An incrementing burst - the next beat is at the next address
+    pub const INCREMENTING: b3 = bits(2);
+    /// The last beat of a burst
+    pub const END_OF_BURST: b3 = bits(7);
+}
+
+/// This module [burst_types] includes the values of
+/// [BurstType] defined in the specification
+pub mod burst_types {
+    use rhdl::prelude::*;
+
+    /// Linear (non-wrapping) bursts
+    pub const LINEAR: b2 = bits(0);
+    /// Bursts that wrap on a 4 beat boundary
+    pub const WRAP4: b2 = bits(1);
+    /// Bursts that wrap on an 8 beat boundary
+    pub const WRAP8: b2 = bits(2);
+    /// Bursts that wrap on a 16 beat boundary
+    pub const WRAP16: b2 = bits(3);
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The signals driven by the master to the slave
+///
+/// The address is a word address, `A` bits wide.
+pub struct ToSlave<A: BitWidth> {
+    /// A bus cycle is in progress
+    pub cyc: bool,
+    /// The master is presenting a beat
+    pub stb: bool,
+    /// The beat is a write
+    pub we: bool,
+    /// The (word) address of the beat
+    pub adr: Bits<A>,
+    /// The data to write
+    pub dat: WbData,
+    /// The byte lanes to write (or that are valid on read)
+    pub sel: WbSel,
+    /// The cycle type identifier
+    pub cti: CycleType,
+    /// The burst type extension
+    pub bte: BurstType,
+}
+
+#[derive(PartialEq, Debug, Digital, Default)]
+/// The signals driven by the slave to the master
+pub struct FromSlave {
+    /// The current beat is complete
+    pub ack: bool,
+    /// The data read (valid when `ack` is asserted on a read)
+    pub dat: WbData,
+}
@@ -0,0 +1,202 @@
+use rhdl::prelude::*;
+use rhdl_fpga::{
+    boot::{
+        sequencer::{self, Sequencer, Step},
+        uart_loader::{self, UartLoader},
+    },
+    core::dff,
+    crc::crc32::crc32,
+    wishbone::RamSlave,
+};
+
+const DWELL: u128 = 12;
+
+// A boot loader that fills a RAM over the serial port, and a sequencer
+// that waits for the load, and then (after a delay) releases the reset
+// of the fabric, which is a counter that runs once out of reset
+#[derive(Clone, Synchronous, SynchronousDQ)]
+pub struct BootSystem {
+    loader: UartLoader<U8>,
+    ram: RamSlave<U8>,
+    seq: Sequencer<2>,
+    count: dff::DFF<b8>,
+}
+
+impl Default for BootSystem {
+    fn default() -> Self {
+        Self {
+            loader: UartLoader::default(),
+            ram: RamSlave::default(),
+            seq: Sequencer::new([
+                Step {
+                    dwell: bits(0),
+                    timeout: None,
+                },
+                Step {
+                    dwell: bits(DWELL),
+                    timeout: None,
+                },
+            ]),
+            count: dff::DFF::default(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+pub struct Out {
+    pub load_ok: bool,
+    pub busy: bool,
+    pub fabric_reset: bool,
+    pub count: b8,
+}
+
+impl SynchronousIO for BootSystem {
+    type I = Option<b8>;
+    type O = Out;
+    type Kernel = boot_system_kernel;
+}
+
+#[kernel]
+pub fn boot_system_kernel(_cr: ClockReset, i: Option<b8>, q: Q) -> (Out, D) {
+    let released = q.seq.enable[1];
+    let d = D {
+        loader: uart_loader::In {
+            data: i,
+            bus: q.ram.bus,
+        },
+        ram: q.loader.bus,
+        seq: sequencer::In::<2> {
+            ready: [q.loader.load_ok, true],
+            retry: false,
+        },
+        count: if released { q.count + 1 } else { bits(0) },
+    };
+    let o = Out {
+        load_ok: q.loader.load_ok,
+        busy: q.loader.busy,
+        fabric_reset: !released,
+        count: q.count,
+    };
+    (o, d)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    Byte(u8),
+    LoadStart,
+    LoadOk,
+    ResetRelease,
+    FirstActivity,
+}
+
+fn frame(words: &[u32]) -> Vec<u8> {
+    let mut body = vec![];
+    body.extend((words.len() as u16).to_le_bytes());
+    body.extend(0_u16.to_le_bytes());
+    for w in words {
+        body.extend(w.to_le_bytes());
+    }
+    let crc = crc32(&body);
+    let mut frame = b"RHDL".to_vec();
+    frame.extend(body);
+    frame.extend(crc.to_le_bytes());
+    frame
+}
+
+type Sample = TimedSample<(ClockReset, Option<b8>, Out)>;
+
+// Reports `event` when `flag` rises
+fn rising(flag: impl Fn(&Sample) -> bool, event: Event) -> impl FnMut(&Sample) -> Option<Event> {
+    let mut prev = false;
+    move |s| {
+        let now = flag(s);
+        let rose = now && !prev;
+        prev = now;
+        rose.then(|| event.clone())
+    }
+}
+
+#[test]
+fn test_boot_event_ordering() -> miette::Result<()> {
+    let uut = BootSystem::default();
+    // Send the frame with a few idle clocks between bytes, as a UART would
+    let bytes = frame(&[0xDEAD_BEEF, 0x1234_5678, 0x0BAD_F00D]);
+    let mut input = vec![];
+    for &b in &bytes {
+        input.push(Some(b8(b as u128)));
+        input.extend([None; 3]);
+    }
+    input.extend([None; 40]);
+    let samples = uut
+        .run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+        .synchronous_sample()
+        .skip(1)
+        .collect::<Vec<_>>();
+    let period = samples[1].time - samples[0].time;
+    // One extractor per interface, merged into a single log
+    let log = EventLog::new()
+        .extract(samples.clone(), |s| {
+            s.value.1.map(|b| Event::Byte(b.raw() as u8))
+        })
+        .extract(
+            samples.clone(),
+            rising(|s| s.value.2.busy, Event::LoadStart),
+        )
+        .extract(
+            samples.clone(),
+            rising(|s| s.value.2.load_ok, Event::LoadOk),
+        )
+        .extract(
+            samples.clone(),
+            rising(|s| !s.value.2.fabric_reset, Event::ResetRelease),
+        )
+        .extract(samples, |s| {
+            (s.value.2.count == b8(1)).then_some(Event::FirstActivity)
+        });
+    let bytes_seen = log
+        .payloads()
+        .filter_map(|e| match e {
+            Event::Byte(b) => Some(*b),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(bytes_seen, bytes);
+    // The load completes after the last byte, and the fabric only
+    // comes out of reset (and starts running) after the load
+    let last_byte = log
+        .iter()
+        .rev()
+        .find(|e| matches!(e.payload, Event::Byte(_)))
+        .unwrap()
+        .time;
+    let times = log
+        .first_in_order(&[
+            &|e| *e == Event::LoadStart,
+            &|e| *e == Event::LoadOk,
+            &|e| *e == Event::ResetRelease,
+            &|e| *e == Event::FirstActivity,
+        ])
+        .expect("Boot events out of order");
+    assert!(times[1] > last_byte);
+    // No bytes arrive once the load has completed
+    assert!(log
+        .window(times[1]..u64::MAX)
+        .iter()
+        .all(|e| !matches!(e.payload, Event::Byte(_))));
+    // The reset is released once the dwell time has passed, and the
+    // fabric runs on the next clock
+    let released = log.find_after(
+        |e| *e == Event::LoadOk,
+        |e| *e == Event::ResetRelease,
+        (DWELL as u64 + 4) * period,
+    );
+    assert_eq!(released.len(), 1);
+    assert!(released[0].delay().unwrap() > DWELL as u64 * period);
+    let active = log.find_after(
+        |e| *e == Event::ResetRelease,
+        |e| *e == Event::FirstActivity,
+        period,
+    );
+    assert_eq!(active[0].delay(), Some(period));
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use rhdl::core::trace::report::{activity_energy, EnergyReport, EnergyWeights};
+use rhdl::prelude::*;
+use rhdl_fpga::{
+    core::counter,
+    fifo::synchronous::{In, Out, SyncFIFO},
+};
+
+// A two block design: a free running counter next to a FIFO
+#[derive(Clone, Synchronous, SynchronousDQ, Default)]
+struct CounterAndFifo {
+    counter: counter::Counter<U4>,
+    fifo: SyncFIFO<Bits<U8>, U3>,
+}
+
+impl SynchronousIO for CounterAndFifo {
+    type I = In<Bits<U8>>;
+    type O = (b4, Out<Bits<U8>>);
+    type Kernel = counter_and_fifo_kernel;
+}
+
+#[kernel]
+fn counter_and_fifo_kernel(_cr: ClockReset, i: In<Bits<U8>>, q: Q) -> ((b4, Out<Bits<U8>>), D) {
+    let d = D {
+        counter: true,
+        fifo: i,
+    };
+    ((q.counter, q.fifo), d)
+}
+
+fn idle_energy(cycles: usize) -> miette::Result<EnergyReport> {
+    let uut = CounterAndFifo::default();
+    let weights = EnergyWeights::default().with_descriptor(&uut.descriptor("top")?);
+    let idle = In {
+        data: None,
+        next: false,
+    };
+    let guard = trace_init_db();
+    uut.run(
+        std::iter::repeat_n(idle, cycles)
+            .with_reset(1)
+            .clock_pos_edge(100),
+    )?
+    .for_each(drop);
+    Ok(activity_energy(&guard.take(), &weights))
+}
+
+#[test]
+fn test_busy_counter_outranks_idle_fifo() -> miette::Result<()> {
+    let report = idle_energy(200)?;
+    assert!(
+        report.subtree("top.counter") > 10.0 * report.subtree("top.fifo"),
+        "{report}"
+    );
+    assert!(report.ranked()[0].0.starts_with("top.counter"), "{report}");
+    // The counter kernel has a 4 bit adder
+    let weights =
+        EnergyWeights::default().with_descriptor(&CounterAndFifo::default().descriptor("top")?);
+    assert_eq!(weights.adder_bits.get("top.counter"), Some(&4));
+    assert!(report.instances["top.counter"].adders > 0.0);
+    Ok(())
+}
+
+#[test]
+fn test_energy_scales_with_length() -> miette::Result<()> {
+    // The counter wraps every 16 clocks, so each extra period
+    // costs the same energy
+    let totals = [1, 2, 3, 4]
+        .into_iter()
+        .map(|periods| Ok(idle_energy(16 * periods)?.total()))
+        .collect::<miette::Result<Vec<_>>>()?;
+    let period = totals[1] - totals[0];
+    assert!(period > 0.0);
+    for pair in totals.windows(2) {
+        assert!((pair[1] - pair[0] - period).abs() < 1e-9, "{totals:?}");
+    }
+    Ok(())
+}
@@ -0,0 +1,396 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use rand::{Rng, SeedableRng};
+use rhdl::core::{
+    sim::error::{RunError, WatchpointError},
+    trace::analyze::activity_report,
+};
+use rhdl::prelude::*;
+use rhdl_fpga::fifo::{
+    synchronous::{In, Out, SyncFIFO},
+    write_logic,
+};
+
+// A long soak, so run it with --ignored
+#[test]
+#[ignore]
+fn test_fifo_soak_fst_is_compact() -> miette::Result<()> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0xf1f0);
+    // A long soak with random writes and reads
+    let input = (0..200_000)
+        .map(|_| In {
+            data: rng
+                .random_bool(0.5)
+                .then(|| bits(rng.random::<u8>() as u128)),
+            next: rng.random_bool(0.5),
+        })
+        .collect::<Vec<_>>();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = || input.iter().copied().with_reset(1).clock_pos_edge(100);
+    let dir = tempfile::tempdir().unwrap();
+    let vcd = dir.path().join("soak.vcd");
+    let fst = dir.path().join("soak.fst");
+    uut.run(stream())?
+        .collect::<Vcd>()
+        .dump_to_file(&vcd)
+        .unwrap();
+    uut.run(stream())?
+        .collect::<Fst>()
+        .dump_to_file(&fst)
+        .unwrap();
+    let vcd = std::fs::metadata(vcd).unwrap().len();
+    let fst = std::fs::metadata(fst).unwrap().len();
+    assert!(fst * 5 <= vcd, "FST is {fst} bytes, VCD is {vcd} bytes");
+    Ok(())
+}
+
+#[test]
+fn test_fifo_soak_flags_toggle() -> miette::Result<()> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0xf1f2);
+    let input = (0..20_000)
+        .map(|_| In {
+            data: rng
+                .random_bool(0.5)
+                .then(|| bits(rng.random::<u8>() as u128)),
+            next: rng.random_bool(0.5),
+        })
+        .collect::<Vec<_>>();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let guard = trace_init_db();
+    uut.run(input.into_iter().with_reset(1).clock_pos_edge(100))?
+        .for_each(drop);
+    let db = guard.take();
+    // The soak should fill and drain the FIFO, and exercise
+    // every signal in the design
+    rhdl::assert_all_toggled!(
+        db,
+        [
+            "top.outputs.full",
+            "top.outputs.almost_full",
+            "top.outputs.almost_empty",
+            "top.read_logic.outputs.empty",
+        ]
+    );
+    let report = activity_report(&db);
+    assert_eq!(report.stuck().count(), 0, "{report}");
+    Ok(())
+}
+
+fn overflow_soak() -> Vec<In<Bits<U8>>> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0xf1f1);
+    // Writes are more common than reads, so the FIFO eventually overflows
+    (0..20_000)
+        .map(|_| In {
+            data: rng
+                .random_bool(0.6)
+                .then(|| bits(rng.random::<u8>() as u128)),
+            next: rng.random_bool(0.4),
+        })
+        .collect()
+}
+
+#[test]
+fn test_fifo_soak_overflow_watchpoint() -> miette::Result<()> {
+    let input = overflow_soak();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = || input.iter().copied().with_reset(1).clock_pos_edge(100);
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("vcd")
+        .join("fifo")
+        .join("synchronous");
+    std::fs::create_dir_all(&root).unwrap();
+    let window = root.join("overflow_window.vcd");
+    let fired = Rc::new(RefCell::new(vec![]));
+    let hooks = Hooks::default().on_output("overflow", |o: &Out<b8>| o.overflow, {
+        let fired = fired.clone();
+        let window = window.clone();
+        move |context| {
+            fired
+                .borrow_mut()
+                .push((context.cycle(), context.dump_state()));
+            context.capture_vcd(&window, 10, 10);
+        }
+    });
+    let hooked = uut
+        .run_with_hooks(stream(), hooks)?
+        .collect::<Result<Vec<_>, _>>()?;
+    // The hooks do not change the simulation
+    let plain = uut.run(stream())?.collect::<Vec<_>>();
+    assert_eq!(hooked, plain);
+    let first = plain
+        .into_iter()
+        .synchronous_sample()
+        .position(|t| t.value.2.overflow)
+        .unwrap();
+    let fired = fired.borrow();
+    assert_eq!(fired[0].0, first as u64);
+    assert!(fired[0].1.contains("top.write_logic.outputs"));
+    let window = std::fs::read_to_string(window).unwrap();
+    assert!(window.contains("$enddefinitions"));
+    Ok(())
+}
+
+#[test]
+fn test_fifo_soak_watchpoint_abort() -> miette::Result<()> {
+    let input = overflow_soak();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = || input.iter().copied().with_reset(1).clock_pos_edge(100);
+    let overflow = Path::default().field("overflow");
+    let hooks = Hooks::default().on_signal(
+        "abort_on_overflow",
+        "top.write_logic.outputs",
+        move |value| value.path(&overflow).unwrap().as_bool().unwrap(),
+        |context| context.abort("the FIFO overflowed"),
+    );
+    let result = uut
+        .run_with_hooks(stream(), hooks)?
+        .collect::<Result<Vec<_>, _>>();
+    let err = result.unwrap_err();
+    let Some(WatchpointError::Aborted {
+        watchpoint, cycle, ..
+    }) = err.watchpoint_error()
+    else {
+        panic!("Expected the watchpoint to abort the run, got {err:?}");
+    };
+    assert_eq!(watchpoint, "abort_on_overflow");
+    let first = uut
+        .run(stream())?
+        .synchronous_sample()
+        .position(|t| t.value.2.overflow)
+        .unwrap();
+    assert_eq!(*cycle, first as u64);
+    Ok(())
+}
+
+#[test]
+fn test_fifo_soak_assertion() -> miette::Result<()> {
+    let input = overflow_soak();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = || input.iter().copied().with_reset(1).clock_pos_edge(100);
+    let overflow = Path::default().field("overflow");
+    let hooks = Hooks::default().assert_signal::<write_logic::Out<U3>, _>(
+        "no_overflow",
+        "top.write_logic.outputs",
+        move |value| !value.path(&overflow).unwrap().as_bool().unwrap(),
+    );
+    let err = uut
+        .run_with_hooks(stream(), hooks)?
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_err();
+    let Some(WatchpointError::AssertionViolation {
+        assertion,
+        instance,
+        cycle,
+        ..
+    }) = err.watchpoint_error()
+    else {
+        panic!("Expected the assertion to fail, got {err:?}");
+    };
+    assert_eq!(assertion, "no_overflow");
+    assert_eq!(instance, "top.write_logic.outputs");
+    let first = uut
+        .run(stream())?
+        .synchronous_sample()
+        .position(|t| t.value.2.overflow)
+        .unwrap();
+    assert_eq!(*cycle, first as u64);
+    Ok(())
+}
+
+#[test]
+fn test_fifo_assertion_width_mismatch() -> miette::Result<()> {
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = overflow_soak()
+        .into_iter()
+        .with_reset(1)
+        .clock_pos_edge(100);
+    // The write logic outputs are not a single bit
+    let hooks =
+        Hooks::default()
+            .assert_signal::<bool, _>("not_a_bool", "top.write_logic.outputs", |_| true);
+    let err = uut
+        .run_with_hooks(stream, hooks)?
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_err();
+    let Some(WatchpointError::WidthMismatch {
+        watchpoint,
+        signal,
+        expected,
+        found,
+    }) = err.watchpoint_error()
+    else {
+        panic!("Expected a width mismatch, got {err:?}");
+    };
+    assert_eq!(watchpoint, "not_a_bool");
+    assert_eq!(signal, "top.write_logic.outputs");
+    assert_eq!(*expected, 1);
+    assert_eq!(*found, write_logic::Out::<U3>::BITS);
+    Ok(())
+}
+
+#[test]
+fn test_fifo_soak_cancelled() -> miette::Result<()> {
+    let input = overflow_soak();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = || input.iter().copied().with_reset(1).clock_pos_edge(100);
+    // Cancel the run from the progress callback at cycle 1000
+    let token = CancelToken::new();
+    let reports = Rc::new(RefCell::new(vec![]));
+    let control = RunControl::default()
+        .on_progress(250, {
+            let token = token.clone();
+            let reports = reports.clone();
+            move |progress| {
+                reports.borrow_mut().push((progress.cycles, progress.time));
+                if progress.cycles == 1000 {
+                    token.cancel();
+                }
+            }
+        })
+        .with_cancel(token);
+    let partial = uut
+        .run_controlled(stream(), control)?
+        .collect_partial()
+        .unwrap_err();
+    assert_eq!(
+        partial.error.run_error(),
+        Some(&RunError::Cancelled {
+            cycle: 1000,
+            time: 100_050,
+        })
+    );
+    // The run stops at the edge after the cancel, and the trace so
+    // far is the start of the uncontrolled run
+    let plain = uut.run(stream())?.collect::<Vec<_>>();
+    assert_eq!(partial.trace[..], plain[..partial.trace.len()]);
+    assert_eq!(partial.trace.last().unwrap().time, 100_000);
+    assert_eq!(partial.trace.into_iter().synchronous_sample().count(), 1001);
+    // A report every 250 cycles, at the positive edge
+    assert_eq!(
+        *reports.borrow(),
+        [(250, 24_950), (500, 49_950), (750, 74_950), (1000, 99_950)]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_fifo_soak_progress_only() -> miette::Result<()> {
+    let input = overflow_soak();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = || input.iter().copied().with_reset(1).clock_pos_edge(100);
+    let cycles = Rc::new(RefCell::new(vec![]));
+    let control = RunControl::default().on_progress(5000, {
+        let cycles = cycles.clone();
+        move |progress| cycles.borrow_mut().push(progress.cycles)
+    });
+    // Without a cancel, the controlled run is the same as a plain one
+    let controlled = uut
+        .run_controlled(stream(), control)?
+        .collect::<Result<Vec<_>, _>>()?;
+    let plain = uut.run(stream())?.collect::<Vec<_>>();
+    assert_eq!(controlled, plain);
+    assert_eq!(*cycles.borrow(), [5000, 10_000, 15_000, 20_000]);
+    Ok(())
+}
+
+#[test]
+fn test_fifo_soak_triggered_trace() -> miette::Result<()> {
+    let input = overflow_soak();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = || input.iter().copied().with_reset(1).clock_pos_edge(100);
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("vcd")
+        .join("fifo")
+        .join("synchronous");
+    std::fs::create_dir_all(&root).unwrap();
+    let guard = trace_init_db();
+    let windows = TriggeredTrace::on_output("triggered_overflow", |o: &Out<b8>| o.overflow)
+        .pre_trigger(12)
+        .post_trigger(5)
+        .in_dir(&root)
+        .capture(uut.run(stream())?)?;
+    // The trace database only holds the end of the run
+    let longest = guard
+        .take()
+        .histories()
+        .values()
+        .map(|h| h.len())
+        .max()
+        .unwrap();
+    assert!(longest < 500, "{longest}");
+    // Find the true event time from a plain run
+    let samples = uut.run(stream())?.collect::<Vec<_>>();
+    let first = samples
+        .iter()
+        .copied()
+        .synchronous_sample()
+        .position(|t| t.value.2.overflow)
+        .unwrap();
+    // The times of the positive edges, so that cycle `n` starts at edges[n - 1]
+    let edges = samples
+        .windows(2)
+        .filter(|w| !w[0].value.0.clock.raw() && w[1].value.0.clock.raw())
+        .map(|w| w[1].time)
+        .collect::<Vec<_>>();
+    let [window] = &windows[..] else {
+        panic!("Expected a single window, got {windows:?}");
+    };
+    assert_eq!(window.path, root.join("triggered_overflow_0.vcd"));
+    assert_eq!(window.trigger_cycle, first as u64);
+    assert_eq!((window.cycles_before, window.cycles_after), (12, 5));
+    assert_eq!(window.start_time, edges[first - 12 - 1]);
+    let end = samples
+        .iter()
+        .take_while(|t| t.time < edges[first + 5])
+        .last()
+        .unwrap()
+        .time;
+    assert_eq!(window.end_time, end);
+    // The file holds the window, and nothing else
+    let vcd = std::fs::read_to_string(&window.path).unwrap();
+    let stamps = vcd
+        .lines()
+        .filter_map(|line| line.strip_prefix('#'))
+        .map(|time| time.parse::<u64>().unwrap())
+        .filter(|time| *time != 0)
+        .collect::<Vec<_>>();
+    assert_eq!(stamps.first(), Some(&window.start_time));
+    assert!(stamps.contains(&window.trigger_time));
+    assert!(stamps.iter().all(|t| (window.start_time..=end).contains(t)));
+    Ok(())
+}
+
+#[test]
+fn test_fifo_soak_triggered_trace_rearms() -> miette::Result<()> {
+    let input = overflow_soak();
+    let uut = SyncFIFO::<Bits<U8>, U3>::default();
+    let stream = input.iter().copied().with_reset(1).clock_pos_edge(100);
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("vcd")
+        .join("fifo")
+        .join("synchronous");
+    std::fs::create_dir_all(&root).unwrap();
+    let full = Path::default().field("full");
+    let windows =
+        TriggeredTrace::on_signal("triggered_full", "top.write_logic.outputs", move |value| {
+            value.path(&full).unwrap().as_bool().unwrap()
+        })
+        .pre_trigger(4)
+        .post_trigger(4)
+        .windows(3)
+        .format(TraceFormat::Fst)
+        .in_dir(&root)
+        .capture(uut.run(stream)?)?;
+    assert_eq!(windows.len(), 3);
+    for (n, window) in windows.iter().enumerate() {
+        assert_eq!(window.path, root.join(format!("triggered_full_{n}.fst")));
+        assert!(std::fs::metadata(&window.path).unwrap().len() > 0);
+        assert_eq!(window.cycles_after, 4);
+    }
+    // The windows do not overlap
+    for pair in windows.windows(2) {
+        assert!(pair[0].end_time < pair[1].start_time);
+        assert!(pair[1].trigger_cycle > pair[0].trigger_cycle + 4);
+    }
+    Ok(())
+}
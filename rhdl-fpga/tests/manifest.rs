@@ -0,0 +1,177 @@
+use expect_test::expect;
+use rhdl::{
+    core::report::{design_manifest, Access, CsrRegister, Manifest},
+    prelude::*,
+};
+use rhdl_fpga::{
+    axi4lite::register::bank::{self, AxiRegBank},
+    boot::uart_loader::{self, UartLoader},
+    core::dff,
+    fifo::synchronous::{self, SyncFIFO},
+};
+
+const BASE: u128 = 0x4000_0100;
+const RESET: [u128; 3] = [0x0, 0x1234_5678, 0xFFFF_0000];
+
+// A small assembly: a boot loader, a FIFO and a CSR bank, with
+// the FIFO and the bank driving interrupts
+#[derive(Clone, Synchronous, SynchronousDQ, Describe)]
+pub struct Board {
+    loader: UartLoader<U10>,
+    #[describe(irq = 2)]
+    fifo: SyncFIFO<b8, U4>,
+    #[describe(irq = 0)]
+    regs: AxiRegBank<3>,
+    #[describe(skip)]
+    busy: dff::DFF<bool>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self {
+            loader: UartLoader::default(),
+            fifo: SyncFIFO::default(),
+            regs: AxiRegBank::new(bits(BASE), RESET.map(bits)),
+            busy: dff::DFF::new(false),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Digital)]
+pub struct In {
+    pub loader: uart_loader::In,
+    pub fifo: synchronous::In<b8>,
+    pub regs: bank::In,
+}
+
+#[derive(PartialEq, Debug, Digital)]
+pub struct Out {
+    pub loader: uart_loader::Out<U10>,
+    pub fifo: synchronous::Out<b8>,
+    pub regs: bank::Out<3>,
+    pub busy: bool,
+}
+
+impl SynchronousIO for Board {
+    type I = In;
+    type O = Out;
+    type Kernel = board_kernel;
+}
+
+#[kernel]
+pub fn board_kernel(_cr: ClockReset, i: In, q: Q) -> (Out, D) {
+    let d = D {
+        loader: i.loader,
+        fifo: i.fifo,
+        regs: i.regs,
+        busy: q.loader.busy,
+    };
+    let o = Out {
+        loader: q.loader,
+        fifo: q.fifo,
+        regs: q.regs,
+        busy: q.busy,
+    };
+    (o, d)
+}
+
+fn manifest() -> miette::Result<Manifest> {
+    Ok(design_manifest(&Board::default())?)
+}
+
+#[test]
+fn test_manifest_markdown() -> miette::Result<()> {
+    let expect = expect![[r#"
+        # Design manifest for `top`
+
+        ## Ports
+
+        | Port | Direction | Bits |
+        |------|-----------|------|
+        | `clock_reset` | Input | 2 |
+        | `i.loader` | Input | 42 |
+        | `i.fifo` | Input | 10 |
+        | `i.regs` | Input | 146 |
+        | `o.loader` | Output | 59 |
+        | `o.fifo` | Output | 14 |
+        | `o.regs` | Output | 137 |
+        | `o.busy` | Output | 1 |
+
+        ## Components
+
+        | Instance | Core | Parameters |
+        |----------|------|------------|
+        | `top` | Board |  |
+        | `top.loader` | UartLoader | address_bits = 10, magic = 0x4c444852, crc = CRC-32 |
+        | `top.fifo` | SyncFIFO | data_bits = 8, address_bits = 4, depth = 15 |
+        | `top.regs` | AxiRegBank | registers = 3 |
+
+        ## CSR map of `top.regs` (base 0x40000100)
+
+        | Offset | Register | Bits | Access | Reset |
+        |--------|----------|------|--------|-------|
+        | 0x0 | reg0 | 32 | ReadWrite | 0x0 |
+        | 0x4 | reg1 | 32 | ReadWrite | 0x12345678 |
+        | 0x8 | reg2 | 32 | ReadWrite | 0xffff0000 |
+
+        ## Interrupts
+
+        | Line | Source |
+        |------|--------|
+        | 0 | `top.regs` |
+        | 2 | `top.fifo` |
+    "#]];
+    expect.assert_eq(&manifest()?.to_markdown());
+    Ok(())
+}
+
+#[test]
+fn test_manifest_json_round_trip() -> miette::Result<()> {
+    let manifest = manifest()?;
+    let json = manifest.to_json();
+    let back: Manifest = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, manifest);
+    Ok(())
+}
+
+#[test]
+fn test_csr_map_matches_bank() -> miette::Result<()> {
+    let manifest = manifest()?;
+    assert_eq!(manifest.csr_maps.len(), 1);
+    let section = &manifest.csr_maps[0];
+    assert_eq!(section.path, "top.regs");
+    assert_eq!(section.map.base, BASE as u64);
+    // One 32 bit register every 4 bytes, with the reset values
+    // the bank was constructed with
+    let expected = RESET
+        .iter()
+        .enumerate()
+        .map(|(ndx, reset)| CsrRegister {
+            name: format!("reg{ndx}"),
+            offset: ndx as u64 * 4,
+            width: 32,
+            access: Access::ReadWrite,
+            reset: *reset as u64,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(section.map.registers, expected);
+    Ok(())
+}
+
+#[test]
+fn test_skipped_fields_and_interrupts() -> miette::Result<()> {
+    let manifest = manifest()?;
+    let paths = manifest
+        .components
+        .iter()
+        .map(|c| c.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, ["top", "top.loader", "top.fifo", "top.regs"]);
+    let irqs = manifest
+        .interrupts
+        .iter()
+        .map(|irq| (irq.line, irq.source.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(irqs, [(0, "top.regs"), (2, "top.fifo")]);
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rhdl::prelude::*;
+use rhdl_fpga::fifo::synchronous::{In, Out, SyncFIFO};
+
+// Counts the allocations (and reallocations) made by the test, so that
+// the owned and buffer reusing ways of collecting outputs can be compared.
+// The simulation itself makes many small allocations, and their number
+// varies a little from run to run, so only the large allocations (like
+// those of a growing output vector) are counted.
+struct CountingAlloc;
+
+const LARGE: usize = 64 * 1024;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+fn count(size: usize) {
+    if size >= LARGE {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+    }
+    BYTES.fetch_add(size, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// A 512 bit wide bus
+type Word = [b128; 4];
+
+fn counted<T>(f: impl FnOnce() -> T) -> (T, usize, usize) {
+    let (allocs, bytes) = (
+        ALLOCS.load(Ordering::Relaxed),
+        BYTES.load(Ordering::Relaxed),
+    );
+    let result = f();
+    (
+        result,
+        ALLOCS.load(Ordering::Relaxed) - allocs,
+        BYTES.load(Ordering::Relaxed) - bytes,
+    )
+}
+
+// Write in bursts, and read whenever the FIFO is not nearly empty
+fn soak(
+    uut: &SyncFIFO<Word, U4>,
+) -> impl Iterator<Item = TimedSample<(ClockReset, In<Word>, Out<Word>)>> + '_ {
+    let input = (0..4000_u128).map(|n| In {
+        data: (n % 13 < 9).then_some([bits(n), bits(!n), bits(n * 3), bits(n ^ 0x55)]),
+        next: n % 7 < 5,
+    });
+    uut.run(input.with_reset(1).clock_pos_edge(100))
+        .unwrap()
+        .synchronous_sample()
+}
+
+#[test]
+fn test_reused_output_buffer_saves_allocations() {
+    let uut = SyncFIFO::default();
+    let mut buffer = vec![];
+    soak(&uut).collect_outputs_into(&mut buffer);
+    let (owned, owned_allocs, owned_bytes) =
+        counted(|| soak(&uut).map(|t| t.value.2).collect::<Vec<_>>());
+    let ((), reused_allocs, reused_bytes) =
+        counted(|| soak(&uut).collect_outputs_into(&mut buffer));
+    // The values are the same either way
+    assert_eq!(buffer, owned);
+    assert!(owned.iter().any(|o| o.data.is_some()));
+    let counts = format!(
+        "owned: {owned_allocs} large allocations ({owned_bytes} bytes), reused: {reused_allocs} large allocations ({reused_bytes} bytes)"
+    );
+    // Growing the owned vector reallocates as it goes, while the
+    // reused buffer already has room for the outputs of the run
+    assert!(reused_allocs < owned_allocs, "{counts}");
+    assert!(
+        reused_bytes + owned.len() * std::mem::size_of::<Out<Word>>() <= owned_bytes,
+        "{counts}"
+    );
+}
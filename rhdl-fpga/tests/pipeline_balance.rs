@@ -0,0 +1,120 @@
+use rhdl::prelude::*;
+
+use rhdl_fpga::core::dff;
+
+// A fork/join datapath.  The data is buffered by two registers,
+// while a checksum is computed in parallel.  The two are joined at
+// the output, and must line up.
+#[derive(Debug, PartialEq, Digital)]
+pub struct Out {
+    pub data: b8,
+    pub check: b8,
+}
+
+mod imbalanced {
+    use super::*;
+
+    // The checksum branch has only one register stage
+    #[derive(Clone, Debug, Default, Synchronous, SynchronousDQ)]
+    pub struct ForkJoin {
+        buffer: dff::DFF<b8>,
+        data: dff::DFF<b8>,
+        check: dff::DFF<b8>,
+    }
+
+    impl SynchronousIO for ForkJoin {
+        type I = b8;
+        type O = Out;
+        type Kernel = fork_join;
+    }
+
+    #[kernel]
+    pub fn fork_join(_cr: ClockReset, i: b8, q: Q) -> (Out, D) {
+        let d = D {
+            buffer: i,
+            data: q.buffer,
+            check: i ^ (i << 1),
+        };
+        let o = Out {
+            data: q.data,
+            check: q.check,
+        };
+        (o, d)
+    }
+}
+
+mod balanced {
+    use super::*;
+
+    // The checksum branch is registered twice, to match the data
+    #[derive(Clone, Debug, Default, Synchronous, SynchronousDQ)]
+    pub struct ForkJoin {
+        buffer: dff::DFF<b8>,
+        data: dff::DFF<b8>,
+        partial: dff::DFF<b8>,
+        check: dff::DFF<b8>,
+    }
+
+    impl SynchronousIO for ForkJoin {
+        type I = b8;
+        type O = Out;
+        type Kernel = fork_join;
+    }
+
+    #[kernel]
+    pub fn fork_join(_cr: ClockReset, i: b8, q: Q) -> (Out, D) {
+        let d = D {
+            buffer: i,
+            data: q.buffer,
+            partial: i ^ (i << 1),
+            check: q.partial,
+        };
+        let o = Out {
+            data: q.data,
+            check: q.check,
+        };
+        (o, d)
+    }
+}
+
+fn pairs() -> [(Path, Path); 1] {
+    [(
+        Path::default().field("data"),
+        Path::default().field("check"),
+    )]
+}
+
+#[test]
+fn test_imbalanced_fork_join_is_caught() {
+    let uut = imbalanced::ForkJoin::default();
+    let report = drc::check_pipeline_balance(&uut, &pairs()).unwrap_err();
+    let err = report.downcast_ref::<drc::PipelineImbalance>().unwrap();
+    assert_eq!(err.imbalances.len(), 1);
+    let imbalance = &err.imbalances[0];
+    assert_eq!(imbalance.first.depths, [2]);
+    assert_eq!(imbalance.first.registers, ["uut_buffer", "uut_data"]);
+    assert_eq!(imbalance.second.depths, [1]);
+    assert_eq!(imbalance.second.registers, ["uut_check"]);
+    assert_eq!(
+        imbalance.suggestion(),
+        "Add 1 delay stage(s) on the path to .check to match .data"
+    );
+}
+
+#[test]
+fn test_balanced_fork_join_passes() -> miette::Result<()> {
+    let uut = balanced::ForkJoin::default();
+    drc::check_pipeline_balance(&uut, &pairs())?;
+    Ok(())
+}
+
+#[test]
+fn test_feedback_can_not_be_balanced() {
+    // A counter has feedback, so its depth from the inputs is not fixed
+    let uut = rhdl_fpga::core::counter::Counter::<U8>::default();
+    let pairs = [(Path::default(), Path::default())];
+    let report = drc::check_pipeline_balance(&uut, &pairs).unwrap_err();
+    let err = report.downcast_ref::<drc::PipelineImbalance>().unwrap();
+    assert!(err.imbalances[0].first.depths.len() > 1);
+    assert!(err.imbalances[0].suggestion().contains("not fixed"));
+}
@@ -0,0 +1,135 @@
+/*
+    The corpus_tests! macro turns a directory of recorded stimulus
+    into tests.  So for example:
+
+    corpus_tests!(check_fifo, "corpus/fifo");
+
+    With a directory holding `overflow.json.gz` and `wrap.json.gz`,
+    translates into:
+
+    #[test]
+    fn corpus_overflow() -> miette::Result<()> {
+        check_fifo(rhdl::core::sim::corpus::replay_corpus(".../corpus/fifo/overflow.json.gz")?)
+    }
+
+    #[test]
+    fn corpus_wrap() -> miette::Result<()> { ... }
+
+    The directory is relative to the crate being compiled, and is read
+    when the macro is expanded.  Each file is also included in the
+    test, so that editing one rebuilds the tests, but adding a file
+    to the directory does not (touch the source file to pick it up).
+*/
+
+use std::path::{Path, PathBuf};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    ExprPath, LitStr, Token,
+    parse::{Parse, ParseStream},
+};
+
+struct CorpusTestsArgs {
+    checker: ExprPath,
+    dir: LitStr,
+}
+
+impl Parse for CorpusTestsArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let checker = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let dir = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(CorpusTestsArgs { checker, dir })
+    }
+}
+
+// The test name for a corpus file: the file name up to the first
+// `.`, with anything that cannot appear in an identifier replaced
+fn test_name(file: &Path) -> String {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let stem = name.split('.').next().unwrap_or_default();
+    let stem = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("corpus_{stem}")
+}
+
+pub fn corpus_tests(input: TokenStream) -> syn::Result<TokenStream> {
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    corpus_tests_in(Path::new(&root), input)
+}
+
+fn corpus_tests_in(root: &Path, input: TokenStream) -> syn::Result<TokenStream> {
+    let CorpusTestsArgs { checker, dir } = syn::parse2(input)?;
+    let path = root.join(dir.value());
+    let entries = std::fs::read_dir(&path).map_err(|err| {
+        syn::Error::new(
+            dir.span(),
+            format!("Unable to read corpus directory {}: {err}", path.display()),
+        )
+    })?;
+    let mut files = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.is_file())
+        .collect::<Vec<PathBuf>>();
+    files.sort();
+    let mut names = std::collections::HashSet::new();
+    let mut tests = vec![];
+    for file in files {
+        let name = test_name(&file);
+        if !names.insert(name.clone()) {
+            return Err(syn::Error::new(
+                dir.span(),
+                format!(
+                    "Corpus file {} gives a test name ({name}) that is already used",
+                    file.display()
+                ),
+            ));
+        }
+        let name = format_ident!("{}", name);
+        let file = file.display().to_string();
+        tests.push(quote! {
+            #[test]
+            fn #name() -> miette::Result<()> {
+                const _: &[u8] = include_bytes!(#file);
+                #checker(rhdl::core::sim::corpus::replay_corpus(#file)?)
+            }
+        });
+    }
+    Ok(quote! { #(#tests)* })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_corpus_tests() {
+        let root = std::env::temp_dir().join(format!("corpus_tests_{}", std::process::id()));
+        let dir = root.join("corpus");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Wrap-Around.json.gz"), b"").unwrap();
+        std::fs::write(dir.join("overflow.json.gz"), b"").unwrap();
+        let output = corpus_tests_in(&root, quote!(check_fifo, "corpus"))
+            .unwrap()
+            .to_string();
+        assert!(output.contains("fn corpus_overflow"));
+        assert!(output.contains("fn corpus_wrap_around"));
+        assert_eq!(output.matches("check_fifo").count(), 2);
+        let missing = corpus_tests_in(&root, quote!(check_fifo, "missing"));
+        assert!(missing.is_err());
+        std::fs::write(dir.join("overflow.bin"), b"").unwrap();
+        let clash = corpus_tests_in(&root, quote!(check_fifo, "corpus"));
+        assert!(clash.is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
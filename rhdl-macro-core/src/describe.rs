@@ -0,0 +1,124 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{spanned::Spanned, Attribute, Data, DeriveInput, Expr, ExprLit, Lit, Meta};
+
+pub fn derive_describe(input: TokenStream) -> syn::Result<TokenStream> {
+    let decl = syn::parse2::<syn::DeriveInput>(input)?;
+    derive_describe_struct(decl)
+}
+
+enum FieldOption {
+    Child,
+    Skip,
+    Irq(usize),
+}
+
+fn parse_describe_attribute(attrs: &[Attribute]) -> syn::Result<FieldOption> {
+    for attr in attrs {
+        if !attr.path().is_ident("describe") {
+            continue;
+        }
+        match attr.parse_args::<Meta>()? {
+            Meta::Path(path) if path.is_ident("skip") => return Ok(FieldOption::Skip),
+            Meta::NameValue(nv) if nv.path.is_ident("irq") => {
+                if let Expr::Lit(ExprLit {
+                    lit: Lit::Int(line),
+                    ..
+                }) = &nv.value
+                {
+                    return Ok(FieldOption::Irq(line.base10_parse()?));
+                }
+                return Err(syn::Error::new(
+                    nv.value.span(),
+                    "The interrupt line must be an integer literal",
+                ));
+            }
+            meta => {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "Expected `skip` or `irq = <line>`",
+                ));
+            }
+        }
+    }
+    Ok(FieldOption::Child)
+}
+
+fn derive_describe_struct(decl: DeriveInput) -> syn::Result<TokenStream> {
+    let struct_name = &decl.ident;
+    let (impl_generics, ty_generics, where_clause) = decl.generics.split_for_impl();
+    let Data::Struct(s) = &decl.data else {
+        return Err(syn::Error::new(
+            decl.span(),
+            "Describe can only be derived for structs with named fields",
+        ));
+    };
+    let mut children = vec![];
+    let mut irq_name = vec![];
+    let mut irq_line = vec![];
+    for field in s.fields.iter() {
+        let Some(name) = &field.ident else {
+            return Err(syn::Error::new(
+                field.span(),
+                "Describe can only be derived for structs with named fields",
+            ));
+        };
+        match parse_describe_attribute(&field.attrs)? {
+            FieldOption::Skip => continue,
+            FieldOption::Irq(line) => {
+                irq_name.push(name.clone());
+                irq_line.push(line);
+            }
+            FieldOption::Child => {}
+        }
+        children.push(name.clone());
+    }
+    Ok(quote! {
+        impl #impl_generics rhdl::core::report::Describe for #struct_name #ty_generics #where_clause {
+            fn describe(&self) -> rhdl::core::report::Description {
+                rhdl::core::report::Description::new(stringify!(#struct_name))
+                #(
+                    .child(stringify!(#children), rhdl::core::report::Describe::describe(&self.#children))
+                )*
+                #(
+                    .interrupt(stringify!(#irq_name), #irq_line)
+                )*
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use expect_test::expect_file;
+
+    use super::*;
+
+    #[test]
+    fn test_describe_derive() {
+        let decl = quote!(
+            pub struct Board<const N: usize> {
+                loader: UartLoader<U10>,
+                #[describe(irq = 3)]
+                fifo: SyncFIFO<b8, U4>,
+                #[describe(skip)]
+                glue: DFF<bool>,
+                regs: AxiRegBank<N>,
+            }
+        );
+        let output = derive_describe(decl).unwrap().to_string();
+        let expected = expect_file!["expect/describe_derive.expect"];
+        expected.assert_eq(&output);
+    }
+
+    #[test]
+    fn test_describe_derive_rejects_bad_attribute() {
+        let decl = quote!(
+            pub struct Board {
+                #[describe(irq = "three")]
+                fifo: SyncFIFO<b8, U4>,
+            }
+        );
+        assert!(derive_describe(decl).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+/*
+    The doc_symbol attribute keeps the schematic symbol in the docs of
+    a core in sync with its SynchronousIO types.  So for example:
+
+    #[doc_symbol(ShiftRegister<U8>)]
+    #[doc = badascii_doc::badascii_formal!("...")]
+    pub struct ShiftRegister<N: BitWidth> { ... }
+
+    Leaves the struct as is, and adds a test that checks the symbol
+    in the docs against the one generated from the I/O types of
+    ShiftRegister<U8>.  With `table` as a second argument, the docs
+    must also contain the generated table of ports.
+*/
+
+use inflections::Inflect;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    DeriveInput, Expr, ExprLit, Ident, Lit, LitStr, Meta, Token, Type,
+    parse::{Parse, ParseStream},
+};
+
+struct DocSymbolArgs {
+    ty: Type,
+    table: bool,
+}
+
+impl Parse for DocSymbolArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ty = input.parse()?;
+        let mut table = false;
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            let option: Ident = input.parse()?;
+            if option != "table" {
+                return Err(syn::Error::new(
+                    option.span(),
+                    "Expected `table` (to also check the table of ports)",
+                ));
+            }
+            table = true;
+        }
+        Ok(DocSymbolArgs { ty, table })
+    }
+}
+
+pub fn doc_symbol(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    let DocSymbolArgs { ty, table } = syn::parse2(attr)?;
+    let decl: DeriveInput = syn::parse2(item)?;
+    let mut symbol = None;
+    let mut docs = vec![];
+    for attr in decl.attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+        let Meta::NameValue(name_value) = &attr.meta else {
+            continue;
+        };
+        match &name_value.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(text),
+                ..
+            }) => {
+                let text = text.value();
+                docs.push(text.strip_prefix(' ').unwrap_or(&text).to_string());
+            }
+            // The symbol is drawn with one of the badascii macros
+            Expr::Macro(mac)
+                if mac
+                    .mac
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident.to_string().starts_with("badascii")) =>
+            {
+                symbol = Some(mac.mac.parse_body::<LitStr>()?.value());
+            }
+            _ => {}
+        }
+    }
+    let name = &decl.ident;
+    let test_name = format_ident!("doc_symbol_{}", name.to_string().to_snake_case());
+    let symbol = match symbol {
+        Some(symbol) => quote!(Some(#symbol)),
+        None => quote!(None),
+    };
+    let docs = docs.join("\n");
+    Ok(quote! {
+        #decl
+
+        #[cfg(test)]
+        #[test]
+        fn #test_name() {
+            rhdl::core::circuit::doc_symbol::check_doc_symbol::<#ty>(
+                stringify!(#name),
+                #symbol,
+                #docs,
+                #table,
+                (env!("CARGO_MANIFEST_DIR"), file!()),
+            );
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use expect_test::expect_file;
+
+    #[test]
+    fn test_doc_symbol() {
+        let attr = quote!(Widget<U4>, table);
+        let item = quote!(
+            /// A widget
+            ///
+            /// | Port | Direction | Type | Bits |
+            #[doc = badascii_doc::badascii_formal!("
+      +-+Widget+-----+
+ bool |              | b4
++---->| input output +---->
+      +--------------+
+")]
+            pub struct Widget<N: BitWidth> {
+                reg: dff::DFF<Bits<N>>,
+            }
+        );
+        let output = doc_symbol(attr, item).unwrap().to_string();
+        let expected = expect_file!["expect/doc_symbol.expect"];
+        expected.assert_debug_eq(&output);
+    }
+
+    #[test]
+    fn test_doc_symbol_bad_option() {
+        let attr = quote!(Widget<U4>, tables);
+        let item = quote!(
+            pub struct Widget;
+        );
+        assert!(doc_symbol(attr, item).is_err());
+    }
+}
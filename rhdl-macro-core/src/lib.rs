@@ -16,8 +16,14 @@ pub use synchronous::derive_synchronous;
 mod synchronous_dq;
 pub use synchronous_dq::derive_synchronous_dq;
 mod clone;
+mod describe;
+pub use describe::derive_describe;
 mod export;
 pub mod typenum_op;
 pub use export::export_macro;
 mod path;
 pub use path::path_macro;
+mod doc_symbol;
+pub use doc_symbol::doc_symbol;
+mod corpus;
+pub use corpus::corpus_tests;
@@ -56,6 +56,14 @@ pub fn synchronous_dq(input: TokenStream) -> TokenStream {
     }
 }
 
+#[proc_macro_derive(Describe, attributes(describe))]
+pub fn describe(input: TokenStream) -> TokenStream {
+    match rhdl_macro_core::derive_describe(input.into()) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 #[proc_macro]
 pub fn export(input: TokenStream) -> TokenStream {
     match rhdl_macro_core::export_macro(input.into()) {
@@ -64,6 +72,14 @@ pub fn export(input: TokenStream) -> TokenStream {
     }
 }
 
+#[proc_macro_attribute]
+pub fn doc_symbol(attr: TokenStream, input: TokenStream) -> TokenStream {
+    match rhdl_macro_core::doc_symbol(attr.into(), input.into()) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 #[proc_macro]
 pub fn path(input: TokenStream) -> TokenStream {
     match rhdl_macro_core::path_macro(input.into()) {
@@ -79,3 +95,11 @@ pub fn op(input: TokenStream) -> TokenStream {
         Err(err) => err.to_compile_error().into(),
     }
 }
+
+#[proc_macro]
+pub fn corpus_tests(input: TokenStream) -> TokenStream {
+    match rhdl_macro_core::corpus_tests(input.into()) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
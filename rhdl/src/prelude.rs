@@ -27,6 +27,7 @@ pub use crate::rhdl_core::hdl::ast::{Direction, Events, HDLKind, non_blocking_as
 pub use crate::rhdl_core::hdl::ast::{
     always, assign, bit_string, continuous_assignment, id, if_statement, initial, port,
 };
+pub use crate::rhdl_core::report::Describe;
 pub use crate::rhdl_core::rhdl_trace_type as rtt;
 pub use crate::rhdl_core::rhif::spec::OpCode;
 pub use crate::rhdl_core::rtl::Object;
@@ -75,10 +76,12 @@ pub use crate::rhdl_core::{
 };
 pub use rhdl_macro::Circuit;
 pub use rhdl_macro::CircuitDQ;
+pub use rhdl_macro::Describe;
 pub use rhdl_macro::Digital;
 pub use rhdl_macro::Synchronous;
 pub use rhdl_macro::SynchronousDQ;
 pub use rhdl_macro::Timed;
+pub use rhdl_macro::doc_symbol;
 pub use rhdl_macro::kernel;
 // Use the extension traits
 pub use crate::rhdl_bits::xadd::XAdd;
@@ -90,27 +93,47 @@ pub use crate::rhdl_core::BitX;
 pub use crate::rhdl_core::bitx::bitx_parse;
 pub use crate::rhdl_core::bitx::bitx_string;
 pub use crate::rhdl_core::bitx_vec;
+pub use crate::rhdl_core::circuit::blackbox::BlackBoxModel;
+pub use crate::rhdl_core::circuit::blackbox::BlackBoxSpec;
+pub use crate::rhdl_core::circuit::blackbox::ModeledBlackBox;
+pub use crate::rhdl_core::circuit::blackbox::ModelOverride;
+pub use crate::rhdl_core::circuit::blackbox::override_black_box_model;
+pub use crate::rhdl_core::circuit::blackbox::register_black_box;
+#[cfg(feature = "iverilog")]
+pub use crate::rhdl_core::circuit::blackbox::verify_black_box_model;
 pub use crate::rhdl_core::circuit::drc;
 pub use crate::rhdl_core::circuit::fixture::Driver;
+pub use crate::rhdl_core::circuit::fixture::DriverPort;
 pub use crate::rhdl_core::circuit::fixture::ExportError;
 pub use crate::rhdl_core::circuit::fixture::Fixture;
 pub use crate::rhdl_core::circuit::fixture::MountPoint;
 pub use crate::rhdl_core::circuit::fixture::passthrough_input_driver;
 pub use crate::rhdl_core::circuit::fixture::passthrough_output_driver;
+pub use crate::rhdl_core::circuit::fixture_builder::BoardTop;
+pub use crate::rhdl_core::circuit::fixture_builder::FixtureBuilder;
+pub use crate::rhdl_core::circuit::fixture_builder::Resource;
+pub use crate::rhdl_core::circuit::fixture_builder::ResourceKind;
 pub use crate::rhdl_core::sim::clock_pos_edge::ClockPosEdgeExt;
 pub use crate::rhdl_core::sim::merge::MergeExt;
 pub use crate::rhdl_core::sim::merge::merge;
+pub use crate::rhdl_core::sim::probe::event_log::EventLog;
 pub use crate::rhdl_core::sim::probe::ext::ProbeExt;
 pub use crate::rhdl_core::sim::probe::ext::SynchronousProbeExt;
+pub use crate::rhdl_core::sim::probe::outputs::SynchronousTraceExt;
 pub use crate::rhdl_core::sim::reset::TimedStreamExt;
 pub use crate::rhdl_core::sim::run::async_fn::run_async_red_blue;
 pub use crate::rhdl_core::sim::run::asynchronous::RunExt;
 pub use crate::rhdl_core::sim::run::sync_fn::RunSynchronousFeedbackExt;
 pub use crate::rhdl_core::sim::run::synchronous::RunSynchronousExt;
 pub use crate::rhdl_core::sim::run::synchronous::RunWithoutSynthesisSynchronousExt;
+pub use crate::rhdl_core::sim::run::control::{CancelToken, RunControl, RunControlledExt};
+pub use crate::rhdl_core::sim::run::hooks::{HookContext, Hooks, RunWithHooksExt};
+pub use crate::rhdl_core::sim::run::session::SimSession;
+pub use crate::rhdl_core::sim::run::triggered::{TraceFormat, TriggeredTrace};
 pub use crate::rhdl_core::sim::testbench::TestBenchOptions;
 pub use crate::rhdl_core::sim::testbench::asynchronous::TestBench;
 pub use crate::rhdl_core::sim::testbench::synchronous::SynchronousTestBench;
+pub use crate::rhdl_core::sim::fst::Fst;
 pub use crate::rhdl_core::sim::vcd::Vcd;
 pub use crate::rhdl_core::trace::svg::SvgOptions;
 pub use crate::rhdl_core::types::path::sub_trace_type;
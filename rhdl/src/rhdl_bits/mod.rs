@@ -426,6 +426,8 @@ pub mod xsub;
 #[doc(hidden)]
 mod impl_macro;
 
+mod serde_impl;
+
 #[doc(hidden)]
 pub mod consts {
     pub use crate::rhdl_typenum::consts::*;
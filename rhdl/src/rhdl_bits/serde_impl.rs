@@ -0,0 +1,75 @@
+//! Serialization of [Bits] and [SignedBits]
+//!
+//! Values are written as plain integers (a `u128` or an `i128`), with
+//! no width attached.  The width comes from the type being read, and a
+//! value that does not fit in it is rejected rather than truncated.
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use super::{BitWidth, Bits, SignedBits};
+
+impl<N: BitWidth> Serialize for Bits<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u128(self.val)
+    }
+}
+
+impl<'de, N: BitWidth> Deserialize<'de> for Bits<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let val = u128::deserialize(deserializer)?;
+        if val > Bits::<N>::mask().val {
+            return Err(D::Error::custom(format!(
+                "value {val} does not fit in {} bits",
+                N::BITS
+            )));
+        }
+        Ok(Bits {
+            marker: std::marker::PhantomData,
+            val,
+        })
+    }
+}
+
+impl<N: BitWidth> Serialize for SignedBits<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i128(self.val)
+    }
+}
+
+impl<'de, N: BitWidth> Deserialize<'de> for SignedBits<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let val = i128::deserialize(deserializer)?;
+        if val > SignedBits::<N>::max_value() || val < SignedBits::<N>::min_value() {
+            return Err(D::Error::custom(format!(
+                "value {val} does not fit in {} signed bits",
+                N::BITS
+            )));
+        }
+        Ok(SignedBits {
+            marker: std::marker::PhantomData,
+            val,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rhdl_bits::alias::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = serde_json::to_string(&(b128(u128::MAX), s128(i128::MIN), b4(9))).unwrap();
+        assert_eq!(
+            text,
+            "[340282366920938463463374607431768211455,-170141183460469231731687303715884105728,9]"
+        );
+        let value: (b128, s128, b4) = serde_json::from_str(&text).unwrap();
+        assert_eq!(value, (b128(u128::MAX), s128(i128::MIN), b4(9)));
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert!(serde_json::from_str::<b4>("16").is_err());
+        assert!(serde_json::from_str::<s4>("-9").is_err());
+        assert!(serde_json::from_str::<s4>("-8").is_ok());
+    }
+}
@@ -0,0 +1,364 @@
+//! Behavioral models for external Verilog IP
+//!
+//! A black box wraps a piece of Verilog (usually vendor IP) that RHDL
+//! cannot simulate itself.  Each black box is registered by name with
+//! a [BlackBoxSpec], which holds both halves of the black box:
+//!
+//! - the Verilog source of the IP, and a template that instantiates
+//!   it, which are used when generating HDL,
+//! - a factory for a Rust [BlackBoxModel] of the IP, which is used
+//!   when simulating.
+//!
+//! The [ModeledBlackBox] circuit looks the spec up by name, so that
+//! the IP can be used as a child of any synchronous design.  Because
+//! the model and the Verilog are written separately, they can drift
+//! apart.  The `verify_black_box_model` function (available with the
+//! `iverilog` feature) runs the Verilog through Icarus Verilog, and
+//! checks that it matches the model for a given stimulus.
+//!
+//! Tests can replace the model of a black box (for example to inject
+//! faults) with [override_black_box_model].  The override only applies
+//! to circuits simulated on the current thread, and is removed when the
+//! returned [ModelOverride] is dropped.
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::{
+    prelude::{
+        CircuitDescriptor, Clock, ClockReset, Digital, HDLDescriptor, Kind, Module, NoKernel3,
+        RHDLError, Synchronous, SynchronousDQ, SynchronousIO,
+    },
+    rhdl_core::{
+        error::rhdl_error,
+        hdl::ast::{
+            Declaration, Direction, HDLKind, Statement, continuous_assignment, index_bit, port,
+            unsigned_width,
+        },
+        ntl::builder::synchronous_black_box,
+    },
+};
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum BlackBoxError {
+    #[error("No black box named {0} has been registered")]
+    Unregistered(String),
+    #[error("Black box {name} was registered with different input or output types")]
+    TypeMismatch { name: String },
+}
+
+/// A behavioral model of a synchronous black box.  The model
+/// is advanced on each rising edge of the clock, and the reset
+/// is synchronous.
+pub trait BlackBoxModel<I, O> {
+    /// Return the model to its reset state
+    fn reset(&mut self);
+    /// The outputs of the model, given its current state and input
+    fn output(&self, input: I) -> O;
+    /// Advance the model by one clock, using the input at the clock edge
+    fn tick(&mut self, input: I);
+}
+
+pub type ModelFactory<I, O> = Arc<dyn Fn() -> Box<dyn BlackBoxModel<I, O>> + Send + Sync>;
+
+type LocalModelFactory<I, O> = Rc<dyn Fn() -> Box<dyn BlackBoxModel<I, O>>>;
+
+/// The registration for a black box.  The `template` is Verilog that
+/// instantiates the IP inside a wrapper module.  In it, `{instance}` is
+/// replaced with a unique instance name, and the wires `clock`, `reset`,
+/// `i` (the input bits) and `o` (the output bits) are available.
+#[derive(Clone)]
+pub struct BlackBoxSpec<I, O> {
+    pub module: String,
+    pub source: String,
+    pub template: String,
+    pub model: ModelFactory<I, O>,
+}
+
+impl<I, O> BlackBoxSpec<I, O> {
+    pub fn new<F>(module: &str, source: &str, template: &str, model: F) -> Self
+    where
+        F: Fn() -> Box<dyn BlackBoxModel<I, O>> + Send + Sync + 'static,
+    {
+        Self {
+            module: module.into(),
+            source: source.into(),
+            template: template.into(),
+            model: Arc::new(model),
+        }
+    }
+}
+
+impl<I, O> std::fmt::Debug for BlackBoxSpec<I, O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlackBoxSpec")
+            .field("module", &self.module)
+            .finish_non_exhaustive()
+    }
+}
+
+type Registry = Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+thread_local! {
+    static OVERRIDES: RefCell<HashMap<String, Rc<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// Register a black box under `name`, replacing any previous registration
+pub fn register_black_box<I: Digital, O: Digital>(name: &str, spec: BlackBoxSpec<I, O>) {
+    registry()
+        .lock()
+        .unwrap()
+        .insert(name.into(), Arc::new(spec));
+}
+
+/// Look up the registration for the black box `name`
+pub fn black_box_spec<I: Digital, O: Digital>(
+    name: &str,
+) -> Result<Arc<BlackBoxSpec<I, O>>, RHDLError> {
+    let entry = registry()
+        .lock()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| rhdl_error(BlackBoxError::Unregistered(name.into())))?;
+    entry
+        .downcast::<BlackBoxSpec<I, O>>()
+        .map_err(|_| rhdl_error(BlackBoxError::TypeMismatch { name: name.into() }))
+}
+
+/// Restores the previous model of a black box when dropped
+#[must_use = "the override is removed when this is dropped"]
+pub struct ModelOverride {
+    name: String,
+    previous: Option<Rc<dyn Any>>,
+}
+
+impl Drop for ModelOverride {
+    fn drop(&mut self) {
+        OVERRIDES.with_borrow_mut(|overrides| match self.previous.take() {
+            Some(previous) => overrides.insert(self.name.clone(), previous),
+            None => overrides.remove(&self.name),
+        });
+    }
+}
+
+/// Replace the model of the (registered) black box `name` for circuits
+/// initialized on this thread, until the returned guard is dropped.
+pub fn override_black_box_model<I, O, F>(name: &str, model: F) -> Result<ModelOverride, RHDLError>
+where
+    I: Digital,
+    O: Digital,
+    F: Fn() -> Box<dyn BlackBoxModel<I, O>> + 'static,
+{
+    black_box_spec::<I, O>(name)?;
+    let factory: LocalModelFactory<I, O> = Rc::new(model);
+    let previous =
+        OVERRIDES.with_borrow_mut(|overrides| overrides.insert(name.into(), Rc::new(factory)));
+    Ok(ModelOverride {
+        name: name.into(),
+        previous,
+    })
+}
+
+fn local_override<I: Digital, O: Digital>(name: &str) -> Option<LocalModelFactory<I, O>> {
+    OVERRIDES.with_borrow(|overrides| {
+        overrides
+            .get(name)
+            .and_then(|entry| entry.downcast_ref::<LocalModelFactory<I, O>>())
+            .cloned()
+    })
+}
+
+/// A synchronous circuit that is simulated with the model of
+/// a registered black box, and instantiates its Verilog in HDL.
+#[derive(Clone, Debug)]
+pub struct ModeledBlackBox<I, O> {
+    name: String,
+    spec: Arc<BlackBoxSpec<I, O>>,
+}
+
+impl<I: Digital, O: Digital> ModeledBlackBox<I, O> {
+    /// Create an instance of the registered black box `name`
+    pub fn new(name: &str) -> Result<Self, RHDLError> {
+        Ok(Self {
+            name: name.into(),
+            spec: black_box_spec(name)?,
+        })
+    }
+}
+
+impl<I: Digital, O: Digital> SynchronousDQ for ModeledBlackBox<I, O> {
+    type D = ();
+    type Q = ();
+}
+
+impl<I: Digital, O: Digital> SynchronousIO for ModeledBlackBox<I, O> {
+    type I = I;
+    type O = O;
+    type Kernel = NoKernel3<ClockReset, I, (), (O, ())>;
+}
+
+#[doc(hidden)]
+pub struct ModelState<I, O> {
+    clock: Clock,
+    reset: bool,
+    input: I,
+    model: Box<dyn BlackBoxModel<I, O>>,
+}
+
+#[doc(hidden)]
+pub struct ModelStateRef<I, O>(Rc<RefCell<ModelState<I, O>>>);
+
+impl<I, O> Clone for ModelStateRef<I, O> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+// Models are opaque, so two states are only equal if they are the same state
+impl<I, O> PartialEq for ModelStateRef<I, O> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<I: Digital, O: Digital> Synchronous for ModeledBlackBox<I, O> {
+    type S = ModelStateRef<I, O>;
+
+    fn init(&self) -> Self::S {
+        let mut model = match local_override::<I, O>(&self.name) {
+            Some(factory) => factory(),
+            None => (self.spec.model)(),
+        };
+        model.reset();
+        ModelStateRef(Rc::new(RefCell::new(ModelState {
+            clock: Clock::default(),
+            reset: false,
+            input: I::dont_care(),
+            model,
+        })))
+    }
+
+    fn description(&self) -> String {
+        format!("Black box {} (module {})", self.name, self.spec.module)
+    }
+
+    fn sim(&self, clock_reset: ClockReset, input: Self::I, state: &mut Self::S) -> Self::O {
+        let state = &mut *state.0.borrow_mut();
+        let clock = clock_reset.clock;
+        if !clock.raw() {
+            state.reset = clock_reset.reset.any();
+            state.input = input;
+        }
+        if clock.raw() && !state.clock.raw() {
+            if state.reset {
+                state.model.reset();
+            } else {
+                state.model.tick(state.input);
+            }
+        }
+        state.clock = clock;
+        state.model.output(input)
+    }
+
+    fn descriptor(&self, name: &str) -> Result<CircuitDescriptor, RHDLError> {
+        Ok(CircuitDescriptor {
+            unique_name: name.to_string(),
+            input_kind: I::static_kind(),
+            output_kind: O::static_kind(),
+            d_kind: Kind::Empty,
+            q_kind: Kind::Empty,
+            children: Default::default(),
+            rtl: None,
+            ntl: synchronous_black_box(self, name)?,
+        })
+    }
+
+    fn hdl(&self, name: &str) -> Result<HDLDescriptor, RHDLError> {
+        let mut module = Module {
+            name: name.into(),
+            description: self.description(),
+            ..Default::default()
+        };
+        module.ports.push(port(
+            "clock_reset",
+            Direction::Input,
+            HDLKind::Wire,
+            unsigned_width(2),
+        ));
+        if I::BITS != 0 {
+            module.ports.push(port(
+                "i",
+                Direction::Input,
+                HDLKind::Wire,
+                unsigned_width(I::BITS),
+            ));
+        }
+        module.ports.push(port(
+            "o",
+            Direction::Output,
+            HDLKind::Wire,
+            unsigned_width(O::BITS),
+        ));
+        module
+            .declarations
+            .extend(["clock", "reset"].map(|wire| Declaration {
+                kind: HDLKind::Wire,
+                name: wire.into(),
+                width: unsigned_width(1),
+                alias: None,
+            }));
+        module.statements.extend([
+            continuous_assignment("clock", index_bit("clock_reset", 0)),
+            continuous_assignment("reset", index_bit("clock_reset", 1)),
+            Statement::Custom(
+                self.spec
+                    .template
+                    .replace("{instance}", &format!("{name}_{}", self.spec.module)),
+            ),
+        ]);
+        // The IP may be instantiated more than once in a design, so
+        // guard against defining it twice
+        let guard = format!("RHDL_BLACK_BOX_{}", self.spec.module.to_uppercase());
+        module.verbatim.push(format!(
+            "`ifndef {guard}\n`define {guard}\n{}\n`endif",
+            self.spec.source
+        ));
+        Ok(HDLDescriptor {
+            name: name.into(),
+            body: module,
+            children: Default::default(),
+        })
+    }
+}
+
+/// Check the model of the black box `name` against its Verilog, by
+/// simulating both with `stimulus` (after a one clock reset), and
+/// running the Verilog with Icarus Verilog.
+#[cfg(feature = "iverilog")]
+pub fn verify_black_box_model<I: Digital, O: Digital>(
+    name: &str,
+    stimulus: impl IntoIterator<Item = I>,
+) -> Result<(), RHDLError> {
+    use crate::prelude::{
+        ClockPosEdgeExt, RunSynchronousExt, SynchronousTestBench, TestBenchOptions,
+    };
+    use crate::rhdl_core::sim::reset::TimedStreamExt;
+    let uut = ModeledBlackBox::<I, O>::new(name)?;
+    let tb = uut
+        .run(stimulus.into_iter().with_reset(1).clock_pos_edge(100))?
+        .collect::<SynchronousTestBench<_, _>>();
+    tb.rtl(&uut, &TestBenchOptions::default())?.run_iverilog()
+}
@@ -0,0 +1,344 @@
+//! Schematic symbols for documentation
+//!
+//! The documentation of a core usually includes a schematic symbol
+//! (drawn with badascii) that shows its inputs and outputs.  Hand drawn
+//! symbols drift as the I/O types of the core change, so this module
+//! generates them from the [SynchronousIO] types instead.
+//!
+//! - A struct input (or output) gives one port per field.
+//! - A tuple gives one port per element, named `i.0`, `i.1`, ... (or
+//!   `o.0`, `o.1`, ... for outputs).
+//! - Anything else (bits, enums, arrays) gives a single `input` (or
+//!   `output`) port.
+//!
+//! Ports are labelled with their types (`bool`, `b8`, `s4`, `[b8;2]`, and
+//! `?T` for an `Option<T>`), in declaration order, so that the output is
+//! identical from build to build.  The [port_table] function produces a
+//! markdown table of the same ports.
+//!
+//! The `#[doc_symbol(Type)]` attribute keeps the symbol in the docs of a
+//! core in sync.  It generates a test that compares the `badascii` symbol
+//! in the doc comments with the generated one.  Run the tests with
+//! `UPDATE_EXPECT=1` to rewrite stale symbols in place.
+use crate::rhdl_core::{Kind, SynchronousIO, types::kind::Enum};
+
+struct Port {
+    name: String,
+    label: String,
+    bits: usize,
+}
+
+// The name of a type, without its path or generic arguments
+fn short_name(name: &str) -> &str {
+    let name = name
+        .split('<')
+        .next()
+        .unwrap_or(name)
+        .trim_end_matches("::");
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+fn option_payload(enumerate: &Enum) -> Option<Kind> {
+    if short_name(&enumerate.name) != "Option" {
+        return None;
+    }
+    let some = enumerate
+        .variants
+        .iter()
+        .find(|v| v.name.as_str() == "Some")?;
+    match some.kind {
+        Kind::Tuple(tuple) if tuple.elements.len() == 1 => Some(tuple.elements[0]),
+        kind => Some(kind),
+    }
+}
+
+fn label(kind: &Kind) -> String {
+    match kind {
+        Kind::Bits(1) => "bool".into(),
+        Kind::Bits(n) => format!("b{n}"),
+        Kind::Signed(n) => format!("s{n}"),
+        Kind::Array(array) => format!("[{};{}]", label(&array.base), array.size),
+        Kind::Tuple(tuple) => format!(
+            "({})",
+            tuple
+                .elements
+                .iter()
+                .map(label)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Kind::Struct(structure) => short_name(&structure.name).into(),
+        Kind::Enum(enumerate) => match option_payload(enumerate) {
+            Some(payload) => format!("?{}", label(&payload)),
+            None => short_name(&enumerate.name).into(),
+        },
+        Kind::Signal(kind, color) => format!("{}@{color:?}", label(kind)),
+        Kind::Empty => "()".into(),
+    }
+}
+
+fn ports(kind: &Kind, name: &str, prefix: &str) -> Vec<Port> {
+    let port = |name: String, kind: &Kind| Port {
+        name,
+        label: label(kind),
+        bits: kind.bits(),
+    };
+    match kind {
+        Kind::Empty => vec![],
+        Kind::Struct(structure) if !structure.is_tuple_struct() => structure
+            .fields
+            .iter()
+            .map(|field| port(field.name.to_string(), &field.kind))
+            .collect(),
+        Kind::Tuple(tuple) => tuple
+            .elements
+            .iter()
+            .enumerate()
+            .map(|(ndx, kind)| port(format!("{prefix}.{ndx}"), kind))
+            .collect(),
+        _ => vec![port(name.into(), kind)],
+    }
+}
+
+/// Draw the schematic symbol of a core called `name`, with
+/// the given input and output kinds.
+pub fn schematic_symbol_for_kinds(name: &str, input: &Kind, output: &Kind) -> String {
+    let inputs = ports(input, "input", "i");
+    let outputs = ports(output, "output", "o");
+    let widest = |ports: &[Port], f: fn(&Port) -> usize| ports.iter().map(f).max().unwrap_or(0);
+    // The gutters on either side hold the type labels and the arrows
+    let left = (widest(&inputs, |p| p.label.len()) + 2).max(6);
+    let right = (widest(&outputs, |p| p.label.len()) + 1).max(5);
+    let inner = (widest(&inputs, |p| p.name.len()) + widest(&outputs, |p| p.name.len()) + 6)
+        .max(name.len() + 6);
+    let mut lines = vec![format!(
+        "{:left$}+-+{name}+{}+",
+        "",
+        "-".repeat(inner - name.len() - 3)
+    )];
+    for row in 0..inputs.len().max(outputs.len()) {
+        let input = inputs.get(row);
+        let output = outputs.get(row);
+        let mut labels = match input {
+            Some(port) => format!(" {:width$}|", port.label, width = left - 1),
+            None => format!("{:left$}|", ""),
+        };
+        labels += &" ".repeat(inner);
+        labels += "|";
+        if let Some(port) = output {
+            labels += &format!(" {}", port.label);
+        }
+        let mut arrows = match input {
+            Some(_) => format!("+{}>|", "-".repeat(left - 2)),
+            None => format!("{:left$}|", ""),
+        };
+        let in_name = input.map(|p| p.name.as_str()).unwrap_or_default();
+        let out_name = output.map(|p| p.name.as_str()).unwrap_or_default();
+        arrows += &format!(
+            " {in_name}{:gap$}{out_name} ",
+            "",
+            gap = inner - 2 - in_name.len() - out_name.len()
+        );
+        arrows += &match output {
+            Some(_) => format!("+{}>", "-".repeat(right - 1)),
+            None => "|".into(),
+        };
+        lines.push(labels.trim_end().into());
+        lines.push(arrows.trim_end().into());
+    }
+    lines.push(format!("{:left$}+{}+", "", "-".repeat(inner)));
+    lines.join("\n")
+}
+
+/// Draw the schematic symbol of the core `T`, which is called `name`.
+pub fn schematic_symbol<T: SynchronousIO>(name: &str) -> String {
+    schematic_symbol_for_kinds(
+        name,
+        &<T::I as crate::rhdl_core::Digital>::static_kind(),
+        &<T::O as crate::rhdl_core::Digital>::static_kind(),
+    )
+}
+
+/// A markdown table of the ports of a core with the given
+/// input and output kinds.
+pub fn port_table_for_kinds(input: &Kind, output: &Kind) -> String {
+    let mut lines = vec![
+        "| Port | Direction | Type | Bits |".to_string(),
+        "|------|-----------|------|------|".to_string(),
+    ];
+    for (direction, ports) in [
+        ("input", ports(input, "input", "i")),
+        ("output", ports(output, "output", "o")),
+    ] {
+        lines.extend(ports.into_iter().map(|port| {
+            format!(
+                "| `{}` | {direction} | `{}` | {} |",
+                port.name, port.label, port.bits
+            )
+        }));
+    }
+    lines.join("\n")
+}
+
+/// A markdown table of the ports of the core `T`
+pub fn port_table<T: SynchronousIO>() -> String {
+    port_table_for_kinds(
+        &<T::I as crate::rhdl_core::Digital>::static_kind(),
+        &<T::O as crate::rhdl_core::Digital>::static_kind(),
+    )
+}
+
+// Compare drawings, ignoring trailing whitespace and blank lines at either end
+fn normalize(text: &str) -> String {
+    text.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
+
+// Replace the symbol `documented` in the source file `file` with `generated`
+fn update_symbol(manifest_dir: &str, file: &str, documented: &str, generated: &str) -> bool {
+    // The file name is relative to the workspace root, which
+    // may be any ancestor of the manifest directory
+    let Some(path) = std::path::Path::new(manifest_dir)
+        .ancestors()
+        .map(|dir| dir.join(file))
+        .find(|path| path.exists())
+    else {
+        return false;
+    };
+    let Ok(source) = std::fs::read_to_string(&path) else {
+        return false;
+    };
+    if !source.contains(documented) {
+        return false;
+    }
+    let source = source.replacen(documented, &format!("\n{generated}\n"), 1);
+    std::fs::write(path, source).is_ok()
+}
+
+/// Used by the tests generated by `#[doc_symbol]`.  Checks that the
+/// documented symbol (and optionally the port table) of the core `T`
+/// match the generated ones.
+#[doc(hidden)]
+pub fn check_doc_symbol<T: SynchronousIO>(
+    name: &str,
+    documented: Option<&str>,
+    docs: &str,
+    table: bool,
+    location: (&str, &str),
+) {
+    let generated = schematic_symbol::<T>(name);
+    let current = documented.is_some_and(|symbol| normalize(symbol) == generated);
+    if !current {
+        let updated = std::env::var_os("UPDATE_EXPECT").is_some()
+            && documented
+                .is_some_and(|symbol| update_symbol(location.0, location.1, symbol, &generated));
+        if !updated {
+            panic!(
+                "The schematic symbol in the docs of {name} is {}.  It should be:\n\
+                 #[doc = badascii_doc::badascii_formal!(\"\n{generated}\n\")]",
+                if documented.is_some() {
+                    "out of date"
+                } else {
+                    "missing"
+                }
+            );
+        }
+    }
+    if table {
+        let generated = port_table::<T>();
+        assert!(
+            normalize(docs).contains(&generated),
+            "The port table in the docs of {name} is out of date.  It should be:\n{generated}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use crate::{
+        prelude::*,
+        rhdl_core::{DiscriminantAlignment, DiscriminantType},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_symbol_for_struct() {
+        let input = Kind::make_struct(
+            "my_core::In<rhdl::Bits<8>>",
+            vec![
+                Kind::make_field("data", Option::<b8>::static_kind()),
+                Kind::make_field("next", bool::static_kind()),
+            ],
+        );
+        let output = Kind::make_struct(
+            "my_core::Out",
+            vec![
+                Kind::make_field("full", bool::static_kind()),
+                Kind::make_field("level", <[b4; 2]>::static_kind()),
+                Kind::make_field("sum", s12::static_kind()),
+            ],
+        );
+        let expect = expect![[r#"
+                  +-+MyCore+------+
+             ?b8  |               | bool
+            +---->| data     full +------>
+             bool |               | [b4;2]
+            +---->| next    level +------>
+                  |               | s12
+                  |           sum +------>
+                  +---------------+"#]];
+        expect.assert_eq(&schematic_symbol_for_kinds("MyCore", &input, &output));
+        let expect = expect![[r#"
+            | Port | Direction | Type | Bits |
+            |------|-----------|------|------|
+            | `data` | input | `?b8` | 9 |
+            | `next` | input | `bool` | 1 |
+            | `full` | output | `bool` | 1 |
+            | `level` | output | `[b4;2]` | 8 |
+            | `sum` | output | `s12` | 12 |"#]];
+        expect.assert_eq(&port_table_for_kinds(&input, &output));
+    }
+
+    #[test]
+    fn test_symbol_for_tuple_and_enum() {
+        let state = Kind::make_enum(
+            "my_core::State",
+            vec![
+                Kind::make_variant("Idle", Kind::Empty, 0),
+                Kind::make_variant("Busy", Kind::Empty, 1),
+            ],
+            Kind::make_discriminant_layout(
+                1,
+                DiscriminantAlignment::Msb,
+                DiscriminantType::Unsigned,
+            ),
+        );
+        let input = <(bool, b4)>::static_kind();
+        let expect = expect![[r#"
+                  +-+Fsm+---------+
+             bool |               | State
+            +---->| i.0    output +----->
+             b4   |               |
+            +---->| i.1           |
+                  +---------------+"#]];
+        expect.assert_eq(&schematic_symbol_for_kinds("Fsm", &input, &state));
+    }
+
+    #[test]
+    fn test_symbol_is_stable() {
+        let input = <(bool, [b8; 4])>::static_kind();
+        let output = Option::<(b8, bool)>::static_kind();
+        let first = schematic_symbol_for_kinds("Stable", &input, &output);
+        for _ in 0..10 {
+            assert_eq!(first, schematic_symbol_for_kinds("Stable", &input, &output));
+        }
+        assert!(first.lines().all(|line| line == line.trim_end()));
+    }
+}
@@ -1,14 +1,21 @@
+use std::collections::{BTreeMap, HashMap};
+
 use miette::{Diagnostic, SourceSpan};
 use petgraph::algo::DfsSpace;
 
 use crate::{
-    prelude::Synchronous,
+    prelude::{Digital, Path, Synchronous},
     rhdl_core::{
         SourcePool,
+        common::symtab::RegisterId,
         ntl::{
+            Object,
             graph::{GraphMode, WriteSource, make_net_graph},
-            spec::Wire,
+            object::BlackBoxMode,
+            spec::{OpCode, Wire, WireKind},
+            visit::visit_wires,
         },
+        types::path::bit_range,
     },
 };
 use thiserror::Error;
@@ -94,3 +101,213 @@ pub fn no_combinatorial_paths<T: Synchronous>(uut: &T) -> miette::Result<()> {
     }
     Ok(())
 }
+
+// Paths through feedback loops can have any depth, so we only follow
+// them this far.
+const MAX_PIPELINE_DEPTH: usize = 64;
+
+// For each register depth seen, the black boxes on one path with that depth
+type Depths = BTreeMap<usize, Vec<usize>>;
+
+/// The register depth of an output of a circuit, measured from its inputs
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathDepth {
+    /// The path into the output of the circuit
+    pub path: Path,
+    /// The register depths of the paths from the inputs to this output.
+    /// An output in a pipeline has a single depth.  Feedback adds more.
+    pub depths: Vec<usize>,
+    /// The names of the registers on the deepest path
+    pub registers: Vec<String>,
+}
+
+impl PathDepth {
+    fn describe(&self) -> String {
+        match self.depths.as_slice() {
+            [] => format!("{:?} is not driven from the inputs", self.path),
+            [depth] => format!(
+                "{:?} is {depth} register(s) deep [{}]",
+                self.path,
+                self.registers.join(", ")
+            ),
+            depths => format!(
+                "{:?} has depths {depths:?} (through feedback) [{}]",
+                self.path,
+                self.registers.join(", ")
+            ),
+        }
+    }
+}
+
+/// A pair of outputs that should have the same register depth, but do not
+#[derive(Clone, Debug, PartialEq)]
+pub struct Imbalance {
+    pub first: PathDepth,
+    pub second: PathDepth,
+}
+
+impl Imbalance {
+    /// A suggested fix, giving where delay stages are needed to
+    /// balance the pair
+    pub fn suggestion(&self) -> String {
+        match (self.first.depths.as_slice(), self.second.depths.as_slice()) {
+            ([a], [b]) => {
+                let (short, long) = if a < b {
+                    (&self.first, &self.second)
+                } else {
+                    (&self.second, &self.first)
+                };
+                format!(
+                    "Add {} delay stage(s) on the path to {:?} to match {:?}",
+                    a.abs_diff(*b),
+                    short.path,
+                    long.path
+                )
+            }
+            _ => format!(
+                "The depths of {:?} and {:?} are not fixed, so delay stages can not balance them",
+                self.first.path, self.second.path
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Imbalance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, but {}",
+            self.first.describe(),
+            self.second.describe()
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("RHDL Pipeline Imbalance")]
+pub struct PipelineImbalance {
+    pub imbalances: Vec<Imbalance>,
+}
+
+impl Diagnostic for PipelineImbalance {
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette::Severity::Error)
+    }
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(
+            self.imbalances
+                .iter()
+                .map(|imbalance| format!("{imbalance}\n  {}", imbalance.suggestion()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ))
+    }
+}
+
+// Propagate the register depths from the inputs through the netlist.
+// Each synchronous black box (a DFF, for example) adds a stage.
+fn register_depths(ntl: &Object) -> HashMap<RegisterId<WireKind>, Depths> {
+    let mut depths: HashMap<RegisterId<WireKind>, Depths> = ntl
+        .inputs
+        .iter()
+        .skip(1)
+        .flatten()
+        .map(|reg| (*reg, Depths::from([(0, vec![])])))
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for lop in &ntl.ops {
+            let stage = match &lop.op {
+                OpCode::BlackBox(bb) => {
+                    let code = bb.code.raw();
+                    (ntl.black_boxes[code].mode == BlackBoxMode::Synchronous).then_some(code)
+                }
+                _ => None,
+            };
+            let mut reached = Depths::new();
+            let mut targets = vec![];
+            visit_wires(&lop.op, |sense, operand| {
+                let Some(reg) = operand.reg() else {
+                    return;
+                };
+                if sense.is_write() {
+                    targets.push(reg);
+                } else if let Some(source) = depths.get(&reg) {
+                    for (depth, chain) in source {
+                        let (depth, chain) = match stage {
+                            Some(code) => (depth + 1, [chain.as_slice(), &[code]].concat()),
+                            None => (*depth, chain.clone()),
+                        };
+                        if depth <= MAX_PIPELINE_DEPTH {
+                            reached.entry(depth).or_insert(chain);
+                        }
+                    }
+                }
+            });
+            for target in targets {
+                let entry = depths.entry(target).or_default();
+                for (depth, chain) in &reached {
+                    if !entry.contains_key(depth) {
+                        entry.insert(*depth, chain.clone());
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    depths
+}
+
+/// Check that pairs of outputs of a circuit are pipelined to the same
+/// depth.  When a datapath forks (to compute a CRC alongside the data,
+/// say) and the branches join again, the branches need the same number
+/// of register stages, or the data is misaligned.  Each pair names two
+/// parts of the output of the circuit, and the check counts the registers
+/// between the inputs of the circuit and each of them.  Any pairs that do
+/// not match are reported, with the registers on each path, and the
+/// number of delay stages needed to balance them.
+pub fn check_pipeline_balance<T: Synchronous>(
+    uut: &T,
+    pairs: &[(Path, Path)],
+) -> miette::Result<()> {
+    let descriptor = uut.descriptor("uut")?;
+    let ntl = &descriptor.ntl;
+    let depths = register_depths(ntl);
+    let path_depth = |path: &Path| -> miette::Result<PathDepth> {
+        let (range, _) = bit_range(T::O::static_kind(), path)?;
+        let mut merged = Depths::new();
+        for reg in ntl.outputs[range].iter().flat_map(|w| w.reg()) {
+            for (depth, chain) in depths.get(&reg).into_iter().flatten() {
+                merged.entry(*depth).or_insert_with(|| chain.clone());
+            }
+        }
+        let registers = merged
+            .last_key_value()
+            .map(|(_, chain)| {
+                chain
+                    .iter()
+                    .map(|&code| ntl.black_boxes[code].code.name.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(PathDepth {
+            path: path.clone(),
+            depths: merged.into_keys().collect(),
+            registers,
+        })
+    };
+    let mut imbalances = vec![];
+    for (first, second) in pairs {
+        let first = path_depth(first)?;
+        let second = path_depth(second)?;
+        if first.depths.len() != 1 || first.depths != second.depths {
+            imbalances.push(Imbalance { first, second });
+        }
+    }
+    if imbalances.is_empty() {
+        Ok(())
+    } else {
+        Err(miette::Report::new(PipelineImbalance { imbalances }))
+    }
+}
@@ -44,6 +44,38 @@ pub enum ExportError {
     },
     #[error("Path {0:?} on input is not a clock output")]
     NotAClockOutput(Path),
+    #[error("Name {0} is already used in the fixture")]
+    DuplicateName(String),
+    #[error("No instance named {0} in the fixture")]
+    UnknownInstance(String),
+    #[error("No resource named {0} in the fixture")]
+    UnknownResource(String),
+    #[error("Resource {resource} cannot be used as an {expected:?}")]
+    ResourceDirection {
+        resource: String,
+        expected: Direction,
+    },
+    #[error(
+        "Mismatch in signal width connecting {from} ({from_width} bits) to {to} ({to_width} bits)"
+    )]
+    ConnectionWidthMismatch {
+        from: String,
+        from_width: usize,
+        to: String,
+        to_width: usize,
+    },
+    #[error("Cannot connect {from} to {to}, as only clocks can drive clock inputs")]
+    ClockMismatch { from: String, to: String },
+    #[error("Cannot synchronize an input to {0}, as it does not have exactly one clock input")]
+    NoClockForSynchronizer(String),
+    #[error("Output resource {0} is not driven")]
+    ResourceNotDriven(String),
+    #[error("Failed to write {path}")]
+    WriteFailed {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
     #[error("BSP Error {0}")]
     Custom(anyhow::Error),
 }
@@ -127,7 +159,7 @@ impl DriverPort {
             width,
         }
     }
-    fn as_module_port(&self) -> Port {
+    pub(crate) fn as_module_port(&self) -> Port {
         Port {
             name: self.name.clone(),
             direction: self.direction,
@@ -0,0 +1,530 @@
+use std::ops::Range;
+
+use super::{circuit_impl::Circuit, fixture::DriverPort, hdl_backend::maybe_decl_wire};
+use crate::{
+    prelude::{
+        BitX, CircuitIO, Digital, Direction, Kind, Module, Path, RHDLError, Timed, bit_range,
+        sub_trace_type,
+    },
+    rhdl_core::{
+        circuit::fixture::ExportError,
+        hdl::ast::{Statement, component_instance, connection, id, unsigned_reg_decl},
+        types::path::leaf_paths,
+    },
+};
+use rhdl_trace_type::TraceType;
+
+// The type of signal provided by (or to) a board resource
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResourceKind {
+    // A clock input.  Clocks can only be connected to clock inputs.
+    Clock,
+    // An input that is not synchronous to any clock in the design.
+    // Synchronizers are inserted when it is connected to a sub-design,
+    // unless you opt out.
+    Asynchronous,
+    // An input or output that needs no special treatment
+    Synchronous,
+}
+
+// A named resource on a board (an LED bank, a clock, a UART pin, etc).
+// The HDL for the resource connects the top level ports to an internal
+// net of the given width, which is named by [Resource::net].
+#[derive(Clone, Debug)]
+pub struct Resource {
+    pub name: String,
+    pub direction: Direction,
+    pub width: usize,
+    pub kind: ResourceKind,
+    pub ports: Vec<DriverPort>,
+    pub hdl: String,
+    pub constraints: String,
+}
+
+impl Resource {
+    pub fn net_name(name: &str) -> String {
+        format!("res_{name}")
+    }
+    pub fn net(&self) -> String {
+        Self::net_name(&self.name)
+    }
+    // An input that is connected directly to a top level port
+    // of the same name
+    pub fn input(name: &str, width: usize, kind: ResourceKind) -> Self {
+        Self {
+            name: name.into(),
+            direction: Direction::Input,
+            width,
+            kind,
+            ports: vec![DriverPort::input(name, width)],
+            hdl: format!("assign {net} = {name};", net = Self::net_name(name)),
+            constraints: String::new(),
+        }
+    }
+    // An output that is connected directly to a top level port
+    // of the same name
+    pub fn output(name: &str, width: usize) -> Self {
+        Self {
+            name: name.into(),
+            direction: Direction::Output,
+            width,
+            kind: ResourceKind::Synchronous,
+            ports: vec![DriverPort::output(name, width)],
+            hdl: format!("assign {name} = {net};", net = Self::net_name(name)),
+            constraints: String::new(),
+        }
+    }
+    // A clock that is connected directly to a top level port
+    pub fn clock(name: &str) -> Self {
+        Self::input(name, 1, ResourceKind::Clock)
+    }
+    pub fn with_constraints(self, constraints: &str) -> Self {
+        Self {
+            constraints: constraints.into(),
+            ..self
+        }
+    }
+}
+
+struct Instance {
+    name: String,
+    module: Module,
+    input_kind: Kind,
+    input_trace: TraceType,
+    output_kind: Kind,
+    output_trace: TraceType,
+    // Coverage of the instance inputs
+    driven: Vec<bool>,
+}
+
+impl Instance {
+    fn input_net(&self) -> String {
+        format!("{}_input", self.name)
+    }
+    fn output_net(&self) -> String {
+        format!("{}_output", self.name)
+    }
+}
+
+enum Source {
+    Net(String),
+    Literal(String),
+    Synchronized { net: String, clock: String },
+}
+
+// Assembles several circuits into a single top level module, with their
+// inputs and outputs connected to board resources, to each other, or
+// to constants.  All of the connections are checked for width and
+// direction as they are made.
+//
+// Asynchronous board inputs are passed through a two stage synchronizer,
+// clocked by the clock of the instance they are connected to, unless
+// [FixtureBuilder::drive_unsynchronized] is used.
+pub struct FixtureBuilder {
+    name: String,
+    resources: Vec<Resource>,
+    resource_driven: Vec<bool>,
+    instances: Vec<Instance>,
+    statements: Vec<Statement>,
+    // The width of each synchronizer
+    synchronizers: Vec<usize>,
+}
+
+fn slice(net: &str, bits: &Range<usize>) -> String {
+    if bits.len() == 1 {
+        format!("{net}[{}]", bits.start)
+    } else {
+        format!("{net}[{}:{}]", bits.end - 1, bits.start)
+    }
+}
+
+impl FixtureBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            resources: vec![],
+            resource_driven: vec![],
+            instances: vec![],
+            statements: vec![],
+            synchronizers: vec![],
+        }
+    }
+    fn check_unique(&self, name: &str) -> Result<(), RHDLError> {
+        if self.resources.iter().any(|r| r.name == name)
+            || self.instances.iter().any(|i| i.name == name)
+            || self.name == name
+        {
+            return Err(ExportError::DuplicateName(name.into()).into());
+        }
+        Ok(())
+    }
+    fn resource_index(&self, name: &str) -> Result<usize, RHDLError> {
+        self.resources
+            .iter()
+            .position(|r| r.name == name)
+            .ok_or_else(|| ExportError::UnknownResource(name.into()).into())
+    }
+    fn instance_index(&self, name: &str) -> Result<usize, RHDLError> {
+        self.instances
+            .iter()
+            .position(|i| i.name == name)
+            .ok_or_else(|| ExportError::UnknownInstance(name.into()).into())
+    }
+    pub fn add_resource(&mut self, resource: Resource) -> Result<(), RHDLError> {
+        self.check_unique(&resource.name)?;
+        self.resources.push(resource);
+        self.resource_driven.push(false);
+        Ok(())
+    }
+    pub fn add_resources(
+        &mut self,
+        resources: impl IntoIterator<Item = Resource>,
+    ) -> Result<(), RHDLError> {
+        resources
+            .into_iter()
+            .try_for_each(|resource| self.add_resource(resource))
+    }
+    pub fn add_instance<T: Circuit>(&mut self, name: &str, circuit: &T) -> Result<(), RHDLError> {
+        self.check_unique(name)?;
+        let hdl = circuit.hdl(name)?;
+        let input_kind = <<T as CircuitIO>::I as Timed>::static_kind();
+        self.instances.push(Instance {
+            name: name.into(),
+            module: hdl.as_module(),
+            input_kind,
+            input_trace: <<T as CircuitIO>::I as Digital>::static_trace_type(),
+            output_kind: <<T as CircuitIO>::O as Timed>::static_kind(),
+            output_trace: <<T as CircuitIO>::O as Digital>::static_trace_type(),
+            driven: vec![false; input_kind.bits()],
+        });
+        Ok(())
+    }
+    // Returns the bit range of an instance input, and whether it is a clock
+    fn target(&self, instance: usize, path: &Path) -> Result<(Range<usize>, bool), RHDLError> {
+        let inst = &self.instances[instance];
+        let (bits, _) = bit_range(inst.input_kind, path)?;
+        let is_clock = sub_trace_type(inst.input_trace.clone(), path)? == TraceType::Clock;
+        Ok((bits, is_clock))
+    }
+    fn mark_driven(&mut self, instance: usize, bits: &Range<usize>) -> Result<(), RHDLError> {
+        let driven = &mut self.instances[instance].driven[bits.clone()];
+        if driven.iter().any(|b| *b) {
+            return Err(ExportError::MultipleDrivers.into());
+        }
+        driven.fill(true);
+        Ok(())
+    }
+    // Find the net that carries the clock of an instance
+    fn instance_clock(&self, instance: usize) -> Result<String, RHDLError> {
+        let inst = &self.instances[instance];
+        let mut clocks = vec![];
+        for path in leaf_paths(&inst.input_kind, Path::default()) {
+            if sub_trace_type(inst.input_trace.clone(), &path)? == TraceType::Clock {
+                let (bits, _) = bit_range(inst.input_kind, &path)?;
+                clocks.push(slice(&inst.input_net(), &bits));
+            }
+        }
+        if clocks.len() != 1 {
+            return Err(ExportError::NoClockForSynchronizer(inst.name.clone()).into());
+        }
+        Ok(clocks.pop().unwrap())
+    }
+    fn check_width(
+        from: String,
+        from_width: usize,
+        to: String,
+        to_width: usize,
+    ) -> Result<(), RHDLError> {
+        if from_width != to_width {
+            return Err(ExportError::ConnectionWidthMismatch {
+                from,
+                from_width,
+                to,
+                to_width,
+            }
+            .into());
+        }
+        Ok(())
+    }
+    fn assign(&mut self, target: String, width: usize, source: Source) {
+        match source {
+            Source::Net(net) | Source::Literal(net) => {
+                self.statements
+                    .push(Statement::Custom(format!("assign {target} = {net};")));
+            }
+            Source::Synchronized { net, clock } => {
+                let meta = format!("sync{}_meta", self.synchronizers.len());
+                let sync = format!("sync{}_out", self.synchronizers.len());
+                self.synchronizers.push(width);
+                self.statements.push(Statement::Custom(format!(
+                    "always @(posedge {clock}) begin\n    {meta} <= {net};\n    {sync} <= {meta};\nend"
+                )));
+                self.statements
+                    .push(Statement::Custom(format!("assign {target} = {sync};")));
+            }
+        }
+    }
+    fn drive_resource(
+        &mut self,
+        resource: &str,
+        instance: &str,
+        path: &Path,
+        synchronize: bool,
+    ) -> Result<(), RHDLError> {
+        let res = self.resource_index(resource)?;
+        let inst = self.instance_index(instance)?;
+        let res = &self.resources[res];
+        if res.direction != Direction::Input {
+            return Err(ExportError::ResourceDirection {
+                resource: resource.into(),
+                expected: Direction::Input,
+            }
+            .into());
+        }
+        let (kind, width, net) = (res.kind, res.width, res.net());
+        let (bits, is_clock) = self.target(inst, path)?;
+        let to = format!("{instance}{path:?}");
+        Self::check_width(resource.into(), width, to.clone(), bits.len())?;
+        if is_clock != (kind == ResourceKind::Clock) {
+            return Err(ExportError::ClockMismatch {
+                from: resource.into(),
+                to,
+            }
+            .into());
+        }
+        self.mark_driven(inst, &bits)?;
+        let source = if synchronize && kind == ResourceKind::Asynchronous {
+            Source::Synchronized {
+                net,
+                clock: self.instance_clock(inst)?,
+            }
+        } else {
+            Source::Net(net)
+        };
+        let target = slice(&self.instances[inst].input_net(), &bits);
+        self.assign(target, width, source);
+        Ok(())
+    }
+    // Drive an instance input from a board resource.  Asynchronous
+    // resources are synchronized to the clock of the instance.
+    pub fn drive(&mut self, resource: &str, instance: &str, path: &Path) -> Result<(), RHDLError> {
+        self.drive_resource(resource, instance, path, true)
+    }
+    // Drive an instance input from a board resource without
+    // inserting a synchronizer.
+    pub fn drive_unsynchronized(
+        &mut self,
+        resource: &str,
+        instance: &str,
+        path: &Path,
+    ) -> Result<(), RHDLError> {
+        self.drive_resource(resource, instance, path, false)
+    }
+    // Drive a board resource from an instance output
+    pub fn sink(&mut self, instance: &str, path: &Path, resource: &str) -> Result<(), RHDLError> {
+        let res = self.resource_index(resource)?;
+        let inst = &self.instances[self.instance_index(instance)?];
+        if self.resources[res].direction != Direction::Output {
+            return Err(ExportError::ResourceDirection {
+                resource: resource.into(),
+                expected: Direction::Output,
+            }
+            .into());
+        }
+        let (bits, _) = bit_range(inst.output_kind, path)?;
+        Self::check_width(
+            format!("{instance}{path:?}"),
+            bits.len(),
+            resource.into(),
+            self.resources[res].width,
+        )?;
+        if self.resource_driven[res] {
+            return Err(ExportError::MultipleDrivers.into());
+        }
+        self.resource_driven[res] = true;
+        let source = Source::Net(slice(&inst.output_net(), &bits));
+        self.assign(self.resources[res].net(), bits.len(), source);
+        Ok(())
+    }
+    // Drive the input of one instance from the output of another
+    pub fn connect(
+        &mut self,
+        from_instance: &str,
+        from_path: &Path,
+        to_instance: &str,
+        to_path: &Path,
+    ) -> Result<(), RHDLError> {
+        let from = &self.instances[self.instance_index(from_instance)?];
+        let (from_bits, _) = bit_range(from.output_kind, from_path)?;
+        let from_clock = sub_trace_type(from.output_trace.clone(), from_path)? == TraceType::Clock;
+        let source = Source::Net(slice(&from.output_net(), &from_bits));
+        let inst = self.instance_index(to_instance)?;
+        let (to_bits, to_clock) = self.target(inst, to_path)?;
+        let target = slice(&self.instances[inst].input_net(), &to_bits);
+        let from = format!("{from_instance}{from_path:?}");
+        let to = format!("{to_instance}{to_path:?}");
+        Self::check_width(from.clone(), from_bits.len(), to.clone(), to_bits.len())?;
+        if from_clock != to_clock {
+            return Err(ExportError::ClockMismatch { from, to }.into());
+        }
+        self.mark_driven(inst, &to_bits)?;
+        self.assign(target, to_bits.len(), source);
+        Ok(())
+    }
+    // Drive an instance input with a constant value
+    pub fn constant<S: Digital>(
+        &mut self,
+        val: S,
+        instance: &str,
+        path: &Path,
+    ) -> Result<(), RHDLError> {
+        let inst = self.instance_index(instance)?;
+        let (_, sub_kind) = bit_range(self.instances[inst].input_kind, path)?;
+        if S::static_kind() != sub_kind {
+            return Err(RHDLError::ExportError(ExportError::WrongConstantType {
+                provided: S::static_kind(),
+                required: sub_kind,
+            }));
+        }
+        let (bits, _) = self.target(inst, path)?;
+        self.mark_driven(inst, &bits)?;
+        let literal = val
+            .bin()
+            .into_iter()
+            .rev()
+            .map(|x| match x {
+                BitX::One => '1',
+                BitX::Zero => '0',
+                BitX::X => 'x',
+            })
+            .collect::<String>();
+        let target = slice(&self.instances[inst].input_net(), &bits);
+        self.assign(
+            target,
+            bits.len(),
+            Source::Literal(format!("{}'b{literal}", bits.len())),
+        );
+        Ok(())
+    }
+    pub fn build(&self) -> Result<BoardTop, RHDLError> {
+        for inst in &self.instances {
+            if inst.driven.iter().any(|b| !b) {
+                let coverage = leaf_paths(&inst.input_kind, Path::default())
+                    .into_iter()
+                    .filter(|path| {
+                        let (bits, _) = bit_range(inst.input_kind, path).unwrap();
+                        !inst.driven[bits].iter().all(|b| *b)
+                    })
+                    .map(|path| format!("Path {}{path:?} is not covered\n", inst.name))
+                    .collect();
+                return Err(ExportError::InputsNotCovered(coverage).into());
+            }
+        }
+        for (resource, driven) in self.resources.iter().zip(&self.resource_driven) {
+            if resource.direction == Direction::Output && !driven {
+                return Err(ExportError::ResourceNotDriven(resource.name.clone()).into());
+            }
+        }
+        let ports = self
+            .resources
+            .iter()
+            .flat_map(|r| r.ports.iter())
+            .map(|x| x.as_module_port())
+            .collect();
+        let resource_nets = self
+            .resources
+            .iter()
+            .flat_map(|r| maybe_decl_wire(r.width, &r.net()));
+        let instance_nets = self.instances.iter().flat_map(|inst| {
+            [
+                maybe_decl_wire(inst.input_kind.bits(), &inst.input_net()),
+                maybe_decl_wire(inst.output_kind.bits(), &inst.output_net()),
+            ]
+            .into_iter()
+            .flatten()
+        });
+        let registers = self
+            .synchronizers
+            .iter()
+            .enumerate()
+            .flat_map(|(ndx, width)| {
+                [
+                    unsigned_reg_decl(&format!("sync{ndx}_meta"), *width),
+                    unsigned_reg_decl(&format!("sync{ndx}_out"), *width),
+                ]
+            });
+        let declarations = resource_nets
+            .chain(instance_nets)
+            .chain(registers)
+            .collect();
+        let mut statements = self
+            .resources
+            .iter()
+            .map(|r| Statement::Custom(r.hdl.clone()))
+            .chain(self.statements.iter().cloned())
+            .collect::<Vec<_>>();
+        let mut submodules = vec![];
+        for inst in &self.instances {
+            let connections = [
+                (inst.input_kind.bits() != 0).then(|| connection("i", id(&inst.input_net()))),
+                (inst.output_kind.bits() != 0).then(|| connection("o", id(&inst.output_net()))),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            statements.push(component_instance(
+                &inst.module.name,
+                &format!("{}_inst", inst.name),
+                connections,
+            ));
+            submodules.push(inst.module.clone());
+        }
+        let module = Module {
+            name: self.name.clone(),
+            description: format!(
+                "Top level for {}",
+                self.instances
+                    .iter()
+                    .map(|inst| inst.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ports,
+            declarations,
+            statements,
+            submodules,
+            ..Default::default()
+        };
+        let constraints = self
+            .resources
+            .iter()
+            .map(|r| r.constraints.clone())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(BoardTop {
+            name: self.name.clone(),
+            module,
+            constraints,
+        })
+    }
+}
+
+// The generated top level module and its pin constraints
+#[derive(Clone, Debug)]
+pub struct BoardTop {
+    name: String,
+    module: Module,
+    constraints: String,
+}
+
+impl BoardTop {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+    pub fn constraints(&self) -> &str {
+        &self.constraints
+    }
+}
@@ -2,11 +2,14 @@ pub mod adapter;
 pub mod array_circuit;
 pub mod array_synchronous;
 pub mod async_func;
+pub mod blackbox;
 pub mod chain;
+pub mod doc_symbol;
 pub mod circuit_descriptor;
 pub mod circuit_impl;
 pub mod drc;
 pub mod fixture;
+pub mod fixture_builder;
 pub mod func;
 pub mod hdl_backend;
 pub mod hdl_descriptor;
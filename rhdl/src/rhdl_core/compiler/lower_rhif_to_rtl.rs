@@ -1169,7 +1169,8 @@ fn compile_rtl(object: &rhif::Object) -> Result<rtl::object::Object> {
             }
         })
         .collect();
-    let return_register = compiler.reverse_operand_map[&object.return_slot];
+    // A kernel that returns a literal has a return slot no op refers to
+    let return_register = compiler.operand(object.return_slot);
     Ok(rtl::object::Object {
         symbols: compiler.symbols,
         symtab: compiler.symtab,
@@ -1,10 +1,13 @@
-use miette::Diagnostic;
+use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
 use crate::rhdl_core::{
-    KernelFnKind, TypedBits,
-    circuit::yosys::YosysSynthError,
+    KernelFnKind, SourcePool, TypedBits,
+    circuit::{fixture::ExportError, yosys::YosysSynthError},
     compiler::mir::ty::UnifyError,
+    sim::error::{
+        RunError, SessionError, TestbenchError, TestbenchMismatch, VerilogError, WatchpointError,
+    },
     types::{bit_string::BitString, path::PathError},
 };
 
@@ -58,10 +61,21 @@ pub enum RHDLError {
         expected: BitString,
         actual: BitString,
     },
-    #[error("Verilog verification error: {0}")]
-    VerilogVerificationErrorString(String),
-    #[error("Testbench Construction Error: {0}")]
-    TestbenchConstructionError(String),
+    #[error("Verilog Verification Error")]
+    #[diagnostic(transparent)]
+    VerilogError(#[from] Box<VerilogError>),
+    #[error("Testbench Construction Error")]
+    #[diagnostic(transparent)]
+    TestbenchError(#[from] Box<TestbenchError>),
+    #[error("Watchpoint Error")]
+    #[diagnostic(transparent)]
+    WatchpointError(#[from] Box<WatchpointError>),
+    #[error("Simulation Session Error")]
+    #[diagnostic(transparent)]
+    SessionError(#[from] Box<SessionError>),
+    #[error("Simulation Run Error")]
+    #[diagnostic(transparent)]
+    RunError(#[from] Box<RunError>),
     #[error("Circuits with no outputs are not synthesizable")]
     NoOutputsError,
     #[error("syn parsing error: {0}")]
@@ -81,6 +95,76 @@ pub enum RHDLError {
     #[error("Type Inference Error")]
     #[diagnostic(transparent)]
     TypeInferenceError(#[from] Box<UnifyError>),
+    #[error("Black Box Error")]
+    #[diagnostic(transparent)]
+    BlackBoxError(#[from] Box<crate::rhdl_core::circuit::blackbox::BlackBoxError>),
+}
+
+impl RHDLError {
+    /// The Verilog verification failure, if this is one
+    pub fn verilog_error(&self) -> Option<&VerilogError> {
+        match self {
+            RHDLError::VerilogError(err) => Some(err),
+            _ => None,
+        }
+    }
+    /// The mismatch between the Verilog simulation and the Rust
+    /// model, if this is one
+    pub fn testbench_mismatch(&self) -> Option<&TestbenchMismatch> {
+        match self.verilog_error()? {
+            VerilogError::Mismatch(mismatch) => Some(mismatch),
+            _ => None,
+        }
+    }
+    /// The testbench construction failure, if this is one
+    pub fn testbench_error(&self) -> Option<&TestbenchError> {
+        match self {
+            RHDLError::TestbenchError(err) => Some(err),
+            _ => None,
+        }
+    }
+    /// The watchpoint failure (or abort) of a simulation, if this is one
+    pub fn watchpoint_error(&self) -> Option<&WatchpointError> {
+        match self {
+            RHDLError::WatchpointError(err) => Some(err),
+            _ => None,
+        }
+    }
+    /// The failure of an interactive simulation session, if this is one
+    pub fn session_error(&self) -> Option<&SessionError> {
+        match self {
+            RHDLError::SessionError(err) => Some(err),
+            _ => None,
+        }
+    }
+    /// The reason a controlled simulation stopped early, if this is one
+    pub fn run_error(&self) -> Option<&RunError> {
+        match self {
+            RHDLError::RunError(err) => Some(err),
+            _ => None,
+        }
+    }
+    /// The kernel source, and the span within it that caused a
+    /// kernel compile error (a syntax, type, partial initialization or
+    /// clock domain error), if this is one
+    pub fn kernel_span(&self) -> Option<(&SourcePool, SourceSpan)> {
+        match self {
+            RHDLError::RHDLSyntaxError(err) => Some((&err.src, err.err_span)),
+            RHDLError::RHDLInternalCompilerError(err) => Some((&err.src, err.err_span)),
+            RHDLError::RHDLTypeError(err) => Some((&err.src, err.err_span)),
+            RHDLError::RHDLTypeCheckError(err) => Some((&err.src, err.cause_span)),
+            RHDLError::RHDLPartialInitializationError(err) => Some((&err.src, err.err_span)),
+            RHDLError::RHDLClockDomainViolation(err) => Some((&err.src, err.cause_span)),
+            _ => None,
+        }
+    }
+    /// The export (fixture or top level) failure, if this is one
+    pub fn export_error(&self) -> Option<&ExportError> {
+        match self {
+            RHDLError::ExportError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 pub fn rhdl_error<T>(error: T) -> RHDLError
@@ -18,6 +18,9 @@ pub struct Module {
     pub statements: Vec<Statement>,
     pub functions: Vec<Function>,
     pub submodules: Vec<Module>,
+    /// Verilog source text emitted as is after the module, for
+    /// example the definition of an externally supplied IP core.
+    pub verbatim: Vec<String>,
 }
 
 impl Module {
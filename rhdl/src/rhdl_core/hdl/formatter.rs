@@ -406,7 +406,8 @@ pub fn module(ast: &Module) -> String {
         .map(module)
         .collect::<Vec<_>>()
         .join("\n");
+    let verbatim = ast.verbatim.join("\n");
     reformat_verilog(&format!(
-        "// {description}\nmodule {name}({ports});\n{declarations}\n{statements}\n{functions}\nendmodule\n{sub_modules}\n",
+        "// {description}\nmodule {name}({ports});\n{declarations}\n{statements}\n{functions}\nendmodule\n{sub_modules}\n{verbatim}\n",
     ))
 }
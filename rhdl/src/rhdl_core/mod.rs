@@ -70,6 +70,7 @@ pub mod sim;
 pub use types::timed_sample::TimedSample;
 pub use types::timed_sample::timed_sample;
 pub mod hdl;
+pub mod report;
 pub mod trace;
 pub use bitx::dyn_bit_manip::move_nbits_to_msb;
 //pub use flow_graph::flow_cost::trivial_cost;
@@ -150,7 +150,7 @@ impl BlackBoxId {
         Self(self.0 + offset)
     }
 
-    pub(crate) fn raw(self) -> usize {
+    pub fn raw(self) -> usize {
         self.0
     }
 }
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+/// The value of a component parameter
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ParamValue {
+    /// An integer (such as a width, or a depth)
+    Int(i64),
+    /// A bit pattern (such as a polynomial), shown in hex
+    Hex(u64),
+    /// A flag
+    Bool(bool),
+    /// Anything else
+    Text(String),
+}
+
+impl std::fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamValue::Int(x) => write!(f, "{x}"),
+            ParamValue::Hex(x) => write!(f, "{x:#x}"),
+            ParamValue::Bool(x) => write!(f, "{x}"),
+            ParamValue::Text(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+impl From<usize> for ParamValue {
+    fn from(x: usize) -> Self {
+        ParamValue::Int(x as i64)
+    }
+}
+
+impl From<i64> for ParamValue {
+    fn from(x: i64) -> Self {
+        ParamValue::Int(x)
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(x: bool) -> Self {
+        ParamValue::Bool(x)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(x: &str) -> Self {
+        ParamValue::Text(x.into())
+    }
+}
+
+impl From<String> for ParamValue {
+    fn from(x: String) -> Self {
+        ParamValue::Text(x)
+    }
+}
+
+/// A named parameter of a component
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    pub name: String,
+    pub value: ParamValue,
+}
+
+/// How a CSR can be accessed over the bus
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Access {
+    ReadWrite,
+    ReadOnly,
+    WriteOnly,
+}
+
+/// A register in a CSR map
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CsrRegister {
+    pub name: String,
+    /// The byte offset of the register from the base of the map
+    pub offset: u64,
+    /// The width of the register in bits
+    pub width: usize,
+    pub access: Access,
+    /// The value of the register after reset
+    pub reset: u64,
+}
+
+/// The registers a component decodes on a bus
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CsrMap {
+    /// The bus address of the first register
+    pub base: u64,
+    pub registers: Vec<CsrRegister>,
+}
+
+/// The description of a component, and of the components it
+/// contains.  Built with the methods below, as in
+///
+///```
+/// use rhdl::core::report::{Description, ParamValue};
+///
+/// let d = Description::new("Crc")
+///     .param("width", 32_usize)
+///     .param("polynomial", ParamValue::Hex(0x04c11db7));
+///```
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Description {
+    /// The name of the core
+    pub core: String,
+    pub params: Vec<Param>,
+    pub csr: Option<CsrMap>,
+    /// The interrupt lines driven by the component (or its
+    /// children), as a source name relative to the component,
+    /// and the line it is assigned to
+    pub interrupts: Vec<(String, usize)>,
+    /// The named children of the component
+    pub children: Vec<(String, Description)>,
+}
+
+impl Description {
+    pub fn new(core: &str) -> Self {
+        Self {
+            core: core.into(),
+            ..Default::default()
+        }
+    }
+    pub fn param(mut self, name: &str, value: impl Into<ParamValue>) -> Self {
+        self.params.push(Param {
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+    pub fn csr(self, map: CsrMap) -> Self {
+        Self {
+            csr: Some(map),
+            ..self
+        }
+    }
+    pub fn interrupt(mut self, source: &str, line: usize) -> Self {
+        self.interrupts.push((source.into(), line));
+        self
+    }
+    pub fn child(mut self, name: &str, child: Description) -> Self {
+        self.children.push((name.into(), child));
+        self
+    }
+}
+
+/// A component that can describe itself in a design manifest.
+///
+/// Assemblies of other components can derive this trait.  The derived
+/// description has a child for each field (fields marked with
+/// `#[describe(skip)]` are left out), and a field marked with
+/// `#[describe(irq = N)]` is recorded as the source of interrupt line `N`.
+pub trait Describe {
+    fn describe(&self) -> Description;
+}
@@ -0,0 +1,192 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rhdl_core::{Kind, RHDLError, Synchronous};
+
+use super::describe::{CsrMap, Describe, Description, Param};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PortDirection {
+    Input,
+    Output,
+}
+
+/// A port of the top level of the design
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Port {
+    pub name: String,
+    pub direction: PortDirection,
+    pub bits: usize,
+}
+
+/// An instance of a component in the design
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Component {
+    /// The hierarchical path of the instance, like `top.fifo`
+    pub path: String,
+    pub core: String,
+    pub params: Vec<Param>,
+}
+
+/// The CSR map of an instance
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CsrSection {
+    pub path: String,
+    pub map: CsrMap,
+}
+
+/// The assignment of an interrupt source to a line
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Interrupt {
+    /// The hierarchical path of the source
+    pub source: String,
+    pub line: usize,
+}
+
+/// A machine readable summary of a design
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub top: String,
+    pub ports: Vec<Port>,
+    /// The instances, in depth first order
+    pub components: Vec<Component>,
+    pub csr_maps: Vec<CsrSection>,
+    /// The interrupt assignments, ordered by line
+    pub interrupts: Vec<Interrupt>,
+}
+
+// The top level fields of a port, or the port itself if
+// it is not a struct
+fn ports(name: &str, kind: Kind, direction: PortDirection) -> Vec<Port> {
+    match kind {
+        Kind::Struct(s) if !s.is_tuple_struct() => s
+            .fields
+            .iter()
+            .map(|f| Port {
+                name: format!("{name}.{}", f.name),
+                direction,
+                bits: f.kind.bits(),
+            })
+            .collect(),
+        Kind::Empty => vec![],
+        _ => vec![Port {
+            name: name.into(),
+            direction,
+            bits: kind.bits(),
+        }],
+    }
+}
+
+impl Manifest {
+    fn collect(&mut self, path: &str, description: Description) {
+        self.components.push(Component {
+            path: path.into(),
+            core: description.core,
+            params: description.params,
+        });
+        if let Some(map) = description.csr {
+            self.csr_maps.push(CsrSection {
+                path: path.into(),
+                map,
+            });
+        }
+        for (source, line) in description.interrupts {
+            self.interrupts.push(Interrupt {
+                source: format!("{path}.{source}"),
+                line,
+            });
+        }
+        for (name, child) in description.children {
+            self.collect(&format!("{path}.{name}"), child);
+        }
+    }
+    /// The manifest as (pretty printed) JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("A manifest can always be serialized")
+    }
+    /// The manifest as a markdown document
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "# Design manifest for `{}`\n", self.top);
+        let _ = writeln!(md, "## Ports\n");
+        let _ = writeln!(md, "| Port | Direction | Bits |");
+        let _ = writeln!(md, "|------|-----------|------|");
+        for port in &self.ports {
+            let _ = writeln!(
+                md,
+                "| `{}` | {:?} | {} |",
+                port.name, port.direction, port.bits
+            );
+        }
+        let _ = writeln!(md, "\n## Components\n");
+        let _ = writeln!(md, "| Instance | Core | Parameters |");
+        let _ = writeln!(md, "|----------|------|------------|");
+        for component in &self.components {
+            let params = component
+                .params
+                .iter()
+                .map(|p| format!("{} = {}", p.name, p.value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(
+                md,
+                "| `{}` | {} | {} |",
+                component.path, component.core, params
+            );
+        }
+        for section in &self.csr_maps {
+            let _ = writeln!(
+                md,
+                "\n## CSR map of `{}` (base {:#x})\n",
+                section.path, section.map.base
+            );
+            let _ = writeln!(md, "| Offset | Register | Bits | Access | Reset |");
+            let _ = writeln!(md, "|--------|----------|------|--------|-------|");
+            for reg in &section.map.registers {
+                let _ = writeln!(
+                    md,
+                    "| {:#x} | {} | {} | {:?} | {:#x} |",
+                    reg.offset, reg.name, reg.width, reg.access, reg.reset
+                );
+            }
+        }
+        if !self.interrupts.is_empty() {
+            let _ = writeln!(md, "\n## Interrupts\n");
+            let _ = writeln!(md, "| Line | Source |");
+            let _ = writeln!(md, "|------|--------|");
+            for irq in &self.interrupts {
+                let _ = writeln!(md, "| {} | `{}` |", irq.line, irq.source);
+            }
+        }
+        md
+    }
+}
+
+/// Collect the manifest of the design with `circuit` at the top
+/// level (with the instance name `top`).  The ports are taken from
+/// the circuit descriptor, and the components, CSR maps and interrupts
+/// from the [Describe] implementations.
+pub fn design_manifest<T: Synchronous + Describe>(circuit: &T) -> Result<Manifest, RHDLError> {
+    let descriptor = circuit.descriptor("top")?;
+    let mut manifest = Manifest {
+        top: descriptor.unique_name.clone(),
+        ports: vec![Port {
+            name: "clock_reset".into(),
+            direction: PortDirection::Input,
+            bits: 2,
+        }],
+        components: vec![],
+        csr_maps: vec![],
+        interrupts: vec![],
+    };
+    manifest
+        .ports
+        .extend(ports("i", descriptor.input_kind, PortDirection::Input));
+    manifest
+        .ports
+        .extend(ports("o", descriptor.output_kind, PortDirection::Output));
+    manifest.collect("top", circuit.describe());
+    manifest.interrupts.sort_by_key(|irq| irq.line);
+    Ok(manifest)
+}
@@ -0,0 +1,15 @@
+//! Design manifests
+//!
+//! A manifest is a machine readable summary of a design, listing the
+//! components it is built from (and their parameters), the CSR maps
+//! and interrupt assignments, and the ports of the top level.  Each
+//! component contributes a [Description] of itself through the
+//! [Describe] trait, which can be derived for assemblies of other
+//! components, and [design_manifest] collects them into a [Manifest].
+pub mod describe;
+pub mod manifest;
+
+pub use describe::{Access, CsrMap, CsrRegister, Describe, Description, Param, ParamValue};
+pub use manifest::{
+    Component, CsrSection, Interrupt, Manifest, Port, PortDirection, design_manifest,
+};
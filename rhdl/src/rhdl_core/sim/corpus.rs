@@ -0,0 +1,175 @@
+//! Regression corpora of recorded stimulus
+//!
+//! Randomized testing occasionally finds a stimulus that drives a core
+//! into a rare state.  Such streams are worth keeping, so that they can
+//! be replayed deterministically (and without the generator that found
+//! them) on every test run.  [record_corpus] writes a stream of values
+//! to a file, and [replay_corpus] reads it back.
+//!
+//! The values are written with [serde], so any [Digital] type that also
+//! derives `Serialize` and `Deserialize` can be recorded, including
+//! structs, enums and [Bits](crate::rhdl_bits::Bits) of any width.  A
+//! corpus file is gzip compressed JSON: a header holding the kind of the
+//! values (as a check against replaying a file with the wrong type) and
+//! their number, followed by the values themselves.
+//!
+//! The [corpus_tests] macro turns a directory of corpus files into
+//! tests, one per file, each of which replays the file and hands the
+//! values to a checking function.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl::core::sim::corpus::{record_corpus, replay_corpus};
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! let path = dir.path().join("wrap.json.gz");
+//! let stream = [(true, b8(0xFF)), (false, b8(0)), (true, b8(0x80))];
+//! record_corpus(&path, stream).unwrap();
+//! let replay = replay_corpus::<(bool, b8)>(&path).unwrap();
+//! assert_eq!(replay, stream);
+//!```
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+use crate::rhdl_core::Digital;
+
+pub use rhdl_macro::corpus_tests;
+
+/// The errors that can come from recording or replaying a corpus
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum CorpusError {
+    #[error("Unable to access corpus file {path}: {message}")]
+    Io { path: String, message: String },
+    #[error("Corpus file {path} is not valid: {message}")]
+    Format { path: String, message: String },
+    #[error("Corpus file {path} holds values of kind {found}, but {expected} was expected")]
+    #[diagnostic(help("The type of the stream has changed since the corpus was recorded"))]
+    Kind {
+        path: String,
+        expected: String,
+        found: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    kind: String,
+    samples: usize,
+}
+
+fn kind_of<T: Digital>() -> String {
+    format!("{:?}", T::static_kind())
+}
+
+/// Record a stream of values to the corpus file at `path`, replacing
+/// any existing file.  Returns the number of values recorded.
+pub fn record_corpus<T: Digital + Serialize>(
+    path: impl AsRef<std::path::Path>,
+    stream: impl IntoIterator<Item = T>,
+) -> Result<usize, CorpusError> {
+    let path = path.as_ref();
+    let io = |err: std::io::Error| CorpusError::Io {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    };
+    let format = |err: serde_json::Error| CorpusError::Format {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    };
+    let samples = stream.into_iter().collect::<Vec<_>>();
+    let header = Header {
+        kind: kind_of::<T>(),
+        samples: samples.len(),
+    };
+    let mut encoder = GzEncoder::new(
+        BufWriter::new(File::create(path).map_err(io)?),
+        Compression::best(),
+    );
+    serde_json::to_writer(&mut encoder, &header).map_err(format)?;
+    for sample in &samples {
+        writeln!(encoder).map_err(io)?;
+        serde_json::to_writer(&mut encoder, sample).map_err(format)?;
+    }
+    encoder.finish().map_err(io)?.flush().map_err(io)?;
+    Ok(samples.len())
+}
+
+/// Replay the values recorded in the corpus file at `path`.  The
+/// values must have been recorded from the same kind of type.
+pub fn replay_corpus<T: Digital + DeserializeOwned>(
+    path: impl AsRef<std::path::Path>,
+) -> Result<Vec<T>, CorpusError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| CorpusError::Io {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    })?;
+    let format = |err: serde_json::Error| CorpusError::Format {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    };
+    let mut de = serde_json::Deserializer::from_reader(GzDecoder::new(BufReader::new(file)));
+    let header = Header::deserialize(&mut de).map_err(format)?;
+    let expected = kind_of::<T>();
+    if header.kind != expected {
+        return Err(CorpusError::Kind {
+            path: path.display().to_string(),
+            expected,
+            found: header.kind,
+        });
+    }
+    let samples = (0..header.samples)
+        .map(|_| T::deserialize(&mut de))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(format)?;
+    de.end().map_err(format)?;
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rhdl_bits::alias::*;
+
+    fn round_trip<T: Digital + Serialize + DeserializeOwned + std::fmt::Debug>(
+        name: &str,
+        stream: Vec<T>,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        assert_eq!(record_corpus(&path, stream.clone()).unwrap(), stream.len());
+        assert_eq!(replay_corpus::<T>(&path).unwrap(), stream);
+    }
+
+    #[test]
+    fn test_round_trip_wide_bits() {
+        round_trip(
+            "bits.json.gz",
+            vec![
+                (b128(u128::MAX), s128(i128::MIN), b1(1)),
+                (b128(0), s128(i128::MAX), b1(0)),
+                (b128(1 << 127), s128(-1), b1(1)),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_kind_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bytes.json.gz");
+        record_corpus(&path, [b8(1), b8(2)]).unwrap();
+        let err = replay_corpus::<b4>(&path).unwrap_err();
+        assert!(matches!(err, CorpusError::Kind { .. }));
+        let err = replay_corpus::<b8>(dir.path().join("missing.json.gz")).unwrap_err();
+        assert!(matches!(err, CorpusError::Io { .. }));
+    }
+}
@@ -0,0 +1,356 @@
+use std::fmt::Display;
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::rhdl_core::{
+    hdl::ast::Direction,
+    types::path::{bit_range, leaf_paths, Path},
+    Kind,
+};
+
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum TestbenchError {
+    #[error("The first port of module {module} must be a 2 bit clock/reset input")]
+    ClockResetPort { module: String },
+    #[error("Module {module} has an input port, but the circuit has no inputs")]
+    UnexpectedInput { module: String },
+    #[error(
+        "Input port of module {module} is {found} bits wide, but {expected} bits were expected"
+    )]
+    InputWidth {
+        module: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error(
+        "Output port of module {module} is a {direction:?} of {found} bits, but an Output of {expected} bits was expected"
+    )]
+    OutputPort {
+        module: String,
+        direction: Direction,
+        expected: usize,
+        found: usize,
+    },
+}
+
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum VerilogError {
+    #[error("Icarus Verilog failed to compile the testbench ({status})")]
+    CompileFailed { status: String },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Mismatch(Box<TestbenchMismatch>),
+    #[error("Verilog testbench failed: {0}")]
+    Failed(String),
+    #[error("Verilog testbench produced no result")]
+    NoResult,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum WatchpointError {
+    #[error("Watchpoint {watchpoint} watches signal {signal}, which the circuit does not trace")]
+    UnknownSignal { watchpoint: String, signal: String },
+    #[error(
+        "Simulation aborted by watchpoint {watchpoint} at cycle {cycle} (time {time}): {source}"
+    )]
+    Aborted {
+        watchpoint: String,
+        cycle: u64,
+        time: u64,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Assertion {assertion} on {instance} failed at cycle {cycle} (time {time})")]
+    AssertionViolation {
+        assertion: String,
+        instance: String,
+        cycle: u64,
+        time: u64,
+    },
+    #[error(
+        "Watchpoint {watchpoint} expects signal {signal} to be {expected} bits wide, but it is {found} bits"
+    )]
+    #[diagnostic(help("Check the type given to the watchpoint, and the name of the signal"))]
+    WidthMismatch {
+        watchpoint: String,
+        signal: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum RunError {
+    #[error("The simulation was cancelled at cycle {cycle} (time {time})")]
+    Cancelled { cycle: u64, time: u64 },
+}
+
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum SessionError {
+    #[error("The condition was not met within {max_cycles} cycles (at cycle {cycle})")]
+    #[diagnostic(help("Check the inputs, or allow more cycles"))]
+    Timeout { max_cycles: u64, cycle: u64 },
+}
+
+/// A leaf signal of the output that differs between the
+/// Verilog simulation and the Rust model.  The values are
+/// binary strings (MSB first), which may contain `x` or `z`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalMismatch {
+    path: Path,
+    expected: String,
+    found: String,
+}
+
+impl SignalMismatch {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+}
+
+impl std::fmt::Display for SignalMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?}: expected {} found {}",
+            self.path, self.expected, self.found
+        )
+    }
+}
+
+/// The output of the Verilog simulation differs from the
+/// output of the Rust model for a test case.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Verilog output 0x{found} does not match the expected 0x{expected} in {case}")]
+pub struct TestbenchMismatch {
+    case: String,
+    test: Option<usize>,
+    time: Option<u64>,
+    expected: String,
+    found: String,
+    signals: Vec<SignalMismatch>,
+    testbench: String,
+    span: Option<SourceSpan>,
+}
+
+impl Diagnostic for TestbenchMismatch {
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.span
+            .map(|_| &self.testbench as &dyn miette::SourceCode)
+    }
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        if self.signals.is_empty() {
+            return None;
+        }
+        let signals = self
+            .signals
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(Box::new(format!("Mismatched signals: {signals}")))
+    }
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let span = self.span?;
+        Some(Box::new(std::iter::once(
+            miette::LabeledSpan::new_primary_with_span(Some("this check failed".into()), span),
+        )))
+    }
+}
+
+impl TestbenchMismatch {
+    /// The description of the failing test case, as written in the testbench
+    pub fn case(&self) -> &str {
+        &self.case
+    }
+    /// The index of the failing test case, if known
+    pub fn test(&self) -> Option<usize> {
+        self.test
+    }
+    /// The simulation time of the failing test case, if known
+    pub fn time(&self) -> Option<u64> {
+        self.time
+    }
+    /// The expected output (in hex)
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+    /// The output of the Verilog simulation (in hex)
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+    /// The output signals that differ.  Empty if the type of
+    /// the output is not known.
+    pub fn signals(&self) -> &[SignalMismatch] {
+        &self.signals
+    }
+}
+
+// Convert a hex value printed by Verilog into a binary string of `width`
+// bits.  Unknown digits become `x` (or `z`) bits.
+fn hex_to_binary(hex: &str, width: usize) -> String {
+    let binary = hex
+        .chars()
+        .map(|c| match c {
+            'x' | 'X' => "xxxx".to_string(),
+            'z' | 'Z' => "zzzz".to_string(),
+            c => format!("{:04b}", c.to_digit(16).unwrap_or(0)),
+        })
+        .collect::<String>();
+    if binary.len() >= width {
+        binary[binary.len() - width..].to_string()
+    } else {
+        "0".repeat(width - binary.len()) + &binary
+    }
+}
+
+fn signal_mismatches(kind: &Kind, expected: &str, found: &str) -> Vec<SignalMismatch> {
+    let width = kind.bits();
+    let expected = hex_to_binary(expected, width);
+    let found = hex_to_binary(found, width);
+    leaf_paths(kind, Path::default())
+        .into_iter()
+        .filter_map(|path| {
+            let (range, _) = bit_range(*kind, &path).ok()?;
+            // The strings are MSB first, while the range is LSB first
+            let slice = (width - range.end)..(width - range.start);
+            let expected = &expected[slice.clone()];
+            let found = &found[slice];
+            (expected != found).then(|| SignalMismatch {
+                path,
+                expected: expected.into(),
+                found: found.into(),
+            })
+        })
+        .collect()
+}
+
+impl VerilogError {
+    // Parse a failure reported by the testbench.  Assertions are printed as
+    // `ASSERTION FAILED 0x<found> !== 0x<expected> CASE <case>`.
+    pub(crate) fn from_failure(line: &str, testbench: &str, output: Option<&Kind>) -> Self {
+        let parse = || -> Option<TestbenchMismatch> {
+            let rest = line.trim().strip_prefix("ASSERTION FAILED 0x")?;
+            let (found, rest) = rest.split_once(" !== 0x")?;
+            let (expected, case) = rest.split_once(" CASE ")?;
+            let mut words = case.split_whitespace();
+            let test = (words.next() == Some("Test"))
+                .then(|| words.next()?.parse().ok())
+                .flatten();
+            let time = case
+                .split_once(" at time ")
+                .and_then(|(_, time)| time.parse().ok());
+            let span = testbench
+                .find(&format!("CASE {case}\""))
+                .and_then(|ndx| testbench[..ndx].rfind("if ("))
+                .map(|start| {
+                    let end = testbench[start..].find(')').map_or(4, |n| n + 1);
+                    SourceSpan::new(start.into(), end)
+                });
+            Some(TestbenchMismatch {
+                case: case.into(),
+                test,
+                time,
+                expected: expected.into(),
+                found: found.into(),
+                signals: output
+                    .map(|kind| signal_mismatches(kind, expected, found))
+                    .unwrap_or_default(),
+                testbench: testbench.into(),
+                span,
+            })
+        };
+        match parse() {
+            Some(mismatch) => VerilogError::Mismatch(Box::new(mismatch)),
+            None => VerilogError::Failed(line.trim().into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    use super::*;
+
+    // The output is (valid, data, flags)
+    type Out = (bool, b8, [bool; 2]);
+
+    const TESTBENCH: &str = "module testbench();
+    initial begin
+        if (o !== rust_out) begin
+            $display(\"ASSERTION FAILED 0x%0h !== 0x%0h CASE Test 3 at time 200\", o, rust_out);
+        end
+    end
+endmodule";
+
+    fn out(valid: bool, data: u8, flags: [bool; 2]) -> String {
+        let bits = (valid, b8(data as u128), flags).bin();
+        let value = bits
+            .iter()
+            .enumerate()
+            .fold(0u32, |v, (n, b)| v | ((*b as u32) << n));
+        format!("{value:x}")
+    }
+
+    #[test]
+    fn test_mismatch_is_decoded() {
+        let line = format!(
+            "ASSERTION FAILED 0x{} !== 0x{} CASE Test 3 at time 200",
+            out(true, 0x5A, [true, false]),
+            out(true, 0x5B, [false, false])
+        );
+        let kind = Out::static_kind();
+        let VerilogError::Mismatch(mismatch) =
+            VerilogError::from_failure(&line, TESTBENCH, Some(&kind))
+        else {
+            panic!("Expected a mismatch");
+        };
+        assert_eq!(mismatch.test(), Some(3));
+        assert_eq!(mismatch.time(), Some(200));
+        assert_eq!(mismatch.case(), "Test 3 at time 200");
+        let signals = mismatch.signals();
+        assert_eq!(signals.len(), 2);
+        assert_eq!(signals[0].path(), &Path::default().tuple_index(1));
+        assert_eq!(signals[0].expected(), "01011011");
+        assert_eq!(signals[0].found(), "01011010");
+        assert_eq!(signals[1].path(), &Path::default().tuple_index(2).index(0));
+        assert_eq!((signals[1].expected(), signals[1].found()), ("0", "1"));
+        // The label points at the failing check
+        let span = mismatch.span.unwrap();
+        assert_eq!(
+            &TESTBENCH[span.offset()..span.offset() + span.len()],
+            "if (o !== rust_out)"
+        );
+    }
+
+    #[test]
+    fn test_unknown_bits_are_reported() {
+        let line = "ASSERTION FAILED 0xx !== 0x3 CASE 7";
+        let kind = Kind::make_bits(4);
+        let VerilogError::Mismatch(mismatch) =
+            VerilogError::from_failure(line, TESTBENCH, Some(&kind))
+        else {
+            panic!("Expected a mismatch");
+        };
+        assert_eq!((mismatch.test(), mismatch.time()), (None, None));
+        assert_eq!(mismatch.signals()[0].found(), "xxxx");
+        assert_eq!(mismatch.signals()[0].expected(), "0011");
+        assert!(mismatch.span.is_none());
+    }
+
+    #[test]
+    fn test_other_failures_are_kept() {
+        let err = VerilogError::from_failure("FAILED: tx is 1, expected 0", TESTBENCH, None);
+        assert_eq!(
+            err,
+            VerilogError::Failed("FAILED: tx is 1, expected 0".into())
+        );
+    }
+}
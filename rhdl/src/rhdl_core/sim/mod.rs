@@ -1,4 +1,7 @@
 pub mod clock_pos_edge;
+pub mod corpus;
+pub mod error;
+pub mod fst;
 pub mod merge;
 pub mod probe;
 pub mod reset;
@@ -6,6 +9,7 @@ pub mod run;
 pub mod test_module;
 pub mod testbench;
 pub mod vcd;
+pub mod vector;
 
 #[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
 pub enum ResetOrData<T> {
@@ -0,0 +1,278 @@
+//! Merged event logs for simulation post-processing
+//!
+//! A design with several output interfaces (a UART, an interrupt line,
+//! a stream) is hard to check with a separate pass over the trace for
+//! each one, since the interesting properties are about how the events
+//! on those interfaces are ordered.  An [EventLog] collects the events
+//! found by any number of extractors into a single, time ordered log.
+//!
+//! An extractor is a closure that is shown each sample of a trace, and
+//! returns `Some(event)` when something of interest happens.  The events
+//! are of a single (user defined) type, typically an enum with a variant
+//! for each kind of event, so that the payloads stay typed.  Each
+//! extractor can read a different trace (for example, the traces of
+//! the domains of a multi-clock run), as long as they share a time base.
+//! The extractors are `FnMut`, so they can keep state, for example to
+//! report only the rising edge of a level signal.
+//!
+//! Events with the same time stay in the order of their extractors
+//! (and, for one extractor, in the order of the trace).
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl::core::sim::probe::event_log::EventLog;
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Event {
+//!     Odd(u8),
+//!     Big,
+//! }
+//!
+//! let trace = (0..10).map(|n| timed_sample(n * 10, b8(n as u128)));
+//! let log = EventLog::new()
+//!     .extract(trace.clone(), |s| (s.value.raw() % 2 == 1).then(|| Event::Odd(s.value.raw() as u8)))
+//!     .extract(trace, |s| (s.value == b8(8)).then_some(Event::Big));
+//! // The Big event at time 80 is found after the odd value at 70,
+//! // and within 10 time units of it
+//! let matches = log.find_after(|e| matches!(e, Event::Odd(7)), |e| *e == Event::Big, 10);
+//! assert_eq!(matches[0].delay(), Some(10));
+//!```
+use std::ops::Range;
+
+use crate::rhdl_core::{Digital, TimedSample};
+
+/// An event in an [EventLog]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event<E> {
+    /// The time of the sample the event was extracted from
+    pub time: u64,
+    /// The index of the extractor that found the event
+    pub source: usize,
+    pub payload: E,
+}
+
+/// The result of a [EventLog::find_after] query
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Match<'a, E> {
+    /// The event that started the search
+    pub cause: &'a Event<E>,
+    /// The first matching event in the window, if any
+    pub effect: Option<&'a Event<E>>,
+}
+
+impl<E> Match<'_, E> {
+    /// The time from the cause to the effect, if it was found
+    pub fn delay(&self) -> Option<u64> {
+        self.effect.map(|e| e.time - self.cause.time)
+    }
+    pub fn is_found(&self) -> bool {
+        self.effect.is_some()
+    }
+}
+
+/// A time ordered log of the events extracted from one or more traces
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventLog<E> {
+    events: Vec<Event<E>>,
+    sources: usize,
+}
+
+impl<E> Default for EventLog<E> {
+    fn default() -> Self {
+        Self {
+            events: vec![],
+            sources: 0,
+        }
+    }
+}
+
+impl<E> EventLog<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add the events found by `extractor` in `trace` to the log
+    pub fn extract<S, I, F>(mut self, trace: I, mut extractor: F) -> Self
+    where
+        S: Digital,
+        I: IntoIterator<Item = TimedSample<S>>,
+        F: FnMut(&TimedSample<S>) -> Option<E>,
+    {
+        let source = self.sources;
+        self.sources += 1;
+        self.events.extend(trace.into_iter().filter_map(|sample| {
+            extractor(&sample).map(|payload| Event {
+                time: sample.time,
+                source,
+                payload,
+            })
+        }));
+        // The sort is stable, so ties keep the order of the extractors
+        self.events.sort_by_key(|e| e.time);
+        self
+    }
+    /// All of the events, in time order
+    pub fn events(&self) -> &[Event<E>] {
+        &self.events
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Event<E>> {
+        self.events.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+    /// The payloads of the events, in time order
+    pub fn payloads(&self) -> impl Iterator<Item = &E> {
+        self.events.iter().map(|e| &e.payload)
+    }
+    /// The events with times in the given range
+    pub fn window(&self, times: Range<u64>) -> &[Event<E>] {
+        let start = self.events.partition_point(|e| e.time < times.start);
+        let end = self.events.partition_point(|e| e.time < times.end);
+        &self.events[start..end.max(start)]
+    }
+    /// The first event that satisfies `pred`
+    pub fn first(&self, pred: impl Fn(&E) -> bool) -> Option<&Event<E>> {
+        self.events.iter().find(|e| pred(&e.payload))
+    }
+    /// For each event that satisfies `cause`, find the first later event
+    /// (in log order) that satisfies `effect`, and is no more than
+    /// `within` time units after it.  There is one [Match] per cause,
+    /// whether or not an effect was found.
+    pub fn find_after(
+        &self,
+        cause: impl Fn(&E) -> bool,
+        effect: impl Fn(&E) -> bool,
+        within: u64,
+    ) -> Vec<Match<'_, E>> {
+        self.events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| cause(&e.payload))
+            .map(|(ndx, c)| Match {
+                cause: c,
+                effect: self.events[ndx + 1..]
+                    .iter()
+                    .take_while(|e| e.time - c.time <= within)
+                    .find(|e| effect(&e.payload)),
+            })
+            .collect()
+    }
+    /// Check that the first events to satisfy each of the predicates
+    /// occur in the order given.  Returns the times of those events,
+    /// or `None` if any of them is missing or out of order.
+    pub fn first_in_order(&self, preds: &[&dyn Fn(&E) -> bool]) -> Option<Vec<u64>> {
+        let positions = preds
+            .iter()
+            .map(|pred| self.events.iter().position(|e| pred(&e.payload)))
+            .collect::<Option<Vec<_>>>()?;
+        positions
+            .windows(2)
+            .all(|w| w[0] < w[1])
+            .then(|| positions.iter().map(|&p| self.events[p].time).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rhdl_bits::alias::*;
+    use crate::rhdl_core::timed_sample;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Ev {
+        Tick(u8),
+        Irq,
+        Byte(u8),
+    }
+
+    // Two traces with different sample times, as from two clock domains
+    fn log() -> EventLog<Ev> {
+        let fast = (0..20).map(|n| timed_sample(n * 10, b8(n as u128)));
+        let slow = (0..5).map(|n| timed_sample(n * 45, b8(0x40 + n as u128)));
+        EventLog::new()
+            .extract(fast.clone(), |s| {
+                (s.value.raw() % 5 == 0).then(|| Ev::Tick(s.value.raw() as u8))
+            })
+            .extract(fast, |s| (s.value == b8(7)).then_some(Ev::Irq))
+            .extract(slow, |s| Some(Ev::Byte(s.value.raw() as u8)))
+    }
+
+    #[test]
+    fn test_merged_in_time_order() {
+        let log = log();
+        let times = log.iter().map(|e| e.time).collect::<Vec<_>>();
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(log.len(), 4 + 1 + 5);
+        // Ties keep the order of the extractors
+        assert_eq!(
+            log.window(0..1)
+                .iter()
+                .map(|e| e.source)
+                .collect::<Vec<_>>(),
+            [0, 2]
+        );
+        assert_eq!(
+            log.payloads().cloned().collect::<Vec<_>>(),
+            [
+                Ev::Tick(0),
+                Ev::Byte(0x40),
+                Ev::Byte(0x41),
+                Ev::Tick(5),
+                Ev::Irq,
+                Ev::Byte(0x42),
+                Ev::Tick(10),
+                Ev::Byte(0x43),
+                Ev::Tick(15),
+                Ev::Byte(0x44)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_window() {
+        let log = log();
+        let window = log.window(50..100);
+        assert_eq!(window.first().unwrap().payload, Ev::Tick(5));
+        assert_eq!(window.last().unwrap().payload, Ev::Byte(0x42));
+        assert!(log.window(300..400).is_empty());
+    }
+
+    #[test]
+    fn test_find_after() {
+        let log = log();
+        let is_tick = |e: &Ev| matches!(e, Ev::Tick(_));
+        let matches = log.find_after(is_tick, |e| matches!(e, Ev::Byte(_)), 20);
+        assert_eq!(matches.len(), 4);
+        // Tick(0) at 0 is followed by the byte at the same time, Tick(5) at 50 by
+        // the byte at 90 (too late), Tick(10) at 100 by the byte at 135 (too late),
+        // and Tick(15) at 150 by the byte at 180 (too late)
+        assert_eq!(
+            matches.iter().map(|m| m.delay()).collect::<Vec<_>>(),
+            [Some(0), None, None, None]
+        );
+        let matches = log.find_after(is_tick, |e| *e == Ev::Irq, 20);
+        assert!(matches[1].is_found());
+        assert_eq!(matches[1].cause.payload, Ev::Tick(5));
+        assert_eq!(matches[1].effect.unwrap().time, 70);
+        assert_eq!(matches.iter().filter(|m| m.is_found()).count(), 1);
+    }
+
+    #[test]
+    fn test_first_in_order() {
+        let log = log();
+        let irq = |e: &Ev| *e == Ev::Irq;
+        let tick = |e: &Ev| *e == Ev::Tick(10);
+        let byte = |e: &Ev| *e == Ev::Byte(0x44);
+        assert_eq!(
+            log.first_in_order(&[&irq, &tick, &byte]),
+            Some(vec![70, 100, 180])
+        );
+        assert_eq!(log.first_in_order(&[&tick, &irq]), None);
+        assert_eq!(log.first_in_order(&[&irq, &|e| *e == Ev::Tick(3)]), None);
+    }
+}
@@ -5,6 +5,7 @@ use crate::rhdl_core::{Clock, ClockReset, Digital, TimedSample};
 use super::{
     edges::{edge_time, EdgeTime},
     glitch_check::{glitch_check, GlitchCheck},
+    outputs::{outputs, Outputs},
     sample_at_pos_edge::{sample_at_pos_edge, SampleAtPosEdge},
     synchronous_sample::{synchronous_sample, SynchronousSample},
     vcd_file::{vcd_file, VCDFile},
@@ -77,6 +78,23 @@ pub trait SynchronousProbeExt<I, P, O> {
         I: Iterator<Item = TimedSample<(ClockReset, P, O)>>,
         P: Digital,
         O: Digital;
+
+    /// Keep only the outputs of each sample
+    fn outputs(self) -> Outputs<I>
+    where
+        Self: Sized,
+        I: Iterator<Item = TimedSample<(ClockReset, P, O)>>,
+        P: Digital,
+        O: Digital;
+
+    /// Replace the contents of `buffer` with the outputs of each sample.
+    /// Passing the same buffer to several runs reuses its allocation.
+    fn collect_outputs_into(self, buffer: &mut Vec<O>)
+    where
+        Self: Sized,
+        I: Iterator<Item = TimedSample<(ClockReset, P, O)>>,
+        P: Digital,
+        O: Digital;
 }
 
 impl<I, P, O> SynchronousProbeExt<I, P, O> for I {
@@ -89,4 +107,25 @@ impl<I, P, O> SynchronousProbeExt<I, P, O> for I {
     {
         synchronous_sample(self)
     }
+
+    fn outputs(self) -> Outputs<I>
+    where
+        Self: Sized,
+        I: Iterator<Item = TimedSample<(ClockReset, P, O)>>,
+        P: Digital,
+        O: Digital,
+    {
+        outputs(self)
+    }
+
+    fn collect_outputs_into(self, buffer: &mut Vec<O>)
+    where
+        Self: Sized,
+        I: Iterator<Item = TimedSample<(ClockReset, P, O)>>,
+        P: Digital,
+        O: Digital,
+    {
+        buffer.clear();
+        buffer.extend(outputs(self));
+    }
 }
@@ -1,6 +1,8 @@
 pub mod edges;
+pub mod event_log;
 pub mod ext;
 pub mod glitch_check;
+pub mod outputs;
 pub mod sample_at_pos_edge;
 pub mod synchronous_sample;
 pub mod vcd_file;
@@ -0,0 +1,105 @@
+use crate::rhdl_core::{ClockReset, Digital, TimedSample};
+
+/// This probe projects a stream of samples of a synchronous circuit
+/// onto just the outputs, so that the clock, reset and inputs are
+/// dropped as each sample is read, rather than carried along.  It is
+/// usually applied after [synchronous_sample](super::synchronous_sample).
+pub struct Outputs<S> {
+    stream: S,
+}
+
+pub fn outputs<S>(stream: S) -> Outputs<S>
+where
+    S: Iterator,
+{
+    Outputs { stream }
+}
+
+impl<S, I, O> Iterator for Outputs<S>
+where
+    S: Iterator<Item = TimedSample<(ClockReset, I, O)>>,
+    I: Digital,
+    O: Digital,
+{
+    type Item = O;
+
+    fn next(&mut self) -> Option<O> {
+        self.stream.next().map(|t| t.value.2)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Borrowing views of a recorded trace of a synchronous circuit.
+/// These allow a trace that is kept around (and checked several
+/// times) to be read without copying the samples out of it.
+pub trait SynchronousTraceExt<I: Digital, O: Digital> {
+    /// The inputs of each sample in the trace
+    fn inputs_ref(&self) -> impl Iterator<Item = &I>;
+    /// The outputs of each sample in the trace
+    fn outputs_ref(&self) -> impl Iterator<Item = &O>;
+}
+
+impl<I, O> SynchronousTraceExt<I, O> for [TimedSample<(ClockReset, I, O)>]
+where
+    I: Digital,
+    O: Digital,
+{
+    fn inputs_ref(&self) -> impl Iterator<Item = &I> {
+        self.iter().map(|t| &t.value.1)
+    }
+
+    fn outputs_ref(&self) -> impl Iterator<Item = &O> {
+        self.iter().map(|t| &t.value.2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::ext::SynchronousProbeExt;
+    use super::*;
+    use crate::rhdl_bits::alias::*;
+    use crate::rhdl_core::sim::{clock_pos_edge::ClockPosEdgeExt, reset::TimedStreamExt};
+
+    fn trace() -> impl Iterator<Item = TimedSample<(ClockReset, b8, b16)>> + Clone {
+        (0..20)
+            .map(b8)
+            .without_reset()
+            .clock_pos_edge(100)
+            .map(|t| t.map(|v| (v.0, v.1, b16(v.1.raw() * 3))))
+    }
+
+    #[test]
+    fn test_outputs_match_owned_path() {
+        let owned = trace()
+            .synchronous_sample()
+            .map(|t| t.value.2)
+            .collect::<Vec<_>>();
+        let projected = trace().synchronous_sample().outputs().collect::<Vec<_>>();
+        assert_eq!(projected, owned);
+        // Reusing a buffer replaces its contents, and keeps its allocation
+        let mut buffer = vec![b16(1); 100];
+        let capacity = buffer.capacity();
+        trace()
+            .synchronous_sample()
+            .collect_outputs_into(&mut buffer);
+        assert_eq!(buffer, owned);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_borrowed_views_of_trace() {
+        let samples = trace().synchronous_sample().collect::<Vec<_>>();
+        let inputs = samples.inputs_ref().copied().collect::<Vec<_>>();
+        assert_eq!(inputs, (0..20).map(b8).collect::<Vec<_>>());
+        assert!(
+            samples
+                .outputs_ref()
+                .zip(&samples)
+                .all(|(o, t)| std::ptr::eq(o, &t.value.2))
+        );
+    }
+}
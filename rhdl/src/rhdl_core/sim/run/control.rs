@@ -0,0 +1,252 @@
+//! Progress reporting and cancellation for long simulations
+//!
+//! A long simulation (a soak test of a FIFO, say) gives no feedback until
+//! it finishes, and can not be stopped cleanly from outside.  A
+//! [RunControl] adds both to a synchronous simulation, without changing
+//! the samples it produces:
+//!
+//! - A progress callback is called every `N` cycles (positive clock edges)
+//!   with a [Progress] report of the cycles completed, the wall clock time
+//!   taken so far, and the current simulation time.
+//! - A [CancelToken] can be cancelled from anywhere (another thread, a
+//!   timeout, or the progress callback itself).  The simulation checks the
+//!   token before each positive clock edge, and stops there, so the samples
+//!   produced always cover whole cycles.
+//!
+//! As for [run_with_hooks](super::hooks::RunWithHooksExt::run_with_hooks),
+//! the samples produced by [run_controlled](RunControlledExt::run_controlled)
+//! are wrapped in a `Result`.  If the run is cancelled, the last item is a
+//! [RunError::Cancelled] error.  To keep the samples simulated before the
+//! cancellation, use [collect_partial](RunControlled::collect_partial), which
+//! returns them along with the error in a [PartialRun].
+//!
+//! The plain `run` does none of this, so it costs nothing when it is not
+//! used.  A controlled run only checks the token on each clock edge, and
+//! reads the wall clock when a progress report is due.
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::rhdl_core::{
+    error::rhdl_error,
+    sim::{
+        error::RunError,
+        run::synchronous::{run_synchronous, RunSynchronous},
+    },
+    ClockReset, RHDLError, Synchronous, SynchronousIO, TimedSample,
+};
+
+type Sample<T> = TimedSample<(ClockReset, <T as SynchronousIO>::I, <T as SynchronousIO>::O)>;
+
+type NoInputs<T> = std::iter::Empty<TimedSample<(ClockReset, <T as SynchronousIO>::I)>>;
+
+type Callback = Box<dyn FnMut(&Progress)>;
+
+/// A flag that asks a controlled simulation to stop.  Clones of the
+/// token share the flag, so one can be kept to cancel the run, while
+/// another is given to the [RunControl].
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a token that has not been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Ask the simulation to stop at the next positive clock edge
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    /// True if the token has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A report on the progress of a simulation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// The number of positive clock edges simulated so far
+    pub cycles: u64,
+    /// The wall clock time since the simulation started
+    pub elapsed: Duration,
+    /// The simulation time of the latest sample
+    pub time: u64,
+}
+
+/// The progress reporting and cancellation for a simulation
+#[derive(Default)]
+pub struct RunControl {
+    every: u64,
+    callback: Option<Callback>,
+    cancel: Option<CancelToken>,
+}
+
+impl RunControl {
+    /// Call `callback` with a [Progress] report every `every` cycles
+    pub fn on_progress(self, every: u64, callback: impl FnMut(&Progress) + 'static) -> Self {
+        assert!(
+            every > 0,
+            "The progress interval must be at least one cycle"
+        );
+        Self {
+            every,
+            callback: Some(Box::new(callback)),
+            ..self
+        }
+    }
+    /// Stop the simulation when `token` is cancelled
+    pub fn with_cancel(self, token: CancelToken) -> Self {
+        Self {
+            cancel: Some(token),
+            ..self
+        }
+    }
+}
+
+/// The samples of a simulation that did not run to the end, and
+/// the reason it stopped
+pub struct PartialRun<S> {
+    /// The samples simulated before the run stopped
+    pub trace: Vec<S>,
+    /// The reason the run stopped
+    pub error: RHDLError,
+}
+
+impl<S> std::fmt::Debug for PartialRun<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PartialRun")
+            .field("trace", &format_args!("[{} samples]", self.trace.len()))
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<S> std::fmt::Display for PartialRun<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (after {} samples)", self.error, self.trace.len())
+    }
+}
+
+impl<S> std::error::Error for PartialRun<S> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[must_use = "To run the simulation, you must exhaust the iterator"]
+pub struct RunControlled<'a, T: Synchronous, I> {
+    run: RunSynchronous<'a, T, NoInputs<T>, T::S>,
+    inputs: I,
+    control: RunControl,
+    start: Option<Instant>,
+    cycle: u64,
+    clock: bool,
+    done: bool,
+}
+
+impl<T, I> RunControlled<'_, T, I>
+where
+    T: Synchronous,
+    I: Iterator<Item = TimedSample<(ClockReset, <T as SynchronousIO>::I)>>,
+{
+    /// Run the simulation to the end, and collect the samples.  If
+    /// the run stops early, the samples simulated up to that point
+    /// are returned with the error.
+    pub fn collect_partial(self) -> Result<Vec<Sample<T>>, PartialRun<Sample<T>>> {
+        let mut trace = vec![];
+        for sample in self {
+            match sample {
+                Ok(sample) => trace.push(sample),
+                Err(error) => return Err(PartialRun { trace, error }),
+            }
+        }
+        Ok(trace)
+    }
+}
+
+impl<T, I> Iterator for RunControlled<'_, T, I>
+where
+    T: Synchronous,
+    I: Iterator<Item = TimedSample<(ClockReset, <T as SynchronousIO>::I)>>,
+{
+    type Item = Result<Sample<T>, RHDLError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let Some(sample) = self.inputs.next() else {
+            self.done = true;
+            return None;
+        };
+        let clock = sample.value.0.clock.raw();
+        let edge = clock && !self.clock;
+        self.clock = clock;
+        if edge {
+            if self
+                .control
+                .cancel
+                .as_ref()
+                .is_some_and(|t| t.is_cancelled())
+            {
+                self.done = true;
+                return Some(Err(rhdl_error(RunError::Cancelled {
+                    cycle: self.cycle,
+                    time: sample.time,
+                })));
+            }
+            self.cycle += 1;
+        }
+        let sample = self.run.step(sample);
+        if let Some(callback) = &mut self.control.callback
+            && edge
+            && self.cycle.is_multiple_of(self.control.every)
+        {
+            callback(&Progress {
+                cycles: self.cycle,
+                elapsed: start.elapsed(),
+                time: sample.time,
+            });
+        }
+        Some(Ok(sample))
+    }
+}
+
+pub trait RunControlledExt<I>: Synchronous + Sized {
+    fn run_controlled(
+        &self,
+        iter: I,
+        control: RunControl,
+    ) -> Result<RunControlled<'_, Self, <I as IntoIterator>::IntoIter>, RHDLError>
+    where
+        I: IntoIterator;
+}
+
+impl<T, I> RunControlledExt<I> for T
+where
+    T: Synchronous,
+    I: IntoIterator<Item = TimedSample<(ClockReset, <T as SynchronousIO>::I)>>,
+{
+    fn run_controlled(
+        &self,
+        iter: I,
+        control: RunControl,
+    ) -> Result<RunControlled<'_, Self, <I as IntoIterator>::IntoIter>, RHDLError> {
+        self.yosys_check()?;
+        Ok(RunControlled {
+            run: run_synchronous(self, std::iter::empty()),
+            inputs: iter.into_iter(),
+            control,
+            start: None,
+            cycle: 0,
+            clock: false,
+            done: false,
+        })
+    }
+}
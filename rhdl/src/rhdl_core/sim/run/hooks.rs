@@ -0,0 +1,391 @@
+//! Watchpoints for synchronous simulations
+//!
+//! A [Hooks] collection holds a set of watchpoints, each of which is a
+//! predicate on either the output of the circuit, or on one of its traced
+//! signals (named hierarchically, like `top.fifo.dff.output`), and an action.
+//! The predicates are checked on the samples taken while the clock is low,
+//! which hold the settled values that are captured by the next positive
+//! edge (these are the samples produced by `synchronous_sample`).  Values that
+//! only appear briefly around the clock edge do not fire a watchpoint.
+//! The action is called each time the predicate _becomes_ true, and is given
+//! a [HookContext] that describes the current cycle, and can be used to
+//! dump the traced signals, capture a window of the simulation into a VCD
+//! file, or abort the simulation with an error.
+//!
+//! An assertion (see [Hooks::assert_signal]) is a watchpoint with a fixed
+//! action: the run ends with a [WatchpointError::AssertionViolation] at the
+//! first sample where the traced signal does not satisfy the predicate.
+//!
+//! Watchpoints only observe the simulation, so the samples produced by
+//! [run_with_hooks](RunWithHooksExt::run_with_hooks) are the same as those
+//! produced by `run`, but wrapped in a `Result`.  If the simulation is aborted,
+//! the error is the last item produced.
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
+
+use crate::rhdl_core::{
+    error::rhdl_error,
+    sim::{
+        error::WatchpointError,
+        run::synchronous::{run_synchronous, RunSynchronous},
+    },
+    trace::db::{with_trace_db, TraceDBGuard},
+    trace_init_db, ClockReset, Digital, RHDLError, Synchronous, SynchronousIO, TimedSample,
+    TypedBits,
+};
+
+type Sample<T> = TimedSample<(ClockReset, <T as SynchronousIO>::I, <T as SynchronousIO>::O)>;
+
+type Action<T> = Box<dyn FnMut(&mut HookContext<'_, T>)>;
+
+pub(crate) enum Trigger<O> {
+    Output(Box<dyn FnMut(&O) -> bool>),
+    Signal {
+        signal: String,
+        predicate: Box<dyn FnMut(&TypedBits) -> bool>,
+    },
+    // Fires when the predicate does _not_ hold for the signal,
+    // which must be `width` bits wide
+    Assert {
+        signal: String,
+        width: usize,
+        predicate: Box<dyn FnMut(&TypedBits) -> bool>,
+    },
+}
+
+impl<O> Trigger<O> {
+    // Evaluate the predicate for the current sample.  The watchpoint
+    // `name` is used to report a signal the circuit does not trace.
+    pub(crate) fn check(&mut self, name: &str, output: &O) -> Result<bool, RHDLError> {
+        match self {
+            Trigger::Output(predicate) => Ok(predicate(output)),
+            Trigger::Signal { signal, predicate } => Ok(predicate(&Self::value(name, signal)?)),
+            Trigger::Assert {
+                signal,
+                width,
+                predicate,
+            } => {
+                let value = Self::value(name, signal)?;
+                if value.bits.len() != *width {
+                    return Err(rhdl_error(WatchpointError::WidthMismatch {
+                        watchpoint: name.into(),
+                        signal: signal.clone(),
+                        expected: *width,
+                        found: value.bits.len(),
+                    }));
+                }
+                Ok(!predicate(&value))
+            }
+        }
+    }
+    fn value(name: &str, signal: &str) -> Result<TypedBits, RHDLError> {
+        let mut value = None;
+        with_trace_db(|db| value = db.signal(signal));
+        value.ok_or_else(|| {
+            rhdl_error(WatchpointError::UnknownSignal {
+                watchpoint: name.into(),
+                signal: signal.into(),
+            })
+        })
+    }
+}
+
+struct Watchpoint<T: Synchronous> {
+    name: String,
+    trigger: Trigger<T::O>,
+    action: Action<T>,
+    triggered: bool,
+}
+
+/// A set of watchpoints for a simulation of the circuit `T`
+pub struct Hooks<T: Synchronous> {
+    watchpoints: Vec<Watchpoint<T>>,
+}
+
+impl<T: Synchronous> Default for Hooks<T> {
+    fn default() -> Self {
+        Self {
+            watchpoints: vec![],
+        }
+    }
+}
+
+impl<T: Synchronous> Hooks<T> {
+    /// Call `action` whenever `predicate` on the output of the circuit becomes true
+    pub fn on_output<P, A>(mut self, name: &str, predicate: P, action: A) -> Self
+    where
+        P: FnMut(&T::O) -> bool + 'static,
+        A: FnMut(&mut HookContext<'_, T>) + 'static,
+    {
+        self.watchpoints.push(Watchpoint {
+            name: name.into(),
+            trigger: Trigger::Output(Box::new(predicate)),
+            action: Box::new(action),
+            triggered: false,
+        });
+        self
+    }
+    /// Call `action` whenever `predicate` on the traced signal with the
+    /// hierarchical name `signal` becomes true
+    pub fn on_signal<P, A>(mut self, name: &str, signal: &str, predicate: P, action: A) -> Self
+    where
+        P: FnMut(&TypedBits) -> bool + 'static,
+        A: FnMut(&mut HookContext<'_, T>) + 'static,
+    {
+        self.watchpoints.push(Watchpoint {
+            name: name.into(),
+            trigger: Trigger::Signal {
+                signal: signal.into(),
+                predicate: Box::new(predicate),
+            },
+            action: Box::new(action),
+            triggered: false,
+        });
+        self
+    }
+    /// Check that `predicate` holds for the traced signal with the
+    /// hierarchical name `signal` (which holds a `V`) in every sample.
+    /// The run ends with a [WatchpointError::AssertionViolation] at the
+    /// first sample where it does not, or with a
+    /// [WatchpointError::WidthMismatch] if the signal is not a `V`.
+    pub fn assert_signal<V, P>(mut self, name: &str, signal: &str, predicate: P) -> Self
+    where
+        V: Digital,
+        P: FnMut(&TypedBits) -> bool + 'static,
+    {
+        let instance = signal.to_string();
+        self.watchpoints.push(Watchpoint {
+            name: name.into(),
+            trigger: Trigger::Assert {
+                signal: signal.into(),
+                width: V::BITS,
+                predicate: Box::new(predicate),
+            },
+            action: Box::new(move |context| {
+                *context.abort = Some(rhdl_error(WatchpointError::AssertionViolation {
+                    assertion: context.name.into(),
+                    instance: instance.clone(),
+                    cycle: context.cycle,
+                    time: context.sample.time,
+                }));
+            }),
+            triggered: false,
+        });
+        self
+    }
+}
+
+// A request to write the samples from `start` to `end` (inclusive) to a VCD
+struct Capture {
+    path: PathBuf,
+    start: usize,
+    end: usize,
+}
+
+/// The view of the simulation given to the action of a watchpoint
+pub struct HookContext<'a, T: Synchronous> {
+    name: &'a str,
+    cycle: u64,
+    index: usize,
+    sample: &'a Sample<T>,
+    state: &'a T::S,
+    captures: &'a mut Vec<Capture>,
+    abort: &'a mut Option<RHDLError>,
+}
+
+impl<T: Synchronous> HookContext<'_, T> {
+    /// The name of the watchpoint that fired
+    pub fn name(&self) -> &str {
+        self.name
+    }
+    /// The number of positive clock edges seen so far.  This matches
+    /// the index of the sample in a `synchronous_sample` of the run.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+    /// The simulation time of the sample
+    pub fn time(&self) -> u64 {
+        self.sample.time
+    }
+    /// The sample (clock and reset, input and output) that fired the watchpoint
+    pub fn sample(&self) -> &Sample<T> {
+        self.sample
+    }
+    /// The internal state of the circuit
+    pub fn state(&self) -> &T::S {
+        self.state
+    }
+    /// The current value of a traced signal
+    pub fn signal(&self, name: &str) -> Option<TypedBits> {
+        let mut value = None;
+        with_trace_db(|db| value = db.signal(name));
+        value
+    }
+    /// The current values of all of the traced signals
+    pub fn signals(&self) -> BTreeMap<String, TypedBits> {
+        let mut signals = BTreeMap::new();
+        with_trace_db(|db| signals = db.signals());
+        signals
+    }
+    /// A listing of the current values of all of the traced signals
+    pub fn dump_state(&self) -> String {
+        self.signals()
+            .into_iter()
+            .map(|(name, value)| format!("{name} = {value:?}\n"))
+            .collect()
+    }
+    /// Write the `before` samples preceding this one, this sample, and the
+    /// `after` samples following it to a VCD file.  The file is written once
+    /// the last of those samples has been simulated (or the run ends).
+    pub fn capture_vcd(&mut self, path: impl Into<PathBuf>, before: usize, after: usize) {
+        self.captures.push(Capture {
+            path: path.into(),
+            start: self.index.saturating_sub(before),
+            end: self.index + after,
+        });
+    }
+    /// Stop the simulation once the watchpoints for this sample have run.  The
+    /// run ends with a [WatchpointError::Aborted] wrapping `error`.
+    pub fn abort(&mut self, error: impl Into<Box<dyn Error + Send + Sync>>) {
+        *self.abort = Some(rhdl_error(WatchpointError::Aborted {
+            watchpoint: self.name.into(),
+            cycle: self.cycle,
+            time: self.sample.time,
+            source: error.into(),
+        }));
+    }
+}
+
+#[must_use = "To run the simulation, you must exhaust the iterator"]
+pub struct RunWithHooks<'a, T: Synchronous, I> {
+    run: RunSynchronous<'a, T, I, T::S>,
+    hooks: Hooks<T>,
+    guard: Option<TraceDBGuard>,
+    times: Vec<u64>,
+    captures: Vec<Capture>,
+    cycle: u64,
+    clock: bool,
+    done: bool,
+}
+
+impl<T: Synchronous, I> RunWithHooks<'_, T, I> {
+    fn write_capture(&self, capture: &Capture) -> Result<(), RHDLError> {
+        let end = capture.end.min(self.times.len() - 1);
+        let time_set = self.times[capture.start..=end].iter().copied().collect();
+        let mut result = Ok(());
+        with_trace_db(|db| {
+            result = std::fs::File::create(&capture.path)
+                .map(std::io::BufWriter::new)
+                .and_then(|file| db.dump_vcd(file, Some(&time_set)));
+        });
+        Ok(result?)
+    }
+    fn write_captures(&mut self, all: bool) -> Result<(), RHDLError> {
+        let (ready, pending) = std::mem::take(&mut self.captures)
+            .into_iter()
+            .partition::<Vec<_>, _>(|capture| all || capture.end < self.times.len());
+        self.captures = pending;
+        ready
+            .iter()
+            .try_for_each(|capture| self.write_capture(capture))
+    }
+    fn check(&mut self, sample: &Sample<T>) -> Result<(), RHDLError> {
+        if sample.value.0.clock.raw() && !self.clock {
+            self.cycle += 1;
+        }
+        self.clock = sample.value.0.clock.raw();
+        self.times.push(sample.time);
+        if self.clock {
+            return Ok(());
+        }
+        let state = self.run.state().expect("The simulation has started");
+        let mut abort = None;
+        for watchpoint in &mut self.hooks.watchpoints {
+            let fired = watchpoint
+                .trigger
+                .check(&watchpoint.name, &sample.value.2)?;
+            if fired && !watchpoint.triggered {
+                let mut context = HookContext {
+                    name: &watchpoint.name,
+                    cycle: self.cycle,
+                    index: self.times.len() - 1,
+                    sample,
+                    state,
+                    captures: &mut self.captures,
+                    abort: &mut abort,
+                };
+                (watchpoint.action)(&mut context);
+            }
+            watchpoint.triggered = fired;
+        }
+        self.write_captures(abort.is_some())?;
+        abort.map_or(Ok(()), Err)
+    }
+}
+
+impl<T, I> Iterator for RunWithHooks<'_, T, I>
+where
+    T: Synchronous,
+    I: Iterator<Item = TimedSample<(ClockReset, <T as SynchronousIO>::I)>>,
+{
+    type Item = Result<Sample<T>, RHDLError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.run.state().is_none() {
+            // Signal watchpoints (and captures) need a trace database,
+            // so create one if the caller has not
+            let mut active = false;
+            with_trace_db(|_| active = true);
+            if !active {
+                self.guard = Some(trace_init_db());
+            }
+        }
+        let Some(sample) = self.run.next() else {
+            self.done = true;
+            return self.write_captures(true).err().map(Err);
+        };
+        match self.check(&sample) {
+            Ok(()) => Some(Ok(sample)),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+pub trait RunWithHooksExt<I>: Synchronous + Sized {
+    fn run_with_hooks(
+        &self,
+        iter: I,
+        hooks: Hooks<Self>,
+    ) -> Result<RunWithHooks<'_, Self, <I as IntoIterator>::IntoIter>, RHDLError>
+    where
+        I: IntoIterator;
+}
+
+impl<T, I> RunWithHooksExt<I> for T
+where
+    T: Synchronous,
+    I: IntoIterator<Item = TimedSample<(ClockReset, <T as SynchronousIO>::I)>>,
+{
+    fn run_with_hooks(
+        &self,
+        iter: I,
+        hooks: Hooks<Self>,
+    ) -> Result<RunWithHooks<'_, Self, <I as IntoIterator>::IntoIter>, RHDLError> {
+        self.yosys_check()?;
+        Ok(RunWithHooks {
+            run: run_synchronous(self, iter.into_iter()),
+            hooks,
+            guard: None,
+            times: vec![],
+            captures: vec![],
+            cycle: 0,
+            clock: false,
+            done: false,
+        })
+    }
+}
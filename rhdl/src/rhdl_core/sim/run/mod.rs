@@ -1,4 +1,8 @@
 pub mod async_fn;
 pub mod asynchronous;
+pub mod control;
+pub mod hooks;
+pub mod session;
 pub mod sync_fn;
 pub mod synchronous;
+pub mod triggered;
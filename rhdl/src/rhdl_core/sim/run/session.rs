@@ -0,0 +1,281 @@
+//! Interactive simulation sessions
+//!
+//! A [SimSession] runs a synchronous circuit one clock cycle at a time,
+//! under the control of the caller, rather than from a precomputed stream of
+//! inputs.  This is handy for exploratory debugging (from a test, or a
+//! notebook), where the next input depends on what the circuit just did.
+//!
+//! Each call to [step](SimSession::step) presents an input for one clock
+//! cycle, and returns the output of the circuit in that cycle (the settled
+//! output just before the positive clock edge, as `synchronous_sample`
+//! would report it).  The clock edge is then applied, so that the traced
+//! signals (which can be read by their hierarchical names with
+//! [peek](SimSession::peek)) hold the values after the edge.
+//!
+//! The session generates exactly the same samples as `with_reset` and
+//! `clock_pos_edge` would for the same sequence of inputs, and feeds them to
+//! the same simulation code as `run`.  So stepping through a sequence of
+//! inputs gives the same samples (and trace) as a batch run of that sequence.
+//! If requested, the samples are recorded, and can be retrieved with
+//! [samples](SimSession::samples), or written to a VCD file.
+//!
+//! The values of the traced signals after each clock edge are kept in the
+//! session.  Unless the session is recorded, the history of the trace
+//! database it creates is discarded as it goes, so a long session does
+//! not grow without bound.  A trace database created by the caller is
+//! left alone.
+use std::{collections::BTreeMap, io::Write};
+
+use crate::rhdl_core::{
+    clock::clock,
+    clock_reset,
+    error::rhdl_error,
+    sim::{
+        error::SessionError,
+        run::synchronous::{run_synchronous, RunSynchronous},
+        ResetOrData,
+    },
+    timed_sample,
+    trace::db::{trace_trim_before, with_trace_db, TraceDBGuard},
+    trace_init_db,
+    types::reset::reset,
+    ClockReset, Digital, RHDLError, Synchronous, SynchronousIO, TimedSample, TypedBits,
+};
+
+type Sample<T> = TimedSample<(ClockReset, <T as SynchronousIO>::I, <T as SynchronousIO>::O)>;
+
+type NoInputs<T> = std::iter::Empty<TimedSample<(ClockReset, <T as SynchronousIO>::I)>>;
+
+/// An interactive simulation of the circuit `T`
+pub struct SimSession<'a, T: Synchronous> {
+    run: RunSynchronous<'a, T, NoInputs<T>, T::S>,
+    guard: Option<TraceDBGuard>,
+    half_period: u64,
+    cycle: u64,
+    input: T::I,
+    output: Option<T::O>,
+    signals: BTreeMap<String, TypedBits>,
+    record: bool,
+    samples: Vec<Sample<T>>,
+}
+
+impl<'a, T: Synchronous> SimSession<'a, T> {
+    /// Start a session for `uut`, with a clock period of 100
+    pub fn new(uut: &'a T) -> Self {
+        // Peeking needs a trace database, so create one if the caller has not
+        let mut active = false;
+        with_trace_db(|_| active = true);
+        Self {
+            run: run_synchronous(uut, std::iter::empty()),
+            guard: (!active).then(trace_init_db),
+            half_period: 50,
+            cycle: 0,
+            input: T::I::dont_care(),
+            output: None,
+            signals: BTreeMap::new(),
+            record: false,
+            samples: vec![],
+        }
+    }
+    /// Use a different clock period (as for `clock_pos_edge`)
+    pub fn with_period(self, period: u64) -> Self {
+        assert!(period >= 4, "The clock period must be at least 4");
+        Self {
+            half_period: period / 2,
+            ..self
+        }
+    }
+    /// Record the samples of the session
+    pub fn recorded(self) -> Self {
+        Self {
+            record: true,
+            ..self
+        }
+    }
+    // Simulate one clock cycle, with the same samples as `clock_pos_edge`
+    fn advance(&mut self, data: ResetOrData<T::I>) -> T::O {
+        let value = |clk| match data {
+            ResetOrData::Reset => (clock_reset(clock(clk), reset(true)), T::I::dont_care()),
+            ResetOrData::Data(x) => (clock_reset(clock(clk), reset(false)), x),
+        };
+        let h = self.half_period;
+        let start = self.cycle * 2 * h;
+        let mut times = vec![];
+        if self.cycle != 0 {
+            // The input changes just after the previous positive edge
+            times.push((start - h + 1, true));
+        }
+        times.push((start, false));
+        times.push((start + h, true));
+        let mut output = None;
+        let end = start + h;
+        for (time, clk) in times {
+            let sample = self.run.step(timed_sample(time, value(clk)));
+            if !clk {
+                output = Some(sample.value.2);
+            }
+            if self.record {
+                self.samples.push(sample);
+            }
+        }
+        self.cycle += 1;
+        self.output = output;
+        with_trace_db(|db| self.signals = db.signals());
+        if !self.record && self.guard.is_some() {
+            trace_trim_before(end);
+        }
+        output.expect("Each cycle has a sample with the clock low")
+    }
+    /// Hold the circuit in reset for one clock cycle
+    pub fn reset(&mut self) -> T::O {
+        self.advance(ResetOrData::Reset)
+    }
+    /// Set the input for the following cycles, without advancing the clock
+    pub fn poke_input(&mut self, input: T::I) {
+        self.input = input;
+    }
+    /// Present `input` for one clock cycle, and return the output in that
+    /// cycle (before the clock edge).  The input is held for later cycles.
+    pub fn step(&mut self, input: T::I) -> T::O {
+        self.poke_input(input);
+        self.advance(ResetOrData::Data(input))
+    }
+    /// Step with the current input until `predicate` is true for the output
+    /// in a cycle, and return the number of cycles taken.  The predicate is
+    /// checked for at most `max_cycles` cycles.
+    pub fn run_until(
+        &mut self,
+        mut predicate: impl FnMut(&T::O) -> bool,
+        max_cycles: u64,
+    ) -> Result<u64, RHDLError> {
+        for n in 1..=max_cycles {
+            let output = self.step(self.input);
+            if predicate(&output) {
+                return Ok(n);
+            }
+        }
+        Err(rhdl_error(SessionError::Timeout {
+            max_cycles,
+            cycle: self.cycle,
+        }))
+    }
+    /// The value of a traced signal (by its hierarchical name, like
+    /// `top.reg.dff.output`) after the last clock edge
+    pub fn peek(&self, name: &str) -> Option<TypedBits> {
+        self.signals.get(name).cloned()
+    }
+    /// The names of all of the traced signals
+    pub fn signal_names(&self) -> Vec<String> {
+        self.signals.keys().cloned().collect()
+    }
+    /// The number of clock cycles simulated so far
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+    /// The output in the last cycle, if any
+    pub fn output(&self) -> Option<T::O> {
+        self.output
+    }
+    /// The internal state of the circuit, once the session has started
+    pub fn state(&self) -> Option<&T::S> {
+        self.run.state()
+    }
+    /// The recorded samples (empty unless the session is [recorded](Self::recorded))
+    pub fn samples(&self) -> &[Sample<T>] {
+        &self.samples
+    }
+    /// Write the trace of the recorded samples as a VCD
+    pub fn dump_vcd<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        let time_set = self.samples.iter().map(|s| s.time).collect();
+        let mut result = Ok(());
+        let mut writer = Some(writer);
+        with_trace_db(|db| {
+            if let Some(writer) = writer.take() {
+                result = db.dump_vcd(writer, Some(&time_set));
+            }
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    use super::*;
+
+    // A counter, simulated by hand, that traces its count
+    #[derive(Clone)]
+    struct Counter;
+
+    impl SynchronousDQ for Counter {
+        type D = ();
+        type Q = ();
+    }
+
+    impl SynchronousIO for Counter {
+        type I = bool;
+        type O = b8;
+        type Kernel = NoKernel3<ClockReset, bool, (), (b8, ())>;
+    }
+
+    impl Synchronous for Counter {
+        type S = (Clock, b8);
+
+        fn init(&self) -> Self::S {
+            (Clock::default(), b8::default())
+        }
+
+        fn sim(&self, clock_reset: ClockReset, enable: bool, state: &mut Self::S) -> b8 {
+            if clock_reset.clock.raw() && !state.0.raw() {
+                if clock_reset.reset.any() {
+                    state.1 = bits(0);
+                } else if enable {
+                    state.1 += 1;
+                }
+            }
+            state.0 = clock_reset.clock;
+            trace("count", &state.1);
+            state.1
+        }
+
+        fn descriptor(&self, _name: &str) -> Result<CircuitDescriptor, RHDLError> {
+            unimplemented!()
+        }
+
+        fn hdl(&self, _name: &str) -> Result<HDLDescriptor, RHDLError> {
+            unimplemented!()
+        }
+    }
+
+    fn history_len(name: &str) -> usize {
+        let mut len = 0;
+        with_trace_db(|db| len = db.history(name).map_or(0, |h| h.len()));
+        len
+    }
+
+    #[test]
+    fn test_unrecorded_session_trims_the_trace() {
+        let uut = Counter;
+        let mut session = SimSession::new(&uut);
+        session.reset();
+        for _ in 0..10 {
+            session.step(true);
+        }
+        assert_eq!(session.peek("top.count").unwrap().as_i64().unwrap(), 10);
+        // The session does not record, so the trace keeps no history
+        assert_eq!(history_len("top.count"), 1);
+    }
+
+    #[test]
+    fn test_recorded_session_keeps_the_trace() {
+        let uut = Counter;
+        let mut session = SimSession::new(&uut).recorded();
+        session.reset();
+        for _ in 0..10 {
+            session.step(true);
+        }
+        assert_eq!(session.peek("top.count").unwrap().as_i64().unwrap(), 10);
+        assert_eq!(history_len("top.count"), 11);
+    }
+}
@@ -34,6 +34,38 @@ pub fn run_synchronous<T, I, S>(uut: &T, inputs: I) -> RunSynchronous<'_, T, I,
     }
 }
 
+impl<T, I, S> RunSynchronous<'_, T, I, S> {
+    // The state of the circuit, once the simulation has started
+    pub(crate) fn state(&self) -> Option<&S> {
+        self.state.as_ref()
+    }
+}
+
+impl<T, I, S> RunSynchronous<'_, T, I, S>
+where
+    T: Synchronous<S = S>,
+{
+    // Simulate a single sample
+    pub(crate) fn step(
+        &mut self,
+        sample: TimedSample<(ClockReset, <T as SynchronousIO>::I)>,
+    ) -> TimedSample<(ClockReset, <T as SynchronousIO>::I, <T as SynchronousIO>::O)> {
+        // Get a mutable borrow to the state.  If the state is None
+        // then initialize it first.
+        let state = self.state.get_or_insert_with(|| self.uut.init());
+        assert!(
+            sample.time >= self.time,
+            "input time must be non-decreasing"
+        );
+        self.time = sample.time;
+        trace_time(sample.time);
+        trace("clock", &sample.value.0.clock);
+        trace("reset", &sample.value.0.reset);
+        let output = self.uut.sim(sample.value.0, sample.value.1, state);
+        sample.map(|(cr, i)| (cr, i, output))
+    }
+}
+
 impl<T, I, S> Iterator for RunSynchronous<'_, T, I, S>
 where
     T: Synchronous<S = S>,
@@ -42,23 +74,10 @@ where
     type Item = TimedSample<(ClockReset, <T as SynchronousIO>::I, <T as SynchronousIO>::O)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // Get a mutable borrow to the state.  If the state is None
-        // then initialize it first.
-        let state = self.state.get_or_insert_with(|| self.uut.init());
-        if let Some(sample) = self.inputs.next() {
-            assert!(
-                sample.time >= self.time,
-                "input time must be non-decreasing"
-            );
-            self.time = sample.time;
-            trace_time(sample.time);
-            trace("clock", &sample.value.0.clock);
-            trace("reset", &sample.value.0.reset);
-            let output = self.uut.sim(sample.value.0, sample.value.1, state);
-            Some(sample.map(|(cr, i)| (cr, i, output)))
-        } else {
-            None
-        }
+        // Initialize the state before pulling the first input
+        self.state.get_or_insert_with(|| self.uut.init());
+        let sample = self.inputs.next()?;
+        Some(self.step(sample))
     }
 }
 
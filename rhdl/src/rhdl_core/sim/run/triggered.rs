@@ -0,0 +1,264 @@
+//! Triggered trace capture for long simulations
+//!
+//! A full trace of a run of millions of cycles is too large to be of use,
+//! and usually only a window around some event is of interest.  A
+//! [TriggeredTrace] consumes the samples of a synchronous run, and writes
+//! the traced signals for a window of cycles around each time its trigger
+//! fires to a numbered VCD (or FST) file.
+//!
+//! The trigger is a predicate on either the output of the circuit, or on
+//! one of its traced signals (as for [Hooks](super::hooks::Hooks)), and is
+//! checked on the samples taken while the clock is low.  It fires when the
+//! predicate _becomes_ true.  A window holds the `pre` cycles before the
+//! trigger, the cycle in which it fired, and the `post` cycles after it.
+//! A cycle starts at a positive edge of the clock, and the samples before
+//! the first edge belong to cycle 0 (so cycle numbers match those of a
+//! `synchronous_sample` of the run).  Once a window has been written, the
+//! trigger is re-armed, until the maximum number of windows is reached.
+//! Windows do not overlap, so the pre trigger cycles of a window never
+//! reach back into the previous window.
+//!
+//! Only the times of the last `pre + 1` cycles are kept while waiting for
+//! the trigger, and the trace database is trimmed as the run proceeds, so
+//! the memory used does not grow with the length of the run.  If a trace
+//! database is already active (for example, because the caller is
+//! collecting a [Vcd](crate::rhdl_core::sim::vcd::Vcd)), it is used, and
+//! is trimmed in the same way.  Otherwise one is created for the run.
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+use crate::rhdl_core::{
+    sim::run::hooks::Trigger,
+    trace::db::{trace_trim_before, with_trace_db, TraceDBGuard},
+    trace_init_db, ClockReset, Digital, RHDLError, TimedSample, TypedBits,
+};
+
+/// The file format for captured windows
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TraceFormat {
+    #[default]
+    Vcd,
+    Fst,
+}
+
+impl TraceFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TraceFormat::Vcd => "vcd",
+            TraceFormat::Fst => "fst",
+        }
+    }
+}
+
+/// A window written by a [TriggeredTrace]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureWindow {
+    /// The file the window was written to
+    pub path: PathBuf,
+    /// The cycle in which the trigger fired
+    pub trigger_cycle: u64,
+    /// The time of the sample on which the trigger fired
+    pub trigger_time: u64,
+    /// The number of cycles captured before the trigger.  This is
+    /// less than requested if the trigger fired early in the run.
+    pub cycles_before: usize,
+    /// The number of cycles captured after the trigger.  This is
+    /// less than requested if the run ended first.
+    pub cycles_after: usize,
+    /// The time of the first sample in the window
+    pub start_time: u64,
+    /// The time of the last sample in the window
+    pub end_time: u64,
+}
+
+// A window that is waiting for its post trigger cycles
+struct Pending {
+    trigger_cycle: u64,
+    trigger_time: u64,
+    cycles_before: usize,
+    times: Vec<u64>,
+}
+
+/// Captures windows of a synchronous run around a trigger.  `O` is
+/// the output type of the circuit.
+pub struct TriggeredTrace<O> {
+    name: String,
+    trigger: Trigger<O>,
+    pre: usize,
+    post: usize,
+    max_windows: usize,
+    format: TraceFormat,
+    dir: PathBuf,
+}
+
+impl<O: Digital> TriggeredTrace<O> {
+    fn new(name: &str, trigger: Trigger<O>) -> Self {
+        Self {
+            name: name.into(),
+            trigger,
+            pre: 16,
+            post: 16,
+            max_windows: 1,
+            format: TraceFormat::Vcd,
+            dir: PathBuf::from("."),
+        }
+    }
+    /// Trigger when `predicate` on the output of the circuit becomes
+    /// true.  The windows are written to `<name>_<n>.vcd`, where `n`
+    /// counts the windows from 0.
+    pub fn on_output(name: &str, predicate: impl FnMut(&O) -> bool + 'static) -> Self {
+        Self::new(name, Trigger::Output(Box::new(predicate)))
+    }
+    /// Trigger when `predicate` on the traced signal with the
+    /// hierarchical name `signal` becomes true
+    pub fn on_signal(
+        name: &str,
+        signal: &str,
+        predicate: impl FnMut(&TypedBits) -> bool + 'static,
+    ) -> Self {
+        Self::new(
+            name,
+            Trigger::Signal {
+                signal: signal.into(),
+                predicate: Box::new(predicate),
+            },
+        )
+    }
+    /// The number of cycles to capture before the trigger (the default is 16)
+    pub fn pre_trigger(self, cycles: usize) -> Self {
+        Self {
+            pre: cycles,
+            ..self
+        }
+    }
+    /// The number of cycles to capture after the trigger (the default is 16)
+    pub fn post_trigger(self, cycles: usize) -> Self {
+        Self {
+            post: cycles,
+            ..self
+        }
+    }
+    /// Re-arm the trigger after each window, until `windows` windows
+    /// have been captured (the default is a single window)
+    pub fn windows(self, windows: usize) -> Self {
+        Self {
+            max_windows: windows,
+            ..self
+        }
+    }
+    /// Write the windows in the given format (the default is VCD)
+    pub fn format(self, format: TraceFormat) -> Self {
+        Self { format, ..self }
+    }
+    /// Write the windows into `dir` (the default is the current directory)
+    pub fn in_dir(self, dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().into(),
+            ..self
+        }
+    }
+    fn write(
+        &self,
+        index: usize,
+        pending: Pending,
+        cycle: u64,
+    ) -> Result<CaptureWindow, RHDLError> {
+        let start_time = pending.times[0];
+        let end_time = *pending.times.last().unwrap();
+        let time_set = pending.times.iter().copied().collect();
+        let path = self
+            .dir
+            .join(format!("{}_{index}.{}", self.name, self.format.extension()));
+        // Trim to the start of the window, so the values in effect
+        // at the start are written
+        trace_trim_before(start_time);
+        let mut result = Ok(());
+        with_trace_db(|db| {
+            result = std::fs::File::create(&path)
+                .map(std::io::BufWriter::new)
+                .and_then(|file| match self.format {
+                    TraceFormat::Vcd => db.dump_vcd(file, Some(&time_set)),
+                    TraceFormat::Fst => db.dump_fst(file, Some(&time_set)),
+                });
+        });
+        result?;
+        Ok(CaptureWindow {
+            path,
+            trigger_cycle: pending.trigger_cycle,
+            trigger_time: pending.trigger_time,
+            cycles_before: pending.cycles_before,
+            cycles_after: (cycle - pending.trigger_cycle) as usize,
+            start_time,
+            end_time,
+        })
+    }
+    /// Run the simulation producing `samples` (which must not have
+    /// started yet), and write a file for each window.  The windows are
+    /// returned in the order they were captured.
+    pub fn capture<I, S>(mut self, samples: S) -> Result<Vec<CaptureWindow>, RHDLError>
+    where
+        I: Digital,
+        S: IntoIterator<Item = TimedSample<(ClockReset, I, O)>>,
+    {
+        let mut active = false;
+        with_trace_db(|_| active = true);
+        let _guard: Option<TraceDBGuard> = (!active).then(trace_init_db);
+        // The times of the samples in each of the last `pre + 1` cycles
+        let mut history = VecDeque::from([vec![]]);
+        let mut pending: Option<Pending> = None;
+        let mut windows = vec![];
+        let mut cycle = 0;
+        let mut clock = false;
+        let mut fired = false;
+        let mut since_trim = 0;
+        let trim_every = self.pre.max(64);
+        for sample in samples {
+            let edge = sample.value.0.clock.raw() && !clock;
+            clock = sample.value.0.clock.raw();
+            if edge {
+                cycle += 1;
+                if pending
+                    .as_ref()
+                    .is_some_and(|p| cycle > p.trigger_cycle + self.post as u64)
+                {
+                    let window = self.write(windows.len(), pending.take().unwrap(), cycle - 1)?;
+                    windows.push(window);
+                    history.clear();
+                }
+                history.push_back(vec![]);
+                if history.len() > self.pre + 1 {
+                    history.pop_front();
+                }
+                since_trim += 1;
+            }
+            match &mut pending {
+                Some(pending) => pending.times.push(sample.time),
+                None => history.back_mut().unwrap().push(sample.time),
+            }
+            if !clock {
+                let now = self.trigger.check(&self.name, &sample.value.2)?;
+                if now && !fired && pending.is_none() && windows.len() < self.max_windows {
+                    pending = Some(Pending {
+                        trigger_cycle: cycle,
+                        trigger_time: sample.time,
+                        cycles_before: history.len() - 1,
+                        times: history.drain(..).flatten().collect(),
+                    });
+                }
+                fired = now;
+            }
+            if pending.is_none() && since_trim >= trim_every {
+                if let Some(&start) = history.iter().flatten().next() {
+                    trace_trim_before(start);
+                }
+                since_trim = 0;
+            }
+        }
+        if let Some(pending) = pending {
+            windows.push(self.write(windows.len(), pending, cycle)?);
+        }
+        Ok(windows)
+    }
+}
@@ -1,16 +1,36 @@
-use crate::rhdl_core::{hdl::ast::Module, RHDLError};
+use crate::rhdl_core::{hdl::ast::Module, Kind};
 
-pub struct TestModule(Module);
+#[cfg(feature = "iverilog")]
+use crate::rhdl_core::{error::rhdl_error, sim::error::VerilogError, RHDLError};
+
+pub struct TestModule {
+    module: Module,
+    output: Option<Kind>,
+}
 
 impl From<Module> for TestModule {
-    fn from(m: Module) -> Self {
-        Self(m)
+    fn from(module: Module) -> Self {
+        Self {
+            module,
+            output: None,
+        }
     }
 }
 
 impl std::fmt::Display for TestModule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.module)
+    }
+}
+
+impl TestModule {
+    // Record the type of the checked output, so that mismatches
+    // can be reported signal by signal
+    pub(crate) fn with_output_kind(self, output: Kind) -> Self {
+        Self {
+            output: Some(output),
+            ..self
+        }
     }
 }
 
@@ -20,7 +40,8 @@ impl TestModule {
         let d = tempfile::tempdir()?;
         // Write the test bench to a file
         let d_path = d.path();
-        std::fs::write(d_path.join("testbench.v"), self.to_string())?;
+        let testbench = self.to_string();
+        std::fs::write(d_path.join("testbench.v"), &testbench)?;
         // Compile the test bench
         let mut cmd = std::process::Command::new("iverilog");
         cmd.arg("-o")
@@ -30,7 +51,9 @@ impl TestModule {
             .status()
             .expect("Icarus Verilog should be installed and in your PATH.");
         if !status.success() {
-            return Err(anyhow::anyhow!("Failed to compile testbench with {}", status).into());
+            return Err(rhdl_error(VerilogError::CompileFailed {
+                status: status.to_string(),
+            }));
         }
         let mut cmd = std::process::Command::new("vvp");
         cmd.arg(d_path.join("testbench"));
@@ -38,14 +61,16 @@ impl TestModule {
         let output_stdout = String::from_utf8_lossy(&output.stdout);
         for line in output_stdout.lines() {
             if line.contains("FAILED") {
-                return Err(RHDLError::VerilogVerificationErrorString(line.into()));
+                return Err(rhdl_error(VerilogError::from_failure(
+                    line,
+                    &testbench,
+                    self.output.as_ref(),
+                )));
             }
             if line.starts_with("TESTBENCH OK") {
                 return Ok(());
             }
         }
-        Err(RHDLError::VerilogVerificationErrorString(
-            "No output".into(),
-        ))
+        Err(rhdl_error(VerilogError::NoResult))
     }
 }
@@ -1,11 +1,12 @@
 use rhdl_trace_type::RTT;
 
 use crate::rhdl_core::{
+    error::rhdl_error,
     hdl::ast::{
         assert, assign, bit_string, component_instance, connection, declaration, delay, display,
         dump_file, dump_vars, finish, id, initial, unsigned_width, Direction, HDLKind, Module,
     },
-    sim::test_module::TestModule,
+    sim::{error::TestbenchError, test_module::TestModule},
     types::bit_string::BitString,
     Circuit, CircuitIO, Digital, RHDLError, TimedSample,
 };
@@ -45,20 +46,26 @@ impl<I: Digital, O: Digital> TestBench<I, O> {
         } else {
             &hdl.ports[0]
         };
+        let module = || hdl.name.clone();
         if has_nonempty_input && I::BITS == 0 {
-            return Err(RHDLError::TestbenchConstructionError(
-                "Input port mismatch".into(),
-            ));
+            return Err(rhdl_error(TestbenchError::UnexpectedInput {
+                module: module(),
+            }));
         }
         if has_nonempty_input && I::BITS != hdl.ports[0].width.len() {
-            return Err(RHDLError::TestbenchConstructionError(
-                "Input port width mismatch".into(),
-            ));
+            return Err(rhdl_error(TestbenchError::InputWidth {
+                module: module(),
+                expected: I::BITS,
+                found: hdl.ports[0].width.len(),
+            }));
         }
         if output_port.direction != Direction::Output || output_port.width.len() != O::BITS {
-            return Err(RHDLError::TestbenchConstructionError(
-                "Output port mismatch".into(),
-            ));
+            return Err(rhdl_error(TestbenchError::OutputPort {
+                module: module(),
+                direction: output_port.direction,
+                expected: O::BITS,
+                found: output_port.width.len(),
+            }));
         }
         let arg1_connection = (has_nonempty_input).then(|| connection(&hdl.ports[0].name, id("i")));
         let arg2_connection = Some(connection(&output_port.name, id("o")));
@@ -148,7 +155,7 @@ impl<I: Digital, O: Digital> TestBench<I, O> {
             submodules: vec![hdl.clone()],
             ..Default::default()
         };
-        Ok(module.into())
+        Ok(TestModule::from(module).with_output_kind(O::static_kind()))
     }
 
     pub fn rtl<T>(&self, uut: &T, options: &TestBenchOptions) -> Result<TestModule, RHDLError>
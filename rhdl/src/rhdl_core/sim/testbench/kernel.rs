@@ -105,7 +105,7 @@ where
         let q = self.apply(args);
         let q: BitString = q.typed_bits().into();
         let d1 = delay(0);
-        let assertion = assert(bit_string(&q), id("out"), &ndx.to_string());
+        let assertion = assert(id("out"), bit_string(&q), &ndx.to_string());
         // Create a vector of statements, with arg0, d1, and assertion, if arg0 is non-empty.  Otherwise, leave it out.
         arg0.into_iter()
             .chain(once(d1))
@@ -141,7 +141,7 @@ where
         let q = self.apply(args);
         let q: BitString = q.typed_bits().into();
         let d1 = delay(0);
-        let assertion = assert(bit_string(&q), id("out"), &ndx.to_string());
+        let assertion = assert(id("out"), bit_string(&q), &ndx.to_string());
         arg0.into_iter()
             .chain(arg1)
             .chain(once(d1))
@@ -181,7 +181,7 @@ where
         let q = self.apply(args);
         let q: BitString = q.typed_bits().into();
         let d1 = delay(0);
-        let assertion = assert(bit_string(&q), id("out"), &ndx.to_string());
+        let assertion = assert(id("out"), bit_string(&q), &ndx.to_string());
         arg0.into_iter()
             .chain(arg1)
             .chain(arg2)
@@ -226,7 +226,7 @@ where
         let q = self.apply(args);
         let q: BitString = q.typed_bits().into();
         let d1 = delay(0);
-        let assertion = assert(bit_string(&q), id("out"), &ndx.to_string());
+        let assertion = assert(id("out"), bit_string(&q), &ndx.to_string());
         arg0.into_iter()
             .chain(arg1)
             .chain(arg2)
@@ -276,7 +276,7 @@ where
         let q = self.apply(args);
         let q: BitString = q.typed_bits().into();
         let d1 = delay(0);
-        let assertion = assert(bit_string(&q), id("out"), &ndx.to_string());
+        let assertion = assert(id("out"), bit_string(&q), &ndx.to_string());
         arg0.into_iter()
             .chain(arg1)
             .chain(arg2)
@@ -2,11 +2,12 @@ use rhdl_trace_type::RTT;
 
 use crate::rhdl_core::{
     clock_reset,
+    error::rhdl_error,
     hdl::ast::{
         assert, assign, bit_string, component_instance, connection, declaration, delay, display,
         dump_file, dump_vars, finish, id, initial, unsigned_width, Direction, HDLKind, Module,
     },
-    sim::test_module::TestModule,
+    sim::{error::TestbenchError, test_module::TestModule},
     types::bit_string::BitString,
     ClockReset, Digital, RHDLError, Synchronous, SynchronousIO, TimedSample,
 };
@@ -48,28 +49,31 @@ impl<I: Digital, O: Digital> SynchronousTestBench<I, O> {
         } else {
             &hdl.ports[1]
         };
+        let module = || hdl.name.clone();
         if hdl.ports[0].direction != Direction::Input || hdl.ports[0].width != unsigned_width(2) {
-            return Err(RHDLError::TestbenchConstructionError(
-                "First port must be an input with 2 bits width".into(),
-            ));
+            return Err(rhdl_error(TestbenchError::ClockResetPort {
+                module: module(),
+            }));
         }
         if has_nonempty_input && (I::BITS == 0) {
-            return Err(RHDLError::TestbenchConstructionError(
-                "Input port mismatch".into(),
-            ));
+            return Err(rhdl_error(TestbenchError::UnexpectedInput {
+                module: module(),
+            }));
         }
         if has_nonempty_input && I::BITS != hdl.ports[1].width.len() {
-            return Err(RHDLError::TestbenchConstructionError(
-                "Input port width mismatch".into(),
-            ));
+            return Err(rhdl_error(TestbenchError::InputWidth {
+                module: module(),
+                expected: I::BITS,
+                found: hdl.ports[1].width.len(),
+            }));
         }
         if output_port.direction != Direction::Output || output_port.width.len() != O::BITS {
-            return Err(RHDLError::TestbenchConstructionError(format!(
-                "Output port mismatch: direction {dir:?} width {width} expected width {expected}",
-                dir = output_port.direction,
-                width = output_port.width.len(),
-                expected = O::BITS
-            )));
+            return Err(rhdl_error(TestbenchError::OutputPort {
+                module: module(),
+                direction: output_port.direction,
+                expected: O::BITS,
+                found: output_port.width.len(),
+            }));
         }
         let arg0_connection = Some(connection(&hdl.ports[0].name, id("clock_reset")));
         let arg1_connection = (has_nonempty_input).then(|| connection(&hdl.ports[1].name, id("i")));
@@ -172,7 +176,7 @@ impl<I: Digital, O: Digital> SynchronousTestBench<I, O> {
             submodules: vec![hdl.clone()],
             ..Default::default()
         };
-        Ok(module.into())
+        Ok(TestModule::from(module).with_output_kind(O::static_kind()))
     }
 
     pub fn rtl<T>(&self, uut: &T, options: &TestBenchOptions) -> Result<TestModule, RHDLError>
@@ -192,3 +196,58 @@ impl<I: Digital, O: Digital> SynchronousTestBench<I, O> {
         self.build_test_module(&module, options)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::rhdl_core::{hdl::ast::port, sim::error::TestbenchError};
+
+    use super::*;
+
+    #[test]
+    fn test_port_mismatches_are_reported() {
+        let tb = SynchronousTestBench::<b4, b8> { samples: vec![] };
+        let options = TestBenchOptions::default();
+        let mut hdl = Module {
+            name: "dut".into(),
+            ports: vec![
+                port(
+                    "clock_reset",
+                    Direction::Input,
+                    HDLKind::Wire,
+                    unsigned_width(2),
+                ),
+                port("i", Direction::Input, HDLKind::Wire, unsigned_width(3)),
+                port("o", Direction::Output, HDLKind::Wire, unsigned_width(8)),
+            ],
+            ..Default::default()
+        };
+        let err = tb.build_test_module(&hdl, &options).err().unwrap();
+        assert_eq!(
+            err.testbench_error(),
+            Some(&TestbenchError::InputWidth {
+                module: "dut".into(),
+                expected: 4,
+                found: 3,
+            })
+        );
+        hdl.ports[1].width = unsigned_width(4);
+        hdl.ports[2].direction = Direction::Input;
+        let err = tb.build_test_module(&hdl, &options).err().unwrap();
+        assert!(matches!(
+            err.testbench_error(),
+            Some(TestbenchError::OutputPort {
+                direction: Direction::Input,
+                expected: 8,
+                found: 8,
+                ..
+            })
+        ));
+        hdl.ports[0].width = unsigned_width(1);
+        let err = tb.build_test_module(&hdl, &options).err().unwrap();
+        assert!(matches!(
+            err.testbench_error(),
+            Some(TestbenchError::ClockResetPort { module }) if module == "dut"
+        ));
+    }
+}
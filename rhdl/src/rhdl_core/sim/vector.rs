@@ -0,0 +1,439 @@
+//! Width independent test vectors
+//!
+//! Most cores are generic over a bit width, but golden data (as kept in
+//! an `expect` snapshot) is usually recorded for a single width, so that
+//! each width needs its own copy.  For many cores the behavior scales
+//! with the width: a shift register or a counter of 4 bits produces the
+//! low 4 bits of what the 16 bit version produces for the same stimulus.
+//! Such cores can share one set of golden vectors.
+//!
+//! A [VectorTable] stores the inputs and outputs of each clock as plain
+//! integers (one per leaf of the type), with no width attached.  Types
+//! that implement [TestVector] can be written to and read from those
+//! integers.  Reading truncates each integer to the width of the field it
+//! fills (and sign extends it, for signed fields), so vectors recorded
+//! from the widest instance of a core can drive, and check, the narrower
+//! ones.  A [VectorError::Mismatch] reports the width of the output at
+//! which the core diverged from the vectors.
+//!
+//! The table is written as text, one clock per line, with the input
+//! values and output values separated by a `|`.  Lines starting with `#`
+//! are comments.
+//!
+//!```text
+//! # 2 inputs | 1 output, recorded at 16 bits
+//! 1 1 | 0
+//! 1 0 | 1
+//! 0 1 | 2
+//!```
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl::core::sim::vector::VectorTable;
+//!
+//! // Golden data recorded from a 16 bit accumulator
+//! let wide = [(b16(0x8000), b16(0xFFF0)), (b16(0x8001), b16(0x7FF0))];
+//! let table = VectorTable::record(wide);
+//! let text = table.to_string();
+//! assert_eq!(text, "# 1 input | 1 output, recorded at 16 bits\n32768 | 65520\n32769 | 32752\n");
+//! // The same vectors check the 8 bit version
+//! let table = text.parse::<VectorTable>().unwrap();
+//! let inputs = table.inputs::<b8>().unwrap();
+//! assert_eq!(inputs, vec![b8(0), b8(1)]);
+//! assert!(table.check([(b8(0), b8(0xF0)), (b8(1), b8(0xF0))]).is_ok());
+//!```
+use std::{fmt::Display, str::FromStr};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::{
+    rhdl_bits::{
+        BitWidth, Bits, SignedBits, bits_impl::bits_masked, signed_bits_impl::signed_wrapped,
+    },
+    rhdl_core::Digital,
+};
+
+/// A single value in a [VectorTable]
+#[derive(Clone, Copy, Debug)]
+pub enum Cell {
+    /// An unsigned value
+    Unsigned(u128),
+    /// A signed value
+    Signed(i128),
+}
+
+impl Cell {
+    /// The value as a (sign extended) 128 bit pattern
+    pub fn bits(self) -> u128 {
+        match self {
+            Cell::Unsigned(x) => x,
+            Cell::Signed(x) => x as u128,
+        }
+    }
+}
+
+impl PartialEq for Cell {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cell::Unsigned(x) => write!(f, "{x}"),
+            Cell::Signed(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+impl FromStr for Cell {
+    type Err = std::num::ParseIntError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('-') {
+            s.parse().map(Cell::Signed)
+        } else {
+            s.parse().map(Cell::Unsigned)
+        }
+    }
+}
+
+/// A type that can be stored in a [VectorTable]
+pub trait TestVector: Digital {
+    /// Append the values of the leaves of `self` to `cells`
+    fn to_cells(&self, cells: &mut Vec<Cell>);
+    /// Build a value from the next cells, truncating each one to
+    /// the width of the leaf it fills.  Returns `None` if there
+    /// are not enough cells.
+    fn from_cells(cells: &mut dyn Iterator<Item = Cell>) -> Option<Self>;
+}
+
+impl TestVector for bool {
+    fn to_cells(&self, cells: &mut Vec<Cell>) {
+        cells.push(Cell::Unsigned(*self as u128));
+    }
+    fn from_cells(cells: &mut dyn Iterator<Item = Cell>) -> Option<Self> {
+        cells.next().map(|c| c.bits() & 1 != 0)
+    }
+}
+
+impl<N: BitWidth> TestVector for Bits<N> {
+    fn to_cells(&self, cells: &mut Vec<Cell>) {
+        cells.push(Cell::Unsigned(self.raw()));
+    }
+    fn from_cells(cells: &mut dyn Iterator<Item = Cell>) -> Option<Self> {
+        cells.next().map(|c| bits_masked(c.bits()))
+    }
+}
+
+impl<N: BitWidth> TestVector for SignedBits<N> {
+    fn to_cells(&self, cells: &mut Vec<Cell>) {
+        cells.push(Cell::Signed(self.raw()));
+    }
+    fn from_cells(cells: &mut dyn Iterator<Item = Cell>) -> Option<Self> {
+        cells.next().map(|c| signed_wrapped(c.bits() as i128))
+    }
+}
+
+impl<T: TestVector, const K: usize> TestVector for [T; K] {
+    fn to_cells(&self, cells: &mut Vec<Cell>) {
+        self.iter().for_each(|x| x.to_cells(cells));
+    }
+    fn from_cells(cells: &mut dyn Iterator<Item = Cell>) -> Option<Self> {
+        (0..K)
+            .map(|_| T::from_cells(cells))
+            .collect::<Option<Vec<_>>>()?
+            .try_into()
+            .ok()
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($t:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($t: TestVector),*> TestVector for ($($t,)*) {
+            fn to_cells(&self, _cells: &mut Vec<Cell>) {
+                let ($($t,)*) = self;
+                $($t.to_cells(_cells);)*
+            }
+            fn from_cells(_cells: &mut dyn Iterator<Item = Cell>) -> Option<Self> {
+                Some(($($t::from_cells(_cells)?,)*))
+            }
+        }
+    };
+}
+
+impl_tuple!();
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+
+/// The errors that can come from reading or checking a [VectorTable]
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum VectorError {
+    #[error("Line {line} of the test vectors is not valid: {text:?}")]
+    Parse { line: usize, text: String },
+    #[error("Row {row} of the test vectors has {found} cells, but {expected} are needed")]
+    Cells {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("The test vectors have {expected} rows, but the run produced {found}")]
+    Length { expected: usize, found: usize },
+    #[error("The inputs at row {row} do not match the test vectors")]
+    Input { row: usize },
+    #[error(
+        "The output at row {row} diverged from the test vectors at a width of {width} bits: expected {expected}, found {found}"
+    )]
+    Mismatch {
+        row: usize,
+        width: usize,
+        expected: String,
+        found: String,
+    },
+}
+
+/// One clock of a [VectorTable]
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct VectorRow {
+    /// The values of the inputs
+    pub input: Vec<Cell>,
+    /// The values of the outputs
+    pub output: Vec<Cell>,
+}
+
+/// A table of width independent test vectors.  See the
+/// [module documentation](self) for details.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct VectorTable {
+    /// The width (in bits) of the output the vectors were recorded from
+    pub width: Option<usize>,
+    /// The clocks in the table
+    pub rows: Vec<VectorRow>,
+}
+
+impl VectorTable {
+    /// Record the inputs and outputs of a run, one pair per clock
+    pub fn record<I: TestVector, O: TestVector>(run: impl IntoIterator<Item = (I, O)>) -> Self {
+        let rows = run
+            .into_iter()
+            .map(|(i, o)| {
+                let mut row = VectorRow::default();
+                i.to_cells(&mut row.input);
+                o.to_cells(&mut row.output);
+                row
+            })
+            .collect();
+        Self {
+            width: Some(O::BITS),
+            rows,
+        }
+    }
+    /// The inputs of each clock, truncated to fit `I`
+    pub fn inputs<I: TestVector>(&self) -> Result<Vec<I>, VectorError> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(row, cells)| read_cells(row, &cells.input))
+            .collect()
+    }
+    /// The outputs of each clock, truncated to fit `O`
+    pub fn outputs<O: TestVector>(&self) -> Result<Vec<O>, VectorError> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(row, cells)| read_cells(row, &cells.output))
+            .collect()
+    }
+    /// Check the inputs and outputs of a run (one pair per clock)
+    /// against the table.  Each cell of the table is truncated to
+    /// the width of the corresponding field before comparing.
+    pub fn check<I: TestVector, O: TestVector + std::fmt::Debug>(
+        &self,
+        run: impl IntoIterator<Item = (I, O)>,
+    ) -> Result<(), VectorError> {
+        let run = run.into_iter().collect::<Vec<_>>();
+        if run.len() != self.rows.len() {
+            return Err(VectorError::Length {
+                expected: self.rows.len(),
+                found: run.len(),
+            });
+        }
+        let inputs = self.inputs::<I>()?;
+        let outputs = self.outputs::<O>()?;
+        for (row, ((i, o), (expected_i, expected_o))) in run
+            .into_iter()
+            .zip(inputs.into_iter().zip(outputs))
+            .enumerate()
+        {
+            if i != expected_i {
+                return Err(VectorError::Input { row });
+            }
+            if o != expected_o {
+                return Err(VectorError::Mismatch {
+                    row,
+                    width: O::BITS,
+                    expected: format!("{expected_o:?}"),
+                    found: format!("{o:?}"),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_cells<T: TestVector>(row: usize, cells: &[Cell]) -> Result<T, VectorError> {
+    let mut iter = cells.iter().copied();
+    let value = T::from_cells(&mut iter);
+    let left = iter.count();
+    match value {
+        Some(value) if left == 0 => Ok(value),
+        _ => {
+            let mut needed = vec![];
+            T::dont_care().to_cells(&mut needed);
+            Err(VectorError::Cells {
+                row,
+                expected: needed.len(),
+                found: cells.len(),
+            })
+        }
+    }
+}
+
+impl Display for VectorTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(first) = self.rows.first() {
+            let plural = |n: usize, what: &str| match n {
+                1 => format!("1 {what}"),
+                n => format!("{n} {what}s"),
+            };
+            write!(
+                f,
+                "# {} | {}",
+                plural(first.input.len(), "input"),
+                plural(first.output.len(), "output")
+            )?;
+            if let Some(width) = self.width {
+                write!(f, ", recorded at {width} bits")?;
+            }
+            writeln!(f)?;
+        }
+        for row in &self.rows {
+            let join = |cells: &[Cell]| {
+                cells
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            writeln!(f, "{} | {}", join(&row.input), join(&row.output))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for VectorTable {
+    type Err = VectorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut table = VectorTable::default();
+        for (ndx, text) in s.lines().enumerate() {
+            let line = text.trim();
+            if let Some(comment) = line.strip_prefix('#') {
+                table.width = table.width.or_else(|| {
+                    comment
+                        .split_once("recorded at ")
+                        .and_then(|(_, rest)| rest.trim_end_matches(" bits").parse().ok())
+                });
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let err = || VectorError::Parse {
+                line: ndx + 1,
+                text: text.into(),
+            };
+            let (input, output) = line.split_once('|').ok_or_else(err)?;
+            let cells = |x: &str| {
+                x.split_whitespace()
+                    .map(|c| c.parse::<Cell>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| err())
+            };
+            table.rows.push(VectorRow {
+                input: cells(input)?,
+                output: cells(output)?,
+            });
+        }
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rhdl_bits::alias::*;
+
+    #[test]
+    fn test_round_trip() {
+        let run = [
+            ((true, b12(0xABC)), [s8(-3), s8(100)]),
+            ((false, b12(0)), [s8(-128), s8(0)]),
+        ];
+        let table = VectorTable::record(run);
+        let text = table.to_string();
+        assert_eq!(
+            text,
+            "# 2 inputs | 2 outputs, recorded at 16 bits\n1 2748 | -3 100\n0 0 | -128 0\n"
+        );
+        let parsed = text.parse::<VectorTable>().unwrap();
+        assert_eq!(parsed, table);
+        assert!(parsed.check(run).is_ok());
+    }
+
+    #[test]
+    fn test_truncation() {
+        let table = "5 | -3\n18 | 254".parse::<VectorTable>().unwrap();
+        assert_eq!(table.width, None);
+        assert_eq!(table.inputs::<b4>().unwrap(), vec![b4(5), b4(2)]);
+        assert_eq!(table.outputs::<s4>().unwrap(), vec![s4(-3), s4(-2)]);
+        assert_eq!(table.outputs::<b8>().unwrap(), vec![b8(0xFD), b8(0xFE)]);
+        assert_eq!(table.outputs::<bool>().unwrap(), vec![true, false]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let table = VectorTable::record([(b8(1), b8(2)), (b8(3), b8(4))]);
+        let err = table.check([(b4(1), b4(2)), (b4(3), b4(5))]).unwrap_err();
+        assert_eq!(
+            err,
+            VectorError::Mismatch {
+                row: 1,
+                width: 4,
+                expected: "4_b4".into(),
+                found: "5_b4".into(),
+            }
+        );
+        assert!(matches!(
+            table.check([(b4(1), b4(2))]),
+            Err(VectorError::Length { .. })
+        ));
+        assert!(matches!(
+            table.inputs::<(b4, b4)>(),
+            Err(VectorError::Cells {
+                row: 0,
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            "1 2\n".parse::<VectorTable>(),
+            Err(VectorError::Parse { line: 1, .. })
+        ));
+    }
+}
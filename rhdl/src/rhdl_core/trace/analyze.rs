@@ -0,0 +1,480 @@
+//! Activity analysis of simulation traces
+//!
+//! After a long soak test, it is worth checking that the design
+//! actually exercised itself.  A flag that never changed (stuck-at)
+//! usually means that the test never reached the condition, or that
+//! the logic driving it is broken.  A signal that changed in every
+//! single clock cycle may be a free running counter that should have
+//! had an enable.
+//!
+//! [activity_report] walks the [TraceDB] of a simulation, and for each
+//! traced signal (and each leaf field of a composite signal, like
+//! `top.outputs.full`) counts the number of changes, and records the
+//! times of the first and last change.  Leaf names are formed from the
+//! signal name and the path to the field, so that tuple elements are
+//! `.0`, array elements are `[3]`, and enum discriminants are `#`.
+//!
+//! The [assert_all_toggled!](crate::assert_all_toggled) macro checks
+//! that a list of signals each changed at least once.
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl::core::trace::{analyze::activity_report, db::trace};
+//!
+//! let guard = trace_init_db();
+//! for i in 0..10 {
+//!     trace_time(i * 100);
+//!     trace("busy", &(i % 2 == 0));
+//!     trace("done", &(i == 9));
+//!     trace("error", &false);
+//! }
+//! let db = guard.take();
+//! let report = activity_report(&db);
+//! assert_eq!(report.get("top.busy").unwrap().toggles, 9);
+//! assert_eq!(report.get("top.done").unwrap().first_change, Some(900));
+//! assert!(report.get("top.error").unwrap().stuck);
+//! rhdl::assert_all_toggled!(db, ["top.busy", "top.done"]);
+//!```
+use std::{collections::BTreeMap, ops::Range};
+
+use super::db::TraceDB;
+use crate::rhdl_core::{
+    TypedBits,
+    bitx::BitX,
+    types::path::{Path, bit_range, leaf_paths},
+};
+
+/// Options for the activity report
+#[derive(Clone, Debug)]
+pub struct ActivityOptions {
+    /// Signals to leave out of the report.  An entry matches a
+    /// signal with that name, and all of its fields.
+    pub ignore: Vec<String>,
+    /// A signal that changes fewer times than this is stuck
+    pub min_toggles: u64,
+    /// A signal that changes in at least this fraction of the
+    /// clock cycles is busy
+    pub busy_fraction: f64,
+    /// The clock used to count cycles
+    pub clock: String,
+}
+
+impl Default for ActivityOptions {
+    fn default() -> Self {
+        Self {
+            ignore: vec![],
+            min_toggles: 1,
+            busy_fraction: 1.0,
+            clock: "top.clock".into(),
+        }
+    }
+}
+
+impl ActivityOptions {
+    pub fn with_ignore(mut self, name: &str) -> Self {
+        self.ignore.push(name.into());
+        self
+    }
+    pub fn with_min_toggles(self, min_toggles: u64) -> Self {
+        Self {
+            min_toggles,
+            ..self
+        }
+    }
+    pub fn with_busy_fraction(self, busy_fraction: f64) -> Self {
+        Self {
+            busy_fraction,
+            ..self
+        }
+    }
+    pub fn with_clock(self, clock: &str) -> Self {
+        Self {
+            clock: clock.into(),
+            ..self
+        }
+    }
+    fn ignored(&self, name: &str) -> bool {
+        self.ignore.iter().any(|prefix| {
+            name.strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[', '#', '@']))
+        })
+    }
+}
+
+/// The activity of a single signal (or field of a signal)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignalActivity {
+    /// The number of times the value changed
+    pub toggles: u64,
+    /// The time of the first change
+    pub first_change: Option<u64>,
+    /// The time of the last change
+    pub last_change: Option<u64>,
+    /// The number of clock cycles in which the value changed (if
+    /// the clock was traced).  A signal can change several times in
+    /// a cycle, as the inputs and the clock are updated.
+    pub active_cycles: Option<u64>,
+    /// The value when the signal was first traced
+    pub initial: TypedBits,
+    /// The signal changed fewer than `min_toggles` times
+    pub stuck: bool,
+    /// The signal changed in (nearly) every clock cycle
+    pub busy: bool,
+}
+
+/// The activity of all of the signals in a trace
+#[derive(Clone, Debug, Default)]
+pub struct ActivityReport {
+    /// The number of clock cycles (rising edges of the clock), if
+    /// the clock was traced
+    pub cycles: Option<u64>,
+    /// The activity of each signal and field, by name
+    pub signals: BTreeMap<String, SignalActivity>,
+}
+
+impl ActivityReport {
+    pub fn get(&self, name: &str) -> Option<&SignalActivity> {
+        self.signals.get(name)
+    }
+    /// The signals that are stuck
+    pub fn stuck(&self) -> impl Iterator<Item = (&str, &SignalActivity)> {
+        self.signals
+            .iter()
+            .filter(|(_, activity)| activity.stuck)
+            .map(|(name, activity)| (name.as_str(), activity))
+    }
+    /// The signals that are busy
+    pub fn busy(&self) -> impl Iterator<Item = (&str, &SignalActivity)> {
+        self.signals
+            .iter()
+            .filter(|(_, activity)| activity.busy)
+            .map(|(name, activity)| (name.as_str(), activity))
+    }
+    /// Those of `names` that never changed, or are not in the report
+    pub fn untoggled<'a>(&self, names: &[&'a str]) -> Vec<&'a str> {
+        names
+            .iter()
+            .copied()
+            .filter(|name| self.get(name).is_none_or(|activity| activity.toggles == 0))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ActivityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(cycles) = self.cycles {
+            writeln!(f, "{cycles} cycles")?;
+        }
+        for (name, activity) in &self.signals {
+            let flag = match (activity.stuck, activity.busy) {
+                (true, _) => " (stuck)",
+                (_, true) => " (busy)",
+                _ => "",
+            };
+            match (activity.first_change, activity.last_change) {
+                (Some(first), Some(last)) => writeln!(
+                    f,
+                    "{name}: {} toggles in {first}..={last}{flag}",
+                    activity.toggles
+                )?,
+                _ => writeln!(f, "{name}: constant {:?}{flag}", activity.initial)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// The times of the changes in a slice of the bits of a signal
+fn changes(history: &[(u64, TypedBits)], range: Range<usize>) -> Vec<u64> {
+    history
+        .windows(2)
+        .filter(|pair| pair[0].1.bits[range.clone()] != pair[1].1.bits[range.clone()])
+        .map(|pair| pair[1].0)
+        .collect()
+}
+
+// The number of distinct clock cycles (delimited by the rising
+// edges) that contain a change
+fn active_cycles(changes: &[u64], edges: &[u64]) -> u64 {
+    let mut cycles = changes
+        .iter()
+        .map(|time| edges.partition_point(|edge| edge <= time))
+        .collect::<Vec<_>>();
+    cycles.dedup();
+    cycles.len() as u64
+}
+
+/// Compute the activity report for a trace with the default options
+pub fn activity_report(trace: &TraceDB) -> ActivityReport {
+    activity_report_with(trace, &ActivityOptions::default())
+}
+
+/// Compute the activity report for a trace
+pub fn activity_report_with(trace: &TraceDB, options: &ActivityOptions) -> ActivityReport {
+    let histories = trace.histories();
+    let edges = histories.get(&options.clock).map(|history| {
+        history
+            .iter()
+            .filter(|(_, value)| value.bits.first() == Some(&BitX::One))
+            .map(|(time, _)| *time)
+            .collect::<Vec<_>>()
+    });
+    let cycles = edges.as_ref().map(|edges| edges.len() as u64);
+    let mut signals = BTreeMap::new();
+    for (name, history) in &histories {
+        let Some((_, initial)) = history.first() else {
+            continue;
+        };
+        if options.ignored(name) {
+            continue;
+        }
+        let mut entries = vec![(name.clone(), Path::default())];
+        let leaves = leaf_paths(&initial.kind, Path::default());
+        if leaves.iter().any(|leaf| !leaf.is_empty()) {
+            entries.extend(
+                leaves
+                    .into_iter()
+                    .map(|leaf| (format!("{name}{leaf:?}"), leaf)),
+            );
+        }
+        for (entry, path) in entries {
+            if options.ignored(&entry) {
+                continue;
+            }
+            let Ok((range, _)) = bit_range(initial.kind, &path) else {
+                continue;
+            };
+            // Fields with no bits (like the payload of `None`) cannot change
+            if range.is_empty() {
+                continue;
+            }
+            let changes = changes(history, range);
+            let toggles = changes.len() as u64;
+            let active_cycles = edges.as_ref().map(|edges| active_cycles(&changes, edges));
+            let busy = *name != options.clock
+                && cycles.zip(active_cycles).is_some_and(|(cycles, active)| {
+                    cycles > 0 && active as f64 >= options.busy_fraction * cycles as f64
+                });
+            signals.insert(
+                entry,
+                SignalActivity {
+                    toggles,
+                    first_change: changes.first().copied(),
+                    last_change: changes.last().copied(),
+                    active_cycles,
+                    initial: initial.path(&path).unwrap_or_else(|_| initial.clone()),
+                    stuck: toggles < options.min_toggles,
+                    busy,
+                },
+            );
+        }
+    }
+    ActivityReport { cycles, signals }
+}
+
+/// Panic if any of `names` never changed in the trace (or is not in it).
+/// This is the function behind [assert_all_toggled!](crate::assert_all_toggled).
+pub fn assert_all_toggled(trace: &TraceDB, names: &[&str]) {
+    let options = ActivityOptions::default();
+    let report = activity_report_with(trace, &options);
+    let untoggled = report.untoggled(names);
+    if untoggled.is_empty() {
+        return;
+    }
+    let details = untoggled
+        .iter()
+        .map(|name| match report.get(name) {
+            Some(activity) => format!("  {name} is stuck at {:?}", activity.initial),
+            None => format!("  {name} is not in the trace"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    panic!("Some signals never toggled:\n{details}");
+}
+
+/// Assert that each of the named signals changed at least once in
+/// a trace.  The trace is a [TraceDB], for example from
+/// `trace_init_db().take()` after a simulation.
+///
+/// ```should_panic
+/// use rhdl::prelude::*;
+/// use rhdl::core::trace::db::trace;
+///
+/// let guard = trace_init_db();
+/// trace_time(0);
+/// trace("empty", &true);
+/// trace_time(100);
+/// trace("empty", &true);
+/// rhdl::assert_all_toggled!(guard.take(), ["top.empty"]);
+/// ```
+#[macro_export]
+macro_rules! assert_all_toggled {
+    ($trace:expr, [$($name:expr),* $(,)?]) => {
+        $crate::core::trace::analyze::assert_all_toggled(&$trace, &[$($name),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rhdl_bits::alias::*,
+        rhdl_core::{Digital, trace_init_db, trace_time, types::clock::clock},
+    };
+
+    use super::super::db::{trace, trace_pop_path, trace_push_path};
+
+    // Trace a clocked counter for `cycles` cycles, with a
+    // flag that rises once, and a constant
+    fn counter_trace(cycles: u64) -> TraceDB {
+        let guard = trace_init_db();
+        for i in 0..cycles {
+            for (offset, level) in [(0, false), (50, true)] {
+                trace_time(i * 100 + offset);
+                trace("clock", &clock(level));
+                trace_push_path("counter");
+                trace("count", &b4(i as u128 % 16));
+                trace_pop_path();
+                trace("flag", &(i >= 3));
+                trace("zero", &b8(0));
+            }
+        }
+        guard.take()
+    }
+
+    #[test]
+    fn test_toggle_counts() {
+        let report = activity_report(&counter_trace(10));
+        assert_eq!(report.cycles, Some(10));
+        let count = report.get("top.counter.count").unwrap();
+        assert_eq!(count.toggles, 9);
+        assert_eq!(count.first_change, Some(100));
+        assert_eq!(count.last_change, Some(900));
+        assert_eq!(count.active_cycles, Some(9));
+        assert!(!count.stuck);
+        let flag = report.get("top.flag").unwrap();
+        assert_eq!(flag.toggles, 1);
+        assert_eq!(flag.first_change, Some(300));
+        assert_eq!(flag.last_change, Some(300));
+    }
+
+    #[test]
+    fn test_stuck_and_busy() {
+        let report = activity_report(&counter_trace(10));
+        let zero = report.get("top.zero").unwrap();
+        assert_eq!(zero.toggles, 0);
+        assert_eq!(zero.first_change, None);
+        assert_eq!(zero.initial, b8(0).typed_bits());
+        assert!(zero.stuck);
+        // The counter changes on all but the first cycle
+        assert!(!report.get("top.counter.count").unwrap().busy);
+        let report = activity_report_with(
+            &counter_trace(10),
+            &ActivityOptions::default().with_busy_fraction(0.9),
+        );
+        assert_eq!(
+            report.busy().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["top.counter.count"]
+        );
+        assert_eq!(
+            report.stuck().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["top.zero"]
+        );
+    }
+
+    #[test]
+    fn test_thresholds_and_ignore() {
+        let options = ActivityOptions::default()
+            .with_min_toggles(2)
+            .with_ignore("top.zero")
+            .with_ignore("top.counter");
+        let report = activity_report_with(&counter_trace(10), &options);
+        assert!(report.get("top.zero").is_none());
+        assert!(report.get("top.counter.count").is_none());
+        assert!(report.get("top.flag").unwrap().stuck);
+        // An ignore entry only matches whole names
+        let options = ActivityOptions::default().with_ignore("top.fl");
+        let report = activity_report_with(&counter_trace(10), &options);
+        assert!(report.get("top.flag").is_some());
+    }
+
+    #[test]
+    fn test_busy_counts_cycles() {
+        // A strobe that follows the clock changes twice per cycle,
+        // and a value that glitches within every other cycle
+        let guard = trace_init_db();
+        for i in 0..10 {
+            for (offset, level) in [(0, false), (10, true), (25, true), (50, true)] {
+                trace_time(i * 100 + offset);
+                trace("clock", &clock(offset == 50));
+                trace("strobe", &level);
+                trace("glitch", &(offset == 10 && i % 2 == 0));
+            }
+        }
+        let report = activity_report(&guard.take());
+        let strobe = report.get("top.strobe").unwrap();
+        assert_eq!(strobe.toggles, 19);
+        assert_eq!(strobe.active_cycles, Some(10));
+        assert!(strobe.busy);
+        let glitch = report.get("top.glitch").unwrap();
+        assert_eq!(glitch.toggles, 10);
+        assert_eq!(glitch.active_cycles, Some(5));
+        assert!(!glitch.busy);
+        assert!(!report.get("top.clock").unwrap().busy);
+    }
+
+    #[test]
+    fn test_without_clock() {
+        let guard = trace_init_db();
+        for i in 0..5 {
+            trace_time(i * 10);
+            trace("a", &(i % 2 == 0));
+        }
+        let report = activity_report(&guard.take());
+        assert_eq!(report.cycles, None);
+        assert!(!report.get("top.a").unwrap().busy);
+    }
+
+    #[test]
+    fn test_composite_leaves() {
+        let guard = trace_init_db();
+        for i in 0..8 {
+            trace_time(i * 10);
+            trace("pair", &(i >= 4, b4(i as u128)));
+            trace("array", &[true, i % 3 == 0]);
+            trace("maybe", &(i == 5).then_some(b4(i as u128)));
+        }
+        let report = activity_report(&guard.take());
+        assert_eq!(report.get("top.pair").unwrap().toggles, 7);
+        assert_eq!(report.get("top.pair.0").unwrap().toggles, 1);
+        assert_eq!(report.get("top.pair.1").unwrap().toggles, 7);
+        assert!(report.get("top.array[0]").unwrap().stuck);
+        assert_eq!(report.get("top.array[1]").unwrap().toggles, 5);
+        assert_eq!(report.get("top.maybe#").unwrap().toggles, 2);
+        assert_eq!(report.get("top.maybe#Some.0").unwrap().toggles, 2);
+        // The payload of None has no bits, so it is not reported
+        assert!(report.get("top.maybe#None").is_none());
+    }
+
+    #[test]
+    fn test_assert_all_toggled() {
+        let db = counter_trace(10);
+        crate::assert_all_toggled!(db, ["top.flag", "top.counter.count",]);
+    }
+
+    #[test]
+    #[should_panic(expected = "top.zero is stuck")]
+    fn test_assert_all_toggled_stuck() {
+        let db = counter_trace(10);
+        crate::assert_all_toggled!(db, ["top.flag", "top.zero"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "top.missing is not in the trace")]
+    fn test_assert_all_toggled_missing() {
+        let db = counter_trace(10);
+        crate::assert_all_toggled!(db, ["top.missing"]);
+    }
+}
@@ -9,10 +9,11 @@ use std::{
 use rhdl_trace_type::{RTT, TraceType};
 use smallvec::SmallVec;
 
-use crate::rhdl_core::Digital;
+use crate::rhdl_core::{Digital, TypedBits};
 
 use super::{
     bit::TraceBit,
+    fst::FstWriter,
     key::TraceKey,
     svg::{SvgOptions, Trace, render_traces_as_svg_document, trace_out},
     vcd::VCDWrite,
@@ -31,6 +32,13 @@ trait TimeSeriesWalk {
     ) -> Option<Cursor>;
     fn advance_cursor(&self, cursor: &mut Cursor);
     fn write_vcd(&self, cursor: &mut Cursor, writer: &mut dyn VCDWrite) -> std::io::Result<()>;
+    fn write_fst(
+        &self,
+        name: &str,
+        writer: &mut FstWriter,
+        start_time: u64,
+        time_set: Option<&fnv::FnvHashSet<u64>>,
+    );
 }
 
 trait SVGRender {
@@ -64,6 +72,16 @@ struct TimeSeriesDetails {
     key: String,
 }
 
+impl TimeSeriesDetails {
+    fn name(&self) -> String {
+        format!(
+            "{}.{}",
+            [&["top"], &self.path[..]].concat().join("."),
+            self.key
+        )
+    }
+}
+
 impl<T: Digital> TimeSeries<T> {
     fn new(time: u64, value: T) -> Self {
         let mut values = Vec::with_capacity(1_000_000);
@@ -80,7 +98,44 @@ impl<T: Digital> TimeSeries<T> {
     }
 }
 
-trait AnyTimeSeries: AsAny + TimeSeriesWalk + SVGRender {}
+trait TimeSeriesValue {
+    fn last_value(&self) -> Option<TypedBits>;
+    fn history(&self) -> Vec<(u64, TypedBits)>;
+}
+
+impl<T: Digital> TimeSeriesValue for TimeSeries<T> {
+    fn last_value(&self) -> Option<TypedBits> {
+        self.0.last().map(|(_, value)| value.typed_bits())
+    }
+    fn history(&self) -> Vec<(u64, TypedBits)> {
+        self.0
+            .iter()
+            .map(|(time, value)| (*time, value.typed_bits()))
+            .collect()
+    }
+}
+
+trait TimeSeriesTrim {
+    fn trim_before(&mut self, time: u64);
+}
+
+impl<T: Digital> TimeSeriesTrim for TimeSeries<T> {
+    fn trim_before(&mut self, time: u64) {
+        let earlier = self.0.partition_point(|(t, _)| *t < time);
+        if earlier == 0 {
+            return;
+        }
+        if self.0.get(earlier).is_some_and(|(t, _)| *t == time) {
+            self.0.drain(..earlier);
+        } else {
+            // Keep the value in effect at `time`, restamped to `time`
+            self.0.drain(..earlier - 1);
+            self.0[0].0 = time;
+        }
+    }
+}
+
+trait AnyTimeSeries: AsAny + TimeSeriesWalk + SVGRender + TimeSeriesValue + TimeSeriesTrim {}
 
 impl<T: Digital> SVGRender for TimeSeries<T> {
     fn render(&self, name: &str, time_set: std::ops::RangeInclusive<u64>) -> Box<[Trace]> {
@@ -155,6 +210,35 @@ impl<T: Digital> TimeSeriesWalk for TimeSeries<T> {
             ))
         }
     }
+    fn write_fst(
+        &self,
+        name: &str,
+        writer: &mut FstWriter,
+        start_time: u64,
+        time_set: Option<&fnv::FnvHashSet<u64>>,
+    ) {
+        let name_sanitized = name.replace("::", "__");
+        let var = writer.add_wire(T::TRACE_BITS, &name_sanitized);
+        if T::BITS == 0 {
+            return;
+        }
+        for (time, value) in self.0.iter().filter(|(time, _)| {
+            start_time <= *time && time_set.map(|x| x.contains(time)).unwrap_or(true)
+        }) {
+            let sbuf = value
+                .trace()
+                .into_iter()
+                .rev()
+                .map(|v| match v {
+                    TraceBit::Zero => b'0',
+                    TraceBit::One => b'1',
+                    TraceBit::X => b'x',
+                    TraceBit::Z => b'z',
+                })
+                .collect::<SmallVec<[u8; 64]>>();
+            writer.change(var, *time, &sbuf);
+        }
+    }
 }
 
 #[derive(Default)]
@@ -245,22 +329,86 @@ impl TraceDB {
         writer.upscope()?;
         Ok(())
     }
+    fn setup_fst_scope(
+        &self,
+        name: &str,
+        scope: &Scope,
+        writer: &mut FstWriter,
+        start_time: u64,
+        time_set: Option<&fnv::FnvHashSet<u64>>,
+    ) {
+        writer.add_module(name);
+        for (name, hash) in &scope.signals {
+            if let Some(series) = self.db.get(hash) {
+                series.write_fst(name, writer, start_time, time_set);
+            }
+        }
+        for (name, child) in &scope.children {
+            self.setup_fst_scope(name, child, writer, start_time, time_set);
+        }
+        writer.upscope();
+    }
     fn collect_rtt_info(&self) -> RTT {
         RTT::TraceInfo(
             self.details
                 .values()
                 .map(|details| {
-                    let name = format!(
-                        "{}.{}",
-                        [&["top"], &details.path[..]].concat().join("."),
-                        details.key
-                    );
+                    let name = details.name();
                     let ty = details.trace_type.clone();
                     (name, ty)
                 })
                 .collect(),
         )
     }
+    /// Discard the history of every signal before `time`.  The value
+    /// each signal held at `time` is kept (with a timestamp of `time`),
+    /// so that a dump starting at `time` is complete.
+    pub fn trim_before(&mut self, time: u64) {
+        for series in self.db.values_mut() {
+            series.trim_before(time);
+        }
+    }
+    /// The most recent value of the signal with the given hierarchical
+    /// name (e.g. `top.counter.dff.output`)
+    pub fn signal(&self, name: &str) -> Option<TypedBits> {
+        self.details
+            .values()
+            .find(|details| details.name() == name)
+            .and_then(|details| self.db.get(&details.hash))
+            .and_then(|series| series.last_value())
+    }
+    /// The most recent values of all of the signals, keyed
+    /// by their hierarchical names
+    pub fn signals(&self) -> BTreeMap<String, TypedBits> {
+        self.details
+            .values()
+            .filter_map(|details| {
+                let value = self.db.get(&details.hash)?.last_value()?;
+                Some((details.name(), value))
+            })
+            .collect()
+    }
+    /// Every recorded change of the signal with the given hierarchical
+    /// name, as `(time, value)` pairs.  The first entry is the value
+    /// when the signal was first traced.
+    pub fn history(&self, name: &str) -> Option<Vec<(u64, TypedBits)>> {
+        self.details
+            .values()
+            .find(|details| details.name() == name)
+            .and_then(|details| self.db.get(&details.hash))
+            .map(|series| series.history())
+    }
+    /// The histories of all of the signals, keyed by their
+    /// hierarchical names
+    pub fn histories(&self) -> BTreeMap<String, Vec<(u64, TypedBits)>> {
+        self.details
+            .values()
+            .filter_map(|details| {
+                let series = self.db.get(&details.hash)?;
+                Some((details.name(), series.history()))
+            })
+            .collect()
+    }
     pub fn dump_svg(
         &self,
         time_set: std::ops::RangeInclusive<u64>,
@@ -269,14 +417,7 @@ impl TraceDB {
         let trace_order = self
             .details
             .iter()
-            .map(|(key, details)| {
-                let name = format!(
-                    "{}.{}",
-                    [&["top"], &details.path[..]].concat().join("."),
-                    details.key
-                );
-                (name, *key)
-            })
+            .map(|(key, details)| (details.name(), *key))
             .collect::<BTreeMap<_, _>>();
         let traces = trace_order
             .iter()
@@ -339,6 +480,23 @@ impl TraceDB {
         }
         Ok(())
     }
+    pub fn dump_fst<W: Write>(
+        &self,
+        w: W,
+        time_set: Option<&fnv::FnvHashSet<u64>>,
+    ) -> std::io::Result<()> {
+        let mut writer = FstWriter::new(-12);
+        let rtt = self.collect_rtt_info();
+        writer.comment(&ron::ser::to_string(&rtt).unwrap());
+        let root_scope = hierarchical_walk(self.details.iter().map(|(hash, details)| TSItem {
+            path: &details.path,
+            name: &details.key,
+            hash: *hash,
+        }));
+        let min_time = time_set.and_then(|x| x.iter().copied().min()).unwrap_or(0);
+        self.setup_fst_scope("top", &root_scope, &mut writer, min_time, time_set);
+        writer.finish(w)
+    }
 }
 
 struct TSItem<'a> {
@@ -432,6 +590,15 @@ pub fn trace_time(time: u64) {
     })
 }
 
+pub fn trace_trim_before(time: u64) {
+    DB.with(|db| {
+        let mut db = db.borrow_mut();
+        if let Some(db) = db.as_mut() {
+            db.trim_before(time);
+        }
+    })
+}
+
 pub fn trace(key: impl TraceKey, value: &impl Digital) {
     DB.with(|db| {
         let mut db = db.borrow_mut();
@@ -617,4 +784,106 @@ mod tests {
         db.dump_vcd(&mut vcd, None).unwrap();
         std::fs::write("test_nested_paths.vcd", vcd).unwrap();
     }
+
+    #[test]
+    fn test_trim_keeps_value_in_effect() {
+        let guard = trace_init_db();
+        for i in 0..10_u64 {
+            trace_time(i * 100);
+            trace("count", &b6(i as u128));
+            trace("slow", &b6((i / 4) as u128));
+        }
+        // Trimming at a change keeps it, and trimming between changes
+        // restamps the value in effect
+        trace_trim_before(500);
+        let db = guard.take();
+        let history = |name| {
+            db.history(name)
+                .unwrap()
+                .into_iter()
+                .map(|(time, value)| (time, value.as_i64().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            history("top.count"),
+            [(500, 5), (600, 6), (700, 7), (800, 8), (900, 9)]
+        );
+        assert_eq!(history("top.slow"), [(500, 1), (800, 2)]);
+    }
+
+    #[test]
+    fn test_fst_round_trip() {
+        use super::super::fst::reader::read_fst;
+        // Enough time points to span several value change blocks
+        let guard = trace_init_db();
+        let mut expected: BTreeMap<&str, Vec<(u64, String)>> = BTreeMap::new();
+        let mut model = |name, time, value: String| {
+            let series = expected.entry(name).or_default();
+            if series.last().map(|(_, v)| v) != Some(&value) {
+                series.push((time, value));
+            }
+        };
+        for i in 0..50_000_u64 {
+            let time = i * 1000;
+            trace_time(time);
+            let clk = i % 2 == 0;
+            trace("clk", &clk);
+            model("top.clk", time, if clk { "1" } else { "0" }.into());
+            let count = b6((i / 3 % 64) as u128);
+            trace_push_path("fn1");
+            trace("count", &count);
+            model("top.fn1.count", time, format!("{:06b}", i / 3 % 64));
+            trace_push_path("fn2");
+            let wide = (i / 100) as u128 * 0x1234_5678_9abc_def1;
+            trace("wide", &b64(wide & 0xffff_ffff_ffff_ffff));
+            model(
+                "top.fn1.fn2.wide",
+                time,
+                format!("{:064b}", wide & 0xffff_ffff_ffff_ffff),
+            );
+            trace_pop_path();
+            trace_pop_path();
+            let opt = (i % 1000 > 500).then_some(b4((i % 16) as u128));
+            trace("opt", &opt);
+            let opt_bits = opt
+                .trace()
+                .into_iter()
+                .rev()
+                .map(|b| match b {
+                    TraceBit::Zero => '0',
+                    TraceBit::One => '1',
+                    TraceBit::X => 'x',
+                    TraceBit::Z => 'z',
+                })
+                .collect();
+            model("top.opt", time, opt_bits);
+        }
+        let db = guard.take();
+        let mut fst = vec![];
+        db.dump_fst(&mut fst, None).unwrap();
+        let mut vcd = vec![];
+        db.dump_vcd(&mut vcd, None).unwrap();
+        assert!(fst.len() * 5 < vcd.len());
+        let file = read_fst(&fst);
+        assert_eq!(file.timescale, -12);
+        assert_eq!(file.end_time, 49_999_000);
+        assert!(file.vc_sections > 1);
+        // The trace type information is attached as a comment, as for VCD
+        assert_eq!(
+            file.comments,
+            [ron::ser::to_string(&db.collect_rtt_info()).unwrap()]
+        );
+        let names = file
+            .signals
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["top.clk", "top.opt", "top.fn1.count", "top.fn1.fn2.wide"]
+        );
+        for ((name, _), changes) in file.signals.iter().zip(&file.changes) {
+            assert_eq!(changes, &expected[name.as_str()], "Mismatch in {name}");
+        }
+    }
 }
@@ -0,0 +1,738 @@
+// A native writer for the FST (Fast Signal Trace) waveform format used
+// by GTKWave and Surfer.  FST files are much smaller than the equivalent
+// VCD, since value changes are stored per signal in compressed blocks,
+// and times are stored as indices into a per-block time table.
+//
+// The file consists of a header block, followed by one or more value change
+// blocks, a geometry block (the width of each signal) and a hierarchy block
+// (the scopes and variables).
+use std::io::Write;
+
+use flate2::{
+    Compression,
+    write::{GzEncoder, ZlibEncoder},
+};
+
+const BL_HDR: u8 = 0;
+const BL_GEOM: u8 = 3;
+const BL_HIER: u8 = 4;
+const BL_VCDATA_DYN_ALIAS2: u8 = 8;
+
+const HDR_SIZE: u64 = 329;
+const HDR_SIM_VERSION_SIZE: usize = 128;
+const HDR_DATE_SIZE: usize = 119;
+const DOUBLE_ENDTEST: f64 = std::f64::consts::E;
+const FT_VERILOG: u8 = 0;
+
+const ST_VCD_MODULE: u8 = 0;
+const ST_GEN_ATTRBEGIN: u8 = 252;
+const ST_VCD_SCOPE: u8 = 254;
+const ST_VCD_UPSCOPE: u8 = 255;
+const AT_MISC: u8 = 0;
+const MT_COMMENT: u8 = 0;
+const VT_VCD_WIRE: u8 = 16;
+const VD_IMPLICIT: u8 = 0;
+
+// The single bit value codes for non-binary values
+const RCV_STR: &[u8] = b"xzhuwl-?";
+
+// The maximum number of distinct time points in a value change block
+pub(crate) const BLOCK_TIMES: usize = 16384;
+
+fn varint(buf: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn svarint(buf: &mut Vec<u8>, mut v: i64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0) {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn zlib(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+// Compress the data if that makes it smaller.  Readers detect
+// uncompressed data by the compressed and uncompressed lengths
+// being equal.
+fn zlib_if_smaller(data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let compressed = zlib(&data)?;
+    Ok(if compressed.len() < data.len() {
+        compressed
+    } else {
+        data
+    })
+}
+
+fn write_block<W: Write>(w: &mut W, block_type: u8, body: &[u8]) -> std::io::Result<()> {
+    w.write_all(&[block_type])?;
+    w.write_all(&(body.len() as u64 + 8).to_be_bytes())?;
+    w.write_all(body)
+}
+
+struct Var {
+    width: usize,
+    // Value changes as (time, value), with the value as
+    // ASCII characters, most significant bit first
+    changes: Vec<(u64, Box<[u8]>)>,
+}
+
+pub(crate) struct FstWriter {
+    hierarchy: Vec<u8>,
+    vars: Vec<Var>,
+    scope_count: u64,
+    timescale: i8,
+    block_times: usize,
+}
+
+impl FstWriter {
+    // The timescale is given as a power of 10 seconds (-12 is 1ps)
+    pub(crate) fn new(timescale: i8) -> Self {
+        Self {
+            hierarchy: vec![],
+            vars: vec![],
+            scope_count: 0,
+            timescale,
+            block_times: BLOCK_TIMES,
+        }
+    }
+    #[cfg(test)]
+    pub(crate) fn with_block_times(self, block_times: usize) -> Self {
+        Self {
+            block_times,
+            ..self
+        }
+    }
+    pub(crate) fn comment(&mut self, text: &str) {
+        self.hierarchy
+            .extend([ST_GEN_ATTRBEGIN, AT_MISC, MT_COMMENT]);
+        self.hierarchy.extend(text.bytes().filter(|b| *b != 0));
+        self.hierarchy.push(0);
+        varint(&mut self.hierarchy, 0);
+    }
+    pub(crate) fn add_module(&mut self, name: &str) {
+        self.hierarchy.extend([ST_VCD_SCOPE, ST_VCD_MODULE]);
+        self.hierarchy.extend(name.bytes());
+        // The name is followed by an (empty) component name
+        self.hierarchy.extend([0, 0]);
+        self.scope_count += 1;
+    }
+    pub(crate) fn upscope(&mut self) {
+        self.hierarchy.push(ST_VCD_UPSCOPE);
+    }
+    // Returns the index of the new variable, to be used with `change`
+    pub(crate) fn add_wire(&mut self, width: usize, name: &str) -> usize {
+        let width = width.max(1);
+        self.hierarchy.extend([VT_VCD_WIRE, VD_IMPLICIT]);
+        self.hierarchy.extend(name.bytes());
+        self.hierarchy.push(0);
+        varint(&mut self.hierarchy, width as u64);
+        // An alias of zero means this is a new signal
+        varint(&mut self.hierarchy, 0);
+        self.vars.push(Var {
+            width,
+            changes: vec![],
+        });
+        self.vars.len() - 1
+    }
+    // Changes for a given variable must be in time order.  The value
+    // is a string of `0`, `1`, `x` or `z` characters, most significant
+    // bit first.
+    pub(crate) fn change(&mut self, var: usize, time: u64, value: &[u8]) {
+        let var = &mut self.vars[var];
+        assert_eq!(var.width, value.len(), "FST value width mismatch");
+        if let Some(last) = var.changes.last_mut() {
+            assert!(last.0 <= time, "FST value changes must be in time order");
+            if last.0 == time {
+                last.1 = value.into();
+                return;
+            }
+        }
+        var.changes.push((time, value.into()));
+    }
+    pub(crate) fn finish<W: Write>(self, mut w: W) -> std::io::Result<()> {
+        let mut times = self
+            .vars
+            .iter()
+            .flat_map(|var| var.changes.iter().map(|(time, _)| *time))
+            .collect::<Vec<_>>();
+        times.sort_unstable();
+        times.dedup();
+        if times.is_empty() {
+            times.push(0);
+        }
+        let blocks = times.chunks(self.block_times).collect::<Vec<_>>();
+        self.write_header(&mut w, &times, blocks.len())?;
+        let mut cursors = vec![0; self.vars.len()];
+        let mut current = self
+            .vars
+            .iter()
+            .map(|var| vec![b'x'; var.width].into_boxed_slice())
+            .collect::<Vec<_>>();
+        for block in blocks {
+            self.write_value_changes(&mut w, block, &mut cursors, &mut current)?;
+        }
+        self.write_geometry(&mut w)?;
+        self.write_hierarchy(&mut w)
+    }
+    fn write_header<W: Write>(
+        &self,
+        w: &mut W,
+        times: &[u64],
+        vc_sections: usize,
+    ) -> std::io::Result<()> {
+        let mut body = vec![];
+        body.extend(times[0].to_be_bytes());
+        body.extend(times[times.len() - 1].to_be_bytes());
+        body.extend(DOUBLE_ENDTEST.to_le_bytes());
+        // Memory used by the writer
+        body.extend(0_u64.to_be_bytes());
+        body.extend(self.scope_count.to_be_bytes());
+        body.extend((self.vars.len() as u64).to_be_bytes());
+        // The maximum handle (which is the number of signals)
+        body.extend((self.vars.len() as u64).to_be_bytes());
+        body.extend((vc_sections as u64).to_be_bytes());
+        body.push(self.timescale as u8);
+        let mut version = b"RHDL".to_vec();
+        version.resize(HDR_SIM_VERSION_SIZE, 0);
+        body.extend(version);
+        // The date is left empty, so that the output is reproducible
+        body.extend([0; HDR_DATE_SIZE]);
+        body.push(FT_VERILOG);
+        // Time zero
+        body.extend(0_i64.to_be_bytes());
+        debug_assert_eq!(body.len() as u64 + 8, HDR_SIZE);
+        write_block(w, BL_HDR, &body)
+    }
+    fn write_value_changes<W: Write>(
+        &self,
+        w: &mut W,
+        times: &[u64],
+        cursors: &mut [usize],
+        current: &mut [Box<[u8]>],
+    ) -> std::io::Result<()> {
+        let start = times[0];
+        let end = times[times.len() - 1];
+        // The frame holds the value of every signal at the start of the block
+        let mut frame = vec![];
+        for (ndx, var) in self.vars.iter().enumerate() {
+            while let Some((time, value)) = var.changes.get(cursors[ndx]) {
+                if *time > start {
+                    break;
+                }
+                current[ndx] = value.clone();
+                cursors[ndx] += 1;
+            }
+            frame.extend(current[ndx].iter());
+        }
+        // Encode the changes for each signal within the block.  Times
+        // are encoded as deltas of indices into the time table.
+        let mut chunks = vec![];
+        for (ndx, var) in self.vars.iter().enumerate() {
+            let mut data = vec![];
+            let mut last_index = 0;
+            while let Some((time, value)) = var.changes.get(cursors[ndx]) {
+                if *time > end {
+                    break;
+                }
+                let index = times.binary_search(time).expect("time missing from table");
+                let delta = (index - last_index) as u64;
+                last_index = index;
+                encode_value(&mut data, delta, value);
+                current[ndx] = value.clone();
+                cursors[ndx] += 1;
+            }
+            chunks.push(data);
+        }
+        let mem_required = frame.len() + chunks.iter().map(|c| c.len()).sum::<usize>();
+        let mut body = vec![];
+        body.extend(start.to_be_bytes());
+        body.extend(end.to_be_bytes());
+        body.extend((mem_required as u64).to_be_bytes());
+        let frame_len = frame.len();
+        let frame = zlib_if_smaller(frame)?;
+        varint(&mut body, frame_len as u64);
+        varint(&mut body, frame.len() as u64);
+        varint(&mut body, self.vars.len() as u64);
+        body.extend(frame);
+        varint(&mut body, self.vars.len() as u64);
+        // The offsets of the signal data are relative to the pack type
+        let vc_start = body.len();
+        body.push(b'Z');
+        let mut offsets = vec![];
+        for data in chunks {
+            if data.is_empty() {
+                offsets.push(None);
+                continue;
+            }
+            offsets.push(Some(body.len() - vc_start));
+            let compressed = zlib(&data)?;
+            if compressed.len() < data.len() {
+                varint(&mut body, data.len() as u64);
+                body.extend(compressed);
+            } else {
+                varint(&mut body, 0);
+                body.extend(data);
+            }
+        }
+        // The chain table holds the offsets as deltas, with runs of
+        // signals that have no changes encoded as a count
+        let chain_start = body.len();
+        let mut previous = 0;
+        let mut zeros = 0;
+        for offset in offsets {
+            match offset {
+                Some(offset) => {
+                    if zeros != 0 {
+                        varint(&mut body, zeros << 1);
+                        zeros = 0;
+                    }
+                    svarint(&mut body, (((offset - previous) as i64) << 1) | 1);
+                    previous = offset;
+                }
+                None => zeros += 1,
+            }
+        }
+        if zeros != 0 {
+            varint(&mut body, zeros << 1);
+        }
+        let chain_len = body.len() - chain_start;
+        body.extend((chain_len as u64).to_be_bytes());
+        // The time table is stored as deltas
+        let mut time_table = vec![];
+        let mut previous = 0;
+        for time in times {
+            varint(&mut time_table, time - previous);
+            previous = *time;
+        }
+        let time_len = time_table.len();
+        let time_table = zlib_if_smaller(time_table)?;
+        let time_clen = time_table.len();
+        body.extend(time_table);
+        body.extend((time_len as u64).to_be_bytes());
+        body.extend((time_clen as u64).to_be_bytes());
+        body.extend((times.len() as u64).to_be_bytes());
+        write_block(w, BL_VCDATA_DYN_ALIAS2, &body)
+    }
+    fn write_geometry<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let mut geometry = vec![];
+        for var in &self.vars {
+            varint(&mut geometry, var.width as u64);
+        }
+        let len = geometry.len();
+        let geometry = zlib_if_smaller(geometry)?;
+        let mut body = vec![];
+        body.extend((len as u64).to_be_bytes());
+        body.extend((self.vars.len() as u64).to_be_bytes());
+        body.extend(geometry);
+        write_block(w, BL_GEOM, &body)
+    }
+    fn write_hierarchy<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&self.hierarchy)?;
+        let mut body = vec![];
+        body.extend((self.hierarchy.len() as u64).to_be_bytes());
+        body.extend(encoder.finish()?);
+        write_block(w, BL_HIER, &body)
+    }
+}
+
+fn encode_value(data: &mut Vec<u8>, delta: u64, value: &[u8]) {
+    let binary = value.iter().all(|v| *v == b'0' || *v == b'1');
+    if value.len() == 1 {
+        if binary {
+            varint(data, (delta << 2) | (((value[0] - b'0') as u64) << 1));
+        } else {
+            let code = RCV_STR.iter().position(|c| *c == value[0]).unwrap_or(0) as u64;
+            varint(data, (delta << 4) | (code << 1) | 1);
+        }
+    } else if binary {
+        varint(data, delta << 1);
+        let mut packed = vec![0_u8; value.len().div_ceil(8)];
+        for (ndx, v) in value.iter().enumerate() {
+            if *v == b'1' {
+                packed[ndx / 8] |= 0x80 >> (ndx % 8);
+            }
+        }
+        data.extend(packed);
+    } else {
+        varint(data, (delta << 1) | 1);
+        data.extend(value);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod reader {
+    // A minimal reader for the subset of FST written above, used to
+    // check that the output can be read back.
+    use std::io::Read;
+
+    use flate2::read::{GzDecoder, ZlibDecoder};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    pub(crate) struct FstFile {
+        pub(crate) timescale: i8,
+        pub(crate) start_time: u64,
+        pub(crate) end_time: u64,
+        pub(crate) vc_sections: u64,
+        pub(crate) comments: Vec<String>,
+        // The full path and width of each signal
+        pub(crate) signals: Vec<(String, usize)>,
+        // The value changes for each signal
+        pub(crate) changes: Vec<Vec<(u64, String)>>,
+    }
+
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Bytes<'_> {
+        fn u8(&mut self) -> u8 {
+            let v = self.0[0];
+            self.0 = &self.0[1..];
+            v
+        }
+        fn u64(&mut self) -> u64 {
+            let v = u64::from_be_bytes(self.0[..8].try_into().unwrap());
+            self.0 = &self.0[8..];
+            v
+        }
+        fn take(&mut self, n: usize) -> &[u8] {
+            let (v, rest) = self.0.split_at(n);
+            self.0 = rest;
+            v
+        }
+        fn varint(&mut self) -> u64 {
+            let mut v = 0;
+            let mut shift = 0;
+            loop {
+                let byte = self.u8();
+                v |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    return v;
+                }
+            }
+        }
+        fn svarint(&mut self) -> i64 {
+            let mut v = 0_i64;
+            let mut shift = 0;
+            loop {
+                let byte = self.u8();
+                v |= ((byte & 0x7f) as i64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    if shift < 64 && byte & 0x40 != 0 {
+                        v |= -1 << shift;
+                    }
+                    return v;
+                }
+            }
+        }
+        fn cstr(&mut self) -> String {
+            let len = self.0.iter().position(|b| *b == 0).unwrap();
+            let s = String::from_utf8(self.0[..len].to_vec()).unwrap();
+            self.0 = &self.0[len + 1..];
+            s
+        }
+    }
+
+    fn unzlib(data: &[u8], len: usize) -> Vec<u8> {
+        if data.len() == len {
+            return data.to_vec();
+        }
+        let mut out = vec![];
+        ZlibDecoder::new(data).read_to_end(&mut out).unwrap();
+        assert_eq!(out.len(), len);
+        out
+    }
+
+    pub(crate) fn read_fst(bytes: &[u8]) -> FstFile {
+        let mut file = FstFile::default();
+        let mut blocks = vec![];
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let block_type = bytes[pos];
+            let len = u64::from_be_bytes(bytes[pos + 1..pos + 9].try_into().unwrap()) as usize;
+            blocks.push((block_type, &bytes[pos + 9..pos + 1 + len]));
+            pos += 1 + len;
+        }
+        assert_eq!(blocks[0].0, BL_HDR, "The header must come first");
+        for (block_type, body) in &blocks {
+            let mut b = Bytes(body);
+            match *block_type {
+                BL_HDR => {
+                    assert_eq!(body.len() as u64 + 8, HDR_SIZE);
+                    file.start_time = b.u64();
+                    file.end_time = b.u64();
+                    let endtest = f64::from_le_bytes(b.take(8).try_into().unwrap());
+                    assert_eq!(endtest, DOUBLE_ENDTEST);
+                    b.take(8 * 4);
+                    file.vc_sections = b.u64();
+                    file.timescale = b.u8() as i8;
+                }
+                BL_GEOM => {
+                    let len = b.u64() as usize;
+                    let count = b.u64() as usize;
+                    let data = unzlib(b.0, len);
+                    let mut g = Bytes(&data);
+                    let widths = (0..count).map(|_| g.varint() as usize).collect::<Vec<_>>();
+                    file.signals = widths.into_iter().map(|w| (String::new(), w)).collect();
+                }
+                _ => {}
+            }
+        }
+        // The hierarchy names the signals in handle order
+        let (_, hier) = blocks.iter().find(|(t, _)| *t == BL_HIER).unwrap();
+        let mut b = Bytes(hier);
+        let len = b.u64() as usize;
+        let mut data = vec![];
+        GzDecoder::new(b.0).read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), len);
+        let mut h = Bytes(&data);
+        let mut scope = vec![];
+        let mut handle = 0;
+        while !h.0.is_empty() {
+            match h.u8() {
+                ST_VCD_SCOPE => {
+                    assert_eq!(h.u8(), ST_VCD_MODULE);
+                    scope.push(h.cstr());
+                    h.cstr();
+                }
+                ST_VCD_UPSCOPE => {
+                    scope.pop();
+                }
+                ST_GEN_ATTRBEGIN => {
+                    assert_eq!(h.u8(), AT_MISC);
+                    assert_eq!(h.u8(), MT_COMMENT);
+                    file.comments.push(h.cstr());
+                    h.varint();
+                }
+                VT_VCD_WIRE => {
+                    assert_eq!(h.u8(), VD_IMPLICIT);
+                    let name = h.cstr();
+                    let width = h.varint() as usize;
+                    assert_eq!(h.varint(), 0);
+                    assert_eq!(file.signals[handle].1, width);
+                    file.signals[handle].0 = [&scope[..], &[name]].concat().join(".");
+                    handle += 1;
+                }
+                x => panic!("Unexpected hierarchy entry {x}"),
+            }
+        }
+        file.changes = vec![vec![]; file.signals.len()];
+        for (_, body) in blocks.iter().filter(|(t, _)| *t == BL_VCDATA_DYN_ALIAS2) {
+            read_value_changes(&mut file, body);
+        }
+        file
+    }
+
+    fn push_change(changes: &mut Vec<(u64, String)>, time: u64, value: String) {
+        if changes.last().map(|(_, v)| v) == Some(&value) {
+            return;
+        }
+        // The frame for the first block holds `x` for signals that
+        // have no value yet
+        if changes.is_empty() && value.bytes().all(|b| b == b'x') {
+            return;
+        }
+        changes.push((time, value));
+    }
+
+    fn read_value_changes(file: &mut FstFile, body: &[u8]) {
+        let count = file.signals.len();
+        // The time table is at the end of the block
+        let tail = &body[body.len() - 24..];
+        let time_len = u64::from_be_bytes(tail[0..8].try_into().unwrap()) as usize;
+        let time_clen = u64::from_be_bytes(tail[8..16].try_into().unwrap()) as usize;
+        let time_count = u64::from_be_bytes(tail[16..24].try_into().unwrap()) as usize;
+        let time_start = body.len() - 24 - time_clen;
+        let table = unzlib(&body[time_start..body.len() - 24], time_len);
+        let mut t = Bytes(&table);
+        let mut time = 0;
+        let times = (0..time_count)
+            .map(|_| {
+                time += t.varint();
+                time
+            })
+            .collect::<Vec<_>>();
+        // The chain table precedes the time table
+        let chain_len =
+            u64::from_be_bytes(body[time_start - 8..time_start].try_into().unwrap()) as usize;
+        let chain_start = time_start - 8 - chain_len;
+        let mut b = Bytes(body);
+        assert_eq!(b.u64(), times[0]);
+        assert_eq!(b.u64(), times[time_count - 1]);
+        b.u64();
+        let frame_len = b.varint() as usize;
+        let frame_clen = b.varint() as usize;
+        assert_eq!(b.varint() as usize, count);
+        let frame = unzlib(b.take(frame_clen), frame_len);
+        let mut offset = 0;
+        for (ndx, (_, width)) in file.signals.iter().enumerate() {
+            let value = String::from_utf8(frame[offset..offset + width].to_vec()).unwrap();
+            push_change(&mut file.changes[ndx], times[0], value);
+            offset += width;
+        }
+        assert_eq!(b.varint() as usize, count);
+        let vc_start = body.len() - b.0.len();
+        assert_eq!(b.u8(), b'Z');
+        let mut c = Bytes(&body[chain_start..time_start - 8]);
+        let mut offsets = vec![];
+        let mut previous = 0;
+        while !c.0.is_empty() {
+            if c.0[0] & 1 == 1 {
+                let delta = c.svarint() >> 1;
+                assert!(delta > 0, "Aliases are not written");
+                previous += delta as usize;
+                offsets.push(Some(previous));
+            } else {
+                let zeros = c.varint() >> 1;
+                offsets.extend((0..zeros).map(|_| None));
+            }
+        }
+        assert_eq!(offsets.len(), count);
+        let mut ends = offsets
+            .iter()
+            .flatten()
+            .skip(1)
+            .copied()
+            .collect::<Vec<_>>();
+        ends.push(chain_start - vc_start);
+        let mut ends = ends.into_iter();
+        for (ndx, offset) in offsets.into_iter().enumerate() {
+            let Some(offset) = offset else {
+                continue;
+            };
+            let end = ends.next().unwrap();
+            let mut chunk = Bytes(&body[vc_start + offset..vc_start + end]);
+            let len = chunk.varint() as usize;
+            let data = if len == 0 {
+                chunk.0.to_vec()
+            } else {
+                unzlib(chunk.0, len)
+            };
+            let width = file.signals[ndx].1;
+            let mut d = Bytes(&data);
+            let mut index = 0;
+            while !d.0.is_empty() {
+                let v = d.varint();
+                let value = if width == 1 {
+                    if v & 1 == 0 {
+                        index += (v >> 2) as usize;
+                        if v & 2 == 0 { "0" } else { "1" }.to_string()
+                    } else {
+                        index += (v >> 4) as usize;
+                        (RCV_STR[((v >> 1) & 7) as usize] as char).to_string()
+                    }
+                } else {
+                    index += (v >> 1) as usize;
+                    if v & 1 == 0 {
+                        let packed = d.take(width.div_ceil(8));
+                        (0..width)
+                            .map(|n| {
+                                if packed[n / 8] & (0x80 >> (n % 8)) != 0 {
+                                    '1'
+                                } else {
+                                    '0'
+                                }
+                            })
+                            .collect()
+                    } else {
+                        String::from_utf8(d.take(width).to_vec()).unwrap()
+                    }
+                };
+                push_change(&mut file.changes[ndx], times[index], value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reader::read_fst, *};
+
+    #[test]
+    fn test_varint_encoding() {
+        let mut buf = vec![];
+        varint(&mut buf, 300);
+        assert_eq!(buf, [0xac, 0x02]);
+        buf.clear();
+        svarint(&mut buf, 63);
+        assert_eq!(buf, [0x3f]);
+        buf.clear();
+        svarint(&mut buf, 64);
+        assert_eq!(buf, [0xc0, 0x00]);
+        buf.clear();
+        svarint(&mut buf, -1);
+        assert_eq!(buf, [0x7f]);
+    }
+
+    #[test]
+    fn test_round_trip_across_blocks() {
+        let mut writer = FstWriter::new(-9).with_block_times(7);
+        writer.comment("hello");
+        writer.add_module("top");
+        let clk = writer.add_wire(1, "clk");
+        writer.add_module("inner");
+        let bus = writer.add_wire(12, "bus");
+        let flag = writer.add_wire(1, "flag");
+        writer.upscope();
+        writer.upscope();
+        let mut expected = vec![vec![]; 3];
+        for t in 0..50_u64 {
+            let time = t * 10;
+            let value = if t % 2 == 0 { "0" } else { "1" };
+            writer.change(clk, time, value.as_bytes());
+            expected[clk].push((time, value.to_string()));
+            if t % 3 == 1 {
+                let value = if t == 13 {
+                    "0101xxzz0101".to_string()
+                } else {
+                    format!("{:012b}", t * 77)
+                };
+                writer.change(bus, time, value.as_bytes());
+                expected[bus].push((time, value));
+            }
+            if t == 20 || t == 35 {
+                let value = if t == 20 { "z" } else { "1" };
+                writer.change(flag, time, value.as_bytes());
+                expected[flag].push((time, value.to_string()));
+            }
+        }
+        let mut buf = vec![];
+        writer.finish(&mut buf).unwrap();
+        let file = read_fst(&buf);
+        assert_eq!(file.timescale, -9);
+        assert_eq!(file.start_time, 0);
+        assert_eq!(file.end_time, 490);
+        assert_eq!(file.vc_sections, 8);
+        assert_eq!(file.comments, ["hello"]);
+        assert_eq!(
+            file.signals,
+            [
+                ("top.clk".to_string(), 1),
+                ("top.inner.bus".to_string(), 12),
+                ("top.inner.flag".to_string(), 1)
+            ]
+        );
+        assert_eq!(file.changes, expected);
+    }
+}
@@ -1,6 +1,9 @@
+pub mod analyze;
 pub mod bit;
 pub mod db;
+pub mod fst;
 pub mod key;
+pub mod report;
 pub mod rtt;
 pub mod svg;
 
@@ -0,0 +1,404 @@
+//! Activity based energy estimates
+//!
+//! Dynamic power is dominated by switching, so the toggle activity of
+//! a simulation, weighted by a rough cost for each kind of node, gives
+//! a useful proxy for which blocks of a design burn the most power for
+//! a given workload.  The numbers are in arbitrary units, and are only
+//! meaningful relative to each other.
+//!
+//! [activity_energy] walks the [TraceDB] of a simulation, and charges
+//! each instance (named by its hierarchical path, like `top.fifo.ram`)
+//! for:
+//!
+//! - every bit toggle of a register it holds (the `dff.output` nets),
+//!   at the `flop` weight,
+//! - every bit toggle of the logic it drives (its `outputs`, and the
+//!   `dff.input` nets of its registers), at the `logic` weight,
+//! - every access to a RAM it holds (a change on the RAM inputs), at
+//!   the `ram_access` weight,
+//! - for each bit of adder (or subtractor) in its kernel, every clock in
+//!   which its logic changed, at the `adder` weight.  The adder widths
+//!   come from the compiled kernels, and are filled in by
+//!   [EnergyWeights::with_descriptor].
+//!
+//! The weights are public, and can be changed to suit the target.
+//! The energy of particular instances can also be scaled (for example,
+//! to account for a hard IP block) with [EnergyWeights::with_scale].
+//!
+//!# Example
+//!
+//!```
+//! use rhdl::prelude::*;
+//! use rhdl::core::trace::{db::trace, report::{activity_energy, EnergyWeights}};
+//!
+//! let guard = trace_init_db();
+//! for i in 0..16 {
+//!     trace_time(i * 100);
+//!     trace_push_path("busy");
+//!     trace_push_path("dff");
+//!     trace("output", &b4(i as u128));
+//!     trace_pop_path();
+//!     trace_pop_path();
+//!     trace_push_path("idle");
+//!     trace_push_path("dff");
+//!     trace("output", &b4(i as u128 / 8));
+//!     trace_pop_path();
+//!     trace_pop_path();
+//! }
+//! let report = activity_energy(&guard.take(), &EnergyWeights::default());
+//! assert_eq!(report.ranked()[0].0, "top.busy");
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::db::TraceDB;
+use crate::rhdl_core::{
+    CircuitDescriptor, TypedBits,
+    rtl::spec::{AluBinary, OpCode},
+};
+
+/// The costs used to turn activity into energy
+#[derive(Clone, Debug)]
+pub struct EnergyWeights {
+    /// The cost of a register bit toggling
+    pub flop: f64,
+    /// The cost of a logic output bit toggling
+    pub logic: f64,
+    /// The cost of a bit of adder, in a clock in which its logic changed
+    pub adder: f64,
+    /// The cost of a RAM access
+    pub ram_access: f64,
+    /// The number of adder bits in each instance
+    pub adder_bits: BTreeMap<String, usize>,
+    /// Factors applied to the energy of instances (and their children)
+    pub scale: Vec<(String, f64)>,
+}
+
+impl Default for EnergyWeights {
+    fn default() -> Self {
+        Self {
+            flop: 1.0,
+            logic: 0.25,
+            adder: 0.5,
+            ram_access: 8.0,
+            adder_bits: BTreeMap::new(),
+            scale: vec![],
+        }
+    }
+}
+
+impl EnergyWeights {
+    /// Estimate the adder widths of each instance in a circuit from
+    /// its descriptor (as built by `descriptor("top")`)
+    pub fn with_descriptor(self, descriptor: &CircuitDescriptor) -> Self {
+        let mut adder_bits = self.adder_bits;
+        collect_adder_bits("top", descriptor, &mut adder_bits);
+        Self { adder_bits, ..self }
+    }
+    /// Multiply the energy of the instance at `path` (and of the
+    /// instances inside it) by `factor`.  If several entries match,
+    /// the longest one wins.
+    pub fn with_scale(mut self, path: &str, factor: f64) -> Self {
+        self.scale.push((path.into(), factor));
+        self
+    }
+    fn factor(&self, instance: &str) -> f64 {
+        self.scale
+            .iter()
+            .filter(|(path, _)| within(instance, path))
+            .max_by_key(|(path, _)| path.len())
+            .map(|(_, factor)| *factor)
+            .unwrap_or(1.0)
+    }
+}
+
+fn collect_adder_bits(
+    path: &str,
+    descriptor: &CircuitDescriptor,
+    adder_bits: &mut BTreeMap<String, usize>,
+) {
+    if let Some(rtl) = &descriptor.rtl {
+        let bits = rtl
+            .ops
+            .iter()
+            .filter_map(|lop| match &lop.op {
+                OpCode::Binary(binary) if matches!(binary.op, AluBinary::Add | AluBinary::Sub) => {
+                    Some(rtl.kind(binary.lhs).bits())
+                }
+                _ => None,
+            })
+            .sum::<usize>();
+        if bits > 0 {
+            adder_bits.insert(path.into(), bits);
+        }
+    }
+    for (name, child) in &descriptor.children {
+        collect_adder_bits(&format!("{path}.{name}"), child, adder_bits);
+    }
+}
+
+// The instance is `path`, or is inside it
+fn within(instance: &str, path: &str) -> bool {
+    instance
+        .strip_prefix(path)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+}
+
+// The instance that contains `instance`
+fn parent(instance: &str) -> &str {
+    instance
+        .rsplit_once('.')
+        .map(|(parent, _)| parent)
+        .unwrap_or(instance)
+}
+
+/// The estimated energy of a single instance, by kind of node
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InstanceEnergy {
+    /// Register toggles
+    pub flops: f64,
+    /// Logic output toggles
+    pub logic: f64,
+    /// Adder activity
+    pub adders: f64,
+    /// RAM accesses
+    pub ram: f64,
+}
+
+impl InstanceEnergy {
+    pub fn total(&self) -> f64 {
+        self.flops + self.logic + self.adders + self.ram
+    }
+    fn scaled(self, factor: f64) -> Self {
+        Self {
+            flops: self.flops * factor,
+            logic: self.logic * factor,
+            adders: self.adders * factor,
+            ram: self.ram * factor,
+        }
+    }
+}
+
+/// The estimated energy of each instance in a trace
+#[derive(Clone, Debug, Default)]
+pub struct EnergyReport {
+    /// The energy of each instance (not including the
+    /// instances inside it), by hierarchical path
+    pub instances: BTreeMap<String, InstanceEnergy>,
+}
+
+impl EnergyReport {
+    /// The energy of the whole design
+    pub fn total(&self) -> f64 {
+        self.instances.values().map(InstanceEnergy::total).sum()
+    }
+    /// The energy of the instance at `path`, including the
+    /// instances inside it
+    pub fn subtree(&self, path: &str) -> f64 {
+        self.instances
+            .iter()
+            .filter(|(instance, _)| within(instance, path))
+            .map(|(_, energy)| energy.total())
+            .sum()
+    }
+    /// The instances, from the most to the least energy
+    pub fn ranked(&self) -> Vec<(&str, f64)> {
+        let mut ranked = self
+            .instances
+            .iter()
+            .map(|(instance, energy)| (instance.as_str(), energy.total()))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+impl std::fmt::Display for EnergyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.total();
+        for (instance, energy) in self.ranked() {
+            let share = if total > 0.0 {
+                100.0 * energy / total
+            } else {
+                0.0
+            };
+            writeln!(f, "{energy:>14.1} {share:>5.1}%  {instance}")?;
+        }
+        writeln!(f, "{total:>14.1} total")
+    }
+}
+
+// The number of bits that change between successive values
+fn bit_toggles(history: &[(u64, TypedBits)]) -> u64 {
+    history
+        .windows(2)
+        .map(|pair| {
+            pair[0]
+                .1
+                .bits
+                .iter()
+                .zip(&pair[1].1.bits)
+                .filter(|(a, b)| a != b)
+                .count() as u64
+        })
+        .sum()
+}
+
+/// Estimate the energy of each instance from the activity in a trace
+pub fn activity_energy(trace: &TraceDB, weights: &EnergyWeights) -> EnergyReport {
+    let mut instances: BTreeMap<String, InstanceEnergy> = BTreeMap::new();
+    // The times at which the logic of each instance changed
+    let mut changes: BTreeMap<String, BTreeSet<u64>> = BTreeMap::new();
+    let mut logic = |instance: &str, history: &[(u64, TypedBits)]| {
+        instances.entry(instance.into()).or_default().logic +=
+            weights.logic * bit_toggles(history) as f64;
+        changes
+            .entry(instance.into())
+            .or_default()
+            .extend(history.iter().skip(1).map(|(time, _)| *time));
+    };
+    let mut flops = vec![];
+    let mut rams = vec![];
+    for (name, history) in trace.histories() {
+        let Some((scope, key)) = name.rsplit_once('.') else {
+            continue;
+        };
+        if let Some(register) = scope.strip_suffix(".dff") {
+            match key {
+                "output" => flops.push((register.to_string(), bit_toggles(&history))),
+                // The next state is driven by the logic that
+                // holds the register
+                "input" => logic(parent(register), &history),
+                _ => {}
+            }
+        } else if key == "outputs" {
+            logic(scope, &history);
+        } else if key == "input" && scope.ends_with("_ram") {
+            rams.push((parent(scope).to_string(), history.len() as u64 - 1));
+        }
+    }
+    for (instance, toggles) in flops {
+        instances.entry(instance).or_default().flops += weights.flop * toggles as f64;
+    }
+    for (instance, accesses) in rams {
+        instances.entry(instance).or_default().ram += weights.ram_access * accesses as f64;
+    }
+    for (instance, bits) in &weights.adder_bits {
+        let active = changes.get(instance).map_or(0, BTreeSet::len);
+        instances.entry(instance.clone()).or_default().adders +=
+            weights.adder * (*bits * active) as f64;
+    }
+    for (instance, energy) in instances.iter_mut() {
+        *energy = energy.scaled(weights.factor(instance));
+    }
+    EnergyReport { instances }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rhdl_bits::alias::*,
+        rhdl_core::{
+            trace::db::{trace, trace_pop_path, trace_push_path},
+            trace_init_db, trace_time,
+        },
+    };
+
+    fn register(name: &'static str, next: &b4, value: &b4) {
+        trace_push_path(name);
+        trace_push_path("dff");
+        trace("input", next);
+        trace("output", value);
+        trace_pop_path();
+        trace_pop_path();
+    }
+
+    // A block holding a counter, and a block holding a RAM that is
+    // accessed every `period` clocks, for `cycles` clocks
+    fn design(cycles: u64, period: u64) -> TraceDB {
+        let guard = trace_init_db();
+        for i in 0..cycles {
+            trace_time(i * 100);
+            trace_push_path("counter");
+            register("count", &b4((i as u128 + 1) % 16), &b4(i as u128 % 16));
+            trace("outputs", &b4(i as u128 % 16));
+            trace_pop_path();
+            trace_push_path("store");
+            trace_push_path("ram");
+            trace_push_path("synchronous_ram");
+            trace("input", &b4((i / period) as u128 % 16));
+            trace_pop_path();
+            trace_pop_path();
+            trace_pop_path();
+        }
+        guard.take()
+    }
+
+    #[test]
+    fn test_charges_by_node_kind() {
+        let weights = EnergyWeights {
+            flop: 1.0,
+            logic: 0.5,
+            adder: 0.0,
+            ram_access: 10.0,
+            ..Default::default()
+        };
+        let report = activity_energy(&design(4, 2), &weights);
+        // The count goes 0, 1, 2, 3 (4 bit toggles), and its next
+        // state 1, 2, 3, 4 (6 bit toggles)
+        let count = report.instances["top.counter.count"];
+        assert_eq!(count.flops, 4.0);
+        let counter = report.instances["top.counter"];
+        assert_eq!(counter.logic, 0.5 * (4.0 + 6.0));
+        // One access after the first
+        assert_eq!(report.instances["top.store.ram"].ram, 10.0);
+        assert_eq!(report.subtree("top.counter"), 4.0 + 5.0);
+        assert_eq!(report.total(), 4.0 + 5.0 + 10.0);
+    }
+
+    #[test]
+    fn test_adders_and_scale() {
+        let weights = EnergyWeights {
+            adder: 1.0,
+            ..Default::default()
+        };
+        let mut weights = weights.with_scale("top.store", 0.5);
+        weights.adder_bits.insert("top.counter".into(), 4);
+        let plain = activity_energy(&design(8, 2), &EnergyWeights::default());
+        let report = activity_energy(&design(8, 2), &weights);
+        // The counter logic changes in 7 clocks
+        let counter = report.instances["top.counter"];
+        assert_eq!(counter.adders, 4.0 * 7.0);
+        assert_eq!(
+            report.instances["top.store.ram"].ram,
+            plain.instances["top.store.ram"].ram * 0.5
+        );
+        // The scale applies to whole path components only
+        let weights = EnergyWeights::default().with_scale("top.count", 0.0);
+        let report = activity_energy(&design(8, 2), &weights);
+        assert!(report.instances["top.counter.count"].flops > 0.0);
+    }
+
+    #[test]
+    fn test_ranking() {
+        // The RAM is only accessed twice
+        let report = activity_energy(&design(64, 32), &EnergyWeights::default());
+        let ranked = report.ranked();
+        assert_eq!(ranked.last().unwrap().0, "top.store.ram");
+        assert!(report.subtree("top.counter") > report.subtree("top.store"));
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(report.to_string().contains("top.store.ram"));
+    }
+
+    #[test]
+    fn test_scales_with_length() {
+        // The workload repeats every 16 clocks, so the energy
+        // per period is constant
+        let energy =
+            |periods: u64| activity_energy(&design(16 * periods + 1, 4), &Default::default());
+        let one = energy(1).total();
+        for periods in [2, 5, 10] {
+            assert!((energy(periods).total() - one * periods as f64).abs() < 1e-9);
+        }
+    }
+}
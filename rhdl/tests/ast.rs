@@ -148,6 +148,25 @@ fn test_empty_return_rejected() -> miette::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_compile_error_has_kernel_span() -> miette::Result<()> {
+    #[kernel]
+    fn foo(a1: Signal<b128, Red>, a2: Signal<b128, Red>) -> Signal<b128, Red> {
+        let a1 = a1.val().dyn_bits();
+        let a2 = a2.val().dyn_bits();
+        let c = a1.xadd(a2);
+        let c: b128 = c.as_bits();
+        signal(c)
+    }
+    let err = compile_design::<foo>(CompilationMode::Asynchronous).unwrap_err();
+    assert!(matches!(err, RHDLError::RHDLTypeError(_)));
+    // The span points at the operation that overflows
+    let (src, span) = err.kernel_span().expect("Expected a kernel compile error");
+    let text = miette::SourceCode::read_span(src, &span, 0, 0).unwrap();
+    assert_eq!(std::str::from_utf8(text.data()).unwrap(), "a1.xadd(a2)");
+    Ok(())
+}
+
 #[test]
 fn test_empty_kernel_args_accepted() -> miette::Result<()> {
     #[kernel]
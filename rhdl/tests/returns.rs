@@ -11,7 +11,9 @@ use rhdl::prelude::*;
 mod common;
 #[cfg(test)]
 use common::*;
-use rhdl::core::sim::testbench::kernel::test_kernel_vm_and_verilog;
+use rhdl::core::sim::testbench::kernel::{
+    test_kernel_vm_and_verilog, test_kernel_vm_and_verilog_synchronous,
+};
 
 #[test]
 fn test_early_return() {
@@ -70,3 +72,17 @@ fn test_empty_return_not_allowed() -> miette::Result<()> {
     assert!(compile_design::<foo>(CompilationMode::Asynchronous).is_err());
     Ok(())
 }
+
+#[test]
+fn test_return_literal() -> miette::Result<()> {
+    #[kernel]
+    fn foo(_a: b8) -> b8 {
+        bits(42)
+    }
+
+    test_kernel_vm_and_verilog_synchronous::<foo, _, _, _>(
+        foo,
+        exhaustive().iter().map(|x| (*x,)),
+    )?;
+    Ok(())
+}